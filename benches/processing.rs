@@ -0,0 +1,145 @@
+//! Throughput benchmarks for the processing paths.
+//!
+//! Run with `cargo bench`. Each group reports throughput in input samples so the
+//! frame sizes and hop ratios can be compared directly.
+
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use synthphone_e_vocal_dsp::{
+    Engine, Formant, MusicalSettings, PitchDetector, ProcessingMode, VocalEffectsConfig,
+    dsp::{
+        DynFft, Fft512, Fft1024, Fft2048, Fft4096, FftOps, detect_fundamental_bin,
+        extract_cepstral_envelope,
+    },
+    ring_buffer::RingBuffer,
+    testsig::{TestSignal, Vowel},
+};
+
+const SAMPLE_RATE: f32 = 48000.0;
+
+/// Fills `output` with a sung "ah" at 220 Hz
+fn voice(output: &mut [f32]) {
+    Vowel::new(220.0, 0.5, SAMPLE_RATE).fill(output);
+}
+
+fn bench_engine<const N: usize, const HALF_N: usize, F: DynFft<N, HALF_N> + Default>(
+    c: &mut Criterion,
+    group_name: &str,
+    hop_ratio: f32,
+) {
+    let mut group = c.benchmark_group(group_name);
+    for mode in [
+        ProcessingMode::Autotune,
+        ProcessingMode::Dry,
+        ProcessingMode::Formant,
+        ProcessingMode::Vocode,
+        ProcessingMode::TalkBox,
+    ] {
+        let config = VocalEffectsConfig::builder().hop_ratio(hop_ratio).build().unwrap();
+        let settings = MusicalSettings { mode, formant: Formant::Lower, ..Default::default() };
+        let mut engine = Engine::<N, HALF_N, F>::new(config, settings);
+        let hop = engine.hop_size();
+        let mut input = vec![0.0f32; hop];
+        voice(&mut input);
+        let carrier: Vec<f32> = (0..hop).map(|n| ((n % 110) as f32 / 55.0) - 1.0).collect();
+        let mut output = vec![0.0f32; hop];
+
+        group.throughput(Throughput::Elements(hop as u64));
+        group.bench_function(BenchmarkId::new(format!("{mode:?}"), N), |b| {
+            b.iter(|| {
+                engine
+                    .process_hop(black_box(&input), Some(black_box(&carrier)), &mut output)
+                    .unwrap();
+                black_box(&output);
+            })
+        });
+    }
+    group.finish();
+}
+
+fn frame_sizes(c: &mut Criterion) {
+    bench_engine::<512, 256, Fft512>(c, "engine_512", 0.25);
+    bench_engine::<1024, 512, Fft1024>(c, "engine_1024", 0.25);
+    bench_engine::<2048, 1024, Fft2048>(c, "engine_2048", 0.25);
+    bench_engine::<4096, 2048, Fft4096>(c, "engine_4096", 0.25);
+}
+
+fn hop_ratios(c: &mut Criterion) {
+    bench_engine::<1024, 512, Fft1024>(c, "engine_1024_hop_1_2", 0.5);
+    bench_engine::<1024, 512, Fft1024>(c, "engine_1024_hop_1_8", 0.125);
+}
+
+fn cepstral_envelope(c: &mut Criterion) {
+    fn bench<const N: usize, const HALF_N: usize, F: DynFft<N, HALF_N> + Default>(
+        group: &mut criterion::BenchmarkGroup<'_, criterion::measurement::WallTime>,
+    ) {
+        let magnitudes: [f32; HALF_N] = core::array::from_fn(|i| 1.0 / (1.0 + i as f32));
+        let mut envelope = [0.0f32; HALF_N];
+        let mut fft = F::default();
+        group.bench_function(BenchmarkId::from_parameter(N), |b| {
+            b.iter(|| {
+                extract_cepstral_envelope(&mut fft, black_box(&magnitudes), &mut envelope, 64);
+                black_box(&envelope);
+            })
+        });
+    }
+
+    let mut group = c.benchmark_group("cepstral_envelope");
+    bench::<512, 256, Fft512>(&mut group);
+    bench::<1024, 512, Fft1024>(&mut group);
+    bench::<2048, 1024, Fft2048>(&mut group);
+    bench::<4096, 2048, Fft4096>(&mut group);
+    group.finish();
+}
+
+fn pitch_detector(c: &mut Criterion) {
+    let mut frame = [0.0f32; 2048];
+    voice(&mut frame);
+    let spectrum = Fft2048::forward_fft(&mut frame);
+    let magnitudes: Vec<f32> =
+        spectrum.iter().map(|c| libm::sqrtf(c.re * c.re + c.im * c.im)).collect();
+
+    let mut group = c.benchmark_group("pitch_detector");
+    for detector in [
+        PitchDetector::PeakBin,
+        PitchDetector::HarmonicProduct,
+        PitchDetector::HarmonicSum,
+    ] {
+        group.bench_function(format!("{detector:?}"), |b| {
+            b.iter(|| detect_fundamental_bin(black_box(&magnitudes), detector))
+        });
+    }
+    group.finish();
+}
+
+fn ring_buffer(c: &mut Criterion) {
+    let buffer: RingBuffer<4096> = RingBuffer::new();
+    let block = [0.25f32; 256];
+    let mut out = [0.0f32; 256];
+
+    let mut group = c.benchmark_group("ring_buffer");
+    group.throughput(Throughput::Elements(block.len() as u64));
+    group.bench_function("push_pop_samples", |b| {
+        b.iter(|| {
+            for &sample in block.iter() {
+                buffer.push(black_box(sample));
+            }
+            for sample in out.iter_mut() {
+                *sample = buffer.pop();
+            }
+            black_box(&out);
+        })
+    });
+    group.bench_function("push_pop_slices", |b| {
+        b.iter(|| {
+            buffer.push_slice(black_box(&block));
+            buffer.pop_slice(&mut out);
+            black_box(&out);
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, frame_sizes, hop_ratios, cepstral_envelope, pitch_detector, ring_buffer);
+criterion_main!(benches);
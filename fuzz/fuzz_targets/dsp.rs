@@ -0,0 +1,58 @@
+//! Arbitrary inputs to the standalone analysis helpers.
+//!
+//! ```text
+//! cargo +nightly fuzz run dsp
+//! ```
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use synthphone_e_vocal_dsp::{
+    PitchDetector,
+    audio::keys,
+    dsp::frequency_analysis::{
+        collect_harmonics, detect_fundamental_bin, parabolic_peak_offset, sample_rate_reduce,
+    },
+};
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    magnitudes: Vec<f32>,
+    peak_index: usize,
+    sample: f32,
+    factor: i32,
+    hold_counter: i32,
+    key: i32,
+    note: i32,
+    octave: i32,
+    vocoder: bool,
+    cents: f32,
+}
+
+fuzz_target!(|input: Input| {
+    for detector in [
+        PitchDetector::PeakBin,
+        PitchDetector::HarmonicProduct,
+        PitchDetector::HarmonicSum,
+    ] {
+        let bin = detect_fundamental_bin(&input.magnitudes, detector);
+        assert!(input.magnitudes.is_empty() || bin < input.magnitudes.len());
+    }
+
+    let offset = parabolic_peak_offset(&input.magnitudes, input.peak_index);
+    assert!(offset.is_nan() || (-0.5..=0.5).contains(&offset));
+
+    collect_harmonics(input.peak_index);
+
+    let mut hold_counter = input.hold_counter;
+    let mut held = 0.0;
+    sample_rate_reduce(input.sample, input.factor, &mut hold_counter, &mut held);
+
+    if let Ok(frequency) =
+        keys::get_frequency(input.key, input.note, input.octave, input.vocoder, input.cents)
+    {
+        assert!(frequency > 0.0 && frequency.is_finite());
+    }
+    keys::get_note_name(input.note, keys::get_key(input.key));
+});
@@ -0,0 +1,72 @@
+//! Drives an [`Engine`] with arbitrary configurations, settings and audio.
+//!
+//! ```text
+//! cargo +nightly fuzz run engine
+//! ```
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use synthphone_e_vocal_dsp::{
+    Engine,
+    dsp::{DynFft, Fft128, Fft256, Fft512, Fft1024, Fft2048, Fft4096},
+};
+use synthphone_e_vocal_dsp_fuzz::{FuzzConfig, FuzzSettings, fill, mode};
+
+#[derive(Arbitrary, Debug)]
+enum Step {
+    /// Process one hop; the buffers are stretched to the hop size unless `exact`
+    Process {
+        input: Vec<f32>,
+        carrier: Option<Vec<f32>>,
+        exact: bool,
+    },
+    SetSettings(FuzzSettings),
+    SetMode(u8),
+    Reset,
+    SoftReset,
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    size: u8,
+    config: FuzzConfig,
+    settings: FuzzSettings,
+    steps: Vec<Step>,
+}
+
+fn run<const N: usize, const HALF_N: usize, F: DynFft<N, HALF_N> + Default>(input: &Input) {
+    let mut engine = Engine::<N, HALF_N, F>::new(input.config.config(N), input.settings.into());
+    // Hop ratios far above 1 are rejected without reading the buffers
+    let hop = engine.hop_size().min(2 * N);
+
+    for step in input.steps.iter().take(64) {
+        match step {
+            Step::Process { input, carrier, exact } => {
+                let (input, carrier) = if *exact {
+                    (input.clone(), carrier.clone())
+                } else {
+                    (fill(input, hop), carrier.as_deref().map(|c| fill(c, hop)))
+                };
+                let mut output = vec![0.0f32; input.len()];
+                let _ = engine.process_hop(&input, carrier.as_deref(), &mut output);
+            }
+            Step::SetSettings(settings) => engine.set_settings((*settings).into()),
+            Step::SetMode(value) => engine.set_mode(mode(*value)),
+            Step::Reset => engine.reset(),
+            Step::SoftReset => engine.soft_reset(),
+        }
+    }
+}
+
+fuzz_target!(|input: Input| {
+    match input.size % 6 {
+        0 => run::<128, 64, Fft128>(&input),
+        1 => run::<256, 128, Fft256>(&input),
+        2 => run::<512, 256, Fft512>(&input),
+        3 => run::<1024, 512, Fft1024>(&input),
+        4 => run::<2048, 1024, Fft2048>(&input),
+        _ => run::<4096, 2048, Fft4096>(&input),
+    }
+});
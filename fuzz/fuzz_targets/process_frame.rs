@@ -0,0 +1,67 @@
+//! Feeds arbitrary frames and phase state into the stateless per-size entry
+//! points and checks that the sanitized output is always finite.
+//!
+//! ```text
+//! cargo +nightly fuzz run process_frame
+//! ```
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use synthphone_e_vocal_dsp::{
+    MusicalSettings, VocalEffectsConfig, process_vocal_effects_512, process_vocal_effects_1024,
+};
+use synthphone_e_vocal_dsp_fuzz::{FuzzConfig, FuzzSettings, fill};
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    large: bool,
+    config: FuzzConfig,
+    settings: FuzzSettings,
+    frame: Vec<f32>,
+    carrier: Option<Vec<f32>>,
+    input_phases: Vec<f32>,
+    output_phases: Vec<f32>,
+    previous_pitch_shift_ratio: f32,
+}
+
+type ProcessFn<const N: usize> = fn(
+    &mut [f32; N],
+    Option<&mut [f32; N]>,
+    &mut [f32; N],
+    &mut [f32; N],
+    f32,
+    &VocalEffectsConfig,
+    &MusicalSettings,
+) -> [f32; N];
+
+fn run<const N: usize>(input: &Input, process: ProcessFn<N>) {
+    let array = |samples: &[f32]| -> [f32; N] { fill(samples, N).try_into().unwrap() };
+    let mut frame = array(&input.frame);
+    // Vocode mode requires a carrier
+    let mut carrier = array(input.carrier.as_deref().unwrap_or_default());
+    let mut input_phases = array(&input.input_phases);
+    let mut output_phases = array(&input.output_phases);
+
+    let output = process(
+        &mut frame,
+        Some(&mut carrier),
+        &mut input_phases,
+        &mut output_phases,
+        input.previous_pitch_shift_ratio,
+        &input.config.config(N),
+        &input.settings.into(),
+    );
+
+    assert!(output.iter().all(|s| s.is_finite()), "non-finite output");
+    assert!(input_phases.iter().chain(output_phases.iter()).all(|p| p.is_finite()));
+}
+
+fuzz_target!(|input: Input| {
+    if input.large {
+        run::<1024>(&input, process_vocal_effects_1024);
+    } else {
+        run::<512>(&input, process_vocal_effects_512);
+    }
+});
@@ -0,0 +1,78 @@
+//! Random push/pop sequences, checked against a reference queue.
+//!
+//! The single-sample API does not guard against overruns and underruns (the
+//! read pointer is left behind or runs ahead), so the comparison stops at the
+//! first one and the rest of the sequence only has to run without panicking.
+//!
+//! ```text
+//! cargo +nightly fuzz run ring_buffer
+//! ```
+
+#![no_main]
+
+use std::collections::VecDeque;
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use synthphone_e_vocal_dsp::ring_buffer::RingBuffer;
+
+const CAPACITY: usize = 64;
+
+#[derive(Arbitrary, Debug)]
+enum Op {
+    Push(f32),
+    Pop,
+    PushSlice(Vec<f32>),
+    PopSlice(u8),
+}
+
+fuzz_target!(|ops: Vec<Op>| {
+    let buffer: RingBuffer<CAPACITY> = RingBuffer::new();
+    let mut reference = Some(VecDeque::new());
+
+    for op in ops {
+        match op {
+            Op::Push(sample) => {
+                buffer.push(sample);
+                if let Some(queue) = &mut reference {
+                    queue.push_back(sample);
+                }
+            }
+            Op::PushSlice(mut samples) => {
+                // Longer slices are documented to panic
+                samples.truncate(CAPACITY);
+                buffer.push_slice(&samples);
+                if let Some(queue) = &mut reference {
+                    queue.extend(samples);
+                }
+            }
+            Op::Pop => {
+                let sample = buffer.pop();
+                match reference.as_mut().map(|queue| queue.pop_front()) {
+                    Some(Some(expected)) => assert_eq!(sample.to_bits(), expected.to_bits()),
+                    Some(None) => reference = None,
+                    None => {}
+                }
+            }
+            Op::PopSlice(len) => {
+                let mut block = vec![f32::NAN; len as usize];
+                let count = buffer.pop_slice(&mut block);
+                assert!(count <= block.len());
+                assert!(block[count..].iter().all(|s| *s == 0.0));
+                if let Some(queue) = &mut reference {
+                    assert_eq!(count, block.len().min(queue.len()));
+                    for (sample, expected) in block.iter().zip(queue.drain(..count)) {
+                        assert_eq!(sample.to_bits(), expected.to_bits());
+                    }
+                }
+            }
+        }
+
+        if reference.as_ref().is_some_and(|queue| queue.len() > CAPACITY) {
+            reference = None;
+        }
+        if let Some(queue) = &reference {
+            assert_eq!(buffer.available_samples() as usize, queue.len());
+        }
+    }
+});
@@ -0,0 +1,285 @@
+//! Arbitrary inputs shared by the fuzz targets.
+//!
+//! Every field is taken as-is from the fuzzer, so configurations and settings
+//! outside their documented ranges (zero or NaN hop ratios, negative keys,
+//! huge crossfade lengths) are exercised as well.
+
+use arbitrary::Arbitrary;
+use synthphone_e_vocal_dsp::{
+    BandLimit, BreathRetention, ChordSpec, ChorusSettings, CorrectionStrength, DelaySettings,
+    DelayTime, ExciterSettings, Glide, IdleOutput, LowConfidence, MainsFrequency, ModulationRate,
+    MusicalSettings, NoiseFill, Ornaments, PhaseReanchor, PitchDecimation, PitchDetector,
+    PitchShiftAlgorithm, ProcessingMode, ReverbSettings, SaturationCurve, SaturationSettings,
+    SibilanceBypass, SidechainDuck, SoftClip, SpectralGate, TargetSource, TransientHandling,
+    VocalEffectsConfig, VocoderEnvelope, VocoderEq, VoiceActivityGate, audio::Tuning,
+};
+
+/// Arbitrary [`VocalEffectsConfig`]
+#[derive(Arbitrary, Debug, Clone, Copy)]
+pub struct FuzzConfig {
+    pub sample_rate: f32,
+    pub hop_ratio: f32,
+    pub frame_size: Option<usize>,
+    pub synthesis_hop_ratio: Option<f32>,
+    pub pitch_shift_algorithm: bool,
+    pub transition_speed: f32,
+    pub pitch_correction_strength: f32,
+    pub min_frequency: f32,
+    pub max_frequency: f32,
+    pub pitch_detector: u8,
+    pub pitch_decimation: u8,
+    pub mode_crossfade_hops: usize,
+    pub lifter_cutoff_override: Option<usize>,
+    pub envelope_update_interval: u8,
+    pub formant_morph: Option<f32>,
+    pub wet_dry: f32,
+    pub glide: u8,
+    pub glide_amount: f32,
+    pub scoop: (f32, f32),
+    pub fall: (f32, f32),
+    pub transients: u8,
+    pub transient_threshold_db: f32,
+    pub harmonic_percussive_separation: bool,
+    pub spectral_gate: Option<(f32, f32, f32)>,
+    pub exciter: Option<(f32, f32, f32)>,
+    /// Curve, drive, mix and oversampling
+    pub saturation: Option<(u8, f32, f32, bool)>,
+    pub sample_rate_reduction: Option<u32>,
+    pub bitcrush: Option<u8>,
+    /// Rate in Hz, delay, depth, feedback and mix
+    pub chorus: Option<(f32, f32, f32, f32, f32)>,
+    /// Time in seconds, feedback, damping and mix
+    pub delay: Option<(f32, f32, f32, f32)>,
+    pub reverb: Option<(f32, f32, f32)>,
+    pub hum_filter: Option<bool>,
+    pub pre_emphasis: Option<f32>,
+    pub soft_clip: Option<(f32, f32)>,
+    pub phase_reanchor: Option<(f32, f32)>,
+    /// Threshold, hold time and whether to output dry while idle
+    pub voice_activity_gate: Option<(f32, f32, bool)>,
+    pub band_limit: Option<(f32, f32)>,
+    pub low_confidence: u8,
+    pub confidence_threshold: f32,
+    /// Base frequency and cents of a custom tuning
+    pub tuning: Option<(f32, [f32; 4])>,
+    pub noise_fill: Option<(f32, f32)>,
+    pub vocoder_envelope: Option<(f32, f32)>,
+    pub sibilance_bypass: Option<(f32, f32)>,
+    /// Threshold, ratio, attack and release
+    pub sidechain_duck: Option<(f32, f32, f32, f32)>,
+    pub breath_retention: Option<(f32, f32)>,
+    pub unwindowed_synth: bool,
+}
+
+impl FuzzConfig {
+    /// Builds the configuration for a frame of `fft_size` samples
+    pub fn config(&self, fft_size: usize) -> VocalEffectsConfig {
+        VocalEffectsConfig {
+            fft_size,
+            hop_size: (fft_size as f32 * self.hop_ratio) as usize,
+            sample_rate: self.sample_rate,
+            hop_ratio: self.hop_ratio,
+            transition_speed: self.transition_speed,
+            pitch_correction_strength: self.pitch_correction_strength,
+            min_frequency: self.min_frequency,
+            max_frequency: self.max_frequency,
+            pitch_detector: match self.pitch_detector % 4 {
+                0 => PitchDetector::PeakBin,
+                1 => PitchDetector::HarmonicProduct,
+                2 => PitchDetector::HarmonicSum,
+                _ => PitchDetector::Autocorrelation,
+            },
+            pitch_decimation: match self.pitch_decimation % 3 {
+                0 => PitchDecimation::None,
+                1 => PitchDecimation::X2,
+                _ => PitchDecimation::X4,
+            },
+            mode_crossfade_hops: self.mode_crossfade_hops,
+            lifter_cutoff_override: self.lifter_cutoff_override,
+            envelope_update_interval: self.envelope_update_interval as usize,
+            formant_morph: self.formant_morph,
+            wet_dry: self.wet_dry,
+            glide: match self.glide % 3 {
+                0 => Glide::Off,
+                1 => Glide::Time(self.glide_amount),
+                _ => Glide::Rate(self.glide_amount),
+            },
+            ornaments: Ornaments {
+                scoop_cents: self.scoop.0,
+                scoop_ms: self.scoop.1,
+                fall_cents: self.fall.0,
+                fall_ms: self.fall.1,
+            },
+            transients: match self.transients % 3 {
+                0 => TransientHandling::Off,
+                1 => TransientHandling::PhaseReset,
+                _ => TransientHandling::Passthrough,
+            },
+            transient_threshold_db: self.transient_threshold_db,
+            harmonic_percussive_separation: self.harmonic_percussive_separation,
+            spectral_gate: self.spectral_gate.map(|(threshold_db, attack_ms, release_ms)| {
+                SpectralGate { threshold_db, attack_ms, release_ms }
+            }),
+            exciter: self.exciter.map(|(crossover_hz, drive, mix)| ExciterSettings {
+                crossover_hz,
+                drive,
+                mix,
+            }),
+            saturation: self.saturation.map(|(curve, drive, mix, oversample)| SaturationSettings {
+                curve: match curve % 3 {
+                    0 => SaturationCurve::SoftClip,
+                    1 => SaturationCurve::Tube,
+                    _ => SaturationCurve::Foldback,
+                },
+                drive,
+                mix,
+                oversample,
+            }),
+            sample_rate_reduction: self.sample_rate_reduction,
+            bitcrush: self.bitcrush,
+            chorus: self.chorus.map(|(hz, delay_ms, depth_ms, feedback, mix)| ChorusSettings {
+                rate: ModulationRate::Hz(hz),
+                delay_ms,
+                depth_ms,
+                feedback,
+                mix,
+            }),
+            delay: self.delay.map(|(seconds, feedback, damping, mix)| DelaySettings {
+                time: DelayTime::Seconds(seconds),
+                feedback,
+                damping,
+                mix,
+            }),
+            reverb: self.reverb.map(|(room_size, damping, mix)| ReverbSettings {
+                room_size,
+                damping,
+                mix,
+            }),
+            hum_filter: self.hum_filter.map(|sixty| {
+                if sixty {
+                    MainsFrequency::Hz60
+                } else {
+                    MainsFrequency::Hz50
+                }
+            }),
+            pre_emphasis: self.pre_emphasis,
+            soft_clip: self.soft_clip.map(|(threshold, knee)| SoftClip { threshold, knee }),
+            phase_reanchor: self
+                .phase_reanchor
+                .map(|(interval_ms, silence_db)| PhaseReanchor { interval_ms, silence_db }),
+            voice_activity_gate: self.voice_activity_gate.map(|(threshold_db, hold_ms, dry)| {
+                VoiceActivityGate {
+                    threshold_db,
+                    hold_ms,
+                    idle_output: if dry {
+                        IdleOutput::Dry
+                    } else {
+                        IdleOutput::Silence
+                    },
+                }
+            }),
+            band_limit: self
+                .band_limit
+                .map(|(cutoff_hz, transition_hz)| BandLimit { cutoff_hz, transition_hz }),
+            low_confidence: match self.low_confidence % 3 {
+                0 => LowConfidence::Correct,
+                1 => LowConfidence::Hold,
+                _ => LowConfidence::Bypass,
+            },
+            confidence_threshold: self.confidence_threshold,
+            noise_fill: self
+                .noise_fill
+                .map(|(threshold_db, level)| NoiseFill { threshold_db, level }),
+            vocoder_envelope: self
+                .vocoder_envelope
+                .map(|(attack_ms, release_ms)| VocoderEnvelope { attack_ms, release_ms }),
+            sibilance_bypass: self
+                .sibilance_bypass
+                .map(|(crossover_hz, mix)| SibilanceBypass { crossover_hz, mix }),
+            breath_retention: self
+                .breath_retention
+                .map(|(cutoff_hz, level)| BreathRetention { cutoff_hz, level }),
+            sidechain_duck: self.sidechain_duck.map(
+                |(threshold_db, ratio, attack_ms, release_ms)| SidechainDuck {
+                    threshold_db,
+                    ratio,
+                    attack_ms,
+                    release_ms,
+                },
+            ),
+            unwindowed_synth: self.unwindowed_synth,
+            frame_size: self.frame_size,
+            synthesis_hop_ratio: self.synthesis_hop_ratio,
+            pitch_shift_algorithm: if self.pitch_shift_algorithm {
+                PitchShiftAlgorithm::ResampleStretch
+            } else {
+                PitchShiftAlgorithm::BinRemap
+            },
+            tuning: self
+                .tuning
+                .and_then(|(base_hz, cents)| Tuning::from_cents(base_hz, &cents).ok()),
+        }
+    }
+}
+
+/// Arbitrary [`MusicalSettings`]
+#[derive(Arbitrary, Debug, Clone, Copy)]
+pub struct FuzzSettings {
+    pub key: i32,
+    pub note: i32,
+    pub octave: i32,
+    pub held_note_cents: f32,
+    pub octave_shift: i32,
+    pub formant: i32,
+    pub vocoder_formant_semitones: f32,
+    pub pitch_shift_semitones: f32,
+    pub synth_mix: f32,
+    pub mode: u8,
+    /// Chord mask to snap to, the key when no pitch class bit is set
+    pub chord: u16,
+    pub correction_strength: [u8; 12],
+    pub vocoder_eq: [u8; 16],
+}
+
+impl From<FuzzSettings> for MusicalSettings {
+    fn from(settings: FuzzSettings) -> Self {
+        MusicalSettings {
+            key: settings.key.try_into().unwrap_or_default(),
+            note: settings.note.try_into().unwrap_or_default(),
+            octave: settings.octave.try_into().unwrap_or_default(),
+            held_note_cents: settings.held_note_cents,
+            octave_shift: settings.octave_shift.try_into().unwrap_or_default(),
+            formant: settings.formant.try_into().unwrap_or_default(),
+            vocoder_formant_semitones: settings.vocoder_formant_semitones,
+            pitch_shift_semitones: settings.pitch_shift_semitones,
+            synth_mix: settings.synth_mix,
+            mode: mode(settings.mode),
+            target: match ChordSpec::from_mask(settings.chord) {
+                chord if chord.is_empty() => TargetSource::Key,
+                chord => TargetSource::Chord(chord),
+            },
+            correction_strength: CorrectionStrength::from_bytes(settings.correction_strength),
+            vocoder_eq: VocoderEq::from_bytes(settings.vocoder_eq),
+        }
+    }
+}
+
+/// Maps a byte onto a [`ProcessingMode`]
+pub fn mode(value: u8) -> ProcessingMode {
+    match value % 5 {
+        0 => ProcessingMode::Autotune,
+        1 => ProcessingMode::Vocode,
+        2 => ProcessingMode::Dry,
+        3 => ProcessingMode::Formant,
+        _ => ProcessingMode::TalkBox,
+    }
+}
+
+/// Repeats `samples` (or silence when empty) to fill `len` samples
+pub fn fill(samples: &[f32], len: usize) -> Vec<f32> {
+    if samples.is_empty() {
+        vec![0.0; len]
+    } else {
+        samples.iter().copied().cycle().take(len).collect()
+    }
+}
@@ -1,5 +1,7 @@
 use libm::fabsf;
 
+use crate::state::ChordSpec;
+
 pub const C_MAJOR_SCALE_STEPS: [usize; 7] = [0, 2, 4, 5, 7, 9, 11];
 pub const MAX_OCTAVES: usize = 10;
 
@@ -169,6 +171,27 @@ pub fn find_nearest_note_in_key(input_frequency: f32, key_frequencies: &[f32]) -
     nearest_frequency
 }
 
+/// Nearest frequency among the tones of `chord` in every octave of the note tables,
+/// `None` for an empty chord
+pub fn find_nearest_chord_tone(input_frequency: f32, chord: ChordSpec) -> Option<f32> {
+    let mut nearest = None;
+    let mut min_difference = f32::INFINITY;
+    for (pitch_class, &base) in BASE_FREQUENCIES.iter().enumerate() {
+        if !chord.contains(pitch_class as u8) {
+            continue;
+        }
+        for octave in 0..MAX_OCTAVES {
+            let frequency = base * (1u32 << octave) as f32;
+            let difference = fabsf(input_frequency - frequency);
+            if difference < min_difference {
+                min_difference = difference;
+                nearest = Some(frequency);
+            }
+        }
+    }
+    nearest
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
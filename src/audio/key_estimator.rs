@@ -0,0 +1,331 @@
+//! Key detection from sung audio.
+//!
+//! [`KeyEstimator`] accumulates a histogram of the pitch classes the singer hits,
+//! fading out old notes, and correlates it with the Krumhansl-Kessler key profiles
+//! of the 24 supported keys. The estimate only changes once another key has
+//! scored clearly better for a while, so a borrowed chord or a passing modulation
+//! does not flip the autotune scale back and forth.
+
+use libm::{log2f, roundf, sqrtf};
+
+use crate::{
+    Key, MusicalSettings,
+    engine::{FrameObserver, FrameView},
+};
+
+/// Krumhansl-Kessler probe-tone ratings of the major scale degrees, from the tonic
+const MAJOR_PROFILE: [f32; 12] =
+    [6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88];
+/// Krumhansl-Kessler probe-tone ratings of the minor scale degrees, from the tonic
+const MINOR_PROFILE: [f32; 12] =
+    [6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17];
+
+/// Tuning of a [`KeyEstimator`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct KeyEstimatorOptions {
+    /// Voiced hops after which a note's weight has decayed to about a third
+    pub memory_hops: usize,
+    /// Frames quieter than this level in dBFS are ignored
+    pub min_level_db: f32,
+    /// Correlation the best key needs before it is reported at all
+    pub min_confidence: f32,
+    /// Correlation by which another key must beat the current one to replace it
+    pub switch_margin: f32,
+    /// Consecutive voiced hops the new key must stay ahead before switching
+    pub hold_hops: usize,
+}
+
+impl Default for KeyEstimatorOptions {
+    fn default() -> Self {
+        Self {
+            memory_hops: 2000,
+            min_level_db: -45.0,
+            min_confidence: 0.6,
+            switch_margin: 0.05,
+            hold_hops: 100,
+        }
+    }
+}
+
+/// Key suggested by a [`KeyEstimator`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct KeyEstimate {
+    pub key: Key,
+    /// Correlation between the pitch-class histogram and the key profile, 0.0 to 1.0
+    pub confidence: f32,
+}
+
+/// Estimates the key of a performance from its detected pitches
+///
+/// Feed it as a [`FrameObserver`] in autotune mode, or call
+/// [`KeyEstimator::add_pitch`] with pitches from elsewhere. Read the suggestion
+/// with [`KeyEstimator::estimate`], or let [`KeyEstimator::apply`] set it.
+///
+/// ```rust
+/// use synthphone_e_vocal_dsp::{Key, audio::KeyEstimator};
+///
+/// let mut estimator = KeyEstimator::default();
+/// // An A minor arpeggio
+/// for _ in 0..200 {
+///     for frequency in [220.0, 261.63, 329.63, 220.0, 246.94, 293.66, 329.63] {
+///         estimator.add_pitch(frequency, 1.0);
+///     }
+/// }
+/// assert_eq!(estimator.estimate().map(|estimate| estimate.key), Some(Key::AMinor));
+/// ```
+#[derive(Debug, Clone)]
+pub struct KeyEstimator {
+    options: KeyEstimatorOptions,
+    histogram: [f32; 12],
+    current: Option<KeyEstimate>,
+    /// Key that is ahead of the current one and the hops it has stayed ahead
+    challenger: Option<(Key, usize)>,
+}
+
+impl KeyEstimator {
+    /// Creates an estimator with an empty history
+    pub const fn new(options: KeyEstimatorOptions) -> Self {
+        Self { options, histogram: [0.0; 12], current: None, challenger: None }
+    }
+
+    /// Returns the estimator options
+    pub fn options(&self) -> &KeyEstimatorOptions {
+        &self.options
+    }
+
+    /// Forgets everything heard so far
+    pub fn reset(&mut self) {
+        self.histogram = [0.0; 12];
+        self.current = None;
+        self.challenger = None;
+    }
+
+    /// Current key estimate, `None` until one is confident enough
+    pub fn estimate(&self) -> Option<KeyEstimate> {
+        self.current
+    }
+
+    /// Accumulated weight of each pitch class, C first
+    pub fn histogram(&self) -> &[f32; 12] {
+        &self.histogram
+    }
+
+    /// Correlation of the histogram with every key, in [`Key::ALL`] order
+    pub fn scores(&self) -> [f32; 24] {
+        Key::ALL.map(|key| self.score(key))
+    }
+
+    /// Sets `settings.key` to the estimate, returning `true` if it changed
+    pub fn apply(&self, settings: &mut MusicalSettings) -> bool {
+        match self.current {
+            Some(estimate) if estimate.key != settings.key => {
+                settings.key = estimate.key;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Adds a detected pitch to the history with `weight` and updates the estimate
+    ///
+    /// Pitches between two semitones count less towards the nearer one.
+    /// Non-positive frequencies are ignored.
+    pub fn add_pitch(&mut self, frequency: f32, weight: f32) {
+        if frequency.is_nan() || frequency <= 0.0 || weight <= 0.0 {
+            return;
+        }
+        let semitones = 12.0 * log2f(frequency / 440.0) + 69.0;
+        let nearest = roundf(semitones);
+        let pitch_class = (nearest as i32).rem_euclid(12) as usize;
+
+        let decay = 1.0 - 1.0 / self.options.memory_hops.max(1) as f32;
+        for bin in &mut self.histogram {
+            *bin *= decay;
+        }
+        self.histogram[pitch_class] += weight * (1.0 - (semitones - nearest).abs());
+        self.update_estimate();
+    }
+
+    fn update_estimate(&mut self) {
+        let (best, best_score) = Key::ALL
+            .iter()
+            .map(|&key| (key, self.score(key)))
+            .fold((Key::CMajor, f32::MIN), |a, b| if b.1 > a.1 { b } else { a });
+
+        let current_score = match self.current {
+            Some(current) if current.key == best => {
+                self.challenger = None;
+                self.current = Some(KeyEstimate { key: best, confidence: best_score.max(0.0) });
+                return;
+            }
+            Some(current) => self.score(current.key),
+            None => self.options.min_confidence - self.options.switch_margin,
+        };
+        if best_score < self.options.min_confidence
+            || best_score - current_score < self.options.switch_margin
+        {
+            self.challenger = None;
+            if let Some(current) = &mut self.current {
+                current.confidence = current_score.max(0.0);
+            }
+            return;
+        }
+
+        let held = match self.challenger {
+            Some((key, hops)) if key == best => hops + 1,
+            _ => 1,
+        };
+        if held >= self.options.hold_hops.max(1) {
+            self.challenger = None;
+            self.current = Some(KeyEstimate { key: best, confidence: best_score });
+        } else {
+            self.challenger = Some((best, held));
+            if let Some(current) = &mut self.current {
+                current.confidence = current_score.max(0.0);
+            }
+        }
+    }
+
+    /// Pearson correlation of the histogram with the profile of `key`
+    fn score(&self, key: Key) -> f32 {
+        let profile = if key.is_minor() {
+            &MINOR_PROFILE
+        } else {
+            &MAJOR_PROFILE
+        };
+        let tonic = key.tonic() as usize;
+        let profile_at = |pitch_class: usize| profile[(pitch_class + 12 - tonic) % 12];
+
+        let histogram_mean = self.histogram.iter().sum::<f32>() / 12.0;
+        let profile_mean = profile.iter().sum::<f32>() / 12.0;
+        let (mut covariance, mut histogram_variance, mut profile_variance) = (0.0, 0.0, 0.0);
+        for (pitch_class, &weight) in self.histogram.iter().enumerate() {
+            let h = weight - histogram_mean;
+            let p = profile_at(pitch_class) - profile_mean;
+            covariance += h * p;
+            histogram_variance += h * h;
+            profile_variance += p * p;
+        }
+        let norm = sqrtf(histogram_variance * profile_variance);
+        if norm > 0.0 { covariance / norm } else { 0.0 }
+    }
+}
+
+impl Default for KeyEstimator {
+    fn default() -> Self {
+        Self::new(KeyEstimatorOptions::default())
+    }
+}
+
+impl FrameObserver for KeyEstimator {
+    fn on_frame(&mut self, frame: &FrameView<'_>) {
+        if frame.level_db() >= self.options.min_level_db {
+            self.add_pitch(frame.analysis.detected_frequency, 1.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Engine1024, VocalEffectsConfig};
+    use core::f32::consts::PI;
+
+    fn frequency(midi_note: i32) -> f32 {
+        440.0 * libm::exp2f((midi_note - 69) as f32 / 12.0)
+    }
+
+    /// Sings `melody` `repeats` times, each note for `hops` hops
+    fn sing(estimator: &mut KeyEstimator, melody: &[i32], hops: usize, repeats: usize) {
+        for _ in 0..repeats {
+            for &note in melody {
+                for _ in 0..hops {
+                    estimator.add_pitch(frequency(note), 1.0);
+                }
+            }
+        }
+    }
+
+    const G_MAJOR_TUNE: [i32; 8] = [67, 71, 74, 72, 71, 69, 66, 67];
+    const E_MINOR_TUNE: [i32; 8] = [64, 67, 71, 69, 67, 66, 64, 59];
+
+    #[test]
+    fn test_tonic_pitch_classes() {
+        assert_eq!(Key::CMajor.tonic(), 0);
+        assert_eq!(Key::GSharpMinor.tonic(), 8);
+        assert_eq!(Key::BFlatMajor.tonic(), 10);
+        assert!(Key::ALL.iter().all(|key| key.tonic() < 12));
+    }
+
+    #[test]
+    fn test_detects_major_and_relative_minor() {
+        let mut estimator = KeyEstimator::default();
+        sing(&mut estimator, &G_MAJOR_TUNE, 20, 10);
+        let estimate = estimator.estimate().unwrap();
+        assert_eq!(estimate.key, Key::GMajor);
+        assert!(estimate.confidence > 0.7, "confidence {}", estimate.confidence);
+
+        let mut estimator = KeyEstimator::default();
+        sing(&mut estimator, &E_MINOR_TUNE, 20, 10);
+        assert_eq!(estimator.estimate().unwrap().key, Key::EMinor);
+    }
+
+    #[test]
+    fn test_no_estimate_without_enough_evidence() {
+        let mut estimator = KeyEstimator::default();
+        assert_eq!(estimator.estimate(), None);
+        // A few hops are not held long enough
+        sing(&mut estimator, &G_MAJOR_TUNE, 5, 1);
+        assert_eq!(estimator.estimate(), None);
+        estimator.add_pitch(0.0, 1.0);
+        assert!(estimator.histogram().iter().sum::<f32>() > 0.0);
+    }
+
+    #[test]
+    fn test_hysteresis_before_switching() {
+        let options = KeyEstimatorOptions { memory_hops: 400, ..Default::default() };
+        let mut estimator = KeyEstimator::new(options);
+        sing(&mut estimator, &G_MAJOR_TUNE, 20, 6);
+        assert_eq!(estimator.estimate().unwrap().key, Key::GMajor);
+
+        // A bar borrowed from D major is not enough to switch
+        const D_MAJOR_TUNE: [i32; 8] = [62, 66, 69, 73, 74, 71, 73, 74];
+        sing(&mut estimator, &D_MAJOR_TUNE, 10, 1);
+        assert_eq!(estimator.estimate().unwrap().key, Key::GMajor);
+
+        // A sustained modulation is
+        sing(&mut estimator, &D_MAJOR_TUNE, 20, 8);
+        assert_eq!(estimator.estimate().unwrap().key, Key::DMajor);
+
+        let mut settings = MusicalSettings::default();
+        assert!(estimator.apply(&mut settings));
+        assert_eq!(settings.key, Key::DMajor);
+        assert!(!estimator.apply(&mut settings));
+    }
+
+    #[test]
+    fn test_observes_engine_pitch() {
+        let mut engine = Engine1024::new(VocalEffectsConfig::default(), MusicalSettings::default());
+        let options = KeyEstimatorOptions { hold_hops: 20, ..Default::default() };
+        let mut estimator = KeyEstimator::new(options);
+        let mut output = [0.0f32; 256];
+        let mut phase = 0.0f32;
+        for (block, note) in
+            E_MINOR_TUNE.iter().cycle().flat_map(|&note| [note; 12]).take(400).enumerate()
+        {
+            let input: [f32; 256] = core::array::from_fn(|i| {
+                phase = (phase + frequency(note) / 48000.0).fract();
+                // Silent gap between notes
+                if block % 12 == 0 && i < 128 {
+                    0.0
+                } else {
+                    0.3 * libm::sinf(2.0 * PI * phase)
+                }
+            });
+            engine.process_hop_observed(&input, None, &mut output, &mut estimator).unwrap();
+        }
+        assert_eq!(estimator.estimate().unwrap().key, Key::EMinor);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::audio::frequencies::*;
+use crate::{VocalEffectsError, audio::frequencies::*};
 
 /// A KeyScale is simply an array of 7 static string slices, e.g. ["C", "D", "E", "F", "G", "A", "B"].
 pub type KeyScaleFrequencies = [f32; 70];
@@ -154,23 +154,83 @@ pub fn get_scale_by_key(key: i32) -> &'static KeyScaleFrequencies {
     }
 }
 
-pub fn get_frequency(key: i32, note: i32, octave: i32, is_vocoder: bool) -> f32 {
-    //TODO: maybe i should have the octave store index insted of values so i don't have to convert here?
+/// Largest detune [`get_frequency`] accepts, a quarter tone either way
+pub const MAX_DETUNE_CENTS: f32 = 50.0;
+
+/// Frequency of a scale degree of a key, detuned by `cents`
+///
+/// `note` is the scale degree from 1 to 7 and `octave` the legacy octave flag
+/// (1, 2 or 4, see [`Octave`](crate::Octave)). Vocoder carriers are taken two
+/// octaves below the voice range. A detune of a few cents sharpens or flattens
+/// the target for ensemble tuning, e.g. a harmony part sitting slightly above
+/// the lead.
+///
+/// # Errors
+///
+/// Returns [`VocalEffectsError::InvalidConfiguration`] if the key, note or octave
+/// is out of range, or `cents` is not within ±[`MAX_DETUNE_CENTS`].
+pub fn get_frequency(
+    key: i32,
+    note: i32,
+    octave: i32,
+    is_vocoder: bool,
+    cents: f32,
+) -> Result<f32, VocalEffectsError> {
     let offset = if is_vocoder { 0 } else { 2 };
 
     let octave_idx = match octave {
         1 => 1 + offset, // first row
         2 => 2 + offset, // second row
         4 => 3 + offset, // third row
-        _ => return 0.0, // invalid flag
+        _ => return Err(VocalEffectsError::InvalidConfiguration),
+    };
+    let note_index = match usize::try_from(note) {
+        Ok(note @ 1..=7) => octave_idx * 7 + note - 1,
+        _ => return Err(VocalEffectsError::InvalidConfiguration),
     };
+    if !(-MAX_DETUNE_CENTS..=MAX_DETUNE_CENTS).contains(&cents) {
+        return Err(VocalEffectsError::InvalidConfiguration);
+    }
 
-    let note_index = octave_idx * 7 + note as usize - 1;
+    let frequency = usize::try_from(key)
+        .ok()
+        .and_then(|key| KEYS.get(key))
+        .and_then(|k| k.0.1.get(note_index))
+        .copied()
+        .ok_or(VocalEffectsError::InvalidConfiguration)?;
+    Ok(if cents == 0.0 {
+        frequency
+    } else {
+        frequency * libm::exp2f(cents / 1200.0)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // out-of-bounds check
-    if key as usize >= KEYS.len() {
-        return 0.0;
+    #[test]
+    fn test_get_frequency_out_of_range() {
+        assert!(get_frequency(0, 1, 2, false, 0.0).unwrap() > 0.0);
+        let invalid = Err(VocalEffectsError::InvalidConfiguration);
+        assert_eq!(get_frequency(0, 0, 2, false, 0.0), invalid);
+        assert_eq!(get_frequency(0, -5, 2, false, 0.0), invalid);
+        assert_eq!(get_frequency(0, 8, 2, false, 0.0), invalid);
+        assert_eq!(get_frequency(0, 1_000_000, 4, true, 0.0), invalid);
+        assert_eq!(get_frequency(-1, 1, 2, false, 0.0), invalid);
+        assert_eq!(get_frequency(KEYS.len() as i32, 1, 2, false, 0.0), invalid);
+        assert_eq!(get_frequency(0, 1, 3, false, 0.0), invalid);
+        assert_eq!(get_frequency(0, 1, 2, false, 51.0), invalid);
+        assert_eq!(get_frequency(0, 1, 2, false, f32::NAN), invalid);
     }
 
-    KEYS[key as usize].0.1[note_index]
+    #[test]
+    fn test_get_frequency_detune() {
+        let a = get_frequency(0, 6, 2, false, 0.0).unwrap();
+        assert!((a - 440.0).abs() < 0.01, "{a}");
+        let sharp = get_frequency(0, 6, 2, false, 10.0).unwrap();
+        assert!((1200.0 * libm::log2f(sharp / a) - 10.0).abs() < 1e-3, "{sharp}");
+        let flat = get_frequency(0, 6, 2, false, -50.0).unwrap();
+        assert!((1200.0 * libm::log2f(flat / a) + 50.0).abs() < 1e-3, "{flat}");
+    }
 }
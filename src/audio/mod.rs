@@ -1,7 +1,11 @@
 pub mod frequencies;
+pub mod key_estimator;
 pub mod keys;
 pub mod oscillator;
+pub mod tuning;
 
 pub use frequencies::*;
+pub use key_estimator::*;
 pub use keys::*;
 pub use oscillator::*;
+pub use tuning::*;
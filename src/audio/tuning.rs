@@ -0,0 +1,305 @@
+//! Custom tunings for automatic correction.
+//!
+//! A [`Tuning`] is a table of note frequencies across one period (usually an
+//! octave) that repeats up and down the range, so scales outside 12-tone equal
+//! temperament can be targeted: maqam scales with quarter tones, gamelan
+//! tunings, just intonation or any equal division of the octave. With the `std`
+//! feature, tunings load from Scala (`.scl`) files, the format of the Scala
+//! scale archive.
+//!
+//! ```rust
+//! use synthphone_e_vocal_dsp::audio::Tuning;
+//!
+//! // 24-tone equal temperament from A4
+//! let steps: Vec<f32> = (1..=24).map(|step| step as f32 * 50.0).collect();
+//! let tuning = Tuning::from_cents(440.0, &steps).unwrap();
+//! assert!((tuning.nearest(450.0) - 452.89).abs() < 0.01);
+//! ```
+
+use libm::{exp2f, fabsf, floorf, log2f};
+
+use crate::VocalEffectsError;
+
+/// Most notes per period of a [`Tuning`], enough for 72-tone equal temperament
+pub const MAX_TUNING_NOTES: usize = 72;
+
+/// Note frequencies of one period of a scale, repeating in every period
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Tuning {
+    /// Frequencies of the notes in the base period in Hz, ascending from the base note
+    frequencies: [f32; MAX_TUNING_NOTES],
+    len: usize,
+    /// Octaves spanned by one period
+    period_octaves: f32,
+}
+
+impl Tuning {
+    /// Creates a tuning from the base note at `base_hz` and the pitches of the
+    /// other notes in cents above it
+    ///
+    /// As in a Scala file, the base note is implied and the last pitch is the
+    /// period the scale repeats at, 1200 cents for an octave. The other pitches
+    /// may be given in any order.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VocalEffectsError::InvalidConfiguration`] if the base frequency
+    /// is not positive, there are no pitches or more than [`MAX_TUNING_NOTES`],
+    /// or a pitch is not finite or not between the base note and the period.
+    pub fn from_cents(base_hz: f32, cents: &[f32]) -> Result<Self, VocalEffectsError> {
+        let (&period, steps) = cents.split_last().ok_or(VocalEffectsError::InvalidConfiguration)?;
+        let positive = |value: f32| value > 0.0 && value.is_finite();
+        if !positive(base_hz)
+            || !positive(period)
+            || steps.len() >= MAX_TUNING_NOTES
+            || steps.iter().any(|&step| !(step > 0.0 && step < period))
+        {
+            return Err(VocalEffectsError::InvalidConfiguration);
+        }
+
+        let mut frequencies = [0.0f32; MAX_TUNING_NOTES];
+        frequencies[0] = base_hz;
+        for (frequency, &step) in frequencies[1..].iter_mut().zip(steps) {
+            *frequency = base_hz * exp2f(step / 1200.0);
+        }
+        let len = steps.len() + 1;
+        frequencies[..len].sort_unstable_by(f32::total_cmp);
+        Ok(Self { frequencies, len, period_octaves: period / 1200.0 })
+    }
+
+    /// Notes per period
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the tuning has no notes, which never happens
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Frequencies of the notes in the base period in Hz, ascending from the base note
+    pub fn frequencies(&self) -> &[f32] {
+        &self.frequencies[..self.len]
+    }
+
+    /// Period the scale repeats at in cents
+    pub fn period_cents(&self) -> f32 {
+        self.period_octaves * 1200.0
+    }
+
+    /// Nearest note of the tuning to `frequency` in Hz, by pitch distance
+    pub fn nearest(&self, frequency: f32) -> f32 {
+        let base = self.frequencies[0];
+        if frequency.is_nan() || frequency <= 0.0 {
+            return base;
+        }
+        // Fold into the base period, then compare with its notes and the next base note
+        let periods = floorf(log2f(frequency / base) / self.period_octaves);
+        let shift = exp2f(periods * self.period_octaves);
+        let folded = frequency / shift;
+        let next_base = base * exp2f(self.period_octaves);
+        let distance = |note: f32| fabsf(log2f(folded / note));
+        let nearest = self.frequencies().iter().copied().fold(next_base, |nearest, note| {
+            if distance(note) < distance(nearest) {
+                note
+            } else {
+                nearest
+            }
+        });
+        nearest * shift
+    }
+}
+
+/// Reasons a Scala file is rejected by [`Tuning::from_scala`]
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScalaError {
+    /// The file could not be read
+    Io(std::io::ErrorKind),
+    /// The file ends before the note count or before all pitches
+    UnexpectedEnd,
+    /// The note count on this line, counted from 1, is not a number
+    InvalidNoteCount {
+        /// Line of the note count
+        line: usize,
+    },
+    /// The pitch on this line, counted from 1, is neither cents nor a positive ratio
+    InvalidPitch {
+        /// Line of the pitch
+        line: usize,
+    },
+    /// The pitches do not form a tuning (see [`Tuning::from_cents`])
+    InvalidTuning,
+}
+
+#[cfg(feature = "std")]
+impl std::fmt::Display for ScalaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScalaError::Io(kind) => write!(f, "Could not read Scala file: {kind}"),
+            ScalaError::UnexpectedEnd => write!(f, "Scala file ends before all pitches"),
+            ScalaError::InvalidNoteCount { line } => {
+                write!(f, "Invalid note count on line {line}")
+            }
+            ScalaError::InvalidPitch { line } => write!(f, "Invalid pitch on line {line}"),
+            ScalaError::InvalidTuning => write!(
+                f,
+                "Scala pitches must lie between the base note and the period, at most {MAX_TUNING_NOTES} notes"
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ScalaError {}
+
+#[cfg(feature = "std")]
+impl Tuning {
+    /// Parses the contents of a Scala (`.scl`) file, with its base note at `base_hz`
+    ///
+    /// Lines starting with `!` are comments. The first other line is the
+    /// description, the next the number of notes, followed by one pitch per line:
+    /// cents if it contains a `.`, otherwise a ratio such as `3/2` or `2`.
+    /// Anything after the pitch is ignored. Scala files do not fix the base
+    /// frequency; a keyboard mapping would, so it is passed in here.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ScalaError`] pointing at the offending line, or
+    /// [`ScalaError::InvalidTuning`] if the pitches do not form a tuning.
+    pub fn from_scala(text: &str, base_hz: f32) -> Result<Self, ScalaError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.starts_with('!'));
+        lines.next().ok_or(ScalaError::UnexpectedEnd)?;
+        let (line, count) = lines.next().ok_or(ScalaError::UnexpectedEnd)?;
+        let count: usize =
+            first_word(count).parse().map_err(|_| ScalaError::InvalidNoteCount { line })?;
+        if count > MAX_TUNING_NOTES {
+            return Err(ScalaError::InvalidTuning);
+        }
+
+        let mut cents = [0.0f32; MAX_TUNING_NOTES];
+        for pitch in cents[..count].iter_mut() {
+            let (line, text) = lines.next().ok_or(ScalaError::UnexpectedEnd)?;
+            *pitch = parse_pitch(first_word(text)).ok_or(ScalaError::InvalidPitch { line })?;
+        }
+        Self::from_cents(base_hz, &cents[..count]).map_err(|_| ScalaError::InvalidTuning)
+    }
+
+    /// Reads a Scala (`.scl`) file, with its base note at `base_hz`
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ScalaError::Io`] if the file cannot be read as text, or any error
+    /// from [`Tuning::from_scala`].
+    pub fn load_scala(path: impl AsRef<std::path::Path>, base_hz: f32) -> Result<Self, ScalaError> {
+        let text = std::fs::read_to_string(path).map_err(|error| ScalaError::Io(error.kind()))?;
+        Self::from_scala(&text, base_hz)
+    }
+}
+
+#[cfg(feature = "std")]
+fn first_word(line: &str) -> &str {
+    line.split_whitespace().next().unwrap_or("")
+}
+
+/// Pitch of a Scala note line in cents, `None` if it does not parse
+#[cfg(feature = "std")]
+fn parse_pitch(text: &str) -> Option<f32> {
+    if text.contains('.') {
+        return text.parse::<f32>().ok().filter(|cents| cents.is_finite());
+    }
+    let (numerator, denominator) = text.split_once('/').unwrap_or((text, "1"));
+    let numerator: u64 = numerator.parse().ok()?;
+    let denominator: u64 = denominator.parse().ok()?;
+    if numerator == 0 || denominator == 0 {
+        return None;
+    }
+    Some((1200.0 * (numerator as f64 / denominator as f64).log2()) as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nearest_follows_periods() {
+        let tuning = Tuning::from_cents(440.0, &[700.0, 1200.0]).unwrap();
+        assert_eq!(tuning.len(), 2);
+        assert!((tuning.nearest(440.0) - 440.0).abs() < 1e-3);
+        // A fifth above, an octave above and two octaves below
+        assert!((tuning.nearest(650.0) - 659.26).abs() < 0.01);
+        assert!((tuning.nearest(870.0) - 880.0).abs() < 1e-2);
+        assert!((tuning.nearest(112.0) - 110.0).abs() < 1e-3);
+        assert!((tuning.nearest(160.0) - 164.81).abs() < 0.01);
+
+        // Non-octave period: Bohlen-Pierce repeats at the tritave
+        let tritave = 1200.0 * libm::log2f(3.0);
+        let bp = Tuning::from_cents(100.0, &[tritave]).unwrap();
+        assert!((bp.nearest(280.0) - 300.0).abs() < 0.01);
+        assert!((bp.nearest(30.0) - 33.333).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_from_cents_rejects_invalid_pitches() {
+        let invalid = Err(VocalEffectsError::InvalidConfiguration);
+        assert_eq!(Tuning::from_cents(440.0, &[]), invalid);
+        assert_eq!(Tuning::from_cents(0.0, &[1200.0]), invalid);
+        assert_eq!(Tuning::from_cents(440.0, &[-100.0, 1200.0]), invalid);
+        assert_eq!(Tuning::from_cents(440.0, &[1300.0, 1200.0]), invalid);
+        assert_eq!(Tuning::from_cents(440.0, &[f32::NAN, 1200.0]), invalid);
+        assert_eq!(Tuning::from_cents(440.0, &[0.0]), invalid);
+        assert_eq!(Tuning::from_cents(440.0, &[100.0; MAX_TUNING_NOTES + 1]), invalid);
+
+        // Unordered pitches are sorted
+        let tuning = Tuning::from_cents(100.0, &[700.0, 400.0, 1200.0]).unwrap();
+        assert!(tuning.frequencies().windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_from_scala() {
+        let scala = "! rast.scl\n\
+                     !\n\
+                     Rast, with neutral third and seventh\n \
+                     7\n\
+                     !\n\
+                     9/8\n\
+                     350.0 neutral third\n\
+                     4/3\n\
+                     3/2\n\
+                     27/16\n\
+                     1050.\n\
+                     2\n";
+        let tuning = Tuning::from_scala(scala, 261.63).unwrap();
+        assert_eq!(tuning.len(), 7);
+        assert!((tuning.period_cents() - 1200.0).abs() < 1e-3);
+        let third = 261.63 * libm::exp2f(350.0 / 1200.0);
+        assert!((tuning.frequencies()[2] - third).abs() < 0.01);
+        assert!((tuning.nearest(321.0) - third).abs() < 0.01);
+        assert!((tuning.frequencies()[3] - 261.63 * 4.0 / 3.0).abs() < 0.01);
+
+        assert_eq!(
+            Tuning::from_scala("only a description", 261.63),
+            Err(ScalaError::UnexpectedEnd)
+        );
+        assert_eq!(
+            Tuning::from_scala("x\nseven\n", 261.63),
+            Err(ScalaError::InvalidNoteCount { line: 2 })
+        );
+        assert_eq!(Tuning::from_scala("x\n2\n3/2\n", 261.63), Err(ScalaError::UnexpectedEnd));
+        assert_eq!(
+            Tuning::from_scala("x\n2\n3/0\n2/1\n", 261.63),
+            Err(ScalaError::InvalidPitch { line: 3 })
+        );
+        assert_eq!(Tuning::from_scala("x\n2\n5/2\n2/1\n", 261.63), Err(ScalaError::InvalidTuning));
+        assert!(matches!(
+            Tuning::load_scala("/nonexistent/scale.scl", 261.63),
+            Err(ScalaError::Io(_))
+        ));
+    }
+}
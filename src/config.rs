@@ -1,16 +1,722 @@
 //! Configuration types for the vocal effects library
+
+use crate::{
+    ConfigError,
+    audio::Tuning,
+    effects::{Bitcrusher, SampleRateReducer, chorus::MAX_CHORUS_MS, delay::MAX_DELAY_SECONDS},
+    tempo::NoteValue,
+};
+
+/// Algorithm used to locate the fundamental in the analysis spectrum
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PitchDetector {
+    /// Loudest bin in the spectrum
+    #[default]
+    PeakBin,
+    /// Harmonic product spectrum - robust when a harmonic is louder than the fundamental
+    HarmonicProduct,
+    /// Peaks scored by their weighted harmonics with an octave-error penalty - robust
+    /// on bright vowels without the product's sensitivity to a missing harmonic
+    HarmonicSum,
+    /// Normalised autocorrelation of the time-domain frame - not limited by the bin
+    /// width, for 128- and 256-point frames
+    Autocorrelation,
+}
+
+/// Decimation applied to the input of the pitch detector
+///
+/// Voice fundamentals sit well below 1 kHz, so the detector does not need the
+/// full band. [`PitchDetector::Autocorrelation`] runs on a low-passed copy of the
+/// frame decimated by the factor, which cuts its cost by roughly the square of
+/// the factor. The spectral detectors share the full-rate FFT with synthesis, so
+/// they search only the bins a decimated frame would contain; their harmonic
+/// search shrinks by the factor. Synthesis always runs at the full rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PitchDecimation {
+    /// Search the whole spectrum
+    #[default]
+    None,
+    /// Detect on every second sample, or the lower half of the spectrum
+    X2,
+    /// Detect on every fourth sample, or the lower quarter of the spectrum
+    X4,
+}
+
+impl PitchDecimation {
+    /// Decimation factor
+    pub fn factor(&self) -> usize {
+        match self {
+            PitchDecimation::None => 1,
+            PitchDecimation::X2 => 2,
+            PitchDecimation::X4 => 4,
+        }
+    }
+}
+
+/// Way the [`Engine`](crate::Engine) transposes the voice by a fixed interval
+///
+/// Moving bins is cheap and works on single frames, but on large shifts the
+/// partials land between bins and the voice turns metallic. Stretching the audio
+/// in time by the ratio and resampling it back moves every partial by the same
+/// ratio within its own bin, and often sounds better an octave or more away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PitchShiftAlgorithm {
+    /// Move each bin to its shifted position
+    #[default]
+    BinRemap,
+    /// Stretch by the ratio with a longer or shorter synthesis hop, then resample
+    /// back to the input rate
+    ResampleStretch,
+}
+
+/// Slide between correction targets when the target note changes
+///
+/// Without a glide the ratio jumps to the new note, smoothed only by the retune
+/// speed. A glide moves the target itself along a straight line in pitch, for
+/// audible slides between notes. It restarts from the detected pitch after an
+/// unvoiced or out-of-range frame.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Glide {
+    /// Jump to the new target
+    #[default]
+    Off,
+    /// Reach the new target in a fixed time in milliseconds, whatever the interval
+    Time(f32),
+    /// Slide at a fixed rate in semitones per second
+    Rate(f32),
+}
+
+impl Glide {
+    /// Largest change of the target in octaves over one hop, for a slide of
+    /// `interval` octaves
+    pub(crate) fn step(&self, interval: f32, hop_seconds: f32) -> f32 {
+        match *self {
+            Glide::Off => f32::INFINITY,
+            Glide::Time(ms) if ms > 0.0 => libm::fabsf(interval) * hop_seconds * 1000.0 / ms,
+            Glide::Time(_) => f32::INFINITY,
+            Glide::Rate(semitones_per_second) => {
+                (semitones_per_second / 12.0 * hop_seconds).max(0.0)
+            }
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        match *self {
+            Glide::Off => true,
+            Glide::Time(ms) => ms.is_finite() && ms >= 0.0,
+            Glide::Rate(rate) => rate.is_finite() && rate > 0.0,
+        }
+    }
+}
+
+/// Pitch ornaments added on top of the correction
+///
+/// A scoop starts a note below its target and slides up into it; a fall drops
+/// the pitch as a phrase ends. Onsets and ends are the frames where the detected
+/// pitch enters and leaves the configured frequency range.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Ornaments {
+    /// How far below the target a note starts, in cents (0.0 = no scoop)
+    pub scoop_cents: f32,
+    /// Time the scoop takes to reach the target in milliseconds
+    pub scoop_ms: f32,
+    /// How far the pitch drops at the end of a phrase, in cents (0.0 = no fall)
+    pub fall_cents: f32,
+    /// Time the fall takes in milliseconds
+    pub fall_ms: f32,
+}
+
+impl Ornaments {
+    /// No scoops or falls
+    pub const NONE: Self = Self { scoop_cents: 0.0, scoop_ms: 0.0, fall_cents: 0.0, fall_ms: 0.0 };
+
+    /// Change of the scoop offset over one hop in cents
+    pub(crate) fn scoop_step(&self, hop_seconds: f32) -> f32 {
+        step_cents(self.scoop_cents, self.scoop_ms, hop_seconds)
+    }
+
+    /// Change of the fall offset over one hop in cents
+    pub(crate) fn fall_step(&self, hop_seconds: f32) -> f32 {
+        step_cents(self.fall_cents, self.fall_ms, hop_seconds)
+    }
+
+    fn is_valid(&self) -> bool {
+        [self.scoop_cents, self.scoop_ms, self.fall_cents, self.fall_ms]
+            .iter()
+            .all(|value| value.is_finite() && *value >= 0.0)
+    }
+}
+
+fn step_cents(cents: f32, ms: f32, hop_seconds: f32) -> f32 {
+    if ms > 0.0 {
+        cents * hop_seconds * 1000.0 / ms
+    } else {
+        f32::INFINITY
+    }
+}
+
+/// Treatment of attacks by the pitch shifter
+///
+/// The phase vocoder smears consonant transients over the whole frame, which
+/// softens plosives at large shift ratios. Frames that contain an attack (see
+/// [`detect_transient`](crate::dsp::frequency_analysis::detect_transient)) can be
+/// resynthesised with their analysis phases or left unshifted; the overlap-add
+/// crossfades them with the shifted frames around them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TransientHandling {
+    /// Shift every frame the same way
+    #[default]
+    Off,
+    /// Shift transient frames, but reset their phases to the analysis phases
+    PhaseReset,
+    /// Pass transient frames through unshifted
+    Passthrough,
+}
+
+/// Correction of frames whose pitch estimate has a low confidence
+///
+/// Noise, breaths, polyphony and silence give pitch estimates that do not
+/// belong to any note; chasing them makes the correction warble. Below the
+/// confidence threshold the correction can hold its last ratio or let the
+/// frame through unshifted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LowConfidence {
+    /// Correct every frame regardless of confidence
+    #[default]
+    Correct,
+    /// Keep the pitch shift ratio and target of the previous frame
+    Hold,
+    /// Return the pitch shift ratio to 1.0 at the retune speed
+    Bypass,
+}
+
+/// Spectral gate applied before resynthesis
+///
+/// Bins more than `threshold_db` below the loudest bin of the frame are faded
+/// out, each with its own gain (see
+/// [`apply_spectral_gate`](crate::dsp::gate::apply_spectral_gate)).
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SpectralGate {
+    /// Level relative to the loudest bin below which a bin is gated, in dB (negative)
+    pub threshold_db: f32,
+    /// Time constant of a gated bin opening in milliseconds
+    pub attack_ms: f32,
+    /// Time constant of a bin closing in milliseconds
+    pub release_ms: f32,
+}
+
+impl SpectralGate {
+    fn is_valid(&self) -> bool {
+        self.threshold_db.is_finite()
+            && self.threshold_db <= 0.0
+            && [self.attack_ms, self.release_ms].iter().all(|ms| ms.is_finite() && *ms >= 0.0)
+    }
+}
+
+/// Harmonic exciter run on the [`Engine`](crate::Engine) output
+///
+/// See [`Exciter`](crate::effects::Exciter).
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ExciterSettings {
+    /// Frequency above which harmonics are generated, in Hz
+    pub crossover_hz: f32,
+    /// Gain into the saturator (1.0 = gentle, 10.0 = dense harmonics)
+    pub drive: f32,
+    /// Level of the generated harmonics mixed back in (0.0 to 1.0)
+    pub mix: f32,
+}
+
+impl ExciterSettings {
+    fn is_valid(&self, sample_rate: f32) -> bool {
+        self.crossover_hz > 0.0
+            && self.crossover_hz < sample_rate / 2.0
+            && self.drive.is_finite()
+            && self.drive > 0.0
+            && (0.0..=1.0).contains(&self.mix)
+    }
+}
+
+/// Time between a sound and its echo
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DelayTime {
+    Seconds(f32),
+    /// Note value at the tempo of the [`Engine`](crate::Engine), following it
+    Note(NoteValue),
+}
+
+impl DelayTime {
+    /// Time in seconds at `bpm`
+    pub fn seconds(&self, bpm: f32) -> f32 {
+        match self {
+            DelayTime::Seconds(seconds) => *seconds,
+            DelayTime::Note(note) => note.seconds(bpm),
+        }
+    }
+}
+
+/// Delay run on the [`Engine`](crate::Engine) output
+///
+/// See [`Delay`](crate::effects::Delay).
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DelaySettings {
+    pub time: DelayTime,
+    /// Level of each echo fed back into the line (0.0 = single echo, below 1.0)
+    pub feedback: f32,
+    /// High-frequency loss of each echo (0.0 = bright, 1.0 = dark)
+    pub damping: f32,
+    /// Level of the echoes mixed in (0.0 to 1.0)
+    pub mix: f32,
+}
+
+impl DelaySettings {
+    fn is_valid(&self) -> bool {
+        let time = match self.time {
+            DelayTime::Seconds(seconds) => seconds > 0.0 && seconds <= MAX_DELAY_SECONDS,
+            DelayTime::Note(note) => note.beats() > 0.0,
+        };
+        time && (0.0..1.0).contains(&self.feedback)
+            && (0.0..=1.0).contains(&self.damping)
+            && (0.0..=1.0).contains(&self.mix)
+    }
+}
+
+/// Transfer curve of the saturator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SaturationCurve {
+    /// Cubic that rounds peaks off and reaches full scale at an input of 1.0
+    #[default]
+    SoftClip,
+    /// Asymmetric tanh with even as well as odd harmonics
+    Tube,
+    /// Folds anything past full scale back down
+    Foldback,
+}
+
+/// Saturation run on the [`Engine`](crate::Engine) output
+///
+/// See [`Saturator`](crate::effects::Saturator).
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SaturationSettings {
+    pub curve: SaturationCurve,
+    /// Gain into the curve (1.0 = only peaks bend, 10.0 = heavy distortion)
+    pub drive: f32,
+    /// Share of the shaped signal in the output (0.0 to 1.0)
+    pub mix: f32,
+    /// Shape at twice the sample rate to keep harmonics from folding back
+    pub oversample: bool,
+}
+
+impl SaturationSettings {
+    fn is_valid(&self) -> bool {
+        self.drive.is_finite() && self.drive > 0.0 && (0.0..=1.0).contains(&self.mix)
+    }
+}
+
+/// Rate of a modulation effect's LFO
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ModulationRate {
+    Hz(f32),
+    /// One cycle per note value, in phase with the [`Engine`](crate::Engine) tempo
+    Note(NoteValue),
+}
+
+/// Chorus or flanger run on the [`Engine`](crate::Engine) output
+///
+/// The delay sweeps between `delay_ms - depth_ms` and `delay_ms + depth_ms`. See
+/// [`Chorus`](crate::effects::Chorus).
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ChorusSettings {
+    pub rate: ModulationRate,
+    /// Centre of the sweep in milliseconds (1 to 5 flanges, 10 to 20 doubles)
+    pub delay_ms: f32,
+    /// Distance the sweep moves either side of the centre, in milliseconds
+    pub depth_ms: f32,
+    /// Level of the swept signal fed back (-1.0 to 1.0, exclusive)
+    pub feedback: f32,
+    /// Level of the swept signal mixed in (0.0 to 1.0)
+    pub mix: f32,
+}
+
+impl ChorusSettings {
+    fn is_valid(&self) -> bool {
+        let rate = match self.rate {
+            ModulationRate::Hz(hz) => hz.is_finite() && hz >= 0.0,
+            ModulationRate::Note(note) => note.beats() > 0.0,
+        };
+        rate && self.depth_ms >= 0.0
+            && self.depth_ms < self.delay_ms
+            && self.delay_ms + self.depth_ms <= MAX_CHORUS_MS
+            && self.feedback.abs() < 1.0
+            && (0.0..=1.0).contains(&self.mix)
+    }
+}
+
+/// Reverb run on the [`Engine`](crate::Engine) output
+///
+/// See [`Reverb`](crate::effects::Reverb).
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ReverbSettings {
+    /// Decay time, from 0.2 s (0.0) to 5 s (1.0) to fall by 60 dB
+    pub room_size: f32,
+    /// High-frequency loss of each reflection (0.0 = bright, 1.0 = dark)
+    pub damping: f32,
+    /// Level of the reverberation mixed in (0.0 to 1.0)
+    pub mix: f32,
+}
+
+impl ReverbSettings {
+    fn is_valid(&self) -> bool {
+        [self.room_size, self.damping, self.mix]
+            .iter()
+            .all(|value| (0.0..=1.0).contains(value))
+    }
+}
+
+/// Soft clipper applied to every processed frame
+///
+/// Samples up to `threshold` pass unchanged. Above it they bend smoothly
+/// towards `threshold + knee` without passing it; a `knee` of 0.0 clips hard at
+/// the threshold. See [`soft_clip`](crate::dsp::clip::soft_clip).
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SoftClip {
+    /// Level where clipping starts
+    pub threshold: f32,
+    /// Width of the region above the threshold the output is bent into
+    pub knee: f32,
+}
+
+impl SoftClip {
+    /// Starts at 0.95 and stays within full scale
+    pub const DEFAULT: SoftClip = SoftClip { threshold: 0.95, knee: 0.05 };
+
+    fn is_valid(&self) -> bool {
+        self.threshold.is_finite()
+            && self.threshold > 0.0
+            && self.knee.is_finite()
+            && self.knee >= 0.0
+    }
+}
+
+impl Default for SoftClip {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Periodic re-anchoring of the synthesis phases to the analysis phases
+///
+/// The phase vocoder accumulates synthesis phases frame after frame, and over
+/// minutes the small errors add up to a phasey, smeared sound. Once
+/// `interval_ms` has passed since the last anchor, the next frame quieter than
+/// `silence_db` re-locks the synthesis phases, where the jump is inaudible.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PhaseReanchor {
+    /// Least time between two anchors, in milliseconds
+    pub interval_ms: f32,
+    /// RMS level of the frame, in dBFS, below which it counts as silent
+    pub silence_db: f32,
+}
+
+impl PhaseReanchor {
+    fn is_valid(&self) -> bool {
+        self.interval_ms.is_finite()
+            && self.interval_ms >= 0.0
+            && self.silence_db.is_finite()
+            && self.silence_db <= 0.0
+    }
+
+    /// Least number of hops between two anchors
+    pub(crate) fn interval_hops(&self, hop_seconds: f32) -> u32 {
+        (self.interval_ms / 1000.0 / hop_seconds) as u32
+    }
+}
+
+/// Output of the engine while the voice activity gate is idle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum IdleOutput {
+    /// Silence once the tails of the last processed frames have played out
+    #[default]
+    Silence,
+    /// The unprocessed input, delayed by the latency like the dry mix
+    Dry,
+}
+
+/// Voice activity gate that stops processing while the input is silent
+///
+/// Once every input hop has stayed below `threshold_db` for `hold_ms`, and for
+/// at least a frame so the last sound has left the analysis frame, the engine
+/// skips the FFT processing altogether and outputs `idle_output`. The first hop
+/// above the threshold wakes it with cleared processing state, since the phases
+/// and pitch of the silence carry nothing worth continuing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct VoiceActivityGate {
+    /// RMS level of an input hop, in dBFS, below which it counts as silent
+    pub threshold_db: f32,
+    /// Time the input has to stay silent before processing stops, in milliseconds
+    pub hold_ms: f32,
+    /// Output while processing is stopped
+    pub idle_output: IdleOutput,
+}
+
+impl VoiceActivityGate {
+    fn is_valid(&self) -> bool {
+        self.threshold_db.is_finite()
+            && self.threshold_db <= 0.0
+            && self.hold_ms.is_finite()
+            && self.hold_ms >= 0.0
+    }
+
+    /// Returns `true` if `hop` is quiet enough to count towards the hold time
+    pub(crate) fn is_silent(&self, hop: &[f32]) -> bool {
+        let energy: f32 = hop.iter().map(|sample| sample * sample).sum();
+        energy <= hop.len() as f32 * libm::powf(10.0, self.threshold_db / 10.0)
+    }
+
+    /// Number of silent hops before processing stops
+    pub(crate) fn hold_hops(&self, hop_seconds: f32) -> usize {
+        libm::ceilf(self.hold_ms / 1000.0 / hop_seconds) as usize
+    }
+}
+
+/// Band limit on the pitch-shifted spectrum
+///
+/// Shifting up moves the top of the spectrum past Nyquist, where it is dropped,
+/// and bins just below it ring harshly. Shifted bins above `cutoff_hz` fade out
+/// with a raised cosine over `transition_hz` and are discarded beyond it; a
+/// `transition_hz` of 0.0 discards them outright.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BandLimit {
+    /// Shifted frequency in Hz above which bins are attenuated
+    pub cutoff_hz: f32,
+    /// Width of the fade above the cutoff, in Hz
+    pub transition_hz: f32,
+}
+
+impl BandLimit {
+    fn is_valid(&self, sample_rate: f32) -> bool {
+        self.cutoff_hz > 0.0
+            && self.cutoff_hz < sample_rate / 2.0
+            && self.transition_hz.is_finite()
+            && self.transition_hz >= 0.0
+    }
+
+    /// Gain of a bin shifted to `frequency_hz`
+    pub(crate) fn gain(&self, frequency_hz: f32) -> f32 {
+        let above = frequency_hz - self.cutoff_hz;
+        if above <= 0.0 {
+            1.0
+        } else if above >= self.transition_hz {
+            0.0
+        } else {
+            0.5 + 0.5 * libm::cosf(core::f32::consts::PI * above / self.transition_hz)
+        }
+    }
+}
+
+/// White-noise fill of weak carrier bins in vocode mode
+///
+/// A carrier without energy in some band (a dull pad, a bass synth) silences the
+/// modulator there, and consonants lose their intelligibility. Carrier bins more
+/// than `threshold_db` below the loudest carrier bin of the frame are replaced by
+/// noise at `level` times the modulator magnitude.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NoiseFill {
+    /// Level relative to the loudest carrier bin below which a bin is filled, in dB (negative)
+    pub threshold_db: f32,
+    /// Level of the fill relative to the modulator (0.0 to 1.0)
+    pub level: f32,
+}
+
+impl NoiseFill {
+    fn is_valid(&self) -> bool {
+        self.threshold_db.is_finite()
+            && self.threshold_db <= 0.0
+            && (0.0..=1.0).contains(&self.level)
+    }
+}
+
+/// Attack and release of the voice envelope in vocode mode
+///
+/// Without smoothing the envelope is taken afresh from every frame, and the
+/// frame-to-frame jitter of the voice spectrum gargles on a steady carrier. Each
+/// bin of the envelope rises with the attack and falls with the release instead.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct VocoderEnvelope {
+    /// Time constant of a rising bin in milliseconds
+    pub attack_ms: f32,
+    /// Time constant of a falling bin in milliseconds
+    pub release_ms: f32,
+}
+
+impl VocoderEnvelope {
+    fn is_valid(&self) -> bool {
+        [self.attack_ms, self.release_ms].iter().all(|ms| ms.is_finite() && *ms >= 0.0)
+    }
+}
+
+/// Bypass of sibilants around the vocoder
+///
+/// A carrier rarely has the noise an "s" or "t" needs, so vocoded consonants turn
+/// to mush. Frames where most of the voice energy lies above `crossover_hz` count
+/// as sibilant, and the voice above the crossover is mixed into the vocoder
+/// output at `mix`. Frames in between get part of the mix, so the bypass fades
+/// in and out with the consonant.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SibilanceBypass {
+    /// Frequency above which the voice is passed through, in Hz
+    pub crossover_hz: f32,
+    /// Level of the passed-through voice (0.0 to 1.0)
+    pub mix: f32,
+}
+
+impl SibilanceBypass {
+    fn is_valid(&self, sample_rate: f32) -> bool {
+        self.crossover_hz > 0.0
+            && self.crossover_hz < sample_rate / 2.0
+            && (0.0..=1.0).contains(&self.mix)
+    }
+
+    /// Share of the bypass mix for a frame with `high_fraction` of its energy above
+    /// the crossover, rising from 0.3 to 0.7
+    pub(crate) fn amount(&self, high_fraction: f32) -> f32 {
+        self.mix * ((high_fraction - 0.3) / 0.4).clamp(0.0, 1.0)
+    }
+}
+
+/// Ducking of the carrier while the voice sounds
+///
+/// An envelope follower on the voice drives a compressor on the carrier, so the
+/// synth blended in dry mode, or the pad under a vocoder with some dry voice
+/// mixed in, backs off while the voice is present and comes back in the gaps.
+/// Above `threshold_db` every dB the voice rises turns the carrier down by
+/// `1 - 1 / ratio` dB.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SidechainDuck {
+    /// Voice peak level above which the carrier is turned down, in dBFS
+    pub threshold_db: f32,
+    /// Compression ratio, 1.0 for none and larger for deeper ducking
+    pub ratio: f32,
+    /// Time constant of the follower on a rising voice in milliseconds
+    pub attack_ms: f32,
+    /// Time constant of the follower on a falling voice in milliseconds
+    pub release_ms: f32,
+}
+
+impl SidechainDuck {
+    fn is_valid(&self) -> bool {
+        self.threshold_db.is_finite()
+            && self.threshold_db <= 0.0
+            && self.ratio.is_finite()
+            && self.ratio >= 1.0
+            && [self.attack_ms, self.release_ms].iter().all(|ms| ms.is_finite() && *ms >= 0.0)
+    }
+}
+
+/// Breath and noise kept through the correction
+///
+/// Heavy correction resynthesises breaths and other unvoiced sounds along with
+/// the voice, and they come out thin or gone, which sounds artificial. In
+/// frames the pitch detector finds unvoiced, with a confidence below
+/// [`VocalEffectsConfig::confidence_threshold`], the original signal above
+/// `cutoff_hz` is mixed back in at `level`, fading in and out over a hop.
+/// Pair it with a detector whose confidence tells a sung note from a breath,
+/// such as [`PitchDetector::HarmonicSum`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BreathRetention {
+    /// Frequency above which the original signal is kept, in Hz
+    pub cutoff_hz: f32,
+    /// Level of the kept signal (0.0 to 1.0)
+    pub level: f32,
+}
+
+impl BreathRetention {
+    fn is_valid(&self, sample_rate: f32) -> bool {
+        self.cutoff_hz > 0.0
+            && self.cutoff_hz < sample_rate / 2.0
+            && (0.0..=1.0).contains(&self.level)
+    }
+}
+
+/// Mains frequency of the region, for hum removal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MainsFrequency {
+    /// 50 Hz mains (Europe, most of Asia, Africa and Oceania)
+    Hz50,
+    /// 60 Hz mains (the Americas, parts of Japan)
+    Hz60,
+}
+
+impl MainsFrequency {
+    /// Mains frequency in Hz
+    pub fn hz(&self) -> f32 {
+        match self {
+            MainsFrequency::Hz50 => 50.0,
+            MainsFrequency::Hz60 => 60.0,
+        }
+    }
+}
+
 /// Configuration for the vocal effects processor
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct VocalEffectsConfig {
-    /// FFT size (must be power of 2, between 512-4096)
+    /// FFT size (must be power of 2, between 128-4096, or 8192 with the `fft-8192` feature)
     pub fft_size: usize,
-    /// Hop size for overlap-add processing
+    /// Analysis hop size for overlap-add processing
     pub hop_size: usize,
     /// Sample rate in Hz
     pub sample_rate: f32,
     /// Hop ratio as fraction of FFT size (0.0625 to 0.5)
     pub hop_ratio: f32,
-    /// Speed of pitch correction transition (0.0 to 1.0)
+    /// Samples of signal in each frame when shorter than the FFT, `None` for the
+    /// whole FFT
+    ///
+    /// The rest of the FFT is zero padding in front of the signal. Latency and the
+    /// hop follow the frame size, while pitch detection and the formant envelope
+    /// see the finer bins of the full FFT: a 1024-sample frame in a 4096-point FFT
+    /// has the latency of a 1024-point engine and four times its resolution. The
+    /// hop ratio is then a fraction of the frame size. The fixed-point and
+    /// double-precision processors always fill the whole FFT.
+    pub frame_size: Option<usize>,
+    /// Synthesis hop as a fraction of the frame size (0.0625 to 0.5), `None` for the
+    /// analysis hop
+    ///
+    /// A synthesis hop longer than the analysis hop stretches the audio in time and a
+    /// shorter one compresses it, leaving the pitch alone. Autotune and dry mode carry
+    /// their phases across the stretch; the other modes take their phases from the
+    /// current frame. [`Engine::process_hop`](crate::Engine::process_hop) then takes
+    /// an analysis hop of input and returns a synthesis hop of output; the block
+    /// adapter and the other wrappers need the hops to be equal.
+    pub synthesis_hop_ratio: Option<f32>,
+    /// Speed of pitch correction transition: the weight of the new target ratio in
+    /// each frame (0.0 to 1.0, 1.0 = instant retune)
     pub transition_speed: f32,
     /// Strength of pitch correction (0.0 to 1.0, closer to 1.0 = stronger)
     pub pitch_correction_strength: f32,
@@ -18,6 +724,161 @@ pub struct VocalEffectsConfig {
     pub min_frequency: f32,
     /// Maximum frequency to process (Hz)
     pub max_frequency: f32,
+    /// Pitch detection algorithm
+    pub pitch_detector: PitchDetector,
+    /// Decimation of the frame or spectrum seen by the pitch detector
+    pub pitch_decimation: PitchDecimation,
+    /// How the engine applies the octave shift, and the semitone shift in dry mode
+    ///
+    /// With [`PitchShiftAlgorithm::ResampleStretch`] the synthesis hop follows the
+    /// ratio, rounded so the resampler can track it, and the rest of the ratio is
+    /// remapped. It needs equal analysis and synthesis hops and a synthesis hop of at
+    /// most half the frame, so use a hop ratio of 0.125 to shift up by an octave.
+    /// Corrections in autotune mode and the frame processors always remap bins.
+    pub pitch_shift_algorithm: PitchShiftAlgorithm,
+    /// Number of hops the engine crossfades over when the processing mode changes
+    /// (0 switches instantly)
+    pub mode_crossfade_hops: usize,
+    /// Cepstral lifter cutoff in samples of quefrency, overriding the default
+    /// scaled with the sample rate
+    pub lifter_cutoff_override: Option<usize>,
+    /// Hops between extractions of the formant envelope (1 = every hop)
+    ///
+    /// The cepstral envelope costs an inverse and a forward FFT, the most expensive
+    /// step with formants on. Longer intervals save that on all but one hop in so
+    /// many and interpolate in between, at the cost of formants that trail the
+    /// voice by up to the interval. Only states that keep an
+    /// [`EnvelopeCache`](crate::dsp::EnvelopeCache), such as the engine's, skip hops.
+    pub envelope_update_interval: usize,
+    /// Share of the way the formant envelope moves toward a captured
+    /// [`FormantSnapshot`](crate::dsp::FormantSnapshot) (0.0 to 1.0), off when `None`
+    ///
+    /// The envelopes are interpolated in log magnitude, with the snapshot brought
+    /// to the level of the live envelope first, so the voice takes on the vowel
+    /// colour and character of the captured one without its loudness. Applies in
+    /// autotune, dry and formant mode, before any formant shift, to states that
+    /// hold a snapshot in their [`EnvelopeCache`](crate::dsp::EnvelopeCache),
+    /// such as the engine's after [`Engine::set_formant_snapshot`](crate::Engine::set_formant_snapshot).
+    pub formant_morph: Option<f32>,
+    /// Mix of processed and latency-aligned dry signal in the [`Engine`](crate::Engine)
+    /// output (0.0 = dry, 1.0 = fully processed)
+    pub wet_dry: f32,
+    /// Slide between correction targets when the target note changes
+    pub glide: Glide,
+    /// Scoops into notes and falls at the end of phrases
+    pub ornaments: Ornaments,
+    /// Treatment of frames that contain an attack
+    pub transients: TransientHandling,
+    /// Rise in energy from one hop to the next that marks an attack, in dB
+    pub transient_threshold_db: f32,
+    /// Shift only the harmonic part of autotune and dry frames and pass the
+    /// percussive part (breaths, clicks, beatboxing) through unshifted
+    ///
+    /// Needs the frame history kept by [`ProcessingState`](crate::ProcessingState)
+    /// and the [`Engine`](crate::Engine); the `process_vocal_effects_*` functions
+    /// ignore it.
+    pub harmonic_percussive_separation: bool,
+    /// Spectral gate applied before resynthesis, off when `None`
+    ///
+    /// The gains are smoothed with the frame history of
+    /// [`ProcessingState`](crate::ProcessingState) and the [`Engine`](crate::Engine);
+    /// the `process_vocal_effects_*` functions gate without attack or release.
+    pub spectral_gate: Option<SpectralGate>,
+    /// Harmonic exciter applied to the processed signal, off when `None`
+    pub exciter: Option<ExciterSettings>,
+    /// Saturation applied to the processed signal after the exciter, off when
+    /// `None`
+    ///
+    /// With [`soft_clip`](Self::soft_clip) off, a soft-clip curve at a drive of
+    /// 1.0 makes a gentler output limiter that bends the whole waveform instead of
+    /// only the samples above a threshold.
+    pub saturation: Option<SaturationSettings>,
+    /// Factor the processed signal's sample rate is divided by after the
+    /// saturation, by holding samples, off when `None`
+    pub sample_rate_reduction: Option<u32>,
+    /// Bit depth the processed signal is quantised to after the sample rate
+    /// reduction (1 to 24), off when `None`
+    pub bitcrush: Option<u8>,
+    /// Chorus or flanger applied to the processed signal after the bitcrusher,
+    /// off when `None`
+    pub chorus: Option<ChorusSettings>,
+    /// Delay applied to the processed signal after the chorus, off when `None`
+    ///
+    /// The [`Engine`](crate::Engine) holds up to [`MAX_DELAY_SECONDS`] with
+    /// `alloc`; without it the delay runs once the firmware provides a buffer, see
+    /// [`Engine::set_delay_buffer`](crate::Engine::set_delay_buffer).
+    pub delay: Option<DelaySettings>,
+    /// Reverb applied to the processed signal after the delay, off when `None`
+    pub reverb: Option<ReverbSettings>,
+    /// Notch out mains hum and its first three harmonics from the
+    /// [`Engine`](crate::Engine) input before analysis, off when `None`
+    ///
+    /// The dry signal mixed back in and bypassed is taken after the filter, so
+    /// it is free of hum as well.
+    pub hum_filter: Option<MainsFrequency>,
+    /// Coefficient of the first-order pre-emphasis the [`Engine`](crate::Engine)
+    /// applies to each analysis frame, undone by a matching de-emphasis of its
+    /// output (0.0 to below 1.0, typically 0.9 to 0.97), off when `None`
+    ///
+    /// Flattens the spectral tilt of low voices so the cepstral envelope follows
+    /// the upper formants. The pitch detector sees the emphasised spectrum as
+    /// well, where the fundamental is no longer the loudest bin; pair it with
+    /// [`PitchDetector::HarmonicProduct`] or [`PitchDetector::Autocorrelation`].
+    pub pre_emphasis: Option<f32>,
+    /// Output protection applied to every processed frame in all modes, off when `None`
+    pub soft_clip: Option<SoftClip>,
+    /// Re-anchoring of the synthesis phases in quiet frames, off when `None`
+    ///
+    /// Applies to callers that keep a [`ProcessingState`](crate::ProcessingState):
+    /// the [`Engine`](crate::Engine) and
+    /// [`process_frame`](crate::vocal_effects::process_frame).
+    pub phase_reanchor: Option<PhaseReanchor>,
+    /// Voice activity gate that skips processing during silence, off when `None`
+    ///
+    /// Only the [`Engine`](crate::Engine) gates, since it keeps the overlap-add
+    /// history the idle output plays out from.
+    pub voice_activity_gate: Option<VoiceActivityGate>,
+    /// Band limit on the pitch-shifted spectrum in autotune and dry mode; without
+    /// it only bins shifted past Nyquist are discarded
+    pub band_limit: Option<BandLimit>,
+    /// Treatment of frames whose pitch confidence is below `confidence_threshold`
+    pub low_confidence: LowConfidence,
+    /// Pitch confidence below which a frame counts as unpitched (0.0 to 1.0, see
+    /// [`PitchEstimate::confidence`](crate::dsp::PitchEstimate::confidence))
+    pub confidence_threshold: f32,
+    /// Notes automatic correction snaps to instead of the key or chord, e.g. from a
+    /// Scala file; held notes still come from the key
+    pub tuning: Option<Tuning>,
+    /// Noise fill of weak carrier bins in vocode mode, off when `None`
+    pub noise_fill: Option<NoiseFill>,
+    /// Smoothing of the voice envelope in vocode mode, off when `None`
+    ///
+    /// Talk-box mode always smooths its envelope and uses this in place of its
+    /// default when set.
+    ///
+    /// Applies to callers that keep a [`ProcessingState`](crate::ProcessingState).
+    pub vocoder_envelope: Option<VocoderEnvelope>,
+    /// Pass-through of sibilants around the vocoder, off when `None`
+    pub sibilance_bypass: Option<SibilanceBypass>,
+    /// Ducking of the carrier by the voice, off when `None`
+    ///
+    /// In autotune and dry mode the synth input is ducked before it is blended
+    /// in. Vocode and talk-box mode output the carrier itself, shaped by the
+    /// voice, so their output is ducked instead, which lets the dry share of
+    /// [`wet_dry`](Self::wet_dry) through. Only the [`Engine`](crate::Engine)
+    /// ducks, since the follower runs on the continuous input stream.
+    pub sidechain_duck: Option<SidechainDuck>,
+    /// Original breath and noise mixed back into unvoiced frames in autotune
+    /// mode, off when `None`
+    ///
+    /// Only the [`Engine`](crate::Engine) keeps breaths, since it holds the
+    /// latency-aligned original signal.
+    pub breath_retention: Option<BreathRetention>,
+    /// Mix the dry-mode synth input without the analysis window
+    ///
+    /// The synth is scaled by the hop ratio instead, so the overlapping frames add
+    /// up to exactly its input level and it never swells and dips with the window.
+    pub unwindowed_synth: bool,
 }
 
 impl Default for VocalEffectsConfig {
@@ -27,37 +888,86 @@ impl Default for VocalEffectsConfig {
             hop_size: 256, // Will be calculated from hop_ratio
             sample_rate: 48000.0,
             hop_ratio: 0.25,
-            transition_speed: 0.1,
+            frame_size: None,
+            synthesis_hop_ratio: None,
+            transition_speed: 0.99,
             pitch_correction_strength: 0.999,
             min_frequency: 50.0,
             max_frequency: 4000.0,
+            pitch_detector: PitchDetector::PeakBin,
+            pitch_decimation: PitchDecimation::None,
+            pitch_shift_algorithm: PitchShiftAlgorithm::BinRemap,
+            mode_crossfade_hops: 4,
+            lifter_cutoff_override: None,
+            envelope_update_interval: 1,
+            formant_morph: None,
+            wet_dry: 1.0,
+            glide: Glide::Off,
+            ornaments: Ornaments::NONE,
+            transients: TransientHandling::Off,
+            transient_threshold_db: 9.0,
+            harmonic_percussive_separation: false,
+            spectral_gate: None,
+            exciter: None,
+            saturation: None,
+            sample_rate_reduction: None,
+            bitcrush: None,
+            chorus: None,
+            delay: None,
+            reverb: None,
+            hum_filter: None,
+            pre_emphasis: None,
+            soft_clip: Some(SoftClip::DEFAULT),
+            phase_reanchor: None,
+            voice_activity_gate: None,
+            band_limit: None,
+            low_confidence: LowConfidence::Correct,
+            confidence_threshold: 0.5,
+            tuning: None,
+            noise_fill: None,
+            vocoder_envelope: None,
+            sibilance_bypass: None,
+            sidechain_duck: None,
+            breath_retention: None,
+            unwindowed_synth: false,
         }
     }
 }
 
 impl VocalEffectsConfig {
+    /// Starts building a validated configuration from the defaults
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use synthphone_e_vocal_dsp::{PitchDetector, VocalEffectsConfig};
+    ///
+    /// let config = VocalEffectsConfig::builder()
+    ///     .sample_rate(44100.0)
+    ///     .hop_ratio(0.125)
+    ///     .retune_speed(0.5)
+    ///     .pitch_detector(PitchDetector::HarmonicProduct)
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(config.hop_size, 128);
+    /// ```
+    pub fn builder() -> VocalEffectsConfigBuilder {
+        VocalEffectsConfigBuilder::default()
+    }
+
     /// Create a new configuration with validation
     pub fn new(
         fft_size: usize,
         sample_rate: f32,
         hop_ratio: f32,
     ) -> Result<Self, crate::VocalEffectsError> {
-        if !fft_size.is_power_of_two() {
-            return Err(crate::VocalEffectsError::InvalidConfiguration);
-        }
-        if !(512..=4096).contains(&fft_size) {
-            return Err(crate::VocalEffectsError::InvalidConfiguration);
-        }
-        if sample_rate <= 0.0 {
-            return Err(crate::VocalEffectsError::InvalidConfiguration);
-        }
-        if !(0.0625..=0.5).contains(&hop_ratio) {
-            return Err(crate::VocalEffectsError::InvalidConfiguration);
-        }
-
-        let hop_size = (fft_size as f32 * hop_ratio) as usize;
-
-        Ok(Self { fft_size, hop_size, sample_rate, hop_ratio, ..Default::default() })
+        let config = Self::builder()
+            .fft_size(fft_size)
+            .sample_rate(sample_rate)
+            .hop_ratio(hop_ratio)
+            .build()
+            .map_err(|_| crate::VocalEffectsError::InvalidConfiguration)?;
+        Ok(config)
     }
 
     /// Update hop ratio and recalculate hop size
@@ -66,17 +976,739 @@ impl VocalEffectsConfig {
             return Err(crate::VocalEffectsError::InvalidConfiguration);
         }
         self.hop_ratio = hop_ratio;
-        self.hop_size = (self.fft_size as f32 * hop_ratio) as usize;
+        self.hop_size = self.hop_size_for(self.fft_size);
         Ok(())
     }
 
+    /// Samples of signal in each frame of an `fft_size`-point FFT
+    pub fn frame_size_for(&self, fft_size: usize) -> usize {
+        self.frame_size.map_or(fft_size, |frame_size| frame_size.clamp(1, fft_size))
+    }
+
+    /// Hop size for an `fft_size`-point FFT, a fraction of the frame size
+    pub fn hop_size_for(&self, fft_size: usize) -> usize {
+        (self.frame_size_for(fft_size) as f32 * self.hop_ratio) as usize
+    }
+
+    /// Synthesis hop size for an `fft_size`-point FFT, rounded to the nearest sample
+    pub fn synthesis_hop_size_for(&self, fft_size: usize) -> usize {
+        match self.synthesis_hop_ratio {
+            Some(ratio) => libm::roundf(self.frame_size_for(fft_size) as f32 * ratio) as usize,
+            None => self.hop_size_for(fft_size),
+        }
+    }
+
     /// Get the bin width in Hz
     pub fn bin_width(&self) -> f32 {
         self.sample_rate / self.fft_size as f32
     }
 
+    /// Get the cepstral lifter cutoff in samples of quefrency.
+    ///
+    /// The envelope smoothing is tuned for 64 coefficients at 48 kHz (1.33 ms). The
+    /// cutoff is scaled with the sample rate so the envelope resolution is the same
+    /// at 32, 44.1, 88.2 or 96 kHz.
+    /// [`VocalEffectsConfig::lifter_cutoff_override`] takes precedence when set.
+    pub fn lifter_cutoff(&self) -> usize {
+        if let Some(cutoff) = self.lifter_cutoff_override {
+            return cutoff;
+        }
+        const REFERENCE_CUTOFF: f32 = 64.0;
+        const REFERENCE_SAMPLE_RATE: f32 = 48000.0;
+        ((REFERENCE_CUTOFF * self.sample_rate / REFERENCE_SAMPLE_RATE + 0.5) as usize).max(1)
+    }
+
+    /// Get the algorithmic latency in samples.
+    ///
+    /// A sample has to travel through a full analysis frame before its overlap-add
+    /// output is complete, minus the hop that is emitted as soon as it is ready.
+    /// Zero padding adds no latency, so this follows the frame size. With a
+    /// separate synthesis hop it is counted in output samples.
+    pub fn latency_samples(&self) -> usize {
+        let synthesis_hop = match self.synthesis_hop_ratio {
+            Some(_) => self.synthesis_hop_size_for(self.fft_size),
+            None => self.hop_size,
+        };
+        self.frame_size_for(self.fft_size).saturating_sub(synthesis_hop)
+    }
+
     /// Get the spectrum size (FFT size / 2)
     pub fn spectrum_size(&self) -> usize {
         self.fft_size / 2
     }
 }
+
+/// Largest FFT size with a built-in backend
+#[cfg(not(feature = "fft-8192"))]
+pub(crate) const MAX_FFT_SIZE: usize = 4096;
+#[cfg(feature = "fft-8192")]
+pub(crate) const MAX_FFT_SIZE: usize = 8192;
+
+/// Builder for a validated [`VocalEffectsConfig`]
+///
+/// Unset parameters keep their [`Default`] values. The hop size is derived from
+/// the FFT size and hop ratio in [`VocalEffectsConfigBuilder::build`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VocalEffectsConfigBuilder {
+    config: VocalEffectsConfig,
+}
+
+impl VocalEffectsConfigBuilder {
+    /// FFT size (power of two, 128 to 4096, or 8192 with the `fft-8192` feature).
+    /// Engines take it from their type.
+    pub fn fft_size(mut self, fft_size: usize) -> Self {
+        self.config.fft_size = fft_size;
+        self
+    }
+
+    /// Sample rate in Hz
+    pub fn sample_rate(mut self, sample_rate: f32) -> Self {
+        self.config.sample_rate = sample_rate;
+        self
+    }
+
+    /// Hop ratio as fraction of the FFT size (0.0625 to 0.5)
+    pub fn hop_ratio(mut self, hop_ratio: f32) -> Self {
+        self.config.hop_ratio = hop_ratio;
+        self
+    }
+
+    /// Synthesis hop as a fraction of the frame size (0.0625 to 0.5), stretching
+    /// the audio by its ratio to the analysis hop
+    pub fn synthesis_hop_ratio(mut self, ratio: f32) -> Self {
+        self.config.synthesis_hop_ratio = Some(ratio);
+        self
+    }
+
+    /// Zero-pads frames of `frame_size` samples to the FFT size (64 up to the FFT
+    /// size)
+    pub fn frame_size(mut self, frame_size: usize) -> Self {
+        self.config.frame_size = Some(frame_size);
+        self
+    }
+
+    /// Weight of the new target ratio per frame (0.0 to 1.0, 1.0 = instant retune)
+    pub fn retune_speed(mut self, retune_speed: f32) -> Self {
+        self.config.transition_speed = retune_speed;
+        self
+    }
+
+    /// Strength of pitch correction (0.0 to 1.0)
+    pub fn correction_strength(mut self, strength: f32) -> Self {
+        self.config.pitch_correction_strength = strength;
+        self
+    }
+
+    /// Range of detected frequencies that are corrected, in Hz
+    pub fn frequency_range(mut self, min_frequency: f32, max_frequency: f32) -> Self {
+        self.config.min_frequency = min_frequency;
+        self.config.max_frequency = max_frequency;
+        self
+    }
+
+    /// Pitch detection algorithm
+    pub fn pitch_detector(mut self, detector: PitchDetector) -> Self {
+        self.config.pitch_detector = detector;
+        self
+    }
+
+    /// Decimation of the frame or spectrum seen by the pitch detector
+    pub fn pitch_decimation(mut self, decimation: PitchDecimation) -> Self {
+        self.config.pitch_decimation = decimation;
+        self
+    }
+
+    /// How the engine applies fixed pitch shifts
+    pub fn pitch_shift_algorithm(mut self, algorithm: PitchShiftAlgorithm) -> Self {
+        self.config.pitch_shift_algorithm = algorithm;
+        self
+    }
+
+    /// Number of hops a mode change is crossfaded over
+    pub fn mode_crossfade_hops(mut self, hops: usize) -> Self {
+        self.config.mode_crossfade_hops = hops;
+        self
+    }
+
+    /// Fixed cepstral lifter cutoff in samples of quefrency
+    pub fn lifter_cutoff(mut self, cutoff: usize) -> Self {
+        self.config.lifter_cutoff_override = Some(cutoff);
+        self
+    }
+
+    /// Hops between extractions of the formant envelope
+    pub fn envelope_update_interval(mut self, hops: usize) -> Self {
+        self.config.envelope_update_interval = hops;
+        self
+    }
+
+    /// Morph the formant envelope `amount` of the way toward a captured snapshot
+    pub fn formant_morph(mut self, amount: f32) -> Self {
+        self.config.formant_morph = Some(amount);
+        self
+    }
+
+    /// Mix of processed and dry signal (0.0 = dry, 1.0 = fully processed)
+    pub fn wet_dry(mut self, wet_dry: f32) -> Self {
+        self.config.wet_dry = wet_dry;
+        self
+    }
+
+    /// Slide between correction targets when the target note changes
+    pub fn glide(mut self, glide: Glide) -> Self {
+        self.config.glide = glide;
+        self
+    }
+
+    /// Start notes `cents` below their target and slide up over `ms` milliseconds
+    pub fn scoop(mut self, cents: f32, ms: f32) -> Self {
+        self.config.ornaments.scoop_cents = cents;
+        self.config.ornaments.scoop_ms = ms;
+        self
+    }
+
+    /// Drop the pitch by `cents` over `ms` milliseconds at the end of a phrase
+    pub fn fall(mut self, cents: f32, ms: f32) -> Self {
+        self.config.ornaments.fall_cents = cents;
+        self.config.ornaments.fall_ms = ms;
+        self
+    }
+
+    /// Treatment of frames whose energy rises by more than `threshold_db` from one
+    /// hop to the next
+    pub fn transients(mut self, handling: TransientHandling, threshold_db: f32) -> Self {
+        self.config.transients = handling;
+        self.config.transient_threshold_db = threshold_db;
+        self
+    }
+
+    /// Treatment of frames whose pitch confidence is below `threshold` (0.0 to 1.0)
+    pub fn confidence_gate(mut self, handling: LowConfidence, threshold: f32) -> Self {
+        self.config.low_confidence = handling;
+        self.config.confidence_threshold = threshold;
+        self
+    }
+
+    /// Snap automatic correction to a custom tuning instead of the key
+    pub fn tuning(mut self, tuning: Tuning) -> Self {
+        self.config.tuning = Some(tuning);
+        self
+    }
+
+    /// Shift only the harmonic part and pass the percussive part through
+    pub fn harmonic_percussive_separation(mut self, enabled: bool) -> Self {
+        self.config.harmonic_percussive_separation = enabled;
+        self
+    }
+
+    /// Gate bins more than `threshold_db` below the loudest bin, with per-bin
+    /// attack and release times in milliseconds
+    pub fn spectral_gate(mut self, threshold_db: f32, attack_ms: f32, release_ms: f32) -> Self {
+        self.config.spectral_gate = Some(SpectralGate { threshold_db, attack_ms, release_ms });
+        self
+    }
+
+    /// Generate harmonics above `crossover_hz` with `drive` and mix them in at `mix`
+    pub fn exciter(mut self, crossover_hz: f32, drive: f32, mix: f32) -> Self {
+        self.config.exciter = Some(ExciterSettings { crossover_hz, drive, mix });
+        self
+    }
+
+    /// Drive the processed signal by `drive` into `curve` and mix the result in at
+    /// `mix`, at twice the sample rate if `oversample`
+    pub fn saturation(
+        mut self,
+        curve: SaturationCurve,
+        drive: f32,
+        mix: f32,
+        oversample: bool,
+    ) -> Self {
+        self.config.saturation = Some(SaturationSettings { curve, drive, mix, oversample });
+        self
+    }
+
+    /// Hold every `factor`th sample of the processed signal
+    pub fn sample_rate_reduction(mut self, factor: u32) -> Self {
+        self.config.sample_rate_reduction = Some(factor);
+        self
+    }
+
+    /// Quantise the processed signal to `bit_depth` bits
+    pub fn bitcrush(mut self, bit_depth: u8) -> Self {
+        self.config.bitcrush = Some(bit_depth);
+        self
+    }
+
+    /// Mix in the signal delayed by `delay_ms`, swept by `depth_ms` at `rate`,
+    /// with `feedback`
+    pub fn chorus(
+        mut self,
+        rate: ModulationRate,
+        delay_ms: f32,
+        depth_ms: f32,
+        feedback: f32,
+        mix: f32,
+    ) -> Self {
+        self.config.chorus = Some(ChorusSettings { rate, delay_ms, depth_ms, feedback, mix });
+        self
+    }
+
+    /// Echo the processed signal after `time`, feeding `feedback` of each echo
+    /// back, darkened by `damping`, and mix the echoes in at `mix`
+    pub fn delay(mut self, time: DelayTime, feedback: f32, damping: f32, mix: f32) -> Self {
+        self.config.delay = Some(DelaySettings { time, feedback, damping, mix });
+        self
+    }
+
+    /// Add reverberation of `room_size`, darkened by `damping`, at `mix`
+    pub fn reverb(mut self, room_size: f32, damping: f32, mix: f32) -> Self {
+        self.config.reverb = Some(ReverbSettings { room_size, damping, mix });
+        self
+    }
+
+    /// Remove hum at the mains frequency and its harmonics from the input
+    pub fn hum_filter(mut self, mains: MainsFrequency) -> Self {
+        self.config.hum_filter = Some(mains);
+        self
+    }
+
+    /// Pre-emphasise the analysis frames with `coefficient` and de-emphasise the output
+    pub fn pre_emphasis(mut self, coefficient: f32) -> Self {
+        self.config.pre_emphasis = Some(coefficient);
+        self
+    }
+
+    /// Soft-clip the processed frames from `threshold`, bending into a `knee` above it
+    pub fn soft_clip(mut self, threshold: f32, knee: f32) -> Self {
+        self.config.soft_clip = Some(SoftClip { threshold, knee });
+        self
+    }
+
+    /// Leave the processed frames unclipped, for offline rendering with headroom
+    pub fn disable_soft_clip(mut self) -> Self {
+        self.config.soft_clip = None;
+        self
+    }
+
+    /// Re-anchor the synthesis phases in the first frame below `silence_db` once
+    /// `interval_ms` has passed since the last anchor
+    pub fn phase_reanchor(mut self, interval_ms: f32, silence_db: f32) -> Self {
+        self.config.phase_reanchor = Some(PhaseReanchor { interval_ms, silence_db });
+        self
+    }
+
+    /// Stop processing once the input has stayed below `threshold_db` for
+    /// `hold_ms`, outputting `idle_output` instead
+    pub fn voice_activity_gate(
+        mut self,
+        threshold_db: f32,
+        hold_ms: f32,
+        idle_output: IdleOutput,
+    ) -> Self {
+        self.config.voice_activity_gate =
+            Some(VoiceActivityGate { threshold_db, hold_ms, idle_output });
+        self
+    }
+
+    /// Fade out shifted bins above `cutoff_hz` over `transition_hz`
+    pub fn band_limit(mut self, cutoff_hz: f32, transition_hz: f32) -> Self {
+        self.config.band_limit = Some(BandLimit { cutoff_hz, transition_hz });
+        self
+    }
+
+    /// Fill carrier bins more than `threshold_db` below the loudest one with noise
+    /// at `level` times the modulator in vocode mode
+    pub fn noise_fill(mut self, threshold_db: f32, level: f32) -> Self {
+        self.config.noise_fill = Some(NoiseFill { threshold_db, level });
+        self
+    }
+
+    /// Let each bin of the voice envelope rise with `attack_ms` and fall with
+    /// `release_ms` in vocode mode
+    pub fn vocoder_envelope(mut self, attack_ms: f32, release_ms: f32) -> Self {
+        self.config.vocoder_envelope = Some(VocoderEnvelope { attack_ms, release_ms });
+        self
+    }
+
+    /// Pass the voice above `crossover_hz` around the vocoder at `mix` in sibilant
+    /// frames
+    pub fn sibilance_bypass(mut self, crossover_hz: f32, mix: f32) -> Self {
+        self.config.sibilance_bypass = Some(SibilanceBypass { crossover_hz, mix });
+        self
+    }
+
+    /// Duck the carrier by `ratio` while the voice peaks above `threshold_db`,
+    /// following the voice with `attack_ms` and `release_ms`
+    pub fn sidechain_duck(
+        mut self,
+        threshold_db: f32,
+        ratio: f32,
+        attack_ms: f32,
+        release_ms: f32,
+    ) -> Self {
+        self.config.sidechain_duck =
+            Some(SidechainDuck { threshold_db, ratio, attack_ms, release_ms });
+        self
+    }
+
+    /// Mix the original signal above `cutoff_hz` back in at `level` in unvoiced
+    /// frames
+    pub fn breath_retention(mut self, cutoff_hz: f32, level: f32) -> Self {
+        self.config.breath_retention = Some(BreathRetention { cutoff_hz, level });
+        self
+    }
+
+    /// Mix the dry-mode synth input without the analysis window
+    pub fn unwindowed_synth(mut self, enabled: bool) -> Self {
+        self.config.unwindowed_synth = enabled;
+        self
+    }
+
+    /// Validates the parameters and derives the hop size
+    ///
+    /// # Errors
+    ///
+    /// Returns the [`ConfigError`] of the first parameter out of range.
+    pub fn build(self) -> Result<VocalEffectsConfig, ConfigError> {
+        let mut config = self.config;
+        if !config.fft_size.is_power_of_two() || !(128..=MAX_FFT_SIZE).contains(&config.fft_size) {
+            return Err(ConfigError::UnsupportedFftSize);
+        }
+        if !(config.sample_rate.is_finite() && config.sample_rate > 0.0) {
+            return Err(ConfigError::InvalidSampleRate);
+        }
+        if !(0.0625..=0.5).contains(&config.hop_ratio)
+            || config.synthesis_hop_ratio.is_some_and(|ratio| !(0.0625..=0.5).contains(&ratio))
+        {
+            return Err(ConfigError::InvalidHopRatio);
+        }
+        if config
+            .frame_size
+            .is_some_and(|frame_size| !(64..=config.fft_size).contains(&frame_size))
+        {
+            return Err(ConfigError::InvalidFrameSize);
+        }
+        if config.pitch_shift_algorithm == PitchShiftAlgorithm::ResampleStretch
+            && config.synthesis_hop_ratio.is_some()
+        {
+            return Err(ConfigError::InvalidPitchShiftAlgorithm);
+        }
+        if !(0.0..=1.0).contains(&config.transition_speed)
+            || !(0.0..=1.0).contains(&config.pitch_correction_strength)
+        {
+            return Err(ConfigError::InvalidRetuneSpeed);
+        }
+        if !(config.min_frequency >= 0.0
+            && config.min_frequency < config.max_frequency
+            && config.max_frequency <= config.sample_rate / 2.0)
+        {
+            return Err(ConfigError::InvalidFrequencyRange);
+        }
+        if config
+            .lifter_cutoff_override
+            .is_some_and(|cutoff| cutoff == 0 || cutoff > config.fft_size / 2)
+        {
+            return Err(ConfigError::InvalidLifterCutoff);
+        }
+        if config.envelope_update_interval == 0 {
+            return Err(ConfigError::InvalidEnvelopeUpdateInterval);
+        }
+        if config.formant_morph.is_some_and(|amount| !(0.0..=1.0).contains(&amount)) {
+            return Err(ConfigError::InvalidFormantMorph);
+        }
+        if !(0.0..=1.0).contains(&config.wet_dry) {
+            return Err(ConfigError::InvalidWetDry);
+        }
+        if !config.glide.is_valid() {
+            return Err(ConfigError::InvalidGlide);
+        }
+        if !config.ornaments.is_valid() {
+            return Err(ConfigError::InvalidOrnament);
+        }
+        if !(config.transient_threshold_db.is_finite() && config.transient_threshold_db > 0.0) {
+            return Err(ConfigError::InvalidTransientThreshold);
+        }
+        if config.spectral_gate.is_some_and(|gate| !gate.is_valid()) {
+            return Err(ConfigError::InvalidSpectralGate);
+        }
+        if config.exciter.is_some_and(|exciter| !exciter.is_valid(config.sample_rate)) {
+            return Err(ConfigError::InvalidExciter);
+        }
+        if config
+            .pre_emphasis
+            .is_some_and(|coefficient| !(0.0..1.0).contains(&coefficient))
+        {
+            return Err(ConfigError::InvalidPreEmphasis);
+        }
+        if config.soft_clip.is_some_and(|clip| !clip.is_valid()) {
+            return Err(ConfigError::InvalidSoftClip);
+        }
+        if config.phase_reanchor.is_some_and(|reanchor| !reanchor.is_valid()) {
+            return Err(ConfigError::InvalidPhaseReanchor);
+        }
+        if config.voice_activity_gate.is_some_and(|gate| !gate.is_valid()) {
+            return Err(ConfigError::InvalidVoiceActivityGate);
+        }
+        if config.band_limit.is_some_and(|limit| !limit.is_valid(config.sample_rate)) {
+            return Err(ConfigError::InvalidBandLimit);
+        }
+        if !(0.0..=1.0).contains(&config.confidence_threshold) {
+            return Err(ConfigError::InvalidConfidenceThreshold);
+        }
+        if config.noise_fill.is_some_and(|fill| !fill.is_valid()) {
+            return Err(ConfigError::InvalidNoiseFill);
+        }
+        if config.vocoder_envelope.is_some_and(|envelope| !envelope.is_valid()) {
+            return Err(ConfigError::InvalidVocoderEnvelope);
+        }
+        if config
+            .sibilance_bypass
+            .is_some_and(|bypass| !bypass.is_valid(config.sample_rate))
+        {
+            return Err(ConfigError::InvalidSibilanceBypass);
+        }
+        if config.sidechain_duck.is_some_and(|duck| !duck.is_valid()) {
+            return Err(ConfigError::InvalidSidechainDuck);
+        }
+        if config
+            .breath_retention
+            .is_some_and(|breath| !breath.is_valid(config.sample_rate))
+        {
+            return Err(ConfigError::InvalidBreathRetention);
+        }
+        if config.saturation.is_some_and(|saturation| !saturation.is_valid()) {
+            return Err(ConfigError::InvalidSaturation);
+        }
+        if let Some(factor) = config.sample_rate_reduction {
+            SampleRateReducer::new(factor)?;
+        }
+        if let Some(bit_depth) = config.bitcrush {
+            Bitcrusher::new(bit_depth)?;
+        }
+        if config.chorus.is_some_and(|chorus| !chorus.is_valid()) {
+            return Err(ConfigError::InvalidChorus);
+        }
+        if config.delay.is_some_and(|delay| !delay.is_valid()) {
+            return Err(ConfigError::InvalidDelay);
+        }
+        if config.reverb.is_some_and(|reverb| !reverb.is_valid()) {
+            return Err(ConfigError::InvalidReverb);
+        }
+
+        config.hop_size = config.hop_size_for(config.fft_size);
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_samples() {
+        assert_eq!(VocalEffectsConfig::default().latency_samples(), 768);
+
+        let config = VocalEffectsConfig::new(2048, 44100.0, 0.125).unwrap();
+        assert_eq!(config.hop_size, 256);
+        assert_eq!(config.latency_samples(), 1792);
+    }
+
+    #[test]
+    fn test_lifter_cutoff_scales_with_sample_rate() {
+        let cutoff = |rate| VocalEffectsConfig::new(1024, rate, 0.25).unwrap().lifter_cutoff();
+        assert_eq!(cutoff(48000.0), 64);
+        assert_eq!(cutoff(96000.0), 128);
+        assert_eq!(cutoff(88200.0), 118);
+        assert_eq!(cutoff(44100.0), 59);
+        assert_eq!(cutoff(32000.0), 43);
+    }
+
+    #[test]
+    fn test_builder_defaults_match_default() {
+        assert_eq!(VocalEffectsConfig::builder().build(), Ok(VocalEffectsConfig::default()));
+    }
+
+    #[test]
+    fn test_builder_sets_parameters() {
+        let config = VocalEffectsConfig::builder()
+            .fft_size(2048)
+            .sample_rate(96000.0)
+            .hop_ratio(0.125)
+            .retune_speed(0.25)
+            .frequency_range(80.0, 1000.0)
+            .pitch_detector(PitchDetector::HarmonicProduct)
+            .lifter_cutoff(40)
+            .wet_dry(0.5)
+            .glide(Glide::Time(80.0))
+            .scoop(100.0, 60.0)
+            .build()
+            .unwrap();
+        assert_eq!(config.hop_size, 256);
+        assert_eq!(config.transition_speed, 0.25);
+        assert_eq!(config.pitch_detector, PitchDetector::HarmonicProduct);
+        assert_eq!(config.lifter_cutoff(), 40);
+        assert_eq!(config.wet_dry, 0.5);
+        assert_eq!(config.glide, Glide::Time(80.0));
+        assert_eq!(config.ornaments.scoop_cents, 100.0);
+        assert_eq!(config.ornaments.fall_cents, 0.0);
+    }
+
+    #[test]
+    fn test_builder_rejects_invalid_parameters() {
+        let builder = VocalEffectsConfig::builder;
+        assert_eq!(builder().fft_size(1000).build(), Err(ConfigError::UnsupportedFftSize));
+        #[cfg(not(feature = "fft-8192"))]
+        assert_eq!(builder().fft_size(8192).build(), Err(ConfigError::UnsupportedFftSize));
+        assert_eq!(builder().fft_size(16384).build(), Err(ConfigError::UnsupportedFftSize));
+        assert_eq!(builder().fft_size(64).build(), Err(ConfigError::UnsupportedFftSize));
+        assert_eq!(builder().sample_rate(0.0).build(), Err(ConfigError::InvalidSampleRate));
+        assert_eq!(builder().sample_rate(f32::NAN).build(), Err(ConfigError::InvalidSampleRate));
+        assert_eq!(builder().hop_ratio(0.75).build(), Err(ConfigError::InvalidHopRatio));
+        assert_eq!(builder().hop_ratio(f32::NAN).build(), Err(ConfigError::InvalidHopRatio));
+        assert_eq!(builder().synthesis_hop_ratio(0.6).build(), Err(ConfigError::InvalidHopRatio));
+        assert_eq!(builder().frame_size(32).build(), Err(ConfigError::InvalidFrameSize));
+        assert_eq!(builder().frame_size(2048).build(), Err(ConfigError::InvalidFrameSize));
+        assert_eq!(
+            builder()
+                .synthesis_hop_ratio(0.125)
+                .pitch_shift_algorithm(PitchShiftAlgorithm::ResampleStretch)
+                .build(),
+            Err(ConfigError::InvalidPitchShiftAlgorithm)
+        );
+        assert_eq!(builder().retune_speed(1.5).build(), Err(ConfigError::InvalidRetuneSpeed));
+        assert_eq!(
+            builder().frequency_range(500.0, 100.0).build(),
+            Err(ConfigError::InvalidFrequencyRange)
+        );
+        assert_eq!(builder().sample_rate(6000.0).build(), Err(ConfigError::InvalidFrequencyRange));
+        assert_eq!(builder().lifter_cutoff(0).build(), Err(ConfigError::InvalidLifterCutoff));
+        assert_eq!(builder().lifter_cutoff(513).build(), Err(ConfigError::InvalidLifterCutoff));
+        assert_eq!(
+            builder().envelope_update_interval(0).build(),
+            Err(ConfigError::InvalidEnvelopeUpdateInterval)
+        );
+        assert_eq!(builder().formant_morph(1.5).build(), Err(ConfigError::InvalidFormantMorph));
+        assert_eq!(
+            builder().formant_morph(f32::NAN).build(),
+            Err(ConfigError::InvalidFormantMorph)
+        );
+        assert_eq!(builder().wet_dry(-0.1).build(), Err(ConfigError::InvalidWetDry));
+        assert_eq!(builder().glide(Glide::Time(-1.0)).build(), Err(ConfigError::InvalidGlide));
+        assert_eq!(builder().glide(Glide::Rate(0.0)).build(), Err(ConfigError::InvalidGlide));
+        assert_eq!(builder().glide(Glide::Rate(f32::NAN)).build(), Err(ConfigError::InvalidGlide));
+        assert_eq!(builder().scoop(-50.0, 60.0).build(), Err(ConfigError::InvalidOrnament));
+        assert_eq!(builder().fall(200.0, f32::INFINITY).build(), Err(ConfigError::InvalidOrnament));
+        assert_eq!(
+            builder().transients(TransientHandling::PhaseReset, 0.0).build(),
+            Err(ConfigError::InvalidTransientThreshold)
+        );
+        assert_eq!(
+            builder().spectral_gate(6.0, 1.0, 50.0).build(),
+            Err(ConfigError::InvalidSpectralGate)
+        );
+        assert_eq!(
+            builder().spectral_gate(-40.0, -1.0, 50.0).build(),
+            Err(ConfigError::InvalidSpectralGate)
+        );
+        assert_eq!(builder().exciter(30000.0, 4.0, 0.2).build(), Err(ConfigError::InvalidExciter));
+        assert_eq!(builder().exciter(3000.0, 4.0, 1.5).build(), Err(ConfigError::InvalidExciter));
+        assert_eq!(builder().pre_emphasis(1.0).build(), Err(ConfigError::InvalidPreEmphasis));
+        assert_eq!(builder().soft_clip(0.0, 0.05).build(), Err(ConfigError::InvalidSoftClip));
+        assert_eq!(builder().soft_clip(0.9, -0.1).build(), Err(ConfigError::InvalidSoftClip));
+        assert_eq!(builder().disable_soft_clip().build().unwrap().soft_clip, None);
+        assert_eq!(
+            builder().phase_reanchor(-1.0, -60.0).build(),
+            Err(ConfigError::InvalidPhaseReanchor)
+        );
+        assert_eq!(
+            builder().phase_reanchor(500.0, 6.0).build(),
+            Err(ConfigError::InvalidPhaseReanchor)
+        );
+        assert_eq!(
+            builder().voice_activity_gate(-50.0, -1.0, IdleOutput::Silence).build(),
+            Err(ConfigError::InvalidVoiceActivityGate)
+        );
+        assert_eq!(
+            builder().voice_activity_gate(3.0, 200.0, IdleOutput::Dry).build(),
+            Err(ConfigError::InvalidVoiceActivityGate)
+        );
+        assert_eq!(
+            builder().confidence_gate(LowConfidence::Hold, 1.5).build(),
+            Err(ConfigError::InvalidConfidenceThreshold)
+        );
+        assert_eq!(builder().noise_fill(3.0, 0.5).build(), Err(ConfigError::InvalidNoiseFill));
+        assert_eq!(builder().noise_fill(-40.0, 2.0).build(), Err(ConfigError::InvalidNoiseFill));
+        assert_eq!(
+            builder().vocoder_envelope(-1.0, 50.0).build(),
+            Err(ConfigError::InvalidVocoderEnvelope)
+        );
+        assert_eq!(
+            builder().sibilance_bypass(30000.0, 0.5).build(),
+            Err(ConfigError::InvalidSibilanceBypass)
+        );
+        assert_eq!(
+            builder().sibilance_bypass(4000.0, -0.5).build(),
+            Err(ConfigError::InvalidSibilanceBypass)
+        );
+        assert_eq!(
+            builder().sidechain_duck(-30.0, 0.5, 5.0, 200.0).build(),
+            Err(ConfigError::InvalidSidechainDuck)
+        );
+        assert_eq!(
+            builder().sidechain_duck(-30.0, 4.0, 5.0, -1.0).build(),
+            Err(ConfigError::InvalidSidechainDuck)
+        );
+        assert_eq!(
+            builder().breath_retention(0.0, 0.3).build(),
+            Err(ConfigError::InvalidBreathRetention)
+        );
+        assert_eq!(
+            builder().breath_retention(2000.0, 1.5).build(),
+            Err(ConfigError::InvalidBreathRetention)
+        );
+        assert_eq!(
+            builder().delay(DelayTime::Seconds(3.0), 0.5, 0.0, 0.5).build(),
+            Err(ConfigError::InvalidDelay)
+        );
+        assert_eq!(
+            builder().delay(DelayTime::Note(NoteValue::new(1, 0)), 0.5, 0.0, 0.5).build(),
+            Err(ConfigError::InvalidDelay)
+        );
+        assert_eq!(
+            builder().delay(DelayTime::Seconds(0.3), 1.0, 0.0, 0.5).build(),
+            Err(ConfigError::InvalidDelay)
+        );
+        assert_eq!(
+            builder().saturation(SaturationCurve::Tube, 0.0, 1.0, true).build(),
+            Err(ConfigError::InvalidSaturation)
+        );
+        assert_eq!(
+            builder().saturation(SaturationCurve::Foldback, 2.0, 1.5, false).build(),
+            Err(ConfigError::InvalidSaturation)
+        );
+        assert_eq!(
+            builder().sample_rate_reduction(0).build(),
+            Err(ConfigError::InvalidSampleRateReduction)
+        );
+        assert_eq!(builder().bitcrush(0).build(), Err(ConfigError::InvalidBitcrush));
+        assert_eq!(builder().bitcrush(25).build(), Err(ConfigError::InvalidBitcrush));
+        assert_eq!(
+            builder().chorus(ModulationRate::Hz(1.0), 2.0, 3.0, 0.0, 0.5).build(),
+            Err(ConfigError::InvalidChorus)
+        );
+        assert_eq!(
+            builder().chorus(ModulationRate::Hz(1.0), 18.0, 3.0, 0.0, 0.5).build(),
+            Err(ConfigError::InvalidChorus)
+        );
+        assert_eq!(
+            builder().chorus(ModulationRate::Hz(-1.0), 12.0, 3.0, 0.0, 0.5).build(),
+            Err(ConfigError::InvalidChorus)
+        );
+        assert_eq!(
+            builder().chorus(ModulationRate::Hz(1.0), 3.0, 2.0, 1.0, 0.5).build(),
+            Err(ConfigError::InvalidChorus)
+        );
+        assert_eq!(builder().reverb(1.5, 0.5, 0.2).build(), Err(ConfigError::InvalidReverb));
+        assert_eq!(builder().reverb(0.5, 0.5, f32::NAN).build(), Err(ConfigError::InvalidReverb));
+    }
+}
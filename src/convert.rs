@@ -0,0 +1,389 @@
+//! Sample format conversion for codec and I2S/SAI integration.
+//!
+//! Every conversion works on whole slices with straight-line loop bodies
+//! (saturation through `clamp` and `as`, no per-sample branches), so the
+//! compiler can vectorize them with whatever SIMD the target offers (SSE/NEON,
+//! or the Cortex-M DSP extension).
+//!
+//! Integer full scale maps to `-1.0..1.0`: `i16::MIN` is exactly `-1.0` and
+//! `1.0` saturates to `i16::MAX`.
+
+use libm::rintf;
+
+use crate::VocalEffectsError;
+
+const I16_SCALE: f32 = 32768.0;
+const I24_SCALE: f32 = 8_388_608.0;
+const I24_MAX: i32 = 0x7F_FFFF;
+const I24_MIN: i32 = -0x80_0000;
+const I32_SCALE: f32 = 2_147_483_648.0;
+
+#[inline(always)]
+fn check_lengths(input: usize, output: usize) -> Result<(), VocalEffectsError> {
+    if input == output {
+        Ok(())
+    } else {
+        Err(VocalEffectsError::BufferSizeMismatch)
+    }
+}
+
+/// Convert 16-bit samples to `f32`
+pub fn i16_to_f32(input: &[i16], output: &mut [f32]) -> Result<(), VocalEffectsError> {
+    check_lengths(input.len(), output.len())?;
+    for (output, &input) in output.iter_mut().zip(input) {
+        *output = input as f32 / I16_SCALE;
+    }
+    Ok(())
+}
+
+/// Convert `f32` samples to 16-bit, rounding to nearest and saturating
+pub fn f32_to_i16(input: &[f32], output: &mut [i16]) -> Result<(), VocalEffectsError> {
+    check_lengths(input.len(), output.len())?;
+    for (output, &input) in output.iter_mut().zip(input) {
+        // `as` saturates and maps NaN to zero
+        *output = rintf(input * I16_SCALE) as i16;
+    }
+    Ok(())
+}
+
+/// Dither applied when reducing to 16-bit with [`f32_to_i16_dithered`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dither {
+    /// Plain rounding, identical to [`f32_to_i16`]
+    #[default]
+    None,
+    /// Triangular (TPDF) dither of ±1 LSB, which decorrelates the quantization
+    /// error from the signal
+    Tpdf,
+    /// TPDF dither with first-order error feedback, moving the noise floor
+    /// towards Nyquist where the ear is less sensitive
+    TpdfShaped,
+}
+
+/// Random and error-feedback state for dithered quantization of one channel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DitherState {
+    dither: Dither,
+    seed: u32,
+    rng: u32,
+    error: f32,
+}
+
+impl DitherState {
+    /// Create a state for the given dither mode
+    pub const fn new(dither: Dither) -> Self {
+        Self::with_seed(dither, 0x2545_F491)
+    }
+
+    /// Create a state with a custom random seed, e.g. to decorrelate channels
+    pub const fn with_seed(dither: Dither, seed: u32) -> Self {
+        // xorshift has a fixed point at zero
+        let seed = if seed == 0 { 1 } else { seed };
+        Self { dither, seed, rng: seed, error: 0.0 }
+    }
+
+    /// Dither mode in use
+    pub fn dither(&self) -> Dither {
+        self.dither
+    }
+
+    /// Restart the random sequence and clear the error feedback
+    pub fn reset(&mut self) {
+        self.rng = self.seed;
+        self.error = 0.0;
+    }
+
+    /// Uniform random value in `[0, 1)`
+    #[inline(always)]
+    fn uniform(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        (self.rng >> 8) as f32 / (1 << 24) as f32
+    }
+
+    /// Triangular random value in `(-1, 1)`
+    #[inline(always)]
+    fn triangular(&mut self) -> f32 {
+        self.uniform() - self.uniform()
+    }
+}
+
+/// Convert `f32` samples to 16-bit with dithering and optional noise shaping
+///
+/// Use this for the final reduction to a 16-bit DAC or file, where plain rounding
+/// turns quiet reverb tails and fades into correlated distortion.
+pub fn f32_to_i16_dithered(
+    input: &[f32],
+    output: &mut [i16],
+    state: &mut DitherState,
+) -> Result<(), VocalEffectsError> {
+    check_lengths(input.len(), output.len())?;
+    match state.dither {
+        Dither::None => f32_to_i16(input, output)?,
+        Dither::Tpdf => {
+            for (output, &input) in output.iter_mut().zip(input) {
+                *output = rintf(input * I16_SCALE + state.triangular()) as i16;
+            }
+        }
+        Dither::TpdfShaped => {
+            for (output, &input) in output.iter_mut().zip(input) {
+                // Subtracting the previous error gives a noise transfer of 1 - z^-1
+                let target = input * I16_SCALE - state.error;
+                *output = rintf(target + state.triangular()) as i16;
+                // Clamp so a clipped sample cannot wind up the feedback loop
+                state.error = (*output as f32 - target).clamp(-2.0, 2.0);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Convert 24-bit samples held in the low bits of an `i32` to `f32`
+///
+/// The upper byte is ignored, so both sign-extended and zero-padded words work.
+pub fn i24_to_f32(input: &[i32], output: &mut [f32]) -> Result<(), VocalEffectsError> {
+    check_lengths(input.len(), output.len())?;
+    for (output, &input) in output.iter_mut().zip(input) {
+        // Sign-extend from bit 23
+        let sample = (input << 8) >> 8;
+        *output = sample as f32 / I24_SCALE;
+    }
+    Ok(())
+}
+
+/// Convert `f32` samples to sign-extended 24-bit samples in an `i32`
+pub fn f32_to_i24(input: &[f32], output: &mut [i32]) -> Result<(), VocalEffectsError> {
+    check_lengths(input.len(), output.len())?;
+    for (output, &input) in output.iter_mut().zip(input) {
+        *output = (rintf(input * I24_SCALE) as i32).clamp(I24_MIN, I24_MAX);
+    }
+    Ok(())
+}
+
+/// Convert packed little-endian 24-bit samples (3 bytes each) to `f32`
+pub fn i24_packed_to_f32(input: &[u8], output: &mut [f32]) -> Result<(), VocalEffectsError> {
+    if !input.len().is_multiple_of(3) {
+        return Err(VocalEffectsError::BufferSizeMismatch);
+    }
+    check_lengths(input.len() / 3, output.len())?;
+    for (output, bytes) in output.iter_mut().zip(input.chunks_exact(3)) {
+        // Assemble in the top three bytes so the arithmetic shift sign-extends
+        let sample = i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8;
+        *output = sample as f32 / I24_SCALE;
+    }
+    Ok(())
+}
+
+/// Convert `f32` samples to packed little-endian 24-bit samples (3 bytes each)
+pub fn f32_to_i24_packed(input: &[f32], output: &mut [u8]) -> Result<(), VocalEffectsError> {
+    if !output.len().is_multiple_of(3) {
+        return Err(VocalEffectsError::BufferSizeMismatch);
+    }
+    check_lengths(input.len(), output.len() / 3)?;
+    for (bytes, &input) in output.chunks_exact_mut(3).zip(input) {
+        let sample = (rintf(input * I24_SCALE) as i32).clamp(I24_MIN, I24_MAX);
+        bytes.copy_from_slice(&sample.to_le_bytes()[..3]);
+    }
+    Ok(())
+}
+
+/// Convert 32-bit samples to `f32`
+///
+/// Also suits 24-bit audio left-justified in 32-bit slots, as most SAI
+/// peripherals deliver it.
+pub fn i32_to_f32(input: &[i32], output: &mut [f32]) -> Result<(), VocalEffectsError> {
+    check_lengths(input.len(), output.len())?;
+    for (output, &input) in output.iter_mut().zip(input) {
+        *output = input as f32 / I32_SCALE;
+    }
+    Ok(())
+}
+
+/// Convert `f32` samples to 32-bit, saturating
+pub fn f32_to_i32(input: &[f32], output: &mut [i32]) -> Result<(), VocalEffectsError> {
+    check_lengths(input.len(), output.len())?;
+    for (output, &input) in output.iter_mut().zip(input) {
+        *output = rintf(input * I32_SCALE) as i32;
+    }
+    Ok(())
+}
+
+/// Interleave two channels into `L R L R ...` frames
+pub fn interleave<T: Copy>(
+    left: &[T],
+    right: &[T],
+    output: &mut [T],
+) -> Result<(), VocalEffectsError> {
+    if left.len() != right.len() || output.len() != left.len() * 2 {
+        return Err(VocalEffectsError::BufferSizeMismatch);
+    }
+    for ((frame, &l), &r) in output.chunks_exact_mut(2).zip(left).zip(right) {
+        frame[0] = l;
+        frame[1] = r;
+    }
+    Ok(())
+}
+
+/// Split `L R L R ...` frames into two channels
+pub fn deinterleave<T: Copy>(
+    input: &[T],
+    left: &mut [T],
+    right: &mut [T],
+) -> Result<(), VocalEffectsError> {
+    if left.len() != right.len() || input.len() != left.len() * 2 {
+        return Err(VocalEffectsError::BufferSizeMismatch);
+    }
+    for ((frame, l), r) in input.chunks_exact(2).zip(left.iter_mut()).zip(right.iter_mut()) {
+        *l = frame[0];
+        *r = frame[1];
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_i16_round_trip() {
+        let input = [i16::MIN, -1, 0, 1, 12345, i16::MAX];
+        let mut floats = [0.0f32; 6];
+        let mut output = [0i16; 6];
+        i16_to_f32(&input, &mut floats).unwrap();
+        assert_eq!(floats[0], -1.0);
+        f32_to_i16(&floats, &mut output).unwrap();
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn test_f32_to_i16_saturates() {
+        let mut output = [0i16; 4];
+        f32_to_i16(&[1.0, -1.5, 2.0, f32::NAN], &mut output).unwrap();
+        assert_eq!(output, [i16::MAX, i16::MIN, i16::MAX, 0]);
+    }
+
+    fn quiet_sine(n: usize) -> f32 {
+        // 0.4 LSB, which plain rounding turns into silence
+        0.4 / I16_SCALE * libm::sinf(2.0 * core::f32::consts::PI * n as f32 / 100.0)
+    }
+
+    #[test]
+    fn test_dither_preserves_sub_lsb_signal() {
+        let input: [f32; 4000] = core::array::from_fn(quiet_sine);
+        let mut output = [0i16; 4000];
+
+        f32_to_i16_dithered(&input, &mut output, &mut DitherState::new(Dither::None)).unwrap();
+        assert!(output.iter().all(|&s| s == 0));
+
+        for dither in [Dither::Tpdf, Dither::TpdfShaped] {
+            let mut state = DitherState::new(dither);
+            f32_to_i16_dithered(&input, &mut output, &mut state).unwrap();
+            // The signal survives on average: output correlates with the input
+            let correlation: f32 =
+                input.iter().zip(output.iter()).map(|(x, &y)| x * I16_SCALE * y as f32).sum();
+            assert!(correlation / 4000.0 > 0.04, "{dither:?}: {correlation}");
+            assert!(output.iter().all(|s| s.abs() <= 2));
+        }
+    }
+
+    #[test]
+    fn test_noise_shaping_moves_error_out_of_the_low_band() {
+        let input: [f32; 4000] = core::array::from_fn(quiet_sine);
+        let mut output = [0i16; 4000];
+
+        // Error power after an 8-sample moving average, i.e. below ~fs/8
+        let mut low_band_error = |dither| {
+            let mut state = DitherState::new(dither);
+            f32_to_i16_dithered(&input, &mut output, &mut state).unwrap();
+            let error: [f32; 4000] =
+                core::array::from_fn(|i| output[i] as f32 - input[i] * I16_SCALE);
+            error.windows(8).map(|w| (w.iter().sum::<f32>() / 8.0).powi(2)).sum::<f32>()
+        };
+
+        let flat = low_band_error(Dither::Tpdf);
+        let shaped = low_band_error(Dither::TpdfShaped);
+        assert!(shaped < flat * 0.5, "shaped {shaped} vs flat {flat}");
+    }
+
+    #[test]
+    fn test_dither_state_reset_repeats_sequence() {
+        let input = [0.0f32; 64];
+        let mut first = [0i16; 64];
+        let mut second = [0i16; 64];
+        let mut state = DitherState::with_seed(Dither::Tpdf, 42);
+        f32_to_i16_dithered(&input, &mut first, &mut state).unwrap();
+        state.reset();
+        f32_to_i16_dithered(&input, &mut second, &mut state).unwrap();
+        assert_eq!(first, second);
+        assert!(first.iter().any(|&s| s != 0));
+    }
+
+    #[test]
+    fn test_i24_round_trip() {
+        let input = [I24_MIN, -1, 0, 1, 0x12_3456, I24_MAX];
+        let mut floats = [0.0f32; 6];
+        let mut output = [0i32; 6];
+        i24_to_f32(&input, &mut floats).unwrap();
+        f32_to_i24(&floats, &mut output).unwrap();
+        assert_eq!(output, input);
+
+        // Zero-padded negative words are sign-extended
+        i24_to_f32(&[0x00FF_FFFF], &mut floats[..1]).unwrap();
+        assert_eq!(floats[0], -1.0 / I24_SCALE);
+
+        f32_to_i24(&[1.0, -2.0], &mut output[..2]).unwrap();
+        assert_eq!(output[..2], [I24_MAX, I24_MIN]);
+    }
+
+    #[test]
+    fn test_i24_packed_round_trip() {
+        let packed = [0x56, 0x34, 0x12, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x80];
+        let mut floats = [0.0f32; 3];
+        i24_packed_to_f32(&packed, &mut floats).unwrap();
+        assert_eq!(floats, [0x12_3456 as f32 / I24_SCALE, -1.0 / I24_SCALE, -1.0]);
+
+        let mut output = [0u8; 9];
+        f32_to_i24_packed(&floats, &mut output).unwrap();
+        assert_eq!(output, packed);
+
+        assert_eq!(
+            i24_packed_to_f32(&packed[..8], &mut floats),
+            Err(VocalEffectsError::BufferSizeMismatch)
+        );
+    }
+
+    #[test]
+    fn test_i32_conversions() {
+        let mut floats = [0.0f32; 3];
+        i32_to_f32(&[i32::MIN, 0, 1 << 30], &mut floats).unwrap();
+        assert_eq!(floats, [-1.0, 0.0, 0.5]);
+
+        let mut output = [0i32; 3];
+        f32_to_i32(&[1.0, -1.0, 0.5], &mut output).unwrap();
+        assert_eq!(output, [i32::MAX, i32::MIN, 1 << 30]);
+    }
+
+    #[test]
+    fn test_interleave_round_trip() {
+        let left = [1, 2, 3];
+        let right = [4, 5, 6];
+        let mut frames = [0; 6];
+        interleave(&left, &right, &mut frames).unwrap();
+        assert_eq!(frames, [1, 4, 2, 5, 3, 6]);
+
+        let mut l = [0; 3];
+        let mut r = [0; 3];
+        deinterleave(&frames, &mut l, &mut r).unwrap();
+        assert_eq!((l, r), (left, right));
+
+        assert_eq!(
+            interleave(&left, &right[..2], &mut frames),
+            Err(VocalEffectsError::BufferSizeMismatch)
+        );
+        assert_eq!(
+            deinterleave(&frames[..5], &mut l, &mut r),
+            Err(VocalEffectsError::BufferSizeMismatch)
+        );
+    }
+}
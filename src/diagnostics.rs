@@ -0,0 +1,290 @@
+//! Recording and export of the engine analysis.
+//!
+//! A [`Recorder`] observes an [`Engine`](crate::Engine) (see
+//! [`Engine::process_hop_observed`](crate::Engine::process_hop_observed)) and keeps
+//! every analysed frame. Afterwards the pitch and target tracks can be written as
+//! CSV, and the spectrogram as CSV or as a PNG with the tracks drawn over it, to
+//! see what the detector heard at the moment a correction went wrong.
+
+use std::io::{self, Write};
+use std::vec::Vec;
+
+use crate::{
+    FrameAnalysis, ProcessingMode, VocalEffectsConfig,
+    engine::{FrameObserver, FrameView},
+};
+
+/// Floor of the spectrogram, in dB below its loudest bin
+const DYNAMIC_RANGE_DB: f32 = 90.0;
+
+/// One frame kept by a [`Recorder`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedFrame {
+    /// Hop the frame was processed at, counted from 1
+    pub hop_index: u64,
+    /// Mode the frame was processed in
+    pub mode: ProcessingMode,
+    /// Spectrum magnitudes from DC up to below Nyquist
+    pub magnitudes: Vec<f32>,
+    /// Detected pitch, target and applied ratio
+    pub analysis: FrameAnalysis,
+}
+
+/// Collects the analysis of every processed frame for export
+///
+/// ```rust
+/// use synthphone_e_vocal_dsp::{
+///     Engine1024, MusicalSettings, VocalEffectsConfig, diagnostics::Recorder,
+/// };
+///
+/// let mut engine = Engine1024::new(VocalEffectsConfig::default(), MusicalSettings::default());
+/// let mut recorder = Recorder::new(engine.config());
+/// let mut output = [0.0f32; 256];
+/// engine.process_hop_observed(&[0.0; 256], None, &mut output, &mut recorder).unwrap();
+///
+/// let mut csv = Vec::new();
+/// recorder.write_pitch_csv(&mut csv).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    sample_rate: f32,
+    fft_size: usize,
+    hop_size: usize,
+    frames: Vec<RecordedFrame>,
+}
+
+impl Recorder {
+    /// Creates a recorder for an engine running with `config`
+    ///
+    /// Pass [`Engine::config`](crate::Engine::config), which has the frame and hop
+    /// size the engine actually uses.
+    pub fn new(config: &VocalEffectsConfig) -> Self {
+        Self {
+            sample_rate: config.sample_rate,
+            fft_size: config.fft_size,
+            hop_size: config.hop_size,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Returns the recorded frames in processing order
+    pub fn frames(&self) -> &[RecordedFrame] {
+        &self.frames
+    }
+
+    /// Discards the recorded frames
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    /// Time in seconds of the input at the centre of `frame`
+    pub fn frame_time(&self, frame: &RecordedFrame) -> f32 {
+        let end = frame.hop_index as usize * self.hop_size;
+        end.saturating_sub(self.fft_size / 2) as f32 / self.sample_rate
+    }
+
+    /// Writes one row per frame with the detected, target and corrected pitch
+    ///
+    /// Columns: `hop,time_s,mode,detected_hz,target_hz,ratio,corrected_hz`. The
+    /// target is 0 where the detection was out of range and the ratio was held.
+    pub fn write_pitch_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "hop,time_s,mode,detected_hz,target_hz,ratio,corrected_hz")?;
+        for frame in &self.frames {
+            let analysis = &frame.analysis;
+            writeln!(
+                writer,
+                "{},{:.6},{:?},{:.3},{:.3},{:.6},{:.3}",
+                frame.hop_index,
+                self.frame_time(frame),
+                frame.mode,
+                analysis.detected_frequency,
+                analysis.target_frequency,
+                analysis.pitch_shift_ratio,
+                analysis.detected_frequency * analysis.pitch_shift_ratio,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes the spectrogram in dB, one row per frame
+    ///
+    /// The header names each bin by its centre frequency in Hz.
+    pub fn write_spectrogram_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write!(writer, "hop,time_s")?;
+        for bin in 0..self.bins() {
+            write!(writer, ",{:.1}", self.bin_frequency(bin))?;
+        }
+        writeln!(writer)?;
+
+        for frame in &self.frames {
+            write!(writer, "{},{:.6}", frame.hop_index, self.frame_time(frame))?;
+            for &magnitude in &frame.magnitudes {
+                write!(writer, ",{:.2}", decibels(magnitude))?;
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the spectrogram as an RGB PNG with the pitch tracks drawn over it
+    ///
+    /// Time runs left to right at one column per frame and frequency bottom to top
+    /// at one row per bin, up to `max_frequency` Hz. The corrected pitch is drawn in
+    /// blue, the detected pitch in red and the target on top in green.
+    ///
+    /// # Errors
+    ///
+    /// Fails if nothing was recorded or if writing fails.
+    pub fn write_spectrogram_png<W: Write>(&self, writer: W, max_frequency: f32) -> io::Result<()> {
+        let bin_width = self.sample_rate / self.fft_size as f32;
+        let height = ((max_frequency / bin_width) as usize).clamp(1, self.bins().max(1));
+        let width = self.frames.len();
+        if width == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "no frames recorded"));
+        }
+
+        let peak = self
+            .frames
+            .iter()
+            .flat_map(|frame| frame.magnitudes.iter().copied())
+            .fold(0.0f32, f32::max);
+        let peak_db = decibels(peak);
+
+        let mut pixels = vec![0u8; width * height * 3];
+        let row_of = |frequency: f32| {
+            let bin = libm::roundf(frequency / bin_width) as usize;
+            (frequency > 0.0 && bin < height).then(|| height - 1 - bin)
+        };
+        for (column, frame) in self.frames.iter().enumerate() {
+            for (bin, &magnitude) in frame.magnitudes.iter().take(height).enumerate() {
+                let level = (decibels(magnitude) - peak_db + DYNAMIC_RANGE_DB) / DYNAMIC_RANGE_DB;
+                let value = (level.clamp(0.0, 1.0) * 255.0) as u8;
+                let pixel = ((height - 1 - bin) * width + column) * 3;
+                pixels[pixel..pixel + 3].fill(value);
+            }
+
+            let analysis = &frame.analysis;
+            let tracks = [
+                (analysis.detected_frequency * analysis.pitch_shift_ratio, [0, 128, 255]),
+                (analysis.detected_frequency, [255, 0, 0]),
+                (analysis.target_frequency, [0, 255, 0]),
+            ];
+            for (frequency, colour) in tracks {
+                if let Some(row) = row_of(frequency) {
+                    let pixel = (row * width + column) * 3;
+                    pixels[pixel..pixel + 3].copy_from_slice(&colour);
+                }
+            }
+        }
+
+        let size = |value: usize| {
+            u32::try_from(value)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "spectrogram too large"))
+        };
+        let mut encoder = png::Encoder::new(writer, size(width)?, size(height)?);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(io::Error::other)?;
+        writer.write_image_data(&pixels).map_err(io::Error::other)?;
+        writer.finish().map_err(io::Error::other)
+    }
+
+    fn bins(&self) -> usize {
+        self.fft_size / 2
+    }
+
+    fn bin_frequency(&self, bin: usize) -> f32 {
+        bin as f32 * self.sample_rate / self.fft_size as f32
+    }
+}
+
+impl FrameObserver for Recorder {
+    fn on_frame(&mut self, frame: &FrameView<'_>) {
+        self.frames.push(RecordedFrame {
+            hop_index: frame.hop_index,
+            mode: frame.mode,
+            magnitudes: frame.magnitudes.to_vec(),
+            analysis: frame.analysis,
+        });
+    }
+}
+
+fn decibels(magnitude: f32) -> f32 {
+    20.0 * libm::log10f(magnitude.max(1e-9))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Engine1024, MusicalSettings};
+    use core::f32::consts::PI;
+
+    fn record(hops: usize) -> Recorder {
+        let mut engine = Engine1024::new(VocalEffectsConfig::default(), MusicalSettings::default());
+        let mut recorder = Recorder::new(engine.config());
+        let mut output = [0.0f32; 256];
+        for block in 0..hops {
+            let input: [f32; 256] = core::array::from_fn(|i| {
+                0.5 * libm::sinf(2.0 * PI * 233.0 * (block * 256 + i) as f32 / 48000.0)
+            });
+            engine.process_hop_observed(&input, None, &mut output, &mut recorder).unwrap();
+        }
+        recorder
+    }
+
+    #[test]
+    fn test_pitch_csv_has_a_row_per_frame() {
+        let recorder = record(16);
+        let mut csv = Vec::new();
+        recorder.write_pitch_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines.len(), 17);
+        assert_eq!(lines[0], "hop,time_s,mode,detected_hz,target_hz,ratio,corrected_hz");
+        // 233 Hz snaps down to A3 in C major
+        let last: Vec<&str> = lines[16].split(',').collect();
+        assert_eq!(last[0], "16");
+        assert_eq!(last[2], "Autotune");
+        let target: f32 = last[4].parse().unwrap();
+        assert!((target - 220.0).abs() < 0.5, "target {target}");
+    }
+
+    #[test]
+    fn test_spectrogram_csv_names_bins() {
+        let recorder = record(4);
+        let mut csv = Vec::new();
+        recorder.write_spectrogram_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let header: Vec<&str> = csv.lines().next().unwrap().split(',').collect();
+
+        assert_eq!(header.len(), 2 + 512);
+        assert_eq!(header[3], "46.9");
+        assert_eq!(csv.lines().count(), 5);
+        assert!(csv.lines().skip(1).all(|row| row.split(',').count() == 2 + 512));
+    }
+
+    #[test]
+    fn test_spectrogram_png_draws_tracks() {
+        let recorder = record(16);
+        let mut png_data = Vec::new();
+        recorder.write_spectrogram_png(&mut png_data, 2000.0).unwrap();
+
+        let decoder = png::Decoder::new(png_data.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0u8; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height), (16, 42));
+
+        let row = 42 - 1 - libm::roundf(220.0 / 46.875) as usize;
+        let pixel = (row * 16 + 15) * 3;
+        assert_eq!(pixels[pixel..pixel + 3], [0, 255, 0]);
+    }
+
+    #[test]
+    fn test_png_needs_frames() {
+        let recorder = Recorder::new(&VocalEffectsConfig::default());
+        let error = recorder.write_spectrogram_png(Vec::new(), 1000.0).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,271 @@
+//! Double-buffered DMA helper for I2S/SAI audio.
+//!
+//! Codec peripherals such as the STM32 SAI on the Daisy run a circular DMA
+//! transfer over a buffer of two halves: while the peripheral works on one half,
+//! the half-transfer or transfer-complete interrupt services the other. The
+//! index math for picking the idle half, deinterleaving, converting and moving
+//! samples through the ring buffers lives here so firmware does not duplicate it.
+
+use core::{
+    ops::Range,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use crate::{
+    VocalEffectsError,
+    convert::{f32_to_i24, f32_to_i32, i24_to_f32, i32_to_f32},
+    ring_buffer::RingBuffer,
+};
+
+/// Half of a circular DMA buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DmaHalf {
+    /// First half, free after the half-transfer interrupt
+    First,
+    /// Second half, free after the transfer-complete interrupt
+    Second,
+}
+
+/// Layout of samples in the DMA words
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SampleFormat {
+    /// 24-bit samples in the low bits of each word
+    I24,
+    /// 32-bit samples, or 24-bit samples left-justified in 32-bit words
+    I32,
+}
+
+/// Stereo input channel carrying the voice
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum InputChannel {
+    /// Left channel (even words)
+    Left,
+    /// Right channel (odd words)
+    Right,
+}
+
+/// Ping-pong servicing of interleaved stereo DMA buffers.
+///
+/// Each DMA buffer holds two halves of `FRAMES` stereo frames, so `4 * FRAMES`
+/// words. Servicing a half pushes the selected input channel into the input ring
+/// buffer and fills both output channels from the output ring buffer.
+///
+/// Running out of processed samples is an underrun (the missing samples are sent
+/// as silence); an input ring buffer without room for a half is an overrun. Both
+/// are counted so the firmware can report them. Each instance must be serviced
+/// from a single interrupt.
+///
+/// # Example
+///
+/// ```rust
+/// use synthphone_e_vocal_dsp::{
+///     dma::{DmaHalf, DmaPingPong, InputChannel, SampleFormat},
+///     ring_buffer::RingBuffer,
+/// };
+///
+/// static DMA: DmaPingPong<32> = DmaPingPong::new(SampleFormat::I24, InputChannel::Left);
+/// static INPUT: RingBuffer<1024> = RingBuffer::new();
+/// static OUTPUT: RingBuffer<1024> = RingBuffer::new();
+///
+/// let rx = [0i32; 128];
+/// let mut tx = [0i32; 128];
+/// // In the half-transfer interrupt
+/// DMA.service(DmaHalf::First, &rx, &mut tx, &INPUT, &OUTPUT).unwrap();
+/// assert_eq!(INPUT.available_samples(), 32);
+/// assert_eq!(DMA.underruns(), 1);
+/// ```
+pub struct DmaPingPong<const FRAMES: usize> {
+    format: SampleFormat,
+    channel: InputChannel,
+    underruns: AtomicU32,
+    overruns: AtomicU32,
+}
+
+impl<const FRAMES: usize> DmaPingPong<FRAMES> {
+    /// Number of words in a complete (two half) DMA buffer
+    pub const BUFFER_WORDS: usize = FRAMES * 4;
+
+    /// Creates the helper, usable in a `static`
+    pub const fn new(format: SampleFormat, channel: InputChannel) -> Self {
+        Self { format, channel, underruns: AtomicU32::new(0), overruns: AtomicU32::new(0) }
+    }
+
+    /// Word range of `half` within a DMA buffer
+    pub const fn half_range(half: DmaHalf) -> Range<usize> {
+        match half {
+            DmaHalf::First => 0..FRAMES * 2,
+            DmaHalf::Second => FRAMES * 2..FRAMES * 4,
+        }
+    }
+
+    /// Services the idle half of the receive and transmit buffers.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VocalEffectsError::BufferSizeMismatch`] unless both buffers are
+    /// [`DmaPingPong::BUFFER_WORDS`] long. Ring buffers shorter than a half fail
+    /// to compile:
+    ///
+    /// ```compile_fail
+    /// use synthphone_e_vocal_dsp::{
+    ///     dma::{DmaHalf, DmaPingPong, InputChannel, SampleFormat},
+    ///     ring_buffer::RingBuffer,
+    /// };
+    ///
+    /// let dma = DmaPingPong::<32>::new(SampleFormat::I24, InputChannel::Left);
+    /// let input = RingBuffer::<16>::new();
+    /// let output = RingBuffer::<1024>::new();
+    /// let _ = dma.service(DmaHalf::First, &[0; 128], &mut [0; 128], &input, &output);
+    /// ```
+    pub fn service<const IN: usize, const OUT: usize>(
+        &self,
+        half: DmaHalf,
+        rx: &[i32],
+        tx: &mut [i32],
+        input: &RingBuffer<IN>,
+        output: &RingBuffer<OUT>,
+    ) -> Result<(), VocalEffectsError> {
+        const {
+            assert!(FRAMES <= IN && FRAMES <= OUT, "ring buffers must hold a DMA half");
+        }
+        if rx.len() != Self::BUFFER_WORDS || tx.len() != Self::BUFFER_WORDS {
+            return Err(VocalEffectsError::BufferSizeMismatch);
+        }
+        let range = Self::half_range(half);
+        let offset = match self.channel {
+            InputChannel::Left => 0,
+            InputChannel::Right => 1,
+        };
+
+        let mut words = [0i32; FRAMES];
+        let mut samples = [0.0f32; FRAMES];
+        for (word, frame) in words.iter_mut().zip(rx[range.clone()].chunks_exact(2)) {
+            *word = frame[offset];
+        }
+        self.decode(&words, &mut samples)?;
+        if input.available_samples() as usize + FRAMES > IN {
+            increment(&self.overruns);
+        }
+        input.push_slice(&samples);
+
+        if output.pop_slice(&mut samples) < FRAMES {
+            increment(&self.underruns);
+        }
+        self.encode(&samples, &mut words)?;
+        for (frame, &word) in tx[range].chunks_exact_mut(2).zip(words.iter()) {
+            frame[0] = word;
+            frame[1] = word;
+        }
+        Ok(())
+    }
+
+    /// Number of halves sent with missing output samples
+    pub fn underruns(&self) -> u32 {
+        self.underruns.load(Ordering::Relaxed)
+    }
+
+    /// Number of halves received without room in the input ring buffer
+    pub fn overruns(&self) -> u32 {
+        self.overruns.load(Ordering::Relaxed)
+    }
+
+    /// Clears the underrun and overrun counters
+    pub fn clear_counters(&self) {
+        self.underruns.store(0, Ordering::Relaxed);
+        self.overruns.store(0, Ordering::Relaxed);
+    }
+
+    fn decode(&self, words: &[i32], samples: &mut [f32]) -> Result<(), VocalEffectsError> {
+        match self.format {
+            SampleFormat::I24 => i24_to_f32(words, samples),
+            SampleFormat::I32 => i32_to_f32(words, samples),
+        }
+    }
+
+    fn encode(&self, samples: &[f32], words: &mut [i32]) -> Result<(), VocalEffectsError> {
+        match self.format {
+            SampleFormat::I24 => f32_to_i24(samples, words),
+            SampleFormat::I32 => f32_to_i32(samples, words),
+        }
+    }
+}
+
+/// Load and store rather than `fetch_add`, which Cortex-M0+ lacks; only the
+/// servicing interrupt writes the counters
+fn increment(counter: &AtomicU32) {
+    counter.store(counter.load(Ordering::Relaxed).wrapping_add(1), Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_half_ranges() {
+        assert_eq!(DmaPingPong::<16>::half_range(DmaHalf::First), 0..32);
+        assert_eq!(DmaPingPong::<16>::half_range(DmaHalf::Second), 32..64);
+        assert_eq!(DmaPingPong::<16>::BUFFER_WORDS, 64);
+    }
+
+    #[test]
+    fn test_rejects_wrong_buffer_length() {
+        let dma = DmaPingPong::<4>::new(SampleFormat::I24, InputChannel::Left);
+        let input = RingBuffer::<16>::new();
+        let output = RingBuffer::<16>::new();
+        assert_eq!(
+            dma.service(DmaHalf::First, &[0; 8], &mut [0; 16], &input, &output),
+            Err(VocalEffectsError::BufferSizeMismatch)
+        );
+    }
+
+    #[test]
+    fn test_service_moves_samples_through_rings() {
+        let dma = DmaPingPong::<4>::new(SampleFormat::I24, InputChannel::Right);
+        let input = RingBuffer::<16>::new();
+        let output = RingBuffer::<16>::new();
+
+        // Right channel carries 0x10000 * frame in the second half
+        let mut rx = [0i32; 16];
+        for frame in 0..4 {
+            rx[8 + frame * 2 + 1] = 0x10000 * (frame as i32 + 1);
+        }
+        output.push_slice(&[0.5, -0.5, 0.25, -0.25]);
+
+        let mut tx = [0i32; 16];
+        dma.service(DmaHalf::Second, &rx, &mut tx, &input, &output).unwrap();
+
+        let mut received = [0.0f32; 4];
+        assert_eq!(input.pop_slice(&mut received), 4);
+        assert_eq!(received, [0.0078125, 0.015625, 0.0234375, 0.03125]);
+        assert_eq!(&tx[..8], &[0; 8]);
+        assert_eq!(&tx[8..12], &[0x40_0000, 0x40_0000, -0x40_0000, -0x40_0000]);
+        assert_eq!(dma.underruns(), 0);
+        assert_eq!(dma.overruns(), 0);
+    }
+
+    #[test]
+    fn test_flags_underrun_and_overrun() {
+        let dma = DmaPingPong::<4>::new(SampleFormat::I32, InputChannel::Left);
+        let input = RingBuffer::<8>::new();
+        let output = RingBuffer::<8>::new();
+        let rx = [0i32; 16];
+        let mut tx = [1i32; 16];
+
+        output.push_slice(&[0.5, 0.5]);
+        dma.service(DmaHalf::First, &rx, &mut tx, &input, &output).unwrap();
+        assert_eq!(dma.underruns(), 1);
+        // The missing samples are sent as silence
+        assert_eq!(&tx[4..8], &[0; 4]);
+
+        dma.service(DmaHalf::Second, &rx, &mut tx, &input, &output).unwrap();
+        dma.service(DmaHalf::First, &rx, &mut tx, &input, &output).unwrap();
+        assert_eq!(dma.overruns(), 1);
+        assert_eq!(dma.underruns(), 3);
+
+        dma.clear_counters();
+        assert_eq!(dma.underruns(), 0);
+    }
+}
@@ -0,0 +1,199 @@
+//! Per-bin phase vocoder tables.
+//!
+//! A partial at the centre of bin `i` advances by `2π·i·hop/N` radians over a
+//! hop. The analysis subtracts this expected advance to find how far each bin
+//! is off its centre, and the synthesis adds it back. [`BinTables`] holds it for
+//! every bin, computed once per hop size instead of once per bin and frame.
+//!
+//! When frames are zero-padded to a larger FFT, the tables also hold the window
+//! over the part of the frame that carries signal. When the synthesis hop differs
+//! from the analysis hop, the synthesis advances are scaled to it.
+
+use core::f32::consts::PI;
+
+use libm::cosf;
+
+/// Expected phase advances of every bin for one hop size
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BinTables<const N: usize> {
+    hop_size: usize,
+    synthesis_hop_size: usize,
+    /// Samples of signal at the end of each frame, the rest being zero padding
+    frame_size: usize,
+    /// Phase advance of a partial at the bin centre over one hop in radians
+    phase_advances: [f32; N],
+    /// Phase advance over one hop per bin of frequency offset
+    radians_per_bin: f32,
+    /// Frequency offset in bins per radian of phase advance over one hop
+    bins_per_radian: f32,
+    /// Phase advance over one synthesis hop per bin of frequency offset
+    synthesis_radians_per_bin: f32,
+    /// Synthesis hop over analysis hop
+    stretch: f32,
+    /// Hann window over the last `frame_size` samples, zero before them
+    window: [f32; N],
+}
+
+impl<const N: usize> BinTables<N> {
+    /// Tables for no hop size, to be replaced before processing
+    pub const fn empty() -> Self {
+        Self {
+            hop_size: 0,
+            synthesis_hop_size: 0,
+            frame_size: N,
+            phase_advances: [0.0; N],
+            radians_per_bin: 0.0,
+            bins_per_radian: 0.0,
+            synthesis_radians_per_bin: 0.0,
+            stretch: 1.0,
+            window: [0.0; N],
+        }
+    }
+
+    /// Computes the tables for `hop_size` samples between frames
+    pub fn new(hop_size: usize) -> Self {
+        let mut tables = Self::empty();
+        tables.hop_size = hop_size;
+        for i in 0..N {
+            tables.phase_advances[i] = tables.centre_frequency(i) * hop_size as f32;
+        }
+        tables.radians_per_bin = 2.0 * PI * hop_size as f32 / N as f32;
+        tables.bins_per_radian = N as f32 / hop_size.max(1) as f32 / (2.0 * PI);
+        tables.synthesis_hop_size = hop_size;
+        tables.synthesis_radians_per_bin = tables.radians_per_bin;
+        tables
+    }
+
+    /// Computes the tables for frames of `frame_size` samples zero-padded to `N`
+    ///
+    /// The signal sits in the last `frame_size` samples of each frame, so the
+    /// newest samples stay at the end as in an unpadded frame.
+    pub fn with_frame_size(hop_size: usize, frame_size: usize) -> Self {
+        let mut tables = Self::new(hop_size);
+        tables.frame_size = frame_size.clamp(1, N);
+        let padding = N - tables.frame_size;
+        if padding > 0 {
+            let span = (tables.frame_size - 1).max(1) as f32;
+            for (n, value) in tables.window[padding..].iter_mut().enumerate() {
+                *value = 0.5 * (1.0 - cosf(2.0 * PI * n as f32 / span));
+            }
+        }
+        tables
+    }
+
+    /// Resynthesises at `synthesis_hop_size` samples between frames instead of
+    /// the analysis hop
+    pub fn with_synthesis_hop(mut self, synthesis_hop_size: usize) -> Self {
+        if synthesis_hop_size != self.hop_size {
+            self.synthesis_hop_size = synthesis_hop_size;
+            self.synthesis_radians_per_bin = 2.0 * PI * synthesis_hop_size as f32 / N as f32;
+            self.stretch = synthesis_hop_size as f32 / self.hop_size.max(1) as f32;
+        }
+        self
+    }
+
+    /// Analysis hop size the tables were computed for
+    pub fn hop_size(&self) -> usize {
+        self.hop_size
+    }
+
+    /// Synthesis hop size the tables were computed for
+    pub fn synthesis_hop_size(&self) -> usize {
+        self.synthesis_hop_size
+    }
+
+    /// Returns `true` if the synthesis hop differs from the analysis hop
+    pub fn stretches(&self) -> bool {
+        self.synthesis_hop_size != self.hop_size
+    }
+
+    /// Samples of signal in each frame, `N` unless frames are zero-padded
+    pub fn frame_size(&self) -> usize {
+        self.frame_size
+    }
+
+    /// Window over the signal of a zero-padded frame, `None` for unpadded frames
+    ///
+    /// Unpadded frames use the Hann window of the FFT backend.
+    pub fn window(&self) -> Option<&[f32; N]> {
+        (self.frame_size < N).then_some(&self.window)
+    }
+
+    /// Centre frequency of `bin` in radians per sample
+    #[inline]
+    pub fn centre_frequency(&self, bin: usize) -> f32 {
+        2.0 * PI * bin as f32 / N as f32
+    }
+
+    /// Phase advance over one hop of a partial at the centre of `bin`
+    #[inline]
+    pub fn phase_advance(&self, bin: usize) -> f32 {
+        self.phase_advances[bin]
+    }
+
+    /// Frequency offset in bins of a partial whose phase advanced `phase_deviation`
+    /// radians more than the bin centre over one hop
+    #[inline]
+    pub fn deviation_bins(&self, phase_deviation: f32) -> f32 {
+        phase_deviation * self.bins_per_radian
+    }
+
+    /// Phase advance over one synthesis hop of a partial at `frequency` bins in `bin`
+    #[inline]
+    pub fn synthesis_advance(&self, bin: usize, frequency: f32) -> f32 {
+        (frequency - bin as f32) * self.synthesis_radians_per_bin
+            + self.phase_advances[bin] * self.stretch
+    }
+}
+
+impl<const N: usize> Default for BinTables<N> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tables_match_direct_computation() {
+        let tables = BinTables::<1024>::new(256);
+        assert_eq!(tables.hop_size(), 256);
+        assert_eq!(tables.phase_advance(0), 0.0);
+        // A bin centre a quarter of the way up advances a quarter turn per sample
+        assert!((tables.centre_frequency(256) - PI / 2.0).abs() < 1e-6);
+        assert!((tables.phase_advance(1) - PI / 2.0).abs() < 1e-6);
+
+        // Half a bin off centre gains an eighth of a turn over a quarter-frame hop
+        assert!((tables.deviation_bins(PI / 4.0) - 0.5).abs() < 1e-6);
+        assert!((tables.synthesis_advance(10, 10.5) - (10.0 + 0.5) * PI / 2.0).abs() < 1e-4);
+        assert_eq!(tables.frame_size(), 1024);
+        assert!(tables.window().is_none());
+        assert_eq!(tables.synthesis_hop_size(), 256);
+        assert!(!tables.stretches());
+    }
+
+    #[test]
+    fn test_synthesis_hop_scales_synthesis_advance() {
+        let tables = BinTables::<1024>::new(256).with_synthesis_hop(512);
+        assert_eq!((tables.hop_size(), tables.synthesis_hop_size()), (256, 512));
+        assert!(tables.stretches());
+        // The analysis side is unchanged, the synthesis side advances twice as far
+        assert!((tables.phase_advance(1) - PI / 2.0).abs() < 1e-6);
+        assert!((tables.synthesis_advance(10, 10.5) - (10.0 + 0.5) * PI).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_padded_window_covers_end_of_frame() {
+        let tables = BinTables::<1024>::with_frame_size(64, 256);
+        assert_eq!(tables.frame_size(), 256);
+        let window = tables.window().unwrap();
+        assert!(window[..768].iter().all(|&w| w == 0.0));
+        assert_eq!(window[768], 0.0);
+        assert!((window[768 + 128] - 1.0).abs() < 1e-3);
+        assert!(window[1023].abs() < 1e-6);
+        // Bin spacing still follows the FFT size
+        assert!((tables.phase_advance(1) - 2.0 * PI * 64.0 / 1024.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,151 @@
+//! Second-order IIR sections for the time-domain stages.
+//!
+//! Coefficients follow the RBJ audio EQ cookbook and run in transposed direct
+//! form II, which keeps only two state values per section.
+
+use core::f32::consts::PI;
+
+use libm::{cosf, sinf};
+
+/// One biquad section with its state
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    /// Section that passes everything unchanged
+    pub const fn identity() -> Self {
+        Self { b0: 1.0, b1: 0.0, b2: 0.0, a1: 0.0, a2: 0.0, z1: 0.0, z2: 0.0 }
+    }
+
+    /// Low-pass with cutoff `frequency` Hz and quality `q` (0.707 for Butterworth)
+    pub fn low_pass(frequency: f32, q: f32, sample_rate: f32) -> Self {
+        let (cos_w, alpha) = Self::prototype(frequency, q, sample_rate);
+        Self::normalized(
+            (1.0 - cos_w) / 2.0,
+            1.0 - cos_w,
+            (1.0 - cos_w) / 2.0,
+            1.0 + alpha,
+            -2.0 * cos_w,
+            1.0 - alpha,
+        )
+    }
+
+    /// High-pass with cutoff `frequency` Hz and quality `q` (0.707 for Butterworth)
+    pub fn high_pass(frequency: f32, q: f32, sample_rate: f32) -> Self {
+        let (cos_w, alpha) = Self::prototype(frequency, q, sample_rate);
+        Self::normalized(
+            (1.0 + cos_w) / 2.0,
+            -(1.0 + cos_w),
+            (1.0 + cos_w) / 2.0,
+            1.0 + alpha,
+            -2.0 * cos_w,
+            1.0 - alpha,
+        )
+    }
+
+    /// Band-pass centred on `frequency` Hz with 0 dB gain at the centre
+    pub fn band_pass(frequency: f32, q: f32, sample_rate: f32) -> Self {
+        let (cos_w, alpha) = Self::prototype(frequency, q, sample_rate);
+        Self::normalized(alpha, 0.0, -alpha, 1.0 + alpha, -2.0 * cos_w, 1.0 - alpha)
+    }
+
+    /// Notch at `frequency` Hz; higher `q` gives a narrower notch
+    pub fn notch(frequency: f32, q: f32, sample_rate: f32) -> Self {
+        let (cos_w, alpha) = Self::prototype(frequency, q, sample_rate);
+        Self::normalized(1.0, -2.0 * cos_w, 1.0, 1.0 + alpha, -2.0 * cos_w, 1.0 - alpha)
+    }
+
+    fn prototype(frequency: f32, q: f32, sample_rate: f32) -> (f32, f32) {
+        let w = 2.0 * PI * frequency / sample_rate;
+        (cosf(w), sinf(w) / (2.0 * q))
+    }
+
+    fn normalized(b0: f32, b1: f32, b2: f32, a0: f32, a1: f32, a2: f32) -> Self {
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    /// Filters one sample
+    #[inline]
+    pub fn process(&mut self, input: f32) -> f32 {
+        let output = self.b0 * input + self.z1;
+        self.z1 = self.b1 * input - self.a1 * output + self.z2;
+        self.z2 = self.b2 * input - self.a2 * output;
+        output
+    }
+
+    /// Clears the filter state
+    pub fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+}
+
+impl Default for Biquad {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Peak output level of a settled sine through `filter`
+    fn gain(mut filter: Biquad, frequency: f32) -> f32 {
+        let mut peak = 0.0f32;
+        for n in 0..48000 {
+            let output = filter.process(sinf(2.0 * PI * frequency * n as f32 / 48000.0));
+            if n >= 43200 {
+                peak = peak.max(output.abs());
+            }
+        }
+        peak
+    }
+
+    #[test]
+    fn test_low_pass() {
+        let filter = Biquad::low_pass(1000.0, core::f32::consts::FRAC_1_SQRT_2, 48000.0);
+        assert!((gain(filter, 100.0) - 1.0).abs() < 0.01);
+        assert!((gain(filter, 1000.0) - core::f32::consts::FRAC_1_SQRT_2).abs() < 0.01);
+        assert!(gain(filter, 10000.0) < 0.02);
+    }
+
+    #[test]
+    fn test_high_pass() {
+        let filter = Biquad::high_pass(1000.0, core::f32::consts::FRAC_1_SQRT_2, 48000.0);
+        assert!(gain(filter, 100.0) < 0.02);
+        assert!((gain(filter, 1000.0) - core::f32::consts::FRAC_1_SQRT_2).abs() < 0.01);
+        assert!((gain(filter, 10000.0) - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_band_pass() {
+        let filter = Biquad::band_pass(1000.0, core::f32::consts::SQRT_2, 48000.0);
+        assert!((gain(filter, 1000.0) - 1.0).abs() < 0.01);
+        assert!(gain(filter, 100.0) < 0.1);
+        assert!(gain(filter, 10000.0) < 0.1);
+    }
+
+    #[test]
+    fn test_notch() {
+        let filter = Biquad::notch(60.0, 10.0, 48000.0);
+        assert!(gain(filter, 60.0) < 0.01);
+        assert!((gain(filter, 220.0) - 1.0).abs() < 0.02);
+    }
+}
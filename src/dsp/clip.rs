@@ -0,0 +1,56 @@
+//! Output protection shared by every processing mode.
+
+use libm::expf;
+
+use crate::config::SoftClip;
+
+/// Soft-clips one sample
+///
+/// Below the threshold the sample is unchanged. Above it the curve
+/// `threshold + knee * (1 - exp(-(|x| - threshold) / knee))` starts with unity
+/// slope, so there is no kink at the threshold, and approaches
+/// `threshold + knee`.
+#[inline]
+pub fn soft_clip(sample: f32, clip: &SoftClip) -> f32 {
+    let level = sample.abs();
+    if level <= clip.threshold {
+        return sample;
+    }
+    let bent = if clip.knee > 0.0 {
+        clip.threshold + clip.knee * (1.0 - expf(-(level - clip.threshold) / clip.knee))
+    } else {
+        clip.threshold
+    };
+    bent.copysign(sample)
+}
+
+/// Soft-clips a frame in place when `clip` is set
+pub fn soft_clip_frame(frame: &mut [f32], clip: Option<&SoftClip>) {
+    if let Some(clip) = clip {
+        for sample in frame.iter_mut() {
+            *sample = soft_clip(*sample, clip);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_soft_clip_curve() {
+        let clip = SoftClip::DEFAULT;
+        assert_eq!(soft_clip(0.5, &clip), 0.5);
+        assert_eq!(soft_clip(-0.95, &clip), -0.95);
+
+        // Continuous with unity slope at the threshold
+        let just_above = soft_clip(0.951, &clip);
+        assert!((just_above - 0.951).abs() < 1e-4, "{just_above}");
+        assert!(soft_clip(10.0, &clip) <= 1.0);
+        assert!(soft_clip(-10.0, &clip) >= -1.0);
+        assert!(soft_clip(2.0, &clip) > soft_clip(1.5, &clip));
+
+        let hard = SoftClip { threshold: 0.8, knee: 0.0 };
+        assert_eq!(soft_clip(-3.0, &hard), -0.8);
+    }
+}
@@ -0,0 +1,67 @@
+//! First-order pre-emphasis and de-emphasis.
+//!
+//! Voiced speech falls off by about 6 dB per octave, so in a low voice the
+//! fundamental and first harmonics dominate the log spectrum and the cepstral
+//! envelope barely follows the upper formants. Pre-emphasis, `y[n] = x[n] - a·x[n-1]`,
+//! tilts the spectrum flat before analysis; de-emphasis,
+//! `y[n] = x[n] + a·y[n-1]`, restores the tilt after synthesis.
+
+/// Pre-emphasises a frame in place
+///
+/// The first sample has no predecessor in the frame and is left as it is; the
+/// analysis window is zero there.
+pub fn pre_emphasize(frame: &mut [f32], coefficient: f32) {
+    for n in (1..frame.len()).rev() {
+        frame[n] -= coefficient * frame[n - 1];
+    }
+}
+
+/// One-pole de-emphasis filter that undoes [`pre_emphasize`] on a stream
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeEmphasis {
+    coefficient: f32,
+    previous: f32,
+}
+
+impl DeEmphasis {
+    /// Creates the filter; `coefficient` must be below 1.0 for it to be stable
+    pub const fn new(coefficient: f32) -> Self {
+        Self { coefficient, previous: 0.0 }
+    }
+
+    /// Filters `samples` in place
+    pub fn process(&mut self, samples: &mut [f32]) {
+        for sample in samples.iter_mut() {
+            *sample += self.coefficient * self.previous;
+            self.previous = *sample;
+        }
+    }
+
+    /// Clears the filter state
+    pub fn reset(&mut self) {
+        self.previous = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_de_emphasis_inverts_pre_emphasis() {
+        let signal: [f32; 256] = core::array::from_fn(|n| libm::sinf(n as f32 * 0.05));
+        let mut emphasized = signal;
+        pre_emphasize(&mut emphasized, 0.97);
+        // A low sine is mostly removed
+        assert!(emphasized[1..].iter().all(|s| s.abs() < 0.1));
+
+        // Restore in two blocks to carry the state across
+        let mut filter = DeEmphasis::new(0.97);
+        let (first, second) = emphasized.split_at_mut(100);
+        filter.process(first);
+        filter.process(second);
+        for (restored, original) in emphasized.iter().zip(signal.iter()) {
+            assert!((restored - original).abs() < 1e-4, "{restored} {original}");
+        }
+    }
+}
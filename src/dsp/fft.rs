@@ -1,4 +1,7 @@
 /// Trait for FFT operations to abstract over different sizes
+///
+/// This is the trait to implement for a custom FFT backend that needs no state.
+/// Every implementation is also a [`DynFft`], which is what the processors take.
 pub trait FftOps<const N: usize, const HALF_N: usize> {
     /// Perform forward real FFT
     fn forward_fft(input: &mut [f32; N]) -> &mut [microfft::Complex32];
@@ -6,11 +9,64 @@ pub trait FftOps<const N: usize, const HALF_N: usize> {
     /// Perform inverse complex FFT
     fn inverse_fft(spectrum: &mut [microfft::Complex32; N]) -> &mut [microfft::Complex32; N];
 
+    /// Perform inverse real FFT of `N / 2` bins packed like the forward output
+    ///
+    /// The default completes the conjugate-symmetric spectrum and runs
+    /// [`FftOps::inverse_fft`]; the built-in sizes override it with a half-size
+    /// complex transform. `spectrum` is used as scratch.
+    fn inverse_real_fft(spectrum: &mut [microfft::Complex32; HALF_N], output: &mut [f32; N]) {
+        let mut full = complete_packed_spectrum(spectrum);
+        real_part(Self::inverse_fft(&mut full), output);
+    }
+
     /// Get the Hann window for this FFT size
     fn get_hann_window() -> &'static [f32; N];
 }
 
+/// FFT operations for 128-point FFT
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Fft128;
+impl FftOps<128, 64> for Fft128 {
+    fn forward_fft(input: &mut [f32; 128]) -> &mut [microfft::Complex32] {
+        microfft::real::rfft_128(input)
+    }
+
+    fn inverse_fft(spectrum: &mut [microfft::Complex32; 128]) -> &mut [microfft::Complex32; 128] {
+        microfft::inverse::ifft_128(spectrum)
+    }
+
+    fn inverse_real_fft(spectrum: &mut [microfft::Complex32; 64], output: &mut [f32; 128]) {
+        packed_real_inverse(spectrum, output, microfft::inverse::ifft_64)
+    }
+
+    fn get_hann_window() -> &'static [f32; 128] {
+        &crate::dsp::windowing::HANN_WINDOW_128
+    }
+}
+
+/// FFT operations for 256-point FFT
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Fft256;
+impl FftOps<256, 128> for Fft256 {
+    fn forward_fft(input: &mut [f32; 256]) -> &mut [microfft::Complex32] {
+        microfft::real::rfft_256(input)
+    }
+
+    fn inverse_fft(spectrum: &mut [microfft::Complex32; 256]) -> &mut [microfft::Complex32; 256] {
+        microfft::inverse::ifft_256(spectrum)
+    }
+
+    fn inverse_real_fft(spectrum: &mut [microfft::Complex32; 128], output: &mut [f32; 256]) {
+        packed_real_inverse(spectrum, output, microfft::inverse::ifft_128)
+    }
+
+    fn get_hann_window() -> &'static [f32; 256] {
+        &crate::dsp::windowing::HANN_WINDOW_256
+    }
+}
+
 /// FFT operations for 512-point FFT
+#[derive(Debug, Clone, Copy, Default)]
 pub struct Fft512;
 impl FftOps<512, 256> for Fft512 {
     fn forward_fft(input: &mut [f32; 512]) -> &mut [microfft::Complex32] {
@@ -21,12 +77,17 @@ impl FftOps<512, 256> for Fft512 {
         microfft::inverse::ifft_512(spectrum)
     }
 
+    fn inverse_real_fft(spectrum: &mut [microfft::Complex32; 256], output: &mut [f32; 512]) {
+        packed_real_inverse(spectrum, output, microfft::inverse::ifft_256)
+    }
+
     fn get_hann_window() -> &'static [f32; 512] {
         &crate::dsp::windowing::HANN_WINDOW_512
     }
 }
 
 /// FFT operations for 1024-point FFT
+#[derive(Debug, Clone, Copy, Default)]
 pub struct Fft1024;
 impl FftOps<1024, 512> for Fft1024 {
     fn forward_fft(input: &mut [f32; 1024]) -> &mut [microfft::Complex32] {
@@ -37,12 +98,17 @@ impl FftOps<1024, 512> for Fft1024 {
         microfft::inverse::ifft_1024(spectrum)
     }
 
+    fn inverse_real_fft(spectrum: &mut [microfft::Complex32; 512], output: &mut [f32; 1024]) {
+        packed_real_inverse(spectrum, output, microfft::inverse::ifft_512)
+    }
+
     fn get_hann_window() -> &'static [f32; 1024] {
         &crate::dsp::windowing::HANN_WINDOW_1024
     }
 }
 
 /// FFT operations for 2048-point FFT
+#[derive(Debug, Clone, Copy, Default)]
 pub struct Fft2048;
 impl FftOps<2048, 1024> for Fft2048 {
     fn forward_fft(input: &mut [f32; 2048]) -> &mut [microfft::Complex32] {
@@ -53,12 +119,17 @@ impl FftOps<2048, 1024> for Fft2048 {
         microfft::inverse::ifft_2048(spectrum)
     }
 
+    fn inverse_real_fft(spectrum: &mut [microfft::Complex32; 1024], output: &mut [f32; 2048]) {
+        packed_real_inverse(spectrum, output, microfft::inverse::ifft_1024)
+    }
+
     fn get_hann_window() -> &'static [f32; 2048] {
         &crate::dsp::windowing::HANN_WINDOW_2048
     }
 }
 
 /// FFT operations for 4096-point FFT
+#[derive(Debug, Clone, Copy, Default)]
 pub struct Fft4096;
 impl FftOps<4096, 2048> for Fft4096 {
     fn forward_fft(input: &mut [f32; 4096]) -> &mut [microfft::Complex32] {
@@ -69,7 +140,415 @@ impl FftOps<4096, 2048> for Fft4096 {
         microfft::inverse::ifft_4096(spectrum)
     }
 
+    fn inverse_real_fft(spectrum: &mut [microfft::Complex32; 2048], output: &mut [f32; 4096]) {
+        packed_real_inverse(spectrum, output, microfft::inverse::ifft_2048)
+    }
+
     fn get_hann_window() -> &'static [f32; 4096] {
         &crate::dsp::windowing::HANN_WINDOW_4096
     }
 }
+
+/// FFT operations for 8192-point FFT
+///
+/// For offline processing: a frame needs a few hundred kilobytes of stack, more
+/// than embedded targets and default worker threads have.
+#[cfg(feature = "fft-8192")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Fft8192;
+#[cfg(feature = "fft-8192")]
+impl FftOps<8192, 4096> for Fft8192 {
+    fn forward_fft(input: &mut [f32; 8192]) -> &mut [microfft::Complex32] {
+        microfft::real::rfft_8192(input)
+    }
+
+    fn inverse_fft(spectrum: &mut [microfft::Complex32; 8192]) -> &mut [microfft::Complex32; 8192] {
+        microfft::inverse::ifft_8192(spectrum)
+    }
+
+    fn inverse_real_fft(spectrum: &mut [microfft::Complex32; 4096], output: &mut [f32; 8192]) {
+        packed_real_inverse(spectrum, output, microfft::inverse::ifft_4096)
+    }
+
+    fn get_hann_window() -> &'static [f32; 8192] {
+        crate::dsp::windowing::static_hann_window::<8192>()
+    }
+}
+
+/// Object-safe FFT backend, as taken by the processors
+///
+/// Every [`FftOps`] type implements it, so the built-in backends can be passed as
+/// `&mut Fft1024`. Backends that need state or are chosen at runtime (a hardware
+/// FFT unit, a CMSIS-DSP instance) implement this trait directly and can be
+/// passed as `&mut dyn DynFft<N, HALF_N>`.
+///
+/// Like `microfft`, [`DynFft::forward`] transforms in place: the `N / 2` complex
+/// bins are returned in the memory of the `N` real input samples, with the
+/// Nyquist value packed into the imaginary part of bin 0.
+pub trait DynFft<const N: usize, const HALF_N: usize> {
+    /// Perform forward real FFT
+    fn forward<'a>(&mut self, input: &'a mut [f32; N]) -> &'a mut [microfft::Complex32];
+
+    /// Perform inverse complex FFT
+    fn inverse<'a>(
+        &mut self,
+        spectrum: &'a mut [microfft::Complex32; N],
+    ) -> &'a mut [microfft::Complex32; N];
+
+    /// Perform inverse real FFT
+    ///
+    /// `spectrum` holds the `N / 2` bins of a real signal packed like the output
+    /// of [`DynFft::forward`] and is used as scratch; the real signal is written to
+    /// `output`. The default completes the conjugate-symmetric spectrum on the
+    /// stack and runs [`DynFft::inverse`], so backends without a real inverse work
+    /// unchanged.
+    fn inverse_real(
+        &mut self,
+        spectrum: &mut [microfft::Complex32; HALF_N],
+        output: &mut [f32; N],
+    ) {
+        let mut full = complete_packed_spectrum(spectrum);
+        real_part(self.inverse(&mut full), output);
+    }
+
+    /// Get the Hann window for this FFT size
+    fn hann_window(&self) -> &'static [f32; N];
+}
+
+impl<const N: usize, const HALF_N: usize, F> DynFft<N, HALF_N> for F
+where
+    F: FftOps<N, HALF_N>,
+{
+    fn forward<'a>(&mut self, input: &'a mut [f32; N]) -> &'a mut [microfft::Complex32] {
+        F::forward_fft(input)
+    }
+
+    fn inverse<'a>(
+        &mut self,
+        spectrum: &'a mut [microfft::Complex32; N],
+    ) -> &'a mut [microfft::Complex32; N] {
+        F::inverse_fft(spectrum)
+    }
+
+    fn inverse_real(
+        &mut self,
+        spectrum: &mut [microfft::Complex32; HALF_N],
+        output: &mut [f32; N],
+    ) {
+        F::inverse_real_fft(spectrum, output)
+    }
+
+    fn hann_window(&self) -> &'static [f32; N] {
+        F::get_hann_window()
+    }
+}
+
+/// Moves the Nyquist value that [`DynFft::forward`] packs into bin 0 out of it
+///
+/// Afterwards bin 0 holds the real DC value only, so its magnitude and phase are
+/// those of DC. Returns the real Nyquist value.
+#[inline]
+pub fn unpack_nyquist(bins: &mut [microfft::Complex32]) -> f32 {
+    let nyquist = bins[0].im;
+    bins[0].im = 0.0;
+    nyquist
+}
+
+/// Completes the spectrum of a real signal from bins `0..N / 2`
+///
+/// DC is made real, the Nyquist bin set to the real `nyquist` and the upper half
+/// mirrored as the complex conjugate of the lower half, so the inverse FFT has no
+/// imaginary part to discard.
+pub fn complete_real_spectrum<const N: usize>(
+    spectrum: &mut [microfft::Complex32; N],
+    nyquist: f32,
+) {
+    let half = N / 2;
+    spectrum[0].im = 0.0;
+    spectrum[half] = microfft::Complex32 { re: nyquist, im: 0.0 };
+    for i in 1..half {
+        spectrum[N - i] = spectrum[i].conj();
+    }
+}
+
+/// Full `N`-point spectrum of the real signal whose bins are packed in `spectrum`
+fn complete_packed_spectrum<const N: usize, const HALF_N: usize>(
+    spectrum: &[microfft::Complex32; HALF_N],
+) -> [microfft::Complex32; N] {
+    let mut full = [microfft::Complex32 { re: 0.0, im: 0.0 }; N];
+    full[..HALF_N].copy_from_slice(spectrum);
+    complete_real_spectrum(&mut full, spectrum[0].im);
+    full
+}
+
+/// Copies the real part of a complex inverse FFT to `output`
+fn real_part<const N: usize>(signal: &[microfft::Complex32; N], output: &mut [f32; N]) {
+    for (sample, value) in output.iter_mut().zip(signal) {
+        *sample = value.re;
+    }
+}
+
+/// Twiddles `e^(2πik/N)` of the real inverse FFT, generated at compile time
+struct RealInverseTwiddles<const N: usize, const HALF_N: usize>;
+
+impl<const N: usize, const HALF_N: usize> RealInverseTwiddles<N, HALF_N> {
+    const TABLE: [microfft::Complex32; HALF_N] = {
+        let mut table = [microfft::Complex32 { re: 0.0, im: 0.0 }; HALF_N];
+        let mut k = 0;
+        while k < HALF_N {
+            table[k] = unit_phasor(k, N);
+            k += 1;
+        }
+        table
+    };
+}
+
+/// `e^(2πik/n)` for `k < n / 2`, evaluated in `f64` so it can run at compile time
+const fn unit_phasor(k: usize, n: usize) -> microfft::Complex32 {
+    use core::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+    // Reflect into the first octant, where the series converges in a few terms
+    let mut angle = 2.0 * PI * k as f64 / n as f64;
+    let cos_sign = if angle > FRAC_PI_2 {
+        angle = PI - angle;
+        -1.0
+    } else {
+        1.0
+    };
+    let swapped = angle > FRAC_PI_4;
+    if swapped {
+        angle = FRAC_PI_2 - angle;
+    }
+
+    let square = angle * angle;
+    let (mut cos, mut sin) = (0.0, 0.0);
+    let (mut cos_term, mut sin_term) = (1.0, angle);
+    let mut i = 0;
+    while i < 10 {
+        cos += cos_term;
+        sin += sin_term;
+        cos_term *= -square / ((2 * i + 1) * (2 * i + 2)) as f64;
+        sin_term *= -square / ((2 * i + 2) * (2 * i + 3)) as f64;
+        i += 1;
+    }
+    if swapped {
+        (cos, sin) = (sin, cos);
+    }
+    microfft::Complex32 { re: (cos_sign * cos) as f32, im: sin as f32 }
+}
+
+/// Inverse real FFT through an `N / 2`-point complex inverse FFT
+///
+/// The even and odd output samples are the inverse transforms of two half-size
+/// spectra, both recoverable from the packed bins because the full spectrum is
+/// conjugate-symmetric. They are combined as `even + j·odd`, transformed at half
+/// the size, and the result interleaved into `output`.
+fn packed_real_inverse<const N: usize, const HALF_N: usize>(
+    spectrum: &mut [microfft::Complex32; HALF_N],
+    output: &mut [f32; N],
+    ifft: fn(&mut [microfft::Complex32; HALF_N]) -> &mut [microfft::Complex32; HALF_N],
+) {
+    let twiddles = &RealInverseTwiddles::<N, HALF_N>::TABLE;
+    let j = microfft::Complex32 { re: 0.0, im: 1.0 };
+
+    let (dc, nyquist) = (spectrum[0].re, spectrum[0].im);
+    spectrum[0] = microfft::Complex32 { re: 0.5 * (dc + nyquist), im: 0.5 * (dc - nyquist) };
+    for k in 1..=HALF_N / 2 {
+        let (upper, lower) = (spectrum[k], spectrum[HALF_N - k].conj());
+        let even = (upper + lower) * 0.5;
+        let odd = (upper - lower) * 0.5 * twiddles[k];
+        // The mirrored bin takes the conjugates of both halves
+        spectrum[k] = even + j * odd;
+        spectrum[HALF_N - k] = even.conj() + j * odd.conj();
+    }
+
+    let signal = ifft(spectrum);
+    for (pair, value) in output.chunks_exact_mut(2).zip(signal.iter()) {
+        pair[0] = value.re;
+        pair[1] = value.im;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Engine, Engine512, MusicalSettings, VocalEffectsConfig, Workspace,
+        vocal_effects::process_frame,
+    };
+
+    /// Stateful backend wrapping the built-in one
+    struct CountingFft {
+        transforms: usize,
+    }
+
+    impl DynFft<512, 256> for CountingFft {
+        fn forward<'a>(&mut self, input: &'a mut [f32; 512]) -> &'a mut [microfft::Complex32] {
+            self.transforms += 1;
+            Fft512::forward_fft(input)
+        }
+
+        fn inverse<'a>(
+            &mut self,
+            spectrum: &'a mut [microfft::Complex32; 512],
+        ) -> &'a mut [microfft::Complex32; 512] {
+            self.transforms += 1;
+            Fft512::inverse_fft(spectrum)
+        }
+
+        fn inverse_real(
+            &mut self,
+            spectrum: &mut [microfft::Complex32; 256],
+            output: &mut [f32; 512],
+        ) {
+            self.transforms += 1;
+            Fft512::inverse_real_fft(spectrum, output)
+        }
+
+        fn hann_window(&self) -> &'static [f32; 512] {
+            Fft512::get_hann_window()
+        }
+    }
+
+    #[test]
+    fn test_runtime_backend_matches_static_backend() {
+        let config = VocalEffectsConfig::default();
+        let settings = MusicalSettings::default();
+        let frame: [f32; 512] = core::array::from_fn(|i| libm::sinf(i as f32 * 0.07));
+
+        let mut counting = CountingFft { transforms: 0 };
+        let backend: &mut dyn DynFft<512, 256> = &mut counting;
+        let mut state = Default::default();
+        let dynamic = process_frame(
+            backend,
+            &mut Workspace::new(),
+            &mut frame.clone(),
+            None,
+            &mut state,
+            &config,
+            &settings,
+        );
+
+        let mut state = Default::default();
+        let fixed = process_frame(
+            &mut Fft512,
+            &mut Workspace::new(),
+            &mut frame.clone(),
+            None,
+            &mut state,
+            &config,
+            &settings,
+        );
+
+        assert_eq!(dynamic, fixed);
+        assert!(counting.transforms >= 2);
+    }
+
+    /// Backend with only a complex inverse, relying on the default real inverse
+    struct ComplexInverseFft;
+
+    impl DynFft<512, 256> for ComplexInverseFft {
+        fn forward<'a>(&mut self, input: &'a mut [f32; 512]) -> &'a mut [microfft::Complex32] {
+            Fft512::forward_fft(input)
+        }
+
+        fn inverse<'a>(
+            &mut self,
+            spectrum: &'a mut [microfft::Complex32; 512],
+        ) -> &'a mut [microfft::Complex32; 512] {
+            Fft512::inverse_fft(spectrum)
+        }
+
+        fn hann_window(&self) -> &'static [f32; 512] {
+            Fft512::get_hann_window()
+        }
+    }
+
+    #[test]
+    fn test_default_real_inverse_matches_half_size_transform() {
+        let frame: [f32; 512] = core::array::from_fn(|i| libm::sinf(i as f32 * 0.07) + 0.1);
+        let mut spectrum = [microfft::Complex32 { re: 0.0, im: 0.0 }; 256];
+        spectrum.copy_from_slice(Fft512::forward_fft(&mut frame.clone()));
+
+        let mut default = [0.0f32; 512];
+        ComplexInverseFft.inverse_real(&mut spectrum.clone(), &mut default);
+        let mut half_size = [0.0f32; 512];
+        Fft512.inverse_real(&mut spectrum, &mut half_size);
+        for (n, (a, b)) in default.iter().zip(half_size.iter()).enumerate() {
+            assert!((a - b).abs() < 1e-5, "sample {n}: {a} {b}");
+            assert!((a - frame[n]).abs() < 1e-4, "sample {n}");
+        }
+    }
+
+    #[test]
+    fn test_engine_with_stateful_backend() {
+        let config = VocalEffectsConfig::default();
+        let settings = MusicalSettings::default();
+        let backend = CountingFft { transforms: 0 };
+        let mut custom = Engine::with_fft(backend, config, settings);
+        let mut builtin = Engine512::new(config, settings);
+
+        let input: [f32; 128] = core::array::from_fn(|i| libm::sinf(i as f32 * 0.05));
+        let mut custom_output = [0.0f32; 128];
+        let mut builtin_output = [0.0f32; 128];
+        for _ in 0..8 {
+            custom.process_hop(&input, None, &mut custom_output).unwrap();
+            builtin.process_hop(&input, None, &mut builtin_output).unwrap();
+            assert_eq!(custom_output, builtin_output);
+        }
+    }
+}
+
+#[cfg(test)]
+mod real_spectrum_tests {
+    use super::*;
+
+    /// A frame with DC, Nyquist and in-between content survives forward and
+    /// inverse transforms through the unpack/complete helpers
+    fn round_trip<const N: usize, const HALF_N: usize, F: FftOps<N, HALF_N>>() {
+        let input: [f32; N] = core::array::from_fn(|n| {
+            let alternating = if n % 2 == 0 { 0.1 } else { -0.1 };
+            0.2 + alternating + 0.3 * libm::sinf(0.3 * n as f32)
+        });
+        let mut frame = input;
+        let bins = F::forward_fft(&mut frame);
+        let nyquist = unpack_nyquist(bins);
+        let dc: f32 = input.iter().sum();
+        let alternating: f32 =
+            input.iter().enumerate().map(|(n, x)| if n % 2 == 0 { *x } else { -*x }).sum();
+        assert!((bins[0].re - dc).abs() < 1e-3 * N as f32, "N={N}");
+        assert_eq!(bins[0].im, 0.0);
+        assert!((nyquist - alternating).abs() < 1e-3 * N as f32, "N={N}");
+
+        let mut spectrum = [microfft::Complex32 { re: 0.0, im: 0.0 }; N];
+        spectrum[..HALF_N].copy_from_slice(&bins[..HALF_N]);
+        complete_real_spectrum(&mut spectrum, nyquist);
+        let output = F::inverse_fft(&mut spectrum);
+        for (n, (sample, expected)) in output.iter().zip(input.iter()).enumerate() {
+            assert!((sample.re - expected).abs() < 1e-4, "N={N} sample {n}");
+            assert!(sample.im.abs() < 1e-4, "N={N} sample {n}");
+        }
+
+        // The real inverse takes the packed bins directly
+        let mut frame = input;
+        let mut packed = [microfft::Complex32 { re: 0.0, im: 0.0 }; HALF_N];
+        packed.copy_from_slice(F::forward_fft(&mut frame));
+        let mut output = [0.0f32; N];
+        F::inverse_real_fft(&mut packed, &mut output);
+        for (n, (sample, expected)) in output.iter().zip(input.iter()).enumerate() {
+            assert!((sample - expected).abs() < 1e-4, "N={N} sample {n}");
+        }
+    }
+
+    #[test]
+    fn test_dc_and_nyquist_round_trip_for_all_sizes() {
+        round_trip::<128, 64, Fft128>();
+        round_trip::<256, 128, Fft256>();
+        round_trip::<512, 256, Fft512>();
+        round_trip::<1024, 512, Fft1024>();
+        round_trip::<2048, 1024, Fft2048>();
+        round_trip::<4096, 2048, Fft4096>();
+        #[cfg(feature = "fft-8192")]
+        round_trip::<8192, 4096, Fft8192>();
+    }
+}
@@ -2,7 +2,7 @@ use core::f32::consts::PI;
 
 use libm::{fabsf, floorf, fmodf, roundf};
 
-use crate::audio::find_nearest_note_frequency;
+use crate::{audio::find_nearest_note_frequency, config::PitchDetector};
 
 #[inline(always)]
 pub fn calculate_updates<const N: usize>(
@@ -43,16 +43,422 @@ pub fn find_fundamental_frequency(analysis_magnitudes: &[f32]) -> usize {
     fundamental_bin
 }
 
+/// Number of down-sampled spectra multiplied together by the harmonic product spectrum
+pub const HPS_HARMONICS: usize = 4;
+
+/// Estimate the fundamental bin with the harmonic product spectrum.
+///
+/// The spectrum is multiplied with copies of itself down-sampled by 2..=`HPS_HARMONICS`,
+/// so only bins whose harmonics all carry energy survive. This suppresses the
+/// octave errors of picking the loudest bin when a harmonic dominates the fundamental.
+#[inline(always)]
+pub fn find_fundamental_frequency_hps(analysis_magnitudes: &[f32]) -> usize {
+    let search_bins = analysis_magnitudes.len() / HPS_HARMONICS;
+    let mut max_product = 0.0;
+    let mut fundamental_bin = 0;
+    // Skip DC, it is its own harmonic
+    for i in 1..search_bins {
+        let mut product = analysis_magnitudes[i];
+        for harmonic in 2..=HPS_HARMONICS {
+            product *= analysis_magnitudes[i * harmonic];
+        }
+        if product > max_product {
+            max_product = product;
+            fundamental_bin = i;
+        }
+    }
+
+    if max_product > 0.0 {
+        fundamental_bin
+    } else {
+        // No harmonic structure, fall back to the loudest bin
+        find_fundamental_frequency(analysis_magnitudes)
+    }
+}
+
+/// Candidates quieter than this fraction of the loudest bin (-20 dB) are not scored
+const HARMONIC_SUM_CANDIDATE_LEVEL: f32 = 0.1;
+
+/// Most spectral peaks scored as candidate fundamentals
+const HARMONIC_SUM_MAX_CANDIDATES: usize = 32;
+
+/// Candidates scoring below this fraction of the best one are passed over, so the
+/// lowest strong fundamental wins over its multiples
+const HARMONIC_SUM_PEAK_THRESHOLD: f32 = 0.8;
+
+/// Weight of the energy found halfway between a candidate's harmonics
+const HARMONIC_SUM_OCTAVE_PENALTY: f32 = 1.0;
+
+/// Largest magnitude within a bin of `position`, or `0.0` outside the spectrum
+fn magnitude_near(analysis_magnitudes: &[f32], position: f32) -> f32 {
+    let centre = roundf(position);
+    if centre < 0.0 {
+        return 0.0;
+    }
+    let centre = centre as usize;
+    let end = centre.saturating_add(2).min(analysis_magnitudes.len());
+    analysis_magnitudes[centre.saturating_sub(1).min(end)..end]
+        .iter()
+        .copied()
+        .fold(0.0, f32::max)
+}
+
+/// Harmonic evidence for a fundamental at `fundamental_index + offset`
+///
+/// Sums the magnitudes at the first eight harmonics, weighted by `1 / n`, and
+/// subtracts the magnitudes halfway between them: a spectrum with energy there
+/// has its fundamental an octave lower.
+fn harmonic_score(analysis_magnitudes: &[f32], fundamental_index: usize, offset: f32) -> f32 {
+    let spacing = fundamental_index as f32 + offset;
+    let mut evidence = 0.0;
+    let mut penalty = 0.0;
+    for (n, &harmonic) in collect_harmonics(fundamental_index).iter().enumerate() {
+        let number = (n + 1) as f32;
+        let position = harmonic as f32 + number * offset;
+        evidence += magnitude_near(analysis_magnitudes, position) / number;
+        let between = roundf(position - 0.5 * spacing) as usize;
+        penalty += analysis_magnitudes.get(between).copied().unwrap_or(0.0) / number;
+    }
+    evidence - HARMONIC_SUM_OCTAVE_PENALTY * penalty
+}
+
+/// Estimate the fundamental bin by scoring spectral peaks on their harmonics.
+///
+/// Every local maximum within 20 dB of the loudest bin is a candidate, scored by
+/// the weighted sum of the magnitudes at its harmonics minus an octave-error
+/// penalty for energy halfway between them. The lowest candidate scoring close
+/// to the best wins, so bright vowels whose second or third harmonic is the
+/// loudest bin still resolve to their fundamental.
+pub fn find_fundamental_frequency_harmonic_sum(analysis_magnitudes: &[f32]) -> usize {
+    let loudest = find_fundamental_frequency(analysis_magnitudes);
+    let floor =
+        analysis_magnitudes.get(loudest).copied().unwrap_or(0.0) * HARMONIC_SUM_CANDIDATE_LEVEL;
+    if floor <= 0.0 || analysis_magnitudes.len() < 3 {
+        return loudest;
+    }
+
+    let mut candidates = [(0usize, 0.0f32); HARMONIC_SUM_MAX_CANDIDATES];
+    let mut count = 0;
+    // Skip DC, it is its own harmonic
+    for (i, window) in analysis_magnitudes.windows(3).enumerate() {
+        let bin = i + 1;
+        if window[1] < floor || window[1] < window[0] || window[1] <= window[2] {
+            continue;
+        }
+        let offset = parabolic_peak_offset(analysis_magnitudes, bin);
+        candidates[count] = (bin, harmonic_score(analysis_magnitudes, bin, offset));
+        count += 1;
+        if count == candidates.len() {
+            break;
+        }
+    }
+
+    let best = candidates[..count].iter().map(|&(_, score)| score).fold(0.0f32, f32::max);
+    candidates[..count]
+        .iter()
+        .find(|&&(_, score)| best > 0.0 && score >= HARMONIC_SUM_PEAK_THRESHOLD * best)
+        .map_or(loudest, |&(bin, _)| bin)
+}
+
+/// Locate the fundamental bin with the selected pitch detector
+#[inline(always)]
+pub fn detect_fundamental_bin(analysis_magnitudes: &[f32], detector: PitchDetector) -> usize {
+    match detector {
+        PitchDetector::PeakBin => find_fundamental_frequency(analysis_magnitudes),
+        PitchDetector::HarmonicProduct => find_fundamental_frequency_hps(analysis_magnitudes),
+        PitchDetector::HarmonicSum => find_fundamental_frequency_harmonic_sum(analysis_magnitudes),
+        // Runs on the time-domain frame where one is available
+        PitchDetector::Autocorrelation => find_fundamental_frequency(analysis_magnitudes),
+    }
+}
+
+/// Fractional offset of a spectral peak found by fitting a parabola through the
+/// peak bin and its two neighbours.
+///
+/// Returns a value in `[-0.5, 0.5]` to add to `peak_index`, or `0.0` when the peak
+/// sits on the edge of the spectrum or the neighbourhood is flat.
+#[inline(always)]
+pub fn parabolic_peak_offset(analysis_magnitudes: &[f32], peak_index: usize) -> f32 {
+    if peak_index == 0 || peak_index.saturating_add(1) >= analysis_magnitudes.len() {
+        return 0.0;
+    }
+    let left = analysis_magnitudes[peak_index - 1];
+    let centre = analysis_magnitudes[peak_index];
+    let right = analysis_magnitudes[peak_index + 1];
+
+    let curvature = left - 2.0 * centre + right;
+    if fabsf(curvature) < 1e-12 {
+        return 0.0;
+    }
+    (0.5 * (left - right) / curvature).clamp(-0.5, 0.5)
+}
+
+/// Mean-square level below which a hop counts as silence for transient detection
+/// (-80 dBFS)
+const TRANSIENT_FLOOR: f32 = 1e-8;
+
+/// Whether a time-domain frame contains an attack
+///
+/// The frame is split into hops and a transient is reported when the energy of
+/// one hop exceeds that of the hop before it by more than `threshold_db`. Every
+/// frame overlapping the attack is flagged, not just the first to reach it.
+pub fn detect_transient(frame: &[f32], hop_size: usize, threshold_db: f32) -> bool {
+    if hop_size == 0 {
+        return false;
+    }
+    let threshold = libm::powf(10.0, threshold_db / 10.0);
+    let mut previous_energy = None;
+    for hop in frame.chunks_exact(hop_size) {
+        let energy = hop.iter().map(|sample| sample * sample).sum::<f32>() / hop_size as f32;
+        if let Some(previous) = previous_energy {
+            let rising = energy > threshold * f32::max(previous, TRANSIENT_FLOOR);
+            if energy > TRANSIENT_FLOOR && rising {
+                return true;
+            }
+        }
+        previous_energy = Some(energy);
+    }
+    false
+}
+
+/// Fundamental frequency of a frame with the detector's confidence in it
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PitchEstimate {
+    /// Detected fundamental in Hz, `0.0` when nothing was found
+    pub frequency: f32,
+    /// How periodic the frame looked, from 0.0 (noise, silence) to 1.0 (a single
+    /// clean voice)
+    pub confidence: f32,
+}
+
+/// Harmonics of the fundamental weighed by [`harmonic_confidence`]
+pub const CONFIDENCE_HARMONICS: usize = 16;
+
+/// Fraction of the spectral energy that lies on the harmonics of `fundamental_bin`
+///
+/// Looks at the first [`CONFIDENCE_HARMONICS`] harmonics and the spectrum up to
+/// them, leaving out DC. Each harmonic collects the bin nearest to it and, when
+/// the harmonics are at least four bins apart, its two neighbours, which hold
+/// the main lobe of a Hann-windowed partial. The share of the energy in those
+/// bins is corrected for the share of the bins they make up, so a single voice
+/// scores close to 1.0, flat noise close to 0.0 and a second voice between the
+/// harmonics in between. Fundamentals less than two bins apart leave no bins
+/// between the harmonics to judge by and score 0.0.
+pub fn harmonic_confidence(analysis_magnitudes: &[f32], fundamental_bin: f32) -> f32 {
+    if fundamental_bin.is_nan() || fundamental_bin < 2.0 {
+        return 0.0;
+    }
+    let end = (((CONFIDENCE_HARMONICS as f32 + 0.5) * fundamental_bin) as usize)
+        .min(analysis_magnitudes.len());
+    let total: f32 = analysis_magnitudes[1.min(end)..end].iter().map(|m| m * m).sum();
+    if total.is_nan() || total <= 0.0 {
+        return 0.0;
+    }
+    let reach = if fundamental_bin >= 4.0 { 1 } else { 0 };
+    let mut harmonic = 0.0;
+    let mut harmonic_bins = 0;
+    let mut counted_to = 1;
+    for n in 1..=CONFIDENCE_HARMONICS {
+        let centre = roundf(n as f32 * fundamental_bin) as usize;
+        if centre >= end {
+            break;
+        }
+        // Close harmonics share bins, count each bin once
+        let start = centre.saturating_sub(reach).max(counted_to);
+        let stop = (centre + reach + 1).min(end);
+        harmonic += analysis_magnitudes[start.min(stop)..stop].iter().map(|m| m * m).sum::<f32>();
+        harmonic_bins += stop.saturating_sub(start);
+        counted_to = counted_to.max(stop);
+    }
+    // Share of the energy that noise spread evenly over the bins would leave there
+    let chance = harmonic_bins as f32 / (end - 1) as f32;
+    if chance >= 1.0 {
+        return 0.0;
+    }
+    ((harmonic / total - chance) / (1.0 - chance)).clamp(0.0, 1.0)
+}
+
+/// Key maxima below this fraction of the highest one are passed over, so the
+/// first strong period wins over its multiples
+const AUTOCORRELATION_PEAK_THRESHOLD: f32 = 0.9;
+
+/// Frames whose strongest period correlates less than this are treated as unvoiced
+const AUTOCORRELATION_CLARITY: f32 = 0.5;
+
+/// Fundamental frequency of a time-domain frame by normalised autocorrelation
+/// (the McLeod pitch method)
+///
+/// The resolution does not depend on the FFT bin width, which makes this the
+/// detector for 128- and 256-point frames. Periods longer than three quarters of the
+/// frame are not searched, so the lowest detectable frequency is about
+/// `sample_rate / (0.75 * N)`. Returns `0.0` for unvoiced or silent frames.
+pub fn detect_frequency_autocorrelation<const N: usize>(
+    frame: &[f32; N],
+    sample_rate: f32,
+    min_frequency: f32,
+    max_frequency: f32,
+) -> f32 {
+    detect_pitch_autocorrelation(frame, sample_rate, min_frequency, max_frequency).frequency
+}
+
+/// [`detect_frequency_autocorrelation`] with the normalised correlation at the
+/// chosen period as the confidence
+pub fn detect_pitch_autocorrelation<const N: usize>(
+    frame: &[f32; N],
+    sample_rate: f32,
+    min_frequency: f32,
+    max_frequency: f32,
+) -> PitchEstimate {
+    let mut nsdf = [0.0f32; N];
+    pitch_from_nsdf(frame, &mut nsdf, sample_rate, min_frequency, max_frequency)
+}
+
+/// [`detect_pitch_autocorrelation`] on a copy of the frame low-passed and
+/// decimated by `factor`
+///
+/// The correlation costs roughly the square of the frame length, so a 2x or 4x
+/// decimated frame cuts the detector cost by about 4x or 16x. Voice fundamentals
+/// stay well below the decimated Nyquist frequency; the coarser lag grid is made
+/// up for by the parabolic refinement of the period.
+pub fn detect_pitch_autocorrelation_decimated<const N: usize>(
+    frame: &[f32; N],
+    factor: usize,
+    sample_rate: f32,
+    min_frequency: f32,
+    max_frequency: f32,
+) -> PitchEstimate {
+    if factor <= 1 || N / factor < 8 {
+        return detect_pitch_autocorrelation(frame, sample_rate, min_frequency, max_frequency);
+    }
+    let len = N / factor;
+    // One scratch array holds both the decimated frame and its NSDF
+    let mut scratch = [0.0f32; N];
+    let (nsdf, decimated) = scratch.split_at_mut(len);
+    let decimated = &mut decimated[..len];
+    decimate(frame, factor, decimated);
+    pitch_from_nsdf(decimated, nsdf, sample_rate / factor as f32, min_frequency, max_frequency)
+}
+
+/// Low-pass `frame` with a triangular FIR of `2 * factor - 1` taps and keep every
+/// `factor`th sample in `decimated`
+///
+/// The response has nulls at every multiple of the decimated sample rate, where
+/// the components that would alias onto the low band sit.
+fn decimate(frame: &[f32], factor: usize, decimated: &mut [f32]) {
+    for (n, out) in decimated.iter_mut().enumerate() {
+        let centre = n * factor;
+        let mut sum = 0.0;
+        let mut weights = 0.0;
+        for tap in 0..2 * factor - 1 {
+            let Some(index) = (centre + tap).checked_sub(factor - 1) else {
+                continue;
+            };
+            let Some(&sample) = frame.get(index) else {
+                break;
+            };
+            let weight = (factor - tap.abs_diff(factor - 1)) as f32;
+            sum += weight * sample;
+            weights += weight;
+        }
+        *out = sum / weights;
+    }
+}
+
+/// Normalised autocorrelation pitch estimate of `frame`, using `nsdf` (at least
+/// as long as the frame) as scratch
+fn pitch_from_nsdf(
+    frame: &[f32],
+    nsdf: &mut [f32],
+    sample_rate: f32,
+    min_frequency: f32,
+    max_frequency: f32,
+) -> PitchEstimate {
+    let n = frame.len();
+    let max_lag =
+        (n - n / 4).min(((sample_rate / min_frequency.max(1.0)) as usize).saturating_add(1));
+    let min_lag = ((sample_rate / max_frequency.max(1.0)) as usize).max(2);
+    if min_lag >= max_lag {
+        return PitchEstimate::default();
+    }
+
+    // Normalised square difference function
+    let nsdf = &mut nsdf[..n];
+    nsdf.fill(0.0);
+    for (lag, value) in nsdf.iter_mut().enumerate().take(max_lag + 2).skip(1) {
+        let mut correlation = 0.0;
+        let mut energy = 0.0;
+        for j in 0..n - lag {
+            correlation += frame[j] * frame[j + lag];
+            energy += frame[j] * frame[j] + frame[j + lag] * frame[j + lag];
+        }
+        *value = if energy > 1e-12 {
+            2.0 * correlation / energy
+        } else {
+            0.0
+        };
+    }
+
+    // Key maxima: the highest value of each positive lobe after the zero-lag lobe
+    let search_end = (max_lag + 1).min(n - 1);
+    let mut key_maxima = [0usize; 64];
+    let mut count = 0;
+    let mut lag = 1;
+    while lag < search_end && nsdf[lag] > 0.0 {
+        lag += 1;
+    }
+    while lag < search_end && count < key_maxima.len() {
+        while lag < search_end && nsdf[lag] <= 0.0 {
+            lag += 1;
+        }
+        let mut best = lag;
+        while lag < search_end && nsdf[lag] > 0.0 {
+            if nsdf[lag] > nsdf[best] {
+                best = lag;
+            }
+            lag += 1;
+        }
+        if best < search_end && nsdf[best] > 0.0 {
+            key_maxima[count] = best;
+            count += 1;
+        }
+    }
+
+    let highest = key_maxima[..count]
+        .iter()
+        .filter(|&&lag| lag >= min_lag)
+        .map(|&lag| nsdf[lag])
+        .fold(0.0f32, f32::max);
+    if highest < AUTOCORRELATION_CLARITY {
+        return PitchEstimate { frequency: 0.0, confidence: highest.max(0.0) };
+    }
+    let Some(&period) = key_maxima[..count]
+        .iter()
+        .find(|&&lag| lag >= min_lag && nsdf[lag] >= AUTOCORRELATION_PEAK_THRESHOLD * highest)
+    else {
+        return PitchEstimate::default();
+    };
+
+    let refined = period as f32 + parabolic_peak_offset(&nsdf[..=max_lag + 1], period);
+    PitchEstimate { frequency: sample_rate / refined, confidence: nsdf[period].clamp(0.0, 1.0) }
+}
+
+/// Bins of the first eight harmonics of `fundamental_index`, saturating on overflow
 #[inline(always)]
 pub fn collect_harmonics(fundamental_index: usize) -> [usize; 8] {
     let mut harmonics = [0; 8];
     for n in 1..=8 {
-        let harmonic_index = fundamental_index * n;
+        let harmonic_index = fundamental_index.saturating_mul(n);
         harmonics[n - 1] = harmonic_index;
     }
     harmonics
 }
 
+/// Holds every `factor`th sample, keeping the hold state in the out-parameters
+///
+/// A zero factor holds the first sample forever. See
+/// [`SampleRateReducer`](crate::effects::SampleRateReducer) for a stateful
+/// version with a validated factor.
 #[inline(always)]
 pub fn sample_rate_reduce(
     sample: f32,
@@ -64,17 +470,20 @@ pub fn sample_rate_reduce(
     if *hold_counter == 0 {
         *held_value = sample;
     }
-    // Increment the hold_counter (wrapping around "factor")
-    //TODO: this can cause a panic if devide by 0
-
+    // Increment the hold_counter (wrapping around "factor"); a zero factor holds
+    // forever instead of dividing by zero
     if factor != 0 {
-        *hold_counter = (*hold_counter + 1) % factor;
+        *hold_counter = hold_counter.wrapping_add(1).wrapping_rem(factor);
     }
 
     // Always return the held_value (which may have just been updated)
     *held_value
 }
 
+/// Quantises a sample in -1.0 to 1.0 to `bit_depth` bits
+///
+/// `bit_depth` must be below 64. See [`Bitcrusher`](crate::effects::Bitcrusher)
+/// for a chain effect with a validated depth.
 #[inline(always)]
 pub fn bitcrush(sample: f32, bit_depth: u8) -> f32 {
     let levels = (1u64 << bit_depth) as f32;
@@ -242,3 +651,264 @@ mod detect_fun_freq_tests {
         assert_eq!(result, 4, "Maximum magnitude at the end index 4");
     }
 }
+
+#[cfg(test)]
+mod parabolic_peak_tests {
+    use super::*;
+
+    #[test]
+    fn test_symmetric_peak_has_no_offset() {
+        let magnitudes = [0.1, 0.5, 1.0, 0.5, 0.1];
+        assert_eq!(parabolic_peak_offset(&magnitudes, 2), 0.0);
+    }
+
+    #[test]
+    fn test_peak_leaning_right() {
+        let magnitudes = [0.1, 0.4, 1.0, 0.8, 0.1];
+        let offset = parabolic_peak_offset(&magnitudes, 2);
+        assert!(offset > 0.0 && offset <= 0.5, "offset {offset}");
+    }
+
+    #[test]
+    fn test_peak_leaning_left() {
+        let magnitudes = [0.1, 0.8, 1.0, 0.4, 0.1];
+        let offset = parabolic_peak_offset(&magnitudes, 2);
+        assert!((-0.5..0.0).contains(&offset), "offset {offset}");
+    }
+
+    #[test]
+    fn test_exact_parabola_vertex() {
+        // Samples of -(x - 2.25)^2 + 4 at x = 1, 2, 3
+        let magnitudes = [0.0, 2.4375, 3.9375, 3.4375, 0.0];
+        let offset = parabolic_peak_offset(&magnitudes, 2);
+        assert!((offset - 0.25).abs() < 1e-5, "offset {offset}");
+    }
+
+    #[test]
+    fn test_edge_and_flat_peaks() {
+        let magnitudes = [1.0, 0.5, 0.2];
+        assert_eq!(parabolic_peak_offset(&magnitudes, 0), 0.0);
+        assert_eq!(parabolic_peak_offset(&magnitudes, 2), 0.0);
+        assert_eq!(parabolic_peak_offset(&[0.5, 0.5, 0.5], 1), 0.0);
+    }
+}
+
+#[cfg(test)]
+mod hps_tests {
+    use super::*;
+
+    fn harmonic_spectrum(fundamental: usize, amplitudes: &[f32]) -> [f32; 256] {
+        let mut magnitudes = [0.01f32; 256];
+        for (n, &amplitude) in amplitudes.iter().enumerate() {
+            magnitudes[fundamental * (n + 1)] = amplitude;
+        }
+        magnitudes
+    }
+
+    #[test]
+    fn test_hps_finds_weak_fundamental() {
+        // Second harmonic louder than the fundamental
+        let magnitudes = harmonic_spectrum(10, &[0.4, 1.0, 0.6, 0.3, 0.2]);
+        assert_eq!(find_fundamental_frequency(&magnitudes), 20);
+        assert_eq!(find_fundamental_frequency_hps(&magnitudes), 10);
+    }
+
+    #[test]
+    fn test_hps_falls_back_on_silence() {
+        let magnitudes = [0.0f32; 64];
+        assert_eq!(find_fundamental_frequency_hps(&magnitudes), 0);
+    }
+
+    #[test]
+    fn test_detect_fundamental_bin_dispatch() {
+        let magnitudes = harmonic_spectrum(12, &[0.3, 1.0, 0.5, 0.4]);
+        assert_eq!(detect_fundamental_bin(&magnitudes, PitchDetector::PeakBin), 24);
+        assert_eq!(detect_fundamental_bin(&magnitudes, PitchDetector::HarmonicProduct), 12);
+        assert_eq!(detect_fundamental_bin(&magnitudes, PitchDetector::HarmonicSum), 12);
+    }
+
+    #[test]
+    fn test_harmonic_sum_resolves_bright_vowels() {
+        // Third harmonic loudest, as on an open vowel sung low
+        let magnitudes = harmonic_spectrum(9, &[0.3, 0.5, 1.0, 0.6, 0.3, 0.2]);
+        assert_eq!(find_fundamental_frequency(&magnitudes), 27);
+        assert_eq!(find_fundamental_frequency_harmonic_sum(&magnitudes), 9);
+
+        // A missing fourth harmonic breaks the product but not the sum
+        let magnitudes = harmonic_spectrum(10, &[0.5, 1.0, 0.7, 0.0, 0.4]);
+        assert_eq!(find_fundamental_frequency_harmonic_sum(&magnitudes), 10);
+    }
+
+    #[test]
+    fn test_harmonic_sum_ignores_subharmonic_noise() {
+        // A stray peak an octave below shares only the even harmonics
+        let mut magnitudes = harmonic_spectrum(20, &[1.0, 0.5, 0.3, 0.2]);
+        magnitudes[10] = 0.15;
+        assert_eq!(find_fundamental_frequency_harmonic_sum(&magnitudes), 20);
+    }
+
+    #[test]
+    fn test_harmonic_confidence_separates_voice_from_noise() {
+        let voice = harmonic_spectrum(10, &[1.0, 0.5, 0.3, 0.2, 0.1]);
+        let confidence = harmonic_confidence(&voice, 10.0);
+        assert!(confidence > 0.95, "{confidence}");
+
+        // A second voice a fourth above puts most of its energy between the harmonics
+        let mut duet = voice;
+        for (n, amplitude) in [1.0, 0.5, 0.3].iter().enumerate() {
+            duet[13 * (n + 1) + 1] = *amplitude;
+        }
+        let confidence = harmonic_confidence(&duet, 10.0);
+        assert!((0.2..0.7).contains(&confidence), "{confidence}");
+
+        let mut seed = 7u32;
+        let noise: [f32; 256] = core::array::from_fn(|_| {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            (seed >> 8) as f32 / (1u32 << 24) as f32
+        });
+        let confidence = harmonic_confidence(&noise, 10.0);
+        assert!(confidence < 0.2, "{confidence}");
+
+        assert_eq!(harmonic_confidence(&[0.0; 256], 10.0), 0.0);
+        assert_eq!(harmonic_confidence(&voice, 1.5), 0.0);
+        assert_eq!(harmonic_confidence(&voice, f32::NAN), 0.0);
+        assert_eq!(harmonic_confidence(&[], 10.0), 0.0);
+    }
+
+    #[test]
+    fn test_harmonic_sum_degenerate_spectra() {
+        assert_eq!(find_fundamental_frequency_harmonic_sum(&[]), 0);
+        assert_eq!(find_fundamental_frequency_harmonic_sum(&[0.0; 64]), 0);
+        assert_eq!(find_fundamental_frequency_harmonic_sum(&[0.2, 1.0]), 1);
+        // Fundamentals high enough for their harmonics to leave the spectrum
+        let magnitudes = harmonic_spectrum(100, &[1.0, 0.5]);
+        assert_eq!(find_fundamental_frequency_harmonic_sum(&magnitudes), 100);
+    }
+
+    #[test]
+    fn test_parabolic_peak_offset_out_of_range() {
+        assert_eq!(parabolic_peak_offset(&[0.5, 1.0, 0.5], usize::MAX), 0.0);
+        assert_eq!(parabolic_peak_offset(&[], 1), 0.0);
+    }
+
+    #[test]
+    fn test_sample_rate_reduce_degenerate_factors() {
+        let (mut counter, mut held) = (0, 0.0);
+        assert_eq!(sample_rate_reduce(1.0, 0, &mut counter, &mut held), 1.0);
+        assert_eq!(sample_rate_reduce(2.0, 0, &mut counter, &mut held), 2.0);
+
+        let mut counter = i32::MAX;
+        sample_rate_reduce(1.0, -1, &mut counter, &mut held);
+        assert_eq!(counter, 0);
+    }
+}
+
+#[cfg(test)]
+mod autocorrelation_tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    fn voice<const N: usize>(frequency: f32) -> [f32; N] {
+        core::array::from_fn(|n| {
+            let phase = 2.0 * PI * frequency * n as f32 / SAMPLE_RATE;
+            (1..=6).map(|h| libm::sinf(phase * h as f32) / h as f32).sum::<f32>() * 0.3
+        })
+    }
+
+    #[test]
+    fn test_detects_pitch_below_bin_resolution() {
+        // A 256-point bin is 187.5 Hz wide at 48 kHz
+        for frequency in [330.0, 440.0, 523.25, 880.0] {
+            let detected = detect_frequency_autocorrelation(
+                &voice::<256>(frequency),
+                SAMPLE_RATE,
+                50.0,
+                2000.0,
+            );
+            let cents = 1200.0 * libm::log2f(detected / frequency);
+            assert!(cents.abs() < 5.0, "{frequency} Hz detected as {detected} Hz");
+        }
+    }
+
+    #[test]
+    fn test_prefers_fundamental_over_period_multiples() {
+        let detected =
+            detect_frequency_autocorrelation(&voice::<1024>(220.0), SAMPLE_RATE, 50.0, 2000.0);
+        assert!((detected - 220.0).abs() < 1.0, "detected {detected} Hz");
+    }
+
+    #[test]
+    fn test_decimated_frame_tracks_pitch() {
+        for factor in [2, 4] {
+            for frequency in [110.0, 220.0, 440.0, 880.0] {
+                let estimate = detect_pitch_autocorrelation_decimated(
+                    &voice::<1024>(frequency),
+                    factor,
+                    SAMPLE_RATE,
+                    50.0,
+                    2000.0,
+                );
+                let cents = 1200.0 * libm::log2f(estimate.frequency / frequency);
+                assert!(cents.abs() < 10.0, "{factor}x: {frequency} Hz as {estimate:?}");
+                assert!(estimate.confidence > 0.9, "{factor}x: {estimate:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_autocorrelation_confidence() {
+        let voiced = detect_pitch_autocorrelation(&voice::<1024>(220.0), SAMPLE_RATE, 50.0, 2000.0);
+        assert!(voiced.confidence > 0.9, "{voiced:?}");
+
+        let mut seed = 1u32;
+        let noise: [f32; 1024] = core::array::from_fn(|_| {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            (seed >> 8) as f32 / (1u32 << 24) as f32 - 0.5
+        });
+        let unvoiced = detect_pitch_autocorrelation(&noise, SAMPLE_RATE, 50.0, 2000.0);
+        assert_eq!(unvoiced.frequency, 0.0);
+        assert!(unvoiced.confidence < AUTOCORRELATION_CLARITY, "{unvoiced:?}");
+    }
+
+    #[test]
+    fn test_silence_and_noise_are_unvoiced() {
+        assert_eq!(
+            detect_frequency_autocorrelation(&[0.0f32; 256], SAMPLE_RATE, 50.0, 2000.0),
+            0.0
+        );
+
+        let mut seed = 1u32;
+        let noise: [f32; 256] = core::array::from_fn(|_| {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            (seed >> 8) as f32 / (1u32 << 24) as f32 - 0.5
+        });
+        assert_eq!(detect_frequency_autocorrelation(&noise, SAMPLE_RATE, 50.0, 2000.0), 0.0);
+    }
+
+    #[test]
+    fn test_frequency_range_limits_search() {
+        // 100 Hz has a 480 sample period, longer than a 256-point frame can hold
+        let frame = voice::<256>(100.0);
+        assert_eq!(detect_frequency_autocorrelation(&frame, SAMPLE_RATE, 50.0, 2000.0), 0.0);
+        assert_eq!(detect_frequency_autocorrelation(&frame, SAMPLE_RATE, 50.0, 0.5), 0.0);
+    }
+
+    #[test]
+    fn test_detect_transient() {
+        let onset: [f32; 1024] = core::array::from_fn(|i| {
+            if i < 600 {
+                0.001 * libm::sinf(i as f32 * 0.1)
+            } else {
+                0.5 * libm::sinf(i as f32 * 0.1)
+            }
+        });
+        assert!(detect_transient(&onset, 256, 9.0));
+
+        let steady: [f32; 1024] = core::array::from_fn(|i| 0.5 * libm::sinf(i as f32 * 0.1));
+        assert!(!detect_transient(&steady, 256, 9.0));
+        // An attack in the first hop has nothing to compare with
+        assert!(!detect_transient(&onset[600..], 256, 9.0));
+        assert!(!detect_transient(&[0.0; 1024], 256, 9.0));
+    }
+}
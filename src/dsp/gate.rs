@@ -0,0 +1,89 @@
+//! Spectral gate applied before resynthesis.
+//!
+//! Bins well below the loudest bin of the frame are background: bleed from
+//! monitors, room noise, or the carrier showing through between the formants of
+//! a vocoder. Each bin has its own gain that opens and closes with the
+//! configured attack and release, so gated bins fade instead of chattering.
+
+use microfft::Complex32;
+
+use crate::config::SpectralGate;
+
+/// Gates the bins below Nyquist of a real signal's spectrum
+///
+/// `gains` holds one gain per bin carried between frames and is advanced by one
+/// hop. Without it the gate opens and closes instantly.
+pub fn apply_spectral_gate(
+    spectrum: &mut [Complex32],
+    gains: Option<&mut [f32]>,
+    gate: &SpectralGate,
+    hop_seconds: f32,
+) {
+    let power = |bin: &Complex32| bin.re * bin.re + bin.im * bin.im;
+    let peak = spectrum.iter().map(power).fold(0.0f32, f32::max);
+    let threshold = peak * libm::powf(10.0, gate.threshold_db / 10.0);
+    let attack = smoothing(gate.attack_ms, hop_seconds);
+    let release = smoothing(gate.release_ms, hop_seconds);
+
+    let mut gains = gains;
+    for i in 0..spectrum.len() {
+        let open = if power(&spectrum[i]) >= threshold && peak > 0.0 {
+            1.0
+        } else {
+            0.0
+        };
+        let gain = match gains.as_deref_mut() {
+            Some(gains) => {
+                let coefficient = if open > gains[i] { attack } else { release };
+                gains[i] += (open - gains[i]) * coefficient;
+                gains[i]
+            }
+            None => open,
+        };
+        spectrum[i].re *= gain;
+        spectrum[i].im *= gain;
+    }
+}
+
+/// Fraction of the way to its target that a gain moves in one hop
+pub(crate) fn smoothing(time_ms: f32, hop_seconds: f32) -> f32 {
+    if time_ms > 0.0 {
+        1.0 - libm::expf(-hop_seconds * 1000.0 / time_ms)
+    } else {
+        1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GATE: SpectralGate =
+        SpectralGate { threshold_db: -40.0, attack_ms: 0.0, release_ms: 50.0 };
+
+    fn spectrum(levels: [f32; 4]) -> [Complex32; 4] {
+        levels.map(|level| Complex32 { re: level, im: 0.0 })
+    }
+
+    #[test]
+    fn test_quiet_bins_are_zeroed() {
+        let mut frame = spectrum([0.001, 1.0, 0.02, 0.005]);
+        apply_spectral_gate(&mut frame, None, &GATE, 0.005);
+        assert_eq!(frame.map(|bin| bin.re), [0.0, 1.0, 0.02, 0.0]);
+    }
+
+    #[test]
+    fn test_gains_release_slowly() {
+        let mut gains = [0.0f32; 4];
+        let mut frame = spectrum([0.1, 1.0, 0.1, 0.1]);
+        apply_spectral_gate(&mut frame, Some(&mut gains), &GATE, 0.005);
+        assert_eq!(gains, [1.0; 4]);
+
+        // Bin 2 falls below the threshold and fades out over the release
+        let mut frame = spectrum([0.1, 1.0, 0.001, 0.1]);
+        apply_spectral_gate(&mut frame, Some(&mut gains), &GATE, 0.005);
+        let expected = libm::expf(-0.1);
+        assert!((gains[2] - expected).abs() < 1e-6, "{gains:?}");
+        assert!((frame[2].re - 0.001 * expected).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,106 @@
+//! Denormal and NaN protection for the processing core.
+//!
+//! A single NaN that reaches the phase state is otherwise carried from frame to
+//! frame forever and silences the output permanently, and denormal magnitudes in
+//! decaying tails are very slow on many FPUs. The sanitizers here are always
+//! applied at frame boundaries. With the `dsp-guards` feature, debug builds
+//! additionally assert on the first non-finite value so its source can be found.
+
+/// Magnitudes below this are treated as silence
+pub const DENORMAL_THRESHOLD: f32 = 1e-20;
+
+/// Flush tiny values (including denormals) to zero
+#[inline(always)]
+pub fn flush_denormal(value: f32) -> f32 {
+    if value.abs() < DENORMAL_THRESHOLD {
+        0.0
+    } else {
+        value
+    }
+}
+
+/// Replace a non-finite value with `fallback`
+#[inline(always)]
+pub fn finite_or(value: f32, fallback: f32) -> f32 {
+    if value.is_finite() { value } else { fallback }
+}
+
+/// Replace non-finite values with zero and flush denormals, in place
+///
+/// Returns `true` if any value was non-finite.
+pub fn sanitize(values: &mut [f32]) -> bool {
+    let mut found = false;
+    for value in values.iter_mut() {
+        found |= !value.is_finite();
+        *value = flush_denormal(finite_or(*value, 0.0));
+    }
+    found
+}
+
+/// Assert that every value is finite when `dsp-guards` is enabled in a debug build
+///
+/// `no-panic` turns the assertion off again.
+#[inline(always)]
+#[allow(unused_variables)]
+pub fn debug_assert_finite(values: &[f32], what: &str) {
+    #[cfg(all(feature = "dsp-guards", not(feature = "no-panic")))]
+    debug_assert!(values.iter().all(|v| v.is_finite()), "non-finite value in {what}");
+}
+
+/// Sanitize the phase vocoder state after a frame
+///
+/// Non-finite phases are reset to zero and a non-finite pitch shift ratio returns
+/// to unity, so a corrupted frame affects at most the following frame.
+pub fn sanitize_state(
+    last_input_phases: &mut [f32],
+    last_output_phases: &mut [f32],
+    pitch_shift_ratio: &mut f32,
+) {
+    debug_assert_finite(last_input_phases, "analysis phases");
+    debug_assert_finite(last_output_phases, "synthesis phases");
+    debug_assert_finite(core::slice::from_ref(pitch_shift_ratio), "pitch shift ratio");
+
+    sanitize(last_input_phases);
+    sanitize(last_output_phases);
+    *pitch_shift_ratio = finite_or(*pitch_shift_ratio, 1.0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flush_denormal() {
+        assert_eq!(flush_denormal(1e-40), 0.0);
+        assert_eq!(flush_denormal(-1e-25), 0.0);
+        assert_eq!(flush_denormal(1e-3), 1e-3);
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let mut values = [1.0, f32::NAN, f32::INFINITY, 1e-38, -0.5];
+        assert!(sanitize(&mut values));
+        assert_eq!(values, [1.0, 0.0, 0.0, 0.0, -0.5]);
+        assert!(!sanitize(&mut values));
+    }
+
+    #[test]
+    #[cfg(not(feature = "dsp-guards"))]
+    fn test_sanitize_state() {
+        let mut input = [0.5, f32::NAN];
+        let mut output = [f32::NEG_INFINITY, 0.25];
+        let mut ratio = f32::NAN;
+        sanitize_state(&mut input, &mut output, &mut ratio);
+        assert_eq!(input, [0.5, 0.0]);
+        assert_eq!(output, [0.0, 0.25]);
+        assert_eq!(ratio, 1.0);
+    }
+
+    #[test]
+    #[cfg(all(feature = "dsp-guards", debug_assertions))]
+    #[cfg(not(feature = "no-panic"))]
+    #[should_panic(expected = "non-finite value in synthesis phases")]
+    fn test_guards_assert_in_debug() {
+        sanitize_state(&mut [0.0], &mut [f32::NAN], &mut 1.0);
+    }
+}
@@ -0,0 +1,84 @@
+//! Mains hum removal.
+//!
+//! Hum from ground loops and unbalanced cables sits at the mains frequency and
+//! its first harmonics. Left in, it is often the loudest bin below the voice and
+//! the peak-bin pitch detector locks onto it. [`HumFilter`] cascades narrow
+//! notches at the mains frequency and its next three harmonics.
+
+use crate::{config::MainsFrequency, dsp::biquad::Biquad};
+
+/// Number of notches: the mains frequency and its first three harmonics
+pub const HUM_NOTCHES: usize = 4;
+
+/// Quality of the notch at the mains frequency, about 5 Hz wide at 50 Hz; the
+/// harmonics get proportionally higher qualities so every notch is as narrow
+const NOTCH_Q: f32 = 10.0;
+
+/// Cascade of notches at the mains frequency and its harmonics
+///
+/// ```rust
+/// use synthphone_e_vocal_dsp::{MainsFrequency, dsp::hum::HumFilter};
+///
+/// let mut filter = HumFilter::new(MainsFrequency::Hz60, 48000.0);
+/// let mut block = [0.0f32; 64];
+/// filter.process(&mut block);
+/// ```
+#[derive(Debug, Clone)]
+pub struct HumFilter {
+    notches: [Biquad; HUM_NOTCHES],
+}
+
+impl HumFilter {
+    /// Creates the filter for the mains frequency of the region
+    ///
+    /// Notches at or above Nyquist are left out.
+    pub fn new(mains: MainsFrequency, sample_rate: f32) -> Self {
+        let notches = core::array::from_fn(|i| {
+            let frequency = mains.hz() * (i + 1) as f32;
+            if frequency < sample_rate / 2.0 {
+                Biquad::notch(frequency, NOTCH_Q * (i + 1) as f32, sample_rate)
+            } else {
+                Biquad::identity()
+            }
+        });
+        Self { notches }
+    }
+
+    /// Filters `samples` in place
+    pub fn process(&mut self, samples: &mut [f32]) {
+        for sample in samples.iter_mut() {
+            *sample = self.notches.iter_mut().fold(*sample, |value, notch| notch.process(value));
+        }
+    }
+
+    /// Clears the filter state
+    pub fn reset(&mut self) {
+        self.notches.iter_mut().for_each(Biquad::reset);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testsig::tone_level;
+    use core::f32::consts::PI;
+
+    #[test]
+    fn test_removes_hum_and_keeps_voice() {
+        let mut filter = HumFilter::new(MainsFrequency::Hz50, 48000.0);
+        let mut samples: [f32; 48000] = core::array::from_fn(|n| {
+            let phase = 2.0 * PI * n as f32 / 48000.0;
+            0.3 * libm::sinf(220.0 * phase)
+                + 0.2 * libm::sinf(50.0 * phase)
+                + 0.1 * libm::sinf(150.0 * phase)
+        });
+        filter.process(&mut samples);
+
+        // One second after the start, in whole periods of every component
+        let settled = &samples[24000..];
+        let [mains, third, voice] = [50.0, 150.0, 220.0].map(|f| tone_level(settled, f, 48000.0));
+        assert!(mains < 0.005, "{mains}");
+        assert!(third < 0.005, "{third}");
+        assert!((voice - 0.3).abs() < 0.03, "{voice}");
+    }
+}
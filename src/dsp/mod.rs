@@ -1,9 +1,20 @@
+pub mod bins;
+pub mod biquad;
+pub mod clip;
+pub mod emphasis;
 pub mod fft;
 pub mod frequency_analysis;
+pub mod gate;
+pub mod guards;
+pub mod hum;
+pub mod resampler;
+pub mod separation;
 pub mod signal_processing;
 pub mod windowing;
 
+pub use bins::BinTables;
 pub use fft::*;
 pub use frequency_analysis::*;
+pub use resampler::*;
 pub use signal_processing::*;
 pub use windowing::*;
@@ -0,0 +1,282 @@
+//! Polyphase sample-rate conversion.
+//!
+//! Converts between any two integer sample rates whose reduced ratio fits the
+//! phase table (e.g. 44.1 kHz ↔ 48 kHz is 160/147), using a windowed-sinc
+//! low-pass split into `L` polyphase branches. Works without `std` or allocation.
+
+use core::f32::consts::PI;
+
+use libm::{cosf, sinf};
+
+use crate::VocalEffectsError;
+
+/// Resampler sized for all common audio rates (32, 44.1, 48, 88.2 and 96 kHz)
+pub type Resampler = PolyphaseResampler<16, 160>;
+
+/// Rational `L/M` polyphase resampler.
+///
+/// # Generic Parameters
+///
+/// * `TAPS` - Filter taps per polyphase branch. More taps give a steeper anti-aliasing
+///   filter at the cost of CPU and `TAPS / 2` input samples of latency.
+/// * `MAX_PHASES` - Largest interpolation factor `L` the coefficient table can hold.
+///
+/// # Example
+///
+/// ```rust
+/// use synthphone_e_vocal_dsp::dsp::Resampler;
+///
+/// let mut resampler = Resampler::new(44100, 48000).unwrap();
+/// let input = [0.0f32; 441];
+/// let mut output = [0.0f32; 512];
+/// let (consumed, produced) = resampler.process(&input, &mut output);
+/// assert_eq!(consumed, 441);
+/// assert_eq!(produced, 480);
+/// ```
+pub struct PolyphaseResampler<const TAPS: usize, const MAX_PHASES: usize> {
+    coefficients: [[f32; TAPS]; MAX_PHASES],
+    history: [f32; TAPS],
+    history_pos: usize,
+    interpolation: usize,
+    decimation: usize,
+    phase: usize,
+    pending_inputs: usize,
+}
+
+const fn gcd(mut a: u32, mut b: u32) -> u32 {
+    while b != 0 {
+        let t = b;
+        b = a % b;
+        a = t;
+    }
+    a
+}
+
+impl<const TAPS: usize, const MAX_PHASES: usize> PolyphaseResampler<TAPS, MAX_PHASES> {
+    /// Largest interpolation factor of the reduced ratio
+    pub const MAX_INTERPOLATION: usize = MAX_PHASES;
+
+    /// Creates a resampler converting from `input_rate` to `output_rate`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VocalEffectsError::InvalidConfiguration`] if either rate is zero or
+    /// the reduced interpolation factor exceeds `MAX_PHASES`.
+    pub fn new(input_rate: u32, output_rate: u32) -> Result<Self, VocalEffectsError> {
+        if input_rate == 0 || output_rate == 0 || TAPS == 0 {
+            return Err(VocalEffectsError::InvalidConfiguration);
+        }
+        let divisor = gcd(input_rate, output_rate);
+        let interpolation = (output_rate / divisor) as usize;
+        let decimation = (input_rate / divisor) as usize;
+        if interpolation > MAX_PHASES {
+            return Err(VocalEffectsError::InvalidConfiguration);
+        }
+
+        // Windowed-sinc prototype at the upsampled rate, cut off at the lower Nyquist
+        let length = TAPS * interpolation;
+        let cutoff = 0.5 / interpolation.max(decimation) as f32;
+        let centre = (length - 1) as f32 / 2.0;
+        let mut coefficients = [[0.0; TAPS]; MAX_PHASES];
+        for j in 0..length {
+            let t = j as f32 - centre;
+            let sinc = if t == 0.0 {
+                2.0 * cutoff
+            } else {
+                sinf(2.0 * PI * cutoff * t) / (PI * t)
+            };
+            // Blackman window
+            let x = 2.0 * PI * (j as f32 + 0.5) / length as f32;
+            let window = 0.42 - 0.5 * cosf(x) + 0.08 * cosf(2.0 * x);
+            coefficients[j % interpolation][j / interpolation] =
+                sinc * window * interpolation as f32;
+        }
+
+        Ok(Self {
+            coefficients,
+            history: [0.0; TAPS],
+            history_pos: 0,
+            interpolation,
+            decimation,
+            phase: 0,
+            pending_inputs: 1,
+        })
+    }
+
+    /// Changes the conversion ratio, keeping the filter history so the stream
+    /// continues without a gap
+    ///
+    /// # Errors
+    ///
+    /// Same as [`PolyphaseResampler::new`]; the resampler is left unchanged.
+    pub fn set_rates(
+        &mut self,
+        input_rate: u32,
+        output_rate: u32,
+    ) -> Result<(), VocalEffectsError> {
+        let mut retuned = Self::new(input_rate, output_rate)?;
+        retuned.history = self.history;
+        retuned.history_pos = self.history_pos;
+        *self = retuned;
+        Ok(())
+    }
+
+    /// Output samples produced per input sample
+    pub fn ratio(&self) -> f32 {
+        self.interpolation as f32 / self.decimation as f32
+    }
+
+    /// Filter delay in input samples
+    pub fn latency(&self) -> usize {
+        TAPS / 2
+    }
+
+    /// Clears the filter history
+    pub fn reset(&mut self) {
+        self.history.fill(0.0);
+        self.history_pos = 0;
+        self.phase = 0;
+        self.pending_inputs = 1;
+    }
+
+    /// Resamples as much of `input` into `output` as possible.
+    ///
+    /// Returns `(consumed, produced)`. Stops when either the input is exhausted or the
+    /// output is full; unconsumed input should be passed again on the next call.
+    pub fn process(&mut self, input: &[f32], output: &mut [f32]) -> (usize, usize) {
+        let mut consumed = 0;
+        let mut produced = 0;
+        loop {
+            while self.pending_inputs > 0 {
+                if consumed == input.len() {
+                    return (consumed, produced);
+                }
+                self.history_pos = (self.history_pos + 1) % TAPS;
+                self.history[self.history_pos] = input[consumed];
+                consumed += 1;
+                self.pending_inputs -= 1;
+            }
+            if produced == output.len() {
+                return (consumed, produced);
+            }
+
+            let branch = &self.coefficients[self.phase];
+            let mut sum = 0.0;
+            for (k, coefficient) in branch.iter().enumerate() {
+                sum += coefficient * self.history[(self.history_pos + TAPS - k) % TAPS];
+            }
+            output[produced] = sum;
+            produced += 1;
+
+            self.phase += self.decimation;
+            self.pending_inputs = self.phase / self.interpolation;
+            self.phase %= self.interpolation;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(n: usize, frequency: f32, sample_rate: f32) -> f32 {
+        sinf(2.0 * PI * frequency * n as f32 / sample_rate)
+    }
+
+    #[test]
+    fn test_rejects_invalid_rates() {
+        assert!(Resampler::new(0, 48000).is_err());
+        assert!(Resampler::new(48000, 0).is_err());
+        // 44100 -> 44101 would need 44101 phases
+        assert!(Resampler::new(44100, 44101).is_err());
+    }
+
+    #[test]
+    fn test_ratios() {
+        let cases =
+            [(44100, 48000), (48000, 44100), (48000, 96000), (96000, 48000), (32000, 48000)];
+        for (from, to) in cases {
+            let resampler = Resampler::new(from, to).unwrap();
+            assert!((resampler.ratio() - to as f32 / from as f32).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_set_rates_keeps_history() {
+        let mut resampler = Resampler::new(1, 1).unwrap();
+        let mut output = [0.0f32; 32];
+        resampler.process(&[1.0; 32], &mut output);
+        assert!((output[31] - 1.0).abs() < 1e-3, "{}", output[31]);
+
+        // A steady input stays steady across the change instead of restarting from zero
+        resampler.set_rates(272, 256).unwrap();
+        assert!((resampler.ratio() - 256.0 / 272.0).abs() < 1e-6);
+        let (_, produced) = resampler.process(&[1.0; 4], &mut output);
+        assert!(produced > 0);
+        assert!(output[..produced].iter().all(|s| (s - 1.0).abs() < 0.01), "{output:?}");
+
+        assert!(resampler.set_rates(44100, 44101).is_err());
+        assert!((resampler.ratio() - 256.0 / 272.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_output_count_tracks_ratio() {
+        for (from, to) in [(44100u32, 48000u32), (48000, 44100), (48000, 88200), (96000, 32000)] {
+            let mut resampler = Resampler::new(from, to).unwrap();
+            let input = [0.0f32; 4410];
+            let mut output = [0.0f32; 16000];
+            let (consumed, produced) = resampler.process(&input, &mut output);
+            assert_eq!(consumed, input.len());
+            let expected = input.len() as f32 * to as f32 / from as f32;
+            assert!((produced as f32 - expected).abs() <= 1.0, "{from}->{to}: {produced}");
+        }
+    }
+
+    #[test]
+    fn test_resampled_sine_matches_ideal() {
+        for (from, to) in [(44100u32, 48000u32), (48000, 44100), (48000, 96000), (32000, 48000)] {
+            let mut resampler = Resampler::new(from, to).unwrap();
+            let input: [f32; 2000] = core::array::from_fn(|n| sine(n, 1000.0, from as f32));
+            let mut output = [0.0f32; 8000];
+            let (_, produced) = resampler.process(&input, &mut output);
+
+            // The prototype is centred half an upsampled sample before TAPS / 2
+            let delay =
+                (resampler.latency() as f32 - 0.5 / resampler.interpolation as f32) / from as f32;
+            let start = produced / 4;
+            for (m, sample) in output[start..produced * 3 / 4].iter().enumerate() {
+                let time = (start + m) as f32 / to as f32 - delay;
+                let expected = sinf(2.0 * PI * 1000.0 * time);
+                assert!(
+                    (sample - expected).abs() < 0.02,
+                    "{from}->{to} at {m}: {sample} vs {expected}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_streaming_matches_single_call() {
+        let input: [f32; 1000] = core::array::from_fn(|n| sine(n, 440.0, 44100.0));
+
+        let mut whole = Resampler::new(44100, 48000).unwrap();
+        let mut expected = [0.0f32; 1200];
+        let (_, expected_len) = whole.process(&input, &mut expected);
+
+        let mut streamed = Resampler::new(44100, 48000).unwrap();
+        let mut output = [0.0f32; 1200];
+        let mut produced = 0;
+        for chunk in input.chunks(37) {
+            let mut offset = 0;
+            while offset < chunk.len() {
+                let (c, p) =
+                    streamed.process(&chunk[offset..], &mut output[produced..produced + 5]);
+                offset += c;
+                produced += p;
+            }
+        }
+
+        assert_eq!(produced, expected_len);
+        assert_eq!(output[..produced], expected[..produced]);
+    }
+}
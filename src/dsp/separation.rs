@@ -0,0 +1,93 @@
+//! Harmonic/percussive separation by median filtering.
+//!
+//! Sustained partials are steady over time but narrow in frequency, while
+//! breaths, clicks and beatboxed drums are short but spread across many bins. A
+//! median across frames therefore keeps the harmonic part of a magnitude
+//! spectrum and a median across bins keeps the percussive part. The two medians
+//! give a soft mask that routes each bin to the pitch shifter or past it.
+
+/// Frames in the median across time, including the current one
+pub const TIME_MEDIAN_FRAMES: usize = 3;
+
+/// Width of the median across frequency in Hz
+const FREQUENCY_MEDIAN_HZ: f32 = 400.0;
+
+/// Most bins on each side of the centre bin in the median across frequency
+const MAX_HALF_WIDTH: usize = 15;
+
+/// Fraction of each bin that is harmonic, from 0.0 (percussive) to 1.0
+///
+/// `history` holds the magnitudes of the previous `TIME_MEDIAN_FRAMES - 1`
+/// frames back to back, newest first, and is advanced by one frame. It must be
+/// twice as long as `magnitudes`; `mask` at least as long.
+pub fn harmonic_mask(magnitudes: &[f32], history: &mut [f32], mask: &mut [f32], bin_width: f32) {
+    let bins = magnitudes.len();
+    let (previous, oldest) = history[..2 * bins].split_at_mut(bins);
+    let half_width = ((FREQUENCY_MEDIAN_HZ / 2.0 / bin_width) as usize).clamp(1, MAX_HALF_WIDTH);
+
+    for bin in 0..bins {
+        let harmonic = median(&mut [magnitudes[bin], previous[bin], oldest[bin]]);
+
+        let start = bin.saturating_sub(half_width);
+        let end = (bin + half_width + 1).min(bins);
+        let mut window = [0.0f32; 2 * MAX_HALF_WIDTH + 1];
+        window[..end - start].copy_from_slice(&magnitudes[start..end]);
+        let percussive = median(&mut window[..end - start]);
+
+        let harmonic_power = harmonic * harmonic;
+        let total_power = harmonic_power + percussive * percussive;
+        mask[bin] = if total_power > 0.0 {
+            harmonic_power / total_power
+        } else {
+            1.0
+        };
+    }
+
+    oldest.copy_from_slice(previous);
+    previous.copy_from_slice(magnitudes);
+}
+
+/// Median of a short slice, reordering it
+fn median(values: &mut [f32]) -> f32 {
+    // Insertion sort: the slices are at most a few dozen values
+    for i in 1..values.len() {
+        let mut j = i;
+        while j > 0 && values[j - 1] > values[j] {
+            values.swap(j - 1, j);
+            j -= 1;
+        }
+    }
+    values[values.len() / 2]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steady_peak_is_harmonic_and_burst_is_percussive() {
+        let mut history = [0.0f32; 128];
+        let mut mask = [0.0f32; 64];
+        let mut peak = [0.01f32; 64];
+        peak[20] = 1.0;
+
+        // A partial held over three frames
+        for _ in 0..3 {
+            harmonic_mask(&peak, &mut history, &mut mask, 46.875);
+        }
+        assert!(mask[20] > 0.99, "{}", mask[20]);
+
+        // Broadband click in the next frame
+        let click = [1.0f32; 64];
+        harmonic_mask(&click, &mut history, &mut mask, 46.875);
+        assert!(mask[40] < 0.01, "{}", mask[40]);
+        assert_eq!(history[..64], click);
+        assert_eq!(history[64..], peak);
+    }
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(&mut [3.0, 1.0, 2.0]), 2.0);
+        assert_eq!(median(&mut [5.0, 4.0, 9.0, 1.0, 7.0]), 5.0);
+    }
+}
@@ -1,75 +1,997 @@
-use libm::{expf, logf};
+use libm::{cosf, exp2f, expf, fabsf, log2f, logf, powf, roundf, sqrtf};
 
-use crate::{MusicalSettings, dsp::FftOps};
+use crate::{
+    FrameAnalysis, Glide, LowConfidence, MusicalSettings, Ornaments, VocalEffectsConfig,
+    dsp::{
+        DynFft,
+        frequency_analysis::{PitchEstimate, harmonic_confidence},
+        unpack_nyquist,
+    },
+    state::TargetSource,
+    workspace::Workspace,
+};
 
 /// Extract cepstral envelope for formant preservation using generic FFT operations
+///
+/// `lifter_cutoff` is the number of cepstral coefficients kept, in samples of quefrency
+/// (see [`VocalEffectsConfig::lifter_cutoff`]).
 pub fn extract_cepstral_envelope<const N: usize, const HALF_N: usize, F>(
+    fft: &mut F,
     analysis_magnitudes: &[f32; HALF_N],
     envelope: &mut [f32; HALF_N],
+    lifter_cutoff: usize,
 ) where
-    F: FftOps<N, HALF_N>,
+    F: DynFft<N, HALF_N> + ?Sized,
 {
-    const LIFTER_CUTOFF: usize = 64;
-    let mut full_spectrum = [microfft::Complex32 { re: 0.0, im: 0.0 }; N];
-    let mut cepstrum_buffer = [0.0f32; N];
+    extract_cepstral_envelope_with(
+        fft,
+        analysis_magnitudes,
+        envelope,
+        lifter_cutoff,
+        &mut [microfft::Complex32 { re: 0.0, im: 0.0 }; HALF_N],
+        &mut [0.0; N],
+    );
+}
 
-    // Compute log spectrum
-    for i in 0..HALF_N {
-        let mag = analysis_magnitudes[i].max(1e-6_f32);
-        let log_mag = logf(mag);
-        full_spectrum[i] = microfft::Complex32 { re: log_mag, im: 0.0 };
-        if i != 0 {
-            full_spectrum[N - i] = microfft::Complex32 { re: log_mag, im: 0.0 };
-        }
+/// [`extract_cepstral_envelope`] with caller-provided scratch buffers
+///
+/// The processors lend it their resynthesis spectrum and a synthesis buffer, which
+/// are not in use yet while the envelope is taken. Both are left zeroed.
+pub fn extract_cepstral_envelope_with<const N: usize, const HALF_N: usize, F>(
+    fft: &mut F,
+    analysis_magnitudes: &[f32; HALF_N],
+    envelope: &mut [f32; HALF_N],
+    lifter_cutoff: usize,
+    spectrum: &mut [microfft::Complex32; HALF_N],
+    cepstrum: &mut [f32; N],
+) where
+    F: DynFft<N, HALF_N> + ?Sized,
+{
+    let lifter_cutoff = lifter_cutoff.clamp(1, HALF_N);
+
+    // Log spectrum, packed like the forward FFT output with a zero Nyquist bin
+    for (bin, &magnitude) in spectrum.iter_mut().zip(analysis_magnitudes) {
+        *bin = microfft::Complex32 { re: logf(magnitude.max(1e-6_f32)), im: 0.0 };
     }
 
-    // Inverse FFT to get cepstrum
-    let cepstrum = F::inverse_fft(&mut full_spectrum);
+    // The log spectrum is real and even, so the cepstrum is real
+    fft.inverse_real(spectrum, cepstrum);
 
     // Apply liftering (low-pass in cepstral domain)
-    cepstrum_buffer.fill(0.0);
-    for i in 0..LIFTER_CUTOFF.min(HALF_N) {
-        cepstrum_buffer[i] = cepstrum[i].re;
-    }
-    for i in (N - LIFTER_CUTOFF.min(HALF_N))..N {
-        cepstrum_buffer[i] = cepstrum[i].re;
-    }
+    cepstrum[lifter_cutoff..N - lifter_cutoff].fill(0.0);
 
     // Forward FFT to get smoothed envelope
-    let envelope_fft = F::forward_fft(&mut cepstrum_buffer);
+    let envelope_fft = fft.forward(cepstrum);
     for i in 0..HALF_N {
         envelope[i] = expf(envelope_fft[i].re);
     }
+
+    spectrum.fill(microfft::Complex32 { re: 0.0, im: 0.0 });
+    cepstrum.fill(0.0);
+}
+
+/// Formant envelope carried across frames, so it only has to be extracted every
+/// few hops
+///
+/// Between extractions the envelope moves in a straight line from the one in use
+/// to the newest, reaching it just before the next extraction, so formants glide
+/// instead of stepping. The envelope covers the lower half of the spectrum, so the
+/// envelope in use and its step per hop share one frame-sized array.
+///
+/// The cache also holds the [`FormantSnapshot`] the envelope morphs toward, see
+/// [`VocalEffectsConfig::formant_morph`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EnvelopeCache<const N: usize> {
+    /// Envelope in use in the lower half, its step per hop in the upper half
+    bins: [f32; N],
+    /// Hops until the next extraction
+    hops_left: usize,
+    /// Whether the lower half holds an envelope yet
+    primed: bool,
+    /// Envelope to morph toward
+    target: Option<FormantSnapshot>,
+}
+
+impl<const N: usize> EnvelopeCache<N> {
+    /// Creates an empty cache that extracts on its first frame
+    pub const fn new() -> Self {
+        Self { bins: [0.0; N], hops_left: 0, primed: false, target: None }
+    }
+
+    /// Forgets the envelope, so the next frame extracts a fresh one instead of
+    /// gliding from a stale one
+    ///
+    /// The morph target is kept.
+    pub fn clear(&mut self) {
+        self.hops_left = 0;
+        self.primed = false;
+    }
+
+    /// Snapshot the envelope morphs toward, if any
+    pub fn target(&self) -> Option<&FormantSnapshot> {
+        self.target.as_ref()
+    }
+
+    /// Sets or removes the snapshot the envelope morphs toward
+    pub fn set_target(&mut self, target: Option<FormantSnapshot>) {
+        self.target = target;
+    }
+
+    /// Writes the envelope of this hop to `envelope`, extracting a new one from
+    /// `analysis_magnitudes` every
+    /// [`envelope_update_interval`](VocalEffectsConfig::envelope_update_interval) hops
+    ///
+    /// An interval of one extracts every hop, exactly like
+    /// [`extract_cepstral_envelope_with`] at the
+    /// [`lifter_cutoff`](VocalEffectsConfig::lifter_cutoff) of `config`, which also
+    /// describes the scratch buffers.
+    pub fn update<const HALF_N: usize, F>(
+        &mut self,
+        fft: &mut F,
+        analysis_magnitudes: &[f32; HALF_N],
+        envelope: &mut [f32; HALF_N],
+        spectrum: &mut [microfft::Complex32; HALF_N],
+        cepstrum: &mut [f32; N],
+        config: &VocalEffectsConfig,
+    ) where
+        F: DynFft<N, HALF_N> + ?Sized,
+    {
+        let interval = config.envelope_update_interval;
+        let fits = 2 * HALF_N == N;
+        let extract = !fits || self.hops_left == 0 || interval <= 1;
+        if extract {
+            extract_cepstral_envelope_with(
+                fft,
+                analysis_magnitudes,
+                envelope,
+                config.lifter_cutoff(),
+                spectrum,
+                cepstrum,
+            );
+            self.hops_left = interval.max(1);
+        }
+        // A cache for another frame size cannot hold the envelope, so it is
+        // extracted every hop
+        if !fits {
+            return;
+        }
+
+        let (current, step) = self.bins.split_at_mut(HALF_N);
+
+        if !self.primed || interval <= 1 {
+            current.copy_from_slice(envelope);
+            step.fill(0.0);
+            self.primed = true;
+        } else {
+            if extract {
+                let hops = interval as f32;
+                for ((step, current), &newest) in step.iter_mut().zip(&*current).zip(&*envelope) {
+                    *step = (newest - current) / hops;
+                }
+            }
+            for ((value, current), step) in envelope.iter_mut().zip(current).zip(&*step) {
+                *current += step;
+                *value = *current;
+            }
+        }
+        self.hops_left -= 1;
+    }
+}
+
+impl<const N: usize> Default for EnvelopeCache<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Points of a [`FormantSnapshot`] envelope from DC to Nyquist
+pub const SNAPSHOT_POINTS: usize = 256;
+
+/// Formant envelope captured from one signal for a voice to morph toward
+///
+/// The envelope is resampled to [`SNAPSHOT_POINTS`] evenly spaced points from
+/// DC to Nyquist, plenty for a cepstral envelope, so a snapshot fits any FFT
+/// size at the sample rate it was taken at. Only its shape is kept: it is
+/// scaled to a geometric mean of one, and brought to the level of the voice it
+/// shapes when morphing.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FormantSnapshot {
+    points: [f32; SNAPSHOT_POINTS],
+}
+
+impl FormantSnapshot {
+    /// Captures the formant envelope of `samples`
+    ///
+    /// The last `N` samples at most are Hann windowed over their length and
+    /// zero padded to a frame. `workspace` only lends scratch space, and
+    /// `lifter_cutoff` is that of [`extract_cepstral_envelope`].
+    pub fn capture<const N: usize, const HALF_N: usize, F>(
+        fft: &mut F,
+        workspace: &mut Workspace<N, HALF_N>,
+        samples: &[f32],
+        lifter_cutoff: usize,
+    ) -> Self
+    where
+        F: DynFft<N, HALF_N> + ?Sized,
+    {
+        workspace.prepare();
+        let Workspace {
+            spectrum,
+            analysis_magnitudes,
+            synthesis_magnitudes: frame,
+            synthesis_frequencies: cepstrum,
+            envelope,
+            ..
+        } = workspace;
+
+        let samples = &samples[samples.len().saturating_sub(N)..];
+        let length = samples.len();
+        for (i, (sample, &input)) in frame[N - length..].iter_mut().zip(samples).enumerate() {
+            let window = 0.5 - 0.5 * cosf(2.0 * core::f32::consts::PI * i as f32 / length as f32);
+            *sample = input * window;
+        }
+        let bins = fft.forward(frame);
+        unpack_nyquist(bins);
+        for (magnitude, bin) in analysis_magnitudes.iter_mut().zip(bins.iter()) {
+            *magnitude = sqrtf(bin.re * bin.re + bin.im * bin.im);
+        }
+        extract_cepstral_envelope_with(
+            fft,
+            analysis_magnitudes,
+            envelope,
+            lifter_cutoff,
+            spectrum,
+            cepstrum,
+        );
+
+        let level = geometric_mean(envelope);
+        let last = HALF_N.saturating_sub(1);
+        let points = core::array::from_fn(|point| {
+            let position = point as f32 * HALF_N as f32 / (SNAPSHOT_POINTS - 1) as f32;
+            let bin = (position as usize).min(last);
+            let next = (bin + 1).min(last);
+            let frac = (position - bin as f32).min(1.0);
+            (envelope[bin] * (1.0 - frac) + envelope[next] * frac) / level
+        });
+        Self { points }
+    }
+
+    /// Envelope at `fraction` of the way from DC to Nyquist, interpolated
+    /// between points
+    pub fn at(&self, fraction: f32) -> f32 {
+        let position = fraction.clamp(0.0, 1.0) * (SNAPSHOT_POINTS - 1) as f32;
+        let point = (position as usize).min(SNAPSHOT_POINTS - 2);
+        let frac = position - point as f32;
+        self.points[point] * (1.0 - frac) + self.points[point + 1] * frac
+    }
+
+    /// Envelope points from DC to Nyquist, at a geometric mean of one
+    pub fn points(&self) -> &[f32; SNAPSHOT_POINTS] {
+        &self.points
+    }
+}
+
+/// Geometric mean of positive `values`, the level of an envelope
+pub(crate) fn geometric_mean(values: &[f32]) -> f32 {
+    if values.is_empty() {
+        return 1.0;
+    }
+    let log_sum: f32 = values.iter().map(|value| logf(value.max(1e-12))).sum();
+    expf(log_sum / values.len() as f32)
 }
 
 pub fn calculate_pitch_shift(
     analysis_magnitudes: &[f32],
     analysis_frequencies: &[f32],
     previous_pitch_shift_ratio: f32,
+    config: &VocalEffectsConfig,
     settings: &MusicalSettings,
     bin_width: f32,
 ) -> f32 {
-    let mut pitch_shift_ratio = previous_pitch_shift_ratio;
-    let fundamental_index =
-        crate::dsp::frequency_analysis::find_fundamental_frequency(analysis_magnitudes);
-    let detected_frequency = analysis_frequencies[fundamental_index] * bin_width;
-
-    if detected_frequency > 0.001 {
-        let target_frequency = if settings.note == 0 {
-            let scale_frequencies = crate::audio::keys::get_scale_by_key(settings.key);
-            crate::audio::frequencies::find_nearest_note_in_key(
-                detected_frequency,
-                scale_frequencies,
-            )
+    analyze_pitch(
+        analysis_magnitudes,
+        analysis_frequencies,
+        previous_pitch_shift_ratio,
+        config,
+        settings,
+        bin_width,
+    )
+    .pitch_shift_ratio
+}
+
+/// Detect the fundamental of a frame and derive the corrected pitch shift ratio
+pub fn analyze_pitch(
+    analysis_magnitudes: &[f32],
+    analysis_frequencies: &[f32],
+    previous_pitch_shift_ratio: f32,
+    config: &VocalEffectsConfig,
+    settings: &MusicalSettings,
+    bin_width: f32,
+) -> FrameAnalysis {
+    let estimate = detect_pitch(analysis_magnitudes, analysis_frequencies, config, bin_width);
+    correct_estimate_from(
+        estimate,
+        &FrameAnalysis::with_ratio(previous_pitch_shift_ratio),
+        &VocalEffectsConfig { ornaments: Ornaments::NONE, ..*config },
+        settings,
+    )
+}
+
+/// Fundamental frequency of a frame and the confidence in it, from the peak of
+/// the analysis spectrum
+///
+/// The peak is placed between bins by parabolic interpolation on every frame.
+pub(crate) fn detect_pitch(
+    analysis_magnitudes: &[f32],
+    analysis_frequencies: &[f32],
+    config: &VocalEffectsConfig,
+    bin_width: f32,
+) -> PitchEstimate {
+    let search_bins = (analysis_magnitudes.len() / config.pitch_decimation.factor()).max(1);
+    let fundamental_index = crate::dsp::frequency_analysis::detect_fundamental_bin(
+        &analysis_magnitudes[..search_bins],
+        config.pitch_detector,
+    );
+    let offset = crate::dsp::frequency_analysis::parabolic_peak_offset(
+        analysis_magnitudes,
+        fundamental_index,
+    );
+    let peak_bin = fundamental_index as f32 + offset;
+    // The phase vocoder estimates of the peak bin and its neighbour towards the interpolated
+    // peak are more precise, so they are blended at the same offset. Each is only meaningful
+    // when it agrees with the interpolated peak (e.g. they are garbage on the first frame
+    // after a reset)
+    let agrees = |bin: f32| fabsf(bin - peak_bin) <= 1.0;
+    let current = analysis_frequencies[fundamental_index];
+    let neighbour = if offset < 0.0 {
+        fundamental_index.wrapping_sub(1)
+    } else {
+        fundamental_index + 1
+    };
+    let detected_bin = match analysis_frequencies.get(neighbour) {
+        _ if !agrees(current) => peak_bin,
+        Some(&next) if agrees(next) => current + (next - current) * fabsf(offset),
+        _ => current,
+    };
+    PitchEstimate {
+        frequency: detected_bin * bin_width,
+        confidence: harmonic_confidence(analysis_magnitudes, detected_bin),
+    }
+}
+
+/// Pitch shift ratio that moves `detected_frequency` onto the target note.
+///
+/// Returns `previous_pitch_shift_ratio` unchanged when the frequency is outside the
+/// configured range, otherwise the smoothed ratio towards the target.
+pub fn pitch_shift_for_frequency(
+    detected_frequency: f32,
+    previous_pitch_shift_ratio: f32,
+    config: &VocalEffectsConfig,
+    settings: &MusicalSettings,
+) -> f32 {
+    correct_frequency(detected_frequency, previous_pitch_shift_ratio, config, settings)
+        .pitch_shift_ratio
+}
+
+/// Target note and smoothed pitch shift ratio for a detected frequency
+///
+/// Starts without history, so neither a [`Glide`] nor the
+/// [`Ornaments`] apply; see [`correct_frequency_from`] to continue from the
+/// previous frame.
+pub fn correct_frequency(
+    detected_frequency: f32,
+    previous_pitch_shift_ratio: f32,
+    config: &VocalEffectsConfig,
+    settings: &MusicalSettings,
+) -> FrameAnalysis {
+    correct_frequency_from(
+        detected_frequency,
+        &FrameAnalysis::with_ratio(previous_pitch_shift_ratio),
+        &VocalEffectsConfig { ornaments: Ornaments::NONE, ..*config },
+        settings,
+    )
+}
+
+/// [`correct_frequency`] continuing from the analysis of the previous frame
+///
+/// Besides the ratio, the previous analysis carries the glide between notes and
+/// the progress of a scoop or fall.
+pub fn correct_frequency_from(
+    detected_frequency: f32,
+    previous: &FrameAnalysis,
+    config: &VocalEffectsConfig,
+    settings: &MusicalSettings,
+) -> FrameAnalysis {
+    let previous_pitch_shift_ratio = previous.pitch_shift_ratio;
+    let hop_seconds = config.hop_size as f32 / config.sample_rate;
+    let mut analysis = FrameAnalysis {
+        detected_frequency,
+        ..FrameAnalysis::with_ratio(previous_pitch_shift_ratio)
+    };
+
+    // Outside the configured range (rumble, sibilance) the previous ratio is held,
+    // as it is when the held note cannot be resolved
+    let note_frequency = if detected_frequency > 0.001
+        && (config.min_frequency..=config.max_frequency).contains(&detected_frequency)
+    {
+        target_note(detected_frequency, config, settings)
+    } else {
+        None
+    };
+    if let Some(note_frequency) = note_frequency {
+        let strength = if note_frequency > 0.0 {
+            let midi_note = roundf(12.0 * log2f(note_frequency / 440.0) + 69.0) as i32;
+            let pitch_class = midi_note.rem_euclid(12) as u8;
+            settings.correction_strength.for_pitch_class(settings.key, pitch_class)
+        } else {
+            1.0
+        };
+
+        // A glide starts from the previous target, so it needs one
+        let target_frequency = if config.glide == Glide::Off
+            || previous.target_frequency <= 0.0
+            || note_frequency <= 0.0
+        {
+            note_frequency
+        } else {
+            let remaining = log2f(note_frequency / previous.target_frequency);
+            analysis.glide_step =
+                if note_frequency == previous.note_frequency && previous.glide_step > 0.0 {
+                    previous.glide_step
+                } else {
+                    config.glide.step(remaining, hop_seconds)
+                };
+            if fabsf(remaining) <= analysis.glide_step {
+                note_frequency
+            } else {
+                previous.target_frequency * exp2f(analysis.glide_step.copysign(remaining))
+            }
+        };
+
+        // A scoop starts at the onset of a note and eases into the target
+        analysis.ornament_cents = if previous.target_frequency <= 0.0 {
+            -config.ornaments.scoop_cents
         } else {
-            crate::audio::keys::get_frequency(settings.key, settings.note, settings.octave, false)
+            approach(previous.ornament_cents, 0.0, config.ornaments.scoop_step(hop_seconds))
         };
-        let raw_ratio = target_frequency / detected_frequency;
+
+        let mut raw_ratio = target_frequency / detected_frequency;
+        if strength < 1.0 {
+            // Scale the correction in cents
+            raw_ratio = powf(raw_ratio, strength);
+        }
+        if analysis.ornament_cents != 0.0 {
+            raw_ratio *= exp2f(analysis.ornament_cents / 1200.0);
+        }
         let clamped_ratio = raw_ratio.clamp(0.5, 2.0);
-        const SMOOTHING_FACTOR: f32 = 0.99;
-        pitch_shift_ratio = clamped_ratio * SMOOTHING_FACTOR
-            + previous_pitch_shift_ratio * (1.0 - SMOOTHING_FACTOR);
+        let retune_speed = config.transition_speed.clamp(0.0, 1.0);
+        analysis.target_frequency = target_frequency;
+        analysis.note_frequency = note_frequency;
+        analysis.pitch_shift_ratio =
+            clamped_ratio * retune_speed + previous_pitch_shift_ratio * (1.0 - retune_speed);
+    } else {
+        // The held ratio falls away at the end of a phrase
+        analysis.ornament_cents = approach(
+            previous.ornament_cents,
+            -config.ornaments.fall_cents,
+            config.ornaments.fall_step(hop_seconds),
+        );
+        if analysis.ornament_cents != previous.ornament_cents {
+            analysis.pitch_shift_ratio *=
+                exp2f((analysis.ornament_cents - previous.ornament_cents) / 1200.0);
+        }
     }
 
-    pitch_shift_ratio
+    analysis
+}
+
+/// Note a detected frequency is corrected towards, `None` if the held note is invalid
+fn target_note(
+    detected_frequency: f32,
+    config: &VocalEffectsConfig,
+    settings: &MusicalSettings,
+) -> Option<f32> {
+    if !settings.note.is_auto() {
+        return crate::audio::keys::get_frequency(
+            settings.key.into(),
+            settings.note.into(),
+            settings.octave.into(),
+            false,
+            settings.held_note_cents,
+        )
+        .ok();
+    }
+    if let Some(tuning) = config.tuning {
+        return Some(tuning.nearest(detected_frequency));
+    }
+    let chord_tone = match settings.target {
+        TargetSource::Chord(chord) => {
+            crate::audio::frequencies::find_nearest_chord_tone(detected_frequency, chord)
+        }
+        TargetSource::Key => None,
+    };
+    Some(chord_tone.unwrap_or_else(|| {
+        crate::audio::frequencies::find_nearest_note_in_key(
+            detected_frequency,
+            settings.key.scale_frequencies(),
+        )
+    }))
+}
+
+/// [`correct_frequency_from`] for a pitch estimate, applying the configured
+/// [`LowConfidence`] handling to frames below the confidence threshold
+///
+/// The returned analysis always carries the detected frequency and confidence,
+/// also when the correction was held or bypassed.
+pub fn correct_estimate_from(
+    estimate: PitchEstimate,
+    previous: &FrameAnalysis,
+    config: &VocalEffectsConfig,
+    settings: &MusicalSettings,
+) -> FrameAnalysis {
+    let confident = estimate.confidence >= config.confidence_threshold;
+    let mut analysis = match config.low_confidence {
+        LowConfidence::Hold if !confident => *previous,
+        LowConfidence::Bypass if !confident => {
+            let retune_speed = config.transition_speed.clamp(0.0, 1.0);
+            FrameAnalysis::with_ratio(
+                retune_speed + previous.pitch_shift_ratio * (1.0 - retune_speed),
+            )
+        }
+        _ => correct_frequency_from(estimate.frequency, previous, config, settings),
+    };
+    analysis.detected_frequency = estimate.frequency;
+    analysis.confidence = estimate.confidence;
+    analysis
+}
+
+/// Moves `from` towards `to` by at most `step`
+fn approach(from: f32, to: f32, step: f32) -> f32 {
+    if fabsf(to - from) <= step {
+        to
+    } else {
+        from + step.copysign(to - from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Key,
+        state::{ChordQuality, ChordSpec, CorrectionStrength},
+    };
+
+    const BIN_WIDTH: f32 = 48000.0 / 1024.0;
+
+    fn single_peak(bin: usize) -> ([f32; 512], [f32; 512]) {
+        let mut magnitudes = [0.0f32; 512];
+        magnitudes[bin] = 1.0;
+        let frequencies: [f32; 512] = core::array::from_fn(|i| i as f32);
+        (magnitudes, frequencies)
+    }
+
+    #[test]
+    fn test_cepstral_envelope_leaves_scratch_zeroed() {
+        // A log spectrum with a single cepstral component survives the lifter
+        let magnitudes: [f32; 512] = core::array::from_fn(|i| {
+            expf(0.5 * libm::cosf(2.0 * core::f32::consts::PI * i as f32 / 512.0))
+        });
+        let mut envelope = [0.0f32; 512];
+        let mut spectrum = [microfft::Complex32 { re: 1.0, im: 1.0 }; 512];
+        let mut cepstrum = [1.0f32; 1024];
+        extract_cepstral_envelope_with(
+            &mut crate::dsp::Fft1024,
+            &magnitudes,
+            &mut envelope,
+            64,
+            &mut spectrum,
+            &mut cepstrum,
+        );
+        for i in 0..400 {
+            assert!((logf(envelope[i]) - logf(magnitudes[i])).abs() < 0.01, "bin {i}");
+        }
+        assert!(spectrum.iter().all(|bin| bin.re == 0.0 && bin.im == 0.0));
+        assert!(cepstrum.iter().all(|&c| c == 0.0));
+    }
+
+    #[test]
+    fn test_envelope_cache_glides_between_updates() {
+        let magnitudes_of = |depth: f32| -> [f32; 512] {
+            core::array::from_fn(|i| {
+                expf(depth * libm::cosf(2.0 * core::f32::consts::PI * i as f32 / 512.0))
+            })
+        };
+        let envelope_of = |depth: f32| -> [f32; 512] {
+            let mut envelope = [0.0f32; 512];
+            extract_cepstral_envelope_with(
+                &mut crate::dsp::Fft1024,
+                &magnitudes_of(depth),
+                &mut envelope,
+                64,
+                &mut [microfft::Complex32 { re: 0.0, im: 0.0 }; 512],
+                &mut [0.0f32; 1024],
+            );
+            envelope
+        };
+        let (first, second) = (envelope_of(0.5), envelope_of(-0.5));
+
+        let mut cache = EnvelopeCache::<1024>::new();
+        let hop = |cache: &mut EnvelopeCache<1024>, depth: f32, interval: usize| {
+            let mut envelope = [0.0f32; 512];
+            let config = VocalEffectsConfig {
+                lifter_cutoff_override: Some(64),
+                envelope_update_interval: interval,
+                ..Default::default()
+            };
+            cache.update(
+                &mut crate::dsp::Fft1024,
+                &magnitudes_of(depth),
+                &mut envelope,
+                &mut [microfft::Complex32 { re: 0.0, im: 0.0 }; 512],
+                &mut [0.0f32; 1024],
+                &config,
+            );
+            envelope
+        };
+
+        // Every hop is a fresh extraction at an interval of one
+        assert_eq!(hop(&mut cache, 0.5, 1), first);
+        assert_eq!(hop(&mut cache, -0.5, 1), second);
+
+        // The first envelope holds until the next update, which is reached in four steps
+        cache.clear();
+        assert_eq!(hop(&mut cache, 0.5, 4), first);
+        for _ in 0..3 {
+            assert_eq!(hop(&mut cache, -0.5, 4), first);
+        }
+        for step in 1..=4 {
+            let envelope = hop(&mut cache, -0.5, 4);
+            for i in 0..512 {
+                let expected = first[i] + (second[i] - first[i]) * step as f32 / 4.0;
+                assert!((envelope[i] - expected).abs() < 1e-4, "hop {step} bin {i}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_pitch_shift_corrects_in_range() {
+        let (magnitudes, frequencies) = single_peak(10);
+        let config = VocalEffectsConfig::default();
+        let settings = MusicalSettings::default();
+
+        let ratio =
+            calculate_pitch_shift(&magnitudes, &frequencies, 1.0, &config, &settings, BIN_WIDTH);
+        // 468.75 Hz is pulled up to B4 in C major
+        let expected = 0.99 * (493.92 / (10.0 * BIN_WIDTH)) + 0.01;
+        assert!((ratio - expected).abs() < 1e-4, "ratio {ratio}");
+    }
+
+    #[test]
+    fn test_pitch_shift_uses_interpolated_peak_between_bins() {
+        // A partial a sixth of a bin above bin 10
+        let mut magnitudes = [0.0f32; 512];
+        magnitudes[9] = 0.5;
+        magnitudes[10] = 1.0;
+        magnitudes[11] = 0.75;
+        let config = VocalEffectsConfig::default();
+        let settings = MusicalSettings::default();
+        let interpolated = 0.99 * (493.92 / ((10.0 + 1.0 / 6.0) * BIN_WIDTH)) + 0.01;
+
+        // Phase estimates from a reset frame disagree with the peak, so the
+        // interpolated bin is used instead of the integer one
+        let reset = [0.0f32; 512];
+        let ratio = calculate_pitch_shift(&magnitudes, &reset, 1.0, &config, &settings, BIN_WIDTH);
+        assert!((ratio - interpolated).abs() < 1e-4, "ratio {ratio}");
+
+        // Agreeing phase estimates of the peak and its upper neighbour are blended at
+        // the same offset on every later frame
+        let mut agreeing = [0.0f32; 512];
+        agreeing[10] = 10.1;
+        agreeing[11] = 10.4;
+        let ratio =
+            calculate_pitch_shift(&magnitudes, &agreeing, 1.0, &config, &settings, BIN_WIDTH);
+        let expected = 0.99 * (493.92 / (10.15 * BIN_WIDTH)) + 0.01;
+        assert!((ratio - expected).abs() < 1e-4, "ratio {ratio}");
+    }
+
+    #[test]
+    fn test_retune_speed_smooths_ratio() {
+        let (magnitudes, frequencies) = single_peak(10);
+        let config = VocalEffectsConfig::builder().retune_speed(0.5).build().unwrap();
+        let settings = MusicalSettings::default();
+
+        let ratio =
+            calculate_pitch_shift(&magnitudes, &frequencies, 1.0, &config, &settings, BIN_WIDTH);
+        let expected = 0.5 * (493.92 / (10.0 * BIN_WIDTH)) + 0.5;
+        assert!((ratio - expected).abs() < 1e-4, "ratio {ratio}");
+    }
+
+    #[test]
+    fn test_pitch_shift_holds_below_min_frequency() {
+        let (magnitudes, frequencies) = single_peak(1);
+        let config = VocalEffectsConfig::default();
+        let settings = MusicalSettings::default();
+
+        let ratio =
+            calculate_pitch_shift(&magnitudes, &frequencies, 1.3, &config, &settings, BIN_WIDTH);
+        assert_eq!(ratio, 1.3);
+    }
+
+    #[test]
+    fn test_pitch_shift_holds_above_max_frequency() {
+        let (magnitudes, frequencies) = single_peak(10);
+        let config = VocalEffectsConfig { max_frequency: 400.0, ..Default::default() };
+        let settings = MusicalSettings::default();
+
+        let ratio =
+            calculate_pitch_shift(&magnitudes, &frequencies, 0.8, &config, &settings, BIN_WIDTH);
+        assert_eq!(ratio, 0.8);
+    }
+
+    #[test]
+    fn test_chord_target_overrides_key() {
+        let config = VocalEffectsConfig { transition_speed: 1.0, ..Default::default() };
+        // 300 Hz is nearest to D4 in C major
+        let settings = MusicalSettings::default();
+        let analysis = correct_frequency(300.0, 1.0, &config, &settings);
+        assert!((analysis.target_frequency - 293.66).abs() < 0.1);
+
+        // During a borrowed Eb major chord it snaps to Eb4 instead
+        let chord = ChordSpec::new(3, ChordQuality::Major);
+        let settings = MusicalSettings { target: TargetSource::Chord(chord), ..settings };
+        let analysis = correct_frequency(300.0, 1.0, &config, &settings);
+        assert!((analysis.target_frequency - 311.2).abs() < 0.1, "{analysis:?}");
+
+        // A held note still wins, and an empty chord falls back to the key
+        let held = MusicalSettings { note: crate::Note::Degree1, ..settings };
+        assert!(
+            (correct_frequency(300.0, 1.0, &config, &held).target_frequency - 261.6).abs() < 0.1
+        );
+        let empty =
+            MusicalSettings { target: TargetSource::Chord(ChordSpec::from_mask(0)), ..settings };
+        assert!(
+            (correct_frequency(300.0, 1.0, &config, &empty).target_frequency - 293.66).abs() < 0.1
+        );
+    }
+
+    #[test]
+    fn test_held_note_detune() {
+        let config = VocalEffectsConfig { transition_speed: 1.0, ..Default::default() };
+        let held = MusicalSettings { note: crate::Note::Degree6, ..Default::default() };
+        let analysis = correct_frequency(430.0, 1.0, &config, &held);
+        assert!((analysis.target_frequency - 440.0).abs() < 0.01, "{analysis:?}");
+
+        // A few cents sharp of A4, still pulled with the strength of A
+        let sharp = MusicalSettings { held_note_cents: 8.0, ..held };
+        let analysis = correct_frequency(430.0, 1.0, &config, &sharp);
+        let cents = 1200.0 * log2f(analysis.target_frequency / 440.0);
+        assert!((cents - 8.0).abs() < 0.01, "{analysis:?}");
+        assert!((analysis.pitch_shift_ratio * 430.0 - analysis.target_frequency).abs() < 1e-2);
+
+        // Out of range leaves the previous ratio in place
+        let invalid = MusicalSettings { held_note_cents: 80.0, ..held };
+        let analysis = correct_frequency(430.0, 1.2, &config, &invalid);
+        assert_eq!(analysis.target_frequency, 0.0);
+        assert_eq!(analysis.pitch_shift_ratio, 1.2);
+    }
+
+    #[test]
+    fn test_tuning_replaces_key_and_chord() {
+        // Quarter-tone tuning from C4
+        let steps: [f32; 24] = core::array::from_fn(|step| (step + 1) as f32 * 50.0);
+        let tuning = crate::audio::Tuning::from_cents(261.63, &steps).unwrap();
+        let config =
+            VocalEffectsConfig::builder().retune_speed(1.0).tuning(tuning).build().unwrap();
+        let chord = ChordSpec::new(0, ChordQuality::Major);
+        let settings = MusicalSettings { target: TargetSource::Chord(chord), ..Default::default() };
+
+        // Halfway between E4 and F4 is a tuning note of its own
+        let neutral = 261.63 * exp2f(450.0 / 1200.0);
+        let analysis = correct_frequency(neutral * 1.01, 1.0, &config, &settings);
+        assert!((analysis.target_frequency - neutral).abs() < 0.01, "{analysis:?}");
+
+        // Held notes still come from the key
+        let held = MusicalSettings { note: crate::Note::Degree3, ..settings };
+        let analysis = correct_frequency(neutral, 1.0, &config, &held);
+        assert!((analysis.target_frequency - 329.6).abs() < 0.01, "{analysis:?}");
+    }
+
+    #[test]
+    fn test_correction_strength_per_degree() {
+        let config = VocalEffectsConfig { transition_speed: 1.0, ..Default::default() };
+        let mut strength = CorrectionStrength::FULL;
+        // Leave the second degree alone, pull the third degree half way
+        strength.set_degree(Key::CMajor, crate::Note::Degree2, 0.0);
+        strength.set_degree(Key::CMajor, crate::Note::Degree3, 0.5);
+        let settings = MusicalSettings { correction_strength: strength, ..Default::default() };
+
+        // Sung sharp of D4: uncorrected
+        let analysis = correct_frequency(300.0, 1.0, &config, &settings);
+        assert!((analysis.target_frequency - 293.66).abs() < 0.1);
+        assert_eq!(analysis.pitch_shift_ratio, 1.0);
+
+        // Sung flat of E4: half the cents
+        let analysis = correct_frequency(320.0, 1.0, &config, &settings);
+        let half = libm::sqrtf(analysis.target_frequency / 320.0);
+        assert!((analysis.pitch_shift_ratio - half).abs() < 1e-3, "{analysis:?}");
+
+        // The tonic keeps full correction, also after the key moves
+        let analysis = correct_frequency(255.0, 1.0, &config, &settings);
+        assert!((analysis.pitch_shift_ratio * 255.0 - analysis.target_frequency).abs() < 1e-2);
+        let d_major = MusicalSettings { key: Key::DMajor, ..settings };
+        let analysis = correct_frequency(300.0, 1.0, &config, &d_major);
+        assert!((analysis.pitch_shift_ratio * 300.0 - analysis.target_frequency).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_glide_time_reaches_new_note() {
+        let config = VocalEffectsConfig::builder()
+            .retune_speed(1.0)
+            .glide(Glide::Time(100.0))
+            .build()
+            .unwrap();
+        let c4 = MusicalSettings { note: crate::Note::Degree1, ..Default::default() };
+        let g4 = MusicalSettings { note: crate::Note::Degree5, ..Default::default() };
+
+        // The first target is reached at once
+        let mut analysis = correct_frequency_from(270.0, &FrameAnalysis::new(), &config, &c4);
+        assert!((analysis.target_frequency - 261.63).abs() < 0.1);
+
+        // 100 ms is 18.75 hops of 256 samples
+        let mut targets = [0.0f32; 19];
+        for target in targets.iter_mut() {
+            analysis = correct_frequency_from(270.0, &analysis, &config, &g4);
+            *target = analysis.target_frequency;
+        }
+        assert!(targets.windows(2).all(|pair| pair[1] > pair[0]), "{targets:?}");
+        assert!(targets[17] < 391.0, "{targets:?}");
+        assert!((targets[18] - 392.0).abs() < 0.1, "{targets:?}");
+        assert_eq!(analysis.note_frequency, targets[18]);
+        assert!((analysis.pitch_shift_ratio * 270.0 - targets[18]).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_glide_rate_and_restart() {
+        let config = VocalEffectsConfig::builder().glide(Glide::Rate(12.0)).build().unwrap();
+        let c4 = MusicalSettings { note: crate::Note::Degree1, ..Default::default() };
+        let g4 = MusicalSettings { note: crate::Note::Degree5, ..Default::default() };
+
+        let mut analysis = correct_frequency_from(270.0, &FrameAnalysis::new(), &config, &c4);
+        for _ in 0..50 {
+            analysis = correct_frequency_from(270.0, &analysis, &config, &g4);
+        }
+        // An octave per second is 0.064 semitones per hop
+        let semitones = 12.0 * libm::log2f(analysis.target_frequency / 261.63);
+        assert!((semitones - 3.2).abs() < 0.01, "{semitones}");
+
+        // An out-of-range frame ends the glide, the next note is reached at once
+        let analysis = correct_frequency_from(20.0, &analysis, &config, &g4);
+        assert_eq!(analysis.target_frequency, 0.0);
+        let analysis = correct_frequency_from(270.0, &analysis, &config, &g4);
+        assert!((analysis.target_frequency - 392.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_scoop_and_fall() {
+        // 60 ms is 11.25 hops of 256 samples
+        let config = VocalEffectsConfig::builder()
+            .retune_speed(1.0)
+            .scoop(90.0, 60.0)
+            .fall(200.0, 30.0)
+            .build()
+            .unwrap();
+        let settings = MusicalSettings { note: crate::Note::Degree1, ..Default::default() };
+        let cents = |analysis: &FrameAnalysis| {
+            1200.0 * libm::log2f(analysis.pitch_shift_ratio * 270.0 / analysis.target_frequency)
+        };
+
+        // The onset starts 90 cents flat and rises 8 cents per hop
+        let mut analysis = correct_frequency_from(270.0, &FrameAnalysis::new(), &config, &settings);
+        assert!((cents(&analysis) + 90.0).abs() < 0.01, "{analysis:?}");
+        analysis = correct_frequency_from(270.0, &analysis, &config, &settings);
+        assert!((cents(&analysis) + 82.0).abs() < 0.01, "{analysis:?}");
+        for _ in 0..11 {
+            analysis = correct_frequency_from(270.0, &analysis, &config, &settings);
+        }
+        assert_eq!(analysis.ornament_cents, 0.0);
+        assert!(cents(&analysis).abs() < 0.01);
+
+        // The phrase ends: the held ratio drops by 200 cents within 6 hops
+        let sung = analysis.pitch_shift_ratio;
+        for _ in 0..6 {
+            analysis = correct_frequency_from(0.0, &analysis, &config, &settings);
+        }
+        assert_eq!(analysis.ornament_cents, -200.0);
+        let fallen = 1200.0 * libm::log2f(analysis.pitch_shift_ratio / sung);
+        assert!((fallen + 200.0).abs() < 0.01, "{fallen}");
+
+        // The next note scoops again, and the stateless path has no ornaments
+        let analysis = correct_frequency_from(270.0, &analysis, &config, &settings);
+        assert!((cents(&analysis) + 90.0).abs() < 0.01, "{analysis:?}");
+        let analysis = correct_frequency(270.0, 1.0, &config, &settings);
+        assert!(cents(&analysis).abs() < 0.01, "{analysis:?}");
+    }
+
+    #[test]
+    fn test_correction_strength_storage() {
+        let mut strength = CorrectionStrength::uniform(0.25);
+        assert!((strength.semitone(14) - 0.25).abs() < 1.0 / 255.0);
+        strength.set_degree(Key::AMinor, crate::Note::Degree3, 1.0);
+        assert_eq!(strength.for_pitch_class(Key::AMinor, 0), 1.0);
+        strength.set_degree(Key::AMinor, crate::Note::Auto, 0.0);
+        assert_eq!(CorrectionStrength::from_bytes(strength.to_bytes()), strength);
+        assert_eq!(CorrectionStrength::uniform(f32::NAN).semitone(0), 0.0);
+    }
+
+    #[test]
+    fn test_chord_spec_pitch_classes() {
+        let g7 = ChordSpec::new(7, ChordQuality::Dominant7);
+        assert_eq!(g7, ChordSpec::from_pitch_classes(&[7, 11, 2, 5]));
+        assert!(g7.contains(5) && !g7.contains(0) && !g7.contains(12));
+        assert_eq!(ChordSpec::new(11, ChordQuality::Diminished).mask(), 1 << 11 | 1 << 2 | 1 << 5);
+        assert!(ChordSpec::from_mask(0xF000).is_empty());
+    }
+
+    #[test]
+    fn test_pitch_decimation_limits_search_band() {
+        let (mut magnitudes, frequencies) = single_peak(10);
+        // Loud sibilance above the decimated band
+        magnitudes[300] = 2.0;
+        let settings = MusicalSettings::default();
+        let expected = 0.99 * (493.92 / (10.0 * BIN_WIDTH)) + 0.01;
+
+        let full = VocalEffectsConfig { max_frequency: 20000.0, ..Default::default() };
+        let ratio =
+            calculate_pitch_shift(&magnitudes, &frequencies, 1.0, &full, &settings, BIN_WIDTH);
+        assert!((ratio - expected).abs() > 1e-3, "ratio {ratio}");
+
+        let decimated = VocalEffectsConfig {
+            max_frequency: 20000.0,
+            pitch_decimation: crate::config::PitchDecimation::X4,
+            ..Default::default()
+        };
+        let ratio =
+            calculate_pitch_shift(&magnitudes, &frequencies, 1.0, &decimated, &settings, BIN_WIDTH);
+        assert!((ratio - expected).abs() < 1e-4, "ratio {ratio}");
+    }
+
+    #[test]
+    fn test_low_confidence_holds_or_bypasses() {
+        let settings = MusicalSettings::default();
+        let previous = FrameAnalysis {
+            target_frequency: 440.0,
+            note_frequency: 440.0,
+            pitch_shift_ratio: 1.2,
+            ..FrameAnalysis::new()
+        };
+        let noisy = PitchEstimate { frequency: 700.0, confidence: 0.2 };
+        let gated = |handling| {
+            let config =
+                VocalEffectsConfig::builder().confidence_gate(handling, 0.5).build().unwrap();
+            correct_estimate_from(noisy, &previous, &config, &settings)
+        };
+
+        let corrected = gated(LowConfidence::Correct);
+        assert!((corrected.target_frequency - 698.56).abs() < 0.01, "{corrected:?}");
+
+        let held = gated(LowConfidence::Hold);
+        assert_eq!(held.pitch_shift_ratio, 1.2);
+        assert_eq!(held.target_frequency, 440.0);
+        assert_eq!((held.detected_frequency, held.confidence), (700.0, 0.2));
+
+        let bypassed = gated(LowConfidence::Bypass);
+        assert!((bypassed.pitch_shift_ratio - (0.99 + 0.01 * 1.2)).abs() < 1e-6);
+        assert_eq!(bypassed.target_frequency, 0.0);
+
+        // Confident frames are corrected whatever the handling
+        let clean = PitchEstimate { frequency: 700.0, confidence: 0.9 };
+        let config = VocalEffectsConfig::builder()
+            .confidence_gate(LowConfidence::Hold, 0.5)
+            .build()
+            .unwrap();
+        let analysis = correct_estimate_from(clean, &previous, &config, &settings);
+        assert_eq!(analysis.target_frequency, corrected.target_frequency);
+        assert_eq!(analysis.confidence, 0.9);
+    }
 }
@@ -48,26 +48,15 @@ const fn hann_window_value(n: usize, total_size: usize) -> f32 {
     0.5 * (1.0 - cos_val)
 }
 
-/// Macro to generate a Hann window array at compile time
-macro_rules! hann_window_array {
-    ($size:expr) => {{
-        const SIZE: usize = $size;
-        const fn generate() -> [f32; SIZE] {
-            let mut window = [0.0; SIZE];
-            let mut i = 0;
-            while i < SIZE {
-                window[i] = hann_window_value(i, SIZE);
-                i += 1;
-            }
-            window
-        }
-        generate()
-    }};
-}
-
-/// Generic const function to create Hann windows
-/// This can be used in const contexts
-pub const fn create_hann_window<const N: usize>() -> [f32; N] {
+/// Symmetric Hann window of any size, usable in const contexts
+///
+/// ```
+/// use synthphone_e_vocal_dsp::dsp::windowing::hann;
+///
+/// const WINDOW: [f32; 8192] = hann::<8192>();
+/// assert_eq!(WINDOW[0], 0.0);
+/// ```
+pub const fn hann<const N: usize>() -> [f32; N] {
     let mut window = [0.0; N];
     let mut i = 0;
     while i < N {
@@ -77,6 +66,12 @@ pub const fn create_hann_window<const N: usize>() -> [f32; N] {
     window
 }
 
+/// Generic const function to create Hann windows
+/// This can be used in const contexts
+pub const fn create_hann_window<const N: usize>() -> [f32; N] {
+    hann::<N>()
+}
+
 /// Struct to hold window data with const generic size
 pub struct HannWindow<const N: usize> {
     data: [f32; N],
@@ -89,6 +84,9 @@ impl<const N: usize> Default for HannWindow<N> {
 }
 
 impl<const N: usize> HannWindow<N> {
+    /// Window data, evaluated at compile time for each size used
+    pub const WINDOW: [f32; N] = hann::<N>();
+
     /// Create a new Hann window at compile time
     pub const fn new() -> Self {
         Self { data: create_hann_window::<N>() }
@@ -116,17 +114,25 @@ pub static HANN_4096: HannWindow<4096> = HannWindow::new();
 
 /// Function to get a Hann window for any size (computed at compile time when possible)
 pub const fn get_hann_window<const N: usize>() -> [f32; N] {
-    create_hann_window::<N>()
+    hann::<N>()
+}
+
+/// Static Hann window of any size
+///
+/// Each size is generated once at compile time, so FFT backends for sizes without a
+/// named table can still return a `&'static` window.
+pub fn static_hann_window<const N: usize>() -> &'static [f32; N] {
+    &HannWindow::<N>::WINDOW
 }
 
-// Pre-computed arrays for common sizes using the macro
-pub const HANN_WINDOW_64: [f32; 64] = hann_window_array!(64);
-pub const HANN_WINDOW_128: [f32; 128] = hann_window_array!(128);
-pub const HANN_WINDOW_256: [f32; 256] = hann_window_array!(256);
-pub const HANN_WINDOW_512: [f32; 512] = hann_window_array!(512);
-pub const HANN_WINDOW_1024: [f32; 1024] = hann_window_array!(1024);
-pub const HANN_WINDOW_2048: [f32; 2048] = hann_window_array!(2048);
-pub const HANN_WINDOW_4096: [f32; 4096] = hann_window_array!(4096);
+// Pre-computed arrays for common sizes
+pub const HANN_WINDOW_64: [f32; 64] = hann::<64>();
+pub const HANN_WINDOW_128: [f32; 128] = hann::<128>();
+pub const HANN_WINDOW_256: [f32; 256] = hann::<256>();
+pub const HANN_WINDOW_512: [f32; 512] = hann::<512>();
+pub const HANN_WINDOW_1024: [f32; 1024] = hann::<1024>();
+pub const HANN_WINDOW_2048: [f32; 2048] = hann::<2048>();
+pub const HANN_WINDOW_4096: [f32; 4096] = hann::<4096>();
 
 // Backwards compatibility
 pub const HANN_WINDOW: [f32; FFT_SIZE] = HANN_WINDOW_1024;
@@ -141,6 +147,8 @@ pub fn get_static_hann_window(size: usize) -> Option<&'static [f32]> {
         1024 => Some(HANN_1024.as_slice()),
         2048 => Some(HANN_2048.as_slice()),
         4096 => Some(HANN_4096.as_slice()),
+        #[cfg(feature = "fft-8192")]
+        8192 => Some(static_hann_window::<8192>().as_slice()),
         _ => None,
     }
 }
@@ -163,6 +171,29 @@ mod tests {
         assert!(WINDOW[8] > 0.8);
     }
 
+    #[test]
+    fn test_hann_matches_reference() {
+        fn check<const N: usize>() {
+            let window = hann::<N>();
+            for (n, value) in window.iter().enumerate() {
+                let expected = 0.5
+                    * (1.0 - libm::cos(2.0 * core::f64::consts::PI * n as f64 / (N - 1) as f64));
+                assert!((*value as f64 - expected).abs() < 1e-3, "N = {N}, n = {n}");
+            }
+        }
+        check::<3>();
+        check::<100>();
+        check::<128>();
+        check::<4096>();
+        check::<8192>();
+    }
+
+    #[test]
+    fn test_static_window_for_any_size() {
+        assert_eq!(static_hann_window::<300>(), &hann::<300>());
+        assert_eq!(static_hann_window::<512>(), &HANN_WINDOW_512);
+    }
+
     #[test]
     fn test_window_struct() {
         const WINDOW: HannWindow<32> = HannWindow::new();
@@ -0,0 +1,111 @@
+//! Breath retention.
+//!
+//! Correction resynthesises every frame, and breaths, air and the noise of the
+//! room come out thin or not at all, which makes a corrected take sound
+//! sterile. The mixer high-passes the original signal continuously and adds it
+//! to the output while the pitch detector finds the frame unvoiced, ramping
+//! the level over a hop so it never clicks in or out.
+
+use crate::{config::BreathRetention, dsp::biquad::Biquad};
+
+/// Quality of the high-pass (Butterworth)
+const CUTOFF_Q: f32 = core::f32::consts::FRAC_1_SQRT_2;
+
+/// Stateful mixer of the original signal's breath into a processed stream
+///
+/// The [`Engine`](crate::Engine) runs one on its output when
+/// [`VocalEffectsConfig::breath_retention`](crate::VocalEffectsConfig::breath_retention)
+/// is set.
+///
+/// ```rust
+/// use synthphone_e_vocal_dsp::{BreathRetention, effects::BreathMixer};
+///
+/// let settings = BreathRetention { cutoff_hz: 2000.0, level: 0.5 };
+/// let mut mixer = BreathMixer::new(settings, 48000.0);
+/// let original = [0.1f32; 64];
+/// let mut processed = [0.0f32; 64];
+/// mixer.process(&original, &mut processed, true);
+/// ```
+#[derive(Debug, Clone)]
+pub struct BreathMixer {
+    settings: BreathRetention,
+    filter: Biquad,
+    /// Level at the end of the last block
+    gain: f32,
+}
+
+impl BreathMixer {
+    /// Creates a mixer for streams at `sample_rate` Hz
+    pub fn new(settings: BreathRetention, sample_rate: f32) -> Self {
+        Self {
+            settings,
+            filter: Biquad::high_pass(settings.cutoff_hz, CUTOFF_Q, sample_rate),
+            gain: 0.0,
+        }
+    }
+
+    /// Returns the settings the mixer was created with
+    pub fn settings(&self) -> &BreathRetention {
+        &self.settings
+    }
+
+    /// Adds the high-passed `original` to `samples` in place if `unvoiced`
+    ///
+    /// The level ramps from where the last block ended, so call it for every
+    /// block, voiced or not, to keep the filter running. Extra samples of the
+    /// longer slice are ignored.
+    pub fn process(&mut self, original: &[f32], samples: &mut [f32], unvoiced: bool) {
+        let target = if unvoiced { self.settings.level } else { 0.0 };
+        let length = original.len().min(samples.len());
+        let step = if length > 0 {
+            (target - self.gain) / length as f32
+        } else {
+            0.0
+        };
+        for (original, sample) in original.iter().zip(samples.iter_mut()) {
+            self.gain += step;
+            *sample += self.gain * self.filter.process(*original);
+        }
+        self.gain = target;
+    }
+
+    /// Clears the filter and fades the level out
+    pub fn reset(&mut self) {
+        self.filter.reset();
+        self.gain = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keeps_highs_of_unvoiced_blocks() {
+        let settings = BreathRetention { cutoff_hz: 2000.0, level: 0.5 };
+        let mut mixer = BreathMixer::new(settings, 48000.0);
+        let tone =
+            |n: usize, hz: f32| libm::sinf(2.0 * core::f32::consts::PI * hz * n as f32 / 48000.0);
+        let peak = |samples: &[f32]| samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+
+        // Voiced blocks run the filter but add nothing
+        let air: [f32; 480] = core::array::from_fn(|n| tone(n, 8000.0));
+        let mut samples = [0.0f32; 480];
+        mixer.process(&air, &mut samples, false);
+        assert!(samples.iter().all(|&sample| sample == 0.0));
+
+        // The level ramps up over the first unvoiced block, then holds
+        let mut ramp = [0.0f32; 480];
+        mixer.process(&air, &mut ramp, true);
+        assert!(peak(&ramp[..48]) < 0.1 && peak(&ramp[432..]) > 0.4, "{ramp:?}");
+        let mut held = [0.0f32; 480];
+        mixer.process(&air, &mut held, true);
+        assert!((peak(&held) - 0.5).abs() < 0.05, "{}", peak(&held));
+
+        // Below the cutoff little comes through
+        let hum: [f32; 4800] = core::array::from_fn(|n| tone(n, 200.0));
+        let mut samples = [0.0f32; 4800];
+        mixer.process(&hum, &mut samples, true);
+        assert!(peak(&samples[2400..]) < 0.01, "{}", peak(&samples[2400..]));
+    }
+}
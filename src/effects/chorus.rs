@@ -0,0 +1,230 @@
+//! Chorus and flanger.
+//!
+//! The signal is read back from a [`DelayBuffer`] at a delay swept by a sine
+//! LFO, with linear interpolation between samples, and mixed in. A few
+//! milliseconds of delay with feedback give a flanger's comb sweep; 10 to 20 ms
+//! with little feedback give the detuned doubling of a chorus, which thickens a
+//! hard-tuned voice or widens a vocoder pad.
+//!
+//! The rate is in Hz or a note value; note values follow the phase of the
+//! [`TempoClock`], so the sweep stays on the beat.
+
+use core::f32::consts::TAU;
+
+use libm::{floorf, sinf};
+
+use crate::{
+    config::{ChorusSettings, ModulationRate},
+    ring_buffer::DelayBuffer,
+    stereo::apply_width,
+    tempo::TempoClock,
+};
+
+/// Longest delay the sweep can reach, in milliseconds
+pub const MAX_CHORUS_MS: f32 = 20.0;
+
+/// Samples the line holds, [`MAX_CHORUS_MS`] at 96 kHz
+const LINE_CAPACITY: usize = 2048;
+
+/// Stateful chorus running on a time-domain stream
+///
+/// The [`Engine`](crate::Engine) runs one on its output when
+/// [`VocalEffectsConfig::chorus`](crate::VocalEffectsConfig::chorus) is set.
+///
+/// ```rust
+/// use synthphone_e_vocal_dsp::{ChorusSettings, ModulationRate, TempoClock, effects::Chorus};
+///
+/// let settings = ChorusSettings {
+///     rate: ModulationRate::Hz(0.8),
+///     delay_ms: 12.0,
+///     depth_ms: 3.0,
+///     feedback: 0.0,
+///     mix: 0.5,
+/// };
+/// let mut chorus = Chorus::new(settings, 48000.0);
+/// let mut block = [0.0f32; 64];
+/// chorus.process(&mut block, &TempoClock::default());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Chorus {
+    settings: ChorusSettings,
+    sample_rate: f32,
+    line: DelayBuffer<[f32; LINE_CAPACITY]>,
+    /// LFO phase from 0 up to 1
+    phase: f32,
+}
+
+impl Chorus {
+    /// Creates a chorus for a stream at `sample_rate` Hz
+    pub fn new(settings: ChorusSettings, sample_rate: f32) -> Self {
+        Self { settings, sample_rate, line: DelayBuffer::new([0.0; LINE_CAPACITY]), phase: 0.0 }
+    }
+
+    /// Returns the settings the chorus was created with
+    pub fn settings(&self) -> &ChorusSettings {
+        &self.settings
+    }
+
+    /// Adds the swept signal to `samples` in place
+    ///
+    /// A rate in note values takes its phase from `tempo` at the first sample.
+    pub fn process(&mut self, samples: &mut [f32], tempo: &TempoClock) {
+        let sweep = self.sweep(tempo);
+        for sample in samples.iter_mut() {
+            let delay = sweep.delay(self.phase);
+            self.advance(sweep.increment);
+            let swept = self.line.tap_fractional(delay);
+            self.line.push(*sample + self.settings.feedback * swept);
+            *sample += self.settings.mix * swept;
+        }
+    }
+
+    /// Adds the swept signal of the mid to both channels in place
+    ///
+    /// The right channel is read with the opposite sweep, so one side is ahead
+    /// while the other lags. `width` scales the difference between them.
+    pub fn process_stereo(
+        &mut self,
+        left: &mut [f32],
+        right: &mut [f32],
+        width: f32,
+        tempo: &TempoClock,
+    ) {
+        let sweep = self.sweep(tempo);
+        for (left, right) in left.iter_mut().zip(right.iter_mut()) {
+            let swept_left = self.line.tap_fractional(sweep.delay(self.phase));
+            let swept_right = self.line.tap_fractional(sweep.delay(self.phase + 0.5));
+            self.advance(sweep.increment);
+            let mid = (*left + *right) * core::f32::consts::FRAC_1_SQRT_2;
+            self.line.push(mid + self.settings.feedback * swept_left);
+            let (wet_left, wet_right) = apply_width(swept_left, swept_right, width);
+            *left += self.settings.mix * wet_left;
+            *right += self.settings.mix * wet_right;
+        }
+    }
+
+    /// Sweep of the next block, taking the phase from `tempo` for note values
+    fn sweep(&mut self, tempo: &TempoClock) -> Sweep {
+        let rate = match self.settings.rate {
+            ModulationRate::Hz(hz) => hz,
+            ModulationRate::Note(note) => {
+                self.phase = tempo.phase(note);
+                tempo.hz(note)
+            }
+        };
+        let per_ms = self.sample_rate / 1000.0;
+        Sweep {
+            increment: rate / self.sample_rate,
+            centre: self.settings.delay_ms * per_ms,
+            depth: self.settings.depth_ms * per_ms,
+        }
+    }
+
+    fn advance(&mut self, increment: f32) {
+        self.phase += increment;
+        self.phase -= floorf(self.phase);
+    }
+
+    /// Clears the line and restarts the LFO
+    pub fn reset(&mut self) {
+        self.line.clear();
+        self.phase = 0.0;
+    }
+}
+
+/// LFO increment and delay range of a block, in samples
+struct Sweep {
+    increment: f32,
+    centre: f32,
+    depth: f32,
+}
+
+impl Sweep {
+    /// Delay at LFO `phase`, kept within the line
+    fn delay(&self, phase: f32) -> f32 {
+        (self.centre + self.depth * sinf(TAU * phase)).clamp(1.0, (LINE_CAPACITY - 1) as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tempo::{NoteValue, Transport};
+
+    #[test]
+    fn test_sweep_follows_the_beat() {
+        // A quarter note at 60 BPM is a 1 Hz sweep, at its peak a quarter beat in
+        let settings = ChorusSettings {
+            rate: ModulationRate::Note(NoteValue::QUARTER),
+            delay_ms: 10.0,
+            depth_ms: 5.0,
+            feedback: 0.0,
+            mix: 1.0,
+        };
+        let mut chorus = Chorus::new(settings, 48000.0);
+        let mut tempo = TempoClock::default();
+        tempo.sync(Transport { bpm: 60.0, beat: 0.25, playing: true });
+        let mut samples = [0.0f32; 1024];
+        samples[0] = 1.0;
+        chorus.process(&mut samples, &tempo);
+
+        // The echo lands 15 ms late, not at the 10 ms centre
+        let echo: f32 = samples[718..=722].iter().sum();
+        assert!((echo - 1.0).abs() < 0.01, "{echo}");
+        assert_eq!(samples[480], 0.0);
+
+        // Half a beat later the sweep is at its shortest
+        chorus.reset();
+        tempo.sync(Transport { bpm: 60.0, beat: 0.75, playing: true });
+        let mut samples = [0.0f32; 1024];
+        samples[0] = 1.0;
+        chorus.process(&mut samples, &tempo);
+        let echo: f32 = samples[238..=242].iter().sum();
+        assert!((echo - 1.0).abs() < 0.01, "{echo}");
+    }
+
+    #[test]
+    fn test_flanger_feedback_stays_bounded() {
+        let settings = ChorusSettings {
+            rate: ModulationRate::Hz(0.5),
+            delay_ms: 2.0,
+            depth_ms: 1.5,
+            feedback: -0.9,
+            mix: 0.7,
+        };
+        let mut chorus = Chorus::new(settings, 96000.0);
+        let tempo = TempoClock::default();
+        let mut peak = 0.0f32;
+        for block in 0..400 {
+            let mut samples: [f32; 480] =
+                core::array::from_fn(|n| 0.5 * sinf((block * 480 + n) as f32 * 0.3));
+            chorus.process(&mut samples, &tempo);
+            peak = samples.iter().fold(peak, |peak, sample| peak.max(sample.abs()));
+        }
+        assert!(peak.is_finite() && peak < 10.0, "{peak}");
+    }
+
+    #[test]
+    fn test_stereo_sides_sweep_in_opposition() {
+        // At the peak of the sweep the left echo is 15 ms late and the right 5 ms
+        let settings = ChorusSettings {
+            rate: ModulationRate::Note(NoteValue::QUARTER),
+            delay_ms: 10.0,
+            depth_ms: 5.0,
+            feedback: 0.0,
+            mix: 1.0,
+        };
+        let mut chorus = Chorus::new(settings, 48000.0);
+        let mut tempo = TempoClock::default();
+        tempo.sync(Transport { bpm: 60.0, beat: 0.25, playing: true });
+        let mut left = [0.0f32; 1024];
+        let mut right = [0.0f32; 1024];
+        left[0] = core::f32::consts::FRAC_1_SQRT_2;
+        right[0] = core::f32::consts::FRAC_1_SQRT_2;
+        chorus.process_stereo(&mut left, &mut right, 1.0, &tempo);
+        let echo = |channel: &[f32], at: usize| channel[at - 2..=at + 2].iter().sum::<f32>();
+        assert!((echo(&left, 720) - 1.0).abs() < 0.01, "{}", echo(&left, 720));
+        assert!((echo(&right, 240) - 1.0).abs() < 0.01, "{}", echo(&right, 240));
+        assert!(echo(&left, 240).abs() < 1e-6 && echo(&right, 720).abs() < 1e-6);
+    }
+}
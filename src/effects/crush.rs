@@ -0,0 +1,156 @@
+//! Bit depth and sample rate reduction.
+//!
+//! Lo-fi degradation as chain effects: [`Bitcrusher`] quantises every sample
+//! to fewer levels, [`SampleRateReducer`] holds each kept sample for a number
+//! of samples, which aliases like an early sampler. Both check their
+//! parameters once when created, so processing cannot shift or divide by an
+//! out-of-range value.
+
+use crate::{ConfigError, dsp::frequency_analysis::bitcrush};
+
+use super::Effect;
+
+/// Highest bit depth a [`Bitcrusher`] takes, the precision of an `f32` mantissa
+pub const MAX_BIT_DEPTH: u8 = 24;
+
+/// Quantiser to a lower bit depth
+///
+/// ```rust
+/// use synthphone_e_vocal_dsp::effects::{Bitcrusher, Effect};
+///
+/// let mut crusher = Bitcrusher::new(4).unwrap();
+/// let mut block = [0.3f32; 64];
+/// crusher.process(&mut block);
+/// assert_eq!(block[0], 0.25);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bitcrusher {
+    bit_depth: u8,
+}
+
+impl Bitcrusher {
+    /// Creates a bitcrusher quantising full scale to `bit_depth` bits
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConfigError::InvalidBitcrush`] unless `bit_depth` is between 1
+    /// and [`MAX_BIT_DEPTH`].
+    pub fn new(bit_depth: u8) -> Result<Self, ConfigError> {
+        if !(1..=MAX_BIT_DEPTH).contains(&bit_depth) {
+            return Err(ConfigError::InvalidBitcrush);
+        }
+        Ok(Self { bit_depth })
+    }
+
+    pub fn bit_depth(&self) -> u8 {
+        self.bit_depth
+    }
+}
+
+impl Effect for Bitcrusher {
+    fn process(&mut self, samples: &mut [f32]) {
+        for sample in samples.iter_mut() {
+            *sample = bitcrush(*sample, self.bit_depth);
+        }
+    }
+
+    fn reset(&mut self) {}
+}
+
+/// Sample-and-hold to a fraction of the sample rate
+///
+/// ```rust
+/// use synthphone_e_vocal_dsp::effects::{Effect, SampleRateReducer};
+///
+/// let mut reducer = SampleRateReducer::new(3).unwrap();
+/// let mut block = [1.0, 2.0, 3.0, 4.0, 5.0];
+/// reducer.process(&mut block);
+/// assert_eq!(block, [1.0, 1.0, 1.0, 4.0, 4.0]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampleRateReducer {
+    factor: u32,
+    /// Samples the held value has been output for
+    counter: u32,
+    held: f32,
+}
+
+impl SampleRateReducer {
+    /// Creates a reducer keeping one sample in every `factor`
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConfigError::InvalidSampleRateReduction`] for a zero factor.
+    pub fn new(factor: u32) -> Result<Self, ConfigError> {
+        if factor == 0 {
+            return Err(ConfigError::InvalidSampleRateReduction);
+        }
+        Ok(Self { factor, counter: 0, held: 0.0 })
+    }
+
+    pub fn factor(&self) -> u32 {
+        self.factor
+    }
+}
+
+impl Effect for SampleRateReducer {
+    fn process(&mut self, samples: &mut [f32]) {
+        for sample in samples.iter_mut() {
+            if self.counter == 0 {
+                self.held = *sample;
+            }
+            self.counter += 1;
+            if self.counter == self.factor {
+                self.counter = 0;
+            }
+            *sample = self.held;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.counter = 0;
+        self.held = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parameters_are_validated() {
+        assert_eq!(Bitcrusher::new(0), Err(ConfigError::InvalidBitcrush));
+        assert_eq!(Bitcrusher::new(64), Err(ConfigError::InvalidBitcrush));
+        assert_eq!(SampleRateReducer::new(0), Err(ConfigError::InvalidSampleRateReduction));
+        assert_eq!(Bitcrusher::new(MAX_BIT_DEPTH).unwrap().bit_depth(), 24);
+    }
+
+    #[test]
+    fn test_hold_continues_across_blocks() {
+        let mut reducer = SampleRateReducer::new(4).unwrap();
+        let mut first = [1.0, 2.0, 3.0];
+        let mut second = [4.0, 5.0, 6.0];
+        reducer.process(&mut first);
+        reducer.process(&mut second);
+        assert_eq!((first, second), ([1.0; 3], [1.0, 5.0, 5.0]));
+
+        reducer.reset();
+        let mut third = [7.0, 8.0];
+        reducer.process(&mut third);
+        assert_eq!(third, [7.0, 7.0]);
+
+        // A factor of one passes everything through
+        let mut unity = SampleRateReducer::new(1).unwrap();
+        let mut samples = [0.1, -0.2, 0.3];
+        unity.process(&mut samples);
+        assert_eq!(samples, [0.1, -0.2, 0.3]);
+    }
+
+    #[test]
+    fn test_bitcrusher_quantises() {
+        let mut crusher = Bitcrusher::new(2).unwrap();
+        let mut samples = [-1.0, -0.3, 0.1, 0.4, 1.0];
+        crusher.process(&mut samples);
+        assert_eq!(samples, [-1.0, -0.5, 0.0, 0.5, 1.0]);
+    }
+}
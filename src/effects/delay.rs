@@ -0,0 +1,263 @@
+//! Delay with feedback.
+//!
+//! Echoes are read from a [`DelayBuffer`] and fed back into it through a
+//! one-pole low-pass, so every repeat comes back quieter and darker, like tape
+//! or an analog bucket brigade. When the time changes, with the tempo or the
+//! settings, the read position glides to it instead of jumping, which bends the
+//! pitch of the echoes briefly rather than clicking.
+//!
+//! In stereo the echoes ping-pong: the first comes back on the left, the next
+//! on the right, all read from the one line so they cost no extra memory.
+//!
+//! With `alloc` the line holds [`MAX_DELAY_SECONDS`]. Without it the storage is
+//! a `&'static mut [f32]` provided by the firmware, whose length sets the
+//! longest time.
+
+use libm::expf;
+
+use crate::{config::DelaySettings, ring_buffer::DelayBuffer, stereo::apply_width};
+
+/// Longest delay time in seconds
+pub const MAX_DELAY_SECONDS: f32 = 2.0;
+
+/// Time constant of the read position gliding to a new time, in seconds
+const GLIDE_SECONDS: f32 = 0.05;
+
+/// Storage of the [`Engine`](crate::Engine) delay line
+#[cfg(feature = "alloc")]
+pub type DelayStorage = alloc::vec::Vec<f32>;
+
+/// Storage of the [`Engine`](crate::Engine) delay line
+#[cfg(not(feature = "alloc"))]
+pub type DelayStorage = &'static mut [f32];
+
+/// Stateful delay running on a time-domain stream
+///
+/// The [`Engine`](crate::Engine) runs one on its output when
+/// [`VocalEffectsConfig::delay`](crate::VocalEffectsConfig::delay) is set.
+///
+/// ```rust
+/// use synthphone_e_vocal_dsp::{DelaySettings, DelayTime, effects::Delay};
+///
+/// let settings =
+///     DelaySettings { time: DelayTime::Seconds(0.01), feedback: 0.5, damping: 0.2, mix: 0.4 };
+/// let mut delay = Delay::with_buffer(settings, 48000.0, [0.0f32; 960]);
+/// let mut block = [0.0f32; 64];
+/// delay.process(&mut block, 120.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Delay<S = DelayStorage> {
+    settings: DelaySettings,
+    sample_rate: f32,
+    line: DelayBuffer<S>,
+    /// Delay in samples being read, `None` until the first sample
+    position: Option<f32>,
+    /// Share of the distance to the set time covered per sample
+    glide: f32,
+    /// State of the damping low-pass
+    damped: f32,
+    /// State of the damping low-pass of the right ping-pong echoes
+    damped_right: f32,
+}
+
+#[cfg(feature = "alloc")]
+impl Delay {
+    /// Creates a delay of up to [`MAX_DELAY_SECONDS`] for a stream at `sample_rate` Hz
+    pub fn new(settings: DelaySettings, sample_rate: f32) -> Self {
+        let capacity = (MAX_DELAY_SECONDS * sample_rate) as usize + 1;
+        Self::with_buffer(settings, sample_rate, alloc::vec![0.0; capacity])
+    }
+}
+
+impl<S> Delay<S>
+where
+    S: AsRef<[f32]> + AsMut<[f32]>,
+{
+    /// Creates a delay on `buffer`, whose length limits the time
+    pub fn with_buffer(settings: DelaySettings, sample_rate: f32, buffer: S) -> Self {
+        let mut line = DelayBuffer::new(buffer);
+        line.clear();
+        Self {
+            settings,
+            sample_rate,
+            line,
+            position: None,
+            glide: 1.0 - expf(-1.0 / (GLIDE_SECONDS * sample_rate)),
+            damped: 0.0,
+            damped_right: 0.0,
+        }
+    }
+
+    /// Returns the settings the delay was created with
+    pub fn settings(&self) -> &DelaySettings {
+        &self.settings
+    }
+
+    /// Longest time the buffer holds, in seconds
+    pub fn max_seconds(&self) -> f32 {
+        self.line.capacity().saturating_sub(1) as f32 / self.sample_rate
+    }
+
+    /// Adds the echoes to `samples` in place, timing note values at `bpm`
+    ///
+    /// Times longer than the buffer are shortened to fit.
+    pub fn process(&mut self, samples: &mut [f32], bpm: f32) {
+        let longest = self.line.capacity().saturating_sub(1) as f32;
+        if longest < 1.0 {
+            return;
+        }
+        let target = self.target(bpm, longest);
+        let mut position = self.position.unwrap_or(target);
+        let smoothing = 1.0 - self.settings.damping;
+        for sample in samples.iter_mut() {
+            position += (target - position) * self.glide;
+            let echo = self.line.tap_fractional(position);
+            self.damped += (echo - self.damped) * smoothing;
+            self.line.push(*sample + self.settings.feedback * self.damped);
+            *sample += self.settings.mix * self.damped;
+        }
+        self.position = Some(position);
+    }
+
+    /// Adds ping-pong echoes of the mid to both channels in place
+    ///
+    /// Echoes alternate between left and right, one delay time apart, starting
+    /// on the left. `width` scales the difference between the channels. Times
+    /// longer than half the buffer are shortened to fit.
+    pub fn process_stereo(&mut self, left: &mut [f32], right: &mut [f32], width: f32, bpm: f32) {
+        let longest = self.line.capacity().saturating_sub(1) as f32 / 2.0;
+        if longest < 1.0 {
+            return;
+        }
+        let target = self.target(bpm, longest);
+        let mut position = self.position.unwrap_or(target);
+        let smoothing = 1.0 - self.settings.damping;
+        let feedback = self.settings.feedback;
+        for (left, right) in left.iter_mut().zip(right.iter_mut()) {
+            position += (target - position) * self.glide;
+            // The line only carries every second echo; the right side sits between
+            let echo_left = self.line.tap_fractional(position);
+            let echo_right = self.line.tap_fractional(2.0 * position);
+            self.damped += (echo_left - self.damped) * smoothing;
+            self.damped_right += (echo_right - self.damped_right) * smoothing;
+            let mid = (*left + *right) * core::f32::consts::FRAC_1_SQRT_2;
+            self.line.push(mid + feedback * feedback * self.damped_right);
+            let (wet_left, wet_right) =
+                apply_width(self.damped, feedback * self.damped_right, width);
+            *left += self.settings.mix * wet_left;
+            *right += self.settings.mix * wet_right;
+        }
+        self.position = Some(position);
+    }
+
+    /// Delay in samples of the set time at `bpm`, within 1 and `longest`
+    fn target(&self, bpm: f32, longest: f32) -> f32 {
+        let seconds = self.settings.time.seconds(bpm);
+        if seconds.is_finite() {
+            (seconds * self.sample_rate).clamp(1.0, longest)
+        } else {
+            longest
+        }
+    }
+
+    /// Clears the echoes
+    pub fn reset(&mut self) {
+        self.line.clear();
+        self.position = None;
+        self.damped = 0.0;
+        self.damped_right = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::DelayTime, tempo::NoteValue};
+
+    #[test]
+    fn test_echoes_repeat_and_decay() {
+        let settings =
+            DelaySettings { time: DelayTime::Seconds(0.01), feedback: 0.5, damping: 0.0, mix: 0.8 };
+        let mut delay = Delay::with_buffer(settings, 48000.0, [0.0f32; 1024]);
+        let mut samples = [0.0f32; 2048];
+        samples[0] = 1.0;
+        delay.process(&mut samples, 120.0);
+
+        // 480 samples apart, each echo half the one before
+        assert_eq!(samples[0], 1.0);
+        assert!((samples[480] - 0.8).abs() < 1e-6);
+        assert!((samples[960] - 0.4).abs() < 1e-6);
+        assert!((samples[1440] - 0.2).abs() < 1e-6);
+        let others = samples.iter().enumerate().filter(|(n, _)| n % 480 != 0);
+        assert!(others.clone().all(|(_, sample)| *sample == 0.0));
+
+        // Damping spreads and darkens the echo without adding energy
+        let settings = DelaySettings { damping: 0.7, ..settings };
+        let mut delay = Delay::with_buffer(settings, 48000.0, [0.0f32; 1024]);
+        let mut samples = [0.0f32; 1024];
+        samples[0] = 1.0;
+        delay.process(&mut samples, 120.0);
+        assert!(samples[480] < 0.8 * 0.5 && samples[481] > 0.0);
+        assert!(samples[480..960].iter().sum::<f32>() <= 0.8 + 1e-5);
+
+        delay.reset();
+        let mut silence = [0.0f32; 1024];
+        delay.process(&mut silence, 120.0);
+        assert!(silence.iter().all(|&sample| sample == 0.0));
+    }
+
+    #[test]
+    fn test_note_time_follows_tempo() {
+        // A sixteenth at 150 BPM is 0.1 s
+        let time = DelayTime::Note(NoteValue::SIXTEENTH);
+        let settings = DelaySettings { time, feedback: 0.0, damping: 0.0, mix: 1.0 };
+        let mut delay = Delay::with_buffer(settings, 48000.0, [0.0f32; 8192]);
+        let mut samples = [0.0f32; 8192];
+        samples[0] = 1.0;
+        delay.process(&mut samples, 150.0);
+        assert_eq!(samples[4800], 1.0);
+        assert!((delay.max_seconds() - 8191.0 / 48000.0).abs() < 1e-6);
+
+        // Longer than the buffer at a slow tempo, the echo comes at its end
+        delay.reset();
+        let mut samples = [0.0f32; 8192];
+        samples[0] = 1.0;
+        delay.process(&mut samples, 20.0);
+        assert_eq!(samples[8191], 1.0);
+    }
+
+    #[test]
+    fn test_stereo_echoes_ping_pong() {
+        let settings =
+            DelaySettings { time: DelayTime::Seconds(0.01), feedback: 0.5, damping: 0.0, mix: 1.0 };
+        let mut delay = Delay::with_buffer(settings, 48000.0, [0.0f32; 2048]);
+        let mut left = [0.0f32; 2048];
+        let mut right = [0.0f32; 2048];
+        left[0] = core::f32::consts::SQRT_2;
+        delay.process_stereo(&mut left, &mut right, 1.0, 120.0);
+
+        // Left, right, left, right, each half the one before
+        for (n, (gain, on_left)) in [(1.0, true), (0.5, false), (0.25, true), (0.125, false)]
+            .into_iter()
+            .enumerate()
+        {
+            let at = 480 * (n + 1);
+            let (hit, quiet) = if on_left {
+                (left[at], right[at])
+            } else {
+                (right[at], left[at])
+            };
+            assert!((hit - gain).abs() < 1e-6 && quiet.abs() < 1e-6, "{n}: {hit} {quiet}");
+        }
+
+        // At zero width both sides hear every echo
+        delay.reset();
+        let mut left = [0.0f32; 1024];
+        let mut right = [0.0f32; 1024];
+        left[0] = core::f32::consts::SQRT_2;
+        delay.process_stereo(&mut left, &mut right, 0.0, 120.0);
+        for (at, gain) in [(480, 0.5), (960, 0.25)] {
+            assert!((left[at] - gain).abs() < 1e-6 && (right[at] - gain).abs() < 1e-6);
+        }
+    }
+}
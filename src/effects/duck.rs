@@ -0,0 +1,132 @@
+//! Sidechain ducking.
+//!
+//! A synth blended under the voice, or a vocoder pad with some dry voice mixed
+//! in, masks the words when both are loud. The ducker follows the peak level
+//! of the voice and compresses a second signal with it: above the threshold
+//! the ducked signal comes down as the voice comes up, and recovers with the
+//! release once the voice stops.
+
+use libm::{expf, log10f, powf};
+
+use crate::config::SidechainDuck;
+
+/// Stateful sidechain compressor running on a time-domain stream
+///
+/// The [`Engine`](crate::Engine) ducks its carrier with one when
+/// [`VocalEffectsConfig::sidechain_duck`](crate::VocalEffectsConfig::sidechain_duck)
+/// is set.
+///
+/// ```rust
+/// use synthphone_e_vocal_dsp::{SidechainDuck, effects::Ducker};
+///
+/// let settings =
+///     SidechainDuck { threshold_db: -30.0, ratio: 4.0, attack_ms: 5.0, release_ms: 200.0 };
+/// let mut ducker = Ducker::new(settings, 48000.0);
+/// let voice = [0.5f32; 64];
+/// let mut synth = [0.5f32; 64];
+/// ducker.process(&voice, &mut synth);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Ducker {
+    settings: SidechainDuck,
+    /// Share of the distance to a rising and a falling level covered per sample
+    attack: f32,
+    release: f32,
+    /// Linear level above which the ducked signal is turned down
+    threshold: f32,
+    /// Followed peak level of the sidechain
+    envelope: f32,
+    /// Gain of the last sample
+    gain: f32,
+}
+
+impl Ducker {
+    /// Creates a ducker for streams at `sample_rate` Hz
+    pub fn new(settings: SidechainDuck, sample_rate: f32) -> Self {
+        let coefficient = |ms: f32| {
+            if ms > 0.0 {
+                1.0 - expf(-1000.0 / (ms * sample_rate))
+            } else {
+                1.0
+            }
+        };
+        Self {
+            settings,
+            attack: coefficient(settings.attack_ms),
+            release: coefficient(settings.release_ms),
+            threshold: powf(10.0, settings.threshold_db / 20.0),
+            envelope: 0.0,
+            gain: 1.0,
+        }
+    }
+
+    /// Returns the settings the ducker was created with
+    pub fn settings(&self) -> &SidechainDuck {
+        &self.settings
+    }
+
+    /// Turns `samples` down in place while `sidechain` is above the threshold
+    ///
+    /// The two are taken sample by sample; extra samples of the longer one are
+    /// ignored.
+    pub fn process(&mut self, sidechain: &[f32], samples: &mut [f32]) {
+        // Gain falls by 1 - 1 / ratio dB for every dB above the threshold
+        let slope = 1.0 / self.settings.ratio - 1.0;
+        for (level, sample) in sidechain.iter().zip(samples.iter_mut()) {
+            let level = level.abs();
+            let coefficient = if level > self.envelope {
+                self.attack
+            } else {
+                self.release
+            };
+            self.envelope += (level - self.envelope) * coefficient;
+            self.gain = if self.envelope > self.threshold {
+                powf(self.envelope / self.threshold, slope)
+            } else {
+                1.0
+            };
+            *sample *= self.gain;
+        }
+    }
+
+    /// Gain applied to the last sample in dB, 0.0 when not ducking
+    pub fn gain_db(&self) -> f32 {
+        20.0 * log10f(self.gain)
+    }
+
+    /// Clears the follower
+    pub fn reset(&mut self) {
+        self.envelope = 0.0;
+        self.gain = 1.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ducks_by_ratio_above_threshold() {
+        // A voice 20 dB over the threshold at 4:1 takes the synth down 15 dB
+        let settings =
+            SidechainDuck { threshold_db: -40.0, ratio: 4.0, attack_ms: 1.0, release_ms: 20.0 };
+        let mut ducker = Ducker::new(settings, 48000.0);
+        let voice = [0.1f32; 4800];
+        let mut synth = [1.0f32; 4800];
+        ducker.process(&voice, &mut synth);
+        assert!((ducker.gain_db() + 15.0).abs() < 0.01, "{}", ducker.gain_db());
+        assert!((20.0 * log10f(synth[4799]) + 15.0).abs() < 0.01);
+
+        // The synth comes back over the release once the voice stops
+        let mut synth = [1.0f32; 4800];
+        ducker.process(&[0.0; 4800], &mut synth);
+        assert!(synth[480] < 0.9 && synth[4799] == 1.0, "{} {}", synth[480], synth[4799]);
+
+        // A quiet voice leaves the synth alone
+        ducker.reset();
+        let mut synth = [1.0f32; 480];
+        ducker.process(&[0.005; 480], &mut synth);
+        assert!(synth.iter().all(|&sample| sample == 1.0));
+        assert_eq!(ducker.gain_db(), 0.0);
+    }
+}
@@ -0,0 +1,107 @@
+//! Harmonic exciter.
+//!
+//! Shifting formants down leaves a voice dull because the upper harmonics move
+//! down with them. The exciter high-passes the signal at the crossover, drives
+//! it through a soft saturator to generate new harmonics, high-passes again so
+//! only content above the crossover remains, and mixes the result back in.
+
+use libm::tanhf;
+
+use crate::{config::ExciterSettings, dsp::biquad::Biquad};
+
+/// Offset of the saturator input, so it adds even harmonics as well as odd ones
+const BIAS: f32 = 0.2;
+
+/// Quality of the crossover high-passes (Butterworth)
+const CROSSOVER_Q: f32 = core::f32::consts::FRAC_1_SQRT_2;
+
+/// Stateful exciter running on a time-domain stream
+///
+/// The [`Engine`](crate::Engine) runs one on its output when
+/// [`VocalEffectsConfig::exciter`](crate::VocalEffectsConfig::exciter) is set.
+///
+/// ```rust
+/// use synthphone_e_vocal_dsp::{ExciterSettings, effects::Exciter};
+///
+/// let settings = ExciterSettings { crossover_hz: 3000.0, drive: 4.0, mix: 0.2 };
+/// let mut exciter = Exciter::new(settings, 48000.0);
+/// let mut block = [0.0f32; 64];
+/// exciter.process(&mut block);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Exciter {
+    settings: ExciterSettings,
+    input_filter: Biquad,
+    output_filter: Biquad,
+}
+
+impl Exciter {
+    /// Creates an exciter for a stream at `sample_rate` Hz
+    pub fn new(settings: ExciterSettings, sample_rate: f32) -> Self {
+        let filter = Biquad::high_pass(settings.crossover_hz, CROSSOVER_Q, sample_rate);
+        Self { settings, input_filter: filter, output_filter: filter }
+    }
+
+    /// Returns the settings the exciter was created with
+    pub fn settings(&self) -> &ExciterSettings {
+        &self.settings
+    }
+
+    /// Adds the generated harmonics to `samples` in place
+    pub fn process(&mut self, samples: &mut [f32]) {
+        let drive = self.settings.drive;
+        let offset = tanhf(BIAS);
+        for sample in samples.iter_mut() {
+            let highs = self.input_filter.process(*sample);
+            let saturated = tanhf(drive * highs + BIAS) - offset;
+            *sample += self.settings.mix * self.output_filter.process(saturated);
+        }
+    }
+
+    /// Clears the filter state
+    pub fn reset(&mut self) {
+        self.input_filter.reset();
+        self.output_filter.reset();
+    }
+}
+
+impl super::Effect for Exciter {
+    fn process(&mut self, samples: &mut [f32]) {
+        Exciter::process(self, samples);
+    }
+
+    fn reset(&mut self) {
+        Exciter::reset(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testsig::tone_level;
+    use core::f32::consts::PI;
+
+    #[test]
+    fn test_adds_harmonics_above_crossover() {
+        let settings = ExciterSettings { crossover_hz: 2000.0, drive: 8.0, mix: 0.5 };
+        let mut exciter = Exciter::new(settings, 48000.0);
+        let mut samples: [f32; 9600] =
+            core::array::from_fn(|n| 0.3 * libm::sinf(2.0 * PI * 3000.0 * n as f32 / 48000.0));
+        let input = samples;
+        exciter.process(&mut samples);
+
+        let settled = 4800..9600;
+        assert!(tone_level(&input[settled.clone()], 6000.0, 48000.0) < 1e-3);
+        assert!(tone_level(&samples[settled.clone()], 6000.0, 48000.0) > 0.01);
+        assert!(tone_level(&samples[settled.clone()], 9000.0, 48000.0) > 0.01);
+
+        // Nothing is added far below the crossover
+        let mut low: [f32; 9600] =
+            core::array::from_fn(|n| 0.3 * libm::sinf(2.0 * PI * 150.0 * n as f32 / 48000.0));
+        let original = low;
+        exciter.reset();
+        exciter.process(&mut low);
+        let added: [f32; 4800] = core::array::from_fn(|n| low[4800 + n] - original[4800 + n]);
+        assert!(added.iter().all(|sample| sample.abs() < 0.01));
+    }
+}
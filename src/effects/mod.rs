@@ -1,383 +1,1885 @@
-use core::f32::consts::PI;
+pub mod breath;
+pub mod chorus;
+pub mod crush;
+pub mod delay;
+pub mod duck;
+pub mod exciter;
+pub mod reverb;
+pub mod saturation;
 
-use libm::{atan2f, cosf, expf, fabsf, floorf, sinf, sqrtf};
+pub use breath::BreathMixer;
+pub use chorus::Chorus;
+pub use crush::{Bitcrusher, SampleRateReducer};
+pub use delay::Delay;
+pub use duck::Ducker;
+pub use exciter::Exciter;
+pub use reverb::Reverb;
+pub use saturation::Saturator;
+
+use core::f32::consts::{FRAC_PI_2, PI};
+
+use libm::{atan2f, cosf, floorf, powf, sinf, sqrtf};
 
 use crate::{
-    MusicalSettings, VocalEffectsConfig,
-    dsp::{self, FftOps, calculate_pitch_shift, extract_cepstral_envelope, frequency_analysis},
+    MusicalSettings, PitchDetector, TransientHandling, VocalEffectsConfig, VocoderEnvelope,
+    dsp::{
+        self, DynFft, EnvelopeCache, FormantSnapshot, clip::soft_clip_frame, correct_estimate_from,
+        detect_pitch, extract_cepstral_envelope_with, frequency_analysis, gate, geometric_mean,
+        separation, unpack_nyquist,
+    },
+    math::semitones_to_ratio,
+    state::{FrameState, VOCODER_BANDS, VocoderEq},
+    workspace::Workspace,
 };
 
+/// Time-domain stage of the [`Engine`](crate::Engine) output chain
+///
+/// Effects that follow the tempo, [`Chorus`] and [`Delay`], take the clock as an
+/// extra argument instead.
+pub trait Effect {
+    /// Processes `samples` in place
+    fn process(&mut self, samples: &mut [f32]);
+
+    /// Clears the state, as if the effect had only heard silence
+    fn reset(&mut self);
+}
+
 /// Generic pitch correction processing (pitch correction)
+///
+/// `state.analysis` holds the pitch shift ratio of the previous frame on entry
+/// and is updated with the pitch detected in this frame and the ratio applied to it.
 pub fn process_pitch_correction_generic<const N: usize, const HALF_N: usize, F>(
+    fft: &mut F,
+    workspace: &mut Workspace<N, HALF_N>,
     unwrapped_buffer: &mut [f32; N],
-    last_input_phases: &mut [f32; N],
-    last_output_phases: &mut [f32; N],
-    previous_pitch_shift_ratio: f32,
+    state: &mut FrameState<'_, N>,
     config: &VocalEffectsConfig,
     settings: &MusicalSettings,
 ) -> [f32; N]
 where
-    F: FftOps<N, HALF_N>,
+    F: DynFft<N, HALF_N> + ?Sized,
+{
+    process_pitch_correction_in_place(fft, workspace, unwrapped_buffer, state, config, settings);
+    *unwrapped_buffer
+}
+
+/// [`process_pitch_correction_generic`] writing the output frame over `unwrapped_buffer`
+///
+/// The input frame is consumed; no second frame-sized buffer is returned.
+pub fn process_pitch_correction_in_place<const N: usize, const HALF_N: usize, F>(
+    fft: &mut F,
+    workspace: &mut Workspace<N, HALF_N>,
+    unwrapped_buffer: &mut [f32; N],
+    state: &mut FrameState<'_, N>,
+    config: &VocalEffectsConfig,
+    settings: &MusicalSettings,
+) where
+    F: DynFft<N, HALF_N> + ?Sized,
 {
     const GAIN_COMPENSATION: f32 = 2.0 / 3.0;
 
-    let hop_size = (N as f32 * config.hop_ratio) as usize;
+    let FrameState {
+        last_input_phases,
+        last_output_phases,
+        bins,
+        magnitude_history,
+        gate_gains,
+        envelope_cache,
+        analysis,
+        ..
+    } = state;
+
+    let hop_size = config.hop_size_for(N);
+    #[cfg(not(feature = "no-panic"))]
+    debug_assert_eq!(bins.hop_size(), hop_size, "bin tables for another hop size");
     let bin_width = config.sample_rate / N as f32;
 
-    let analysis_window_buffer = F::get_hann_window();
-    let mut full_spectrum = [microfft::Complex32 { re: 0.0, im: 0.0 }; N];
-    let mut analysis_magnitudes = [0.0; HALF_N];
-    let mut analysis_frequencies = [0.0; HALF_N];
-    let mut synthesis_magnitudes = [0.0; N];
-    let mut synthesis_frequencies = [0.0; N];
-    let mut envelope = [1.0f32; HALF_N];
+    let analysis_window_buffer = bins.window().unwrap_or_else(|| fft.hann_window());
+    workspace.prepare();
+    let Workspace {
+        spectrum,
+        analysis_magnitudes,
+        analysis_frequencies,
+        synthesis_magnitudes,
+        synthesis_frequencies,
+        envelope,
+        harmonic_mask,
+    } = workspace;
 
     let formant = settings.formant;
 
+    // The time-domain detector needs the frame before windowing
+    let time_domain_estimate =
+        (config.pitch_detector == PitchDetector::Autocorrelation).then(|| {
+            profile_stage!(
+                PitchDetection,
+                frequency_analysis::detect_pitch_autocorrelation_decimated(
+                    unwrapped_buffer,
+                    config.pitch_decimation.factor(),
+                    config.sample_rate,
+                    config.min_frequency,
+                    config.max_frequency,
+                )
+            )
+        });
+
+    let transient = detect_transient(unwrapped_buffer, hop_size, config);
+
     // Apply windowing
-    for i in 0..N {
-        unwrapped_buffer[i] *= analysis_window_buffer[i];
-    }
+    profile_stage!(
+        Window,
+        for i in 0..N {
+            unwrapped_buffer[i] *= analysis_window_buffer[i];
+        }
+    );
 
     // Forward FFT
-    let fft_result = F::forward_fft(unwrapped_buffer);
+    let fft_result = profile_stage!(Fft, fft.forward(unwrapped_buffer));
+    let nyquist = unpack_nyquist(fft_result);
 
     // Process frequency bins - limit to the actual number of bins we have arrays for
     let num_bins = HALF_N.min(fft_result.len());
-    for i in 0..num_bins {
-        let amplitude =
-            sqrtf(fft_result[i].re * fft_result[i].re + fft_result[i].im * fft_result[i].im);
-        let phase = atan2f(fft_result[i].im, fft_result[i].re);
-        let mut phase_diff = phase - last_input_phases[i];
-        let bin_centre_frequency = 2.0 * PI * i as f32 / N as f32;
-        phase_diff = dsp::frequency_analysis::wrap_phase(
-            phase_diff - bin_centre_frequency * hop_size as f32,
-        );
-        let bin_deviation = phase_diff * N as f32 / hop_size as f32 / (2.0 * PI);
-        analysis_frequencies[i] = i as f32 + bin_deviation;
-        analysis_magnitudes[i] = amplitude;
-        last_input_phases[i] = phase;
-    }
+    profile_stage!(
+        Analysis,
+        for i in 0..num_bins {
+            let amplitude =
+                sqrtf(fft_result[i].re * fft_result[i].re + fft_result[i].im * fft_result[i].im);
+            let phase = atan2f(fft_result[i].im, fft_result[i].re);
+            let phase_diff = dsp::frequency_analysis::wrap_phase(
+                phase - last_input_phases[i] - bins.phase_advance(i),
+            );
+            let bin_deviation = bins.deviation_bins(phase_diff);
+            analysis_frequencies[i] = i as f32 + bin_deviation;
+            analysis_magnitudes[i] = dsp::guards::flush_denormal(amplitude);
+            last_input_phases[i] = phase;
+        }
+    );
+    let separate = separate_harmonics(
+        analysis_magnitudes,
+        magnitude_history.as_deref_mut(),
+        harmonic_mask,
+        num_bins,
+        bin_width,
+        config,
+    );
 
     // Extract formant envelope if needed
-    if formant != 0 {
-        extract_cepstral_envelope::<N, HALF_N, F>(&analysis_magnitudes, &mut envelope);
+    let mut morph = None;
+    if formant.is_shifted() || morphs(envelope_cache.as_deref(), config) {
+        morph = profile_stage!(
+            Envelope,
+            formant_envelope(
+                fft,
+                envelope_cache.as_deref_mut(),
+                analysis_magnitudes,
+                envelope,
+                spectrum,
+                synthesis_magnitudes,
+                config,
+            )
+        );
+    } else if let Some(cache) = envelope_cache {
+        cache.clear();
     }
 
     // Calculate pitch shift
-    let pitch_shift_ratio = calculate_pitch_shift(
-        &analysis_magnitudes,
-        &analysis_frequencies,
-        previous_pitch_shift_ratio,
-        settings,
-        bin_width,
+    **analysis = profile_stage!(PitchDetection, {
+        let estimate = time_domain_estimate.unwrap_or_else(|| {
+            detect_pitch(analysis_magnitudes, analysis_frequencies, config, bin_width)
+        });
+        correct_estimate_from(estimate, analysis, config, settings)
+    });
+    dsp_trace!(
+        "pitch detected: {=f32} Hz -> {=f32} Hz",
+        analysis.detected_frequency,
+        analysis.target_frequency
     );
+    let pitch_shift_ratio = analysis.pitch_shift_ratio * settings.octave_shift.ratio();
 
-    // Apply spectral shift
-    synthesis_magnitudes.fill(0.0);
-    synthesis_frequencies.fill(0.0);
-    let formant_ratio = match formant {
-        1 => 0.5,
-        2 => 2.0,
-        _ => 1.0,
-    };
-    let use_formants = formant != 0;
+    // Apply spectral shift; the Nyquist bin only survives unshifted frames
+    let mut output_nyquist = 0.0;
+    if transient == Some(TransientHandling::Passthrough) {
+        output_nyquist = nyquist;
+        profile_stage!(
+            Synthesis,
+            pass_through_transient(
+                fft_result,
+                spectrum,
+                last_input_phases,
+                last_output_phases,
+                num_bins
+            )
+        );
+    } else {
+        let reset_phases = transient == Some(TransientHandling::PhaseReset);
+        profile_stage!(Synthesis, {
+            synthesis_magnitudes.fill(0.0);
+            synthesis_frequencies.fill(0.0);
+            let formant_ratio = formant.ratio();
+            let use_formants = formant.is_shifted() || morph.is_some();
 
-    for i in 0..num_bins {
-        if analysis_magnitudes[i] <= 1e-8 {
-            continue;
-        }
-        let residual = if use_formants {
-            analysis_magnitudes[i] / envelope[i].max(1e-6_f32)
-        } else {
-            analysis_magnitudes[i]
-        };
-        let new_bin_f = i as f32 * pitch_shift_ratio;
-        let new_bin = (floorf(new_bin_f + 0.5) as usize).min(num_bins - 1);
-        if new_bin >= num_bins {
-            continue;
-        }
+            for i in 0..num_bins {
+                if analysis_magnitudes[i] <= 1e-8 {
+                    continue;
+                }
+                let residual = if use_formants {
+                    analysis_magnitudes[i] / envelope[i].max(1e-6_f32)
+                } else {
+                    analysis_magnitudes[i]
+                };
+                let new_bin_f = i as f32 * pitch_shift_ratio;
+                let new_bin = floorf(new_bin_f + 0.5) as usize;
+                let band_gain = band_limit_gain(new_bin_f, bin_width, config);
+                if new_bin >= num_bins || band_gain == 0.0 {
+                    continue;
+                }
 
-        let shifted_envelope = if use_formants {
-            let env_pos = (i as f32 / formant_ratio).clamp(0.0, (num_bins - 1) as f32);
-            let env_idx = env_pos as usize;
-            let frac = env_pos - env_idx as f32;
-            if env_idx < num_bins - 1 {
-                envelope[env_idx] * (1.0 - frac) + envelope[env_idx + 1] * frac
-            } else {
-                envelope[env_idx]
-            }
-        } else {
-            1.0
-        };
+                let shifted_envelope = if use_formants {
+                    shaped_envelope(envelope, morph.as_ref(), i, formant_ratio, num_bins)
+                } else {
+                    1.0
+                };
 
-        synthesis_magnitudes[new_bin] = residual * shifted_envelope;
-        synthesis_frequencies[new_bin] = analysis_frequencies[i] * pitch_shift_ratio;
-    }
+                synthesis_magnitudes[new_bin] =
+                    residual * shifted_envelope * harmonic_mask[i] * band_gain;
+                synthesis_frequencies[new_bin] = analysis_frequencies[i] * pitch_shift_ratio;
+            }
 
-    // Synthesis phase reconstruction
-    for i in 0..num_bins {
-        let magnitude = synthesis_magnitudes[i];
-        let bin_deviation = synthesis_frequencies[i] - i as f32;
-        let mut phase_increment = bin_deviation * 2.0 * PI * hop_size as f32 / N as f32;
-        let bin_center_frequency = 2.0 * PI * i as f32 / N as f32;
-        phase_increment += bin_center_frequency * hop_size as f32;
-        let output_phase = frequency_analysis::wrap_phase(last_output_phases[i] + phase_increment);
-        let real_part = magnitude * cosf(output_phase);
-        let imaginary_part = magnitude * sinf(output_phase);
-        full_spectrum[i] = microfft::Complex32 { re: real_part, im: imaginary_part };
-        if i > 0 && i < num_bins {
-            full_spectrum[N - i] = microfft::Complex32 { re: real_part, im: -imaginary_part };
-        }
-        last_output_phases[i] = output_phase;
+            // Synthesis phase reconstruction
+            for i in 0..num_bins {
+                let phase_increment = bins.synthesis_advance(i, synthesis_frequencies[i]);
+                last_output_phases[i] = if reset_phases {
+                    last_input_phases[source_bin(i, pitch_shift_ratio, num_bins)]
+                } else {
+                    frequency_analysis::wrap_phase(last_output_phases[i] + phase_increment)
+                };
+            }
+            if bins.stretches() && !reset_phases {
+                lock_phases_to_peaks(
+                    synthesis_magnitudes,
+                    last_output_phases,
+                    last_input_phases,
+                    pitch_shift_ratio,
+                    num_bins,
+                );
+            }
+            for i in 0..num_bins {
+                let magnitude = synthesis_magnitudes[i];
+                let output_phase = last_output_phases[i];
+                spectrum[i] = microfft::Complex32 {
+                    re: magnitude * cosf(output_phase),
+                    im: magnitude * sinf(output_phase),
+                };
+            }
+            if separate {
+                add_percussive(fft_result, spectrum, harmonic_mask, num_bins);
+            }
+        });
     }
 
-    // Inverse FFT
-    let time_domain_result = F::inverse_fft(&mut full_spectrum);
-    let mut output_samples = [0.0f32; N];
-
+    resynthesize(
+        fft,
+        spectrum,
+        output_nyquist,
+        gate_gains.as_deref_mut(),
+        unwrapped_buffer,
+        config,
+    );
     for i in 0..N {
-        let mut sample = time_domain_result[i].re;
-        sample *= analysis_window_buffer[i];
-        sample *= GAIN_COMPENSATION;
-        if sample.abs() > 0.95 {
-            let sign = if sample >= 0.0 { 1.0 } else { -1.0 };
-            let compressed = 0.95 - 0.05 * expf(-fabsf(sample));
-            sample = sign * compressed;
-        }
-        output_samples[i] = sample;
+        unwrapped_buffer[i] = unwrapped_buffer[i] * analysis_window_buffer[i] * GAIN_COMPENSATION;
     }
-
-    output_samples
+    soft_clip_frame(unwrapped_buffer, config.soft_clip.as_ref());
 }
 
 /// Generic vocoder processing
+///
+/// The phases of `state` are left alone, as the output keeps the carrier phases.
 pub fn process_vocode_generic<const N: usize, const HALF_N: usize, F>(
+    fft: &mut F,
+    workspace: &mut Workspace<N, HALF_N>,
     input_buffer: &mut [f32; N],
     carrier_buffer: &mut [f32; N],
-    // TODO if we don't need this, remove it
-    _last_input_phases: &mut [f32; N],
-    _last_output_phases: &mut [f32; N],
-    _config: &VocalEffectsConfig,
-    _settings: &MusicalSettings,
+    state: &mut FrameState<'_, N>,
+    config: &VocalEffectsConfig,
+    settings: &MusicalSettings,
 ) -> [f32; N]
 where
-    F: FftOps<N, HALF_N>,
+    F: DynFft<N, HALF_N> + ?Sized,
+{
+    process_vocode_in_place(fft, workspace, input_buffer, carrier_buffer, state, config, settings);
+    *input_buffer
+}
+
+/// [`process_vocode_generic`] writing the output frame over `input_buffer`
+///
+/// The input frame is consumed; no second frame-sized buffer is returned.
+///
+/// `carrier_buffer` is used as scratch and holds its spectrum afterwards.
+pub fn process_vocode_in_place<const N: usize, const HALF_N: usize, F>(
+    fft: &mut F,
+    workspace: &mut Workspace<N, HALF_N>,
+    input_buffer: &mut [f32; N],
+    carrier_buffer: &mut [f32; N],
+    state: &mut FrameState<'_, N>,
+    config: &VocalEffectsConfig,
+    settings: &MusicalSettings,
+) where
+    F: DynFft<N, HALF_N> + ?Sized,
 {
-    let analysis_window_buffer = F::get_hann_window();
-    let mut full_spectrum = [microfft::Complex32 { re: 0.0, im: 0.0 }; N];
+    let FrameState {
+        bins, gate_gains, vocoder_envelope: envelope, vocoder_eq_gains: eq_gains, ..
+    } = state;
+    let analysis_window_buffer = bins.window().unwrap_or_else(|| fft.hann_window());
+    workspace.prepare();
+    let Workspace {
+        spectrum, analysis_magnitudes, synthesis_magnitudes: carrier_magnitudes, ..
+    } = workspace;
 
     // Apply windowing to both inputs
-    for i in 0..N {
-        input_buffer[i] *= analysis_window_buffer[i];
-        carrier_buffer[i] *= analysis_window_buffer[i];
-    }
+    profile_stage!(
+        Window,
+        for i in 0..N {
+            input_buffer[i] *= analysis_window_buffer[i];
+            carrier_buffer[i] *= analysis_window_buffer[i];
+        }
+    );
 
     // Forward FFT on both signals
-    let modulator_fft = F::forward_fft(input_buffer);
-    let carrier_fft = F::forward_fft(carrier_buffer);
+    let modulator_fft = profile_stage!(Fft, fft.forward(input_buffer));
+    let carrier_fft = profile_stage!(Fft, fft.forward(carrier_buffer));
+    let modulator_nyquist = unpack_nyquist(modulator_fft);
+    let carrier_nyquist = unpack_nyquist(carrier_fft);
 
     // Process first half of spectrum (including DC and Nyquist)
     let num_bins = HALF_N.min(modulator_fft.len()).min(carrier_fft.len());
+    profile_stage!(Synthesis, {
+        let mut loudest_carrier = 0.0f32;
+        for i in 0..num_bins {
+            // Get modulator magnitude (vocal envelope)
+            analysis_magnitudes[i] = sqrtf(
+                modulator_fft[i].re * modulator_fft[i].re
+                    + modulator_fft[i].im * modulator_fft[i].im,
+            );
+            carrier_magnitudes[i] = sqrtf(
+                carrier_fft[i].re * carrier_fft[i].re + carrier_fft[i].im * carrier_fft[i].im,
+            );
+            loudest_carrier = loudest_carrier.max(carrier_magnitudes[i]);
+        }
+        let hop_seconds = config.hop_size_for(N) as f32 / config.sample_rate;
+        let envelope: &[f32] = match (envelope, config.vocoder_envelope) {
+            (Some(envelope), Some(smoothing)) => {
+                let attack = gate::smoothing(smoothing.attack_ms, hop_seconds);
+                let release = gate::smoothing(smoothing.release_ms, hop_seconds);
+                for (smoothed, &magnitude) in
+                    envelope[..num_bins].iter_mut().zip(&analysis_magnitudes[..num_bins])
+                {
+                    let coefficient = if magnitude > *smoothed {
+                        attack
+                    } else {
+                        release
+                    };
+                    *smoothed += (magnitude - *smoothed) * coefficient;
+                }
+                &envelope[..num_bins]
+            }
+            _ => &analysis_magnitudes[..num_bins],
+        };
+        let fill_below = config
+            .noise_fill
+            .map_or(0.0, |fill| loudest_carrier * libm::powf(10.0, fill.threshold_db / 20.0));
+        let formant_ratio = semitones_to_ratio(settings.vocoder_formant_semitones);
+        let warp = formant_ratio.is_finite() && formant_ratio > 0.0 && formant_ratio != 1.0;
+        let band_gains = smooth_eq_gains(eq_gains.as_deref_mut(), settings, hop_seconds);
+        let bin_width = config.sample_rate / N as f32;
+
+        for i in 0..num_bins {
+            let mod_mag = if warp {
+                warped_envelope(envelope, i, formant_ratio, num_bins)
+            } else {
+                envelope[i]
+            } * VocoderEq::gain_at(&band_gains, i as f32 * bin_width);
+            let car_mag = carrier_magnitudes[i];
+
+            if let Some(fill) = config.noise_fill.filter(|_| car_mag < fill_below) {
+                // The carrier has nothing here, fill with noise at the modulator level
+                let phase = noise_phase(modulator_fft[i], i);
+                let magnitude = mod_mag * fill.level;
+                spectrum[i].re = magnitude * cosf(phase);
+                spectrum[i].im = magnitude * sinf(phase);
+                continue;
+            }
+
+            // Scale carrier by modulator envelope
+            let scale_factor = if car_mag > 0.0001 {
+                mod_mag / car_mag
+            } else {
+                0.0
+            };
+
+            // Apply scaling to carrier, keeping carrier phase
+            spectrum[i].re = carrier_fft[i].re * scale_factor;
+            spectrum[i].im = carrier_fft[i].im * scale_factor;
+        }
+
+        if let Some(bypass) = &config.sibilance_bypass {
+            let crossover = ((bypass.crossover_hz / bin_width) as usize).min(num_bins);
+            let energy = |bins: &[f32]| bins.iter().map(|m| m * m).sum::<f32>();
+            let total = energy(&analysis_magnitudes[..num_bins]);
+            let high = energy(&analysis_magnitudes[crossover..num_bins]);
+            let amount = if total > 0.0 {
+                bypass.amount(high / total)
+            } else {
+                0.0
+            };
+            for i in crossover..num_bins {
+                spectrum[i].re += modulator_fft[i].re * amount;
+                spectrum[i].im += modulator_fft[i].im * amount;
+            }
+        }
+    });
+    let nyquist = if carrier_nyquist.abs() > 0.0001 {
+        modulator_nyquist.abs().copysign(carrier_nyquist)
+    } else {
+        0.0
+    };
+
+    resynthesize(fft, spectrum, nyquist, gate_gains.as_deref_mut(), input_buffer, config);
+    for i in 0..N {
+        input_buffer[i] *= analysis_window_buffer[i];
+    }
+    soft_clip_frame(input_buffer, config.soft_clip.as_ref());
+}
+
+/// Envelope smoothing of talk-box mode when no
+/// [`VocoderEnvelope`](crate::VocoderEnvelope) is configured
+const TALKBOX_ENVELOPE: VocoderEnvelope = VocoderEnvelope { attack_ms: 5.0, release_ms: 40.0 };
+
+/// Level of the loudest voice partial, relative to full scale, from which the
+/// talk-box passes the carrier at full level
+const TALKBOX_PRESENCE_LEVEL: f32 = 0.1;
+
+/// Generic talk-box processing
+///
+/// The smooth formant envelope of the voice filters the carrier, like a synth
+/// played through a tube into the mouth. Unlike the vocoder, the carrier keeps
+/// its own spectrum and level; the voice only shapes it, so the harmonics and
+/// noise of the voice never reach the output.
+pub fn process_talkbox_generic<const N: usize, const HALF_N: usize, F>(
+    fft: &mut F,
+    workspace: &mut Workspace<N, HALF_N>,
+    input_buffer: &mut [f32; N],
+    carrier_buffer: &mut [f32; N],
+    state: &mut FrameState<'_, N>,
+    config: &VocalEffectsConfig,
+) -> [f32; N]
+where
+    F: DynFft<N, HALF_N> + ?Sized,
+{
+    process_talkbox_in_place(fft, workspace, input_buffer, carrier_buffer, state, config);
+    *input_buffer
+}
+
+/// [`process_talkbox_generic`] writing the output frame over `input_buffer`
+///
+/// The input frame is consumed; no second frame-sized buffer is returned.
+///
+/// `carrier_buffer` is used as scratch and holds its spectrum afterwards.
+pub fn process_talkbox_in_place<const N: usize, const HALF_N: usize, F>(
+    fft: &mut F,
+    workspace: &mut Workspace<N, HALF_N>,
+    input_buffer: &mut [f32; N],
+    carrier_buffer: &mut [f32; N],
+    state: &mut FrameState<'_, N>,
+    config: &VocalEffectsConfig,
+) where
+    F: DynFft<N, HALF_N> + ?Sized,
+{
+    let FrameState {
+        last_input_phases,
+        last_output_phases,
+        bins,
+        gate_gains,
+        envelope_cache,
+        vocoder_envelope: envelope,
+        ..
+    } = state;
+    let analysis_window_buffer = bins.window().unwrap_or_else(|| fft.hann_window());
+    workspace.prepare();
+    let Workspace {
+        spectrum,
+        analysis_magnitudes,
+        synthesis_magnitudes: cepstrum,
+        envelope: voice_envelope,
+        ..
+    } = workspace;
+
+    profile_stage!(
+        Window,
+        for i in 0..N {
+            input_buffer[i] *= analysis_window_buffer[i];
+            carrier_buffer[i] *= analysis_window_buffer[i];
+        }
+    );
+
+    // The voice only needs its envelope, taken before the carrier reuses the FFT
+    let modulator_fft = profile_stage!(Fft, fft.forward(input_buffer));
+    unpack_nyquist(modulator_fft);
+    let num_bins = HALF_N.min(modulator_fft.len());
     for i in 0..num_bins {
-        // Get modulator magnitude (vocal envelope)
-        let mod_mag = sqrtf(
+        analysis_magnitudes[i] = dsp::guards::flush_denormal(sqrtf(
             modulator_fft[i].re * modulator_fft[i].re + modulator_fft[i].im * modulator_fft[i].im,
-        );
+        ));
+    }
+    profile_stage!(
+        Envelope,
+        formant_envelope(
+            fft,
+            envelope_cache.as_deref_mut(),
+            analysis_magnitudes,
+            voice_envelope,
+            spectrum,
+            cepstrum,
+            config,
+        )
+    );
 
-        // Get carrier magnitude
-        let car_mag =
-            sqrtf(carrier_fft[i].re * carrier_fft[i].re + carrier_fft[i].im * carrier_fft[i].im);
+    let carrier_fft = profile_stage!(Fft, fft.forward(carrier_buffer));
+    let carrier_nyquist = unpack_nyquist(carrier_fft);
+    let num_bins = num_bins.min(carrier_fft.len());
 
-        // Scale carrier by modulator envelope
-        let scale_factor = if car_mag > 0.0001 {
-            mod_mag / car_mag
+    let nyquist = profile_stage!(Synthesis, {
+        let hop_seconds = config.hop_size_for(N) as f32 / config.sample_rate;
+        let envelope: &[f32] = match envelope {
+            Some(envelope) => {
+                let smoothing = config.vocoder_envelope.unwrap_or(TALKBOX_ENVELOPE);
+                let attack = gate::smoothing(smoothing.attack_ms, hop_seconds);
+                let release = gate::smoothing(smoothing.release_ms, hop_seconds);
+                for (smoothed, &magnitude) in
+                    envelope[..num_bins].iter_mut().zip(&voice_envelope[..num_bins])
+                {
+                    let coefficient = if magnitude > *smoothed {
+                        attack
+                    } else {
+                        release
+                    };
+                    *smoothed += (magnitude - *smoothed) * coefficient;
+                }
+                &envelope[..num_bins]
+            }
+            None => &voice_envelope[..num_bins],
+        };
+
+        // The envelope peaks at unity, so the carrier keeps its level at the
+        // strongest formant and only fades when the voice falls below the presence
+        // level. A full-scale sine peaks at N / 4 in a Hann-windowed frame.
+        let peak = |bins: &[f32]| bins.iter().fold(0.0f32, |peak, &m| peak.max(m));
+        let envelope_peak = peak(envelope);
+        let voice_level = peak(&analysis_magnitudes[..num_bins]) * 4.0 / bins.frame_size() as f32;
+        let scale = if envelope_peak > 0.0 {
+            (voice_level / TALKBOX_PRESENCE_LEVEL).min(1.0) / envelope_peak
         } else {
             0.0
         };
-
-        // Apply scaling to carrier, keeping carrier phase
-        full_spectrum[i].re = carrier_fft[i].re * scale_factor;
-        full_spectrum[i].im = carrier_fft[i].im * scale_factor;
-
-        // Conjugate symmetry for real output
-        if i > 0 && i < num_bins {
-            full_spectrum[N - i].re = full_spectrum[i].re;
-            full_spectrum[N - i].im = -full_spectrum[i].im;
+        for i in 0..num_bins {
+            let gain = envelope[i] * scale;
+            spectrum[i].re = carrier_fft[i].re * gain;
+            spectrum[i].im = carrier_fft[i].im * gain;
+            // Keep the phase histories on the carrier so switching modes is seamless
+            let phase = atan2f(carrier_fft[i].im, carrier_fft[i].re);
+            last_input_phases[i] = phase;
+            last_output_phases[i] = phase;
         }
+        carrier_nyquist * envelope[num_bins - 1] * scale
+    });
+
+    resynthesize(fft, spectrum, nyquist, gate_gains.as_deref_mut(), input_buffer, config);
+    for i in 0..N {
+        input_buffer[i] *= analysis_window_buffer[i];
     }
+    soft_clip_frame(input_buffer, config.soft_clip.as_ref());
+}
 
-    // Inverse FFT
-    let time_domain_result = F::inverse_fft(&mut full_spectrum);
-    let mut output_samples = [0.0f32; N];
+/// Time constant of the vocoder band gains following a change, in milliseconds
+const VOCODER_EQ_SMOOTHING_MS: f32 = 20.0;
 
-    for i in 0..N {
-        let mut sample = time_domain_result[i].re;
-        sample *= analysis_window_buffer[i];
-        output_samples[i] = sample;
+/// Moves the smoothed band gains one hop towards the [`VocoderEq`] of `settings`
+/// and returns them; without smoothing state the gains apply at once
+fn smooth_eq_gains(
+    eq_gains: Option<&mut [f32; VOCODER_BANDS]>,
+    settings: &MusicalSettings,
+    hop_seconds: f32,
+) -> [f32; VOCODER_BANDS] {
+    let target = settings.vocoder_eq.gains();
+    let Some(gains) = eq_gains else {
+        return target;
+    };
+    let coefficient = libm::expf(-1000.0 * hop_seconds / VOCODER_EQ_SMOOTHING_MS);
+    for (gain, target) in gains.iter_mut().zip(target) {
+        *gain = target + (*gain - target) * coefficient;
     }
+    *gains
+}
 
-    output_samples
+/// Pseudo-random phase for noise in bin `bin`
+///
+/// Hashed from the modulator bin, which changes from frame to frame, so the fill
+/// needs no generator state and sounds like noise rather than a static buzz.
+fn noise_phase(modulator: microfft::Complex32, bin: usize) -> f32 {
+    let mut hash = modulator.re.to_bits()
+        ^ modulator.im.to_bits().rotate_left(16)
+        ^ (bin as u32).wrapping_mul(0x9E37_79B9);
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x7FEB_352D);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x846C_A68B);
+    hash ^= hash >> 16;
+    hash as f32 / u32::MAX as f32 * 2.0 * PI
 }
 
 /// Generic dry processing (pitch shifting with formant preservation but no correction)
 pub fn process_dry_generic<const N: usize, const HALF_N: usize, F>(
+    fft: &mut F,
+    workspace: &mut Workspace<N, HALF_N>,
     unwrapped_buffer: &mut [f32; N],
     synth_buffer: Option<&mut [f32; N]>,
-    last_input_phases: &mut [f32; N],
-    last_output_phases: &mut [f32; N],
+    state: &mut FrameState<'_, N>,
     config: &VocalEffectsConfig,
     settings: &MusicalSettings,
 ) -> [f32; N]
 where
-    F: FftOps<N, HALF_N>,
+    F: DynFft<N, HALF_N> + ?Sized,
+{
+    process_dry_in_place(fft, workspace, unwrapped_buffer, synth_buffer, state, config, settings);
+    *unwrapped_buffer
+}
+
+/// [`process_dry_generic`] writing the output frame over `unwrapped_buffer`
+///
+/// The input frame is consumed; no second frame-sized buffer is returned.
+///
+/// `synth_buffer` is only read.
+pub fn process_dry_in_place<const N: usize, const HALF_N: usize, F>(
+    fft: &mut F,
+    workspace: &mut Workspace<N, HALF_N>,
+    unwrapped_buffer: &mut [f32; N],
+    synth_buffer: Option<&mut [f32; N]>,
+    state: &mut FrameState<'_, N>,
+    config: &VocalEffectsConfig,
+    settings: &MusicalSettings,
+) where
+    F: DynFft<N, HALF_N> + ?Sized,
 {
-    let hop_size = (N as f32 * config.hop_ratio) as usize;
-    let analysis_window_buffer = F::get_hann_window();
-    let mut full_spectrum = [microfft::Complex32 { re: 0.0, im: 0.0 }; N];
-    let mut analysis_magnitudes = [0.0; HALF_N];
-    let mut analysis_frequencies = [0.0; HALF_N];
-    let mut synthesis_magnitudes = [0.0; N];
-    let mut synthesis_frequencies = [0.0; N];
-    let mut envelope = [1.0f32; HALF_N];
+    let FrameState {
+        last_input_phases,
+        last_output_phases,
+        bins,
+        magnitude_history,
+        gate_gains,
+        envelope_cache,
+        ..
+    } = state;
+    let hop_size = config.hop_size_for(N);
+    #[cfg(not(feature = "no-panic"))]
+    debug_assert_eq!(bins.hop_size(), hop_size, "bin tables for another hop size");
+    let bin_width = config.sample_rate / N as f32;
+    let analysis_window_buffer = bins.window().unwrap_or_else(|| fft.hann_window());
+    workspace.prepare();
+    let Workspace {
+        spectrum,
+        analysis_magnitudes,
+        analysis_frequencies,
+        synthesis_magnitudes,
+        synthesis_frequencies,
+        envelope,
+        harmonic_mask,
+    } = workspace;
 
     let formant = settings.formant;
     let note = settings.note;
+    let transient = detect_transient(unwrapped_buffer, hop_size, config);
 
     // Apply windowing
-    for i in 0..N {
-        unwrapped_buffer[i] *= analysis_window_buffer[i];
-    }
+    profile_stage!(
+        Window,
+        for i in 0..N {
+            unwrapped_buffer[i] *= analysis_window_buffer[i];
+        }
+    );
 
     // Forward FFT
-    let fft_result = F::forward_fft(unwrapped_buffer);
+    let fft_result = profile_stage!(Fft, fft.forward(unwrapped_buffer));
+    let nyquist = unpack_nyquist(fft_result);
 
-    let octave_factor = settings.octave as f32 * 0.5;
-    let pitch_shift_ratio = if octave_factor <= 0.4 {
-        1.0
-    } else {
-        octave_factor
-    };
+    let pitch_shift_ratio =
+        settings.octave_shift.ratio() * semitones_to_ratio(settings.pitch_shift_semitones);
 
-    // If no effects, just pass through
-    if formant == 0 && (pitch_shift_ratio > 0.99 && pitch_shift_ratio < 1.01) {
+    // If no effects, just pass through; the Nyquist bin only survives unshifted frames.
+    // A longer or shorter synthesis hop still needs the phases carried forward.
+    let mut output_nyquist = 0.0;
+    let use_formants = formant.is_shifted() || morphs(envelope_cache.as_deref(), config);
+    if !use_formants && (pitch_shift_ratio > 0.99 && pitch_shift_ratio < 1.01) && !bins.stretches()
+    {
         // Direct pass-through - just copy spectrum
         let num_bins = HALF_N.min(fft_result.len());
-        full_spectrum[..num_bins].copy_from_slice(&fft_result[..num_bins]);
-        for i in 1..num_bins {
-            if N - i < full_spectrum.len() {
-                full_spectrum[N - i] = fft_result[i].conj();
-            }
-        }
+        spectrum[..num_bins].copy_from_slice(&fft_result[..num_bins]);
+        output_nyquist = nyquist;
     } else {
         // Process with phase vocoder
         let num_bins = HALF_N.min(fft_result.len());
 
         // Analysis phase
-        for i in 0..num_bins {
-            let amplitude =
-                sqrtf(fft_result[i].re * fft_result[i].re + fft_result[i].im * fft_result[i].im);
-            let phase = atan2f(fft_result[i].im, fft_result[i].re);
+        profile_stage!(
+            Analysis,
+            for i in 0..num_bins {
+                let amplitude = sqrtf(
+                    fft_result[i].re * fft_result[i].re + fft_result[i].im * fft_result[i].im,
+                );
+                let phase = atan2f(fft_result[i].im, fft_result[i].re);
 
-            let mut phase_diff = phase - last_input_phases[i];
-            let bin_centre_frequency = 2.0 * PI * i as f32 / N as f32;
-            phase_diff =
-                frequency_analysis::wrap_phase(phase_diff - bin_centre_frequency * hop_size as f32);
-            let bin_deviation = phase_diff * N as f32 / hop_size as f32 / (2.0 * PI);
+                let phase_diff = frequency_analysis::wrap_phase(
+                    phase - last_input_phases[i] - bins.phase_advance(i),
+                );
+                let bin_deviation = bins.deviation_bins(phase_diff);
 
-            analysis_frequencies[i] = i as f32 + bin_deviation;
-            analysis_magnitudes[i] = amplitude;
-            last_input_phases[i] = phase;
-        }
+                analysis_frequencies[i] = i as f32 + bin_deviation;
+                analysis_magnitudes[i] = dsp::guards::flush_denormal(amplitude);
+                last_input_phases[i] = phase;
+            }
+        );
+        let separate = separate_harmonics(
+            analysis_magnitudes,
+            magnitude_history.as_deref_mut(),
+            harmonic_mask,
+            num_bins,
+            bin_width,
+            config,
+        );
 
         // Extract formant envelope if needed
-        if formant != 0 {
-            extract_cepstral_envelope::<N, HALF_N, F>(&analysis_magnitudes, &mut envelope);
+        let mut morph = None;
+        if use_formants {
+            morph = profile_stage!(
+                Envelope,
+                formant_envelope(
+                    fft,
+                    envelope_cache.as_deref_mut(),
+                    analysis_magnitudes,
+                    envelope,
+                    spectrum,
+                    synthesis_magnitudes,
+                    config,
+                )
+            );
+        } else if let Some(cache) = envelope_cache {
+            cache.clear();
         }
 
-        // Zero synthesis arrays
-        synthesis_magnitudes.fill(0.0);
-        synthesis_frequencies.fill(0.0);
+        if transient == Some(TransientHandling::Passthrough) {
+            output_nyquist = nyquist;
+            profile_stage!(
+                Synthesis,
+                pass_through_transient(
+                    fft_result,
+                    spectrum,
+                    last_input_phases,
+                    last_output_phases,
+                    num_bins
+                )
+            );
+        } else {
+            let reset_phases = transient == Some(TransientHandling::PhaseReset);
+            // Zero synthesis arrays
+            profile_stage!(Synthesis, {
+                synthesis_magnitudes.fill(0.0);
+                synthesis_frequencies.fill(0.0);
+
+                let formant_ratio = formant.uncorrected_ratio();
 
-        let formant_ratio = match formant {
-            1 => 0.8, // Lower formants
-            2 => 1.3, // Raise formants
-            _ => 1.0, // No formant shift
+                // Pitch and formant shifting
+                for i in 0..num_bins {
+                    let residual = if use_formants {
+                        analysis_magnitudes[i] / envelope[i].max(1e-6)
+                    } else {
+                        analysis_magnitudes[i]
+                    };
+
+                    let new_bin_f = i as f32 * pitch_shift_ratio;
+                    let new_bin = floorf(new_bin_f + 0.5) as usize;
+                    let band_gain = band_limit_gain(new_bin_f, bin_width, config);
+
+                    if new_bin < num_bins && band_gain > 0.0 {
+                        let shifted_envelope = if use_formants {
+                            shaped_envelope(envelope, morph.as_ref(), i, formant_ratio, num_bins)
+                        } else {
+                            1.0
+                        };
+
+                        let final_magnitude =
+                            residual * shifted_envelope * harmonic_mask[i] * band_gain;
+                        synthesis_magnitudes[new_bin] += final_magnitude;
+                        synthesis_frequencies[new_bin] =
+                            analysis_frequencies[i] * pitch_shift_ratio;
+                    }
+                }
+
+                // Synthesis phase reconstruction
+                for i in 0..num_bins {
+                    let phase_diff = bins.synthesis_advance(i, synthesis_frequencies[i]);
+
+                    last_output_phases[i] = if reset_phases {
+                        last_input_phases[source_bin(i, pitch_shift_ratio, num_bins)]
+                    } else {
+                        frequency_analysis::wrap_phase(last_output_phases[i] + phase_diff)
+                    };
+                }
+                if bins.stretches() && !reset_phases {
+                    lock_phases_to_peaks(
+                        synthesis_magnitudes,
+                        last_output_phases,
+                        last_input_phases,
+                        pitch_shift_ratio,
+                        num_bins,
+                    );
+                }
+                for i in 0..num_bins {
+                    let amplitude = synthesis_magnitudes[i];
+                    let out_phase = last_output_phases[i];
+
+                    spectrum[i] = microfft::Complex32 {
+                        re: amplitude * cosf(out_phase),
+                        im: amplitude * sinf(out_phase),
+                    };
+                }
+                if separate {
+                    add_percussive(fft_result, spectrum, harmonic_mask, num_bins);
+                }
+            });
+        }
+    }
+
+    resynthesize(
+        fft,
+        spectrum,
+        output_nyquist,
+        gate_gains.as_deref_mut(),
+        unwrapped_buffer,
+        config,
+    );
+    // Equal-power crossfade between voice and synth while a note is held
+    let (vocal_gain, synth_gain) = match synth_buffer {
+        Some(_) if !note.is_auto() => {
+            let angle = settings.synth_mix.clamp(0.0, 1.0) * FRAC_PI_2;
+            (cosf(angle), sinf(angle))
+        }
+        _ => (1.0, 0.0),
+    };
+    for i in 0..N {
+        let vocals = unwrapped_buffer[i] * vocal_gain * analysis_window_buffer[i];
+        let synth = match &synth_buffer {
+            Some(synth_buf) if config.unwindowed_synth => synth_buf[i] * config.hop_ratio,
+            Some(synth_buf) => synth_buf[i] * analysis_window_buffer[i],
+            None => 0.0,
         };
+        unwrapped_buffer[i] = vocals + synth * synth_gain;
+    }
+    soft_clip_frame(unwrapped_buffer, config.soft_clip.as_ref());
+}
+
+/// Generic formant processing (formant shifting with the pitch left untouched)
+///
+/// The spectral envelope is divided out of each bin, re-sampled at the shifted
+/// position and re-applied to the residual. Bins are not moved, so the analysis
+/// phases are reused directly and no phase vocoder accumulation is needed.
+pub fn process_formant_generic<const N: usize, const HALF_N: usize, F>(
+    fft: &mut F,
+    workspace: &mut Workspace<N, HALF_N>,
+    unwrapped_buffer: &mut [f32; N],
+    state: &mut FrameState<'_, N>,
+    config: &VocalEffectsConfig,
+    settings: &MusicalSettings,
+) -> [f32; N]
+where
+    F: DynFft<N, HALF_N> + ?Sized,
+{
+    process_formant_in_place(fft, workspace, unwrapped_buffer, state, config, settings);
+    *unwrapped_buffer
+}
+
+/// [`process_formant_generic`] writing the output frame over `unwrapped_buffer`
+///
+/// The input frame is consumed; no second frame-sized buffer is returned.
+pub fn process_formant_in_place<const N: usize, const HALF_N: usize, F>(
+    fft: &mut F,
+    workspace: &mut Workspace<N, HALF_N>,
+    unwrapped_buffer: &mut [f32; N],
+    state: &mut FrameState<'_, N>,
+    config: &VocalEffectsConfig,
+    settings: &MusicalSettings,
+) where
+    F: DynFft<N, HALF_N> + ?Sized,
+{
+    const GAIN_COMPENSATION: f32 = 2.0 / 3.0;
 
-        // Pitch and formant shifting
+    let FrameState {
+        last_input_phases, last_output_phases, bins, gate_gains, envelope_cache, ..
+    } = state;
+
+    let analysis_window_buffer = bins.window().unwrap_or_else(|| fft.hann_window());
+    workspace.prepare();
+    let Workspace {
+        spectrum,
+        analysis_magnitudes,
+        analysis_frequencies: analysis_phases,
+        synthesis_magnitudes: cepstrum,
+        envelope,
+        ..
+    } = workspace;
+
+    let formant_ratio = settings.formant.uncorrected_ratio();
+
+    // Apply windowing
+    profile_stage!(
+        Window,
+        for i in 0..N {
+            unwrapped_buffer[i] *= analysis_window_buffer[i];
+        }
+    );
+
+    // Forward FFT
+    let fft_result = profile_stage!(Fft, fft.forward(unwrapped_buffer));
+    let nyquist = unpack_nyquist(fft_result);
+
+    // Analysis phase
+    let num_bins = HALF_N.min(fft_result.len());
+    profile_stage!(
+        Analysis,
+        for i in 0..num_bins {
+            analysis_magnitudes[i] = dsp::guards::flush_denormal(sqrtf(
+                fft_result[i].re * fft_result[i].re + fft_result[i].im * fft_result[i].im,
+            ));
+            analysis_phases[i] = atan2f(fft_result[i].im, fft_result[i].re);
+        }
+    );
+
+    let use_formants = formant_ratio != 1.0 || morphs(envelope_cache.as_deref(), config);
+    let mut morph = None;
+    if use_formants {
+        morph = profile_stage!(
+            Envelope,
+            formant_envelope(
+                fft,
+                envelope_cache.as_deref_mut(),
+                analysis_magnitudes,
+                envelope,
+                spectrum,
+                cepstrum,
+                config,
+            )
+        );
+    } else if let Some(cache) = envelope_cache {
+        cache.clear();
+    }
+
+    // Re-apply the shifted envelope to the residual, keeping the analysis phase
+    profile_stage!(
+        Synthesis,
         for i in 0..num_bins {
-            let residual = if formant != 0 {
-                analysis_magnitudes[i] / envelope[i].max(1e-6)
+            let magnitude = if use_formants {
+                let residual = analysis_magnitudes[i] / envelope[i].max(1e-6);
+                residual * shaped_envelope(envelope, morph.as_ref(), i, formant_ratio, num_bins)
             } else {
                 analysis_magnitudes[i]
             };
 
-            let new_bin = (floorf(i as f32 * pitch_shift_ratio + 0.5)) as usize;
+            let phase = analysis_phases[i];
+            spectrum[i] =
+                microfft::Complex32 { re: magnitude * cosf(phase), im: magnitude * sinf(phase) };
+
+            // Keep both phase histories aligned so switching to a phase vocoder mode is seamless
+            last_input_phases[i] = phase;
+            last_output_phases[i] = phase;
+        }
+    );
 
-            if new_bin < num_bins {
-                let shifted_envelope = if formant != 0 {
-                    let env_pos = (i as f32 / formant_ratio).clamp(0.0, (num_bins - 1) as f32);
-                    let env_idx = env_pos as usize;
-                    let frac = env_pos - env_idx as f32;
+    // Bins are not moved, so the Nyquist value passes through
+    resynthesize(fft, spectrum, nyquist, gate_gains.as_deref_mut(), unwrapped_buffer, config);
+    for i in 0..N {
+        unwrapped_buffer[i] = unwrapped_buffer[i] * analysis_window_buffer[i] * GAIN_COMPENSATION;
+    }
+    soft_clip_frame(unwrapped_buffer, config.soft_clip.as_ref());
+}
 
-                    if env_idx < num_bins - 1 {
-                        envelope[env_idx] * (1.0 - frac) + envelope[env_idx + 1] * frac
-                    } else {
-                        envelope[env_idx]
-                    }
-                } else {
-                    1.0
-                };
+/// Gates the spectrum, packs the output Nyquist value into bin 0 and transforms
+/// it back into `output`
+fn resynthesize<const N: usize, const HALF_N: usize, F>(
+    fft: &mut F,
+    spectrum: &mut [microfft::Complex32; HALF_N],
+    nyquist: f32,
+    gate_gains: Option<&mut [f32; N]>,
+    output: &mut [f32; N],
+    config: &VocalEffectsConfig,
+) where
+    F: DynFft<N, HALF_N> + ?Sized,
+{
+    spectrum[0].im = 0.0;
+    gate_spectrum(spectrum, gate_gains, config);
+    spectrum[0].im = nyquist;
+    profile_stage!(Ifft, fft.inverse_real(spectrum, output));
+}
 
-                let final_magnitude = residual * shifted_envelope;
-                synthesis_magnitudes[new_bin] += final_magnitude;
-                synthesis_frequencies[new_bin] = analysis_frequencies[i] * pitch_shift_ratio;
+/// Gates the resynthesis spectrum when the spectral gate is enabled
+fn gate_spectrum<const N: usize>(
+    spectrum: &mut [microfft::Complex32],
+    gate_gains: Option<&mut [f32; N]>,
+    config: &VocalEffectsConfig,
+) {
+    if let Some(gate) = &config.spectral_gate {
+        let hop_seconds = config.hop_size_for(N) as f32 / config.sample_rate;
+        profile_stage!(
+            Synthesis,
+            gate::apply_spectral_gate(
+                spectrum,
+                gate_gains.map(|gains| &mut gains[..N / 2]),
+                gate,
+                hop_seconds,
+            )
+        );
+    }
+}
+
+/// Extracts the formant envelope of the frame, or takes it from `envelope_cache`,
+/// and returns the morph toward the cache's snapshot if one applies
+/// on the hops between its updates
+fn formant_envelope<'a, const N: usize, const HALF_N: usize, F>(
+    fft: &mut F,
+    envelope_cache: Option<&'a mut EnvelopeCache<N>>,
+    analysis_magnitudes: &[f32; HALF_N],
+    envelope: &mut [f32; HALF_N],
+    spectrum: &mut [microfft::Complex32; HALF_N],
+    cepstrum: &mut [f32; N],
+    config: &VocalEffectsConfig,
+) -> Option<Morph<'a>>
+where
+    F: DynFft<N, HALF_N> + ?Sized,
+{
+    let Some(cache) = envelope_cache else {
+        extract_cepstral_envelope_with(
+            fft,
+            analysis_magnitudes,
+            envelope,
+            config.lifter_cutoff(),
+            spectrum,
+            cepstrum,
+        );
+        return None;
+    };
+    cache.update(fft, analysis_magnitudes, envelope, spectrum, cepstrum, config);
+    let cache: &'a EnvelopeCache<N> = cache;
+    let amount = config.formant_morph?;
+    let target = cache.target()?;
+    Some(Morph { target, level: geometric_mean(envelope), amount })
+}
+
+/// Whether the formant envelope morphs toward a snapshot held in `envelope_cache`
+fn morphs<const N: usize>(
+    envelope_cache: Option<&EnvelopeCache<N>>,
+    config: &VocalEffectsConfig,
+) -> bool {
+    config.formant_morph.is_some() && envelope_cache.is_some_and(|cache| cache.target().is_some())
+}
+
+/// Morph of the live formant envelope toward a snapshot
+struct Morph<'a> {
+    target: &'a FormantSnapshot,
+    /// Geometric mean of the live envelope, the level the snapshot is brought to
+    level: f32,
+    /// Share of the way toward the snapshot
+    amount: f32,
+}
+
+/// Handling to apply to the frame, `None` unless it contains an attack
+fn detect_transient<const N: usize>(
+    frame: &[f32; N],
+    hop_size: usize,
+    config: &VocalEffectsConfig,
+) -> Option<TransientHandling> {
+    (config.transients != TransientHandling::Off
+        && frequency_analysis::detect_transient(frame, hop_size, config.transient_threshold_db))
+    .then_some(config.transients)
+}
+
+/// Splits the frame into harmonic and percussive parts when enabled and the
+/// caller keeps the magnitude history
+///
+/// Returns whether `harmonic_mask` was filled; otherwise it is left at 1.0.
+fn separate_harmonics<const N: usize>(
+    analysis_magnitudes: &[f32],
+    magnitude_history: Option<&mut [f32; N]>,
+    harmonic_mask: &mut [f32],
+    num_bins: usize,
+    bin_width: f32,
+    config: &VocalEffectsConfig,
+) -> bool {
+    match magnitude_history {
+        Some(history) if config.harmonic_percussive_separation => {
+            profile_stage!(
+                Analysis,
+                separation::harmonic_mask(
+                    &analysis_magnitudes[..num_bins],
+                    history,
+                    harmonic_mask,
+                    bin_width,
+                )
+            );
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Adds the percussive part of the analysis spectrum to the output unshifted
+fn add_percussive(
+    analysis_spectrum: &[microfft::Complex32],
+    spectrum: &mut [microfft::Complex32],
+    harmonic_mask: &[f32],
+    num_bins: usize,
+) {
+    for i in 0..num_bins {
+        let percussive = 1.0 - harmonic_mask[i];
+        spectrum[i].re += analysis_spectrum[i].re * percussive;
+        spectrum[i].im += analysis_spectrum[i].im * percussive;
+    }
+}
+
+/// Envelope at `bin` after moving its features up by `formant_ratio`,
+/// interpolated between the neighbouring bins
+fn warped_envelope(envelope: &[f32], bin: usize, formant_ratio: f32, num_bins: usize) -> f32 {
+    let env_pos = (bin as f32 / formant_ratio).clamp(0.0, (num_bins - 1) as f32);
+    let env_idx = env_pos as usize;
+    let frac = env_pos - env_idx as f32;
+    if env_idx < num_bins - 1 {
+        envelope[env_idx] * (1.0 - frac) + envelope[env_idx + 1] * frac
+    } else {
+        envelope[env_idx]
+    }
+}
+
+/// Envelope to re-apply at `bin`: the live one, moved toward the morph target
+/// in log magnitude if there is one, and warped by `formant_ratio`
+fn shaped_envelope(
+    envelope: &[f32],
+    morph: Option<&Morph<'_>>,
+    bin: usize,
+    formant_ratio: f32,
+    num_bins: usize,
+) -> f32 {
+    let live = warped_envelope(envelope, bin, formant_ratio, num_bins);
+    match morph {
+        Some(morph) => {
+            let position = (bin as f32 / formant_ratio).min((num_bins - 1) as f32);
+            let target = morph.level * morph.target.at(position / num_bins as f32);
+            powf(live, 1.0 - morph.amount) * powf(target, morph.amount)
+        }
+        None => live,
+    }
+}
+
+/// Gain of a bin shifted to `shifted_bin`, from the configured band limit
+fn band_limit_gain(shifted_bin: f32, bin_width: f32, config: &VocalEffectsConfig) -> f32 {
+    config.band_limit.map_or(1.0, |limit| limit.gain(shifted_bin * bin_width))
+}
+
+/// Analysis bin that a synthesis bin was shifted from
+fn source_bin(bin: usize, pitch_shift_ratio: f32, num_bins: usize) -> usize {
+    ((floorf(bin as f32 / pitch_shift_ratio + 0.5)) as usize).min(num_bins - 1)
+}
+
+/// Locks the phase of every bin to the spectral peak whose region it lies in,
+/// keeping the phase offsets the analysis frame has around that peak
+///
+/// When the synthesis hop differs from the analysis hop, bins advancing on their
+/// own lose the phase relations that shape each partial, and a partial starting
+/// from silence can cancel itself out. Peaks keep their own phases; regions end
+/// at the quietest bin between two peaks.
+fn lock_phases_to_peaks<const N: usize>(
+    magnitudes: &[f32; N],
+    output_phases: &mut [f32; N],
+    input_phases: &[f32; N],
+    pitch_shift_ratio: f32,
+    num_bins: usize,
+) {
+    let is_peak = |i: usize| {
+        magnitudes[i] > 0.0
+            && (i == 0 || magnitudes[i] >= magnitudes[i - 1])
+            && (i + 1 == num_bins || magnitudes[i] > magnitudes[i + 1])
+    };
+    let mut lock = |peak: usize, region: core::ops::Range<usize>| {
+        let peak_input = input_phases[source_bin(peak, pitch_shift_ratio, num_bins)];
+        for bin in region {
+            let offset = input_phases[source_bin(bin, pitch_shift_ratio, num_bins)] - peak_input;
+            output_phases[bin] = frequency_analysis::wrap_phase(output_phases[peak] + offset);
+        }
+    };
+
+    let mut previous: Option<usize> = None;
+    for peak in (0..num_bins).filter(|&i| is_peak(i)) {
+        let boundary = match previous {
+            Some(previous) => {
+                let quietest = (previous + 1..peak)
+                    .min_by(|&a, &b| magnitudes[a].total_cmp(&magnitudes[b]))
+                    .unwrap_or(peak);
+                lock(previous, previous + 1..quietest);
+                quietest
+            }
+            None => 0,
+        };
+        lock(peak, boundary..peak);
+        previous = Some(peak);
+    }
+    if let Some(previous) = previous {
+        lock(previous, previous + 1..num_bins);
+    }
+}
+
+/// Resynthesises a transient frame from its analysis spectrum, restarting the
+/// synthesis phases from the analysis phases
+fn pass_through_transient<const N: usize>(
+    analysis_spectrum: &[microfft::Complex32],
+    spectrum: &mut [microfft::Complex32],
+    last_input_phases: &[f32; N],
+    last_output_phases: &mut [f32; N],
+    num_bins: usize,
+) {
+    spectrum[..num_bins].copy_from_slice(&analysis_spectrum[..num_bins]);
+    last_output_phases[..num_bins].copy_from_slice(&last_input_phases[..num_bins]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Formant, FrameAnalysis, OctaveShift, ProcessingMode,
+        dsp::{BinTables, Fft1024, FftOps},
+    };
+
+    /// Storage for a [`FrameState`] with the default hop and none of the optional
+    /// parts, like the state of the `process_vocal_effects_*` functions
+    struct StatelessFrame {
+        input_phases: [f32; 1024],
+        output_phases: [f32; 1024],
+        bins: BinTables<1024>,
+        analysis: FrameAnalysis,
+    }
+
+    impl StatelessFrame {
+        fn new() -> Self {
+            Self {
+                input_phases: [0.0; 1024],
+                output_phases: [0.0; 1024],
+                bins: BinTables::new(256),
+                analysis: FrameAnalysis::new(),
             }
         }
 
-        // Synthesis phase reconstruction
-        for i in 0..num_bins {
-            let amplitude = synthesis_magnitudes[i];
-            let bin_deviation = synthesis_frequencies[i] - i as f32;
+        fn state(&mut self) -> FrameState<'_, 1024> {
+            let Self { input_phases, output_phases, bins, analysis } = self;
+            FrameState::stateless(input_phases, output_phases, bins, analysis)
+        }
+    }
 
-            let mut phase_diff = bin_deviation * 2.0 * PI * hop_size as f32 / N as f32;
-            let bin_centre_frequency = 2.0 * PI * i as f32 / N as f32;
-            phase_diff += bin_centre_frequency * hop_size as f32;
+    /// Magnitude of one bin of a test spectrum
+    fn magnitude(bin: microfft::Complex32) -> f32 {
+        sqrtf(bin.norm_sqr())
+    }
 
-            let out_phase = frequency_analysis::wrap_phase(last_output_phases[i] + phase_diff);
-            last_output_phases[i] = out_phase;
+    fn sine_frame<const N: usize>(frequency: f32, sample_rate: f32) -> [f32; N] {
+        let mut frame = [0.0f32; N];
+        for (i, sample) in frame.iter_mut().enumerate() {
+            *sample = 0.5 * sinf(2.0 * PI * frequency * i as f32 / sample_rate);
+        }
+        frame
+    }
+
+    #[test]
+    fn test_formant_mode_without_shift_reconstructs_input() {
+        let config = VocalEffectsConfig::default();
+        let settings = MusicalSettings { mode: ProcessingMode::Formant, ..Default::default() };
+        let input = sine_frame::<1024>(220.0, config.sample_rate);
+        let mut buffer = input;
+
+        let output = process_formant_generic(
+            &mut Fft1024,
+            &mut Workspace::new(),
+            &mut buffer,
+            &mut StatelessFrame::new().state(),
+            &config,
+            &settings,
+        );
 
-            full_spectrum[i] = microfft::Complex32 {
-                re: amplitude * cosf(out_phase),
-                im: amplitude * sinf(out_phase),
+        let window = Fft1024::get_hann_window();
+        for i in 0..1024 {
+            let expected = input[i] * window[i] * window[i] * (2.0 / 3.0);
+            assert!((output[i] - expected).abs() < 1e-3, "sample {i}: {} vs {expected}", output[i]);
+        }
+    }
+
+    #[test]
+    fn test_unshifted_modes_keep_dc_and_nyquist() {
+        let config = VocalEffectsConfig::default();
+        let input: [f32; 1024] =
+            core::array::from_fn(|n| 0.3 + if n % 2 == 0 { 0.2 } else { -0.2 });
+        let window = Fft1024::get_hann_window();
+
+        for mode in [ProcessingMode::Formant, ProcessingMode::Dry] {
+            let settings = MusicalSettings { mode, ..Default::default() };
+            let mut buffer = input;
+            let output = if mode == ProcessingMode::Formant {
+                process_formant_generic(
+                    &mut Fft1024,
+                    &mut Workspace::new(),
+                    &mut buffer,
+                    &mut StatelessFrame::new().state(),
+                    &config,
+                    &settings,
+                )
+            } else {
+                process_dry_generic(
+                    &mut Fft1024,
+                    &mut Workspace::new(),
+                    &mut buffer,
+                    None,
+                    &mut StatelessFrame::new().state(),
+                    &config,
+                    &settings,
+                )
             };
 
-            if i > 0 && i < num_bins && N - i < full_spectrum.len() {
-                full_spectrum[N - i] = full_spectrum[i].conj();
+            // The dry path applies no gain compensation
+            let gain = if mode == ProcessingMode::Formant {
+                2.0 / 3.0
+            } else {
+                1.0
+            };
+            for i in 0..1024 {
+                let expected = input[i] * window[i] * window[i] * gain;
+                assert!((output[i] - expected).abs() < 1e-3, "{mode:?} sample {i}: {}", output[i]);
             }
         }
     }
 
-    // Inverse FFT
-    let time_domain_result = F::inverse_fft(&mut full_spectrum);
-    let mut output_samples = [0.0f32; N];
+    #[test]
+    fn test_formant_mode_keeps_pitch() {
+        let config = VocalEffectsConfig::default();
+        let bin_width = config.sample_rate / 1024.0;
+        let mut output_peaks = [0usize; 2];
 
-    let playing_note = note != 0;
-    for i in 0..N {
-        let vocals = time_domain_result[i].re;
-        let synth = if let Some(ref synth_buf) = synth_buffer {
-            synth_buf[i]
-        } else {
-            0.0
+        for (formant, peak) in
+            [Formant::Lower, Formant::Higher].into_iter().zip(output_peaks.iter_mut())
+        {
+            let settings =
+                MusicalSettings { formant, mode: ProcessingMode::Formant, ..Default::default() };
+            let mut buffer = sine_frame::<1024>(20.0 * bin_width, config.sample_rate);
+
+            let mut output = process_formant_generic(
+                &mut Fft1024,
+                &mut Workspace::new(),
+                &mut buffer,
+                &mut StatelessFrame::new().state(),
+                &config,
+                &settings,
+            );
+            assert!(output.iter().all(|s| s.is_finite()));
+
+            let spectrum = Fft1024::forward_fft(&mut output);
+            let magnitudes: [f32; 512] = core::array::from_fn(|i| magnitude(spectrum[i]));
+            *peak = frequency_analysis::find_fundamental_frequency(&magnitudes);
+        }
+
+        assert_eq!(output_peaks, [20, 20]);
+    }
+
+    #[test]
+    fn test_dry_mode_semitone_shift() {
+        let config = VocalEffectsConfig::default();
+        let bin_width = config.sample_rate / 1024.0;
+        let settings = MusicalSettings {
+            pitch_shift_semitones: 12.0,
+            mode: ProcessingMode::Dry,
+            ..Default::default()
         };
-        let mixed = if playing_note {
-            vocals * 0.96 + synth * 0.04
-        } else {
-            vocals
+        let mut buffer = sine_frame::<1024>(20.0 * bin_width, config.sample_rate);
+
+        let mut output = process_dry_generic(
+            &mut Fft1024,
+            &mut Workspace::new(),
+            &mut buffer,
+            None,
+            &mut StatelessFrame::new().state(),
+            &config,
+            &settings,
+        );
+
+        let spectrum = Fft1024::forward_fft(&mut output);
+        let magnitudes: [f32; 512] = core::array::from_fn(|i| magnitude(spectrum[i]));
+        assert_eq!(frequency_analysis::find_fundamental_frequency(&magnitudes), 40);
+    }
+
+    #[test]
+    fn test_dry_synth_mix_is_equal_power() {
+        let window = Fft1024::get_hann_window();
+        let synth: [f32; 1024] = core::array::from_fn(|i| ((i % 64) as f32 / 32.0) - 1.0);
+        let run = |synth_mix: f32, config: &VocalEffectsConfig| {
+            let settings = MusicalSettings {
+                note: crate::Note::Degree1,
+                synth_mix,
+                mode: ProcessingMode::Dry,
+                ..Default::default()
+            };
+            process_dry_generic(
+                &mut Fft1024,
+                &mut Workspace::new(),
+                &mut [0.0; 1024],
+                Some(&mut synth.clone()),
+                &mut StatelessFrame::new().state(),
+                config,
+                &settings,
+            )
+        };
+
+        // A silent voice leaves the synth at the sine of the mix angle
+        let config = VocalEffectsConfig::default();
+        let half = run(0.5, &config);
+        let gain = core::f32::consts::FRAC_1_SQRT_2;
+        for i in 0..1024 {
+            assert!((half[i] - synth[i] * window[i] * gain).abs() < 1e-5, "sample {i}");
+        }
+        assert!(run(0.0, &config).iter().all(|&sample| sample.abs() < 1e-6));
+
+        // Unwindowed, every frame adds a hop's share of the synth
+        let config = VocalEffectsConfig::builder().unwindowed_synth(true).build().unwrap();
+        let full = run(1.0, &config);
+        for i in 0..1024 {
+            assert!((full[i] - synth[i] * config.hop_ratio).abs() < 1e-5, "sample {i}");
+        }
+    }
+
+    #[test]
+    fn test_octave_shift_is_the_same_in_autotune_and_dry() {
+        let config = VocalEffectsConfig::default();
+        let peak = |mode| {
+            let settings =
+                MusicalSettings { mode, octave_shift: OctaveShift::Up1, ..Default::default() };
+            // A5, already on a note of C major
+            let mut buffer = sine_frame::<1024>(880.0, config.sample_rate);
+            let mut frame = StatelessFrame::new();
+            let mut output = if mode == ProcessingMode::Autotune {
+                process_pitch_correction_generic(
+                    &mut Fft1024,
+                    &mut Workspace::new(),
+                    &mut buffer,
+                    &mut frame.state(),
+                    &config,
+                    &settings,
+                )
+            } else {
+                process_dry_generic(
+                    &mut Fft1024,
+                    &mut Workspace::new(),
+                    &mut buffer,
+                    None,
+                    &mut StatelessFrame::new().state(),
+                    &config,
+                    &settings,
+                )
+            };
+            // The correction itself stays at the note, the shift is applied on top
+            if mode == ProcessingMode::Autotune {
+                let analysis = frame.analysis;
+                assert!((analysis.target_frequency - 880.0).abs() < 0.1, "{analysis:?}");
+            }
+            let spectrum = Fft1024::forward_fft(&mut output);
+            let magnitudes: [f32; 512] = core::array::from_fn(|i| magnitude(spectrum[i]));
+            frequency_analysis::find_fundamental_frequency(&magnitudes)
+        };
+
+        // 1760 Hz is bin 37.5
+        let autotune = peak(ProcessingMode::Autotune);
+        assert!((37..=38).contains(&autotune), "{autotune}");
+        assert_eq!(peak(ProcessingMode::Dry), autotune);
+    }
+
+    #[test]
+    fn test_noise_fill_keeps_modulator_above_dull_carrier() {
+        let settings = MusicalSettings { mode: ProcessingMode::Vocode, ..Default::default() };
+        // A low carrier and a modulator with a partial far above it, like an "s"
+        let level = |config: VocalEffectsConfig| {
+            let bin_width = config.sample_rate / 1024.0;
+            let low = sine_frame::<1024>(20.0 * bin_width, config.sample_rate);
+            let high = sine_frame::<1024>(200.0 * bin_width, config.sample_rate);
+            let mut modulator: [f32; 1024] = core::array::from_fn(|i| low[i] + high[i]);
+            let mut carrier = low;
+            let mut output = process_vocode_generic(
+                &mut Fft1024,
+                &mut Workspace::new(),
+                &mut modulator,
+                &mut carrier,
+                &mut StatelessFrame::new().state(),
+                &config,
+                &settings,
+            );
+            assert!(output.iter().all(|s| s.is_finite()));
+            let spectrum = Fft1024::forward_fft(&mut output);
+            [20, 200].map(|bin| magnitude(spectrum[bin]))
+        };
+
+        let [low, high] = level(VocalEffectsConfig::default());
+        assert!(high < 1e-3 * low, "{low} {high}");
+        let filled = VocalEffectsConfig::builder().noise_fill(-60.0, 0.5).build().unwrap();
+        let [filled_low, filled_high] = level(filled);
+        assert!((filled_low - low).abs() < 0.01 * low, "{filled_low} {low}");
+        assert!(filled_high > 0.1 * low, "{filled_high} {low}");
+    }
+
+    #[test]
+    fn test_vocoder_formant_shift_warps_the_voice() {
+        let config = VocalEffectsConfig::default();
+        let peak = |semitones: f32| {
+            let settings = MusicalSettings {
+                mode: ProcessingMode::Vocode,
+                vocoder_formant_semitones: semitones,
+                ..Default::default()
+            };
+            let bin_width = config.sample_rate / 1024.0;
+            let mut modulator = sine_frame::<1024>(100.0 * bin_width, config.sample_rate);
+            // An impulse in the middle of the frame has a flat spectrum
+            let mut carrier: [f32; 1024] = core::array::from_fn(|i| (i == 512) as u8 as f32);
+            let mut output = process_vocode_generic(
+                &mut Fft1024,
+                &mut Workspace::new(),
+                &mut modulator,
+                &mut carrier,
+                &mut StatelessFrame::new().state(),
+                &config,
+                &settings,
+            );
+            let spectrum = Fft1024::forward_fft(&mut output);
+            let magnitudes: [f32; 512] = core::array::from_fn(|i| magnitude(spectrum[i]));
+            frequency_analysis::find_fundamental_frequency(&magnitudes)
+        };
+
+        assert_eq!(peak(0.0), 100);
+        assert_eq!(peak(12.0), 200);
+        assert_eq!(peak(-12.0), 50);
+    }
+
+    #[test]
+    fn test_vocoder_eq_mutes_bands_smoothly() {
+        let config = VocalEffectsConfig::default();
+        let level = |settings: &MusicalSettings, eq_gains: Option<&mut [f32; VOCODER_BANDS]>| {
+            let bin_width = config.sample_rate / 1024.0;
+            let mut modulator = sine_frame::<1024>(21.0 * bin_width, config.sample_rate);
+            let mut carrier: [f32; 1024] = core::array::from_fn(|i| (i == 512) as u8 as f32);
+            let mut output = process_vocode_generic(
+                &mut Fft1024,
+                &mut Workspace::new(),
+                &mut modulator,
+                &mut carrier,
+                &mut FrameState { vocoder_eq_gains: eq_gains, ..StatelessFrame::new().state() },
+                &config,
+                settings,
+            );
+            magnitude(Fft1024::forward_fft(&mut output)[21])
+        };
+
+        let flat = MusicalSettings { mode: ProcessingMode::Vocode, ..Default::default() };
+        let mut eq = VocoderEq::FLAT;
+        // 984 Hz lies between the bands at 774 Hz and 1036 Hz
+        for band in 0..VOCODER_BANDS {
+            eq.set_band_db(
+                band,
+                if (7..=8).contains(&band) {
+                    VocoderEq::MUTE_DB
+                } else {
+                    0.0
+                },
+            );
+        }
+        let muted = MusicalSettings { vocoder_eq: eq, ..flat };
+        let reference = level(&flat, None);
+        assert!(level(&muted, None) < 1e-3 * reference);
+
+        // With smoothing state the cut fades in over a few hops
+        let mut gains = [1.0f32; VOCODER_BANDS];
+        let first = level(&muted, Some(&mut gains));
+        assert!(first > 0.5 * reference && first < 0.9 * reference, "{first} {reference}");
+        for _ in 0..40 {
+            level(&muted, Some(&mut gains));
+        }
+        assert!(level(&muted, Some(&mut gains)) < 1e-3 * reference);
+    }
+
+    #[test]
+    fn test_vocoder_envelope_releases_slowly() {
+        let settings = MusicalSettings { mode: ProcessingMode::Vocode, ..Default::default() };
+        let level = |config: &VocalEffectsConfig, amplitude: f32, envelope: &mut [f32; 1024]| {
+            let bin_width = config.sample_rate / 1024.0;
+            let mut modulator = sine_frame::<1024>(21.0 * bin_width, config.sample_rate)
+                .map(|sample| sample * amplitude);
+            let mut carrier: [f32; 1024] = core::array::from_fn(|i| (i == 512) as u8 as f32);
+            let mut output = process_vocode_generic(
+                &mut Fft1024,
+                &mut Workspace::new(),
+                &mut modulator,
+                &mut carrier,
+                &mut FrameState {
+                    vocoder_envelope: Some(envelope),
+                    ..StatelessFrame::new().state()
+                },
+                config,
+                &settings,
+            );
+            magnitude(Fft1024::forward_fft(&mut output)[21])
+        };
+
+        // Without smoothing the state is ignored and the voice stops at once
+        let mut envelope = [0.0f32; 1024];
+        let config = VocalEffectsConfig::default();
+        let loud = level(&config, 1.0, &mut envelope);
+        assert_eq!(level(&config, 0.0, &mut envelope), 0.0);
+
+        // A 1 ms attack is there within a hop, a 100 ms release takes many hops
+        let config = VocalEffectsConfig::builder().vocoder_envelope(1.0, 100.0).build().unwrap();
+        let attacked = level(&config, 1.0, &mut envelope);
+        assert!((attacked - loud).abs() < 0.01 * loud, "{attacked} {loud}");
+        let released = level(&config, 0.0, &mut envelope);
+        // One hop of 5.3 ms keeps exp(-5.3 / 100) of the level
+        assert!((released / loud - 0.948).abs() < 0.01, "{released} {loud}");
+        for _ in 0..100 {
+            level(&config, 0.0, &mut envelope);
+        }
+        assert!(level(&config, 0.0, &mut envelope) < 0.01 * loud);
+    }
+
+    #[test]
+    fn test_sibilance_bypass_passes_only_sibilant_frames() {
+        let settings = MusicalSettings { mode: ProcessingMode::Vocode, ..Default::default() };
+        let config = VocalEffectsConfig::builder().sibilance_bypass(4000.0, 0.8).build().unwrap();
+        let bin_width = config.sample_rate / 1024.0;
+        let low = sine_frame::<1024>(20.0 * bin_width, config.sample_rate);
+        let hiss = sine_frame::<1024>(200.0 * bin_width, config.sample_rate);
+        let level = |config: &VocalEffectsConfig, voiced: f32| {
+            let mut modulator: [f32; 1024] = core::array::from_fn(|i| voiced * low[i] + hiss[i]);
+            let mut carrier = low;
+            let mut output = process_vocode_generic(
+                &mut Fft1024,
+                &mut Workspace::new(),
+                &mut modulator,
+                &mut carrier,
+                &mut StatelessFrame::new().state(),
+                config,
+                &settings,
+            );
+            magnitude(Fft1024::forward_fft(&mut output)[200])
+        };
+        // The bypassed voice is windowed twice, like the vocoder output
+        let window = Fft1024::get_hann_window();
+        let mut windowed: [f32; 1024] = core::array::from_fn(|i| hiss[i] * window[i] * window[i]);
+        let reference = magnitude(Fft1024::forward_fft(&mut windowed)[200]);
+
+        // An "s" has no carrier to ride on, the bypass lets it through
+        assert!(level(&VocalEffectsConfig::default(), 0.0) < 1e-3 * reference);
+        let passed = level(&config, 0.0);
+        assert!((passed - 0.8 * reference).abs() < 0.01 * reference, "{passed} {reference}");
+
+        // A vowel with a little hiss stays vocoded
+        assert!(level(&config, 4.0) < 1e-3 * reference);
+    }
+
+    #[test]
+    fn test_talkbox_shapes_carrier_by_voice_envelope() {
+        let config = VocalEffectsConfig::default();
+        let bin_width = config.sample_rate / 1024.0;
+        let low = sine_frame::<1024>(24.0 * bin_width, config.sample_rate);
+        let high = sine_frame::<1024>(160.0 * bin_width, config.sample_rate);
+        let voice = sine_frame::<1024>(20.0 * bin_width, config.sample_rate);
+        let levels = |voice_level: f32| {
+            let mut modulator: [f32; 1024] = core::array::from_fn(|i| voice_level * voice[i]);
+            let mut carrier: [f32; 1024] = core::array::from_fn(|i| low[i] + high[i]);
+            let mut output = process_talkbox_generic(
+                &mut Fft1024,
+                &mut Workspace::new(),
+                &mut modulator,
+                &mut carrier,
+                &mut StatelessFrame::new().state(),
+                &config,
+            );
+            let spectrum = Fft1024::forward_fft(&mut output);
+            [24, 20, 160].map(|bin| magnitude(spectrum[bin]))
+        };
+
+        // The carrier rides the formant of the voice, not the voice itself
+        let [formant, voice_bin, above] = levels(1.0);
+        assert!(formant > 10.0 * above, "{formant} {above}");
+        assert!(voice_bin < formant, "{voice_bin} {formant}");
+
+        // Above the presence level the carrier keeps its level, below it fades out
+        let [quieter, _, _] = levels(0.5);
+        assert!((quieter - formant).abs() < 0.01 * formant, "{quieter} {formant}");
+        let [faded, _, _] = levels(0.05);
+        assert!(faded < 0.5 * formant, "{faded} {formant}");
+        assert!(levels(0.0).iter().all(|&level| level < 1e-3 * formant));
+    }
+
+    #[test]
+    fn test_band_limit_fades_shifted_partials() {
+        let settings = MusicalSettings {
+            pitch_shift_semitones: 12.0,
+            mode: ProcessingMode::Dry,
+            ..Default::default()
         };
-        output_samples[i] = mixed * analysis_window_buffer[i];
+        // Partials at 4.5 and 7.5 kHz land on 9 and 15 kHz
+        let level = |config: VocalEffectsConfig| {
+            let bin_width = config.sample_rate / 1024.0;
+            let low = sine_frame::<1024>(96.0 * bin_width, config.sample_rate);
+            let high = sine_frame::<1024>(160.0 * bin_width, config.sample_rate);
+            let mut buffer: [f32; 1024] = core::array::from_fn(|i| low[i] + high[i]);
+            let mut output = process_dry_generic(
+                &mut Fft1024,
+                &mut Workspace::new(),
+                &mut buffer,
+                None,
+                &mut StatelessFrame::new().state(),
+                &config,
+                &settings,
+            );
+            let spectrum = Fft1024::forward_fft(&mut output);
+            [192, 320].map(|bin| magnitude(spectrum[bin]))
+        };
+
+        let [low, high] = level(VocalEffectsConfig::default());
+        assert!(high > 0.5 * low, "{low} {high}");
+        let limited = VocalEffectsConfig::builder().band_limit(11000.0, 2000.0).build().unwrap();
+        let [limited_low, limited_high] = level(limited);
+        assert!((limited_low - low).abs() < 0.01 * low, "{limited_low} {low}");
+        assert!(limited_high < 0.01 * low, "{limited_high}");
+    }
+
+    #[test]
+    fn test_band_limit_gain() {
+        let limit = crate::BandLimit { cutoff_hz: 10000.0, transition_hz: 2000.0 };
+        assert_eq!(limit.gain(9000.0), 1.0);
+        assert!((limit.gain(11000.0) - 0.5).abs() < 1e-6);
+        assert_eq!(limit.gain(12000.0), 0.0);
+        let hard = crate::BandLimit { cutoff_hz: 10000.0, transition_hz: 0.0 };
+        assert_eq!(hard.gain(10000.5), 0.0);
+    }
+
+    #[test]
+    fn test_transient_handling_reduces_pre_echo() {
+        const ONSET: usize = 4100;
+        let settings = MusicalSettings {
+            pitch_shift_semitones: 7.0,
+            mode: ProcessingMode::Dry,
+            ..Default::default()
+        };
+        let pre_echo = |handling| {
+            let config = VocalEffectsConfig::builder().transients(handling, 9.0).build().unwrap();
+            let latency = config.latency_samples();
+            let mut engine = crate::Engine1024::new(config, settings);
+            let input: [f32; 8192] = core::array::from_fn(|i| {
+                if i < ONSET {
+                    0.0
+                } else {
+                    0.5 * sinf(2.0 * PI * 300.0 * i as f32 / 48000.0)
+                }
+            });
+            let mut output = [0.0f32; 8192];
+            for (input, output) in input.chunks_exact(256).zip(output.chunks_exact_mut(256)) {
+                engine.process_hop(input, None, output).unwrap();
+            }
+            // Energy that arrives ahead of the attack, against that of the note
+            let before: f32 =
+                output[ONSET + latency - 512..ONSET + latency].iter().map(|s| s * s).sum();
+            let after: f32 =
+                output[ONSET + latency..ONSET + latency + 512].iter().map(|s| s * s).sum();
+            before / after
+        };
+
+        let off = pre_echo(TransientHandling::Off);
+        let reset = pre_echo(TransientHandling::PhaseReset);
+        let passthrough = pre_echo(TransientHandling::Passthrough);
+        assert!(reset < off, "{reset} vs {off}");
+        assert!(passthrough < 0.1 * off, "{passthrough} vs {off}");
     }
 
-    output_samples
+    #[test]
+    fn test_separation_shifts_partials_and_passes_clicks() {
+        const CLICK: usize = 5000;
+        let settings = MusicalSettings {
+            pitch_shift_semitones: 12.0,
+            mode: ProcessingMode::Dry,
+            ..Default::default()
+        };
+        let render = |separate, input: &[f32; 8192]| {
+            let config = VocalEffectsConfig::builder()
+                .harmonic_percussive_separation(separate)
+                .build()
+                .unwrap();
+            let mut engine = crate::Engine1024::new(config, settings);
+            let mut output = [0.0f32; 8192];
+            for (input, output) in input.chunks_exact(256).zip(output.chunks_exact_mut(256)) {
+                engine.process_hop(input, None, output).unwrap();
+            }
+            output
+        };
+
+        // Fraction of the click's energy that stays within 1.5 ms of it
+        let mut click = [0.0f32; 8192];
+        click[CLICK] = 1.0;
+        let concentration = |separate| {
+            let output = render(separate, &click);
+            let centre = CLICK + VocalEffectsConfig::default().latency_samples();
+            let near: f32 = output[centre - 72..centre + 72].iter().map(|s| s * s).sum();
+            near / output.iter().map(|s| s * s).sum::<f32>()
+        };
+        let (shifted, separated) = (concentration(false), concentration(true));
+        assert!(separated > 0.9, "{separated} vs {shifted}");
+        assert!(separated > shifted, "{separated} vs {shifted}");
+
+        // A held partial is still shifted up an octave
+        let bin_width = 48000.0 / 1024.0;
+        let sine: [f32; 8192] =
+            core::array::from_fn(|i| 0.5 * sinf(2.0 * PI * 20.0 * bin_width * i as f32 / 48000.0));
+        let output = render(true, &sine);
+        let mut frame: [f32; 1024] = core::array::from_fn(|i| output[6000 + i]);
+        let spectrum = Fft1024::forward_fft(&mut frame);
+        let magnitudes: [f32; 512] = core::array::from_fn(|i| magnitude(spectrum[i]));
+        assert_eq!(frequency_analysis::find_fundamental_frequency(&magnitudes), 40);
+    }
+
+    #[test]
+    fn test_spectral_gate_removes_quiet_bleed() {
+        let bin_width = 48000.0 / 1024.0;
+        let input: [f32; 8192] = core::array::from_fn(|i| {
+            let t = 2.0 * PI * bin_width * i as f32 / 48000.0;
+            0.5 * sinf(20.0 * t) + 0.0005 * sinf(100.0 * t)
+        });
+        let bleed = |config: VocalEffectsConfig| {
+            let settings = MusicalSettings { mode: ProcessingMode::Dry, ..Default::default() };
+            let mut engine = crate::Engine1024::new(config, settings);
+            let mut output = [0.0f32; 8192];
+            for (input, output) in input.chunks_exact(256).zip(output.chunks_exact_mut(256)) {
+                engine.process_hop(input, None, output).unwrap();
+            }
+            let mut frame: [f32; 1024] = core::array::from_fn(|i| output[6000 + i]);
+            let spectrum = Fft1024::forward_fft(&mut frame);
+            magnitude(spectrum[100]) / magnitude(spectrum[20])
+        };
+
+        let open = bleed(VocalEffectsConfig::default());
+        let gated =
+            bleed(VocalEffectsConfig::builder().spectral_gate(-40.0, 5.0, 50.0).build().unwrap());
+        assert!(open > 5e-4, "{open}");
+        assert!(gated < 0.1 * open, "{gated} vs {open}");
+    }
 }
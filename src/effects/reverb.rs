@@ -0,0 +1,236 @@
+//! Feedback delay network reverb.
+//!
+//! Four delay lines of mutually prime lengths feed back into each other through
+//! a Hadamard matrix, which keeps the loop lossless while spreading every echo
+//! across all lines, so the reflections thicken into a smooth tail. Two
+//! allpass diffusers in front smear the input first, so a consonant does not
+//! come back as a flutter. Each line loses a little high end per trip, as air
+//! and soft walls do, and the loop gain sets the decay time.
+//!
+//! In stereo the left channel hears two of the lines and the right the other
+//! two, which decorrelates the tails and spreads the room around the voice.
+//!
+//! The lines are fixed arrays of [`LINE_CAPACITY`] samples, about 18 kB in all,
+//! so the reverb needs no allocator. Above 48 kHz the lines are shortened to
+//! fit, which makes the room sound slightly smaller.
+
+use libm::powf;
+
+use crate::{config::ReverbSettings, ring_buffer::DelayBuffer, stereo::apply_width};
+
+/// Samples each feedback line holds
+pub const LINE_CAPACITY: usize = 1024;
+
+/// Samples each input diffuser holds
+const DIFFUSER_CAPACITY: usize = 512;
+
+/// Feedback line lengths at 48 kHz, mutually prime so their echoes rarely coincide
+const LINE_LENGTHS: [usize; 4] = [601, 773, 919, 1063];
+
+/// Input diffuser lengths at 48 kHz
+const DIFFUSER_LENGTHS: [usize; 2] = [241, 373];
+
+/// Feedback of the input diffusers
+const DIFFUSION: f32 = 0.6;
+
+/// Decay time to -60 dB of the smallest and largest room, in seconds
+const DECAY_SECONDS: (f32, f32) = (0.2, 5.0);
+
+/// Stateful reverb running on a time-domain stream
+///
+/// The [`Engine`](crate::Engine) runs one on its output when
+/// [`VocalEffectsConfig::reverb`](crate::VocalEffectsConfig::reverb) is set.
+///
+/// ```rust
+/// use synthphone_e_vocal_dsp::{ReverbSettings, effects::Reverb};
+///
+/// let settings = ReverbSettings { room_size: 0.5, damping: 0.4, mix: 0.2 };
+/// let mut reverb = Reverb::new(settings, 48000.0);
+/// let mut block = [0.0f32; 64];
+/// reverb.process(&mut block);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Reverb {
+    settings: ReverbSettings,
+    lines: [DelayBuffer<[f32; LINE_CAPACITY]>; 4],
+    lengths: [usize; 4],
+    /// Loop gain of each line for the decay time
+    gains: [f32; 4],
+    /// State of each line's damping low-pass
+    damped: [f32; 4],
+    diffusers: [DelayBuffer<[f32; DIFFUSER_CAPACITY]>; 2],
+    diffuser_lengths: [usize; 2],
+}
+
+impl Reverb {
+    /// Creates a reverb for a stream at `sample_rate` Hz
+    pub fn new(settings: ReverbSettings, sample_rate: f32) -> Self {
+        // All lengths scale together so their ratios, and the room's colour, stay
+        let longest = LINE_LENGTHS[3] as f32;
+        let scale = (sample_rate / 48000.0).min(LINE_CAPACITY as f32 / longest);
+        let scaled = |length: usize| ((length as f32 * scale) as usize).max(1);
+        let lengths = LINE_LENGTHS.map(scaled);
+        let (shortest, largest) = DECAY_SECONDS;
+        let decay = shortest + (largest - shortest) * settings.room_size;
+        let gains = lengths.map(|length| powf(10.0, -3.0 * length as f32 / (sample_rate * decay)));
+        Self {
+            settings,
+            lines: core::array::from_fn(|_| DelayBuffer::new([0.0; LINE_CAPACITY])),
+            lengths,
+            gains,
+            damped: [0.0; 4],
+            diffusers: core::array::from_fn(|_| DelayBuffer::new([0.0; DIFFUSER_CAPACITY])),
+            diffuser_lengths: DIFFUSER_LENGTHS.map(scaled),
+        }
+    }
+
+    /// Returns the settings the reverb was created with
+    pub fn settings(&self) -> &ReverbSettings {
+        &self.settings
+    }
+
+    /// Adds the reverberation to `samples` in place
+    pub fn process(&mut self, samples: &mut [f32]) {
+        for sample in samples.iter_mut() {
+            let [a, b, c, d] = self.tick(*sample);
+            *sample += self.settings.mix * 0.5 * (a + b + c + d);
+        }
+    }
+
+    /// Adds the reverberation of the mid to both channels in place
+    ///
+    /// Each channel takes two of the lines, and `width` scales the difference
+    /// between them.
+    pub fn process_stereo(&mut self, left: &mut [f32], right: &mut [f32], width: f32) {
+        use core::f32::consts::FRAC_1_SQRT_2;
+        for (left, right) in left.iter_mut().zip(right.iter_mut()) {
+            let [a, b, c, d] = self.tick((*left + *right) * FRAC_1_SQRT_2);
+            let (wet_left, wet_right) =
+                apply_width((a + c) * FRAC_1_SQRT_2, (b + d) * FRAC_1_SQRT_2, width);
+            *left += self.settings.mix * wet_left;
+            *right += self.settings.mix * wet_right;
+        }
+    }
+
+    /// Runs one sample through the network, returning the damped line outputs
+    fn tick(&mut self, sample: f32) -> [f32; 4] {
+        let smoothing = 1.0 - self.settings.damping;
+        let mut input = sample;
+        for (diffuser, &length) in self.diffusers.iter_mut().zip(&self.diffuser_lengths) {
+            let delayed = diffuser.tap(length);
+            let fed = input - DIFFUSION * delayed;
+            diffuser.push(fed);
+            input = DIFFUSION * fed + delayed;
+        }
+
+        for (line, (damped, &length)) in
+            self.lines.iter().zip(self.damped.iter_mut().zip(&self.lengths))
+        {
+            *damped += (line.tap(length) - *damped) * smoothing;
+        }
+        let [a, b, c, d] = self.damped;
+
+        // Orthogonal 4x4 Hadamard mix, scaled by 1/2 to keep its energy
+        let mixed = [a + b + c + d, a - b + c - d, a + b - c - d, a - b - c + d];
+        for ((line, gain), mixed) in self.lines.iter_mut().zip(self.gains).zip(mixed) {
+            line.push(input + 0.5 * gain * mixed);
+        }
+        self.damped
+    }
+
+    /// Clears the reverberation
+    pub fn reset(&mut self) {
+        self.lines.iter_mut().for_each(DelayBuffer::clear);
+        self.diffusers.iter_mut().for_each(DelayBuffer::clear);
+        self.damped = [0.0; 4];
+    }
+}
+
+impl super::Effect for Reverb {
+    fn process(&mut self, samples: &mut [f32]) {
+        Reverb::process(self, samples);
+    }
+
+    fn reset(&mut self) {
+        Reverb::reset(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Energy of an impulse response in windows of 4800 samples
+    fn decay(settings: ReverbSettings) -> [f32; 10] {
+        let mut reverb = Reverb::new(settings, 48000.0);
+        let mut energy = [0.0f32; 10];
+        for (window, energy) in energy.iter_mut().enumerate() {
+            let mut block = [0.0f32; 4800];
+            if window == 0 {
+                block[0] = 1.0;
+            }
+            reverb.process(&mut block);
+            let skip = if window == 0 { 1 } else { 0 };
+            *energy = block[skip..].iter().map(|sample| sample * sample).sum();
+        }
+        energy
+    }
+
+    #[test]
+    fn test_tail_decays_with_room_size() {
+        let small = decay(ReverbSettings { room_size: 0.1, damping: 0.0, mix: 1.0 });
+        let large = decay(ReverbSettings { room_size: 0.9, damping: 0.0, mix: 1.0 });
+        assert!(small[0] > 0.0 && large[0] > 0.0);
+        assert!(small.windows(2).all(|pair| pair[1] < pair[0]), "{small:?}");
+        // The large room builds up over the first window before it decays
+        assert!(large[1..].windows(2).all(|pair| pair[1] < pair[0]), "{large:?}");
+
+        // A 0.68 s room is more than 60 dB down 0.8 s in, a 4.5 s room about 10 dB
+        assert!(small[8] < 1e-6 * small[0], "{small:?}");
+        assert!(large[8] > 0.01 * large[0], "{large:?}");
+
+        // Damping takes energy out of every trip round the loop
+        let damped = decay(ReverbSettings { room_size: 0.9, damping: 0.8, mix: 1.0 });
+        assert!(damped[5] < 0.5 * large[5], "{damped:?}");
+    }
+
+    #[test]
+    fn test_largest_room_stays_bounded() {
+        let settings = ReverbSettings { room_size: 1.0, damping: 0.0, mix: 1.0 };
+        let mut reverb = Reverb::new(settings, 96000.0);
+        let mut peak = 0.0f32;
+        for block in 0..200 {
+            let mut samples: [f32; 480] =
+                core::array::from_fn(|n| libm::sinf((block * 480 + n) as f32 * 0.05));
+            reverb.process(&mut samples);
+            peak = samples.iter().fold(peak, |peak, sample| peak.max(sample.abs()));
+        }
+        assert!(peak.is_finite() && peak < 50.0, "{peak}");
+
+        reverb.reset();
+        let mut silence = [0.0f32; 480];
+        reverb.process(&mut silence);
+        assert!(silence.iter().all(|&sample| sample == 0.0));
+    }
+
+    #[test]
+    fn test_stereo_tails_are_decorrelated() {
+        let settings = ReverbSettings { room_size: 0.6, damping: 0.3, mix: 1.0 };
+        let mut reverb = Reverb::new(settings, 48000.0);
+        let mut left = [0.0f32; 9600];
+        let mut right = [0.0f32; 9600];
+        left[0] = 1.0;
+        right[0] = 1.0;
+        reverb.process_stereo(&mut left, &mut right, 1.0);
+        let tail = crate::stereo::mono_check(&left[1..], &right[1..]);
+        assert!(tail.correlation.abs() < 0.5, "{tail:?}");
+
+        // At zero width the tail is the same on both sides
+        reverb.reset();
+        let mut left = [0.0f32; 4800];
+        let mut right = [0.0f32; 4800];
+        left[0] = 1.0;
+        reverb.process_stereo(&mut left, &mut right, 0.0);
+        assert!(left[1..].iter().zip(&right[1..]).all(|(l, r)| (l - r).abs() < 1e-6));
+    }
+}
@@ -0,0 +1,214 @@
+//! Saturation and drive.
+//!
+//! A memoryless waveshaper bends the signal into one of three curves: a cubic
+//! soft clip that only rounds off peaks, an asymmetric tanh that adds the even
+//! harmonics of a driven tube stage, and a wavefolder that mirrors anything
+//! past full scale back down for a hollow, synthetic edge.
+//!
+//! Shaping creates harmonics above Nyquist, which fold back as inharmonic
+//! aliases. With oversampling on, the signal is shaped at twice the sample rate
+//! between sixth-order Butterworth filters at 40% of the host rate, which
+//! removes most of what would fold. The filters are IIR, so oversampling adds
+//! phase shift near the top of the band but no latency to compensate.
+
+use libm::{fabsf, floorf, tanhf};
+
+use crate::{
+    config::{SaturationCurve, SaturationSettings},
+    dsp::biquad::Biquad,
+};
+
+/// Offset of the tanh curve, so it adds even harmonics as well as odd ones
+const TUBE_BIAS: f32 = 0.25;
+
+/// Quality of the sections of a sixth-order Butterworth low-pass
+const BUTTERWORTH_Q: [f32; 3] = [0.517_638, core::f32::consts::FRAC_1_SQRT_2, 1.931_852];
+
+/// Cutoff of the oversampling filters relative to the host sample rate
+const OVERSAMPLING_CUTOFF: f32 = 0.4;
+
+/// Cutoff of the high-pass removing the offset of asymmetric curves, in Hz
+const DC_BLOCK_HZ: f32 = 10.0;
+
+/// Shapes one sample that has already been driven
+///
+/// Every curve keeps the output within -1.0 to 1.0 and passes 0.0 through.
+#[inline]
+pub fn shape(sample: f32, curve: SaturationCurve) -> f32 {
+    match curve {
+        SaturationCurve::SoftClip => {
+            let x = sample.clamp(-1.0, 1.0);
+            1.5 * x - 0.5 * x * x * x
+        }
+        SaturationCurve::Tube => {
+            let offset = tanhf(TUBE_BIAS);
+            (tanhf(sample + TUBE_BIAS) - offset) / (1.0 + offset)
+        }
+        SaturationCurve::Foldback => {
+            // Triangle wave of period 4 that follows the input between -1 and 1
+            let cycle = (sample + 1.0) * 0.25;
+            1.0 - fabsf(4.0 * (cycle - floorf(cycle)) - 2.0)
+        }
+    }
+}
+
+/// Stateful saturator running on a time-domain stream
+///
+/// The [`Engine`](crate::Engine) runs one on its output when
+/// [`VocalEffectsConfig::saturation`](crate::VocalEffectsConfig::saturation) is
+/// set.
+///
+/// ```rust
+/// use synthphone_e_vocal_dsp::{SaturationCurve, SaturationSettings, effects::Saturator};
+///
+/// let settings = SaturationSettings {
+///     curve: SaturationCurve::Tube,
+///     drive: 3.0,
+///     mix: 1.0,
+///     oversample: true,
+/// };
+/// let mut saturator = Saturator::new(settings, 48000.0);
+/// let mut block = [0.0f32; 64];
+/// saturator.process(&mut block);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Saturator {
+    settings: SaturationSettings,
+    /// Anti-imaging filter after zero stuffing
+    upsampling: [Biquad; 3],
+    /// Anti-aliasing filter before decimation
+    downsampling: [Biquad; 3],
+    dc_block: Biquad,
+}
+
+impl Saturator {
+    /// Creates a saturator for a stream at `sample_rate` Hz
+    pub fn new(settings: SaturationSettings, sample_rate: f32) -> Self {
+        let cutoff = OVERSAMPLING_CUTOFF * sample_rate;
+        let filter = BUTTERWORTH_Q.map(|q| Biquad::low_pass(cutoff, q, 2.0 * sample_rate));
+        let dc_block = match settings.curve {
+            SaturationCurve::Tube => {
+                Biquad::high_pass(DC_BLOCK_HZ, core::f32::consts::FRAC_1_SQRT_2, sample_rate)
+            }
+            _ => Biquad::identity(),
+        };
+        Self { settings, upsampling: filter, downsampling: filter, dc_block }
+    }
+
+    /// Returns the settings the saturator was created with
+    pub fn settings(&self) -> &SaturationSettings {
+        &self.settings
+    }
+
+    /// Shapes `samples` in place, blending with the input at the mix
+    pub fn process(&mut self, samples: &mut [f32]) {
+        let SaturationSettings { curve, drive, mix, oversample } = self.settings;
+        for sample in samples.iter_mut() {
+            let shaped = if oversample {
+                // Zero stuffing halves the level, which the first sample makes up
+                let mut last = 0.0;
+                for stuffed in [2.0 * *sample, 0.0] {
+                    let upsampled = cascade(&mut self.upsampling, stuffed);
+                    last = cascade(&mut self.downsampling, shape(drive * upsampled, curve));
+                }
+                last
+            } else {
+                shape(drive * *sample, curve)
+            };
+            let shaped = self.dc_block.process(shaped);
+            *sample += (shaped - *sample) * mix;
+        }
+    }
+
+    /// Clears the filter state
+    pub fn reset(&mut self) {
+        self.upsampling.iter_mut().chain(&mut self.downsampling).for_each(Biquad::reset);
+        self.dc_block.reset();
+    }
+}
+
+/// Runs a sample through a cascade of sections
+#[inline]
+fn cascade(sections: &mut [Biquad; 3], sample: f32) -> f32 {
+    sections.iter_mut().fold(sample, |sample, section| section.process(sample))
+}
+
+impl super::Effect for Saturator {
+    fn process(&mut self, samples: &mut [f32]) {
+        Saturator::process(self, samples);
+    }
+
+    fn reset(&mut self) {
+        Saturator::reset(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testsig::tone_level;
+    use core::f32::consts::PI;
+
+    #[test]
+    fn test_curves() {
+        for curve in [SaturationCurve::SoftClip, SaturationCurve::Tube, SaturationCurve::Foldback] {
+            assert_eq!(shape(0.0, curve), 0.0);
+            for x in [-50.0, -3.0, -1.0, 0.3, 1.0, 7.5, 100.0] {
+                assert!(shape(x, curve).abs() <= 1.0, "{curve:?} {x}");
+            }
+        }
+        assert_eq!(shape(1.0, SaturationCurve::SoftClip), 1.0);
+        assert_eq!(shape(-4.0, SaturationCurve::SoftClip), -1.0);
+        assert_eq!(shape(0.5, SaturationCurve::Foldback), 0.5);
+        assert_eq!(shape(1.5, SaturationCurve::Foldback), 0.5);
+        assert_eq!(shape(-2.0, SaturationCurve::Foldback), 0.0);
+
+        // The tube curve squashes one polarity harder than the other
+        let tube = |x| shape(x, SaturationCurve::Tube);
+        assert!((tube(2.0) + tube(-2.0)).abs() > 0.01);
+    }
+
+    #[test]
+    fn test_oversampling_reduces_aliasing() {
+        // The third harmonic of 15 kHz lies at 45 kHz and folds to 3 kHz
+        let alias = |oversample| {
+            let settings = SaturationSettings {
+                curve: SaturationCurve::SoftClip,
+                drive: 4.0,
+                mix: 1.0,
+                oversample,
+            };
+            let mut saturator = Saturator::new(settings, 48000.0);
+            let mut samples: [f32; 9600] =
+                core::array::from_fn(|n| 0.5 * libm::sinf(2.0 * PI * 15000.0 * n as f32 / 48000.0));
+            saturator.process(&mut samples);
+            (
+                tone_level(&samples[4800..], 3000.0, 48000.0),
+                tone_level(&samples[4800..], 15000.0, 48000.0),
+            )
+        };
+        let (plain, plain_fundamental) = alias(false);
+        let (oversampled, oversampled_fundamental) = alias(true);
+        assert!(plain > 0.05, "{plain}");
+        assert!(oversampled < 0.1 * plain, "{oversampled} {plain}");
+        assert!((oversampled_fundamental - plain_fundamental).abs() < 0.2 * plain_fundamental);
+    }
+
+    #[test]
+    fn test_mix_blends_with_input() {
+        let settings = SaturationSettings {
+            curve: SaturationCurve::Foldback,
+            drive: 3.0,
+            mix: 0.25,
+            oversample: false,
+        };
+        let mut saturator = Saturator::new(settings, 48000.0);
+        let mut samples = [0.5f32];
+        saturator.process(&mut samples);
+        // 1.5 folds to 0.5, so a quarter of the way there is still 0.5
+        assert_eq!(samples[0], 0.5);
+        let mut samples = [0.2f32];
+        saturator.process(&mut samples);
+        assert!((samples[0] - (0.75 * 0.2 + 0.25 * 0.6)).abs() < 1e-6, "{}", samples[0]);
+    }
+}
@@ -0,0 +1,259 @@
+//! Adapter between arbitrary host block sizes and the engine hop size.
+
+use crate::{
+    VocalEffectsError,
+    dsp::DynFft,
+    engine::{Engine, FrameObserver, FrameView},
+};
+
+/// Feeds an [`Engine`] from host blocks of any size.
+///
+/// Audio hosts (cpal, JACK, plugin APIs) deliver blocks of whatever size the
+/// driver chose - 64, 96, 441 samples - which rarely match the engine hop. The
+/// adapter buffers input until a full hop is available and plays the processed
+/// hop back over the following calls, at the cost of one extra hop of latency.
+///
+/// # Example
+///
+/// ```rust
+/// use synthphone_e_vocal_dsp::{
+///     Engine1024, MusicalSettings, VocalEffectsConfig, engine::BlockAdapter,
+/// };
+///
+/// let engine = Engine1024::new(VocalEffectsConfig::default(), MusicalSettings::default());
+/// let mut adapter = BlockAdapter::new(engine);
+///
+/// let input = [0.0f32; 441];
+/// let mut output = [0.0f32; 441];
+/// adapter.process(&input, None, &mut output).unwrap();
+/// assert_eq!(adapter.latency(), 1024);
+/// ```
+pub struct BlockAdapter<const N: usize, const HALF_N: usize, F>
+where
+    F: DynFft<N, HALF_N>,
+{
+    engine: Engine<N, HALF_N, F>,
+    input_fifo: [f32; N],
+    carrier_fifo: [f32; N],
+    output_fifo: [f32; N],
+    fill: usize,
+}
+
+impl<const N: usize, const HALF_N: usize, F> BlockAdapter<N, HALF_N, F>
+where
+    F: DynFft<N, HALF_N>,
+{
+    /// Wraps an engine
+    pub fn new(engine: Engine<N, HALF_N, F>) -> Self {
+        Self {
+            engine,
+            input_fifo: [0.0; N],
+            carrier_fifo: [0.0; N],
+            output_fifo: [0.0; N],
+            fill: 0,
+        }
+    }
+
+    /// Returns the wrapped engine
+    pub fn engine(&self) -> &Engine<N, HALF_N, F> {
+        &self.engine
+    }
+
+    /// Returns the wrapped engine for parameter changes
+    pub fn engine_mut(&mut self) -> &mut Engine<N, HALF_N, F> {
+        &mut self.engine
+    }
+
+    /// Unwraps the engine
+    pub fn into_engine(self) -> Engine<N, HALF_N, F> {
+        self.engine
+    }
+
+    /// Total delay in samples between an input sample and its processed output
+    pub fn latency(&self) -> usize {
+        // One hop of input buffering on top of the engine latency
+        self.engine.hop_size() + self.engine.latency()
+    }
+
+    /// Processes a host block of any length.
+    ///
+    /// # Parameters
+    ///
+    /// * `input` - Input samples
+    /// * `carrier` - Carrier samples, same length as `input`, see [`Engine::process_hop`]
+    /// * `output` - Receives processed samples, same length as `input`
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VocalEffectsError::BufferSizeMismatch`] if the buffer lengths differ,
+    /// or [`VocalEffectsError::MissingCarrier`] if `carrier` is `None` while the
+    /// engine [needs one](Engine::needs_carrier).
+    pub fn process(
+        &mut self,
+        input: &[f32],
+        carrier: Option<&[f32]>,
+        output: &mut [f32],
+    ) -> Result<(), VocalEffectsError> {
+        self.process_observed(input, carrier, output, &mut |_: &FrameView<'_>| {})
+    }
+
+    /// Processes a host block, reporting every frame completed during it to
+    /// `observer`, see [`Engine::process_hop_observed`]
+    ///
+    /// # Errors
+    ///
+    /// See [`BlockAdapter::process`].
+    pub fn process_observed<O>(
+        &mut self,
+        input: &[f32],
+        carrier: Option<&[f32]>,
+        output: &mut [f32],
+        observer: &mut O,
+    ) -> Result<(), VocalEffectsError>
+    where
+        O: FrameObserver + ?Sized,
+    {
+        if output.len() != input.len() || carrier.is_some_and(|c| c.len() != input.len()) {
+            return Err(VocalEffectsError::BufferSizeMismatch);
+        }
+        if carrier.is_none() && self.engine.needs_carrier() {
+            return Err(VocalEffectsError::MissingCarrier);
+        }
+
+        let hop = self.engine.hop_size();
+        let mut offset = 0;
+        while offset < input.len() {
+            let count = (hop - self.fill).min(input.len() - offset);
+            let fifo = self.fill..self.fill + count;
+            let block = offset..offset + count;
+
+            self.input_fifo[fifo.clone()].copy_from_slice(&input[block.clone()]);
+            match carrier {
+                Some(carrier) => {
+                    self.carrier_fifo[fifo.clone()].copy_from_slice(&carrier[block.clone()])
+                }
+                None => self.carrier_fifo[fifo.clone()].fill(0.0),
+            }
+            output[block].copy_from_slice(&self.output_fifo[fifo]);
+
+            self.fill += count;
+            offset += count;
+
+            if self.fill == hop {
+                self.engine.process_hop_observed(
+                    &self.input_fifo[..hop],
+                    Some(&self.carrier_fifo[..hop]),
+                    &mut self.output_fifo[..hop],
+                    observer,
+                )?;
+                self.fill = 0;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Clears the buffered audio and resets the engine
+    pub fn reset(&mut self) {
+        self.engine.reset();
+        self.input_fifo.fill(0.0);
+        self.carrier_fifo.fill(0.0);
+        self.output_fifo.fill(0.0);
+        self.fill = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Engine1024, MusicalSettings, ProcessingMode, VocalEffectsConfig};
+    use core::f32::consts::PI;
+
+    fn sine(n: usize) -> f32 {
+        0.5 * libm::sinf(2.0 * PI * 330.0 * n as f32 / 48000.0)
+    }
+
+    fn formant_adapter() -> BlockAdapter<1024, 512, crate::dsp::Fft1024> {
+        let settings = MusicalSettings { mode: ProcessingMode::Formant, ..Default::default() };
+        BlockAdapter::new(Engine1024::new(VocalEffectsConfig::default(), settings))
+    }
+
+    #[test]
+    fn test_rejects_mismatched_lengths() {
+        let mut adapter = formant_adapter();
+        let mut output = [0.0f32; 64];
+        assert_eq!(
+            adapter.process(&[0.0; 63], None, &mut output),
+            Err(VocalEffectsError::BufferSizeMismatch)
+        );
+        assert_eq!(
+            adapter.process(&[0.0; 64], Some(&[0.0; 32]), &mut output),
+            Err(VocalEffectsError::BufferSizeMismatch)
+        );
+    }
+
+    #[test]
+    fn test_odd_block_sizes_are_delayed_by_latency() {
+        for block_size in [64usize, 96, 441] {
+            let mut adapter = formant_adapter();
+            let latency = adapter.latency();
+            let mut input = [0.0f32; 441];
+            let mut output = [0.0f32; 441];
+
+            let mut n = 0;
+            while n < 8000 {
+                for (i, sample) in input[..block_size].iter_mut().enumerate() {
+                    *sample = sine(n + i);
+                }
+                adapter.process(&input[..block_size], None, &mut output[..block_size]).unwrap();
+
+                for (i, sample) in output[..block_size].iter().enumerate() {
+                    // Skip the start-up while the overlap-add fills
+                    if n + i >= latency + 1024 {
+                        let expected = sine(n + i - latency);
+                        assert!(
+                            (sample - expected).abs() < 1e-2,
+                            "block {block_size}, sample {}: {sample} vs {expected}",
+                            n + i
+                        );
+                    }
+                }
+                n += block_size;
+            }
+        }
+    }
+
+    #[test]
+    fn test_reset_clears_buffers() {
+        let mut adapter = formant_adapter();
+        let input = [0.5f32; 100];
+        let mut output = [0.0f32; 100];
+        for _ in 0..20 {
+            adapter.process(&input, None, &mut output).unwrap();
+        }
+
+        adapter.reset();
+        adapter.process(&[0.0; 100], None, &mut output).unwrap();
+        assert!(output.iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn test_observer_runs_once_per_hop() {
+        let mut adapter = formant_adapter();
+        let mut hops = [0u64; 16];
+        let mut count = 0;
+        let input = [0.1f32; 100];
+        let mut output = [0.0f32; 100];
+        for _ in 0..10 {
+            adapter
+                .process_observed(&input, None, &mut output, &mut |frame: &FrameView<'_>| {
+                    hops[count] = frame.hop_index;
+                    count += 1;
+                })
+                .unwrap();
+        }
+
+        // 1000 samples complete three 256-sample hops
+        assert_eq!(&hops[..count], &[1, 2, 3]);
+    }
+}
@@ -0,0 +1,409 @@
+//! Scheduled parameter changes.
+//!
+//! An [`Automation`] holds timestamped changes to the musical settings, such as
+//! a key change at bar 17 or a vocoder formant sweep over two seconds. Times are
+//! in input samples from the start of the stream, so hosts convert bars and
+//! seconds at their tempo and sample rate.
+//!
+//! In real time, [`Engine::process_hop_automated`] applies everything due by the
+//! end of each input hop before processing it: a change lands on the hop its
+//! time falls in, and ramps move once per hop.
+//!
+//! Offline, `Engine::process_iter_automated` (with `std`) knows the whole timeline and
+//! places each change on the frame centred at its time instead, so the
+//! overlap-add transition is centred on the exact sample of the event in the
+//! aligned output, and ramps are evaluated at the centre of every frame.
+
+use crate::{
+    Formant, Key, MusicalSettings, Note, Octave, OctaveShift, ProcessingMode, TargetSource,
+    VocalEffectsError, dsp::DynFft,
+};
+
+use super::Engine;
+
+/// Continuously variable setting that can be ramped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Parameter {
+    /// [`MusicalSettings::pitch_shift_semitones`]
+    PitchShiftSemitones,
+    /// [`MusicalSettings::vocoder_formant_semitones`]
+    VocoderFormantSemitones,
+    /// [`MusicalSettings::synth_mix`]
+    SynthMix,
+    /// [`MusicalSettings::held_note_cents`]
+    HeldNoteCents,
+}
+
+impl Parameter {
+    /// All rampable parameters
+    pub const ALL: [Parameter; 4] = [
+        Parameter::PitchShiftSemitones,
+        Parameter::VocoderFormantSemitones,
+        Parameter::SynthMix,
+        Parameter::HeldNoteCents,
+    ];
+
+    /// Current value of the parameter in `settings`
+    pub fn get(self, settings: &MusicalSettings) -> f32 {
+        match self {
+            Parameter::PitchShiftSemitones => settings.pitch_shift_semitones,
+            Parameter::VocoderFormantSemitones => settings.vocoder_formant_semitones,
+            Parameter::SynthMix => settings.synth_mix,
+            Parameter::HeldNoteCents => settings.held_note_cents,
+        }
+    }
+
+    /// Sets the parameter in `settings`
+    pub fn set(self, settings: &mut MusicalSettings, value: f32) {
+        match self {
+            Parameter::PitchShiftSemitones => settings.pitch_shift_semitones = value,
+            Parameter::VocoderFormantSemitones => settings.vocoder_formant_semitones = value,
+            Parameter::SynthMix => settings.synth_mix = value,
+            Parameter::HeldNoteCents => settings.held_note_cents = value,
+        }
+    }
+}
+
+/// Change made by an [`AutomationEvent`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Change {
+    /// Moves a parameter linearly from its value at the event to `value` over
+    /// `duration` samples; a zero duration jumps
+    Ramp {
+        /// Parameter to move
+        parameter: Parameter,
+        /// Value at the end of the ramp
+        value: f32,
+        /// Length of the ramp in samples
+        duration: u32,
+    },
+    /// Switches the key
+    Key(Key),
+    /// Switches the held note
+    Note(Note),
+    /// Switches the octave of held notes
+    Octave(Octave),
+    /// Switches the octave transposition
+    OctaveShift(OctaveShift),
+    /// Switches the formant shift
+    Formant(Formant),
+    /// Switches the processing mode, crossfaded like [`Engine::set_mode`]
+    Mode(ProcessingMode),
+    /// Switches the notes automatic correction snaps to
+    Target(TargetSource),
+}
+
+/// Change scheduled at a sample position
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AutomationEvent {
+    /// Input sample the change happens at, counted from the start of the stream
+    pub at: u64,
+    /// What changes
+    pub change: Change,
+}
+
+impl AutomationEvent {
+    /// Creates an event at input sample `at`
+    pub const fn new(at: u64, change: Change) -> Self {
+        Self { at, change }
+    }
+
+    /// Creates an event `seconds` into a stream at `sample_rate`
+    pub fn at_seconds(seconds: f32, sample_rate: f32, change: Change) -> Self {
+        Self::new(libm::roundf((seconds * sample_rate).max(0.0)) as u64, change)
+    }
+
+    /// Ramp of `parameter` to `value` over `duration` samples, starting at `at`
+    pub const fn ramp(at: u64, parameter: Parameter, value: f32, duration: u32) -> Self {
+        Self::new(at, Change::Ramp { parameter, value, duration })
+    }
+}
+
+/// Ramp in progress
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ActiveRamp {
+    start: u64,
+    from: f32,
+    to: f32,
+    duration: u32,
+}
+
+impl ActiveRamp {
+    /// Value at `position`, and whether the ramp has finished
+    fn value_at(&self, position: u64) -> (f32, bool) {
+        let elapsed = position.saturating_sub(self.start);
+        if elapsed >= u64::from(self.duration) {
+            return (self.to, true);
+        }
+        let progress = elapsed as f32 / self.duration as f32;
+        (self.from + (self.to - self.from) * progress, false)
+    }
+}
+
+/// Timeline of up to `CAPACITY` scheduled changes
+///
+/// Events are kept after they have been applied, so [`Automation::rewind`]
+/// replays them for another pass over the same material.
+///
+/// ```rust
+/// use synthphone_e_vocal_dsp::{
+///     Engine1024, Key, MusicalSettings, VocalEffectsConfig,
+///     engine::automation::{Automation, AutomationEvent, Change, Parameter},
+/// };
+///
+/// let mut automation = Automation::<8>::new();
+/// automation.schedule(AutomationEvent::new(96_000, Change::Key(Key::GMajor))).unwrap();
+/// automation
+///     .schedule(AutomationEvent::ramp(48_000, Parameter::VocoderFormantSemitones, -5.0, 96_000))
+///     .unwrap();
+///
+/// let mut engine = Engine1024::new(VocalEffectsConfig::default(), MusicalSettings::default());
+/// let mut output = [0.0f32; 256];
+/// engine.process_hop_automated(&[0.0; 256], None, &mut output, &mut automation).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Automation<const CAPACITY: usize> {
+    /// Scheduled events in time order, the first `len` used
+    events: [Option<AutomationEvent>; CAPACITY],
+    len: usize,
+    /// First event not yet applied
+    next: usize,
+    /// Ramp in progress per entry of [`Parameter::ALL`]
+    ramps: [Option<ActiveRamp>; 4],
+    /// Input samples consumed so far
+    position: u64,
+}
+
+impl<const CAPACITY: usize> Default for Automation<CAPACITY> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const CAPACITY: usize> Automation<CAPACITY> {
+    /// Creates an empty timeline at position 0
+    pub const fn new() -> Self {
+        Self { events: [None; CAPACITY], len: 0, next: 0, ramps: [None; 4], position: 0 }
+    }
+
+    /// Adds an event, after any already scheduled for the same sample
+    ///
+    /// An event at or before the current position is applied at the next hop.
+    /// Returns the event back if the timeline is full.
+    pub fn schedule(&mut self, event: AutomationEvent) -> Result<(), AutomationEvent> {
+        if self.len == CAPACITY {
+            return Err(event);
+        }
+        let index = self.events[self.next..self.len]
+            .iter()
+            .position(|scheduled| scheduled.is_some_and(|scheduled| scheduled.at > event.at))
+            .map_or(self.len, |offset| self.next + offset);
+        self.events.copy_within(index..self.len, index + 1);
+        self.events[index] = Some(event);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Scheduled events in time order, including ones already applied
+    pub fn events(&self) -> impl Iterator<Item = &AutomationEvent> {
+        self.events[..self.len].iter().flatten()
+    }
+
+    /// Number of scheduled events
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if no events are scheduled
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Input samples consumed so far
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Goes back to the start so every event applies again
+    ///
+    /// The settings the events changed are not restored.
+    pub fn rewind(&mut self) {
+        self.next = 0;
+        self.ramps = [None; 4];
+        self.position = 0;
+    }
+
+    /// Removes all events and goes back to the start
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+}
+
+/// Automation of any capacity, for the engine to hold by reference
+pub(super) trait Timeline {
+    /// Moves on by `samples` input samples and updates `settings` to the
+    /// timeline as it stands `delay` samples before the new position
+    fn advance(&mut self, settings: &mut MusicalSettings, samples: usize, delay: usize);
+}
+
+impl<const CAPACITY: usize> Timeline for Automation<CAPACITY> {
+    fn advance(&mut self, settings: &mut MusicalSettings, samples: usize, delay: usize) {
+        self.position += samples as u64;
+        let Some(now) = self.position.checked_sub(delay as u64) else {
+            return;
+        };
+
+        while let Some(event) = self.events[self.next..self.len].first().copied().flatten() {
+            if event.at > now {
+                break;
+            }
+            self.next += 1;
+            match event.change {
+                Change::Ramp { parameter, value, duration } => {
+                    self.ramps[parameter as usize] = Some(ActiveRamp {
+                        start: event.at,
+                        from: parameter.get(settings),
+                        to: value,
+                        duration,
+                    });
+                }
+                Change::Key(key) => settings.key = key,
+                Change::Note(note) => settings.note = note,
+                Change::Octave(octave) => settings.octave = octave,
+                Change::OctaveShift(octave_shift) => settings.octave_shift = octave_shift,
+                Change::Formant(formant) => settings.formant = formant,
+                Change::Mode(mode) => settings.mode = mode,
+                Change::Target(target) => settings.target = target,
+            }
+        }
+
+        for (slot, parameter) in self.ramps.iter_mut().zip(Parameter::ALL) {
+            if let Some(ramp) = slot {
+                let (value, finished) = ramp.value_at(now);
+                parameter.set(settings, value);
+                if finished {
+                    *slot = None;
+                }
+            }
+        }
+    }
+}
+
+impl<const N: usize, const HALF_N: usize, F> Engine<N, HALF_N, F>
+where
+    F: DynFft<N, HALF_N>,
+{
+    /// Applies the automation due by the end of this hop, then processes it
+    ///
+    /// For real-time use: changes land on the hop their time falls in. The
+    /// automation counts input samples, so it has to see every hop. Otherwise
+    /// behaves like [`Engine::process_hop`].
+    ///
+    /// # Errors
+    ///
+    /// See [`Engine::process_hop`]. The automation still moves on by a hop.
+    pub fn process_hop_automated<const CAPACITY: usize>(
+        &mut self,
+        input: &[f32],
+        carrier: Option<&[f32]>,
+        output: &mut [f32],
+        automation: &mut Automation<CAPACITY>,
+    ) -> Result<(), VocalEffectsError> {
+        self.apply_automation(automation, 0);
+        self.process_hop(input, carrier, output)
+    }
+
+    /// Moves `automation` on by a hop, applying what is due `delay` samples
+    /// before the end of the hop
+    pub(super) fn apply_automation(&mut self, automation: &mut dyn Timeline, delay: usize) {
+        let mut settings = self.settings;
+        automation.advance(&mut settings, self.hop_size(), delay);
+        if settings != self.settings {
+            self.set_settings(settings);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Engine512, VocalEffectsConfig};
+
+    #[test]
+    fn test_events_apply_in_time_order() {
+        let mut automation = Automation::<4>::new();
+        automation
+            .schedule(AutomationEvent::new(300, Change::Key(Key::DMajor)))
+            .unwrap();
+        automation
+            .schedule(AutomationEvent::new(100, Change::Key(Key::GMajor)))
+            .unwrap();
+        automation
+            .schedule(AutomationEvent::new(100, Change::Mode(ProcessingMode::Dry)))
+            .unwrap();
+        let order: [u64; 3] = core::array::from_fn(|i| automation.events().nth(i).unwrap().at);
+        assert_eq!(order, [100, 100, 300]);
+
+        let mut settings = MusicalSettings::default();
+        automation.advance(&mut settings, 99, 0);
+        assert_eq!(settings, MusicalSettings::default());
+        automation.advance(&mut settings, 1, 0);
+        assert_eq!((settings.key, settings.mode), (Key::GMajor, ProcessingMode::Dry));
+        automation.advance(&mut settings, 300, 0);
+        assert_eq!(settings.key, Key::DMajor);
+
+        // Rewinding replays the timeline, filling up returns the event
+        automation.rewind();
+        automation.advance(&mut settings, 150, 0);
+        assert_eq!(settings.key, Key::GMajor);
+        automation
+            .schedule(AutomationEvent::new(0, Change::Formant(Formant::Higher)))
+            .unwrap();
+        let late = AutomationEvent::new(0, Change::Note(Note::Degree5));
+        assert_eq!(automation.schedule(late), Err(late));
+    }
+
+    #[test]
+    fn test_ramp_moves_from_current_value() {
+        let mut automation = Automation::<2>::new();
+        let sweep = AutomationEvent::ramp(1000, Parameter::VocoderFormantSemitones, -6.0, 2000);
+        automation.schedule(sweep).unwrap();
+        let mut settings = MusicalSettings { vocoder_formant_semitones: 2.0, ..Default::default() };
+
+        automation.advance(&mut settings, 1000, 0);
+        assert_eq!(settings.vocoder_formant_semitones, 2.0);
+        automation.advance(&mut settings, 500, 0);
+        assert_eq!(settings.vocoder_formant_semitones, 0.0);
+        automation.advance(&mut settings, 5000, 0);
+        assert_eq!(settings.vocoder_formant_semitones, -6.0);
+
+        // A delay evaluates the timeline that many samples in the past
+        automation.rewind();
+        settings.vocoder_formant_semitones = 2.0;
+        automation.advance(&mut settings, 2500, 1000);
+        assert_eq!(settings.vocoder_formant_semitones, 0.0);
+    }
+
+    #[test]
+    fn test_engine_applies_automation_per_hop() {
+        let mut engine = Engine512::new(VocalEffectsConfig::default(), MusicalSettings::default());
+        let mut automation = Automation::<2>::new();
+        automation
+            .schedule(AutomationEvent::new(300, Change::Key(Key::EMajor)))
+            .unwrap();
+        let mut output = [0.0f32; 128];
+
+        // 300 falls in the third 128-sample hop
+        for hop in 0..3 {
+            assert_eq!(engine.settings().key, Key::CMajor, "{hop}");
+            engine
+                .process_hop_automated(&[0.0; 128], None, &mut output, &mut automation)
+                .unwrap();
+        }
+        assert_eq!(engine.settings().key, Key::EMajor);
+        assert_eq!(automation.position(), 384);
+    }
+}
@@ -0,0 +1,74 @@
+//! Tempo of the engine.
+//!
+//! The engine keeps a [`TempoClock`] that moves with every hop, for effects
+//! whose rates and times are note values. Hosts hand it their transport before
+//! each block; firmware sets a tempo or taps one with
+//! [`ControlEvent::TapTempo`](super::ControlEvent::TapTempo).
+
+use crate::{
+    dsp::DynFft,
+    tempo::{TempoClock, Transport},
+};
+
+use super::Engine;
+
+impl<const N: usize, const HALF_N: usize, F> Engine<N, HALF_N, F>
+where
+    F: DynFft<N, HALF_N>,
+{
+    /// Beat clock of the engine, at the start of the next hop
+    pub fn tempo(&self) -> &TempoClock {
+        &self.tempo
+    }
+
+    /// Locks the tempo to the host, with its position at the start of the next hop
+    pub fn set_transport(&mut self, transport: Transport) {
+        self.tempo.sync(transport);
+    }
+
+    /// Lets the tempo run on from the host's last tempo, for tapping or setting it
+    pub fn release_transport(&mut self) {
+        self.tempo.release();
+    }
+
+    /// Sets the tempo unless it follows the host
+    pub fn set_tempo_bpm(&mut self, bpm: f32) {
+        self.tempo.set_bpm(bpm);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ControlEvent, Engine1024, MusicalSettings, VocalEffectsConfig,
+        tempo::{NoteValue, TempoSource, Transport},
+    };
+
+    #[test]
+    fn test_clock_moves_with_hops() {
+        let mut engine = Engine1024::new(VocalEffectsConfig::default(), MusicalSettings::default());
+        let mut output = [0.0f32; 256];
+
+        // 375 hops of 256 are 2 s at 48 kHz, four beats at 120 BPM
+        for _ in 0..375 {
+            engine.process_hop(&[0.0; 256], None, &mut output).unwrap();
+        }
+        assert!((engine.tempo().beat() - 4.0).abs() < 1e-9);
+
+        engine.set_transport(Transport { bpm: 96.0, beat: 32.25, playing: true });
+        engine.process_hop(&[0.0; 256], None, &mut output).unwrap();
+        let beat = 32.25 + 256.0 / 48000.0 * 96.0 / 60.0;
+        assert!((engine.tempo().beat() - beat).abs() < 1e-9);
+        assert_eq!(engine.tempo().seconds(NoteValue::QUARTER), 0.625);
+
+        // Taps only set the tempo once the host lets go
+        engine.handle_event(ControlEvent::TapTempo).unwrap();
+        engine.process_hop(&[0.0; 256], None, &mut output).unwrap();
+        engine.release_transport();
+        engine.set_tempo_bpm(140.0);
+        assert_eq!((engine.tempo().source(), engine.tempo().bpm()), (TempoSource::Internal, 140.0));
+
+        engine.reset();
+        assert_eq!(engine.tempo().beat(), 0.0);
+    }
+}
@@ -0,0 +1,176 @@
+//! Footswitch and encoder events.
+//!
+//! Firmware maps its buttons to a [`ControlEvent`] and hands it to
+//! [`Engine::handle_event`], so what a key change, a tap or a preset switch
+//! does is implemented once here instead of in every firmware. Events are
+//! handled between hops, from the same context that calls
+//! [`Engine::process_hop`], or through a queue drained there.
+
+use crate::{Key, MusicalSettings, VocalEffectsError, dsp::DynFft};
+
+use super::Engine;
+
+/// Action of a footswitch, button or encoder step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ControlEvent {
+    /// Moves the key up a semitone, keeping major or minor
+    KeyUp,
+    /// Moves the key down a semitone, keeping major or minor
+    KeyDown,
+    /// Switches between the major and minor scale on the same root
+    NextScale,
+    /// Turns the bypass on or off
+    ToggleBypass,
+    /// Taps a beat at the current position of the stream, setting the tempo of
+    /// the engine's clock unless it follows the host
+    TapTempo,
+    /// Recalls a settings slot, see [`Engine::recall_slot`]
+    Preset(u8),
+}
+
+impl<const N: usize, const HALF_N: usize, F> Engine<N, HALF_N, F>
+where
+    F: DynFft<N, HALF_N>,
+{
+    /// Applies a control event
+    ///
+    /// Key changes act on the live settings and take effect with the next hop.
+    /// Taps are timed on the input stream, to the nearest hop.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VocalEffectsError::InvalidConfiguration`] for a preset without a
+    /// settings slot.
+    pub fn handle_event(&mut self, event: ControlEvent) -> Result<(), VocalEffectsError> {
+        let key = |key: Key| MusicalSettings { key, ..self.settings };
+        match event {
+            ControlEvent::KeyUp => self.set_settings(key(self.settings.key.transposed(1))),
+            ControlEvent::KeyDown => self.set_settings(key(self.settings.key.transposed(-1))),
+            ControlEvent::NextScale => self.set_settings(key(self.settings.key.parallel())),
+            ControlEvent::ToggleBypass => self.set_bypass(!self.bypassed),
+            ControlEvent::TapTempo => {
+                let seconds = self.samples_received as f64 / f64::from(self.config.sample_rate);
+                if let Some(bpm) = self.tap_tempo.tap(seconds) {
+                    self.tempo.tapped(bpm);
+                }
+            }
+            ControlEvent::Preset(slot) => self.recall_slot(usize::from(slot))?,
+        }
+        Ok(())
+    }
+
+    /// Tempo tapped with [`ControlEvent::TapTempo`], in beats per minute
+    pub fn tapped_bpm(&self) -> Option<f32> {
+        self.tap_tempo.bpm()
+    }
+
+    /// Returns `true` while the output is the dry input
+    pub fn is_bypassed(&self) -> bool {
+        self.bypassed
+    }
+
+    /// Replaces the output with the input, delayed by the latency
+    ///
+    /// Processing carries on underneath, so turning the bypass off again picks
+    /// up without a gap or a jump in time. The switch starts with the next hop
+    /// and fades between the processed and the dry signal over it, which are
+    /// aligned to the sample, so neither direction clicks.
+    pub fn set_bypass(&mut self, bypassed: bool) {
+        self.bypassed = bypassed;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Engine1024, ProcessingMode, VocalEffectsConfig};
+
+    #[test]
+    fn test_events_change_key_and_slot() {
+        let mut engine = Engine1024::new(VocalEffectsConfig::default(), MusicalSettings::default());
+        engine.handle_event(ControlEvent::KeyUp).unwrap();
+        assert_eq!(engine.settings().key, Key::CSharpMajor);
+        engine.handle_event(ControlEvent::KeyDown).unwrap();
+        engine.handle_event(ControlEvent::KeyDown).unwrap();
+        assert_eq!(engine.settings().key, Key::BMajor);
+        engine.handle_event(ControlEvent::NextScale).unwrap();
+        assert_eq!(engine.settings().key, Key::BMinor);
+
+        let dry = MusicalSettings { mode: ProcessingMode::Dry, ..MusicalSettings::default() };
+        engine.store_slot(2, dry).unwrap();
+        engine.handle_event(ControlEvent::Preset(2)).unwrap();
+        assert_eq!(*engine.settings(), dry);
+        assert!(engine.handle_event(ControlEvent::Preset(200)).is_err());
+    }
+
+    #[test]
+    fn test_bypass_passes_delayed_input() {
+        let mut engine = Engine1024::new(VocalEffectsConfig::default(), MusicalSettings::default());
+        engine.handle_event(ControlEvent::ToggleBypass).unwrap();
+        assert!(engine.is_bypassed());
+
+        let latency = engine.latency();
+        let mut output = [0.0f32; 256];
+        for hop in 0..8 {
+            let input: [f32; 256] = core::array::from_fn(|i| (hop * 256 + i) as f32);
+            engine.process_hop(&input, None, &mut output).unwrap();
+            if hop * 256 >= latency {
+                assert_eq!(output[0], (hop * 256 - latency) as f32);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bypass_fades_over_one_hop() {
+        // Shifted up a fifth, the processed voice is out of step with the dry one
+        let settings = MusicalSettings {
+            mode: ProcessingMode::Dry,
+            pitch_shift_semitones: 7.0,
+            ..Default::default()
+        };
+        let mut engine = Engine1024::new(VocalEffectsConfig::default(), settings);
+        let latency = engine.latency();
+        let sine = |n: usize| 0.5 * libm::sinf(n as f32 * 0.05);
+        let mut output = [0.0f32; 256];
+        let mut last = 0.0f32;
+        let mut largest_step = 0.0f32;
+        for hop in 0..24 {
+            if hop == 8 || hop == 16 {
+                engine.set_bypass(hop == 8);
+            }
+            let input: [f32; 256] = core::array::from_fn(|i| sine(hop * 256 + i));
+            engine.process_hop(&input, None, &mut output).unwrap();
+            if hop >= 4 {
+                for &sample in &output {
+                    largest_step = largest_step.max((sample - last).abs());
+                    last = sample;
+                }
+            }
+            last = output[255];
+
+            // A full hop after the switch the output is exactly the delayed input
+            if (9..16).contains(&hop) {
+                let n = hop * 256 - latency;
+                assert!(output.iter().enumerate().all(|(i, &sample)| sample == sine(n + i)));
+            }
+        }
+        // No sample jumps further than the shifted voice itself moves
+        assert!(largest_step < 0.05, "{largest_step}");
+    }
+
+    #[test]
+    fn test_taps_follow_the_stream() {
+        // 0.5 s is 93.75 hops of 256 at 48 kHz, so taps land within a hop of it
+        let mut engine = Engine1024::new(VocalEffectsConfig::default(), MusicalSettings::default());
+        let mut output = [0.0f32; 256];
+        for hop in 0..400 {
+            if hop % 94 == 0 {
+                engine.handle_event(ControlEvent::TapTempo).unwrap();
+            }
+            engine.process_hop(&[0.0; 256], None, &mut output).unwrap();
+        }
+        let bpm = engine.tapped_bpm().unwrap();
+        assert!((bpm - 120.0).abs() < 1.0, "{bpm}");
+    }
+}
@@ -0,0 +1,271 @@
+//! Two independent voices through one engine.
+//!
+//! Some users feed two singers through one unit. [`DualEngine`] runs two
+//! lanes, each a full [`Engine`] with its own frame history, phases, settings
+//! and effects, over one hop of two mono inputs. What holds no state between
+//! frames is shared: the window and FFT tables are statics already, and the
+//! second lane transforms its frames with the first lane's FFT backend and
+//! scratch workspace, so a second voice costs its state but no second
+//! workspace.
+
+use crate::{
+    MusicalSettings, VocalEffectsConfig, VocalEffectsError,
+    dsp::DynFft,
+    stereo::{MonoReading, mono_check},
+    workspace::Workspace,
+};
+
+use super::{Engine, FrameView};
+
+/// Number of lanes of a [`DualEngine`]
+pub const LANES: usize = 2;
+
+/// Streaming processor for two independent mono voices
+///
+/// Both lanes share the configuration, so they have the same hop and latency,
+/// and one carrier, which suits a single synth playing under two singers.
+/// Settings, placement and every other control are per lane, through
+/// [`DualEngine::lane_mut`].
+///
+/// # Example
+///
+/// ```rust
+/// use synthphone_e_vocal_dsp::{
+///     Formant, MusicalSettings, VocalEffectsConfig, dsp::Fft1024, engine::DualEngine,
+/// };
+///
+/// let lower = MusicalSettings { formant: Formant::Lower, ..Default::default() };
+/// let mut engine = DualEngine::<1024, 512, Fft1024>::new(
+///     VocalEffectsConfig::default(),
+///     [MusicalSettings::default(), lower],
+/// );
+/// let (first, second) = ([0.0f32; 256], [0.0f32; 256]);
+/// let (mut first_out, mut second_out) = ([0.0f32; 256], [0.0f32; 256]);
+/// engine.process_hop([&first, &second], None, [&mut first_out, &mut second_out]).unwrap();
+/// ```
+pub struct DualEngine<const N: usize, const HALF_N: usize, F>
+where
+    F: DynFft<N, HALF_N>,
+{
+    lanes: [Engine<N, HALF_N, F>; LANES],
+    /// Mono compatibility of the last stereo mix
+    mono_reading: MonoReading,
+}
+
+impl<const N: usize, const HALF_N: usize, F> DualEngine<N, HALF_N, F>
+where
+    F: DynFft<N, HALF_N> + Default,
+{
+    /// Creates a dual engine, one lane per entry of `settings`
+    ///
+    /// The configuration is prepared as for [`Engine::new`].
+    pub fn new(config: VocalEffectsConfig, settings: [MusicalSettings; LANES]) -> Self {
+        let [first, second] = settings;
+        let (first, second) = (Engine::new(config, first), Engine::new(config, second));
+        // The second lane borrows the first lane's workspace
+        #[cfg(feature = "alloc")]
+        let second = Engine { workspace: None, ..second };
+        Self { lanes: [first, second], mono_reading: MonoReading::MONO }
+    }
+}
+
+impl<const N: usize, const HALF_N: usize, F> DualEngine<N, HALF_N, F>
+where
+    F: DynFft<N, HALF_N>,
+{
+    /// Returns lane `lane`, or `None` if there is no such lane
+    pub fn lane(&self, lane: usize) -> Option<&Engine<N, HALF_N, F>> {
+        self.lanes.get(lane)
+    }
+
+    /// Returns lane `lane` for changing its settings, or `None` if there is no such lane
+    ///
+    /// A lane processed on its own works, but the second lane then transforms
+    /// its frames in a workspace on the stack.
+    pub fn lane_mut(&mut self, lane: usize) -> Option<&mut Engine<N, HALF_N, F>> {
+        self.lanes.get_mut(lane)
+    }
+
+    /// Input samples per hop, the same for both lanes
+    pub fn hop_size(&self) -> usize {
+        self.lanes[0].hop_size()
+    }
+
+    /// Output samples per hop, the same for both lanes
+    pub fn synthesis_hop_size(&self) -> usize {
+        self.lanes[0].synthesis_hop_size()
+    }
+
+    /// Latency in samples, the same for both lanes
+    pub fn latency(&self) -> usize {
+        self.lanes[0].latency()
+    }
+
+    /// Resets both lanes, see [`Engine::reset`]
+    pub fn reset(&mut self) {
+        self.lanes.iter_mut().for_each(Engine::reset);
+        self.mono_reading = MonoReading::MONO;
+    }
+
+    /// Processes one hop of each input into its own output
+    ///
+    /// Lane `i` processes `inputs[i]` into `outputs[i]`; both hear `carrier`.
+    ///
+    /// # Errors
+    ///
+    /// See [`Engine::process_hop`]. The first lane has processed its hop if only
+    /// the second fails.
+    pub fn process_hop(
+        &mut self,
+        inputs: [&[f32]; LANES],
+        carrier: Option<&[f32]>,
+        outputs: [&mut [f32]; LANES],
+    ) -> Result<(), VocalEffectsError> {
+        let [first_out, second_out] = outputs;
+        self.process_lanes(inputs, carrier, (first_out, None), (second_out, None))
+    }
+
+    /// Processes one hop of each input into a stereo mix of both
+    ///
+    /// Each lane is placed with its own [`Placement`](crate::Placement), see
+    /// [`Engine::set_placement`], so the two voices can sit apart in the image.
+    /// `left` and `right` each receive one synthesis hop.
+    ///
+    /// # Errors
+    ///
+    /// See [`DualEngine::process_hop`].
+    pub fn process_hop_stereo(
+        &mut self,
+        inputs: [&[f32]; LANES],
+        carrier: Option<&[f32]>,
+        left: &mut [f32],
+        right: &mut [f32],
+    ) -> Result<(), VocalEffectsError> {
+        let output_hop = self.synthesis_hop_size();
+        if output_hop > N || left.len() != output_hop || right.len() != output_hop {
+            return Err(VocalEffectsError::BufferSizeMismatch);
+        }
+        let mut second_left = [0.0f32; N];
+        let mut second_right = [0.0f32; N];
+        self.process_lanes(
+            inputs,
+            carrier,
+            (left, Some(right)),
+            (&mut second_left[..output_hop], Some(&mut second_right[..output_hop])),
+        )?;
+        let (left, right) = (&mut *left, &mut *right);
+        for (sample, second) in left.iter_mut().zip(&second_left) {
+            *sample += *second;
+        }
+        for (sample, second) in right.iter_mut().zip(&second_right) {
+            *sample += *second;
+        }
+        self.mono_reading = mono_check(left, right);
+        Ok(())
+    }
+
+    /// Mono compatibility of the last stereo mix, [`MonoReading::MONO`] before the first
+    pub fn mono_compatibility(&self) -> MonoReading {
+        self.mono_reading
+    }
+
+    /// Runs both lanes, the second on the first's FFT backend and workspace
+    fn process_lanes(
+        &mut self,
+        inputs: [&[f32]; LANES],
+        carrier: Option<&[f32]>,
+        first_out: (&mut [f32], Option<&mut [f32]>),
+        second_out: (&mut [f32], Option<&mut [f32]>),
+    ) -> Result<(), VocalEffectsError> {
+        // Neither lane runs unless both can
+        if carrier.is_none() && self.lanes.iter().any(Engine::needs_carrier) {
+            return Err(VocalEffectsError::MissingCarrier);
+        }
+        let [first, second] = &mut self.lanes;
+        let observer = &mut |_: &FrameView<'_>| {};
+        first.process_hop_placed(inputs[0], carrier, first_out.0, first_out.1, observer, None)?;
+
+        #[cfg(feature = "alloc")]
+        let mut fallback = None;
+        #[cfg(feature = "alloc")]
+        let workspace = match first.workspace.as_deref_mut() {
+            Some(workspace) => workspace,
+            None => fallback.insert(Workspace::new()),
+        };
+        #[cfg(not(feature = "alloc"))]
+        let workspace = &mut Workspace::new();
+        let scratch = Some((&mut first.fft, workspace));
+        second.process_hop_placed(inputs[1], carrier, second_out.0, second_out.1, observer, scratch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Engine512, Placement, ProcessingMode, dsp::Fft512};
+
+    type Dual512 = DualEngine<512, 256, Fft512>;
+
+    fn sine(n: usize, step: f32) -> f32 {
+        0.5 * libm::sinf(n as f32 * step)
+    }
+
+    #[test]
+    fn test_lanes_match_separate_engines() {
+        let config = VocalEffectsConfig::default();
+        let settings = [
+            MusicalSettings::default(),
+            MusicalSettings { pitch_shift_semitones: 5.0, ..Default::default() },
+        ];
+        let steps = [0.05, 0.031];
+        let input = |lane: usize, hop: usize| -> [f32; 128] {
+            core::array::from_fn(|i| sine(hop * 128 + i, steps[lane]))
+        };
+
+        let mut dual = Dual512::new(config, settings);
+        let mut outputs = [[[0.0f32; 128]; LANES]; 24];
+        for (hop, [first, second]) in outputs.iter_mut().enumerate() {
+            dual.process_hop([&input(0, hop), &input(1, hop)], None, [first, second])
+                .unwrap();
+        }
+        assert!(dual.lane(2).is_none());
+
+        for (lane, settings) in settings.into_iter().enumerate() {
+            let mut engine = Engine512::new(config, settings);
+            let mut expected = [0.0f32; 128];
+            for (hop, output) in outputs.iter().enumerate() {
+                engine.process_hop(&input(lane, hop), None, &mut expected).unwrap();
+                assert_eq!(output[lane], expected, "{lane} {hop}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_stereo_mix_keeps_voices_apart() {
+        // One singer on each side; the second lane is muted by vocode mode on a
+        // silent carrier
+        let settings = MusicalSettings { mode: ProcessingMode::Dry, ..Default::default() };
+        let mut dual = Dual512::new(VocalEffectsConfig::default(), [settings; LANES]);
+        dual.lane_mut(0).unwrap().set_placement(Placement { pan: -1.0, width: 1.0 });
+        dual.lane_mut(1).unwrap().set_placement(Placement { pan: 1.0, width: 1.0 });
+        dual.lane_mut(1).unwrap().set_mode(ProcessingMode::Vocode);
+
+        let (mut left, mut right) = ([0.0f32; 128], [0.0f32; 128]);
+        for hop in 0..24 {
+            let input: [f32; 128] = core::array::from_fn(|i| sine(hop * 128 + i, 0.05));
+            let carrier = Some(&[0.0; 128][..]);
+            dual.process_hop_stereo([&input, &input], carrier, &mut left, &mut right)
+                .unwrap();
+        }
+        assert!(left.iter().any(|sample| sample.abs() > 0.1));
+        assert!(right.iter().all(|sample| sample.abs() < 1e-6));
+        assert!(dual.mono_compatibility().is_mono_compatible());
+
+        assert_eq!(
+            dual.process_hop_stereo([&[0.0; 128], &[0.0; 128]], None, &mut left, &mut [0.0; 64]),
+            Err(VocalEffectsError::BufferSizeMismatch)
+        );
+        dual.reset();
+        assert_eq!(dual.mono_compatibility(), MonoReading::MONO);
+    }
+}
@@ -0,0 +1,233 @@
+//! Async task wrapper for Embassy firmware.
+//!
+//! Audio drivers hand filled hops to the processing task through an
+//! `embassy_sync` [`Channel`], and the task sends processed hops back through a
+//! second one. Vocode and talk box modes take their carrier hops from a third
+//! channel. Settings changes from UI tasks arrive through a [`Signal`] and are
+//! applied between hops, so nothing outside the task touches the engine.
+//!
+//! Enabled with the `embassy` feature.
+
+use embassy_sync::{
+    blocking_mutex::raw::RawMutex,
+    channel::{Channel, Receiver, Sender},
+    signal::Signal,
+};
+
+use crate::{MusicalSettings, VocalEffectsError, dsp::DynFft, engine::Engine};
+
+/// Async wrapper that awaits input hops and yields processed hops.
+///
+/// `HOP` must equal the engine hop size, and `DEPTH` is the capacity of the
+/// channels: two is enough for a ping-pong DMA driver.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// static INPUT: Channel<CriticalSectionRawMutex, [f32; 256], 2> = Channel::new();
+/// static OUTPUT: Channel<CriticalSectionRawMutex, [f32; 256], 2> = Channel::new();
+/// static SYNTH: Channel<CriticalSectionRawMutex, [f32; 256], 2> = Channel::new();
+/// static SETTINGS: Signal<CriticalSectionRawMutex, MusicalSettings> = Signal::new();
+///
+/// #[embassy_executor::task]
+/// async fn vocals(engine: Engine1024) {
+///     let mut task = AsyncEngine::new(engine, &INPUT, &OUTPUT)
+///         .unwrap()
+///         .with_carrier(&SYNTH)
+///         .with_settings(&SETTINGS);
+///     task.run().await;
+/// }
+/// ```
+pub struct AsyncEngine<
+    'a,
+    M,
+    const N: usize,
+    const HALF_N: usize,
+    F,
+    const HOP: usize,
+    const DEPTH: usize,
+> where
+    M: RawMutex,
+    F: DynFft<N, HALF_N>,
+{
+    engine: Engine<N, HALF_N, F>,
+    input: Receiver<'a, M, [f32; HOP], DEPTH>,
+    output: Sender<'a, M, [f32; HOP], DEPTH>,
+    carrier: Option<Receiver<'a, M, [f32; HOP], DEPTH>>,
+    settings: Option<&'a Signal<M, MusicalSettings>>,
+}
+
+impl<'a, M, const N: usize, const HALF_N: usize, F, const HOP: usize, const DEPTH: usize>
+    AsyncEngine<'a, M, N, HALF_N, F, HOP, DEPTH>
+where
+    M: RawMutex,
+    F: DynFft<N, HALF_N>,
+{
+    /// Wraps an engine, receiving hops from `input` and sending them to `output`
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VocalEffectsError::BufferSizeMismatch`] if `HOP` is not the engine
+    /// hop size.
+    pub fn new(
+        engine: Engine<N, HALF_N, F>,
+        input: &'a Channel<M, [f32; HOP], DEPTH>,
+        output: &'a Channel<M, [f32; HOP], DEPTH>,
+    ) -> Result<Self, VocalEffectsError> {
+        if engine.hop_size() != HOP {
+            return Err(VocalEffectsError::BufferSizeMismatch);
+        }
+        Ok(Self {
+            engine,
+            input: input.receiver(),
+            output: output.sender(),
+            carrier: None,
+            settings: None,
+        })
+    }
+
+    /// Receives one carrier hop from `carrier` along with every input hop
+    ///
+    /// Vocode and talk box modes play the carrier; without this channel their
+    /// hops fail with [`VocalEffectsError::MissingCarrier`].
+    pub fn with_carrier(mut self, carrier: &'a Channel<M, [f32; HOP], DEPTH>) -> Self {
+        self.carrier = Some(carrier.receiver());
+        self
+    }
+
+    /// Applies settings published on `signal` before the next hop
+    pub fn with_settings(mut self, signal: &'a Signal<M, MusicalSettings>) -> Self {
+        self.settings = Some(signal);
+        self
+    }
+
+    /// Returns the wrapped engine
+    pub fn engine(&self) -> &Engine<N, HALF_N, F> {
+        &self.engine
+    }
+
+    /// Returns the wrapped engine for parameter changes
+    pub fn engine_mut(&mut self) -> &mut Engine<N, HALF_N, F> {
+        &mut self.engine
+    }
+
+    /// Waits for one input hop, and one carrier hop if there is a carrier channel,
+    /// processes them and sends the result.
+    ///
+    /// Waits for space in the output channel if the consumer has fallen behind.
+    ///
+    /// # Errors
+    ///
+    /// See [`Engine::process_hop`]. The failed hop is sent as silence, so the
+    /// output keeps pace with the input.
+    pub async fn process_next(&mut self) -> Result<(), VocalEffectsError> {
+        let input = self.input.receive().await;
+        let carrier = match &self.carrier {
+            Some(carrier) => Some(carrier.receive().await),
+            None => None,
+        };
+        if let Some(settings) = self.settings.and_then(Signal::try_take) {
+            self.engine.set_settings(settings);
+        }
+
+        let mut output = [0.0f32; HOP];
+        let result =
+            self.engine
+                .process_hop(&input, carrier.as_ref().map(|c| c.as_slice()), &mut output);
+        self.output.send(output).await;
+        result
+    }
+
+    /// Processes hops forever
+    ///
+    /// Hops that fail, such as vocode hops without a carrier channel, play as
+    /// silence.
+    pub async fn run(&mut self) -> ! {
+        loop {
+            let _ = self.process_next().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Engine1024, ProcessingMode, VocalEffectsConfig};
+    use embassy_futures::block_on;
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+
+    #[test]
+    fn test_rejects_wrong_hop_size() {
+        let input = Channel::<NoopRawMutex, [f32; 128], 2>::new();
+        let output = Channel::<NoopRawMutex, [f32; 128], 2>::new();
+        let engine = Engine1024::new(VocalEffectsConfig::default(), MusicalSettings::default());
+        assert!(matches!(
+            AsyncEngine::new(engine, &input, &output),
+            Err(VocalEffectsError::BufferSizeMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_processes_hops_and_applies_settings() {
+        let input = Channel::<NoopRawMutex, [f32; 256], 2>::new();
+        let output = Channel::<NoopRawMutex, [f32; 256], 2>::new();
+        let settings = Signal::<NoopRawMutex, MusicalSettings>::new();
+        let engine = Engine1024::new(VocalEffectsConfig::default(), MusicalSettings::default());
+        let mut task = AsyncEngine::new(engine, &input, &output).unwrap().with_settings(&settings);
+
+        let mut reference =
+            Engine1024::new(VocalEffectsConfig::default(), MusicalSettings::default());
+        let hop: [f32; 256] = core::array::from_fn(|i| 0.5 * libm::sinf(i as f32 * 0.05));
+        let mut expected = [0.0f32; 256];
+        for _ in 0..4 {
+            input.try_send(hop).unwrap();
+            block_on(task.process_next()).unwrap();
+            reference.process_hop(&hop, None, &mut expected).unwrap();
+            assert_eq!(output.try_receive().unwrap(), expected);
+        }
+
+        let formant = MusicalSettings { mode: ProcessingMode::Formant, ..Default::default() };
+        settings.signal(formant);
+        input.try_send(hop).unwrap();
+        block_on(task.process_next()).unwrap();
+        assert_eq!(task.engine().settings().mode, ProcessingMode::Formant);
+        assert!(output.try_receive().is_ok());
+    }
+
+    #[test]
+    fn test_vocodes_with_carrier_channel() {
+        let input = Channel::<NoopRawMutex, [f32; 256], 2>::new();
+        let carrier = Channel::<NoopRawMutex, [f32; 256], 2>::new();
+        let output = Channel::<NoopRawMutex, [f32; 256], 2>::new();
+        let settings = MusicalSettings { mode: ProcessingMode::Vocode, ..Default::default() };
+        let engine = Engine1024::new(VocalEffectsConfig::default(), settings);
+        let mut task = AsyncEngine::new(engine, &input, &output).unwrap().with_carrier(&carrier);
+
+        let mut reference = Engine1024::new(VocalEffectsConfig::default(), settings);
+        let voice: [f32; 256] = core::array::from_fn(|i| 0.5 * libm::sinf(i as f32 * 0.05));
+        let synth: [f32; 256] = core::array::from_fn(|i| if i % 64 < 32 { 0.3 } else { -0.3 });
+        let mut expected = [0.0f32; 256];
+        for _ in 0..8 {
+            input.try_send(voice).unwrap();
+            carrier.try_send(synth).unwrap();
+            block_on(task.process_next()).unwrap();
+            reference.process_hop(&voice, Some(&synth), &mut expected).unwrap();
+            assert_eq!(output.try_receive().unwrap(), expected);
+        }
+        assert!(expected.iter().any(|&sample| sample.abs() > 1e-3));
+    }
+
+    #[test]
+    fn test_sends_silence_without_carrier_channel() {
+        let input = Channel::<NoopRawMutex, [f32; 256], 2>::new();
+        let output = Channel::<NoopRawMutex, [f32; 256], 2>::new();
+        let settings = MusicalSettings { mode: ProcessingMode::TalkBox, ..Default::default() };
+        let engine = Engine1024::new(VocalEffectsConfig::default(), settings);
+        let mut task = AsyncEngine::new(engine, &input, &output).unwrap();
+
+        input.try_send([0.5; 256]).unwrap();
+        let result = block_on(task.process_next());
+        assert_eq!(result, Err(VocalEffectsError::MissingCarrier));
+        assert_eq!(output.try_receive().unwrap(), [0.0; 256]);
+    }
+}
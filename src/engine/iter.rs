@@ -0,0 +1,280 @@
+//! Sample iterator over an engine, for offline pipelines.
+//!
+//! [`Engine::process_iter`] turns a stream of input samples into a stream of
+//! processed samples aligned with it: the first [`Engine::latency`] output
+//! samples, which only carry the engine filling up, are dropped, and the end of
+//! the input is followed by silence until its last sample has come out. A
+//! file in gives a file of the same length out, ready to line up with the
+//! original.
+
+use std::{iter::Fuse, vec, vec::Vec};
+
+use crate::dsp::DynFft;
+
+use super::{
+    Engine,
+    automation::{Automation, Timeline},
+    observer::FrameObserver,
+};
+
+/// Iterator returned by [`Engine::process_iter`]
+pub struct ProcessIter<'a, const N: usize, const HALF_N: usize, F, I>
+where
+    F: DynFft<N, HALF_N>,
+    I: Iterator<Item = f32>,
+{
+    engine: &'a mut Engine<N, HALF_N, F>,
+    input: Fuse<I>,
+    input_hop: Vec<f32>,
+    /// Silent carrier, as there is no carrier stream
+    carrier_hop: Vec<f32>,
+    output_hop: Vec<f32>,
+    /// Next sample of `output_hop` to yield
+    position: usize,
+    /// Output samples still to drop for the latency
+    skip: usize,
+    /// Input samples taken so far
+    consumed: usize,
+    /// Output samples yielded so far
+    emitted: usize,
+    /// Output samples to yield in total, known once the input has ended
+    total: Option<usize>,
+    /// Timeline applied before each hop, with the delay that centres it on frames
+    automation: Option<(&'a mut dyn Timeline, usize)>,
+    /// Observer of every frame processed
+    observer: Option<&'a mut dyn FrameObserver>,
+}
+
+impl<const N: usize, const HALF_N: usize, F> Engine<N, HALF_N, F>
+where
+    F: DynFft<N, HALF_N>,
+{
+    /// Processes a stream of samples, yielding the output aligned with the input
+    ///
+    /// The output is as long as the input, or as long as it is stretched to
+    /// with a separate synthesis hop, and its first sample is the processed
+    /// first input sample. Vocode and talk-box mode get a silent carrier. The
+    /// iterator ends early if processing fails, which only an invalid hop size
+    /// can cause.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use synthphone_e_vocal_dsp::{Engine1024, MusicalSettings, VocalEffectsConfig};
+    ///
+    /// let mut engine = Engine1024::new(VocalEffectsConfig::default(), MusicalSettings::default());
+    /// let input = (0..4800).map(|n| 0.5 * (n as f32 * 0.03).sin());
+    /// let output: Vec<f32> = engine.process_iter(input).collect();
+    /// assert_eq!(output.len(), 4800);
+    /// ```
+    pub fn process_iter<I>(&mut self, input: I) -> ProcessIter<'_, N, HALF_N, F, I::IntoIter>
+    where
+        I: IntoIterator<Item = f32>,
+    {
+        let hop = self.hop_size();
+        let synthesis_hop = self.synthesis_hop_size();
+        let skip = self.latency();
+        ProcessIter {
+            engine: self,
+            input: input.into_iter().fuse(),
+            input_hop: vec![0.0; hop],
+            carrier_hop: vec![0.0; hop],
+            output_hop: vec![0.0; synthesis_hop],
+            position: synthesis_hop,
+            skip,
+            consumed: 0,
+            emitted: 0,
+            total: None,
+            automation: None,
+            observer: None,
+        }
+    }
+
+    /// Processes a stream of samples with scheduled changes, yielding the output
+    /// aligned with the input
+    ///
+    /// Each change takes effect in the frame centred on its time, so in the
+    /// aligned output its transition is centred on that sample, and ramps move
+    /// with every frame. The automation should start at position 0, see
+    /// [`Automation::rewind`]. Otherwise behaves like [`Engine::process_iter`].
+    pub fn process_iter_automated<'a, I, const CAPACITY: usize>(
+        &'a mut self,
+        input: I,
+        automation: &'a mut Automation<CAPACITY>,
+    ) -> ProcessIter<'a, N, HALF_N, F, I::IntoIter>
+    where
+        I: IntoIterator<Item = f32>,
+    {
+        let centre = self.config.frame_size_for(N) / 2;
+        let mut iter = self.process_iter(input);
+        iter.automation = Some((automation, centre));
+        iter
+    }
+
+    /// Processes a stream of samples, yielding the output aligned with the input
+    /// and reporting every frame to `observer`
+    ///
+    /// Frames are reported as they are processed, a latency ahead of the output
+    /// being yielded, and include those of the silence that flushes the end.
+    /// Otherwise behaves like [`Engine::process_iter`].
+    pub fn process_iter_observed<'a, I>(
+        &'a mut self,
+        input: I,
+        observer: &'a mut dyn FrameObserver,
+    ) -> ProcessIter<'a, N, HALF_N, F, I::IntoIter>
+    where
+        I: IntoIterator<Item = f32>,
+    {
+        let mut iter = self.process_iter(input);
+        iter.observer = Some(observer);
+        iter
+    }
+}
+
+impl<const N: usize, const HALF_N: usize, F, I> ProcessIter<'_, N, HALF_N, F, I>
+where
+    F: DynFft<N, HALF_N>,
+    I: Iterator<Item = f32>,
+{
+    /// Processes the next input hop, padding the end of the input with silence
+    fn refill(&mut self) -> Option<()> {
+        let mut ended = false;
+        for sample in self.input_hop.iter_mut() {
+            *sample = match self.input.next() {
+                Some(sample) => {
+                    self.consumed += 1;
+                    sample
+                }
+                None => {
+                    ended = true;
+                    0.0
+                }
+            };
+        }
+        if ended && self.total.is_none() {
+            let stretch = self.output_hop.len() as f64 / self.input_hop.len() as f64;
+            self.total = Some((self.consumed as f64 * stretch).round() as usize);
+        }
+        if let Some((automation, delay)) = &mut self.automation {
+            self.engine.apply_automation(*automation, *delay);
+        }
+        let (input, output) = (&self.input_hop, &mut self.output_hop);
+        let carrier = Some(self.carrier_hop.as_slice());
+        match &mut self.observer {
+            Some(observer) => {
+                self.engine.process_hop_observed(input, carrier, output, &mut **observer)
+            }
+            None => self.engine.process_hop(input, carrier, output),
+        }
+        .ok()?;
+        self.position = 0;
+        Some(())
+    }
+}
+
+impl<const N: usize, const HALF_N: usize, F, I> Iterator for ProcessIter<'_, N, HALF_N, F, I>
+where
+    F: DynFft<N, HALF_N>,
+    I: Iterator<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        loop {
+            if self.total.is_some_and(|total| self.emitted >= total) {
+                return None;
+            }
+            if self.position == self.output_hop.len() {
+                self.refill()?;
+                continue;
+            }
+            let sample = self.output_hop[self.position];
+            self.position += 1;
+            if self.skip > 0 {
+                self.skip -= 1;
+                continue;
+            }
+            self.emitted += 1;
+            return Some(sample);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Engine512, Engine1024, MusicalSettings, ProcessingMode, VocalEffectsConfig,
+        engine::automation::{Automation, AutomationEvent, Change},
+    };
+
+    #[test]
+    fn test_output_lines_up_with_input() {
+        // Dry output passes the impulse through, so it has to stay where it was
+        let config = VocalEffectsConfig::builder().wet_dry(0.0).build().unwrap();
+        let mut engine = Engine1024::new(config, MusicalSettings::default());
+        for length in [0, 100, 256, 1000, 4096] {
+            engine.reset();
+            let input = (0..length).map(|n| if n == length / 2 { 1.0 } else { 0.0 });
+            let output: std::vec::Vec<f32> = engine.process_iter(input).collect();
+            assert_eq!(output.len(), length);
+            if length > 0 {
+                assert_eq!(output[length / 2], 1.0, "{length}");
+                assert_eq!(output.iter().filter(|&&sample| sample != 0.0).count(), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_matches_process_hop() {
+        let settings = MusicalSettings {
+            mode: ProcessingMode::Dry,
+            pitch_shift_semitones: 3.0,
+            ..Default::default()
+        };
+        let signal = |n: usize| 0.4 * libm::sinf(n as f32 * 0.05);
+        let mut engine = Engine512::new(VocalEffectsConfig::default(), settings);
+        let output: std::vec::Vec<f32> = engine.process_iter((0..2048).map(signal)).collect();
+
+        let mut engine = Engine512::new(VocalEffectsConfig::default(), settings);
+        let latency = engine.latency();
+        let mut reference = std::vec::Vec::new();
+        let mut block = [0.0f32; 128];
+        for hop in 0..(2048 + latency) / 128 {
+            let input: [f32; 128] = core::array::from_fn(|i| {
+                let n = hop * 128 + i;
+                if n < 2048 { signal(n) } else { 0.0 }
+            });
+            engine.process_hop(&input, None, &mut block).unwrap();
+            reference.extend_from_slice(&block);
+        }
+        assert_eq!(output, reference[latency..latency + 2048]);
+    }
+
+    #[test]
+    fn test_automated_change_is_centred_on_its_sample() {
+        // Switching to vocode mode with a silent carrier mutes the sine at the event
+        let config = VocalEffectsConfig::builder().mode_crossfade_hops(0).build().unwrap();
+        let settings = MusicalSettings { mode: ProcessingMode::Dry, ..Default::default() };
+        let mut engine = Engine1024::new(config, settings);
+        let mut automation = Automation::<1>::new();
+        let at = 8000;
+        automation
+            .schedule(AutomationEvent::new(at, Change::Mode(ProcessingMode::Vocode)))
+            .unwrap();
+
+        let input = (0..16000).map(|n| 0.5 * libm::sinf(n as f32 * 0.1));
+        let output: std::vec::Vec<f32> =
+            engine.process_iter_automated(input, &mut automation).collect();
+        let peak = |from: usize| {
+            output[from - 32..from + 32].iter().fold(0.0f32, |peak, s| peak.max(s.abs()))
+        };
+        let at = at as usize;
+        let steady = peak(at - 1000);
+        assert!(peak(at + 600) < 0.01 * steady, "{}", peak(at + 600));
+
+        // The output falls through half its level within half a hop of the event
+        let half = (at - 1000..at + 1000).find(|&n| peak(n) < 0.5 * steady).unwrap();
+        assert!(half.abs_diff(at) < 128, "{half}");
+        assert_eq!(engine.settings().mode, ProcessingMode::Vocode);
+    }
+}
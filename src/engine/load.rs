@@ -0,0 +1,110 @@
+//! CPU load estimate of the engine.
+//!
+//! Every hop is timed and compared with the time the hop lasts at the sample
+//! rate, its real-time budget. The load is smoothed with an exponentially
+//! weighted moving average, so a UI can warn before the audio drops out rather
+//! than flicker with every slow hop.
+//!
+//! With the `profiling` feature the hop is timed with
+//! [`profiling::cycle_count`](crate::profiling::cycle_count), the DWT cycle
+//! counter on Cortex-M, whose clock the firmware has to give to
+//! [`Engine::set_cpu_clock_hz`](super::Engine::set_cpu_clock_hz). On `std`
+//! hosts it is timed in nanoseconds.
+
+use libm::expf;
+
+/// Time constant of the load average in seconds
+const SMOOTHING_SECONDS: f32 = 0.3;
+
+/// Ticks per second of [`now`], zero where it counts CPU cycles of an unknown clock
+const DEFAULT_TICKS_PER_SECOND: f32 = if cfg!(all(
+    feature = "std",
+    not(all(feature = "profiling", feature = "cortex-m", target_arch = "arm"))
+)) {
+    1.0e9
+} else {
+    0.0
+};
+
+/// Current time in ticks of the load clock
+#[inline(always)]
+pub(super) fn now() -> u32 {
+    #[cfg(feature = "profiling")]
+    {
+        crate::profiling::cycle_count()
+    }
+    #[cfg(not(feature = "profiling"))]
+    {
+        static EPOCH: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+        EPOCH.get_or_init(std::time::Instant::now).elapsed().as_nanos() as u32
+    }
+}
+
+/// Smoothed processing time per hop relative to its real-time budget
+pub(super) struct LoadMeter {
+    ticks_per_second: f32,
+    /// Smoothed load in percent of the budget
+    load: f32,
+}
+
+impl LoadMeter {
+    pub(super) const fn new() -> Self {
+        Self { ticks_per_second: DEFAULT_TICKS_PER_SECOND, load: 0.0 }
+    }
+
+    pub(super) fn set_ticks_per_second(&mut self, ticks_per_second: f32) {
+        self.ticks_per_second = ticks_per_second;
+    }
+
+    /// Smoothed load in percent of the real-time budget
+    pub(super) fn load(&self) -> f32 {
+        self.load
+    }
+
+    /// Clears the average, keeping the clock rate
+    pub(super) fn reset(&mut self) {
+        self.load = 0.0;
+    }
+
+    /// Adds a hop of `hop_seconds` that started at tick `start`
+    pub(super) fn record(&mut self, start: u32, hop_seconds: f32) {
+        self.record_ticks(now().wrapping_sub(start), hop_seconds);
+    }
+
+    /// Adds a hop of `hop_seconds` that took `ticks` to process
+    fn record_ticks(&mut self, ticks: u32, hop_seconds: f32) {
+        if self.ticks_per_second <= 0.0 || hop_seconds <= 0.0 {
+            return;
+        }
+        let load = 100.0 * ticks as f32 / (self.ticks_per_second * hop_seconds);
+        let weight = 1.0 - expf(-hop_seconds / SMOOTHING_SECONDS);
+        self.load += (load - self.load) * weight;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_settles_on_budget_fraction() {
+        let mut meter = LoadMeter::new();
+        meter.set_ticks_per_second(1.0e6);
+
+        // 2.5 ms of work in every 10 ms hop settles at a quarter of the budget
+        for _ in 0..200 {
+            meter.record_ticks(2500, 0.01);
+        }
+        assert!((meter.load() - 25.0).abs() < 0.1, "{}", meter.load());
+
+        // A single slow hop only nudges the average
+        meter.record_ticks(20_000, 0.01);
+        assert!(meter.load() > 25.0 && meter.load() < 35.0, "{}", meter.load());
+
+        meter.reset();
+        assert_eq!(meter.load(), 0.0);
+        meter.set_ticks_per_second(0.0);
+        meter.record_ticks(2500, 0.01);
+        assert_eq!(meter.load(), 0.0);
+    }
+}
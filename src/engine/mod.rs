@@ -0,0 +1,1777 @@
+//! Streaming vocal effects engine.
+//!
+//! The `process_vocal_effects_*` functions work on a single analysis frame and leave
+//! the frame history, overlap-add and phase bookkeeping to the caller. [`Engine`]
+//! owns all of that state so a continuous stream can be processed one hop at a time.
+
+pub mod adapter;
+pub mod automation;
+mod clock;
+pub mod control;
+pub mod dual;
+#[cfg(feature = "embassy")]
+pub mod embassy;
+#[cfg(feature = "std")]
+pub mod iter;
+#[cfg(any(feature = "std", feature = "profiling"))]
+mod load;
+mod morph;
+pub mod observer;
+#[cfg(feature = "std")]
+pub mod oversampled;
+mod resample_shift;
+pub mod self_test;
+pub mod shared;
+mod slots;
+mod stereo;
+
+pub use adapter::BlockAdapter;
+pub use automation::{Automation, AutomationEvent};
+pub use control::ControlEvent;
+pub use dual::DualEngine;
+#[cfg(feature = "embassy")]
+pub use embassy::AsyncEngine;
+pub use observer::{FrameObserver, FrameSnapshot, FrameView};
+#[cfg(feature = "std")]
+pub use oversampled::OversampledEngine;
+pub use self_test::SelfTestResult;
+pub use shared::{SharedControls, SharedEngine};
+pub use slots::SETTINGS_SLOTS;
+
+#[cfg(feature = "alloc")]
+use crate::workspace::HeapWorkspace;
+use crate::workspace::Workspace;
+use crate::{
+    IdleOutput, MusicalSettings, PitchShiftAlgorithm, ProcessingMode, VocalEffectsConfig,
+    VocalEffectsError,
+    dsp::{
+        BinTables, DynFft, Fft128, Fft256, Fft512, Fft1024, Fft2048, Fft4096,
+        emphasis::{DeEmphasis, pre_emphasize},
+        hum::HumFilter,
+    },
+    effects::{
+        Bitcrusher, BreathMixer, Chorus, Delay, Ducker, Effect, Exciter, Reverb, SampleRateReducer,
+        Saturator, delay::DelayStorage,
+    },
+    meter::Meter,
+    state::ProcessingState,
+    stereo::{MonoReading, Placement, mono_check, pan_gains},
+    tempo::{DEFAULT_BPM, TapTempo, TempoClock},
+    vocal_effects::process_frame_in_place,
+};
+use resample_shift::ResampleShift;
+
+/// Engine for 128-point frames, best paired with [`PitchDetector::Autocorrelation`](crate::PitchDetector::Autocorrelation)
+pub type Engine128 = Engine<128, 64, Fft128>;
+/// Engine for 256-point frames, best paired with [`PitchDetector::Autocorrelation`](crate::PitchDetector::Autocorrelation)
+pub type Engine256 = Engine<256, 128, Fft256>;
+/// Engine for 512-point frames
+pub type Engine512 = Engine<512, 256, Fft512>;
+/// Engine for 1024-point frames
+pub type Engine1024 = Engine<1024, 512, Fft1024>;
+/// Engine for 2048-point frames
+pub type Engine2048 = Engine<2048, 1024, Fft2048>;
+/// Engine for 4096-point frames
+pub type Engine4096 = Engine<4096, 2048, Fft4096>;
+/// Engine for 8192-point frames, for offline processing of low voices
+#[cfg(feature = "fft-8192")]
+pub type Engine8192 = Engine<8192, 4096, crate::dsp::Fft8192>;
+
+/// Outgoing mode kept alive while a mode change is crossfaded
+struct ModeCrossfade<const N: usize> {
+    mode: ProcessingMode,
+    /// Complete outgoing settings when switching settings slots; otherwise the
+    /// outgoing side runs the incoming settings in `mode`
+    settings: Option<MusicalSettings>,
+    state: ProcessingState<N>,
+    /// Gain the incoming side starts from
+    start_gain: f32,
+    hops_done: usize,
+    total_hops: usize,
+}
+
+impl<const N: usize> ModeCrossfade<N> {
+    /// Gain of the incoming side on the last hop processed
+    fn gain(&self) -> f32 {
+        let progress = self.hops_done as f32 / self.total_hops.saturating_add(1) as f32;
+        self.start_gain + (1.0 - self.start_gain) * progress
+    }
+}
+
+/// Streaming vocal effects processor.
+///
+/// Each call to [`Engine::process_hop`] consumes one hop of input samples and
+/// produces one hop of output samples. Internally the engine keeps the last `N`
+/// input samples as the analysis frame, runs the configured processing mode on it
+/// and overlap-adds the result into its output accumulator.
+///
+/// # Generic Parameters
+///
+/// * `N` - Frame (FFT) size
+/// * `HALF_N` - Half the frame size
+/// * `F` - FFT backend for the frame size
+///
+/// # Example
+///
+/// ```rust
+/// use synthphone_e_vocal_dsp::{Engine1024, MusicalSettings, VocalEffectsConfig};
+///
+/// let mut engine = Engine1024::new(VocalEffectsConfig::default(), MusicalSettings::default());
+/// let hop = engine.hop_size();
+///
+/// let input = [0.0f32; 256];
+/// let mut output = [0.0f32; 256];
+/// assert_eq!(hop, 256);
+/// engine.process_hop(&input, None, &mut output).unwrap();
+/// ```
+pub struct Engine<const N: usize, const HALF_N: usize, F>
+where
+    F: DynFft<N, HALF_N>,
+{
+    config: VocalEffectsConfig,
+    settings: MusicalSettings,
+    state: ProcessingState<N>,
+    input_frame: [f32; N],
+    carrier_frame: [f32; N],
+    output_accumulator: [f32; N],
+    crossfade: Option<ModeCrossfade<N>>,
+    exciter: Option<Exciter>,
+    saturator: Option<Saturator>,
+    sample_rate_reducer: Option<SampleRateReducer>,
+    bitcrusher: Option<Bitcrusher>,
+    chorus: Option<Chorus>,
+    delay: Option<Delay>,
+    reverb: Option<Reverb>,
+    hum_filter: Option<HumFilter>,
+    ducker: Option<Ducker>,
+    breath: Option<BreathMixer>,
+    de_emphasis: Option<DeEmphasis>,
+    resample_shift: Option<ResampleShift>,
+    input_meter: Meter,
+    fft: F,
+    hops_processed: u64,
+    /// Consecutive input hops below the voice activity gate threshold
+    quiet_hops: usize,
+    /// Stored settings slots, the active one as it was when last left
+    slots: [MusicalSettings; SETTINGS_SLOTS],
+    active_slot: usize,
+    /// Slot [`Engine::toggle`] switches to
+    previous_slot: usize,
+    bypassed: bool,
+    /// Whether the last hop ended bypassed, where the next hop's fade starts
+    bypass_faded: bool,
+    /// Input samples received since the engine was created or reset
+    samples_received: u64,
+    tap_tempo: TapTempo,
+    tempo: TempoClock,
+    placement: Placement,
+    /// Mono compatibility of the last stereo hop
+    mono_reading: MonoReading,
+    #[cfg(any(feature = "std", feature = "profiling"))]
+    load: load::LoadMeter,
+    /// Scratch buffers reused across hops, on the stack without `alloc`
+    ///
+    /// `None` in the second lane of a [`DualEngine`], which borrows the first's.
+    #[cfg(feature = "alloc")]
+    workspace: Option<HeapWorkspace<N, HALF_N>>,
+}
+
+impl<const N: usize, const HALF_N: usize, F> Engine<N, HALF_N, F>
+where
+    F: DynFft<N, HALF_N> + Default,
+{
+    /// Creates a new engine.
+    ///
+    /// The FFT size of `config` is taken from the engine size and the hop size is
+    /// recalculated from the hop ratio.
+    pub fn new(config: VocalEffectsConfig, settings: MusicalSettings) -> Self {
+        Self::with_fft(F::default(), config, settings)
+    }
+}
+
+impl<const N: usize, const HALF_N: usize, F> Engine<N, HALF_N, F>
+where
+    F: DynFft<N, HALF_N>,
+{
+    /// Creates a new engine running on the given FFT backend
+    ///
+    /// Use this for backends that carry state, such as a handle to a hardware FFT
+    /// unit. Otherwise behaves like [`Engine::new`].
+    pub fn with_fft(fft: F, mut config: VocalEffectsConfig, settings: MusicalSettings) -> Self {
+        config.fft_size = N;
+        config.hop_size = config.hop_size_for(N);
+        Self {
+            config,
+            settings,
+            state: ProcessingState {
+                bin_tables: BinTables::with_frame_size(config.hop_size, config.frame_size_for(N))
+                    .with_synthesis_hop(config.synthesis_hop_size_for(N)),
+                ..ProcessingState::new()
+            },
+            input_frame: [0.0; N],
+            carrier_frame: [0.0; N],
+            output_accumulator: [0.0; N],
+            crossfade: None,
+            exciter: config.exciter.map(|settings| Exciter::new(settings, config.sample_rate)),
+            saturator: config
+                .saturation
+                .map(|settings| Saturator::new(settings, config.sample_rate)),
+            sample_rate_reducer: config
+                .sample_rate_reduction
+                .and_then(|factor| SampleRateReducer::new(factor).ok()),
+            bitcrusher: config.bitcrush.and_then(|bit_depth| Bitcrusher::new(bit_depth).ok()),
+            chorus: config.chorus.map(|settings| Chorus::new(settings, config.sample_rate)),
+            #[cfg(feature = "alloc")]
+            delay: config.delay.map(|settings| Delay::new(settings, config.sample_rate)),
+            #[cfg(not(feature = "alloc"))]
+            delay: None,
+            reverb: config.reverb.map(|settings| Reverb::new(settings, config.sample_rate)),
+            hum_filter: config.hum_filter.map(|mains| HumFilter::new(mains, config.sample_rate)),
+            ducker: config.sidechain_duck.map(|duck| Ducker::new(duck, config.sample_rate)),
+            breath: config
+                .breath_retention
+                .map(|breath| BreathMixer::new(breath, config.sample_rate)),
+            de_emphasis: config.pre_emphasis.map(DeEmphasis::new),
+            resample_shift: match config.pitch_shift_algorithm {
+                PitchShiftAlgorithm::BinRemap => None,
+                PitchShiftAlgorithm::ResampleStretch => ResampleShift::new(),
+            },
+            input_meter: Meter::new(),
+            fft,
+            hops_processed: 0,
+            quiet_hops: 0,
+            slots: [settings; SETTINGS_SLOTS],
+            active_slot: 0,
+            previous_slot: 1,
+            bypassed: false,
+            bypass_faded: false,
+            samples_received: 0,
+            tap_tempo: TapTempo::new(),
+            tempo: TempoClock::new(DEFAULT_BPM),
+            placement: Placement::CENTRE,
+            mono_reading: MonoReading::MONO,
+            #[cfg(any(feature = "std", feature = "profiling"))]
+            load: load::LoadMeter::new(),
+            #[cfg(feature = "alloc")]
+            workspace: Some(HeapWorkspace::new()),
+        }
+    }
+
+    /// Returns the engine configuration
+    pub fn config(&self) -> &VocalEffectsConfig {
+        &self.config
+    }
+
+    /// Returns the current musical settings
+    pub fn settings(&self) -> &MusicalSettings {
+        &self.settings
+    }
+
+    /// Returns the phase vocoder state of the active mode
+    pub fn state(&self) -> &ProcessingState<N> {
+        &self.state
+    }
+
+    /// Number of samples consumed and produced by each [`Engine::process_hop`] call
+    ///
+    /// With a separate [`synthesis_hop_ratio`](VocalEffectsConfig::synthesis_hop_ratio)
+    /// this is the input hop, and [`Engine::synthesis_hop_size`] the output hop.
+    pub fn hop_size(&self) -> usize {
+        self.config.hop_size
+    }
+
+    /// Number of samples produced by each [`Engine::process_hop`] call
+    pub fn synthesis_hop_size(&self) -> usize {
+        self.config.synthesis_hop_size_for(N)
+    }
+
+    /// Delay in samples between an input sample and its processed output.
+    ///
+    /// Hosts can use this for plugin delay compensation. With
+    /// [`PitchShiftAlgorithm::ResampleStretch`] it changes with the pitch shift.
+    pub fn latency(&self) -> usize {
+        let hop = self.hop_size();
+        match &self.resample_shift {
+            Some(shift) => {
+                let frame_size = self.config.frame_size_for(N);
+                let (synthesis_hop, _) = resample_shift::plan(hop, frame_size, &self.settings);
+                if synthesis_hop == hop {
+                    self.config.latency_samples()
+                } else {
+                    shift.latency(hop, synthesis_hop, frame_size)
+                }
+            }
+            None => self.config.latency_samples(),
+        }
+    }
+
+    /// Levels of the last input hop and the input clip counter
+    ///
+    /// The input is metered as it arrives, before hum removal.
+    pub fn input_meter(&self) -> &Meter {
+        &self.input_meter
+    }
+
+    /// Clears the input clip counter, such as when the user acknowledges a clip light
+    pub fn clear_input_clips(&mut self) {
+        self.input_meter.clear_clips();
+    }
+
+    /// Gain the sidechain ducking gave the end of the last carrier hop, in dB
+    ///
+    /// 0.0 while the voice is below the threshold or without
+    /// [`VocalEffectsConfig::sidechain_duck`].
+    pub fn carrier_duck_db(&self) -> f32 {
+        self.ducker.as_ref().map_or(0.0, Ducker::gain_db)
+    }
+
+    /// Updates the musical settings.
+    ///
+    /// A change of [`ProcessingMode`] is crossfaded over
+    /// [`VocalEffectsConfig::mode_crossfade_hops`] hops.
+    pub fn set_settings(&mut self, settings: MusicalSettings) {
+        if settings.mode != self.settings.mode {
+            self.begin_crossfade(self.settings.mode, None, self.config.mode_crossfade_hops);
+        }
+        self.settings = settings;
+    }
+
+    /// Switches the processing mode, crossfading from the current one
+    pub fn set_mode(&mut self, mode: ProcessingMode) {
+        self.set_settings(MusicalSettings { mode, ..self.settings });
+    }
+
+    /// Smoothed processing time per hop, in percent of the time the hop lasts
+    ///
+    /// Around 100% the engine barely keeps up with real time. The average follows
+    /// the load over about 300 ms. On Cortex-M with the `profiling` feature it
+    /// stays at zero until [`Engine::set_cpu_clock_hz`] gives the cycle counter
+    /// its clock.
+    #[cfg(any(feature = "std", feature = "profiling"))]
+    pub fn load(&self) -> f32 {
+        self.load.load()
+    }
+
+    /// Sets the clock of the cycle counter that times hops for [`Engine::load`]
+    ///
+    /// Only needed where hops are timed in CPU cycles, on Cortex-M with the
+    /// `profiling` feature; `std` hosts time them in nanoseconds.
+    #[cfg(any(feature = "std", feature = "profiling"))]
+    pub fn set_cpu_clock_hz(&mut self, hz: u32) {
+        self.load.set_ticks_per_second(hz as f32);
+    }
+
+    /// Gives the delay line its storage, whose length limits the delay time
+    ///
+    /// Without `alloc` the configured delay only runs once it has a buffer, such
+    /// as a `static` array placed in spare RAM. Does nothing without a delay in
+    /// the configuration.
+    pub fn set_delay_buffer(&mut self, buffer: DelayStorage) {
+        if let Some(settings) = self.config.delay {
+            self.delay = Some(Delay::with_buffer(settings, self.config.sample_rate, buffer));
+        }
+    }
+
+    /// Returns `true` while the voice activity gate has stopped processing
+    pub fn is_idle(&self) -> bool {
+        self.idle_after().is_some_and(|hold| self.quiet_hops >= hold)
+    }
+
+    /// Silent hops after which the voice activity gate stops processing, `None`
+    /// without a gate
+    ///
+    /// The gate also waits for the last sound to leave the analysis frame, so
+    /// no frame that still overlaps it is skipped.
+    fn idle_after(&self) -> Option<usize> {
+        let gate = self.config.voice_activity_gate?;
+        let hop = self.hop_size().max(1);
+        let hop_seconds = hop as f32 / self.config.sample_rate;
+        Some(gate.hold_hops(hop_seconds).max(self.config.frame_size_for(N).div_ceil(hop)))
+    }
+
+    /// Counts the input hop towards the voice activity gate and returns `true`
+    /// if processing stops for it
+    fn gate_voice_activity(&mut self, input: &[f32]) -> bool {
+        let (Some(gate), Some(hold)) = (self.config.voice_activity_gate, self.idle_after()) else {
+            return false;
+        };
+        if !gate.is_silent(input) {
+            // Waking up starts from scratch rather than from the phases of the silence
+            if self.quiet_hops >= hold {
+                self.state.reset();
+            }
+            self.quiet_hops = 0;
+            return false;
+        }
+        self.quiet_hops = self.quiet_hops.saturating_add(1);
+        if self.quiet_hops >= hold {
+            self.crossfade = None;
+            return true;
+        }
+        false
+    }
+
+    /// Returns `true` while a mode change is being crossfaded
+    pub fn is_crossfading(&self) -> bool {
+        self.crossfade.is_some()
+    }
+
+    /// Returns `true` while [`Engine::process_hop`] needs a carrier
+    ///
+    /// That is while the mode, or the mode being crossfaded from, is vocode or
+    /// talk box.
+    pub fn needs_carrier(&self) -> bool {
+        self.settings.mode.carrier_mode().is_some()
+            || self.crossfade.as_ref().is_some_and(|fade| fade.mode.carrier_mode().is_some())
+    }
+
+    /// Clears all processing state: phases, pitch shift ratio, frame history and
+    /// the overlap-add accumulator. The next hops start from silence.
+    ///
+    /// Controls are kept, including the formant snapshot.
+    pub fn reset(&mut self) {
+        let snapshot = self.state.formant_envelope.target().copied();
+        self.state.reset();
+        self.state.formant_envelope.set_target(snapshot);
+        self.input_frame.fill(0.0);
+        self.carrier_frame.fill(0.0);
+        self.output_accumulator.fill(0.0);
+        self.crossfade = None;
+        if let Some(exciter) = &mut self.exciter {
+            exciter.reset();
+        }
+        if let Some(saturator) = &mut self.saturator {
+            saturator.reset();
+        }
+        if let Some(reducer) = &mut self.sample_rate_reducer {
+            reducer.reset();
+        }
+        if let Some(chorus) = &mut self.chorus {
+            chorus.reset();
+        }
+        if let Some(delay) = &mut self.delay {
+            delay.reset();
+        }
+        if let Some(reverb) = &mut self.reverb {
+            reverb.reset();
+        }
+        if let Some(hum_filter) = &mut self.hum_filter {
+            hum_filter.reset();
+        }
+        if let Some(ducker) = &mut self.ducker {
+            ducker.reset();
+        }
+        if let Some(breath) = &mut self.breath {
+            breath.reset();
+        }
+        if let Some(de_emphasis) = &mut self.de_emphasis {
+            de_emphasis.reset();
+        }
+        if let Some(shift) = &mut self.resample_shift {
+            shift.reset();
+        }
+        self.input_meter.reset();
+        self.bypass_faded = self.bypassed;
+        self.hops_processed = 0;
+        self.quiet_hops = 0;
+        self.samples_received = 0;
+        self.tap_tempo.restart();
+        self.tempo.rewind();
+        self.mono_reading = MonoReading::MONO;
+        #[cfg(any(feature = "std", feature = "profiling"))]
+        self.load.reset();
+    }
+
+    /// Clears accumulated phase drift without interrupting the output.
+    ///
+    /// The synthesis phases are re-anchored to the analysis phases while the
+    /// un-anchored state is crossfaded out, the same way a mode change is, so this
+    /// is safe to call from host automation during playback. The fade lasts at
+    /// least one hop, even with [`VocalEffectsConfig::mode_crossfade_hops`] at 0.
+    pub fn soft_reset(&mut self) {
+        self.begin_crossfade(self.settings.mode, None, self.config.mode_crossfade_hops.max(1));
+        self.state.reanchor_phases();
+    }
+
+    /// Fades the current output out over `total_hops` hops
+    ///
+    /// A crossfade that is still running is not restarted from silence. Its louder
+    /// side keeps fading out from the gain it has reached, and the incoming mode
+    /// takes over the gain of the quieter side, which is dropped. Keeping both would
+    /// cost a third [`ProcessingState`].
+    fn begin_crossfade(
+        &mut self,
+        outgoing: ProcessingMode,
+        settings: Option<MusicalSettings>,
+        total_hops: usize,
+    ) {
+        if total_hops == 0 {
+            self.crossfade = None;
+            return;
+        }
+        // The incoming mode continues from the current phases, the outgoing mode
+        // keeps running on a copy of them until it has faded out
+        let fresh = ModeCrossfade {
+            mode: outgoing,
+            settings,
+            state: self.state,
+            start_gain: 0.0,
+            hops_done: 0,
+            total_hops,
+        };
+        self.crossfade = Some(match self.crossfade.take() {
+            Some(running) => {
+                let gain = running.gain();
+                let kept = if gain < 0.5 { running } else { fresh };
+                ModeCrossfade { start_gain: gain.min(1.0 - gain), hops_done: 0, total_hops, ..kept }
+            }
+            None => fresh,
+        });
+    }
+
+    /// Processes one hop of audio.
+    ///
+    /// # Parameters
+    ///
+    /// * `input` - One hop of input samples
+    /// * `carrier` - One hop of carrier samples for vocode and talk-box mode, or of
+    ///   synth samples for dry mode, where silence is used when `None`
+    /// * `output` - Receives one synthesis hop of processed samples, the same length
+    ///   as `input` unless the synthesis hop is set separately
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VocalEffectsError::BufferSizeMismatch`] if any buffer is not exactly
+    /// one hop long, [`VocalEffectsError::MissingCarrier`] if `carrier` is `None`
+    /// while the engine [needs one](Engine::needs_carrier), or
+    /// [`VocalEffectsError::InvalidConfiguration`] if a hop ratio gives an empty hop
+    /// or one longer than the frame. Nothing is processed on error.
+    pub fn process_hop(
+        &mut self,
+        input: &[f32],
+        carrier: Option<&[f32]>,
+        output: &mut [f32],
+    ) -> Result<(), VocalEffectsError> {
+        self.process_hop_observed(input, carrier, output, &mut |_: &FrameView<'_>| {})
+    }
+
+    /// Processes one hop of audio and reports the frame analysis to `observer`.
+    ///
+    /// The observer is called after the frame is analysed, before the hop is
+    /// written to `output`, so spectrum and tuner displays can reuse the engine's
+    /// analysis instead of running their own. Otherwise behaves like
+    /// [`Engine::process_hop`].
+    ///
+    /// # Errors
+    ///
+    /// See [`Engine::process_hop`]. The observer is not called on error, nor for
+    /// hops the voice activity gate skips.
+    pub fn process_hop_observed<O>(
+        &mut self,
+        input: &[f32],
+        carrier: Option<&[f32]>,
+        output: &mut [f32],
+        observer: &mut O,
+    ) -> Result<(), VocalEffectsError>
+    where
+        O: FrameObserver + ?Sized,
+    {
+        self.process_hop_placed(input, carrier, output, None, observer, None)
+    }
+
+    /// Processes one hop of audio and also returns the dry input aligned with it
+    ///
+    /// `dry` receives one input hop delayed by the frame size less the hop, the
+    /// dry signal [`VocalEffectsConfig::wet_dry`] mixes in, untouched by the
+    /// processing and the mix. Like that signal it has already passed the
+    /// [`VocalEffectsConfig::hum_filter`], if one is set. Recording `output` and
+    /// `dry` side by side lets a host re-blend them later. They line up exactly while the synthesis hop
+    /// equals the input hop, see [`Engine::latency`]. Otherwise behaves like
+    /// [`Engine::process_hop`].
+    ///
+    /// # Errors
+    ///
+    /// See [`Engine::process_hop`], with `dry` checked like `input`. Nothing is
+    /// processed if `dry` has the wrong length.
+    pub fn process_hop_with_dry(
+        &mut self,
+        input: &[f32],
+        carrier: Option<&[f32]>,
+        output: &mut [f32],
+        dry: &mut [f32],
+    ) -> Result<(), VocalEffectsError> {
+        let hop = self.hop_size();
+        if dry.len() != hop {
+            return Err(VocalEffectsError::BufferSizeMismatch);
+        }
+        self.process_hop(input, carrier, output)?;
+        let frame_size = self.config.frame_size_for(N);
+        dry.copy_from_slice(&self.input_frame[N - frame_size..][..hop]);
+        Ok(())
+    }
+
+    /// Processes one hop, into `output` alone or placed between `output` as the
+    /// left channel and `right`
+    ///
+    /// Frames are transformed with `scratch` if given, otherwise with the
+    /// engine's own FFT backend and workspace.
+    fn process_hop_placed<O>(
+        &mut self,
+        input: &[f32],
+        carrier: Option<&[f32]>,
+        output: &mut [f32],
+        mut right: Option<&mut [f32]>,
+        observer: &mut O,
+        scratch: Option<(&mut F, &mut Workspace<N, HALF_N>)>,
+    ) -> Result<(), VocalEffectsError>
+    where
+        O: FrameObserver + ?Sized,
+    {
+        let hop = self.hop_size();
+        let output_hop = self.synthesis_hop_size();
+        if hop == 0 || hop > N || output_hop == 0 || output_hop > N {
+            return Err(VocalEffectsError::InvalidConfiguration);
+        }
+        if input.len() != hop
+            || output.len() != output_hop
+            || right.as_ref().is_some_and(|right| right.len() != output_hop)
+            || carrier.is_some_and(|c| c.len() != hop)
+        {
+            return Err(VocalEffectsError::BufferSizeMismatch);
+        }
+        if carrier.is_none() && self.needs_carrier() {
+            return Err(VocalEffectsError::MissingCarrier);
+        }
+        #[cfg(any(feature = "std", feature = "profiling"))]
+        let start = load::now();
+
+        // Resample-and-stretch shifting resynthesises at its own hop
+        let frame_size = self.config.frame_size_for(N);
+        let (synthesis_hop, settings) = match self.resample_shift {
+            Some(_) => resample_shift::plan(hop, frame_size, &self.settings),
+            None => (output_hop, self.settings),
+        };
+        let config = if synthesis_hop == output_hop {
+            self.config
+        } else {
+            VocalEffectsConfig {
+                synthesis_hop_ratio: Some(synthesis_hop as f32 / frame_size as f32),
+                ..self.config
+            }
+        };
+
+        self.input_meter.measure(input);
+        self.samples_received += hop as u64;
+        self.tempo.advance(hop as f64 / f64::from(self.config.sample_rate));
+        let idle = self.gate_voice_activity(input);
+
+        // Slide the frame histories along by one hop
+        self.input_frame.copy_within(hop.., 0);
+        self.input_frame[N - hop..].copy_from_slice(input);
+        if let Some(hum_filter) = &mut self.hum_filter {
+            hum_filter.process(&mut self.input_frame[N - hop..]);
+        }
+        self.carrier_frame.copy_within(hop.., 0);
+        match carrier {
+            Some(carrier) => self.carrier_frame[N - hop..].copy_from_slice(carrier),
+            None => self.carrier_frame[N - hop..].fill(0.0),
+        }
+        // The synth blended in dry mode is ducked before it meets the voice
+        if let Some(ducker) = &mut self.ducker {
+            if self.settings.mode.carrier_mode().is_none() {
+                ducker.process(&self.input_frame[N - hop..], &mut self.carrier_frame[N - hop..]);
+            }
+        }
+
+        // An idle hop only plays out what the accumulator already holds
+        if !idle {
+            let mut own_workspace = None;
+            let (fft, workspace) = match scratch {
+                Some(scratch) => scratch,
+                #[cfg(feature = "alloc")]
+                None => match self.workspace.as_deref_mut() {
+                    Some(workspace) => (&mut self.fft, workspace),
+                    None => (&mut self.fft, own_workspace.insert(Workspace::new())),
+                },
+                #[cfg(not(feature = "alloc"))]
+                None => (&mut self.fft, own_workspace.insert(Workspace::new())),
+            };
+
+            let mut processed = Self::analysis_frame(&self.input_frame, &config);
+            let mut carrier_frame = self.carrier_frame;
+            process_frame_in_place(
+                fft,
+                workspace,
+                &mut processed,
+                Some(&mut carrier_frame),
+                &mut self.state,
+                &config,
+                &settings,
+            )?;
+
+            // Report before the outgoing mode of a crossfade reuses the workspace
+            self.hops_processed += 1;
+            observer.on_frame(&FrameView {
+                hop_index: self.hops_processed,
+                mode: self.settings.mode,
+                sample_rate: self.config.sample_rate,
+                magnitudes: &workspace.analysis_magnitudes,
+                analysis: self.state.analysis,
+            });
+
+            if let Some(fade) = &mut self.crossfade {
+                let mut outgoing = Self::analysis_frame(&self.input_frame, &config);
+                let mut carrier_frame = self.carrier_frame;
+                let outgoing_settings =
+                    fade.settings.unwrap_or(MusicalSettings { mode: fade.mode, ..settings });
+                process_frame_in_place(
+                    fft,
+                    workspace,
+                    &mut outgoing,
+                    Some(&mut carrier_frame),
+                    &mut fade.state,
+                    &config,
+                    &outgoing_settings,
+                )?;
+
+                fade.hops_done += 1;
+                let gain = fade.gain();
+                for (sample, outgoing) in processed.iter_mut().zip(outgoing.iter()) {
+                    *sample = *sample * gain + *outgoing * (1.0 - gain);
+                }
+
+                if fade.hops_done >= fade.total_hops {
+                    self.crossfade = None;
+                }
+            }
+
+            // Overlap-add; zero padding leaves only the end of the frame, which is
+            // where its signal is
+            for (acc, sample) in
+                self.output_accumulator.iter_mut().zip(&processed[N - frame_size..])
+            {
+                *acc += *sample;
+            }
+        }
+
+        // Emit the completed hop
+        let stretched = &self.output_accumulator[..synthesis_hop];
+        match &mut self.resample_shift {
+            Some(shift) => shift.process(stretched, output),
+            None => output.copy_from_slice(stretched),
+        }
+        // Carrier modes output the carrier itself, so their output is ducked
+        // against the voice it is aligned with
+        if let Some(ducker) = &mut self.ducker {
+            if self.settings.mode.carrier_mode().is_some() {
+                ducker.process(&self.input_frame[N - frame_size..], output);
+            }
+        }
+        if let Some(de_emphasis) = &mut self.de_emphasis {
+            de_emphasis.process(output);
+        }
+        if let Some(breath) = &mut self.breath {
+            // Only correction detects pitch, and so tells voiced from unvoiced
+            let unvoiced = !idle
+                && self.settings.mode == ProcessingMode::Autotune
+                && self.state.analysis.confidence < self.config.confidence_threshold;
+            breath.process(&self.input_frame[N - frame_size..], output, unvoiced);
+        }
+        if let Some(exciter) = &mut self.exciter {
+            exciter.process(output);
+        }
+        if let Some(saturator) = &mut self.saturator {
+            saturator.process(output);
+        }
+        if let Some(reducer) = &mut self.sample_rate_reducer {
+            reducer.process(output);
+        }
+        if let Some(bitcrusher) = &mut self.bitcrusher {
+            bitcrusher.process(output);
+        }
+        let (left_gain, right_gain) = pan_gains(self.placement.pan);
+        match right.as_deref_mut() {
+            None => {
+                if let Some(chorus) = &mut self.chorus {
+                    chorus.process(output, &self.tempo);
+                }
+                if let Some(delay) = &mut self.delay {
+                    delay.process(output, self.tempo.bpm());
+                }
+                if let Some(reverb) = &mut self.reverb {
+                    reverb.process(output);
+                }
+            }
+            Some(right) => {
+                // Pan the voice, then spread the time effects around it
+                for (left, right) in output.iter_mut().zip(right.iter_mut()) {
+                    *right = *left * right_gain;
+                    *left *= left_gain;
+                }
+                let width = self.placement.width;
+                if let Some(chorus) = &mut self.chorus {
+                    chorus.process_stereo(output, right, width, &self.tempo);
+                }
+                if let Some(delay) = &mut self.delay {
+                    delay.process_stereo(output, right, width, self.tempo.bpm());
+                }
+                if let Some(reverb) = &mut self.reverb {
+                    reverb.process_stereo(output, right, width);
+                }
+            }
+        }
+        let idle_dry = idle
+            && self
+                .config
+                .voice_activity_gate
+                .is_some_and(|gate| gate.idle_output == IdleOutput::Dry);
+        let mix = if idle_dry {
+            0.0
+        } else {
+            self.config.wet_dry.clamp(0.0, 1.0)
+        };
+        // Switching the bypass fades between processed and dry over the hop
+        let engaged = |bypassed: bool| if bypassed { 0.0 } else { 1.0 };
+        let (from, to) = (engaged(self.bypass_faded), engaged(self.bypassed));
+        self.bypass_faded = self.bypassed;
+        let step = (to - from) / output_hop as f32;
+        let wet = |i: usize| mix * (from + step * (i + 1) as f32);
+        let dry = &self.input_frame[N - frame_size..];
+        match right {
+            Some(right) => {
+                // The dry voice sits where the processed one does
+                let samples = output.iter_mut().zip(right.iter_mut()).zip(dry);
+                for (i, ((left, right), dry)) in samples.enumerate() {
+                    let wet = wet(i);
+                    *left = *left * wet + *dry * left_gain * (1.0 - wet);
+                    *right = *right * wet + *dry * right_gain * (1.0 - wet);
+                }
+                self.mono_reading = mono_check(output, right);
+            }
+            // The oldest hop of the frame is delayed by exactly the latency
+            None if mix < 1.0 || from < 1.0 || to < 1.0 => {
+                for (i, (sample, dry)) in output.iter_mut().zip(dry).enumerate() {
+                    let wet = wet(i);
+                    *sample = *sample * wet + *dry * (1.0 - wet);
+                }
+            }
+            None => {}
+        }
+        self.output_accumulator.copy_within(synthesis_hop.., 0);
+        self.output_accumulator[N - synthesis_hop..].fill(0.0);
+
+        #[cfg(any(feature = "std", feature = "profiling"))]
+        self.load.record(start, hop as f32 / self.config.sample_rate);
+        Ok(())
+    }
+
+    /// Copy of the input frame as the processors see it, pre-emphasised if enabled
+    ///
+    /// The frame history itself stays flat for the dry signal.
+    fn analysis_frame(input_frame: &[f32; N], config: &VocalEffectsConfig) -> [f32; N] {
+        let mut frame = *input_frame;
+        if let Some(coefficient) = config.pre_emphasis {
+            pre_emphasize(&mut frame, coefficient);
+        }
+        frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{OctaveShift, PitchDetector, testsig::tone_level};
+    use core::f32::consts::PI;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    fn sine(n: usize) -> f32 {
+        0.5 * libm::sinf(2.0 * PI * 220.0 * n as f32 / SAMPLE_RATE)
+    }
+
+    #[test]
+    fn test_rejects_wrong_hop_length() {
+        let mut engine = Engine512::new(VocalEffectsConfig::default(), MusicalSettings::default());
+        let mut output = [0.0f32; 128];
+        assert_eq!(
+            engine.process_hop(&[0.0; 64], None, &mut output),
+            Err(VocalEffectsError::BufferSizeMismatch)
+        );
+        assert_eq!(
+            engine.process_hop(&[0.0; 128], Some(&[0.0; 64]), &mut output),
+            Err(VocalEffectsError::BufferSizeMismatch)
+        );
+        assert!(engine.process_hop(&[0.0; 128], None, &mut output).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_out_of_range_hop_ratio() {
+        for hop_ratio in [0.0, f32::NAN, 1.5] {
+            let config = VocalEffectsConfig { hop_ratio, ..Default::default() };
+            let mut engine = Engine512::new(config, MusicalSettings::default());
+            let hop = engine.hop_size();
+            let mut output = [0.0f32; 768];
+            assert_eq!(
+                engine.process_hop(&[0.0; 768][..hop], None, &mut output[..hop]),
+                Err(VocalEffectsError::InvalidConfiguration)
+            );
+        }
+    }
+
+    #[test]
+    fn test_engine_takes_fft_size_from_type() {
+        let engine = Engine2048::new(VocalEffectsConfig::default(), MusicalSettings::default());
+        assert_eq!(engine.config().fft_size, 2048);
+        assert_eq!(engine.hop_size(), 512);
+    }
+
+    #[test]
+    fn test_small_frames_track_pitch_with_autocorrelation() {
+        // 440 Hz sits between bins 2 and 3 of a 256-point frame at 48 kHz
+        let config = VocalEffectsConfig::builder()
+            .fft_size(256)
+            .pitch_detector(PitchDetector::Autocorrelation)
+            .build()
+            .unwrap();
+        let mut engine = Engine256::new(config, MusicalSettings::default());
+        let hop = engine.hop_size();
+        assert_eq!(engine.latency(), 192);
+
+        let mut output = [0.0f32; 64];
+        for block in 0..32 {
+            let input: [f32; 64] = core::array::from_fn(|i| {
+                0.5 * libm::sinf(2.0 * PI * 440.0 * (block * hop + i) as f32 / SAMPLE_RATE)
+            });
+            engine.process_hop(&input, None, &mut output).unwrap();
+            assert!(output.iter().all(|s| s.is_finite()));
+        }
+        let analysis = engine.state().analysis;
+        assert!((analysis.detected_frequency - 440.0).abs() < 2.0, "{analysis:?}");
+        assert_eq!(analysis.target_frequency, 440.0);
+    }
+
+    #[test]
+    fn test_settled_frames_resolve_pitch_between_bins() {
+        // 453 Hz is two thirds of the way from bin 9 to bin 10 of a 1024-point frame
+        let mut engine = Engine1024::new(VocalEffectsConfig::default(), MusicalSettings::default());
+        let hop = engine.hop_size();
+
+        let mut output = [0.0f32; 256];
+        for block in 0..32 {
+            let input: [f32; 256] = core::array::from_fn(|i| {
+                0.5 * libm::sinf(2.0 * PI * 453.0 * (block * hop + i) as f32 / SAMPLE_RATE)
+            });
+            engine.process_hop(&input, None, &mut output).unwrap();
+            // Every settled frame is placed between bins, not only reset frames
+            if block > 4 {
+                let analysis = engine.state().analysis;
+                assert!((analysis.detected_frequency - 453.0).abs() < 1.0, "{analysis:?}");
+            }
+        }
+    }
+
+    #[cfg(feature = "fft-8192")]
+    #[test]
+    fn test_8192_point_frames_resolve_bass_voice() {
+        // A 5.9 Hz bin keeps neighbouring semitones apart even at 55 Hz
+        let config = VocalEffectsConfig::builder()
+            .fft_size(8192)
+            .frequency_range(40.0, 4000.0)
+            .build()
+            .unwrap();
+        let settings = MusicalSettings { formant: crate::Formant::Lower, ..Default::default() };
+        let mut engine = Engine8192::new(config, settings);
+        let hop = engine.hop_size();
+        assert_eq!(hop, 2048);
+
+        let mut input = [0.0f32; 2048];
+        let mut output = [0.0f32; 2048];
+        for block in 0..8 {
+            for (i, sample) in input.iter_mut().enumerate() {
+                let t = (block * hop + i) as f32 / SAMPLE_RATE;
+                *sample = (1..=8)
+                    .map(|h| libm::sinf(2.0 * PI * 55.0 * h as f32 * t) / h as f32)
+                    .sum::<f32>()
+                    * 0.2;
+            }
+            engine.process_hop(&input, None, &mut output).unwrap();
+            assert!(output.iter().all(|s| s.is_finite()));
+        }
+        let analysis = engine.state().analysis;
+        assert!((analysis.detected_frequency - 55.0).abs() < 0.5, "{analysis:?}");
+        assert_eq!(analysis.target_frequency, 55.0);
+    }
+
+    #[test]
+    fn test_formant_mode_reconstructs_stream() {
+        let settings = MusicalSettings { mode: ProcessingMode::Formant, ..Default::default() };
+        let mut engine = Engine1024::new(VocalEffectsConfig::default(), settings);
+        let hop = engine.hop_size();
+        let delay = engine.latency();
+        assert_eq!(delay, 768);
+
+        let mut n = 0;
+        for block in 0..16 {
+            let input: [f32; 256] = core::array::from_fn(|i| sine(n + i));
+            let mut output = [0.0f32; 256];
+            engine.process_hop(&input, None, &mut output).unwrap();
+
+            // Once the overlap-add has filled up, the output is the delayed input
+            if block >= 4 {
+                for (i, sample) in output.iter().enumerate() {
+                    let expected = sine(n + i - delay);
+                    assert!((sample - expected).abs() < 1e-2, "{sample} vs {expected}");
+                }
+            }
+            n += hop;
+        }
+    }
+
+    #[test]
+    fn test_zero_padded_frames_keep_frame_latency() {
+        let config = VocalEffectsConfig::builder().fft_size(4096).frame_size(1024).build().unwrap();
+        let settings = MusicalSettings { mode: ProcessingMode::Formant, ..Default::default() };
+        let mut engine = Engine4096::new(config, settings);
+        let hop = engine.hop_size();
+        let delay = engine.latency();
+        assert_eq!((hop, delay), (256, 768));
+        assert_eq!(engine.state().bin_tables.frame_size(), 1024);
+
+        let mut n = 0;
+        for block in 0..16 {
+            let input: [f32; 256] = core::array::from_fn(|i| sine(n + i));
+            let mut output = [0.0f32; 256];
+            engine.process_hop(&input, None, &mut output).unwrap();
+            if block >= 4 {
+                for (i, sample) in output.iter().enumerate() {
+                    let expected = sine(n + i - delay);
+                    assert!((sample - expected).abs() < 1e-2, "{sample} vs {expected}");
+                }
+            }
+            n += hop;
+        }
+    }
+
+    #[test]
+    fn test_longer_synthesis_hop_stretches_time() {
+        /// Zero crossings per second and RMS of the settled output for a 220 Hz sine
+        fn play(synthesis_hop_ratio: f32) -> (f32, f32) {
+            // Both hops keep four or more frames overlapping so the Hann windows sum flat
+            let config = VocalEffectsConfig::builder()
+                .hop_ratio(0.125)
+                .synthesis_hop_ratio(synthesis_hop_ratio)
+                .build()
+                .unwrap();
+            let settings = MusicalSettings { mode: ProcessingMode::Dry, ..Default::default() };
+            let mut engine = Engine1024::new(config, settings);
+            let (hop, synthesis_hop) = (engine.hop_size(), engine.synthesis_hop_size());
+
+            let mut input = [0.0f32; 128];
+            let mut output = [0.0f32; 256];
+            let mut settled = [0.0f32; 8192];
+            let mut written = 0;
+            for block in 0.. {
+                for (i, sample) in input.iter_mut().enumerate() {
+                    let n = block * hop + i;
+                    *sample = 0.5 * libm::sinf(2.0 * PI * 220.0 * n as f32 / SAMPLE_RATE);
+                }
+                engine.process_hop(&input, None, &mut output[..synthesis_hop]).unwrap();
+                if block >= 32 {
+                    let take = synthesis_hop.min(settled.len() - written);
+                    settled[written..][..take].copy_from_slice(&output[..take]);
+                    written += take;
+                    if written == settled.len() {
+                        break;
+                    }
+                }
+            }
+            let crossings = settled.windows(2).filter(|w| w[0] < 0.0 && w[1] >= 0.0).count();
+            let rms = libm::sqrtf(settled.iter().map(|s| s * s).sum::<f32>() / 8192.0);
+            (crossings as f32 * SAMPLE_RATE / 8192.0, rms)
+        }
+
+        let config = VocalEffectsConfig::builder().synthesis_hop_ratio(0.5).build().unwrap();
+        let mut engine = Engine1024::new(config, MusicalSettings::default());
+        assert_eq!((engine.hop_size(), engine.synthesis_hop_size()), (256, 512));
+        assert_eq!(engine.latency(), 512);
+        assert_eq!(
+            engine.process_hop(&[0.0; 256], None, &mut [0.0; 256]),
+            Err(VocalEffectsError::BufferSizeMismatch)
+        );
+
+        // Played at half and two-thirds speed, 220 Hz keeps its pitch and level
+        let (_, reference) = play(0.125);
+        for ratio in [0.1875, 0.25] {
+            let (frequency, rms) = play(ratio);
+            assert!((frequency - 220.0).abs() < 12.0, "{ratio}: {frequency} Hz");
+            assert!((rms - reference).abs() < 0.1 * reference, "{ratio}: {rms} vs {reference}");
+        }
+    }
+
+    #[test]
+    fn test_resample_stretch_shifts_pitch() {
+        /// Zero crossings per second and RMS of the settled output for a 220 Hz sine
+        fn play(algorithm: PitchShiftAlgorithm, semitones: f32) -> (f32, f32, usize) {
+            let config = VocalEffectsConfig::builder()
+                .hop_ratio(0.125)
+                .pitch_shift_algorithm(algorithm)
+                .build()
+                .unwrap();
+            let settings = MusicalSettings {
+                mode: ProcessingMode::Dry,
+                pitch_shift_semitones: semitones,
+                ..Default::default()
+            };
+            let mut engine = Engine1024::new(config, settings);
+            let mut input = [0.0f32; 128];
+            let mut settled = [0.0f32; 8192];
+            for block in 0..96 {
+                for (i, sample) in input.iter_mut().enumerate() {
+                    *sample = sine(block * 128 + i);
+                }
+                let mut output = [0.0f32; 128];
+                engine.process_hop(&input, None, &mut output).unwrap();
+                if block >= 32 {
+                    settled[(block - 32) * 128..][..128].copy_from_slice(&output);
+                }
+            }
+            let crossings = settled.windows(2).filter(|w| w[0] < 0.0 && w[1] >= 0.0).count();
+            let rms = libm::sqrtf(settled.iter().map(|s| s * s).sum::<f32>() / 8192.0);
+            (crossings as f32 * SAMPLE_RATE / 8192.0, rms, engine.latency())
+        }
+
+        let (_, reference, unshifted_latency) = play(PitchShiftAlgorithm::BinRemap, 0.0);
+        assert_eq!(play(PitchShiftAlgorithm::ResampleStretch, 0.0).2, unshifted_latency);
+        for semitones in [7.0, -12.0] {
+            let (frequency, rms, latency) = play(PitchShiftAlgorithm::ResampleStretch, semitones);
+            let expected = 220.0 * crate::math::semitones_to_ratio(semitones);
+            assert!((frequency - expected).abs() < 0.03 * expected, "{semitones}: {frequency} Hz");
+            assert!((rms - reference).abs() < 0.2 * reference, "{semitones}: {rms} vs {reference}");
+            assert_ne!(latency, unshifted_latency);
+        }
+    }
+
+    #[test]
+    fn test_wet_dry_mixes_latency_aligned_input() {
+        let settings = MusicalSettings {
+            mode: ProcessingMode::Dry,
+            octave_shift: OctaveShift::Up1,
+            ..Default::default()
+        };
+        let config = VocalEffectsConfig::builder().wet_dry(0.0).build().unwrap();
+        let mut engine = Engine1024::new(config, settings);
+        let hop = engine.hop_size();
+        let delay = engine.latency();
+
+        let mut n = 0;
+        for _ in 0..8 {
+            let input: [f32; 256] = core::array::from_fn(|i| sine(n + i));
+            let mut output = [0.0f32; 256];
+            engine.process_hop(&input, None, &mut output).unwrap();
+
+            for (i, sample) in output.iter().enumerate() {
+                let expected = if n + i >= delay {
+                    sine(n + i - delay)
+                } else {
+                    0.0
+                };
+                assert!((sample - expected).abs() < 1e-6, "{sample} vs {expected}");
+            }
+            n += hop;
+        }
+    }
+
+    #[test]
+    fn test_dry_output_is_aligned_with_processed_output() {
+        let settings = MusicalSettings { mode: ProcessingMode::Dry, ..Default::default() };
+        let config = VocalEffectsConfig::builder().wet_dry(0.5).build().unwrap();
+        let mut engine = Engine1024::new(config, settings);
+        let mut reference = Engine1024::new(config, settings);
+        let delay = engine.latency();
+        assert_eq!(
+            engine.process_hop_with_dry(&[0.0; 256], None, &mut [0.0; 256], &mut [0.0; 128]),
+            Err(VocalEffectsError::BufferSizeMismatch)
+        );
+
+        for block in 0..8 {
+            let n = block * 256;
+            let input: [f32; 256] = core::array::from_fn(|i| sine(n + i));
+            let (mut output, mut dry, mut expected) = ([0.0f32; 256], [0.0f32; 256], [0.0f32; 256]);
+            engine.process_hop_with_dry(&input, None, &mut output, &mut dry).unwrap();
+            reference.process_hop(&input, None, &mut expected).unwrap();
+
+            // The processed output is unchanged, the dry one is the delayed input
+            assert_eq!(output, expected);
+            for (i, sample) in dry.iter().enumerate() {
+                let expected = if n + i >= delay {
+                    sine(n + i - delay)
+                } else {
+                    0.0
+                };
+                assert_eq!(*sample, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_dry_output_has_hum_removed() {
+        let config = VocalEffectsConfig::builder()
+            .hum_filter(crate::MainsFrequency::Hz60)
+            .build()
+            .unwrap();
+        let settings = MusicalSettings { mode: ProcessingMode::Dry, ..Default::default() };
+        let mut engine = Engine1024::new(config, settings);
+        let mut recorded = [0.0f32; 24064];
+        let mut output = [0.0f32; 256];
+        for block in 0..282 {
+            let input: [f32; 256] = core::array::from_fn(|i| {
+                let t = (block * 256 + i) as f32 / SAMPLE_RATE;
+                0.5 * libm::sinf(2.0 * PI * 60.0 * t) + 0.3 * libm::sinf(2.0 * PI * 220.0 * t)
+            });
+            let mut dry = [0.0f32; 256];
+            engine.process_hop_with_dry(&input, None, &mut output, &mut dry).unwrap();
+            if block >= 188 {
+                recorded[(block - 188) * 256..][..256].copy_from_slice(&dry);
+            }
+        }
+
+        // The last half second, in whole periods of both tones
+        let settled = &recorded[..24000];
+        let hum = tone_level(settled, 60.0, SAMPLE_RATE);
+        let voice = tone_level(settled, 220.0, SAMPLE_RATE);
+        assert!(hum < 0.01, "{hum}");
+        assert!((voice - 0.3).abs() < 0.03, "{voice}");
+    }
+
+    #[test]
+    fn test_mode_switch_is_crossfaded() {
+        let settings = MusicalSettings { mode: ProcessingMode::Formant, ..Default::default() };
+        let mut engine = Engine1024::new(VocalEffectsConfig::default(), settings);
+        let hop = engine.hop_size();
+
+        let mut n = 0;
+        let mut previous = 0.0f32;
+        let mut max_step = 0.0f32;
+        for block in 0..24 {
+            if block == 8 {
+                engine.set_mode(ProcessingMode::Dry);
+                assert!(engine.is_crossfading());
+            }
+            let input: [f32; 256] = core::array::from_fn(|i| sine(n + i));
+            let mut output = [0.0f32; 256];
+            engine.process_hop(&input, None, &mut output).unwrap();
+
+            if block >= 4 {
+                for &sample in output.iter() {
+                    assert!(sample.is_finite());
+                    max_step = max_step.max((sample - previous).abs());
+                    previous = sample;
+                }
+            } else {
+                previous = output[hop - 1];
+            }
+            n += hop;
+        }
+
+        assert!(!engine.is_crossfading());
+        assert_eq!(engine.settings().mode, ProcessingMode::Dry);
+        // A 220 Hz sine at 0.5 moves at most ~0.015 per sample; allow for the dry mode gain
+        assert!(max_step < 0.05, "max step {max_step}");
+    }
+
+    #[test]
+    fn test_autotune_at_common_sample_rates() {
+        for sample_rate in [32000.0f32, 44100.0, 48000.0, 88200.0, 96000.0] {
+            let config = VocalEffectsConfig { sample_rate, ..Default::default() };
+            let mut engine = Engine2048::new(config, MusicalSettings::default());
+            let hop = engine.hop_size();
+
+            let mut output = [0.0f32; 512];
+            for block in 0..16 {
+                let input: [f32; 512] = core::array::from_fn(|i| {
+                    libm::sinf(2.0 * PI * 430.0 * (block * hop + i) as f32 / sample_rate)
+                });
+                engine.process_hop(&input, None, &mut output).unwrap();
+            }
+
+            // 430 Hz is pulled up to A4 in C major regardless of the sample rate
+            let ratio = engine.state().pitch_shift_ratio();
+            assert!((ratio - 440.0 / 430.0).abs() < 2e-3, "{sample_rate} Hz: ratio {ratio}");
+            assert!(output.iter().all(|s| s.is_finite()));
+        }
+    }
+
+    #[test]
+    #[cfg(not(feature = "dsp-guards"))]
+    fn test_recovers_from_non_finite_input() {
+        let settings = MusicalSettings { mode: ProcessingMode::Dry, ..Default::default() };
+        let mut engine = Engine1024::new(VocalEffectsConfig::default(), settings);
+        let mut output = [0.0f32; 256];
+
+        let mut n = 0;
+        for block in 0..24 {
+            let mut input: [f32; 256] = core::array::from_fn(|i| sine(n + i));
+            if block == 4 {
+                input[10] = f32::NAN;
+                input[20] = f32::INFINITY;
+            }
+            engine.process_hop(&input, None, &mut output).unwrap();
+            assert!(output.iter().all(|s| s.is_finite()), "block {block}");
+            n += 256;
+        }
+
+        let state = engine.state();
+        assert!(state.last_output_phases.iter().all(|p| p.is_finite()));
+        assert!(output.iter().any(|&s| s.abs() > 0.1));
+    }
+
+    #[test]
+    fn test_reset_clears_stream() {
+        let mut engine = Engine512::new(VocalEffectsConfig::default(), MusicalSettings::default());
+        let mut output = [0.0f32; 128];
+        for block in 0..8 {
+            let input: [f32; 128] = core::array::from_fn(|i| sine(block * 128 + i));
+            engine.process_hop(&input, None, &mut output).unwrap();
+        }
+        engine.set_mode(ProcessingMode::Dry);
+
+        engine.reset();
+        assert!(!engine.is_crossfading());
+        assert_eq!(*engine.state(), ProcessingState::new());
+
+        engine.process_hop(&[0.0; 128], None, &mut output).unwrap();
+        assert!(output.iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn test_soft_reset_keeps_output_continuous() {
+        let settings = MusicalSettings {
+            mode: ProcessingMode::Dry,
+            octave_shift: OctaveShift::Up1,
+            ..Default::default()
+        };
+        let mut engine = Engine1024::new(VocalEffectsConfig::default(), settings);
+
+        let mut n = 0;
+        let mut previous = 0.0f32;
+        let mut max_step = 0.0f32;
+        for block in 0..24 {
+            if block == 10 {
+                engine.soft_reset();
+                let state = engine.state();
+                assert_eq!(state.last_output_phases, state.last_input_phases);
+            }
+            let input: [f32; 256] = core::array::from_fn(|i| sine(n + i));
+            let mut output = [0.0f32; 256];
+            engine.process_hop(&input, None, &mut output).unwrap();
+            if block >= 6 {
+                for &sample in output.iter() {
+                    max_step = max_step.max((sample - previous).abs());
+                    previous = sample;
+                }
+            } else {
+                previous = output[255];
+            }
+            n += 256;
+        }
+
+        // Shifting up an octave doubles the slope of the sine
+        assert!(max_step < 0.1, "max step {max_step}");
+    }
+
+    #[test]
+    fn test_zero_crossfade_hops_switches_instantly() {
+        let config = VocalEffectsConfig { mode_crossfade_hops: 0, ..Default::default() };
+        let mut engine = Engine512::new(config, MusicalSettings::default());
+        engine.set_mode(ProcessingMode::Vocode);
+        assert!(!engine.is_crossfading());
+        assert_eq!(engine.settings().mode, ProcessingMode::Vocode);
+
+        // A soft reset still fades the un-anchored state out over a hop
+        engine.soft_reset();
+        assert!(engine.is_crossfading());
+        engine.process_hop(&[0.0; 128], Some(&[0.0; 128]), &mut [0.0; 128]).unwrap();
+        assert!(!engine.is_crossfading());
+    }
+
+    #[test]
+    fn test_mode_switch_during_crossfade_keeps_output_continuous() {
+        // Formant mode keeps the pitch, the others shift it up an octave
+        let settings = MusicalSettings {
+            mode: ProcessingMode::Formant,
+            octave_shift: OctaveShift::Up1,
+            ..Default::default()
+        };
+        let config = VocalEffectsConfig { mode_crossfade_hops: 8, ..Default::default() };
+        let mut engine = Engine1024::new(config, settings);
+
+        // Level of the unshifted voice in each hop
+        let mut levels = [0.0f32; 32];
+        for (block, level) in levels.iter_mut().enumerate() {
+            match block {
+                8 => engine.set_mode(ProcessingMode::Dry),
+                10 => engine.set_mode(ProcessingMode::Autotune),
+                _ => {}
+            }
+            let input: [f32; 256] = core::array::from_fn(|i| sine(block * 256 + i));
+            let mut output = [0.0f32; 256];
+            engine.process_hop(&input, None, &mut output).unwrap();
+            *level = tone_level(&output, 220.0, SAMPLE_RATE);
+        }
+
+        // The unshifted voice still fades out after the second switch rather than
+        // dropping with the crossfade it was part of
+        let before = levels[4..8].iter().sum::<f32>() / 4.0;
+        let after = levels[11..13].iter().sum::<f32>() / 2.0;
+        assert!(after > 0.45 * before, "{levels:?}");
+        assert!(!engine.is_crossfading());
+    }
+
+    #[test]
+    fn test_snapshot_reports_spectrum_and_pitch() {
+        let mut engine = Engine1024::new(VocalEffectsConfig::default(), MusicalSettings::default());
+        let mut snapshot = FrameSnapshot::<512>::new();
+        let mut output = [0.0f32; 256];
+        for block in 0..12 {
+            let input: [f32; 256] = core::array::from_fn(|i| sine(block * 256 + i));
+            engine.process_hop_observed(&input, None, &mut output, &mut snapshot).unwrap();
+        }
+
+        assert_eq!(snapshot.hop_index, 12);
+        assert_eq!(snapshot.analysis, engine.state().analysis);
+        assert!((snapshot.analysis.detected_frequency - 220.0).abs() < 5.0);
+        let peak =
+            (0..512).max_by(|&a, &b| snapshot.magnitudes[a].total_cmp(&snapshot.magnitudes[b]));
+        assert!((snapshot.bin_frequency(peak.unwrap()) - 220.0).abs() < SAMPLE_RATE / 1024.0);
+
+        engine.reset();
+        let mut hops = 0;
+        engine
+            .process_hop_observed(&[0.0; 256], None, &mut output, &mut |frame: &FrameView<'_>| {
+                hops = frame.hop_index;
+            })
+            .unwrap();
+        assert_eq!(hops, 1);
+    }
+
+    #[test]
+    fn test_observer_sees_modulator_in_vocode_mode() {
+        let settings = MusicalSettings { mode: ProcessingMode::Vocode, ..Default::default() };
+        let mut engine = Engine512::new(VocalEffectsConfig::default(), settings);
+        let mut energy = 0.0f32;
+        let mut output = [0.0f32; 128];
+        for block in 0..8 {
+            let input: [f32; 128] = core::array::from_fn(|i| sine(block * 128 + i));
+            engine
+                .process_hop_observed(
+                    &input,
+                    Some(&[0.0; 128]),
+                    &mut output,
+                    &mut |frame: &FrameView<'_>| {
+                        assert_eq!(frame.mode, ProcessingMode::Vocode);
+                        energy = frame.magnitudes.iter().sum();
+                    },
+                )
+                .unwrap();
+        }
+        assert!(energy > 1.0, "modulator energy {energy}");
+    }
+
+    #[test]
+    fn test_carrier_modes_require_a_carrier() {
+        let settings = MusicalSettings { mode: ProcessingMode::TalkBox, ..Default::default() };
+        let mut engine = Engine512::new(VocalEffectsConfig::default(), settings);
+        let input: [f32; 128] = core::array::from_fn(sine);
+        let mut output = [1.0f32; 128];
+        assert!(engine.needs_carrier());
+        assert_eq!(
+            engine.process_hop(&input, None, &mut output),
+            Err(VocalEffectsError::MissingCarrier)
+        );
+        assert_eq!((engine.samples_received, output), (0, [1.0; 128]));
+
+        // The outgoing side of a crossfade still plays the carrier
+        engine.set_mode(ProcessingMode::Dry);
+        assert!(engine.needs_carrier());
+        for _ in 0..engine.config.mode_crossfade_hops {
+            engine.process_hop(&input, Some(&[0.0; 128]), &mut output).unwrap();
+        }
+        assert!(!engine.needs_carrier());
+        engine.process_hop(&input, None, &mut output).unwrap();
+    }
+
+    #[test]
+    fn test_hum_filter_keeps_detector_on_voice() {
+        let detected = |config: VocalEffectsConfig| {
+            let mut engine = Engine2048::new(config, MusicalSettings::default());
+            let mut snapshot = FrameSnapshot::<1024>::new();
+            let mut output = [0.0f32; 512];
+            for block in 0..120 {
+                let input: [f32; 512] = core::array::from_fn(|i| {
+                    let t = (block * 512 + i) as f32 / SAMPLE_RATE;
+                    0.6 * libm::sinf(2.0 * PI * 60.0 * t) + 0.2 * libm::sinf(2.0 * PI * 233.0 * t)
+                });
+                engine.process_hop_observed(&input, None, &mut output, &mut snapshot).unwrap();
+            }
+            snapshot.analysis.detected_frequency
+        };
+
+        let humming = detected(VocalEffectsConfig::default());
+        assert!((humming - 60.0).abs() < 5.0, "{humming}");
+        let filtered = detected(
+            VocalEffectsConfig::builder()
+                .hum_filter(crate::MainsFrequency::Hz60)
+                .build()
+                .unwrap(),
+        );
+        assert!((filtered - 233.0).abs() < 5.0, "{filtered}");
+    }
+
+    #[test]
+    fn test_confidence_gate_holds_through_noise() {
+        use crate::{
+            LowConfidence,
+            testsig::{PinkNoise, TestSignal, Vowel},
+        };
+
+        let config = VocalEffectsConfig::builder()
+            .pitch_detector(PitchDetector::HarmonicSum)
+            .confidence_gate(LowConfidence::Hold, 0.5)
+            .build()
+            .unwrap();
+        let mut engine = Engine1024::new(config, MusicalSettings::default());
+        let mut vowel = Vowel::new(245.0, 0.5, SAMPLE_RATE);
+        let mut noise = PinkNoise::new(0.5, 3);
+        let mut input = [0.0f32; 256];
+        let mut output = [0.0f32; 256];
+
+        for _ in 0..40 {
+            vowel.fill(&mut input);
+            engine.process_hop(&input, None, &mut output).unwrap();
+        }
+        let sung = engine.state().analysis;
+        assert!(sung.confidence > 0.8, "{sung:?}");
+        assert!((sung.target_frequency - 246.94).abs() < 0.1, "{sung:?}");
+
+        // Frames that still hold some of the vowel are corrected as usual
+        let mut held = sung;
+        for hop in 0..40 {
+            noise.fill(&mut input);
+            engine.process_hop(&input, None, &mut output).unwrap();
+            let analysis = engine.state().analysis;
+            if hop < 4 {
+                held = analysis;
+                continue;
+            }
+            assert!(analysis.confidence < 0.5, "{analysis:?}");
+            assert_eq!(analysis.pitch_shift_ratio, held.pitch_shift_ratio);
+            assert_eq!(analysis.target_frequency, held.target_frequency);
+        }
+    }
+
+    #[test]
+    fn test_pre_emphasis_round_trip_keeps_level() {
+        let peak = |config: VocalEffectsConfig| {
+            let mut engine = Engine1024::new(config, MusicalSettings::default());
+            let mut output = [0.0f32; 256];
+            let mut peak = 0.0f32;
+            for block in 0..40 {
+                let input: [f32; 256] = core::array::from_fn(|i| sine(block * 256 + i));
+                engine.process_hop(&input, None, &mut output).unwrap();
+                if block >= 30 {
+                    peak = output.iter().fold(peak, |peak, s| peak.max(s.abs()));
+                }
+            }
+            peak
+        };
+
+        let flat = peak(VocalEffectsConfig::default());
+        let emphasized = peak(VocalEffectsConfig::builder().pre_emphasis(0.95).build().unwrap());
+        assert!(flat > 0.1, "{flat}");
+        assert!((emphasized / flat - 1.0).abs() < 0.1, "{emphasized} vs {flat}");
+    }
+
+    #[test]
+    fn test_input_meter_counts_clips() {
+        let mut engine = Engine512::new(VocalEffectsConfig::default(), MusicalSettings::default());
+        let mut output = [0.0f32; 128];
+        let mut input: [f32; 128] = core::array::from_fn(sine);
+        engine.process_hop(&input, None, &mut output).unwrap();
+        assert!((engine.input_meter().reading().peak - 0.5).abs() < 0.01);
+        assert_eq!(engine.input_meter().clip_count(), 0);
+
+        input[7] = 1.2;
+        input[90] = -1.0;
+        engine.process_hop(&input, None, &mut output).unwrap();
+        engine.process_hop(&[0.0; 128], None, &mut output).unwrap();
+        assert_eq!(engine.input_meter().reading().peak, 0.0);
+        assert_eq!(engine.input_meter().clip_count(), 2);
+        engine.clear_input_clips();
+        assert_eq!(engine.input_meter().clip_count(), 0);
+    }
+
+    #[test]
+    fn test_breath_is_kept_in_unvoiced_frames() {
+        use crate::testsig::{PinkNoise, TestSignal, Vowel};
+
+        let run = |config: VocalEffectsConfig, signal: &mut dyn TestSignal| {
+            let mut engine = Engine1024::new(config, MusicalSettings::default());
+            let mut input = [0.0f32; 256];
+            let mut output = [0.0f32; 256];
+            let mut energy = 0.0f32;
+            for hop in 0..32 {
+                signal.fill(&mut input);
+                engine.process_hop(&input, None, &mut output).unwrap();
+                if hop >= 8 {
+                    energy += output.iter().map(|sample| sample * sample).sum::<f32>();
+                }
+            }
+            energy
+        };
+
+        let builder = || VocalEffectsConfig::builder().pitch_detector(PitchDetector::HarmonicSum);
+        let plain = builder().build().unwrap();
+        let breathy = builder().breath_retention(1000.0, 1.0).build().unwrap();
+        let noise = |config| run(config, &mut PinkNoise::new(0.5, 3));
+        assert!(noise(breathy) > 1.2 * noise(plain), "{} {}", noise(breathy), noise(plain));
+        let vowel = |config| run(config, &mut Vowel::new(245.0, 0.5, SAMPLE_RATE));
+        assert_eq!(vowel(breathy), vowel(plain));
+    }
+
+    #[test]
+    fn test_sidechain_ducks_the_vocoder_pad() {
+        let settings = MusicalSettings { mode: ProcessingMode::Vocode, ..Default::default() };
+        let run = |config: VocalEffectsConfig| {
+            let mut engine = Engine512::new(config, settings);
+            let mut output = [0.0f32; 128];
+            let mut peak = 0.0f32;
+            for block in 0..24 {
+                let input: [f32; 128] = core::array::from_fn(|i| sine(block * 128 + i));
+                let carrier: [f32; 128] =
+                    core::array::from_fn(|i| 0.3 * libm::sinf((block * 128 + i) as f32 * 0.3));
+                engine.process_hop(&input, Some(&carrier), &mut output).unwrap();
+                if block >= 16 {
+                    peak = output.iter().fold(peak, |peak, sample| peak.max(sample.abs()));
+                }
+            }
+            (peak, engine.carrier_duck_db())
+        };
+
+        let (open, none) = run(VocalEffectsConfig::default());
+        assert_eq!(none, 0.0);
+        // The 0.5 peak voice is 26 dB over the threshold, so the pad drops 13 dB
+        let config = VocalEffectsConfig::builder()
+            .sidechain_duck(-32.0, 2.0, 1.0, 100.0)
+            .build()
+            .unwrap();
+        let (ducked, gain_db) = run(config);
+        assert!(gain_db < -10.0 && gain_db > -14.0, "{gain_db}");
+        assert!(ducked < 0.35 * open, "{ducked} {open}");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_load_tracks_processing_time() {
+        let mut engine = Engine1024::new(VocalEffectsConfig::default(), MusicalSettings::default());
+        assert_eq!(engine.load(), 0.0);
+        let mut output = [0.0f32; 256];
+        for block in 0..16 {
+            let input: [f32; 256] = core::array::from_fn(|i| sine(block * 256 + i));
+            engine.process_hop(&input, None, &mut output).unwrap();
+        }
+        assert!(engine.load() > 0.0 && engine.load().is_finite(), "{}", engine.load());
+        engine.reset();
+        assert_eq!(engine.load(), 0.0);
+    }
+
+    #[test]
+    fn test_voice_activity_gate_skips_silent_hops() {
+        // 20 ms is four 256-sample hops at 48 kHz, as long as the frame
+        let config = VocalEffectsConfig::builder()
+            .voice_activity_gate(-50.0, 20.0, IdleOutput::Silence)
+            .build()
+            .unwrap();
+        let mut engine = Engine1024::new(config, MusicalSettings::default());
+        let mut output = [0.0f32; 256];
+        let mut snapshot = FrameSnapshot::<1024>::new();
+
+        for block in 0..20 {
+            let input: [f32; 256] = core::array::from_fn(|i| sine(block * 256 + i));
+            engine.process_hop_observed(&input, None, &mut output, &mut snapshot).unwrap();
+        }
+        assert!(!engine.is_idle());
+
+        // Three silent hops are processed, then the tails play out and the output stops
+        for block in 0..12 {
+            engine
+                .process_hop_observed(&[0.0; 256], None, &mut output, &mut snapshot)
+                .unwrap();
+            assert_eq!(engine.is_idle(), block >= 3, "hop {block}");
+        }
+        assert!(output.iter().all(|&sample| sample == 0.0));
+        assert_eq!(snapshot.hop_index, 23);
+
+        // The voice wakes it from a cleared state
+        let input: [f32; 256] = core::array::from_fn(sine);
+        engine.process_hop_observed(&input, None, &mut output, &mut snapshot).unwrap();
+        assert!(!engine.is_idle());
+        assert_eq!(snapshot.hop_index, 24);
+        assert_eq!(engine.state().frames_since_anchor, 1);
+    }
+
+    #[test]
+    fn test_idle_dry_output_is_delayed_input() {
+        let config = VocalEffectsConfig::builder()
+            .voice_activity_gate(-20.0, 0.0, IdleOutput::Dry)
+            .build()
+            .unwrap();
+        let settings = MusicalSettings { pitch_shift_semitones: 5.0, ..Default::default() };
+        let mut engine = Engine1024::new(config, settings);
+        let hops = engine.latency() / 256;
+        let mut history = [[0.0f32; 256]; 16];
+        let mut output = [0.0f32; 256];
+
+        // A -29 dBFS tone stays below the threshold, so only the frame length holds it
+        for block in 0..16 {
+            history[block] = core::array::from_fn(|i| 0.1 * sine(block * 256 + i));
+            engine.process_hop(&history[block], None, &mut output).unwrap();
+            if block >= 4 {
+                assert!(engine.is_idle());
+                assert_eq!(output, history[block - hops], "hop {block}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_phase_reanchor_waits_for_quiet_frames() {
+        let config = VocalEffectsConfig::builder().phase_reanchor(100.0, -60.0).build().unwrap();
+        let settings = MusicalSettings {
+            mode: ProcessingMode::Dry,
+            pitch_shift_semitones: 3.0,
+            ..Default::default()
+        };
+        let mut engine = Engine1024::new(config, settings);
+        let mut output = [0.0f32; 256];
+
+        // A steady tone never anchors however long it runs
+        for block in 0..40 {
+            let input: [f32; 256] = core::array::from_fn(|i| sine(block * 256 + i));
+            engine.process_hop(&input, None, &mut output).unwrap();
+        }
+        assert_eq!(engine.state().frames_since_anchor, 40);
+        assert_ne!(engine.state().last_output_phases, engine.state().last_input_phases);
+
+        // Once the frame has emptied, the first silent frame re-locks the phases
+        for _ in 0..4 {
+            engine.process_hop(&[0.0; 256], None, &mut output).unwrap();
+        }
+        assert_eq!(engine.state().frames_since_anchor, 0);
+        assert_eq!(engine.state().last_output_phases, engine.state().last_input_phases);
+
+        // Further silent frames wait for the interval, about 19 hops at 48 kHz
+        for _ in 0..5 {
+            engine.process_hop(&[0.0; 256], None, &mut output).unwrap();
+        }
+        assert_eq!(engine.state().frames_since_anchor, 5);
+    }
+}
@@ -0,0 +1,107 @@
+//! Formant morphing toward a captured voice.
+//!
+//! [`Engine::capture_formant_snapshot`] takes the formant envelope of any
+//! signal, another singer, an instrument or a recorded vowel, and
+//! [`Engine::set_formant_snapshot`] gives it to the engine. With
+//! [`VocalEffectsConfig::formant_morph`](crate::VocalEffectsConfig::formant_morph)
+//! set, every frame's envelope then moves that share of the way toward the
+//! snapshot, so the voice takes on its character while keeping its own pitch.
+
+use crate::{dsp::DynFft, dsp::FormantSnapshot, workspace::Workspace};
+
+use super::Engine;
+
+impl<const N: usize, const HALF_N: usize, F> Engine<N, HALF_N, F>
+where
+    F: DynFft<N, HALF_N>,
+{
+    /// Captures the formant envelope of `samples`
+    ///
+    /// Pass about a frame of a steady vowel; the last `N` samples at most are
+    /// analysed. The engine only lends its FFT backend and workspace, so the
+    /// stream is unaffected and the snapshot can go to another engine running
+    /// at the same sample rate, such as the other lane of a
+    /// [`DualEngine`](crate::engine::DualEngine).
+    pub fn capture_formant_snapshot(&mut self, samples: &[f32]) -> FormantSnapshot {
+        let lifter_cutoff = self.config.lifter_cutoff();
+        #[cfg(feature = "alloc")]
+        if let Some(workspace) = self.workspace.as_deref_mut() {
+            return FormantSnapshot::capture(&mut self.fft, workspace, samples, lifter_cutoff);
+        }
+        let mut workspace = Workspace::new();
+        FormantSnapshot::capture(&mut self.fft, &mut workspace, samples, lifter_cutoff)
+    }
+
+    /// Snapshot the formant envelope morphs toward, if any
+    pub fn formant_snapshot(&self) -> Option<&FormantSnapshot> {
+        self.state.formant_envelope.target()
+    }
+
+    /// Sets or removes the snapshot the formant envelope morphs toward, from the next hop
+    ///
+    /// The snapshot survives [`Engine::reset`]. It has no effect until
+    /// [`VocalEffectsConfig::formant_morph`](crate::VocalEffectsConfig::formant_morph)
+    /// is set.
+    pub fn set_formant_snapshot(&mut self, snapshot: Option<FormantSnapshot>) {
+        self.state.formant_envelope.set_target(snapshot);
+        if let Some(fade) = &mut self.crossfade {
+            fade.state.formant_envelope.set_target(snapshot);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Engine1024, MusicalSettings, ProcessingMode, VocalEffectsConfig,
+        testsig::{Sine, TestSignal, Vowel},
+    };
+
+    /// Share of the energy of `samples` in their first difference, higher for brighter sounds
+    fn brightness(samples: &[f32]) -> f32 {
+        let energy: f32 = samples.iter().map(|sample| sample * sample).sum();
+        let slope: f32 = samples.windows(2).map(|pair| (pair[1] - pair[0]).powi(2)).sum();
+        slope / energy
+    }
+
+    fn run(engine: &mut Engine1024) -> f32 {
+        let mut voice = Vowel::new(220.0, 0.5, 48000.0);
+        let (mut input, mut output) = ([0.0f32; 256], [0.0f32; 256]);
+        let mut tail = [0.0f32; 1024];
+        for hop in 0..16 {
+            voice.fill(&mut input);
+            engine.process_hop(&input, None, &mut output).unwrap();
+            if hop >= 12 {
+                tail[(hop - 12) * 256..][..256].copy_from_slice(&output);
+            }
+        }
+        brightness(&tail)
+    }
+
+    #[test]
+    fn test_morph_takes_on_the_snapshot_envelope() {
+        let settings = MusicalSettings { mode: ProcessingMode::Dry, ..Default::default() };
+        let config = VocalEffectsConfig::builder().formant_morph(1.0).build().unwrap();
+        let mut engine = Engine1024::new(config, settings);
+        assert!(engine.formant_snapshot().is_none());
+        let bright = run(&mut engine);
+
+        // A low tone has all of its envelope at the bottom of the spectrum
+        let mut dull = [0.0f32; 1024];
+        Sine::new(300.0, 0.5, 48000.0).fill(&mut dull);
+        let snapshot = engine.capture_formant_snapshot(&dull);
+        assert!(snapshot.at(0.0125) > 10.0 * snapshot.at(0.5), "{snapshot:?}");
+
+        engine.reset();
+        engine.set_formant_snapshot(Some(snapshot));
+        let morphed = run(&mut engine);
+        assert!(morphed < 0.5 * bright, "{morphed} {bright}");
+
+        // The snapshot survives a reset and can be removed
+        engine.reset();
+        assert_eq!(engine.formant_snapshot(), Some(&snapshot));
+        engine.set_formant_snapshot(None);
+        let restored = run(&mut engine);
+        assert!((restored - bright).abs() < 0.01 * bright, "{restored} {bright}");
+    }
+}
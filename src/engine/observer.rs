@@ -0,0 +1,123 @@
+//! Per-hop analysis for visualization.
+//!
+//! Spectrogram and tuner displays need the same spectrum and pitch the engine has
+//! just computed. [`Engine::process_hop_observed`](crate::Engine::process_hop_observed)
+//! hands them to a [`FrameObserver`] after each frame is analysed, either a closure
+//! that forwards what it needs or a [`FrameSnapshot`] the UI reads later.
+
+use crate::{FrameAnalysis, ProcessingMode};
+
+/// Analysis of the frame processed by the current hop
+///
+/// Borrows the engine scratch buffers, so it is only valid inside the observer.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameView<'a> {
+    /// Number of hops processed since the engine was created or reset, counting this one
+    pub hop_index: u64,
+    /// Mode the frame was processed in
+    pub mode: ProcessingMode,
+    /// Sample rate of the stream in Hz
+    pub sample_rate: f32,
+    /// Magnitudes of the windowed input spectrum from DC up to below Nyquist
+    pub magnitudes: &'a [f32],
+    /// Detected pitch, target and applied ratio; only autotune mode updates these,
+    /// the other modes hold the last values
+    pub analysis: FrameAnalysis,
+}
+
+impl FrameView<'_> {
+    /// Centre frequency of magnitude bin `bin` in Hz
+    pub fn bin_frequency(&self, bin: usize) -> f32 {
+        bin as f32 * self.sample_rate / (2 * self.magnitudes.len()) as f32
+    }
+
+    /// Level of the frame in dBFS, as the peak level of a sine with the same energy
+    pub fn level_db(&self) -> f32 {
+        // A sine of amplitude A under a Hann window puts 3 A² N² / 32 into the
+        // magnitudes of half an N-point spectrum
+        let size = (2 * self.magnitudes.len()) as f32;
+        let energy: f32 = self.magnitudes.iter().map(|magnitude| magnitude * magnitude).sum();
+        20.0 * libm::log10f((libm::sqrtf(32.0 * energy / 3.0) / size).max(1e-9))
+    }
+}
+
+/// Receives the analysis of every processed frame
+///
+/// Implemented for closures taking a [`FrameView`] and for [`FrameSnapshot`].
+/// Observers run on the audio thread and should not block.
+pub trait FrameObserver {
+    /// Called once per hop, after the frame has been analysed
+    fn on_frame(&mut self, frame: &FrameView<'_>);
+}
+
+impl<T: FnMut(&FrameView<'_>)> FrameObserver for T {
+    fn on_frame(&mut self, frame: &FrameView<'_>) {
+        self(frame)
+    }
+}
+
+/// Copy of the latest [`FrameView`] in fixed-size storage
+///
+/// Needs no allocation, so it works on `no_std` targets: process with the
+/// snapshot as observer and read it from the UI task between hops.
+///
+/// ```rust
+/// use synthphone_e_vocal_dsp::{
+///     Engine1024, MusicalSettings, VocalEffectsConfig, engine::FrameSnapshot,
+/// };
+///
+/// let mut engine = Engine1024::new(VocalEffectsConfig::default(), MusicalSettings::default());
+/// let mut snapshot = FrameSnapshot::<512>::new();
+/// let mut output = [0.0f32; 256];
+/// engine.process_hop_observed(&[0.0; 256], None, &mut output, &mut snapshot).unwrap();
+/// assert_eq!(snapshot.hop_index, 1);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameSnapshot<const HALF_N: usize> {
+    /// Hop the snapshot was taken at, 0 before the first frame
+    pub hop_index: u64,
+    /// Mode the frame was processed in
+    pub mode: ProcessingMode,
+    /// Sample rate of the stream in Hz
+    pub sample_rate: f32,
+    /// Magnitudes of the windowed input spectrum
+    pub magnitudes: [f32; HALF_N],
+    /// Detected pitch, target and applied ratio
+    pub analysis: FrameAnalysis,
+}
+
+impl<const HALF_N: usize> FrameSnapshot<HALF_N> {
+    /// Creates an empty snapshot
+    pub const fn new() -> Self {
+        Self {
+            hop_index: 0,
+            mode: ProcessingMode::Autotune,
+            sample_rate: 0.0,
+            magnitudes: [0.0; HALF_N],
+            analysis: FrameAnalysis::new(),
+        }
+    }
+
+    /// Centre frequency of magnitude bin `bin` in Hz
+    pub fn bin_frequency(&self, bin: usize) -> f32 {
+        bin as f32 * self.sample_rate / (2 * HALF_N) as f32
+    }
+}
+
+impl<const HALF_N: usize> Default for FrameSnapshot<HALF_N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const HALF_N: usize> FrameObserver for FrameSnapshot<HALF_N> {
+    fn on_frame(&mut self, frame: &FrameView<'_>) {
+        self.hop_index = frame.hop_index;
+        self.mode = frame.mode;
+        self.sample_rate = frame.sample_rate;
+        self.analysis = frame.analysis;
+        let bins = HALF_N.min(frame.magnitudes.len());
+        self.magnitudes[..bins].copy_from_slice(&frame.magnitudes[..bins]);
+        self.magnitudes[bins..].fill(0.0);
+    }
+}
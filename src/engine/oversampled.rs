@@ -0,0 +1,263 @@
+//! 2x oversampled synthesis.
+//!
+//! Shifting up pushes partials towards Nyquist, where the phase vocoder's bin
+//! frequencies get coarse and anything past the top bin is lost.
+//! [`OversampledEngine`] runs the engine at twice the host rate so the shifted
+//! spectrum has an extra octave of headroom, then filters it back down, which
+//! removes everything above the host Nyquist instead of letting it fold.
+
+use std::vec::Vec;
+
+use crate::{
+    BandLimit, MusicalSettings, VocalEffectsConfig, VocalEffectsError,
+    dsp::{DynFft, Resampler},
+};
+
+use super::Engine;
+
+/// Streaming processor running an [`Engine`] at twice the host sample rate.
+///
+/// Each hop is upsampled, processed and downsampled again. The engine frame
+/// covers half the time at the doubled rate, so use a frame twice as large as
+/// you would at the host rate to keep the same frequency resolution.
+///
+/// # Example
+///
+/// ```rust
+/// use synthphone_e_vocal_dsp::{
+///     MusicalSettings, VocalEffectsConfig, dsp::Fft2048, engine::OversampledEngine,
+/// };
+///
+/// let mut engine = OversampledEngine::<2048, 1024, Fft2048>::new(
+///     VocalEffectsConfig::default(),
+///     MusicalSettings { pitch_shift_semitones: 12.0, ..Default::default() },
+/// )
+/// .unwrap();
+/// let hop = engine.hop_size();
+/// assert_eq!(hop, 256);
+///
+/// let input = vec![0.0f32; hop];
+/// let mut output = vec![0.0f32; hop];
+/// engine.process_hop(&input, None, &mut output).unwrap();
+/// ```
+pub struct OversampledEngine<const N: usize, const HALF_N: usize, F>
+where
+    F: DynFft<N, HALF_N>,
+{
+    engine: Engine<N, HALF_N, F>,
+    upsampler: Resampler,
+    carrier_upsampler: Resampler,
+    downsampler: Resampler,
+    input: Vec<f32>,
+    carrier: Vec<f32>,
+    output: Vec<f32>,
+}
+
+impl<const N: usize, const HALF_N: usize, F> OversampledEngine<N, HALF_N, F>
+where
+    F: DynFft<N, HALF_N> + Default,
+{
+    /// Creates an oversampled engine.
+    ///
+    /// `config` describes the host stream; the inner engine runs the same
+    /// configuration at twice its sample rate. Without a
+    /// [`band_limit`](VocalEffectsConfig::band_limit), shifted bins are faded out
+    /// from 40% of the host rate to the host Nyquist, so the downsampler never
+    /// sees anything it would fold back.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VocalEffectsError::InvalidConfiguration`] if the sample rate is
+    /// not a whole number of Hz or the inner hop is odd.
+    pub fn new(
+        mut config: VocalEffectsConfig,
+        settings: MusicalSettings,
+    ) -> Result<Self, VocalEffectsError> {
+        let host_rate = config.sample_rate as u32;
+        if host_rate as f32 != config.sample_rate {
+            return Err(VocalEffectsError::InvalidConfiguration);
+        }
+        config.band_limit = config.band_limit.or(Some(BandLimit {
+            cutoff_hz: 0.4 * config.sample_rate,
+            transition_hz: 0.1 * config.sample_rate,
+        }));
+        config.sample_rate *= 2.0;
+        let engine = Engine::new(config, settings);
+        let hop = engine.hop_size();
+        if hop % 2 != 0 {
+            return Err(VocalEffectsError::InvalidConfiguration);
+        }
+
+        Ok(Self {
+            engine,
+            upsampler: Resampler::new(host_rate, 2 * host_rate)?,
+            carrier_upsampler: Resampler::new(host_rate, 2 * host_rate)?,
+            downsampler: Resampler::new(2 * host_rate, host_rate)?,
+            input: vec![0.0; hop],
+            carrier: vec![0.0; hop],
+            output: vec![0.0; hop],
+        })
+    }
+}
+
+impl<const N: usize, const HALF_N: usize, F> OversampledEngine<N, HALF_N, F>
+where
+    F: DynFft<N, HALF_N>,
+{
+    /// Returns the inner engine, running at twice the host rate
+    pub fn engine(&self) -> &Engine<N, HALF_N, F> {
+        &self.engine
+    }
+
+    /// Returns the inner engine for parameter changes
+    pub fn engine_mut(&mut self) -> &mut Engine<N, HALF_N, F> {
+        &mut self.engine
+    }
+
+    /// Number of host samples consumed and produced by each [`OversampledEngine::process_hop`] call
+    pub fn hop_size(&self) -> usize {
+        self.engine.hop_size() / 2
+    }
+
+    /// Delay in host samples between an input sample and its processed output
+    pub fn latency(&self) -> usize {
+        (self.engine.latency() + self.downsampler.latency()) / 2 + self.upsampler.latency()
+    }
+
+    /// Clears the engine and the resampler histories
+    pub fn reset(&mut self) {
+        self.engine.reset();
+        self.upsampler.reset();
+        self.carrier_upsampler.reset();
+        self.downsampler.reset();
+    }
+
+    /// Processes one hop of audio at the host rate.
+    ///
+    /// Behaves like [`Engine::process_hop`], with buffers of
+    /// [`OversampledEngine::hop_size`] samples.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VocalEffectsError::BufferSizeMismatch`] if any buffer is not exactly
+    /// one hop long, or any error from [`Engine::process_hop`].
+    pub fn process_hop(
+        &mut self,
+        input: &[f32],
+        carrier: Option<&[f32]>,
+        output: &mut [f32],
+    ) -> Result<(), VocalEffectsError> {
+        let hop = self.hop_size();
+        if input.len() != hop || output.len() != hop || carrier.is_some_and(|c| c.len() != hop) {
+            return Err(VocalEffectsError::BufferSizeMismatch);
+        }
+
+        // A 1:2 resampler turns every input sample into exactly two outputs, and a
+        // 2:1 resampler every pair back into one, so the buffers always fill
+        self.upsampler.process(input, &mut self.input);
+        let carrier = match carrier {
+            Some(carrier) => {
+                self.carrier_upsampler.process(carrier, &mut self.carrier);
+                Some(&self.carrier[..])
+            }
+            None => None,
+        };
+        self.engine.process_hop(&self.input, carrier, &mut self.output)?;
+        self.downsampler.process(&self.output, output);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Engine1024, ProcessingMode,
+        dsp::Fft2048,
+        testsig::{Sine, TestSignal, tone_level},
+    };
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    type Oversampled2048 = OversampledEngine<2048, 1024, Fft2048>;
+
+    #[test]
+    fn test_rejects_fractional_rate_and_wrong_hop() {
+        let config = VocalEffectsConfig { sample_rate: 44100.5, ..Default::default() };
+        assert!(Oversampled2048::new(config, MusicalSettings::default()).is_err());
+
+        let mut engine =
+            Oversampled2048::new(VocalEffectsConfig::default(), MusicalSettings::default())
+                .unwrap();
+        assert_eq!(engine.engine().config().sample_rate, 2.0 * SAMPLE_RATE);
+        let mut output = [0.0f32; 256];
+        assert_eq!(
+            engine.process_hop(&[0.0; 512], None, &mut output),
+            Err(VocalEffectsError::BufferSizeMismatch)
+        );
+    }
+
+    #[test]
+    fn test_dry_signal_arrives_at_reported_latency() {
+        let config = VocalEffectsConfig::builder().wet_dry(0.0).build().unwrap();
+        let mut engine = Oversampled2048::new(config, MusicalSettings::default()).unwrap();
+        let hop = engine.hop_size();
+
+        let mut output = Vec::new();
+        let mut block = vec![0.0f32; hop];
+        for index in 0..16 {
+            let input: Vec<f32> =
+                (0..hop).map(|n| if index == 0 && n == 0 { 1.0 } else { 0.0 }).collect();
+            engine.process_hop(&input, None, &mut block).unwrap();
+            output.extend_from_slice(&block);
+        }
+        let peak = (0..output.len())
+            .max_by(|&a, &b| output[a].abs().total_cmp(&output[b].abs()))
+            .unwrap();
+        assert!(peak.abs_diff(engine.latency()) <= 1, "{peak} {}", engine.latency());
+    }
+
+    #[test]
+    fn test_octave_up_does_not_alias() {
+        let settings = MusicalSettings {
+            pitch_shift_semitones: 12.0,
+            mode: ProcessingMode::Dry,
+            ..Default::default()
+        };
+        // 4 kHz lands on 8 kHz; 15 kHz lands on 30 kHz, past Nyquist, and would
+        // alias to 18 kHz if it folded
+        let mut low = Sine::new(4000.0, 0.25, SAMPLE_RATE);
+        let mut high = Sine::new(15000.0, 0.25, SAMPLE_RATE);
+        let mut signal = || low.next_sample() + high.next_sample();
+
+        let mut oversampled =
+            Oversampled2048::new(VocalEffectsConfig::default(), settings).unwrap();
+        let hop = oversampled.hop_size();
+        let mut output = Vec::new();
+        let mut input = vec![0.0f32; hop];
+        let mut block = vec![0.0f32; hop];
+        for _ in 0..96 {
+            input.iter_mut().for_each(|sample| *sample = signal());
+            oversampled.process_hop(&input, None, &mut block).unwrap();
+            output.extend_from_slice(&block);
+        }
+        let settled = &output[output.len() - 8192..];
+        assert!(settled.iter().all(|sample| sample.is_finite()));
+        let shifted = tone_level(settled, 8000.0, SAMPLE_RATE);
+        let alias = tone_level(settled, 18000.0, SAMPLE_RATE);
+        assert!(shifted > 0.05, "{shifted}");
+        assert!(alias < 0.01 * shifted, "{shifted} {alias}");
+
+        // The host-rate engine keeps the same in-band result
+        let mut engine = Engine1024::new(VocalEffectsConfig::default(), settings);
+        assert_eq!(engine.hop_size(), hop);
+        let mut output = Vec::new();
+        for _ in 0..96 {
+            input.iter_mut().for_each(|sample| *sample = signal());
+            engine.process_hop(&input, None, &mut block).unwrap();
+            output.extend_from_slice(&block);
+        }
+        let reference = tone_level(&output[output.len() - 8192..], 8000.0, SAMPLE_RATE);
+        assert!((shifted - reference).abs() < 0.25 * reference, "{shifted} {reference}");
+    }
+}
@@ -0,0 +1,158 @@
+//! Resample-and-stretch pitch shifting.
+//!
+//! With [`PitchShiftAlgorithm::ResampleStretch`](crate::PitchShiftAlgorithm::ResampleStretch),
+//! the engine turns a fixed transposition into a time stretch: each frame is
+//! resynthesised at a synthesis hop longer or shorter than the input hop by the
+//! ratio, which keeps the pitch, and a [`Resampler`] squeezes the stretched hop
+//! back into one input hop, which moves every partial by the ratio. The synthesis
+//! hop is rounded so the resampler's phase table can hold the ratio; whatever the
+//! rounded hop misses is left to bin remapping.
+
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+
+use libm::{log2f, roundf};
+
+use crate::{
+    MusicalSettings, OctaveShift, ProcessingMode, dsp::Resampler, math::semitones_to_ratio,
+};
+
+/// Resampler from the stretched hop back to the input hop
+///
+/// The phase table is on the heap with `alloc`, like the workspace, so engines
+/// that never shift this way stay as small as before.
+pub(super) struct ResampleShift {
+    #[cfg(feature = "alloc")]
+    resampler: Box<Resampler>,
+    #[cfg(not(feature = "alloc"))]
+    resampler: Resampler,
+    /// Stretched hop the resampler is set up for, 0 before the first hop
+    synthesis_hop: usize,
+}
+
+impl ResampleShift {
+    pub(super) fn new() -> Option<Self> {
+        let resampler = Resampler::new(1, 1).ok()?;
+        #[cfg(feature = "alloc")]
+        let resampler = Box::new(resampler);
+        Some(Self { resampler, synthesis_hop: 0 })
+    }
+
+    /// Clears the resampler history
+    pub(super) fn reset(&mut self) {
+        self.resampler.reset();
+    }
+
+    /// Resamples the stretched hop `stretched` into `output`
+    ///
+    /// An unstretched hop is copied as it is, but still runs through the
+    /// resampler so its history is ready when a shift starts.
+    pub(super) fn process(&mut self, stretched: &[f32], output: &mut [f32]) {
+        if stretched.len() != self.synthesis_hop
+            && self.resampler.set_rates(stretched.len() as u32, output.len() as u32).is_ok()
+        {
+            self.synthesis_hop = stretched.len();
+        }
+        let (_, produced) = self.resampler.process(stretched, output);
+        output[produced..].fill(0.0);
+        if stretched.len() == output.len() {
+            output.copy_from_slice(stretched);
+        }
+    }
+
+    /// Delay in samples between an input sample and its output for hops of `hop`
+    /// samples stretched to `synthesis_hop`
+    ///
+    /// A frame plays at its own rate within the stretched stream, so its middle
+    /// arrives later on a downward shift and earlier on an upward one.
+    pub(super) fn latency(&self, hop: usize, synthesis_hop: usize, frame_size: usize) -> usize {
+        let squeeze = hop as f32 / synthesis_hop as f32;
+        let centre = frame_size as f32 / 2.0;
+        let delay =
+            centre * (1.0 + squeeze) - hop as f32 + self.resampler.latency() as f32 * squeeze;
+        roundf(delay.max(0.0)) as usize
+    }
+}
+
+/// Synthesis hop that carries the fixed transposition of `settings`, and the
+/// settings left for the frame processors
+///
+/// The transposition is the octave shift, plus the semitone shift in dry mode.
+/// Dry mode hands whatever the rounded hop misses to the semitone shift. Autotune
+/// mode has no fine shift to hand it to, so it only stretches when the hop gives
+/// the octave shift exactly. The synthesis hop stays within half the frame.
+pub(super) fn plan(
+    hop: usize,
+    frame_size: usize,
+    settings: &MusicalSettings,
+) -> (usize, MusicalSettings) {
+    let octave = settings.octave_shift.ratio();
+    let ratio = match settings.mode {
+        ProcessingMode::Dry => octave * semitones_to_ratio(settings.pitch_shift_semitones),
+        ProcessingMode::Autotune => octave,
+        _ => return (hop, *settings),
+    };
+
+    // The reduced ratio of the two hops has to fit the resampler's phase table
+    let step = (1..=hop)
+        .find(|&step| hop.is_multiple_of(step) && hop / step <= Resampler::MAX_INTERPOLATION)
+        .unwrap_or(1);
+    let longest = (frame_size / 2 / step).max(1) * step;
+    let synthesis_hop =
+        (roundf(ratio * hop as f32 / step as f32) as usize * step).clamp(step, longest);
+    let stretch = synthesis_hop as f32 / hop as f32;
+
+    match settings.mode {
+        ProcessingMode::Dry => {
+            let residual = MusicalSettings {
+                octave_shift: OctaveShift::None,
+                pitch_shift_semitones: 12.0 * log2f(ratio / stretch),
+                ..*settings
+            };
+            (synthesis_hop, residual)
+        }
+        _ if stretch == octave => {
+            (synthesis_hop, MusicalSettings { octave_shift: OctaveShift::None, ..*settings })
+        }
+        _ => (hop, *settings),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_splits_ratio_between_hop_and_semitones() {
+        let dry = MusicalSettings { mode: ProcessingMode::Dry, ..Default::default() };
+
+        // A fifth up stretches 256 to 384 and leaves 2 cents for the bins
+        let (synthesis_hop, residual) =
+            plan(256, 1024, &MusicalSettings { pitch_shift_semitones: 7.0, ..dry });
+        assert_eq!(synthesis_hop, 384);
+        assert!(
+            residual.pitch_shift_semitones.abs() < 0.03,
+            "{}",
+            residual.pitch_shift_semitones
+        );
+
+        // An octave down is exact, an octave up is capped at half the frame
+        let (synthesis_hop, residual) =
+            plan(128, 1024, &MusicalSettings { octave_shift: OctaveShift::Down1, ..dry });
+        assert_eq!((synthesis_hop, residual.octave_shift), (64, OctaveShift::None));
+        assert!(residual.pitch_shift_semitones.abs() < 1e-4);
+        let (synthesis_hop, residual) =
+            plan(256, 1024, &MusicalSettings { octave_shift: OctaveShift::Up2, ..dry });
+        assert_eq!(synthesis_hop, 512);
+        assert!((residual.pitch_shift_semitones - 12.0).abs() < 1e-3);
+
+        // Autotune only stretches for whole octaves it can reach
+        let autotune = MusicalSettings { octave_shift: OctaveShift::Up1, ..Default::default() };
+        assert_eq!(plan(128, 1024, &autotune), (256, MusicalSettings::default()));
+        assert_eq!(plan(256, 512, &autotune), (256, autotune));
+
+        // Modes without a transposition keep the hop
+        let vocode = MusicalSettings { mode: ProcessingMode::Vocode, ..autotune };
+        assert_eq!(plan(256, 1024, &vocode), (256, vocode));
+    }
+}
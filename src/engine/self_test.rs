@@ -0,0 +1,205 @@
+//! Built-in self-test for hardware bring-up.
+//!
+//! [`Engine::self_test`] plays a synthetic vowel through the configured
+//! pipeline and checks the output the way a factory test would: every sample
+//! finite, a sensible level, and the signal arriving when
+//! [`Engine::latency`] says it should. The result is a plain enum, so firmware
+//! can map it to an error code or a status LED.
+
+use crate::{
+    VocalEffectsError,
+    audio::{Oscillator, Waveform},
+    dsp::DynFft,
+    testsig::{TestSignal, Vowel},
+};
+
+use super::Engine;
+
+/// Fundamental of the test vowel in Hz, sung "ah" on A3
+const TEST_F0: f32 = 220.0;
+
+/// Peak level of the test vowel
+const TEST_LEVEL: f32 = 0.5;
+
+/// Output level, relative to the input, below which the pipeline counts as silent
+const SILENT_RATIO: f32 = 0.1;
+
+/// Output peak, relative to the input, above which the pipeline counts as overloaded
+const OVERLOAD_RATIO: f32 = 4.0;
+
+/// Outcome of [`Engine::self_test`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SelfTestResult {
+    /// Output was finite, at a sensible level and arrived on time
+    Passed {
+        /// RMS of the settled output
+        output_rms: f32,
+        /// Samples between the start of the vowel and its arrival at the output
+        measured_latency: usize,
+    },
+    /// Processing returned an error
+    Error(VocalEffectsError),
+    /// The hop at this index, counted from 0, produced NaN or infinity
+    NonFinite {
+        /// Index of the first bad hop
+        hop: usize,
+    },
+    /// The settled output was more than 20 dB below the input
+    Silent {
+        /// RMS of the settled output
+        output_rms: f32,
+    },
+    /// The output peaked more than 12 dB above the input
+    Overload {
+        /// Largest absolute output sample
+        peak: f32,
+    },
+    /// The vowel arrived more than half a frame away from the reported latency
+    LatencyMismatch {
+        /// Latency reported by [`Engine::latency`]
+        expected: usize,
+        /// Samples until the vowel arrived at the output, if it did
+        measured: Option<usize>,
+    },
+}
+
+impl SelfTestResult {
+    /// Returns `true` if the self-test passed
+    pub fn passed(&self) -> bool {
+        matches!(self, SelfTestResult::Passed { .. })
+    }
+}
+
+impl<const N: usize, const HALF_N: usize, F> Engine<N, HALF_N, F>
+where
+    F: DynFft<N, HALF_N>,
+{
+    /// Runs a synthetic vowel through the configured pipeline and checks the output
+    ///
+    /// A 220 Hz vowel starts after a frame of silence, with a 110 Hz sawtooth on
+    /// the carrier input for the vocoder. The test runs for the latency plus four
+    /// frames and checks that every output sample is finite, that the settled
+    /// output is neither silent nor overloaded, and that the vowel arrives within
+    /// half a frame of [`Engine::latency`].
+    ///
+    /// The engine is reset before and after, so run this at start-up rather than
+    /// in the middle of a stream.
+    pub fn self_test(&mut self) -> SelfTestResult {
+        self.reset();
+        let result = self.run_self_test();
+        self.reset();
+        result
+    }
+
+    fn run_self_test(&mut self) -> SelfTestResult {
+        let hop = self.hop_size();
+        if hop == 0 || hop > N {
+            return SelfTestResult::Error(VocalEffectsError::InvalidConfiguration);
+        }
+        let sample_rate = self.config.sample_rate;
+        let latency = self.latency();
+        let lead_in_hops = N.div_ceil(hop);
+        let test_hops = (latency + 4 * N).div_ceil(hop);
+        let settled_from = (latency + 2 * N).div_ceil(hop);
+
+        let mut vowel = Vowel::new(TEST_F0, TEST_LEVEL, sample_rate);
+        let mut carrier = Oscillator::new(TEST_F0 / 2.0, sample_rate, Waveform::Saw);
+        let mut input = [0.0f32; N];
+        let mut carrier_block = [0.0f32; N];
+        let mut output = [0.0f32; N];
+
+        let mut onset = None;
+        let mut peak = 0.0f32;
+        let mut settled_energy = 0.0f32;
+        let mut settled_samples = 0usize;
+        let mut input_energy = 0.0f32;
+        let mut input_samples = 0usize;
+
+        for index in 0..lead_in_hops + test_hops {
+            let sounding = index >= lead_in_hops;
+            for (sample, carrier_sample) in input[..hop].iter_mut().zip(&mut carrier_block[..hop]) {
+                *sample = if sounding { vowel.next_sample() } else { 0.0 };
+                *carrier_sample = 0.5 * carrier.next_value();
+            }
+            if let Err(error) =
+                self.process_hop(&input[..hop], Some(&carrier_block[..hop]), &mut output[..hop])
+            {
+                return SelfTestResult::Error(error);
+            }
+            if output[..hop].iter().any(|sample| !sample.is_finite()) {
+                return SelfTestResult::NonFinite { hop: index };
+            }
+            if !sounding {
+                continue;
+            }
+
+            let elapsed = (index - lead_in_hops) * hop;
+            if onset.is_none() {
+                onset = output[..hop]
+                    .iter()
+                    .position(|sample| sample.abs() >= SILENT_RATIO * TEST_LEVEL)
+                    .map(|offset| elapsed + offset);
+            }
+            peak = output[..hop].iter().fold(peak, |peak, sample| peak.max(sample.abs()));
+            input_energy += input[..hop].iter().map(|s| s * s).sum::<f32>();
+            input_samples += hop;
+            if index - lead_in_hops >= settled_from {
+                settled_energy += output[..hop].iter().map(|s| s * s).sum::<f32>();
+                settled_samples += hop;
+            }
+        }
+
+        let input_rms = libm::sqrtf(input_energy / input_samples as f32);
+        let output_rms = libm::sqrtf(settled_energy / settled_samples.max(1) as f32);
+        if peak > OVERLOAD_RATIO * TEST_LEVEL {
+            return SelfTestResult::Overload { peak };
+        }
+        if output_rms < SILENT_RATIO * input_rms {
+            return SelfTestResult::Silent { output_rms };
+        }
+        match onset {
+            Some(measured) if measured.abs_diff(latency) <= N / 2 => {
+                SelfTestResult::Passed { output_rms, measured_latency: measured }
+            }
+            measured => SelfTestResult::LatencyMismatch { expected: latency, measured },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Engine512, Engine1024, MusicalSettings, ProcessingMode, VocalEffectsConfig};
+
+    #[test]
+    fn test_every_mode_passes() {
+        for mode in [
+            ProcessingMode::Autotune,
+            ProcessingMode::Dry,
+            ProcessingMode::Formant,
+            ProcessingMode::Vocode,
+            ProcessingMode::TalkBox,
+        ] {
+            let settings = MusicalSettings { mode, ..Default::default() };
+            let mut engine = Engine1024::new(VocalEffectsConfig::default(), settings);
+            let result = engine.self_test();
+            assert!(result.passed(), "{mode:?}: {result:?}");
+        }
+    }
+
+    #[test]
+    fn test_dry_signal_arrives_at_reported_latency() {
+        let config = VocalEffectsConfig::builder().wet_dry(0.0).build().unwrap();
+        let mut engine = Engine512::new(config, MusicalSettings::default());
+        match engine.self_test() {
+            SelfTestResult::Passed { measured_latency, .. } => {
+                // The vowel starts at zero and crosses the onset level within a few samples
+                assert!(measured_latency >= engine.latency(), "{measured_latency}");
+                assert!(measured_latency < engine.latency() + 16, "{measured_latency}");
+            }
+            result => panic!("{result:?}"),
+        }
+        assert_eq!(engine.state().analysis, crate::FrameAnalysis::new());
+    }
+}
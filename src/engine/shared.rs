@@ -0,0 +1,461 @@
+//! Engine wrapper with lock-free control-rate parameter updates.
+//!
+//! In RTIC (or any interrupt driven firmware) the audio interrupt owns the engine
+//! while buttons, MIDI and UI tasks change the key or mode. Sharing the whole
+//! engine behind a lock makes the control tasks block the audio interrupt for a
+//! full frame. Instead, [`SharedControls`] holds the settings in atomics that any
+//! priority can write, and [`SharedEngine`] picks them up between hops.
+
+use core::sync::atomic::{AtomicI32, AtomicU32, Ordering};
+
+use crate::{
+    ChordSpec, CorrectionStrength, Formant, Key, MusicalSettings, Note, Octave, OctaveShift,
+    ProcessingMode, TargetSource, VocalEffectsError, VocoderEq, dsp::DynFft, engine::Engine,
+    state::VOCODER_BANDS,
+};
+
+/// Musical settings shared between control tasks and the audio interrupt.
+///
+/// Updates are published with a sequence counter, so the audio side never
+/// applies a half-written update. Only plain atomic loads and stores are used,
+/// which keeps it usable on cores without compare-and-swap (Cortex-M0+).
+///
+/// There must be a single writer at a time: either update from one task only or
+/// from tasks that cannot preempt each other.
+pub struct SharedControls {
+    key: AtomicI32,
+    note: AtomicI32,
+    octave: AtomicI32,
+    held_note_cents: AtomicU32,
+    octave_shift: AtomicI32,
+    formant: AtomicI32,
+    vocoder_formant_semitones: AtomicU32,
+    pitch_shift_semitones: AtomicU32,
+    synth_mix: AtomicU32,
+    mode: AtomicU32,
+    target: AtomicU32,
+    /// Correction strength bytes, four per word
+    correction_strength: [AtomicU32; 3],
+    /// Vocoder band gains, four per word
+    vocoder_eq: [AtomicU32; 4],
+    /// Odd while an update is being written
+    sequence: AtomicU32,
+}
+
+impl Default for SharedControls {
+    fn default() -> Self {
+        Self::new(MusicalSettings::default())
+    }
+}
+
+impl SharedControls {
+    /// Creates the controls with initial settings, usable in a `static`
+    pub const fn new(settings: MusicalSettings) -> Self {
+        Self {
+            key: AtomicI32::new(settings.key as i32),
+            note: AtomicI32::new(settings.note as i32),
+            octave: AtomicI32::new(settings.octave as i32),
+            held_note_cents: AtomicU32::new(settings.held_note_cents.to_bits()),
+            octave_shift: AtomicI32::new(settings.octave_shift as i32),
+            formant: AtomicI32::new(settings.formant as i32),
+            vocoder_formant_semitones: AtomicU32::new(settings.vocoder_formant_semitones.to_bits()),
+            pitch_shift_semitones: AtomicU32::new(settings.pitch_shift_semitones.to_bits()),
+            synth_mix: AtomicU32::new(settings.synth_mix.to_bits()),
+            mode: AtomicU32::new(mode_to_u32(settings.mode)),
+            target: AtomicU32::new(target_to_u32(settings.target)),
+            correction_strength: {
+                let words = strength_to_words(settings.correction_strength);
+                [AtomicU32::new(words[0]), AtomicU32::new(words[1]), AtomicU32::new(words[2])]
+            },
+            vocoder_eq: {
+                let words = eq_to_words(settings.vocoder_eq);
+                [
+                    AtomicU32::new(words[0]),
+                    AtomicU32::new(words[1]),
+                    AtomicU32::new(words[2]),
+                    AtomicU32::new(words[3]),
+                ]
+            },
+            sequence: AtomicU32::new(0),
+        }
+    }
+
+    /// Returns the most recently published settings
+    pub fn settings(&self) -> MusicalSettings {
+        MusicalSettings {
+            // Only valid values are ever stored, the fallbacks are unreachable
+            key: Key::try_from(self.key.load(Ordering::Relaxed)).unwrap_or_default(),
+            note: Note::try_from(self.note.load(Ordering::Relaxed)).unwrap_or_default(),
+            octave: Octave::try_from(self.octave.load(Ordering::Relaxed)).unwrap_or_default(),
+            held_note_cents: f32::from_bits(self.held_note_cents.load(Ordering::Relaxed)),
+            octave_shift: OctaveShift::try_from(self.octave_shift.load(Ordering::Relaxed))
+                .unwrap_or_default(),
+            formant: Formant::try_from(self.formant.load(Ordering::Relaxed)).unwrap_or_default(),
+            vocoder_formant_semitones: f32::from_bits(
+                self.vocoder_formant_semitones.load(Ordering::Relaxed),
+            ),
+            pitch_shift_semitones: f32::from_bits(
+                self.pitch_shift_semitones.load(Ordering::Relaxed),
+            ),
+            synth_mix: f32::from_bits(self.synth_mix.load(Ordering::Relaxed)),
+            mode: mode_from_u32(self.mode.load(Ordering::Relaxed)),
+            target: target_from_u32(self.target.load(Ordering::Relaxed)),
+            correction_strength: strength_from_words(
+                self.correction_strength.each_ref().map(|word| word.load(Ordering::Relaxed)),
+            ),
+            vocoder_eq: eq_from_words(
+                self.vocoder_eq.each_ref().map(|word| word.load(Ordering::Relaxed)),
+            ),
+        }
+    }
+
+    /// Publishes new settings
+    pub fn set_settings(&self, settings: MusicalSettings) {
+        let sequence = self.sequence.load(Ordering::Relaxed);
+        self.sequence.store(sequence.wrapping_add(1), Ordering::Release);
+        core::sync::atomic::fence(Ordering::Release);
+
+        self.key.store(settings.key as i32, Ordering::Relaxed);
+        self.note.store(settings.note as i32, Ordering::Relaxed);
+        self.octave.store(settings.octave as i32, Ordering::Relaxed);
+        self.held_note_cents
+            .store(settings.held_note_cents.to_bits(), Ordering::Relaxed);
+        self.octave_shift.store(settings.octave_shift as i32, Ordering::Relaxed);
+        self.formant.store(settings.formant as i32, Ordering::Relaxed);
+        self.vocoder_formant_semitones
+            .store(settings.vocoder_formant_semitones.to_bits(), Ordering::Relaxed);
+        self.pitch_shift_semitones
+            .store(settings.pitch_shift_semitones.to_bits(), Ordering::Relaxed);
+        self.synth_mix.store(settings.synth_mix.to_bits(), Ordering::Relaxed);
+        self.mode.store(mode_to_u32(settings.mode), Ordering::Relaxed);
+        self.target.store(target_to_u32(settings.target), Ordering::Relaxed);
+        for (word, value) in self
+            .correction_strength
+            .iter()
+            .zip(strength_to_words(settings.correction_strength))
+        {
+            word.store(value, Ordering::Relaxed);
+        }
+        for (word, value) in self.vocoder_eq.iter().zip(eq_to_words(settings.vocoder_eq)) {
+            word.store(value, Ordering::Relaxed);
+        }
+
+        self.sequence.store(sequence.wrapping_add(2), Ordering::Release);
+    }
+
+    /// Publishes a new key, keeping the other settings
+    pub fn set_key(&self, key: Key) {
+        self.set_settings(MusicalSettings { key, ..self.settings() });
+    }
+
+    /// Publishes a new target note, keeping the other settings
+    pub fn set_note(&self, note: Note) {
+        self.set_settings(MusicalSettings { note, ..self.settings() });
+    }
+
+    /// Publishes a new octave shift, keeping the other settings
+    pub fn set_octave_shift(&self, octave_shift: OctaveShift) {
+        self.set_settings(MusicalSettings { octave_shift, ..self.settings() });
+    }
+
+    /// Publishes a new formant shift mode, keeping the other settings
+    pub fn set_formant(&self, formant: Formant) {
+        self.set_settings(MusicalSettings { formant, ..self.settings() });
+    }
+
+    /// Publishes the notes the correction snaps to, e.g. the chord of the current
+    /// bar, keeping the other settings
+    pub fn set_target(&self, target: TargetSource) {
+        self.set_settings(MusicalSettings { target, ..self.settings() });
+    }
+
+    /// Publishes a new dry-mode synth mix, keeping the other settings
+    pub fn set_synth_mix(&self, synth_mix: f32) {
+        self.set_settings(MusicalSettings { synth_mix, ..self.settings() });
+    }
+
+    /// Publishes new vocoder band gains, keeping the other settings
+    pub fn set_vocoder_eq(&self, vocoder_eq: VocoderEq) {
+        self.set_settings(MusicalSettings { vocoder_eq, ..self.settings() });
+    }
+
+    /// Publishes a new processing mode, keeping the other settings
+    pub fn set_mode(&self, mode: ProcessingMode) {
+        self.set_settings(MusicalSettings { mode, ..self.settings() });
+    }
+
+    /// Returns the settings if an update newer than `seen` has been completely
+    /// written, updating `seen`
+    fn poll(&self, seen: &mut u32) -> Option<MusicalSettings> {
+        let before = self.sequence.load(Ordering::Acquire);
+        if before == *seen || before & 1 == 1 {
+            return None;
+        }
+        let settings = self.settings();
+        core::sync::atomic::fence(Ordering::Acquire);
+        if self.sequence.load(Ordering::Relaxed) != before {
+            // Preempted by a writer, try again next hop
+            return None;
+        }
+        *seen = before;
+        Some(settings)
+    }
+}
+
+const fn mode_to_u32(mode: ProcessingMode) -> u32 {
+    match mode {
+        ProcessingMode::Autotune => 0,
+        ProcessingMode::Vocode => 1,
+        ProcessingMode::Dry => 2,
+        ProcessingMode::Formant => 3,
+        ProcessingMode::TalkBox => 4,
+    }
+}
+
+const fn mode_from_u32(value: u32) -> ProcessingMode {
+    match value {
+        1 => ProcessingMode::Vocode,
+        2 => ProcessingMode::Dry,
+        3 => ProcessingMode::Formant,
+        4 => ProcessingMode::TalkBox,
+        _ => ProcessingMode::Autotune,
+    }
+}
+
+/// Key targets are stored as 0, chords as their mask with bit 16 set
+const fn target_to_u32(target: TargetSource) -> u32 {
+    match target {
+        TargetSource::Key => 0,
+        TargetSource::Chord(chord) => 1 << 16 | chord.mask() as u32,
+    }
+}
+
+const fn target_from_u32(value: u32) -> TargetSource {
+    if value & 1 << 16 == 0 {
+        TargetSource::Key
+    } else {
+        TargetSource::Chord(ChordSpec::from_mask(value as u16))
+    }
+}
+
+const fn strength_to_words(strength: CorrectionStrength) -> [u32; 3] {
+    let bytes = strength.to_bytes();
+    let mut words = [0; 3];
+    let mut i = 0;
+    while i < 3 {
+        words[i] = u32::from_le_bytes([
+            bytes[4 * i],
+            bytes[4 * i + 1],
+            bytes[4 * i + 2],
+            bytes[4 * i + 3],
+        ]);
+        i += 1;
+    }
+    words
+}
+
+fn strength_from_words(words: [u32; 3]) -> CorrectionStrength {
+    let mut bytes = [0; 12];
+    for (chunk, word) in bytes.chunks_exact_mut(4).zip(words) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    CorrectionStrength::from_bytes(bytes)
+}
+
+const fn eq_to_words(eq: VocoderEq) -> [u32; 4] {
+    let bytes = eq.to_bytes();
+    let mut words = [0; 4];
+    let mut i = 0;
+    while i < 4 {
+        words[i] = u32::from_le_bytes([
+            bytes[4 * i],
+            bytes[4 * i + 1],
+            bytes[4 * i + 2],
+            bytes[4 * i + 3],
+        ]);
+        i += 1;
+    }
+    words
+}
+
+fn eq_from_words(words: [u32; 4]) -> VocoderEq {
+    let mut bytes = [0; VOCODER_BANDS];
+    for (chunk, word) in bytes.chunks_exact_mut(4).zip(words) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    VocoderEq::from_bytes(bytes)
+}
+
+/// Audio-rate side of an engine controlled through [`SharedControls`].
+///
+/// The wrapper is owned by the audio task as a local resource; only the
+/// controls are shared, so control tasks never lock out the audio interrupt.
+///
+/// # Example
+///
+/// ```rust
+/// use synthphone_e_vocal_dsp::{
+///     CorrectionStrength, Engine1024, Formant, Key, MusicalSettings, Note, Octave, OctaveShift,
+///     ProcessingMode, TargetSource, VocalEffectsConfig, VocoderEq,
+///     engine::{SharedControls, SharedEngine},
+/// };
+///
+/// static CONTROLS: SharedControls = SharedControls::new(MusicalSettings {
+///     key: Key::CMajor,
+///     note: Note::Auto,
+///     octave: Octave::Middle,
+///     held_note_cents: 0.0,
+///     octave_shift: OctaveShift::None,
+///     formant: Formant::None,
+///     vocoder_formant_semitones: 0.0,
+///     pitch_shift_semitones: 0.0,
+///     synth_mix: 0.04,
+///     mode: ProcessingMode::Autotune,
+///     target: TargetSource::Key,
+///     correction_strength: CorrectionStrength::FULL,
+///     vocoder_eq: VocoderEq::FLAT,
+/// });
+///
+/// // Audio task
+/// let engine = Engine1024::new(VocalEffectsConfig::default(), CONTROLS.settings());
+/// let mut shared = SharedEngine::new(engine, &CONTROLS);
+///
+/// // Control task
+/// CONTROLS.set_key(Key::CSharpMajor);
+///
+/// let input = [0.0f32; 256];
+/// let mut output = [0.0f32; 256];
+/// shared.process_hop(&input, None, &mut output).unwrap();
+/// assert_eq!(shared.engine().settings().key, Key::CSharpMajor);
+/// ```
+pub struct SharedEngine<'a, const N: usize, const HALF_N: usize, F>
+where
+    F: DynFft<N, HALF_N>,
+{
+    engine: Engine<N, HALF_N, F>,
+    controls: &'a SharedControls,
+    seen: u32,
+}
+
+impl<'a, const N: usize, const HALF_N: usize, F> SharedEngine<'a, N, HALF_N, F>
+where
+    F: DynFft<N, HALF_N>,
+{
+    /// Wraps an engine controlled by `controls`
+    ///
+    /// Settings already published on `controls` are applied on the first hop.
+    pub fn new(engine: Engine<N, HALF_N, F>, controls: &'a SharedControls) -> Self {
+        Self { engine, controls, seen: u32::MAX }
+    }
+
+    /// Returns the wrapped engine
+    pub fn engine(&self) -> &Engine<N, HALF_N, F> {
+        &self.engine
+    }
+
+    /// Returns the shared controls
+    pub fn controls(&self) -> &'a SharedControls {
+        self.controls
+    }
+
+    /// Applies pending control updates, then processes one hop.
+    ///
+    /// See [`Engine::process_hop`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VocalEffectsError::BufferSizeMismatch`] if any buffer is not exactly
+    /// one hop long.
+    pub fn process_hop(
+        &mut self,
+        input: &[f32],
+        carrier: Option<&[f32]>,
+        output: &mut [f32],
+    ) -> Result<(), VocalEffectsError> {
+        if let Some(settings) = self.controls.poll(&mut self.seen) {
+            if settings != *self.engine.settings() {
+                self.engine.set_settings(settings);
+            }
+        }
+        self.engine.process_hop(input, carrier, output)
+    }
+
+    /// Clears all processing state of the wrapped engine
+    pub fn reset(&mut self) {
+        self.engine.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Engine512, VocalEffectsConfig};
+
+    #[test]
+    fn test_controls_round_trip() {
+        let controls = SharedControls::default();
+        assert_eq!(controls.settings(), MusicalSettings::default());
+
+        let settings = MusicalSettings {
+            key: Key::BMajor,
+            note: Note::Degree3,
+            octave: Octave::Low,
+            held_note_cents: -12.5,
+            octave_shift: OctaveShift::Down2,
+            formant: Formant::Higher,
+            vocoder_formant_semitones: -7.0,
+            pitch_shift_semitones: -2.5,
+            synth_mix: 0.5,
+            mode: ProcessingMode::Vocode,
+            target: TargetSource::Chord(crate::ChordSpec::from_pitch_classes(&[11, 3, 6])),
+            correction_strength: CorrectionStrength::from_bytes(core::array::from_fn(|i| {
+                i as u8 * 20
+            })),
+            vocoder_eq: {
+                let mut eq = VocoderEq::FLAT;
+                eq.set_band_db(0, -60.0);
+                eq.set_band_db(7, -4.0);
+                eq.set_band_db(15, 12.0);
+                eq
+            },
+        };
+        controls.set_settings(settings);
+        assert_eq!(controls.settings(), settings);
+
+        controls.set_target(TargetSource::Key);
+        assert_eq!(controls.settings().target, TargetSource::Key);
+    }
+
+    #[test]
+    fn test_updates_applied_between_hops() {
+        let controls = SharedControls::default();
+        let engine = Engine512::new(VocalEffectsConfig::default(), MusicalSettings::default());
+        let mut shared = SharedEngine::new(engine, &controls);
+        let mut output = [0.0f32; 128];
+
+        controls.set_key(Key::AMajor);
+        controls.set_mode(ProcessingMode::Dry);
+        assert_eq!(shared.engine().settings().key, Key::CMajor);
+
+        shared.process_hop(&[0.0; 128], None, &mut output).unwrap();
+        assert_eq!(shared.engine().settings().key, Key::AMajor);
+        assert_eq!(shared.engine().settings().mode, ProcessingMode::Dry);
+        assert!(shared.engine().is_crossfading());
+    }
+
+    #[test]
+    fn test_partial_update_is_not_applied() {
+        let controls = SharedControls::default();
+        let mut seen = u32::MAX;
+        assert!(controls.poll(&mut seen).is_some());
+        assert!(controls.poll(&mut seen).is_none());
+
+        // A writer that has started but not finished an update
+        controls.sequence.store(1, Ordering::Relaxed);
+        controls.key.store(9, Ordering::Relaxed);
+        assert!(controls.poll(&mut seen).is_none());
+
+        controls.sequence.store(2, Ordering::Relaxed);
+        assert_eq!(controls.poll(&mut seen).map(|s| s.key), Some(Key::BFlatMajor));
+    }
+}
@@ -0,0 +1,151 @@
+//! Settings slots for A/B switching.
+//!
+//! The engine keeps [`SETTINGS_SLOTS`] complete sets of musical settings, such
+//! as "natural correction" in one and "hard tune with formants up" in another.
+//! Recalling a slot stores the live settings in the active slot first, so edits
+//! made while a slot is active are kept, and crossfades to the recalled
+//! settings like a mode change. [`Engine::toggle`] flips between the last two
+//! slots, which suits a single footswitch.
+
+use crate::{MusicalSettings, VocalEffectsError, dsp::DynFft};
+
+use super::Engine;
+
+/// Number of settings slots of an [`Engine`]
+pub const SETTINGS_SLOTS: usize = 4;
+
+impl<const N: usize, const HALF_N: usize, F> Engine<N, HALF_N, F>
+where
+    F: DynFft<N, HALF_N>,
+{
+    /// Index of the slot the live settings belong to
+    pub fn active_slot(&self) -> usize {
+        self.active_slot
+    }
+
+    /// Settings stored in `slot`, the live settings for the active slot
+    pub fn slot(&self, slot: usize) -> Option<&MusicalSettings> {
+        if slot == self.active_slot {
+            return Some(&self.settings);
+        }
+        self.slots.get(slot)
+    }
+
+    /// Stores `settings` in `slot`
+    ///
+    /// Storing into the active slot changes the live settings, like
+    /// [`Engine::set_settings`]. Every slot starts with the settings the engine
+    /// was created with.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VocalEffectsError::InvalidConfiguration`] if there is no such slot.
+    pub fn store_slot(
+        &mut self,
+        slot: usize,
+        settings: MusicalSettings,
+    ) -> Result<(), VocalEffectsError> {
+        if slot >= SETTINGS_SLOTS {
+            return Err(VocalEffectsError::InvalidConfiguration);
+        }
+        if slot == self.active_slot {
+            self.set_settings(settings);
+        } else {
+            self.slots[slot] = settings;
+        }
+        Ok(())
+    }
+
+    /// Switches to the settings of `slot`, crossfading from the live settings
+    ///
+    /// The live settings are kept in the slot being left. The crossfade lasts
+    /// [`VocalEffectsConfig::mode_crossfade_hops`](crate::VocalEffectsConfig::mode_crossfade_hops)
+    /// hops, whatever changes; with resample-and-stretch shifting the outgoing
+    /// slot fades out at the transposition of the incoming one. Recalling the
+    /// active slot does nothing.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VocalEffectsError::InvalidConfiguration`] if there is no such slot.
+    pub fn recall_slot(&mut self, slot: usize) -> Result<(), VocalEffectsError> {
+        if slot >= SETTINGS_SLOTS {
+            return Err(VocalEffectsError::InvalidConfiguration);
+        }
+        if slot == self.active_slot {
+            return Ok(());
+        }
+        let outgoing = self.settings;
+        self.slots[self.active_slot] = outgoing;
+        self.previous_slot = self.active_slot;
+        self.active_slot = slot;
+        self.settings = self.slots[slot];
+        if outgoing != self.settings {
+            self.begin_crossfade(outgoing.mode, Some(outgoing), self.config.mode_crossfade_hops);
+        }
+        Ok(())
+    }
+
+    /// Switches back to the slot recalled before the active one, slot 1 at first
+    pub fn toggle(&mut self) {
+        // The previous slot is always in range
+        let _ = self.recall_slot(self.previous_slot);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Engine1024, Formant, Note, ProcessingMode, VocalEffectsConfig};
+
+    #[test]
+    fn test_toggle_flips_between_slots() {
+        let natural = MusicalSettings::default();
+        let hard = MusicalSettings { formant: Formant::Higher, ..natural };
+        let mut engine = Engine1024::new(VocalEffectsConfig::default(), natural);
+        assert_eq!((engine.active_slot(), engine.slot(1)), (0, Some(&natural)));
+
+        engine.store_slot(1, hard).unwrap();
+        engine.toggle();
+        assert_eq!((engine.active_slot(), *engine.settings()), (1, hard));
+        assert!(engine.is_crossfading());
+
+        // Edits stick to the active slot
+        engine.set_mode(ProcessingMode::Dry);
+        engine.toggle();
+        assert_eq!((engine.active_slot(), *engine.settings()), (0, natural));
+        assert_eq!(engine.slot(1).unwrap().mode, ProcessingMode::Dry);
+
+        engine.recall_slot(3).unwrap();
+        engine.toggle();
+        assert_eq!(engine.active_slot(), 0);
+        assert!(engine.recall_slot(SETTINGS_SLOTS).is_err());
+        assert!(engine.store_slot(SETTINGS_SLOTS, hard).is_err());
+    }
+
+    #[test]
+    fn test_toggle_crossfades_without_mode_change() {
+        // A held note played entirely on the silent synth input mutes the voice
+        let sine = |n: usize| 0.5 * libm::sinf(n as f32 * 0.06);
+        let natural = MusicalSettings { mode: ProcessingMode::Dry, ..Default::default() };
+        let muted = MusicalSettings { note: Note::Degree1, synth_mix: 1.0, ..natural };
+        let mut engine = Engine1024::new(VocalEffectsConfig::default(), natural);
+        engine.store_slot(1, muted).unwrap();
+
+        let mut peaks = [0.0f32; 24];
+        for (block, peak) in peaks.iter_mut().enumerate() {
+            if block == 8 {
+                engine.toggle();
+            }
+            let input: [f32; 256] = core::array::from_fn(|i| sine(block * 256 + i));
+            let mut output = [0.0f32; 256];
+            engine.process_hop(&input, None, &mut output).unwrap();
+            *peak = output.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        }
+
+        // The voice fades out over the crossfade instead of with the last frame
+        let steady = peaks[7];
+        assert!(peaks[11] > 0.4 * steady && peaks[11] < 0.8 * steady, "{peaks:?}");
+        assert!(peaks[16] < 1e-3 * steady, "{peaks:?}");
+        assert!(!engine.is_crossfading());
+    }
+}
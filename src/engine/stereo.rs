@@ -0,0 +1,130 @@
+//! Stereo output of the engine.
+//!
+//! The input stays mono. [`Engine::process_hop_stereo`] places the processed
+//! voice with the engine's [`Placement`] using the constant-power pan law, so
+//! a centred voice comes out 3 dB down on each side, and runs the chorus, delay
+//! and reverb in stereo around it. Every stereo hop is checked for mono
+//! compatibility, see [`Engine::mono_compatibility`].
+
+use crate::{
+    VocalEffectsError,
+    dsp::DynFft,
+    stereo::{MonoReading, Placement},
+};
+
+use super::{Engine, FrameView};
+
+impl<const N: usize, const HALF_N: usize, F> Engine<N, HALF_N, F>
+where
+    F: DynFft<N, HALF_N>,
+{
+    /// Processes one hop of audio into a stereo pair
+    ///
+    /// `left` and `right` each receive one synthesis hop. Otherwise behaves
+    /// like [`Engine::process_hop`]; the engine can switch between mono and
+    /// stereo hops at any time.
+    ///
+    /// # Errors
+    ///
+    /// See [`Engine::process_hop`], with `right` checked like the output.
+    pub fn process_hop_stereo(
+        &mut self,
+        input: &[f32],
+        carrier: Option<&[f32]>,
+        left: &mut [f32],
+        right: &mut [f32],
+    ) -> Result<(), VocalEffectsError> {
+        self.process_hop_placed(
+            input,
+            carrier,
+            left,
+            Some(right),
+            &mut |_: &FrameView<'_>| {},
+            None,
+        )
+    }
+
+    /// Position and spread of the voice in stereo hops
+    pub fn placement(&self) -> Placement {
+        self.placement
+    }
+
+    /// Moves the voice, from the next stereo hop
+    ///
+    /// Pan and width are clamped to their ranges. Pan jumps rather than glides,
+    /// so move it in small steps while the voice sounds.
+    pub fn set_placement(&mut self, placement: Placement) {
+        self.placement = placement.clamped();
+    }
+
+    /// Mono compatibility of the last stereo hop, [`MonoReading::MONO`] before the first
+    pub fn mono_compatibility(&self) -> MonoReading {
+        self.mono_reading
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Engine1024, MusicalSettings, ProcessingMode, ReverbSettings, VocalEffectsConfig};
+
+    use super::*;
+
+    fn run(engine: &mut Engine1024, hops: usize) -> ([f32; 256], [f32; 256]) {
+        let (mut left, mut right) = ([0.0f32; 256], [0.0f32; 256]);
+        for hop in 0..hops {
+            let input: [f32; 256] =
+                core::array::from_fn(|i| 0.5 * libm::sinf((hop * 256 + i) as f32 * 0.05));
+            engine.process_hop_stereo(&input, None, &mut left, &mut right).unwrap();
+        }
+        (left, right)
+    }
+
+    #[test]
+    fn test_pan_places_the_voice() {
+        let settings = MusicalSettings { mode: ProcessingMode::Dry, ..Default::default() };
+        let mut engine = Engine1024::new(VocalEffectsConfig::default(), settings);
+        let (left, right) = run(&mut engine, 8);
+        assert!(left.iter().any(|&sample| sample != 0.0));
+        assert!(left.iter().zip(&right).all(|(l, r)| (l - r).abs() < 1e-6));
+        assert!((engine.mono_compatibility().correlation - 1.0).abs() < 1e-5);
+
+        // Hard left leaves the right side silent and keeps the power of the centre
+        let power = |samples: &[f32]| samples.iter().map(|sample| sample * sample).sum::<f32>();
+        let centred = power(&left) + power(&right);
+        engine.set_placement(Placement { pan: -1.0, width: 1.0 });
+        let (left, right) = run(&mut engine, 1);
+        assert!(right.iter().all(|sample| sample.abs() < 1e-6));
+        assert!((power(&left) / centred - 1.0).abs() < 0.05, "{} {centred}", power(&left));
+
+        engine.set_placement(Placement { pan: 3.0, width: -1.0 });
+        assert_eq!(engine.placement(), Placement { pan: 1.0, width: 0.0 });
+    }
+
+    #[test]
+    fn test_width_spreads_the_reverb() {
+        let config = VocalEffectsConfig::builder().reverb(0.7, 0.2, 0.8).build().unwrap();
+        let settings = MusicalSettings { mode: ProcessingMode::Dry, ..Default::default() };
+        let mut engine = Engine1024::new(config, settings);
+        let mut buffer = [0.0f32; 256];
+        assert_eq!(
+            engine.process_hop_stereo(&[0.0; 256], None, &mut buffer, &mut [0.0; 128]),
+            Err(VocalEffectsError::BufferSizeMismatch)
+        );
+
+        engine.set_placement(Placement { pan: 0.0, width: 0.0 });
+        run(&mut engine, 16);
+        assert!((engine.mono_compatibility().correlation - 1.0).abs() < 1e-4);
+
+        engine.set_placement(Placement { pan: 0.0, width: 2.0 });
+        run(&mut engine, 16);
+        let wide = engine.mono_compatibility();
+        assert!(wide.correlation < 0.9 && wide.mono_loss_db < 0.0, "{wide:?}");
+        assert_eq!(
+            engine.config().reverb,
+            Some(ReverbSettings { room_size: 0.7, damping: 0.2, mix: 0.8 })
+        );
+
+        engine.reset();
+        assert_eq!(engine.mono_compatibility(), MonoReading::MONO);
+    }
+}
@@ -2,6 +2,7 @@
 
 /// Errors that can occur during vocal effects processing
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum VocalEffectsError {
     /// Input/output buffer size doesn't match expected size
     BufferSizeMismatch,
@@ -11,6 +12,8 @@ pub enum VocalEffectsError {
     InvalidConfiguration,
     /// Processing failed due to invalid input
     ProcessingFailed,
+    /// Vocode or talk-box mode was given no carrier to play
+    MissingCarrier,
 }
 
 #[cfg(feature = "std")]
@@ -29,9 +32,237 @@ impl std::fmt::Display for VocalEffectsError {
             VocalEffectsError::ProcessingFailed => {
                 write!(f, "Vocal effects processing failed")
             }
+            VocalEffectsError::MissingCarrier => {
+                write!(f, "Vocode and talk-box modes need a carrier")
+            }
         }
     }
 }
 
 #[cfg(feature = "std")]
 impl std::error::Error for VocalEffectsError {}
+
+/// Reasons a [`VocalEffectsConfig`](crate::VocalEffectsConfig) is rejected by
+/// [`VocalEffectsConfigBuilder::build`](crate::config::VocalEffectsConfigBuilder::build)
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConfigError {
+    /// FFT size is not a power of two between 128 and 4096 (8192 with the
+    /// `fft-8192` feature)
+    UnsupportedFftSize,
+    /// Sample rate is not a positive finite number
+    InvalidSampleRate,
+    /// Hop ratio or synthesis hop ratio is outside 0.0625 to 0.5
+    InvalidHopRatio,
+    /// Retune speed is outside 0.0 to 1.0
+    InvalidRetuneSpeed,
+    /// Frequency range is empty or reaches above Nyquist
+    InvalidFrequencyRange,
+    /// Lifter cutoff is zero or longer than half the FFT size
+    InvalidLifterCutoff,
+    /// Formant envelope update interval is zero hops
+    InvalidEnvelopeUpdateInterval,
+    /// Formant morph amount is outside 0.0 to 1.0
+    InvalidFormantMorph,
+    /// Wet/dry mix is outside 0.0 to 1.0
+    InvalidWetDry,
+    /// Glide time is negative or glide rate is not positive
+    InvalidGlide,
+    /// Scoop or fall depth or time is negative or not finite
+    InvalidOrnament,
+    /// Transient threshold is not a positive number of dB
+    InvalidTransientThreshold,
+    /// Spectral gate threshold is above 0 dB or a time is negative
+    InvalidSpectralGate,
+    /// Exciter crossover is above Nyquist, drive is not positive or mix is
+    /// outside 0.0 to 1.0
+    InvalidExciter,
+    /// Pre-emphasis coefficient is outside 0.0 to below 1.0
+    InvalidPreEmphasis,
+    /// Soft-clip threshold is not positive or knee is negative
+    InvalidSoftClip,
+    /// Phase re-anchor interval is negative or silence level above 0 dBFS
+    InvalidPhaseReanchor,
+    /// Voice activity gate hold time is negative or threshold above 0 dBFS
+    InvalidVoiceActivityGate,
+    /// Band-limit cutoff is not below Nyquist or transition is negative
+    InvalidBandLimit,
+    /// Pitch confidence threshold is outside 0.0 to 1.0
+    InvalidConfidenceThreshold,
+    /// Noise-fill threshold is above 0 dB or level outside 0.0 to 1.0
+    InvalidNoiseFill,
+    /// Vocoder envelope attack or release is negative or not finite
+    InvalidVocoderEnvelope,
+    /// Sibilance crossover is not below Nyquist or mix is outside 0.0 to 1.0
+    InvalidSibilanceBypass,
+    /// Sidechain threshold is above 0 dBFS, ratio below 1.0, or attack or
+    /// release negative
+    InvalidSidechainDuck,
+    /// Breath retention cutoff is not below Nyquist or level is outside 0.0 to 1.0
+    InvalidBreathRetention,
+    /// Frame size is below 64 samples or larger than the FFT
+    InvalidFrameSize,
+    /// Resample-and-stretch pitch shifting is combined with a separate synthesis hop
+    InvalidPitchShiftAlgorithm,
+    /// Delay time is not positive or too long, feedback is not below 1.0, or
+    /// damping or mix is outside 0.0 to 1.0
+    InvalidDelay,
+    /// Reverb room size, damping or mix is outside 0.0 to 1.0
+    InvalidReverb,
+    /// Chorus rate is negative, the sweep goes below 0 ms or beyond the longest
+    /// delay, or feedback or mix is out of range
+    InvalidChorus,
+    /// Saturation drive is not positive or mix is outside 0.0 to 1.0
+    InvalidSaturation,
+    /// Bitcrush depth is outside 1 to 24 bits
+    InvalidBitcrush,
+    /// Sample rate reduction factor is zero
+    InvalidSampleRateReduction,
+}
+
+impl From<ConfigError> for VocalEffectsError {
+    fn from(error: ConfigError) -> Self {
+        match error {
+            ConfigError::UnsupportedFftSize => VocalEffectsError::UnsupportedFftSize,
+            _ => VocalEffectsError::InvalidConfiguration,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::UnsupportedFftSize => {
+                let max = crate::config::MAX_FFT_SIZE;
+                write!(f, "FFT size must be a power of two between 128 and {max}")
+            }
+            ConfigError::InvalidSampleRate => write!(f, "Sample rate must be positive"),
+            ConfigError::InvalidHopRatio => {
+                write!(f, "Hop ratio and synthesis hop ratio must be between 0.0625 and 0.5")
+            }
+            ConfigError::InvalidRetuneSpeed => {
+                write!(f, "Retune speed must be between 0.0 and 1.0")
+            }
+            ConfigError::InvalidFrequencyRange => {
+                write!(f, "Frequency range must be non-empty and below Nyquist")
+            }
+            ConfigError::InvalidLifterCutoff => {
+                write!(f, "Lifter cutoff must be between 1 and half the FFT size")
+            }
+            ConfigError::InvalidEnvelopeUpdateInterval => {
+                write!(f, "Envelope update interval must be at least one hop")
+            }
+            ConfigError::InvalidFormantMorph => {
+                write!(f, "Formant morph amount must be between 0.0 and 1.0")
+            }
+            ConfigError::InvalidWetDry => write!(f, "Wet/dry mix must be between 0.0 and 1.0"),
+            ConfigError::InvalidGlide => {
+                write!(f, "Glide time must not be negative and glide rate must be positive")
+            }
+            ConfigError::InvalidOrnament => {
+                write!(f, "Scoop and fall depths and times must not be negative")
+            }
+            ConfigError::InvalidTransientThreshold => {
+                write!(f, "Transient threshold must be a positive number of dB")
+            }
+            ConfigError::InvalidSpectralGate => {
+                write!(f, "Spectral gate threshold must not be above 0 dB nor times negative")
+            }
+            ConfigError::InvalidExciter => write!(
+                f,
+                "Exciter crossover must be below Nyquist, drive positive and mix between 0.0 and 1.0"
+            ),
+            ConfigError::InvalidPreEmphasis => {
+                write!(f, "Pre-emphasis coefficient must be at least 0.0 and below 1.0")
+            }
+            ConfigError::InvalidSoftClip => {
+                write!(f, "Soft-clip threshold must be positive and knee not negative")
+            }
+            ConfigError::InvalidBandLimit => {
+                write!(f, "Band-limit cutoff must be below Nyquist and transition not negative")
+            }
+            ConfigError::InvalidConfidenceThreshold => {
+                write!(f, "Pitch confidence threshold must be between 0.0 and 1.0")
+            }
+            ConfigError::InvalidNoiseFill => {
+                write!(
+                    f,
+                    "Noise-fill threshold must not be above 0 dB and level between 0.0 and 1.0"
+                )
+            }
+            ConfigError::InvalidVocoderEnvelope => {
+                write!(f, "Vocoder envelope attack and release must not be negative")
+            }
+            ConfigError::InvalidSibilanceBypass => {
+                write!(f, "Sibilance crossover must be below Nyquist and mix between 0.0 and 1.0")
+            }
+            ConfigError::InvalidBreathRetention => {
+                write!(
+                    f,
+                    "Breath retention cutoff must be below Nyquist and level between 0.0 and 1.0"
+                )
+            }
+            ConfigError::InvalidSidechainDuck => {
+                write!(
+                    f,
+                    "Sidechain threshold must be at most 0 dBFS, ratio at least 1.0, and attack \
+                     and release not negative"
+                )
+            }
+            ConfigError::InvalidDelay => {
+                let max = crate::effects::delay::MAX_DELAY_SECONDS;
+                write!(
+                    f,
+                    "Delay time must be positive and at most {max} s, feedback between 0.0 and \
+                     below 1.0, and damping and mix between 0.0 and 1.0"
+                )
+            }
+            ConfigError::InvalidReverb => {
+                write!(f, "Reverb room size, damping and mix must be between 0.0 and 1.0")
+            }
+            ConfigError::InvalidChorus => {
+                let max = crate::effects::chorus::MAX_CHORUS_MS;
+                write!(
+                    f,
+                    "Chorus rate must not be negative, delay plus depth at most {max} ms with \
+                     depth below delay, feedback between -1.0 and 1.0 and mix between 0.0 and 1.0"
+                )
+            }
+            ConfigError::InvalidSaturation => {
+                write!(f, "Saturation drive must be positive and mix between 0.0 and 1.0")
+            }
+            ConfigError::InvalidBitcrush => {
+                let max = crate::effects::crush::MAX_BIT_DEPTH;
+                write!(f, "Bitcrush depth must be between 1 and {max} bits")
+            }
+            ConfigError::InvalidSampleRateReduction => {
+                write!(f, "Sample rate reduction factor must be at least 1")
+            }
+            ConfigError::InvalidFrameSize => {
+                write!(f, "Frame size must be between 64 samples and the FFT size")
+            }
+            ConfigError::InvalidPitchShiftAlgorithm => {
+                write!(
+                    f,
+                    "Resample-and-stretch pitch shifting needs equal analysis and synthesis hops"
+                )
+            }
+            ConfigError::InvalidPhaseReanchor => {
+                write!(
+                    f,
+                    "Phase re-anchor interval must not be negative and silence at most 0 dBFS"
+                )
+            }
+            ConfigError::InvalidVoiceActivityGate => {
+                write!(
+                    f,
+                    "Voice activity hold time must not be negative and threshold at most 0 dBFS"
+                )
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ConfigError {}
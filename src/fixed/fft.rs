@@ -0,0 +1,128 @@
+//! Radix-2 fixed-point FFT with Q15 twiddles.
+
+use super::trig::{SINE_TABLE_Q15, SINE_TABLE_SIZE};
+
+/// Largest FFT size the fixed-point backend supports (one twiddle per table entry)
+pub const MAX_FIXED_FFT_SIZE: usize = SINE_TABLE_SIZE;
+
+/// Complex value with `i32` parts
+///
+/// Holds Q15 samples with headroom for the `log2(N)` bits of growth of an
+/// unscaled forward transform.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ComplexQ31 {
+    pub re: i32,
+    pub im: i32,
+}
+
+/// Multiply by a Q15 coefficient
+#[inline(always)]
+fn mul_q15(value: i32, coefficient: i16) -> i64 {
+    value as i64 * coefficient as i64
+}
+
+fn bit_reverse<const N: usize>(buffer: &mut [ComplexQ31; N]) {
+    let bits = N.trailing_zeros();
+    for i in 0..N {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if j > i {
+            buffer.swap(i, j);
+        }
+    }
+}
+
+fn transform<const N: usize, const INVERSE: bool>(buffer: &mut [ComplexQ31; N]) {
+    const {
+        assert!(N.is_power_of_two() && N >= 2 && N <= MAX_FIXED_FFT_SIZE);
+    }
+    bit_reverse(buffer);
+
+    let mut length = 2;
+    while length <= N {
+        let half = length / 2;
+        let stride = SINE_TABLE_SIZE / length;
+        for start in (0..N).step_by(length) {
+            for k in 0..half {
+                let index = k * stride;
+                let cos = SINE_TABLE_Q15[(index + SINE_TABLE_SIZE / 4) % SINE_TABLE_SIZE];
+                // exp(-2πik/len) forward, exp(+2πik/len) inverse
+                let sin = if INVERSE {
+                    SINE_TABLE_Q15[index]
+                } else {
+                    -SINE_TABLE_Q15[index]
+                };
+
+                let a = buffer[start + k];
+                let b = buffer[start + k + half];
+                let t_re = ((mul_q15(b.re, cos) - mul_q15(b.im, sin)) >> 15) as i32;
+                let t_im = ((mul_q15(b.re, sin) + mul_q15(b.im, cos)) >> 15) as i32;
+
+                if INVERSE {
+                    // Halve every stage so the result is scaled by 1/N without overflowing
+                    buffer[start + k] =
+                        ComplexQ31 { re: (a.re + t_re + 1) >> 1, im: (a.im + t_im + 1) >> 1 };
+                    buffer[start + k + half] =
+                        ComplexQ31 { re: (a.re - t_re + 1) >> 1, im: (a.im - t_im + 1) >> 1 };
+                } else {
+                    buffer[start + k] = ComplexQ31 { re: a.re + t_re, im: a.im + t_im };
+                    buffer[start + k + half] = ComplexQ31 { re: a.re - t_re, im: a.im - t_im };
+                }
+            }
+        }
+        length *= 2;
+    }
+}
+
+/// Forward FFT in place, unscaled
+///
+/// Q15 input grows by up to `log2(N)` bits, which `i32` holds for every
+/// supported size.
+pub fn fft_q31<const N: usize>(buffer: &mut [ComplexQ31; N]) {
+    transform::<N, false>(buffer);
+}
+
+/// Inverse FFT in place, scaled by `1/N` (the inverse of [`fft_q31`])
+pub fn ifft_q31<const N: usize>(buffer: &mut [ComplexQ31; N]) {
+    transform::<N, true>(buffer);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::f32::consts::PI;
+
+    fn sine_frame<const N: usize>(bin: usize, amplitude: f32) -> [ComplexQ31; N] {
+        core::array::from_fn(|i| ComplexQ31 {
+            re: (amplitude * 32767.0 * libm::sinf(2.0 * PI * (bin * i) as f32 / N as f32)) as i32,
+            im: 0,
+        })
+    }
+
+    #[test]
+    fn test_sine_lands_in_its_bin() {
+        let mut buffer = sine_frame::<512>(10, 0.5);
+        fft_q31(&mut buffer);
+
+        // A real sine of amplitude A puts A·N/2 in bin k, split between +k and -k
+        let expected = 0.5 * 32767.0 * 256.0;
+        assert!((buffer[10].im as f32 + expected).abs() < expected * 1e-3);
+        assert!((buffer[502].im as f32 - expected).abs() < expected * 1e-3);
+        for (i, bin) in buffer.iter().enumerate() {
+            if i != 10 && i != 502 {
+                assert!(bin.re.abs() < 200 && bin.im.abs() < 200, "bin {i}: {bin:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let original = sine_frame::<1024>(37, 0.9);
+        let mut buffer = original;
+        fft_q31(&mut buffer);
+        ifft_q31(&mut buffer);
+        for (output, input) in buffer.iter().zip(original.iter()) {
+            assert!((output.re - input.re).abs() <= 8, "{output:?} vs {input:?}");
+            assert!(output.im.abs() <= 8);
+        }
+    }
+}
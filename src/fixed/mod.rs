@@ -0,0 +1,112 @@
+//! Fixed-point processing path for MCUs without an FPU.
+//!
+//! Samples are Q15 (`i16`, full scale ±1.0), spectra are Q31-range `i32`
+//! accumulators and phases are binary angles (`i16`, one turn = 65536) so that
+//! phase wrapping is plain integer overflow. The per-bin analysis, shifting and
+//! synthesis loops contain no floating point; only the once-per-frame pitch
+//! decision reuses the `f32` note tables.
+//!
+//! Enabled with the `fixed-point` feature.
+
+pub mod fft;
+pub mod pitch_correction;
+pub mod trig;
+
+pub use fft::{ComplexQ31, MAX_FIXED_FFT_SIZE, fft_q31, ifft_q31};
+pub use pitch_correction::{ProcessingStateQ15, process_pitch_correction_q15};
+pub use trig::{Angle, atan2_angle, cos_q15, sin_q15};
+
+/// Signed Q1.15 sample, `-1.0..1.0`
+pub type Q15 = i16;
+
+/// Signed Q1.31 value, `-1.0..1.0`
+pub type Q31 = i32;
+
+/// Convert an `f32` sample to Q15, saturating outside `-1.0..1.0`
+#[inline(always)]
+pub fn f32_to_q15(sample: f32) -> Q15 {
+    (sample * 32768.0).clamp(i16::MIN as f32, i16::MAX as f32) as Q15
+}
+
+/// Convert a Q15 sample to `f32`
+#[inline(always)]
+pub fn q15_to_f32(sample: Q15) -> f32 {
+    sample as f32 / 32768.0
+}
+
+/// Convert an `f32` value to Q31, saturating outside `-1.0..1.0`
+#[inline(always)]
+pub fn f32_to_q31(value: f32) -> Q31 {
+    // `as` saturates, and 2^31 is not representable in f32 below i32::MAX anyway
+    (value as f64 * 2_147_483_648.0) as Q31
+}
+
+/// Convert a Q31 value to `f32`
+#[inline(always)]
+pub fn q31_to_f32(value: Q31) -> f32 {
+    (value as f64 / 2_147_483_648.0) as f32
+}
+
+/// Multiply two Q15 values with rounding and saturation
+#[inline(always)]
+pub fn q15_mul(a: Q15, b: Q15) -> Q15 {
+    let product = (a as i32 * b as i32 + (1 << 14)) >> 15;
+    product.clamp(i16::MIN as i32, i16::MAX as i32) as Q15
+}
+
+/// Saturate an accumulator to a Q15 sample
+#[inline(always)]
+pub fn saturate_q15(value: i32) -> Q15 {
+    value.clamp(i16::MIN as i32, i16::MAX as i32) as Q15
+}
+
+/// Create a Q15 Hann window at compile time
+pub const fn hann_window_q15<const N: usize>() -> [Q15; N] {
+    let window = crate::dsp::windowing::create_hann_window::<N>();
+    let mut output = [0; N];
+    let mut i = 0;
+    while i < N {
+        let value = window[i] * 32768.0;
+        output[i] = if value >= 32767.0 {
+            i16::MAX
+        } else {
+            value as i16
+        };
+        i += 1;
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_q15_conversions() {
+        assert_eq!(f32_to_q15(0.5), 16384);
+        assert_eq!(f32_to_q15(-1.0), i16::MIN);
+        assert_eq!(f32_to_q15(2.0), i16::MAX);
+        assert_eq!(q15_to_f32(-16384), -0.5);
+        assert_eq!(f32_to_q31(0.25), 1 << 29);
+        assert_eq!(f32_to_q31(-3.0), i32::MIN);
+        assert_eq!(q31_to_f32(1 << 30), 0.5);
+    }
+
+    #[test]
+    fn test_q15_mul() {
+        assert_eq!(q15_mul(16384, 16384), 8192);
+        assert_eq!(q15_mul(-16384, 16384), -8192);
+        assert_eq!(q15_mul(i16::MIN, i16::MIN), i16::MAX);
+    }
+
+    #[test]
+    fn test_hann_window_q15_matches_float() {
+        let window = hann_window_q15::<512>();
+        let reference = crate::dsp::windowing::HANN_WINDOW_512;
+        for (fixed, float) in window.iter().zip(reference.iter()) {
+            // The float window peaks slightly above 1.0, which Q15 saturates
+            let float = float.min(q15_to_f32(i16::MAX));
+            assert!((q15_to_f32(*fixed) - float).abs() < 1e-4, "{fixed} vs {float}");
+        }
+    }
+}
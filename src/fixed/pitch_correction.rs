@@ -0,0 +1,253 @@
+//! Fixed-point phase vocoder pitch correction.
+
+use libm::fabsf;
+
+use super::{
+    Angle, ComplexQ31, Q15, atan2_angle, cos_q15, fft_q31, hann_window_q15, ifft_q31, saturate_q15,
+    sin_q15,
+};
+use crate::{
+    MusicalSettings, VocalEffectsConfig,
+    dsp::{frequency_analysis::parabolic_peak_offset, pitch_shift_for_frequency},
+};
+
+/// Per-stream state of the fixed-point phase vocoder
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProcessingStateQ15<const N: usize> {
+    /// Analysis phases of the previous frame
+    pub last_input_phases: [Angle; N],
+    /// Synthesis phases of the previous frame
+    pub last_output_phases: [Angle; N],
+    /// Pitch shift ratio applied to the previous frame
+    pub pitch_shift_ratio: f32,
+}
+
+impl<const N: usize> Default for ProcessingStateQ15<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> ProcessingStateQ15<N> {
+    /// Create a state with zeroed phases and a unity pitch shift ratio
+    pub const fn new() -> Self {
+        Self { last_input_phases: [0; N], last_output_phases: [0; N], pitch_shift_ratio: 1.0 }
+    }
+
+    /// Clear all phases and return the pitch shift ratio to unity
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+/// Fixed-point pitch correction of one frame
+///
+/// The Q15 counterpart of the autotune path of
+/// [`process_vocal_effects_512`](crate::process_vocal_effects_512) and friends:
+/// the returned frame is windowed and ready for overlap-add. Formant settings are
+/// ignored and the fundamental is always the loudest bin.
+///
+/// # Example
+///
+/// ```rust
+/// use synthphone_e_vocal_dsp::{
+///     MusicalSettings, VocalEffectsConfig,
+///     fixed::{ProcessingStateQ15, process_pitch_correction_q15},
+/// };
+///
+/// let config = VocalEffectsConfig::new(512, 48000.0, 0.25).unwrap();
+/// let settings = MusicalSettings::default();
+/// let mut state = ProcessingStateQ15::<512>::new();
+///
+/// let frame = [0i16; 512];
+/// let output = process_pitch_correction_q15::<512, 256>(&frame, &mut state, &config, &settings);
+/// assert!(output.iter().all(|&s| s == 0));
+/// ```
+pub fn process_pitch_correction_q15<const N: usize, const HALF_N: usize>(
+    input: &[Q15; N],
+    state: &mut ProcessingStateQ15<N>,
+    config: &VocalEffectsConfig,
+    settings: &MusicalSettings,
+) -> [Q15; N] {
+    // 2/3 in Q15
+    const GAIN_COMPENSATION: i64 = 21845;
+
+    let window = &const { hann_window_q15::<N>() };
+    let hop_size = (N as f32 * config.hop_ratio) as usize;
+    let bin_width = config.sample_rate / N as f32;
+
+    // Apply windowing
+    let mut spectrum = [ComplexQ31::default(); N];
+    for i in 0..N {
+        spectrum[i].re = (input[i] as i32 * window[i] as i32) >> 15;
+    }
+
+    fft_q31(&mut spectrum);
+
+    // Analysis: magnitudes and frequencies in Q16 bins
+    let mut magnitudes = [0u32; HALF_N];
+    let mut frequencies = [0i32; HALF_N];
+    for i in 0..HALF_N {
+        let bin = spectrum[i];
+        let power = (bin.re as i64 * bin.re as i64 + bin.im as i64 * bin.im as i64) as u64;
+        magnitudes[i] = power.isqrt() as u32;
+
+        let phase = atan2_angle(bin.im, bin.re);
+        // Phase advance of the bin centre over one hop, modulo one turn
+        let expected = ((((i * hop_size) % N) << 16) / N) as u16 as Angle;
+        let deviation = phase.wrapping_sub(state.last_input_phases[i]).wrapping_sub(expected);
+        frequencies[i] = ((i as i32) << 16) + deviation as i32 * N as i32 / hop_size as i32;
+        state.last_input_phases[i] = phase;
+    }
+
+    // Pitch decision, once per frame
+    let search_bins = (HALF_N / config.pitch_decimation.factor()).max(1);
+    let mut fundamental_index = 0;
+    for i in 1..search_bins {
+        if magnitudes[i] > magnitudes[fundamental_index] {
+            fundamental_index = i;
+        }
+    }
+    let (offset, neighbour) = if fundamental_index > 0 && fundamental_index + 1 < HALF_N {
+        let neighbourhood = [
+            magnitudes[fundamental_index - 1] as f32,
+            magnitudes[fundamental_index] as f32,
+            magnitudes[fundamental_index + 1] as f32,
+        ];
+        let offset = parabolic_peak_offset(&neighbourhood, 1);
+        let neighbour = if offset < 0.0 {
+            fundamental_index - 1
+        } else {
+            fundamental_index + 1
+        };
+        (offset, neighbour)
+    } else {
+        (0.0, fundamental_index)
+    };
+    let peak_bin = fundamental_index as f32 + offset;
+    let current = frequencies[fundamental_index] as f32 / 65536.0;
+    let next = frequencies[neighbour] as f32 / 65536.0;
+    let agrees = |bin: f32| fabsf(bin - peak_bin) <= 1.0;
+    let detected_bin = if !agrees(current) {
+        peak_bin
+    } else if agrees(next) {
+        current + (next - current) * fabsf(offset)
+    } else {
+        current
+    };
+    state.pitch_shift_ratio = pitch_shift_for_frequency(
+        detected_bin * bin_width,
+        state.pitch_shift_ratio,
+        config,
+        settings,
+    );
+    let ratio = (state.pitch_shift_ratio * 65536.0) as i64;
+
+    // Apply spectral shift
+    let mut synthesis_magnitudes = [0u32; HALF_N];
+    let mut synthesis_frequencies = [0i32; HALF_N];
+    for i in 0..HALF_N {
+        if magnitudes[i] == 0 {
+            continue;
+        }
+        let new_bin = (((i as i64 * ratio) + 0x8000) >> 16) as usize;
+        let new_bin = new_bin.min(HALF_N - 1);
+        synthesis_magnitudes[new_bin] = magnitudes[i];
+        synthesis_frequencies[new_bin] = ((frequencies[i] as i64 * ratio) >> 16) as i32;
+    }
+
+    // Synthesis phase reconstruction
+    let mut spectrum = [ComplexQ31::default(); N];
+    for i in 0..HALF_N {
+        // A frequency of f bins advances the phase by f·hop/N turns per hop
+        let increment = (synthesis_frequencies[i] as i64 * hop_size as i64 / N as i64) as Angle;
+        let phase = state.last_output_phases[i].wrapping_add(increment);
+        let magnitude = synthesis_magnitudes[i] as i64;
+        let re = ((magnitude * cos_q15(phase) as i64) >> 15) as i32;
+        let im = ((magnitude * sin_q15(phase) as i64) >> 15) as i32;
+        spectrum[i] = ComplexQ31 { re, im };
+        if i > 0 {
+            spectrum[N - i] = ComplexQ31 { re, im: -im };
+        }
+        state.last_output_phases[i] = phase;
+    }
+
+    ifft_q31(&mut spectrum);
+
+    let mut output = [0; N];
+    for i in 0..N {
+        let sample = (spectrum[i].re as i64 * window[i] as i64) >> 15;
+        output[i] = saturate_q15(((sample * GAIN_COMPENSATION) >> 15) as i32);
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixed::f32_to_q15;
+    use core::f32::consts::PI;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    fn sine_frame<const N: usize>(frequency: f32, start: usize) -> [Q15; N] {
+        core::array::from_fn(|i| {
+            f32_to_q15(0.5 * libm::sinf(2.0 * PI * frequency * (start + i) as f32 / SAMPLE_RATE))
+        })
+    }
+
+    #[test]
+    fn test_ratio_converges_to_nearest_note() {
+        let config = VocalEffectsConfig::new(512, SAMPLE_RATE, 0.25).unwrap();
+        let settings = MusicalSettings::default();
+        let mut state = ProcessingStateQ15::<512>::new();
+
+        for frame in 0..16 {
+            let input = sine_frame::<512>(430.0, frame * config.hop_size);
+            let output =
+                process_pitch_correction_q15::<512, 256>(&input, &mut state, &config, &settings);
+            assert!(output.iter().any(|&s| s != 0));
+        }
+
+        // 430 Hz snaps to A4 in C major
+        let expected = 440.0 / 430.0;
+        assert!((state.pitch_shift_ratio - expected).abs() < 2e-3, "{}", state.pitch_shift_ratio);
+    }
+
+    #[test]
+    fn test_matches_float_path_on_an_in_tune_note() {
+        let config = VocalEffectsConfig::new(512, SAMPLE_RATE, 0.25).unwrap();
+        let settings = MusicalSettings::default();
+        let mut fixed_state = ProcessingStateQ15::<512>::new();
+        let mut float_state = crate::ProcessingState::<512>::new();
+
+        for frame in 0..8 {
+            let input = sine_frame::<512>(440.0, frame * config.hop_size);
+            let fixed = process_pitch_correction_q15::<512, 256>(
+                &input,
+                &mut fixed_state,
+                &config,
+                &settings,
+            );
+
+            let mut buffer: [f32; 512] =
+                core::array::from_fn(|i| crate::fixed::q15_to_f32(input[i]));
+            let float = crate::vocal_effects::process_frame(
+                &mut crate::dsp::Fft512,
+                &mut crate::Workspace::new(),
+                &mut buffer,
+                None,
+                &mut float_state,
+                &config,
+                &settings,
+            )
+            .unwrap();
+
+            if frame >= 2 {
+                for (fixed, float) in fixed.iter().zip(float.iter()) {
+                    assert!((crate::fixed::q15_to_f32(*fixed) - float).abs() < 5e-3);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! Table-based trigonometry on binary angles.
+
+use core::f32::consts::PI;
+
+use super::Q15;
+
+/// Binary angle: the full `i16` range is one turn, so `i16::MIN` is `-π` and
+/// wrapping arithmetic wraps the phase.
+pub type Angle = i16;
+
+/// Angle of a quarter turn (`π / 2`)
+pub const QUARTER_TURN: i32 = 16384;
+
+const SINE_TABLE_BITS: u32 = 10;
+
+/// Number of entries in one period of the sine table
+pub const SINE_TABLE_SIZE: usize = 1 << SINE_TABLE_BITS;
+
+/// Sine of `x` for `x` in `[0, π / 2]`
+const fn sine_first_quadrant(x: f32) -> f32 {
+    let x2 = x * x;
+    // Taylor series to x^13, accurate well below one Q15 LSB on this interval
+    let mut term = x;
+    let mut sum = x;
+    let mut n = 1;
+    while n <= 6 {
+        term = -term * x2 / ((2 * n) * (2 * n + 1)) as f32;
+        sum += term;
+        n += 1;
+    }
+    sum
+}
+
+const fn generate_sine_table() -> [Q15; SINE_TABLE_SIZE] {
+    let mut table = [0; SINE_TABLE_SIZE];
+    let quarter = SINE_TABLE_SIZE / 4;
+
+    // First two quadrants from the symmetric first-quadrant series
+    let mut i = 0;
+    while i <= quarter {
+        let value = sine_first_quadrant(PI / 2.0 * i as f32 / quarter as f32) * 32768.0;
+        let sample = if value >= 32767.0 {
+            i16::MAX
+        } else {
+            (value + 0.5) as i16
+        };
+        table[i] = sample;
+        table[2 * quarter - i] = sample;
+        i += 1;
+    }
+
+    // The second half period is the negated first
+    let mut i = 1;
+    while i < 2 * quarter {
+        table[2 * quarter + i] = -table[i];
+        i += 1;
+    }
+    table
+}
+
+/// One period of a Q15 sine, indexed by the top bits of an [`Angle`]
+pub static SINE_TABLE_Q15: [Q15; SINE_TABLE_SIZE] = generate_sine_table();
+
+/// Sine of a binary angle, linearly interpolated from [`SINE_TABLE_Q15`]
+#[inline]
+pub fn sin_q15(angle: Angle) -> Q15 {
+    const FRACTION_BITS: u32 = 16 - SINE_TABLE_BITS;
+    let position = angle as u16 as usize;
+    let index = position >> FRACTION_BITS;
+    let fraction = (position & ((1 << FRACTION_BITS) - 1)) as i32;
+    let a = SINE_TABLE_Q15[index] as i32;
+    let b = SINE_TABLE_Q15[(index + 1) & (SINE_TABLE_SIZE - 1)] as i32;
+    (a + (((b - a) * fraction) >> FRACTION_BITS)) as Q15
+}
+
+/// Cosine of a binary angle
+#[inline]
+pub fn cos_q15(angle: Angle) -> Q15 {
+    sin_q15(angle.wrapping_add(QUARTER_TURN as i16))
+}
+
+/// Arctangent of `ratio` in `[0, 1]` (Q15), as an angle in `[0, π / 4]`
+#[inline]
+fn atan_first_octant(ratio: i64) -> i32 {
+    // atan(z) ≈ π/4·z + z(1 - z)(0.2447 + 0.0663z), max error ~0.0015 rad.
+    // Coefficients are pre-scaled to binary angle units (65536 / 2π).
+    const EIGHTH_TURN: i64 = 8192;
+    const C1: i64 = 2552;
+    const C2: i64 = 692;
+    let linear = (EIGHTH_TURN * ratio) >> 15;
+    let bend = (ratio * ((1 << 15) - ratio)) >> 15;
+    let correction = (bend * (C1 + ((C2 * ratio) >> 15))) >> 15;
+    (linear + correction) as i32
+}
+
+/// Four-quadrant arctangent of `y / x` as a binary angle
+///
+/// Returns `0` for the origin.
+pub fn atan2_angle(y: i32, x: i32) -> Angle {
+    if x == 0 && y == 0 {
+        return 0;
+    }
+    let ax = (x as i64).abs();
+    let ay = (y as i64).abs();
+    let mut angle = if ax >= ay {
+        atan_first_octant((ay << 15) / ax)
+    } else {
+        QUARTER_TURN - atan_first_octant((ax << 15) / ay)
+    };
+    if x < 0 {
+        angle = 2 * QUARTER_TURN - angle;
+    }
+    if y < 0 {
+        angle = -angle;
+    }
+    angle as u16 as Angle
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_radians(angle: Angle) -> f32 {
+        angle as f32 * PI / 32768.0
+    }
+
+    #[test]
+    fn test_sine_table_matches_libm() {
+        for (i, &value) in SINE_TABLE_Q15.iter().enumerate() {
+            let expected = libm::sinf(2.0 * PI * i as f32 / SINE_TABLE_SIZE as f32);
+            assert!((value as f32 / 32768.0 - expected).abs() < 1e-4, "entry {i}");
+        }
+    }
+
+    #[test]
+    fn test_sin_cos_interpolation() {
+        for step in 0..=4096 {
+            let angle = (step * 16) as u16 as Angle;
+            let radians = to_radians(angle);
+            assert!((sin_q15(angle) as f32 / 32768.0 - libm::sinf(radians)).abs() < 2e-4);
+            assert!((cos_q15(angle) as f32 / 32768.0 - libm::cosf(radians)).abs() < 2e-4);
+        }
+    }
+
+    #[test]
+    fn test_atan2_angle_accuracy() {
+        assert_eq!(atan2_angle(0, 0), 0);
+        assert_eq!(atan2_angle(0, 1000), 0);
+        assert_eq!(atan2_angle(1000, 0), 16384);
+        assert_eq!(atan2_angle(0, -1000), i16::MIN);
+
+        for step in 0..360 {
+            let radians = (step as f32 - 180.0).to_radians() + 0.01;
+            let x = (libm::cosf(radians) * 1.0e6) as i32;
+            let y = (libm::sinf(radians) * 1.0e6) as i32;
+            let error = libm::atan2f(
+                libm::sinf(to_radians(atan2_angle(y, x)) - radians),
+                libm::cosf(to_radians(atan2_angle(y, x)) - radians),
+            );
+            assert!(error.abs() < 0.002, "{step} degrees: error {error}");
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Radix-2 complex FFT in `f64`.
+
+use core::f64::consts::PI;
+
+use libm::{cos, sin};
+
+/// Complex value with `f64` parts
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Complex64 {
+    pub re: f64,
+    pub im: f64,
+}
+
+fn transform<const N: usize, const HALF_N: usize, const INVERSE: bool>(
+    buffer: &mut [Complex64; N],
+) {
+    const {
+        assert!(N.is_power_of_two() && N >= 2 && HALF_N * 2 == N);
+    }
+
+    let bits = N.trailing_zeros();
+    for i in 0..N {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if j > i {
+            buffer.swap(i, j);
+        }
+    }
+
+    // exp(∓2πik/N), computed directly rather than by recurrence to avoid drift
+    let sign = if INVERSE { 1.0 } else { -1.0 };
+    let twiddles: [Complex64; HALF_N] = core::array::from_fn(|k| {
+        let angle = sign * 2.0 * PI * k as f64 / N as f64;
+        Complex64 { re: cos(angle), im: sin(angle) }
+    });
+
+    let mut length = 2;
+    while length <= N {
+        let half = length / 2;
+        let stride = N / length;
+        for start in (0..N).step_by(length) {
+            for k in 0..half {
+                let w = twiddles[k * stride];
+                let a = buffer[start + k];
+                let b = buffer[start + k + half];
+                let t = Complex64 { re: b.re * w.re - b.im * w.im, im: b.re * w.im + b.im * w.re };
+                buffer[start + k] = Complex64 { re: a.re + t.re, im: a.im + t.im };
+                buffer[start + k + half] = Complex64 { re: a.re - t.re, im: a.im - t.im };
+            }
+        }
+        length *= 2;
+    }
+
+    if INVERSE {
+        let scale = 1.0 / N as f64;
+        for value in buffer.iter_mut() {
+            value.re *= scale;
+            value.im *= scale;
+        }
+    }
+}
+
+/// Forward FFT in place, unscaled
+pub fn fft_f64<const N: usize, const HALF_N: usize>(buffer: &mut [Complex64; N]) {
+    transform::<N, HALF_N, false>(buffer);
+}
+
+/// Inverse FFT in place, scaled by `1/N`
+pub fn ifft_f64<const N: usize, const HALF_N: usize>(buffer: &mut [Complex64; N]) {
+    transform::<N, HALF_N, true>(buffer);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_and_bin_placement() {
+        let original: [Complex64; 256] = core::array::from_fn(|i| Complex64 {
+            re: cos(2.0 * PI * 5.0 * i as f64 / 256.0),
+            im: 0.0,
+        });
+        let mut buffer = original;
+        fft_f64::<256, 128>(&mut buffer);
+        assert!((buffer[5].re - 128.0).abs() < 1e-9);
+        assert!((buffer[251].re - 128.0).abs() < 1e-9);
+        assert!(buffer[6].re.abs() < 1e-9);
+
+        ifft_f64::<256, 128>(&mut buffer);
+        for (output, input) in buffer.iter().zip(original.iter()) {
+            assert!((output.re - input.re).abs() < 1e-12);
+            assert!(output.im.abs() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,265 @@
+//! Double precision processing path for offline rendering.
+//!
+//! Over long renders the single precision phase accumulators drift audibly,
+//! especially for large frames where bin phase advances reach millions of
+//! radians. This module runs the autotune path entirely in `f64`; audio is
+//! converted at the boundaries with [`widen`] and [`narrow`].
+//!
+//! Enabled with the `high-precision` feature.
+
+pub mod fft;
+pub mod pitch_correction;
+
+pub use fft::{Complex64, fft_f64, ifft_f64};
+pub use pitch_correction::{ProcessingStateF64, process_pitch_correction_f64, wrap_phase_f64};
+
+use crate::{MusicalSettings, VocalEffectsConfig, VocalEffectsError};
+
+/// Convert `f32` samples to `f64`
+///
+/// # Errors
+///
+/// Returns [`VocalEffectsError::BufferSizeMismatch`] if the slices differ in length.
+pub fn widen(input: &[f32], output: &mut [f64]) -> Result<(), VocalEffectsError> {
+    if input.len() != output.len() {
+        return Err(VocalEffectsError::BufferSizeMismatch);
+    }
+    for (output, &input) in output.iter_mut().zip(input) {
+        *output = input as f64;
+    }
+    Ok(())
+}
+
+/// Convert `f64` samples to `f32`
+///
+/// # Errors
+///
+/// Returns [`VocalEffectsError::BufferSizeMismatch`] if the slices differ in length.
+pub fn narrow(input: &[f64], output: &mut [f32]) -> Result<(), VocalEffectsError> {
+    if input.len() != output.len() {
+        return Err(VocalEffectsError::BufferSizeMismatch);
+    }
+    for (output, &input) in output.iter_mut().zip(input) {
+        *output = input as f32;
+    }
+    Ok(())
+}
+
+/// Streaming `f64` pitch correction, the double precision counterpart of
+/// [`Engine`](crate::Engine) in autotune mode.
+///
+/// # Example
+///
+/// ```rust
+/// use synthphone_e_vocal_dsp::{
+///     MusicalSettings, VocalEffectsConfig, high_precision::EngineF64,
+/// };
+///
+/// let mut engine = EngineF64::<2048, 1024>::new(
+///     VocalEffectsConfig::default(),
+///     MusicalSettings::default(),
+/// );
+/// let input = [0.0f32; 512];
+/// let mut output = [0.0f32; 512];
+/// engine.process_hop(&input, &mut output).unwrap();
+/// ```
+pub struct EngineF64<const N: usize, const HALF_N: usize> {
+    config: VocalEffectsConfig,
+    settings: MusicalSettings,
+    state: ProcessingStateF64<N>,
+    input_frame: [f64; N],
+    output_accumulator: [f64; N],
+}
+
+impl<const N: usize, const HALF_N: usize> EngineF64<N, HALF_N> {
+    /// Creates a new engine, taking the FFT size from `N`
+    pub fn new(mut config: VocalEffectsConfig, settings: MusicalSettings) -> Self {
+        config.fft_size = N;
+        config.hop_size = (N as f32 * config.hop_ratio) as usize;
+        Self {
+            config,
+            settings,
+            state: ProcessingStateF64::new(),
+            input_frame: [0.0; N],
+            output_accumulator: [0.0; N],
+        }
+    }
+
+    /// Returns the engine configuration
+    pub fn config(&self) -> &VocalEffectsConfig {
+        &self.config
+    }
+
+    /// Returns the phase vocoder state
+    pub fn state(&self) -> &ProcessingStateF64<N> {
+        &self.state
+    }
+
+    /// Replaces the musical settings
+    pub fn set_settings(&mut self, settings: MusicalSettings) {
+        self.settings = settings;
+    }
+
+    /// Number of samples consumed and produced by each hop
+    pub fn hop_size(&self) -> usize {
+        self.config.hop_size
+    }
+
+    /// Delay in samples between an input sample and its processed output
+    pub fn latency(&self) -> usize {
+        self.config.latency_samples()
+    }
+
+    /// Clears all audio and phase state
+    pub fn reset(&mut self) {
+        self.state.reset();
+        self.input_frame.fill(0.0);
+        self.output_accumulator.fill(0.0);
+    }
+
+    /// Processes one hop of `f64` samples.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VocalEffectsError::BufferSizeMismatch`] unless both buffers are
+    /// exactly one hop long, or [`VocalEffectsError::InvalidConfiguration`] if the
+    /// hop ratio gives an empty hop or one longer than the frame.
+    pub fn process_hop_f64(
+        &mut self,
+        input: &[f64],
+        output: &mut [f64],
+    ) -> Result<(), VocalEffectsError> {
+        let hop = self.check_hop(input.len(), output.len())?;
+        self.advance_input(hop).copy_from_slice(input);
+        self.process_frame();
+        output.copy_from_slice(&self.output_accumulator[..hop]);
+        self.advance_output(hop);
+        Ok(())
+    }
+
+    /// Processes one hop of `f32` samples, converting at the boundaries.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VocalEffectsError::BufferSizeMismatch`] unless both buffers are
+    /// exactly one hop long, or [`VocalEffectsError::InvalidConfiguration`] if the
+    /// hop ratio gives an empty hop or one longer than the frame.
+    pub fn process_hop(
+        &mut self,
+        input: &[f32],
+        output: &mut [f32],
+    ) -> Result<(), VocalEffectsError> {
+        let hop = self.check_hop(input.len(), output.len())?;
+        // Samples are converted straight into and out of the engine's own frames
+        widen(input, self.advance_input(hop))?;
+        self.process_frame();
+        narrow(&self.output_accumulator[..hop], output)?;
+        self.advance_output(hop);
+        Ok(())
+    }
+
+    /// Hop size, checked against the frame and the lengths of one hop of input and output
+    fn check_hop(&self, input: usize, output: usize) -> Result<usize, VocalEffectsError> {
+        let hop = self.config.hop_size;
+        if hop == 0 || hop > N {
+            return Err(VocalEffectsError::InvalidConfiguration);
+        }
+        if input != hop || output != hop {
+            return Err(VocalEffectsError::BufferSizeMismatch);
+        }
+        Ok(hop)
+    }
+
+    /// Slides the input frame along by one hop, returning the space for the new samples
+    fn advance_input(&mut self, hop: usize) -> &mut [f64] {
+        self.input_frame.copy_within(hop.., 0);
+        &mut self.input_frame[N - hop..]
+    }
+
+    /// Overlap-adds the processed input frame into the output accumulator
+    fn process_frame(&mut self) {
+        let processed = process_pitch_correction_f64::<N, HALF_N>(
+            &self.input_frame,
+            &mut self.state,
+            &self.config,
+            &self.settings,
+        );
+        for (acc, sample) in self.output_accumulator.iter_mut().zip(processed.iter()) {
+            *acc += *sample;
+        }
+    }
+
+    /// Drops the emitted hop from the output accumulator
+    fn advance_output(&mut self, hop: usize) {
+        self.output_accumulator.copy_within(hop.., 0);
+        self.output_accumulator[N - hop..].fill(0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::f64::consts::PI;
+
+    #[test]
+    fn test_conversions() {
+        let mut wide = [0.0f64; 3];
+        widen(&[0.5, -0.25, 1.0], &mut wide).unwrap();
+        assert_eq!(wide, [0.5, -0.25, 1.0]);
+
+        let mut narrowed = [0.0f32; 3];
+        narrow(&wide, &mut narrowed).unwrap();
+        assert_eq!(narrowed, [0.5, -0.25, 1.0]);
+
+        assert_eq!(widen(&[0.5], &mut wide), Err(VocalEffectsError::BufferSizeMismatch));
+        assert_eq!(narrow(&wide, &mut [0.0; 2]), Err(VocalEffectsError::BufferSizeMismatch));
+    }
+
+    #[test]
+    fn test_rejects_wrong_hop_length() {
+        let mut engine =
+            EngineF64::<512, 256>::new(VocalEffectsConfig::default(), MusicalSettings::default());
+        let mut output = [0.0f32; 128];
+        assert_eq!(
+            engine.process_hop(&[0.0; 64], &mut output),
+            Err(VocalEffectsError::BufferSizeMismatch)
+        );
+        assert!(engine.process_hop(&[0.0; 128], &mut output).is_ok());
+    }
+
+    #[test]
+    fn test_long_render_keeps_level_stable() {
+        // A6 is in C major, so the note passes through at unity ratio
+        const FREQUENCY: f64 = 1760.0;
+        let config = VocalEffectsConfig::default();
+        let sample_rate = config.sample_rate as f64;
+        let mut engine = EngineF64::<4096, 2048>::new(config, MusicalSettings::default());
+        let hop = engine.hop_size();
+        let sine = |n: usize| 0.5 * libm::sin(2.0 * PI * FREQUENCY * n as f64 / sample_rate);
+
+        let mut input = [0.0f64; 1024];
+        let mut output = [0.0f64; 1024];
+        let mut energy = 0.0f64;
+        let mut count = 0;
+        // Ten seconds of audio, measured once per second after the first
+        for block in 0..(480_000 / hop) {
+            let start = block * hop;
+            for (i, sample) in input[..hop].iter_mut().enumerate() {
+                *sample = sine(start + i);
+            }
+            engine.process_hop_f64(&input[..hop], &mut output[..hop]).unwrap();
+            if start >= 48_000 {
+                energy += output[..hop].iter().map(|s| s * s).sum::<f64>();
+                count += hop;
+                if count >= 48_000 {
+                    let rms = libm::sqrt(energy / count as f64);
+                    assert!((rms - 0.5 / core::f64::consts::SQRT_2).abs() < 0.02, "rms {rms}");
+                    energy = 0.0;
+                    count = 0;
+                }
+            }
+        }
+
+        assert!((engine.state().pitch_shift_ratio - 1.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,204 @@
+//! `f64` phase vocoder pitch correction.
+
+use core::f64::consts::PI;
+
+use libm::{atan2, cos, exp, fmod, log, sin, sqrt};
+
+use super::{Complex64, fft_f64, ifft_f64};
+use crate::{
+    Formant, MusicalSettings, VocalEffectsConfig,
+    dsp::{calculate_pitch_shift, clip::soft_clip},
+};
+
+/// Per-stream phase vocoder state in `f64`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProcessingStateF64<const N: usize> {
+    /// Analysis phases of the previous frame
+    pub last_input_phases: [f64; N],
+    /// Synthesis phases of the previous frame
+    pub last_output_phases: [f64; N],
+    /// Pitch shift ratio applied to the previous frame
+    pub pitch_shift_ratio: f64,
+}
+
+impl<const N: usize> Default for ProcessingStateF64<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> ProcessingStateF64<N> {
+    /// Create a state with zeroed phases and a unity pitch shift ratio
+    pub const fn new() -> Self {
+        Self {
+            last_input_phases: [0.0; N],
+            last_output_phases: [0.0; N],
+            pitch_shift_ratio: 1.0,
+        }
+    }
+
+    /// Clear all phases and return the pitch shift ratio to unity
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+/// Wrap a phase to `[-π, π)`
+#[inline(always)]
+pub fn wrap_phase_f64(phase: f64) -> f64 {
+    if phase >= 0.0 {
+        return fmod(phase + PI, 2.0 * PI) - PI;
+    }
+    fmod(phase - PI, -2.0 * PI) + PI
+}
+
+/// Symmetric Hann window, matching the `f32` tables
+fn hann<const N: usize>(i: usize) -> f64 {
+    0.5 * (1.0 - cos(2.0 * PI * i as f64 / (N - 1) as f64))
+}
+
+fn extract_cepstral_envelope<const N: usize, const HALF_N: usize>(
+    analysis_magnitudes: &[f64; HALF_N],
+    envelope: &mut [f64; HALF_N],
+    lifter_cutoff: usize,
+) {
+    let lifter_cutoff = lifter_cutoff.clamp(1, HALF_N);
+    let mut spectrum = [Complex64::default(); N];
+    for i in 0..HALF_N {
+        let log_magnitude = log(analysis_magnitudes[i].max(1e-9));
+        spectrum[i].re = log_magnitude;
+        if i != 0 {
+            spectrum[N - i].re = log_magnitude;
+        }
+    }
+
+    ifft_f64::<N, HALF_N>(&mut spectrum);
+    for (i, value) in spectrum.iter_mut().enumerate() {
+        if i >= lifter_cutoff && i < N - lifter_cutoff {
+            *value = Complex64::default();
+        }
+    }
+    fft_f64::<N, HALF_N>(&mut spectrum);
+
+    for i in 0..HALF_N {
+        envelope[i] = exp(spectrum[i].re);
+    }
+}
+
+/// `f64` pitch correction of one frame
+///
+/// A double precision port of the autotune path of
+/// [`process_frame`](crate::vocal_effects::process_frame), including formant
+/// preservation and shifting. The returned frame is windowed and ready for
+/// overlap-add.
+pub fn process_pitch_correction_f64<const N: usize, const HALF_N: usize>(
+    buffer: &[f64; N],
+    state: &mut ProcessingStateF64<N>,
+    config: &VocalEffectsConfig,
+    settings: &MusicalSettings,
+) -> [f64; N] {
+    const GAIN_COMPENSATION: f64 = 2.0 / 3.0;
+
+    let hop_size = (N as f32 * config.hop_ratio) as usize;
+    let bin_width = config.sample_rate / N as f32;
+
+    let mut spectrum = [Complex64::default(); N];
+    for i in 0..N {
+        spectrum[i].re = buffer[i] * hann::<N>(i);
+    }
+    fft_f64::<N, HALF_N>(&mut spectrum);
+
+    let mut analysis_magnitudes = [0.0f64; HALF_N];
+    let mut analysis_frequencies = [0.0f64; HALF_N];
+    for i in 0..HALF_N {
+        let amplitude = sqrt(spectrum[i].re * spectrum[i].re + spectrum[i].im * spectrum[i].im);
+        let phase = atan2(spectrum[i].im, spectrum[i].re);
+        // The bin centre advance is reduced modulo 2π in integers, so large bins and
+        // hops stay exact
+        let bin_centre_advance = 2.0 * PI * ((i * hop_size) % N) as f64 / N as f64;
+        let phase_diff = wrap_phase_f64(phase - state.last_input_phases[i] - bin_centre_advance);
+        let bin_deviation = phase_diff * N as f64 / hop_size as f64 / (2.0 * PI);
+        analysis_frequencies[i] = i as f64 + bin_deviation;
+        analysis_magnitudes[i] = amplitude;
+        state.last_input_phases[i] = phase;
+    }
+
+    let mut envelope = [1.0f64; HALF_N];
+    let formant = settings.formant;
+    if formant.is_shifted() {
+        extract_cepstral_envelope::<N, HALF_N>(
+            &analysis_magnitudes,
+            &mut envelope,
+            config.lifter_cutoff(),
+        );
+    }
+
+    // The pitch decision only needs single precision
+    let magnitudes: [f32; HALF_N] = core::array::from_fn(|i| analysis_magnitudes[i] as f32);
+    let frequencies: [f32; HALF_N] = core::array::from_fn(|i| analysis_frequencies[i] as f32);
+    state.pitch_shift_ratio = calculate_pitch_shift(
+        &magnitudes,
+        &frequencies,
+        state.pitch_shift_ratio as f32,
+        config,
+        settings,
+        bin_width,
+    ) as f64;
+    let pitch_shift_ratio = state.pitch_shift_ratio;
+
+    let formant_ratio = match formant {
+        Formant::Lower => 0.5,
+        Formant::Higher => 2.0,
+        Formant::None => 1.0,
+    };
+    let mut synthesis_magnitudes = [0.0f64; HALF_N];
+    let mut synthesis_frequencies = [0.0f64; HALF_N];
+    for i in 0..HALF_N {
+        if analysis_magnitudes[i] <= 1e-12 {
+            continue;
+        }
+        let new_bin = ((i as f64 * pitch_shift_ratio + 0.5) as usize).min(HALF_N - 1);
+        let (residual, shifted_envelope) = if formant.is_shifted() {
+            let env_pos = (i as f64 / formant_ratio).clamp(0.0, (HALF_N - 1) as f64);
+            let env_idx = env_pos as usize;
+            let frac = env_pos - env_idx as f64;
+            let shifted = if env_idx < HALF_N - 1 {
+                envelope[env_idx] * (1.0 - frac) + envelope[env_idx + 1] * frac
+            } else {
+                envelope[env_idx]
+            };
+            (analysis_magnitudes[i] / envelope[i].max(1e-9), shifted)
+        } else {
+            (analysis_magnitudes[i], 1.0)
+        };
+        synthesis_magnitudes[new_bin] = residual * shifted_envelope;
+        synthesis_frequencies[new_bin] = analysis_frequencies[i] * pitch_shift_ratio;
+    }
+
+    let mut spectrum = [Complex64::default(); N];
+    for i in 0..HALF_N {
+        let phase_increment = 2.0 * PI * synthesis_frequencies[i] * hop_size as f64 / N as f64;
+        let output_phase = wrap_phase_f64(state.last_output_phases[i] + phase_increment);
+        let re = synthesis_magnitudes[i] * cos(output_phase);
+        let im = synthesis_magnitudes[i] * sin(output_phase);
+        spectrum[i] = Complex64 { re, im };
+        if i > 0 {
+            spectrum[N - i] = Complex64 { re, im: -im };
+        }
+        state.last_output_phases[i] = output_phase;
+    }
+    ifft_f64::<N, HALF_N>(&mut spectrum);
+
+    let mut output = [0.0f64; N];
+    for i in 0..N {
+        let mut sample = spectrum[i].re * hann::<N>(i) * GAIN_COMPENSATION;
+        if let Some(clip) = &config.soft_clip {
+            // Only clipped samples go through f32, the rest keep full precision
+            if sample.abs() > f64::from(clip.threshold) {
+                sample = f64::from(soft_clip(sample as f32, clip));
+            }
+        }
+        output[i] = sample;
+    }
+    output
+}
@@ -1,31 +1,107 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+/// Emits a `defmt` trace point when the `defmt` feature is enabled
+macro_rules! dsp_trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "defmt")]
+        defmt::trace!($($arg)*);
+    };
+}
+
+/// Times a processing stage when the `profiling` feature is enabled
+#[cfg(feature = "profiling")]
+macro_rules! profile_stage {
+    ($stage:ident, $body:expr) => {{
+        let _timer = $crate::profiling::StageTimer::start($crate::profiling::Stage::$stage);
+        $body
+    }};
+}
+
+#[cfg(not(feature = "profiling"))]
+macro_rules! profile_stage {
+    ($stage:ident, $body:expr) => {
+        $body
+    };
+}
+
 // Core modules
 pub mod config;
 pub mod error;
 pub mod state;
+pub mod workspace;
 
 // Audio processing modules
 pub mod audio;
+pub mod engine;
 pub mod vocal_effects;
 
 // Buffer management
 pub mod ring_buffer;
 
 // Utility modules
+pub mod convert;
+pub mod dma;
 pub mod math;
+pub mod meter;
+pub mod midi;
+#[cfg(feature = "profiling")]
+pub mod profiling;
+pub mod stereo;
+pub mod tempo;
+pub mod testsig;
 
 pub mod dsp;
 pub mod effects;
 
+#[cfg(feature = "rayon")]
+pub mod batch;
+#[cfg(feature = "diagnostics")]
+pub mod diagnostics;
+#[cfg(feature = "fixed-point")]
+pub mod fixed;
+#[cfg(feature = "high-precision")]
+pub mod high_precision;
+#[cfg(feature = "std")]
+pub mod null_test;
+#[cfg(feature = "std")]
+pub mod offline;
+
 // Re-export main API
-pub use config::VocalEffectsConfig;
-pub use error::VocalEffectsError;
-pub use state::{MusicalSettings, ProcessingMode};
+pub use config::{
+    BandLimit, BreathRetention, ChorusSettings, DelaySettings, DelayTime, ExciterSettings, Glide,
+    IdleOutput, LowConfidence, MainsFrequency, ModulationRate, NoiseFill, Ornaments, PhaseReanchor,
+    PitchDecimation, PitchDetector, PitchShiftAlgorithm, ReverbSettings, SaturationCurve,
+    SaturationSettings, SibilanceBypass, SidechainDuck, SoftClip, SpectralGate, TransientHandling,
+    VocalEffectsConfig, VocalEffectsConfigBuilder, VocoderEnvelope, VoiceActivityGate,
+};
+#[cfg(feature = "fft-8192")]
+pub use engine::Engine8192;
+pub use engine::{
+    BlockAdapter, ControlEvent, Engine, Engine128, Engine256, Engine512, Engine1024, Engine2048,
+    Engine4096, SelfTestResult,
+};
+pub use error::{ConfigError, VocalEffectsError};
+pub use meter::{Meter, MeterReading};
+pub use state::{
+    CarrierMode, ChordQuality, ChordSpec, CorrectionStrength, Formant, FrameAnalysis, FrameState,
+    Key, MusicalSettings, Note, Octave, OctaveShift, ProcessingMode, ProcessingState, TargetSource,
+    VocoderEq,
+};
+pub use stereo::{MonoReading, Placement};
+pub use tempo::{NoteValue, TapTempo, TempoClock, Transport};
+
+#[cfg(feature = "alloc")]
+pub use workspace::HeapWorkspace;
+pub use workspace::Workspace;
 
 // Re-export commonly used functions
 pub use vocal_effects::{
-    process_vocal_effects_512, process_vocal_effects_1024, process_vocal_effects_2048,
-    process_vocal_effects_4096,
+    process_carrier_effects_128, process_carrier_effects_256, process_carrier_effects_512,
+    process_carrier_effects_1024, process_carrier_effects_2048, process_carrier_effects_4096,
+    process_vocal_effects_128, process_vocal_effects_256, process_vocal_effects_512,
+    process_vocal_effects_1024, process_vocal_effects_2048, process_vocal_effects_4096,
 };
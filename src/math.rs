@@ -1,6 +1,6 @@
 //! Mathematical utilities
 
-use libm::{expf, fabsf};
+use libm::{exp2f, expf, fabsf};
 
 /// Clamp a value between min and max
 #[inline(always)]
@@ -26,6 +26,14 @@ pub fn is_power_of_two(n: usize) -> bool {
     n != 0 && (n & (n - 1)) == 0
 }
 
+/// Convert a pitch shift in semitones to a frequency ratio
+///
+/// Fractional semitones are allowed, so `0.01` is a one cent shift.
+#[inline(always)]
+pub fn semitones_to_ratio(semitones: f32) -> f32 {
+    exp2f(semitones / 12.0)
+}
+
 pub fn normalize_sample(sample: f32, target_peak: f32) -> f32 {
     let abs_sample = fabsf(sample);
     if abs_sample > target_peak {
@@ -37,3 +45,28 @@ pub fn normalize_sample(sample: f32, target_peak: f32) -> f32 {
         sample
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_semitones_to_ratio() {
+        assert!((semitones_to_ratio(0.0) - 1.0).abs() < 1e-6);
+        assert!((semitones_to_ratio(12.0) - 2.0).abs() < 1e-5);
+        assert!((semitones_to_ratio(-12.0) - 0.5).abs() < 1e-6);
+        assert!((semitones_to_ratio(3.0) - 1.189_207).abs() < 1e-5);
+        assert!((semitones_to_ratio(-2.0) - 0.890_899).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_semitones_to_ratio_cents() {
+        // 100 cents make a semitone
+        let one_cent = semitones_to_ratio(0.01);
+        assert!((one_cent - 1.000_577_8).abs() < 1e-6);
+        assert!(
+            (semitones_to_ratio(0.5) * semitones_to_ratio(0.5) - semitones_to_ratio(1.0)).abs()
+                < 1e-6
+        );
+    }
+}
@@ -0,0 +1,167 @@
+//! Level metering and clip detection.
+//!
+//! [`Meter`] measures peak and RMS level per block and counts clipped samples
+//! until the counter is cleared, so a UI can show an input level and latch a clip
+//! light without repeating the math in firmware. Every [`Engine`](crate::Engine)
+//! meters its input; the type also works standalone on any block of samples.
+
+use libm::{log10f, sqrtf};
+
+/// Level reported for silence, in dBFS
+pub const SILENCE_DB: f32 = -120.0;
+
+/// Levels of one block
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MeterReading {
+    /// Largest absolute sample value
+    pub peak: f32,
+    /// Root mean square of the samples
+    pub rms: f32,
+    /// Samples at or above the clip threshold
+    pub clipped_samples: usize,
+}
+
+impl MeterReading {
+    /// Peak level in dBFS, [`SILENCE_DB`] at the lowest
+    pub fn peak_db(&self) -> f32 {
+        to_db(self.peak)
+    }
+
+    /// RMS level in dBFS, [`SILENCE_DB`] at the lowest
+    pub fn rms_db(&self) -> f32 {
+        to_db(self.rms)
+    }
+
+    /// Returns `true` if any sample of the block clipped
+    pub fn clipped(&self) -> bool {
+        self.clipped_samples > 0
+    }
+}
+
+/// Peak/RMS meter with a clip counter
+///
+/// ```rust
+/// use synthphone_e_vocal_dsp::Meter;
+///
+/// let mut meter = Meter::new();
+/// let reading = meter.measure(&[0.5, -1.0, 0.25, 0.0]);
+/// assert_eq!(reading.peak, 1.0);
+/// assert_eq!(meter.clip_count(), 1);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Meter {
+    clip_threshold: f32,
+    reading: MeterReading,
+    clip_count: u32,
+}
+
+impl Meter {
+    /// Creates a meter that counts samples at full scale (1.0) as clipped
+    pub const fn new() -> Self {
+        Self::with_clip_threshold(1.0)
+    }
+
+    /// Creates a meter that counts samples at or above `threshold` as clipped
+    ///
+    /// Integer codecs saturate just below full scale, so a threshold such as
+    /// 0.999 catches clips that have already been converted.
+    pub const fn with_clip_threshold(threshold: f32) -> Self {
+        Self {
+            clip_threshold: threshold,
+            reading: MeterReading { peak: 0.0, rms: 0.0, clipped_samples: 0 },
+            clip_count: 0,
+        }
+    }
+
+    /// Measures one block and adds its clipped samples to the counter
+    pub fn measure(&mut self, block: &[f32]) -> MeterReading {
+        let mut peak = 0.0f32;
+        let mut sum_squares = 0.0f32;
+        let mut clipped_samples = 0;
+        for &sample in block {
+            let level = sample.abs();
+            peak = peak.max(level);
+            sum_squares += sample * sample;
+            clipped_samples += usize::from(level >= self.clip_threshold);
+        }
+        let rms = if block.is_empty() {
+            0.0
+        } else {
+            sqrtf(sum_squares / block.len() as f32)
+        };
+
+        self.reading = MeterReading { peak, rms, clipped_samples };
+        self.clip_count = self.clip_count.saturating_add(clipped_samples as u32);
+        self.reading
+    }
+
+    /// Levels of the last measured block
+    pub fn reading(&self) -> MeterReading {
+        self.reading
+    }
+
+    /// Clipped samples since creation or the last [`Meter::clear_clips`]
+    pub fn clip_count(&self) -> u32 {
+        self.clip_count
+    }
+
+    /// Clears the clip counter, such as when the user acknowledges a clip light
+    pub fn clear_clips(&mut self) {
+        self.clip_count = 0;
+    }
+
+    /// Clears the reading and the clip counter
+    pub fn reset(&mut self) {
+        *self = Self::with_clip_threshold(self.clip_threshold);
+    }
+}
+
+impl Default for Meter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn to_db(level: f32) -> f32 {
+    if level > 0.0 {
+        (20.0 * log10f(level)).max(SILENCE_DB)
+    } else {
+        SILENCE_DB
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sine_levels() {
+        let mut meter = Meter::new();
+        let block: [f32; 480] = core::array::from_fn(|n| {
+            0.5 * libm::sinf(2.0 * core::f32::consts::PI * n as f32 / 48.0)
+        });
+        let reading = meter.measure(&block);
+        assert!((reading.peak - 0.5).abs() < 1e-3);
+        assert!((reading.rms - 0.5 * core::f32::consts::FRAC_1_SQRT_2).abs() < 1e-3);
+        assert!((reading.peak_db() + 6.02).abs() < 0.05);
+        assert!(!reading.clipped());
+        assert_eq!(Meter::new().measure(&[]).rms_db(), SILENCE_DB);
+    }
+
+    #[test]
+    fn test_clip_counter_accumulates_until_cleared() {
+        let mut meter = Meter::with_clip_threshold(0.99);
+        assert_eq!(meter.measure(&[0.995, -1.0, 0.5]).clipped_samples, 2);
+        meter.measure(&[1.0, 0.0]);
+        assert_eq!(meter.clip_count(), 3);
+        assert_eq!(meter.reading().clipped_samples, 1);
+
+        meter.clear_clips();
+        assert_eq!(meter.clip_count(), 0);
+        assert_eq!(meter.reading().peak, 1.0);
+        meter.reset();
+        assert_eq!(meter.reading(), MeterReading::default());
+    }
+}
@@ -0,0 +1,558 @@
+//! MIDI note output from the pitch detector.
+//!
+//! [`MidiTracker`] turns the per-hop pitch analysis of an [`Engine`](crate::Engine)
+//! into note-on, note-off and pitch-bend events, so the engine can act as an
+//! audio-to-MIDI front end. Notes start once the input is louder than
+//! [`MidiOptions::onset_db`] and has stayed on the same note for
+//! [`MidiOptions::onset_hops`] hops, and end when it falls below
+//! [`MidiOptions::offset_db`] or the pitch is lost.
+//!
+//! With an [`MpeZone`] each note is sent on its own member channel with its own
+//! pitch bend, so an MPE synth follows the singer's intonation, slides included,
+//! without bending the release of the previous note.
+//!
+//! The tracker reads the detected pitch, which only the autotune mode updates.
+
+use libm::{log2f, roundf};
+
+use crate::engine::{FrameObserver, FrameView};
+
+/// Centre value of a 14-bit pitch bend
+pub const PITCH_BEND_CENTRE: u16 = 8192;
+
+/// Thresholds and output options of a [`MidiTracker`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MidiOptions {
+    /// MIDI channel of the events, 0 to 15, when not sending MPE
+    pub channel: u8,
+    /// MPE zone whose member channels the notes rotate through
+    pub mpe: Option<MpeZone>,
+    /// Level in dBFS a note must exceed to start
+    pub onset_db: f32,
+    /// Level in dBFS below which a sounding note ends
+    pub offset_db: f32,
+    /// Hops a new note must be held before its note-on is sent
+    pub onset_hops: usize,
+    /// How far in cents past the half-semitone boundary the pitch may drift before
+    /// a different note is started
+    pub note_hysteresis_cents: f32,
+    /// Pitch-bend range in semitones, or `None` to send no pitch bends
+    pub pitch_bend_range: Option<f32>,
+}
+
+impl Default for MidiOptions {
+    fn default() -> Self {
+        Self {
+            channel: 0,
+            mpe: None,
+            onset_db: -40.0,
+            offset_db: -50.0,
+            onset_hops: 2,
+            note_hysteresis_cents: 20.0,
+            pitch_bend_range: Some(2.0),
+        }
+    }
+}
+
+impl MidiOptions {
+    /// Options for MPE output in `zone`, with the MPE default bend range of 48 semitones
+    pub fn mpe(zone: MpeZone) -> Self {
+        Self { mpe: Some(zone), pitch_bend_range: Some(48.0), ..Self::default() }
+    }
+}
+
+/// MPE zone: a master channel and the member channels that carry the notes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MpeZone {
+    /// Master channel 0 (MIDI channel 1), members counting up from channel 1
+    Lower { members: u8 },
+    /// Master channel 15 (MIDI channel 16), members counting down from channel 14
+    Upper { members: u8 },
+}
+
+impl MpeZone {
+    /// Channel of the zone-wide messages
+    pub fn master_channel(self) -> u8 {
+        match self {
+            MpeZone::Lower { .. } => 0,
+            MpeZone::Upper { .. } => 15,
+        }
+    }
+
+    /// Number of member channels, 1 to 15
+    pub fn members(self) -> u8 {
+        match self {
+            MpeZone::Lower { members } | MpeZone::Upper { members } => members.clamp(1, 15),
+        }
+    }
+
+    /// Channel of member `index`, wrapping around the zone
+    pub fn member_channel(self, index: u8) -> u8 {
+        let offset = 1 + index % self.members();
+        match self {
+            MpeZone::Lower { .. } => offset,
+            MpeZone::Upper { .. } => 15 - offset,
+        }
+    }
+
+    /// Messages that set up the zone on a receiver: the MPE configuration message
+    /// on the master channel, then the pitch-bend range of every member channel
+    pub fn configuration(self, pitch_bend_range: u8) -> impl Iterator<Item = MidiEvent> {
+        let rpn = |channel: u8, parameter: u8, value: u8| {
+            [(101, 0), (100, parameter), (6, value)]
+                .map(|(controller, value)| MidiEvent::ControlChange { channel, controller, value })
+        };
+        let members = (0..self.members())
+            .flat_map(move |index| rpn(self.member_channel(index), 0, pitch_bend_range.min(127)));
+        rpn(self.master_channel(), 6, self.members()).into_iter().chain(members)
+    }
+}
+
+/// Channel voice message produced by a [`MidiTracker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MidiEvent {
+    /// A note started
+    NoteOn { channel: u8, note: u8, velocity: u8 },
+    /// A note ended
+    NoteOff { channel: u8, note: u8 },
+    /// Pitch bend, 14 bits with [`PITCH_BEND_CENTRE`] as no bend
+    PitchBend { channel: u8, value: u16 },
+    /// Control change, used for the MPE zone configuration
+    ControlChange { channel: u8, controller: u8, value: u8 },
+}
+
+impl MidiEvent {
+    /// Encodes the event as a MIDI 1.0 message
+    pub fn to_bytes(&self) -> [u8; 3] {
+        match *self {
+            MidiEvent::NoteOn { channel, note, velocity } => {
+                [0x90 | (channel & 0x0F), note & 0x7F, velocity & 0x7F]
+            }
+            MidiEvent::NoteOff { channel, note } => [0x80 | (channel & 0x0F), note & 0x7F, 0],
+            MidiEvent::PitchBend { channel, value } => {
+                [0xE0 | (channel & 0x0F), (value & 0x7F) as u8, ((value >> 7) & 0x7F) as u8]
+            }
+            MidiEvent::ControlChange { channel, controller, value } => {
+                [0xB0 | (channel & 0x0F), controller & 0x7F, value & 0x7F]
+            }
+        }
+    }
+}
+
+/// [`MidiEvent`] with the hop it was produced at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TimedMidiEvent {
+    /// [`FrameView::hop_index`] of the frame that produced the event
+    pub hop_index: u64,
+    pub event: MidiEvent,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SoundingNote {
+    channel: u8,
+    note: u8,
+    bend: u16,
+}
+
+/// Converts the detected pitch of each frame into MIDI events
+///
+/// As a [`FrameObserver`] the tracker queues up to `CAPACITY` events, which are
+/// taken with [`MidiTracker::events`] after each processed block; events that do
+/// not fit are dropped and counted. [`MidiTracker::update`] hands the events of
+/// one frame to a callback instead.
+///
+/// ```rust
+/// use synthphone_e_vocal_dsp::{
+///     Engine1024, MusicalSettings, VocalEffectsConfig, midi::{MidiOptions, MidiTracker},
+/// };
+///
+/// let mut engine = Engine1024::new(VocalEffectsConfig::default(), MusicalSettings::default());
+/// let mut tracker = MidiTracker::<16>::new(MidiOptions::default());
+/// let mut output = [0.0f32; 256];
+/// engine.process_hop_observed(&[0.0; 256], None, &mut output, &mut tracker).unwrap();
+/// for event in tracker.events() {
+///     let _bytes = event.event.to_bytes();
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct MidiTracker<const CAPACITY: usize = 32> {
+    options: MidiOptions,
+    sounding: Option<SoundingNote>,
+    /// Note waiting to be confirmed and the number of hops it has been held
+    candidate: Option<(u8, usize)>,
+    queue: [Option<TimedMidiEvent>; CAPACITY],
+    queued: usize,
+    dropped: usize,
+    /// MPE member the next note is sent on
+    next_member: u8,
+}
+
+impl<const CAPACITY: usize> MidiTracker<CAPACITY> {
+    /// Creates a tracker with no note sounding
+    pub const fn new(options: MidiOptions) -> Self {
+        Self {
+            options,
+            sounding: None,
+            candidate: None,
+            queue: [None; CAPACITY],
+            queued: 0,
+            dropped: 0,
+            next_member: 0,
+        }
+    }
+
+    /// Returns the tracker options
+    pub fn options(&self) -> &MidiOptions {
+        &self.options
+    }
+
+    /// Replaces the tracker options, releasing the sounding note if the channels change
+    pub fn set_options(&mut self, options: MidiOptions) {
+        if options.channel != self.options.channel || options.mpe != self.options.mpe {
+            let mut released = None;
+            self.release(0, |event| released = Some(event));
+            if let Some(event) = released {
+                self.push(event);
+            }
+        }
+        self.options = options;
+    }
+
+    /// Returns the note currently sounding
+    pub fn sounding_note(&self) -> Option<u8> {
+        self.sounding.map(|sounding| sounding.note)
+    }
+
+    /// Takes the queued events in the order they were produced
+    pub fn events(&mut self) -> impl Iterator<Item = TimedMidiEvent> + '_ {
+        let queued = core::mem::take(&mut self.queued);
+        self.queue[..queued].iter_mut().filter_map(Option::take)
+    }
+
+    /// Number of events dropped because the queue was full
+    pub fn dropped_events(&self) -> usize {
+        self.dropped
+    }
+
+    /// Ends the sounding note, if any, e.g. when playback stops
+    pub fn release(&mut self, hop_index: u64, mut emit: impl FnMut(TimedMidiEvent)) {
+        if let Some(sounding) = self.sounding.take() {
+            let event = MidiEvent::NoteOff { channel: sounding.channel, note: sounding.note };
+            emit(TimedMidiEvent { hop_index, event });
+        }
+        self.candidate = None;
+    }
+
+    /// Analyses one frame and passes the resulting events to `emit`
+    pub fn update(&mut self, frame: &FrameView<'_>, mut emit: impl FnMut(TimedMidiEvent)) {
+        let hop_index = frame.hop_index;
+        let level = frame.level_db();
+        let threshold = match self.sounding {
+            Some(_) => self.options.offset_db,
+            None => self.options.onset_db,
+        };
+        let pitch = midi_pitch(frame.analysis.detected_frequency);
+        let Some(pitch) = pitch.filter(|_| level >= threshold) else {
+            self.release(hop_index, emit);
+            return;
+        };
+
+        let nearest = roundf(pitch).clamp(0.0, 127.0) as u8;
+        let holding = self.sounding.is_some_and(|sounding| {
+            let boundary = 0.5 + self.options.note_hysteresis_cents / 100.0;
+            (pitch - sounding.note as f32).abs() <= boundary
+        });
+        if holding {
+            self.candidate = None;
+        } else {
+            let held = match self.candidate {
+                Some((note, hops)) if note == nearest => hops + 1,
+                _ => 1,
+            };
+            self.candidate = Some((nearest, held));
+
+            if held >= self.options.onset_hops.max(1) {
+                self.candidate = None;
+                if let Some(sounding) = self.sounding.take() {
+                    let event =
+                        MidiEvent::NoteOff { channel: sounding.channel, note: sounding.note };
+                    emit(TimedMidiEvent { hop_index, event });
+                }
+                // Bend first so the note starts at the sung pitch
+                let channel = self.next_channel();
+                let bend = self.pitch_bend(pitch, nearest);
+                if self.options.pitch_bend_range.is_some() {
+                    let event = MidiEvent::PitchBend { channel, value: bend };
+                    emit(TimedMidiEvent { hop_index, event });
+                }
+                let velocity = self.velocity(level);
+                let event = MidiEvent::NoteOn { channel, note: nearest, velocity };
+                emit(TimedMidiEvent { hop_index, event });
+                self.sounding = Some(SoundingNote { channel, note: nearest, bend });
+                return;
+            }
+        }
+
+        // Track the intonation of the sounding note
+        if let Some(sounding) = self.sounding {
+            if self.options.pitch_bend_range.is_some() {
+                let bend = self.pitch_bend(pitch, sounding.note);
+                if bend != sounding.bend {
+                    let event = MidiEvent::PitchBend { channel: sounding.channel, value: bend };
+                    emit(TimedMidiEvent { hop_index, event });
+                    self.sounding = Some(SoundingNote { bend, ..sounding });
+                }
+            }
+        }
+    }
+
+    fn push(&mut self, event: TimedMidiEvent) {
+        match self.queue.get_mut(self.queued) {
+            Some(slot) => {
+                *slot = Some(event);
+                self.queued += 1;
+            }
+            None => self.dropped += 1,
+        }
+    }
+
+    /// Channel for a new note, the next MPE member in turn or the fixed channel
+    fn next_channel(&mut self) -> u8 {
+        match self.options.mpe {
+            Some(zone) => {
+                let channel = zone.member_channel(self.next_member);
+                self.next_member = (self.next_member + 1) % zone.members();
+                channel
+            }
+            None => self.options.channel,
+        }
+    }
+
+    /// Bend from `note` to `pitch`, clamped to the bend range
+    fn pitch_bend(&self, pitch: f32, note: u8) -> u16 {
+        let Some(range) = self.options.pitch_bend_range.filter(|range| *range > 0.0) else {
+            return PITCH_BEND_CENTRE;
+        };
+        let offset = (pitch - note as f32) / range * PITCH_BEND_CENTRE as f32;
+        (PITCH_BEND_CENTRE as f32 + roundf(offset)).clamp(0.0, 16383.0) as u16
+    }
+
+    /// Velocity from the level between the offset threshold and full scale
+    fn velocity(&self, level: f32) -> u8 {
+        let floor = self.options.offset_db.min(-1.0);
+        let scaled = ((level - floor) / -floor).clamp(0.0, 1.0);
+        1 + roundf(scaled * 126.0) as u8
+    }
+}
+
+impl<const CAPACITY: usize> Default for MidiTracker<CAPACITY> {
+    fn default() -> Self {
+        Self::new(MidiOptions::default())
+    }
+}
+
+impl<const CAPACITY: usize> FrameObserver for MidiTracker<CAPACITY> {
+    fn on_frame(&mut self, frame: &FrameView<'_>) {
+        let mut events = [None; 3];
+        let mut count = 0;
+        self.update(frame, |event| {
+            events[count] = Some(event);
+            count += 1;
+        });
+        for event in events.into_iter().flatten() {
+            self.push(event);
+        }
+    }
+}
+
+/// Fractional MIDI note number of `frequency`, `None` outside the MIDI range
+fn midi_pitch(frequency: f32) -> Option<f32> {
+    if frequency.is_nan() || frequency <= 0.0 {
+        return None;
+    }
+    let pitch = 69.0 + 12.0 * log2f(frequency / 440.0);
+    (-0.5..127.5).contains(&pitch).then_some(pitch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Engine1024, FrameAnalysis, MusicalSettings, ProcessingMode, VocalEffectsConfig};
+    use core::f32::consts::PI;
+    use libm::sqrtf;
+
+    /// Magnitudes of a 1024-point frame holding a sine of `amplitude`
+    fn magnitudes(amplitude: f32) -> [f32; 512] {
+        let mut magnitudes = [0.0f32; 512];
+        magnitudes[10] = amplitude * 1024.0 / sqrtf(32.0 / 3.0);
+        magnitudes
+    }
+
+    fn frame<const CAPACITY: usize>(
+        tracker: &mut MidiTracker<CAPACITY>,
+        hop_index: u64,
+        frequency: f32,
+        amplitude: f32,
+    ) -> [Option<MidiEvent>; 3] {
+        let magnitudes = magnitudes(amplitude);
+        let view = FrameView {
+            hop_index,
+            mode: ProcessingMode::Autotune,
+            sample_rate: 48000.0,
+            magnitudes: &magnitudes,
+            analysis: FrameAnalysis { detected_frequency: frequency, ..FrameAnalysis::new() },
+        };
+        let mut events = [None; 3];
+        let mut count = 0;
+        tracker.update(&view, |event| {
+            assert_eq!(event.hop_index, hop_index);
+            events[count] = Some(event.event);
+            count += 1;
+        });
+        events
+    }
+
+    #[test]
+    fn test_level_matches_sine_amplitude() {
+        for (amplitude, expected) in [(1.0, 0.0), (0.1, -20.0)] {
+            let magnitudes = magnitudes(amplitude);
+            let view = FrameView {
+                hop_index: 1,
+                mode: ProcessingMode::Autotune,
+                sample_rate: 48000.0,
+                magnitudes: &magnitudes,
+                analysis: FrameAnalysis::new(),
+            };
+            assert!((view.level_db() - expected).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_note_on_after_onset_hops_and_off_on_silence() {
+        let options = MidiOptions { pitch_bend_range: None, ..Default::default() };
+        let mut tracker = MidiTracker::<8>::new(options);
+
+        assert_eq!(frame(&mut tracker, 1, 220.0, 0.5), [None; 3]);
+        let events = frame(&mut tracker, 2, 220.0, 0.5);
+        assert_eq!(events[0], Some(MidiEvent::NoteOn { channel: 0, note: 57, velocity: 112 }));
+        assert_eq!(tracker.sounding_note(), Some(57));
+
+        // Quieter than the onset but above the offset keeps the note
+        assert_eq!(frame(&mut tracker, 3, 220.0, 0.005), [None; 3]);
+        let events = frame(&mut tracker, 4, 220.0, 0.001);
+        assert_eq!(events[0], Some(MidiEvent::NoteOff { channel: 0, note: 57 }));
+        assert_eq!(tracker.sounding_note(), None);
+    }
+
+    #[test]
+    fn test_hysteresis_holds_note_across_boundary() {
+        let mut tracker =
+            MidiTracker::<8>::new(MidiOptions { onset_hops: 1, ..Default::default() });
+        frame(&mut tracker, 1, 220.0, 0.5);
+
+        // 60 cents sharp is inside the 20 cent hysteresis and bends the note
+        let sharp = 220.0 * libm::exp2f(0.6 / 12.0);
+        let events = frame(&mut tracker, 2, sharp, 0.5);
+        assert_eq!(events[0], Some(MidiEvent::PitchBend { channel: 0, value: 8192 + 2458 }));
+        assert_eq!(events[1], None);
+
+        // 80 cents sharp moves on to the next note, bent down 20 cents
+        let events = frame(&mut tracker, 3, 220.0 * libm::exp2f(0.8 / 12.0), 0.5);
+        assert_eq!(events[0], Some(MidiEvent::NoteOff { channel: 0, note: 57 }));
+        assert_eq!(events[1], Some(MidiEvent::PitchBend { channel: 0, value: 8192 - 819 }));
+        assert!(matches!(events[2], Some(MidiEvent::NoteOn { note: 58, .. })));
+    }
+
+    #[test]
+    fn test_mpe_notes_rotate_member_channels() {
+        let zone = MpeZone::Lower { members: 2 };
+        let mut tracker =
+            MidiTracker::<8>::new(MidiOptions { onset_hops: 1, ..MidiOptions::mpe(zone) });
+
+        // 10 cents flat of A3 bends each note on its own channel
+        let flat = libm::exp2f(-0.1 / 12.0);
+        let mut channels = [0u8; 3];
+        for (i, frequency) in [220.0, 246.94, 261.63].into_iter().enumerate() {
+            let events = frame(&mut tracker, 1 + 2 * i as u64, frequency * flat, 0.5);
+            let offset = if i == 0 { 0 } else { 1 };
+            let Some(MidiEvent::PitchBend { channel, value }) = events[offset] else {
+                panic!("no bend before note {i}: {events:?}");
+            };
+            assert_eq!(value, 8192 - 17);
+            assert!(
+                matches!(events[offset + 1], Some(MidiEvent::NoteOn { channel: c, .. }) if c == channel)
+            );
+            channels[i] = channel;
+
+            // Intonation drifts on the sounding note's channel only
+            let events = frame(&mut tracker, 2 + 2 * i as u64, frequency, 0.5);
+            assert_eq!(events[0], Some(MidiEvent::PitchBend { channel, value: 8192 }));
+        }
+        assert_eq!(channels, [1, 2, 1]);
+
+        let events = frame(&mut tracker, 7, 0.0, 0.5);
+        assert_eq!(events[0], Some(MidiEvent::NoteOff { channel: 1, note: 60 }));
+    }
+
+    #[test]
+    fn test_mpe_zone_configuration() {
+        let upper = MpeZone::Upper { members: 3 };
+        assert_eq!(upper.master_channel(), 15);
+        assert_eq!([0, 1, 2, 3].map(|index| upper.member_channel(index)), [14, 13, 12, 14]);
+
+        let mut messages = MpeZone::Lower { members: 15 }.configuration(48);
+        let mcm: [[u8; 3]; 3] = core::array::from_fn(|_| messages.next().unwrap().to_bytes());
+        assert_eq!(mcm, [[0xB0, 101, 0], [0xB0, 100, 6], [0xB0, 6, 15]]);
+        let member = messages.next().unwrap();
+        assert_eq!(member, MidiEvent::ControlChange { channel: 1, controller: 101, value: 0 });
+        assert_eq!(messages.count(), 15 * 3 - 1);
+    }
+
+    #[test]
+    fn test_event_bytes() {
+        let note_on = MidiEvent::NoteOn { channel: 2, note: 60, velocity: 100 };
+        assert_eq!(note_on.to_bytes(), [0x92, 60, 100]);
+        assert_eq!(MidiEvent::NoteOff { channel: 2, note: 60 }.to_bytes(), [0x82, 60, 0]);
+        let bend = MidiEvent::PitchBend { channel: 0, value: PITCH_BEND_CENTRE };
+        assert_eq!(bend.to_bytes(), [0xE0, 0x00, 0x40]);
+    }
+
+    #[test]
+    fn test_engine_queue_follows_sung_notes() {
+        let mut engine = Engine1024::new(VocalEffectsConfig::default(), MusicalSettings::default());
+        let mut tracker =
+            MidiTracker::<64>::new(MidiOptions { pitch_bend_range: None, ..Default::default() });
+        let mut output = [0.0f32; 256];
+        let mut notes = [0u8; 8];
+        let mut count = 0;
+
+        let mut phase = 0.0f32;
+        for block in 0..120 {
+            let frequency = match block {
+                0..40 => 220.0,
+                40..80 => 261.63,
+                _ => 0.0,
+            };
+            let input: [f32; 256] = core::array::from_fn(|_| {
+                phase = (phase + frequency / 48000.0).fract();
+                0.3 * libm::sinf(2.0 * PI * phase)
+            });
+            engine.process_hop_observed(&input, None, &mut output, &mut tracker).unwrap();
+            for event in tracker.events() {
+                if let MidiEvent::NoteOn { note, .. } | MidiEvent::NoteOff { note, .. } =
+                    event.event
+                {
+                    notes[count] = note;
+                    count += 1;
+                }
+            }
+        }
+
+        assert_eq!(&notes[..count], &[57, 57, 60, 60]);
+        assert_eq!(tracker.dropped_events(), 0);
+    }
+}
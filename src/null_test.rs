@@ -0,0 +1,226 @@
+//! Null testing of two renders.
+//!
+//! Rendering the same input before and after a change and subtracting the
+//! results shows what the change did to the sound: identical processing nulls
+//! to silence, and whatever is left tells where in the spectrum the two differ.
+//! [`null_test`] aligns the renders first, since a change in latency would
+//! otherwise swamp the residual, then reports the residual level per octave band.
+
+use core::fmt;
+use std::vec::Vec;
+
+use libm::{log10f, sqrtf};
+
+use crate::dsp::biquad::Biquad;
+
+/// Centre frequencies of the octave bands reported by [`null_test`]
+pub const OCTAVE_BANDS_HZ: [f32; 9] =
+    [63.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0];
+
+/// Quality of a band-pass one octave wide
+const OCTAVE_Q: f32 = core::f32::consts::SQRT_2;
+
+/// Samples compared when searching for the alignment
+const ALIGNMENT_WINDOW: usize = 1 << 16;
+
+/// Residual level in one frequency band
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BandResidual {
+    /// Centre frequency of the band in Hz
+    pub centre_hz: f32,
+    /// RMS of the reference render in the band
+    pub reference_rms: f32,
+    /// RMS of the difference between the renders in the band
+    pub residual_rms: f32,
+}
+
+impl BandResidual {
+    /// Residual relative to the reference in dB; very negative when the band nulls
+    pub fn residual_db(&self) -> f32 {
+        relative_db(self.residual_rms, self.reference_rms)
+    }
+}
+
+/// Result of a [`null_test`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct NullTestReport {
+    /// Samples the candidate lags the reference by; negative if it leads
+    pub offset: isize,
+    /// Samples compared after alignment
+    pub compared_samples: usize,
+    /// RMS of the reference render over the compared samples
+    pub reference_rms: f32,
+    /// RMS of the broadband difference
+    pub residual_rms: f32,
+    /// Residual per octave band, for the bands below Nyquist
+    pub bands: Vec<BandResidual>,
+}
+
+impl NullTestReport {
+    /// Broadband residual relative to the reference in dB
+    pub fn residual_db(&self) -> f32 {
+        relative_db(self.residual_rms, self.reference_rms)
+    }
+
+    /// Returns `true` if the residual is at least `depth_db` below the reference
+    /// overall and in every band
+    pub fn nulls_to(&self, depth_db: f32) -> bool {
+        self.residual_db() <= -depth_db
+            && self.bands.iter().all(|band| band.residual_db() <= -depth_db)
+    }
+}
+
+impl fmt::Display for NullTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "offset {} samples, {} samples compared", self.offset, self.compared_samples)?;
+        writeln!(f, "broadband {:8.1} dB", self.residual_db())?;
+        for band in &self.bands {
+            writeln!(f, "{:7.0} Hz {:8.1} dB", band.centre_hz, band.residual_db())?;
+        }
+        Ok(())
+    }
+}
+
+/// Aligns `candidate` to `reference`, subtracts it and measures the residual
+///
+/// The alignment is the lag within `max_offset` samples either way with the
+/// highest cross-correlation over the first 65536 samples. Levels are not
+/// matched: a gain change shows up in the residual.
+///
+/// ```rust
+/// use synthphone_e_vocal_dsp::null_test::null_test;
+///
+/// let reference: Vec<f32> = (0..4800).map(|n| (n as f32 * 0.05).sin()).collect();
+/// let mut candidate = vec![0.0; 32];
+/// candidate.extend_from_slice(&reference);
+///
+/// let report = null_test(&reference, &candidate, 48000.0, 64);
+/// assert_eq!(report.offset, 32);
+/// assert!(report.nulls_to(90.0));
+/// ```
+pub fn null_test(
+    reference: &[f32],
+    candidate: &[f32],
+    sample_rate: f32,
+    max_offset: usize,
+) -> NullTestReport {
+    let offset = best_offset(reference, candidate, max_offset);
+    let (reference, candidate) = aligned(reference, candidate, offset);
+    let residual: Vec<f32> = candidate.iter().zip(reference).map(|(c, r)| c - r).collect();
+
+    let bands = OCTAVE_BANDS_HZ
+        .iter()
+        .filter(|&&centre| centre * core::f32::consts::SQRT_2 < sample_rate / 2.0)
+        .map(|&centre_hz| BandResidual {
+            centre_hz,
+            reference_rms: band_rms(reference, centre_hz, sample_rate),
+            residual_rms: band_rms(&residual, centre_hz, sample_rate),
+        })
+        .collect();
+
+    NullTestReport {
+        offset,
+        compared_samples: residual.len(),
+        reference_rms: rms(reference.iter().copied()),
+        residual_rms: rms(residual.iter().copied()),
+        bands,
+    }
+}
+
+/// Lag of `candidate` behind `reference` with the highest cross-correlation
+fn best_offset(reference: &[f32], candidate: &[f32], max_offset: usize) -> isize {
+    let max_offset = max_offset.min(isize::MAX as usize) as isize;
+    let mut best = (0isize, f32::NEG_INFINITY);
+    for offset in -max_offset..=max_offset {
+        let (reference, candidate) = aligned(reference, candidate, offset);
+        let correlation: f32 =
+            reference.iter().zip(candidate).take(ALIGNMENT_WINDOW).map(|(r, c)| r * c).sum();
+        // Prefer the smallest lag on ties, such as between two silent renders
+        if correlation > best.1 || (correlation == best.1 && offset.abs() < best.0.abs()) {
+            best = (offset, correlation);
+        }
+    }
+    best.0
+}
+
+/// Overlapping parts of the renders with `candidate` shifted back by `offset`
+fn aligned<'a>(
+    reference: &'a [f32],
+    candidate: &'a [f32],
+    offset: isize,
+) -> (&'a [f32], &'a [f32]) {
+    let (reference, candidate) = if offset >= 0 {
+        (reference, candidate.get(offset as usize..).unwrap_or(&[]))
+    } else {
+        (reference.get(offset.unsigned_abs()..).unwrap_or(&[]), candidate)
+    };
+    let len = reference.len().min(candidate.len());
+    (&reference[..len], &candidate[..len])
+}
+
+fn band_rms(samples: &[f32], centre_hz: f32, sample_rate: f32) -> f32 {
+    let mut filter = Biquad::band_pass(centre_hz, OCTAVE_Q, sample_rate);
+    rms(samples.iter().map(|&sample| filter.process(sample)))
+}
+
+fn rms(samples: impl Iterator<Item = f32>) -> f32 {
+    let (sum, count) = samples.fold((0.0f64, 0usize), |(sum, count), sample| {
+        (sum + f64::from(sample) * f64::from(sample), count + 1)
+    });
+    if count == 0 {
+        0.0
+    } else {
+        sqrtf((sum / count as f64) as f32)
+    }
+}
+
+fn relative_db(level: f32, reference: f32) -> f32 {
+    if level <= 0.0 {
+        f32::NEG_INFINITY
+    } else {
+        20.0 * log10f(level / reference.max(f32::MIN_POSITIVE))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::f32::consts::PI;
+
+    fn tone(frequency: f32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|n| 0.5 * libm::sinf(2.0 * PI * frequency * n as f32 / 48000.0))
+            .collect()
+    }
+
+    #[test]
+    fn test_identical_renders_null_after_alignment() {
+        let reference: Vec<f32> =
+            tone(220.0, 9600).iter().zip(tone(3100.0, 9600)).map(|(a, b)| a + b).collect();
+        let candidate = &reference[17..];
+        let report = null_test(&reference, candidate, 48000.0, 64);
+        assert_eq!(report.offset, -17);
+        assert_eq!(report.compared_samples, 9600 - 17);
+        assert_eq!(report.residual_rms, 0.0);
+        assert!(report.nulls_to(120.0));
+        assert_eq!(report.bands.len(), 9);
+    }
+
+    #[test]
+    fn test_residual_is_reported_in_its_band() {
+        let reference = tone(250.0, 24000);
+        let candidate: Vec<f32> = reference
+            .iter()
+            .zip(tone(4000.0, 24000))
+            .map(|(r, added)| r + 0.01 * added)
+            .collect();
+        let report = null_test(&reference, &candidate, 48000.0, 8);
+        assert_eq!(report.offset, 0);
+
+        let band = |hz: f32| report.bands.iter().find(|band| band.centre_hz == hz).unwrap();
+        assert!(band(4000.0).residual_rms > 0.003, "{report}");
+        assert!(band(250.0).residual_rms < 0.001, "{report}");
+        assert!((report.residual_db() + 40.0).abs() < 1.0, "{report}");
+        assert!(!report.nulls_to(50.0));
+    }
+}
@@ -0,0 +1,134 @@
+//! FLAC decoding with claxon.
+//!
+//! [`read`] decodes FLAC files of 8 to 32 bits with any number of channels. The
+//! processed version is written as a WAV file of the same bit depth, widened to
+//! the nearest one [`wav::write`](super::wav::write) supports.
+
+use std::{io::Read, vec::Vec};
+
+use claxon::FlacReader;
+
+use super::{Audio, wav::WavFormat};
+
+/// Reads a FLAC file, returning its audio and the format to write it back in
+pub fn read<R: Read>(reader: R) -> Result<(Audio, WavFormat), claxon::Error> {
+    let mut reader = FlacReader::new(reader)?;
+    let info = reader.streaminfo();
+    let scale = 1.0 / (1u64 << (info.bits_per_sample - 1)) as f32;
+    let samples: Vec<f32> = reader
+        .samples()
+        .map(|sample| sample.map(|sample| sample as f32 * scale))
+        .collect::<Result<_, _>>()?;
+    let audio = Audio::from_interleaved(info.sample_rate, info.channels as usize, &samples);
+    Ok((audio, WavFormat::for_int_bits(info.bits_per_sample)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        Engine1024, MusicalSettings, ProcessingMode, VocalEffectsConfig,
+        offline::{self, wav},
+    };
+
+    /// CRC-8 of FLAC frame headers, polynomial 0x07
+    fn crc8(bytes: &[u8]) -> u8 {
+        bytes.iter().fold(0u8, |crc, &byte| {
+            (0..8).fold(crc ^ byte, |crc, _| {
+                if crc & 0x80 != 0 {
+                    (crc << 1) ^ 0x07
+                } else {
+                    crc << 1
+                }
+            })
+        })
+    }
+
+    /// CRC-16 of FLAC frames, polynomial 0x8005
+    fn crc16(bytes: &[u8]) -> u16 {
+        bytes.iter().fold(0u16, |crc, &byte| {
+            (0..8).fold(crc ^ ((byte as u16) << 8), |crc, _| {
+                if crc & 0x8000 != 0 {
+                    (crc << 1) ^ 0x8005
+                } else {
+                    crc << 1
+                }
+            })
+        })
+    }
+
+    /// Encodes interleaved 16-bit `samples` as FLAC with verbatim subframes
+    fn encode(sample_rate: u32, channels: usize, samples: &[i16]) -> Vec<u8> {
+        const BLOCK: usize = 256;
+        let frames = samples.len() / channels;
+        let mut bytes = b"fLaC".to_vec();
+        // Last metadata block, STREAMINFO, 34 bytes
+        bytes.extend_from_slice(&[0x80, 0, 0, 34]);
+        bytes.extend_from_slice(&(BLOCK as u16).to_be_bytes());
+        bytes.extend_from_slice(&(BLOCK as u16).to_be_bytes());
+        bytes.extend_from_slice(&[0; 6]);
+        // Sample rate (20 bits), channels - 1 (3), bits - 1 (5), total frames (36)
+        let packed =
+            (sample_rate as u64) << 44 | ((channels as u64 - 1) << 41) | (15 << 36) | frames as u64;
+        bytes.extend_from_slice(&packed.to_be_bytes());
+        bytes.extend_from_slice(&[0; 16]);
+
+        for (index, block) in samples.chunks(BLOCK * channels).enumerate() {
+            let start = bytes.len();
+            // Fixed block size, 8-bit block size at the end, rate from STREAMINFO,
+            // independent channels, 16 bits per sample
+            bytes.extend_from_slice(&[0xFF, 0xF8, 0x60, ((channels as u8 - 1) << 4) | 0x08]);
+            bytes.push(index as u8);
+            bytes.push((block.len() / channels - 1) as u8);
+            bytes.push(crc8(&bytes[start..]));
+            for channel in 0..channels {
+                bytes.push(0x02);
+                for frame in block.chunks_exact(channels) {
+                    bytes.extend_from_slice(&frame[channel].to_be_bytes());
+                }
+            }
+            let crc = crc16(&bytes[start..]);
+            bytes.extend_from_slice(&crc.to_be_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_reads_flac() {
+        let samples: Vec<i16> = (0..1200).map(|n| (n as i16 - 600) * 50).collect();
+        let (audio, format) = read(Cursor::new(encode(44100, 2, &samples))).unwrap();
+        assert_eq!(format, WavFormat::Int16);
+        assert_eq!((audio.sample_rate, audio.channels.len(), audio.len()), (44100, 2, 600));
+        for (read_back, &original) in audio.interleaved().iter().zip(&samples) {
+            assert_eq!(*read_back, original as f32 / 32768.0);
+        }
+
+        let (_, format) = offline::read(Cursor::new(encode(44100, 1, &samples))).unwrap();
+        assert_eq!(format, WavFormat::Int16);
+        assert!(matches!(
+            offline::read(Cursor::new(&b"fLaC\x80\0\0\x22"[..])),
+            Err(offline::OfflineError::Flac(_))
+        ));
+    }
+
+    #[test]
+    fn test_processed_flac_is_aligned_with_input() {
+        let clicks: Vec<i16> = (0..5000).map(|n| if n == 777 { 16384 } else { 0 }).collect();
+        let config = VocalEffectsConfig::builder().wet_dry(0.0).build().unwrap();
+        let settings = MusicalSettings { mode: ProcessingMode::Dry, ..Default::default() };
+        let mut engine = Engine1024::new(config, settings);
+
+        let (audio, format) = offline::read(Cursor::new(encode(48000, 1, &clicks))).unwrap();
+        let processed = offline::process(&mut engine, &audio).unwrap();
+        let mut output = Cursor::new(Vec::new());
+        wav::write(&mut output, &processed, format).unwrap();
+
+        output.set_position(0);
+        let (written, _) = wav::read(output).unwrap();
+        assert_eq!(written.len(), clicks.len());
+        assert_eq!(written.channels[0][777], 0.5);
+        assert_eq!(written.channels[0].iter().filter(|&&sample| sample != 0.0).count(), 1);
+    }
+}
@@ -0,0 +1,488 @@
+//! Offline processing of recorded audio.
+//!
+//! [`Audio`] holds a recording as one buffer per channel, and [`process`] runs
+//! every channel through an engine with [`Engine::process_iter`], so the result
+//! lines up with the recording sample for sample: the engine latency is removed
+//! and the tail is flushed.
+//!
+//! With the `wav` feature, the [`wav`] module reads and writes WAV files with
+//! hound and processes them in one call. The `flac` and `ogg` features add
+//! decoders for FLAC and Ogg Vorbis recordings, which [`read`] recognises by
+//! their first bytes, so field recordings can be processed without converting
+//! them to WAV first.
+//!
+//! [`process_logged`] also returns the pitch correction of every frame, which
+//! an editor can show and change to re-render with its own targets without
+//! detecting the pitch again. With the `serde` feature the log serializes to any
+//! format serde supports.
+
+use core::fmt;
+#[cfg(feature = "wav")]
+use std::io::{Read, Seek};
+use std::{io, vec::Vec};
+
+use crate::{
+    Engine, ProcessingMode, VocalEffectsError,
+    dsp::DynFft,
+    engine::{Automation, FrameView},
+};
+
+#[cfg(feature = "flac")]
+pub mod flac;
+#[cfg(feature = "ogg")]
+pub mod ogg;
+#[cfg(feature = "wav")]
+pub mod wav;
+
+/// A recording held as one buffer per channel
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Audio {
+    /// Sample rate in Hz
+    pub sample_rate: u32,
+    /// Samples of each channel, full scale at ±1.0
+    pub channels: Vec<Vec<f32>>,
+}
+
+impl Audio {
+    /// Creates a single-channel recording
+    pub fn mono(sample_rate: u32, samples: Vec<f32>) -> Self {
+        Self { sample_rate, channels: std::vec![samples] }
+    }
+
+    /// Splits interleaved `L R L R ...` frames of `channels` channels
+    ///
+    /// A trailing partial frame is dropped.
+    pub fn from_interleaved(sample_rate: u32, channels: usize, samples: &[f32]) -> Self {
+        let channels = (0..channels)
+            .map(|channel| samples.chunks_exact(channels).map(|frame| frame[channel]).collect())
+            .collect();
+        Self { sample_rate, channels }
+    }
+
+    /// Interleaves the channels into `L R L R ...` frames
+    ///
+    /// Channels are cut to the shortest one.
+    pub fn interleaved(&self) -> Vec<f32> {
+        let mut samples = Vec::with_capacity(self.len() * self.channels.len());
+        for frame in 0..self.len() {
+            samples.extend(self.channels.iter().map(|channel| channel[frame]));
+        }
+        samples
+    }
+
+    /// Length in samples per channel, that of the shortest channel
+    pub fn len(&self) -> usize {
+        self.channels.iter().map(Vec::len).min().unwrap_or(0)
+    }
+
+    /// Returns `true` if there are no channels or no samples
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Length in seconds
+    pub fn duration(&self) -> f32 {
+        if self.sample_rate == 0 {
+            0.0
+        } else {
+            self.len() as f32 / self.sample_rate as f32
+        }
+    }
+}
+
+/// Container format of an audio file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    /// RIFF WAVE
+    Wav,
+    /// Native FLAC
+    Flac,
+    /// Ogg, decoded as Vorbis
+    Ogg,
+}
+
+impl FileFormat {
+    /// Recognises a file by its first four bytes
+    pub fn detect(header: &[u8]) -> Option<Self> {
+        match header.get(..4)? {
+            b"RIFF" => Some(Self::Wav),
+            b"fLaC" => Some(Self::Flac),
+            b"OggS" => Some(Self::Ogg),
+            _ => None,
+        }
+    }
+}
+
+/// Reads a recording in any enabled format, returning its audio and the WAV
+/// format to write a processed version in
+///
+/// FLAC keeps its bit depth and Ogg Vorbis, which decodes to floating point, is
+/// written as 32-bit float.
+///
+/// # Errors
+///
+/// Returns [`OfflineError::UnsupportedFormat`] if the file is in none of the
+/// formats enabled by features, and the decoder error if it is corrupt.
+#[cfg(feature = "wav")]
+pub fn read<R: Read + Seek>(mut reader: R) -> Result<(Audio, wav::WavFormat), OfflineError> {
+    let mut header = Vec::new();
+    reader.by_ref().take(4).read_to_end(&mut header)?;
+    reader.rewind()?;
+    match FileFormat::detect(&header) {
+        Some(FileFormat::Wav) => Ok(wav::read(reader)?),
+        #[cfg(feature = "flac")]
+        Some(FileFormat::Flac) => Ok(flac::read(reader)?),
+        #[cfg(feature = "ogg")]
+        Some(FileFormat::Ogg) => Ok(ogg::read(reader)?),
+        _ => Err(OfflineError::UnsupportedFormat),
+    }
+}
+
+/// Error while processing a recording offline
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum OfflineError {
+    /// The engine cannot process the recording
+    Processing(VocalEffectsError),
+    /// A file could not be opened or read
+    Io(io::Error),
+    /// The file is in a format without an enabled decoder
+    UnsupportedFormat,
+    /// A WAV file could not be read or written
+    #[cfg(feature = "wav")]
+    Wav(hound::Error),
+    /// A FLAC file could not be decoded
+    #[cfg(feature = "flac")]
+    Flac(claxon::Error),
+    /// An Ogg Vorbis file could not be decoded
+    #[cfg(feature = "ogg")]
+    Ogg(lewton::VorbisError),
+}
+
+impl From<VocalEffectsError> for OfflineError {
+    fn from(error: VocalEffectsError) -> Self {
+        Self::Processing(error)
+    }
+}
+
+impl From<io::Error> for OfflineError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+#[cfg(feature = "wav")]
+impl From<hound::Error> for OfflineError {
+    fn from(error: hound::Error) -> Self {
+        Self::Wav(error)
+    }
+}
+
+#[cfg(feature = "flac")]
+impl From<claxon::Error> for OfflineError {
+    fn from(error: claxon::Error) -> Self {
+        Self::Flac(error)
+    }
+}
+
+#[cfg(feature = "ogg")]
+impl From<lewton::VorbisError> for OfflineError {
+    fn from(error: lewton::VorbisError) -> Self {
+        Self::Ogg(error)
+    }
+}
+
+impl fmt::Display for OfflineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Processing(error) => write!(f, "processing failed: {error}"),
+            Self::Io(error) => write!(f, "I/O error: {error}"),
+            Self::UnsupportedFormat => write!(f, "unsupported audio file format"),
+            #[cfg(feature = "wav")]
+            Self::Wav(error) => write!(f, "WAV file error: {error}"),
+            #[cfg(feature = "flac")]
+            Self::Flac(error) => write!(f, "FLAC file error: {error}"),
+            #[cfg(feature = "ogg")]
+            Self::Ogg(error) => write!(f, "Ogg Vorbis file error: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for OfflineError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Processing(error) => Some(error),
+            Self::Io(error) => Some(error),
+            Self::UnsupportedFormat => None,
+            #[cfg(feature = "wav")]
+            Self::Wav(error) => Some(error),
+            #[cfg(feature = "flac")]
+            Self::Flac(error) => Some(error),
+            #[cfg(feature = "ogg")]
+            Self::Ogg(error) => Some(error),
+        }
+    }
+}
+
+/// Processes every channel of `audio` and returns the output aligned with it
+///
+/// Each channel starts from a reset engine and keeps its configuration and
+/// settings. The output is as long as the input, or as long as it is stretched
+/// to with a separate synthesis hop. Vocode and talk-box mode get a silent
+/// carrier.
+///
+/// # Errors
+///
+/// Returns [`VocalEffectsError::InvalidConfiguration`] if the engine is
+/// configured for another sample rate than the recording, or its hop ratio gives
+/// no valid hop size.
+pub fn process<const N: usize, const HALF_N: usize, F>(
+    engine: &mut Engine<N, HALF_N, F>,
+    audio: &Audio,
+) -> Result<Audio, VocalEffectsError>
+where
+    F: DynFft<N, HALF_N>,
+{
+    check_engine(engine, audio)?;
+    let length = audio.len();
+    let channels = audio
+        .channels
+        .iter()
+        .map(|channel| {
+            engine.reset();
+            engine.process_iter(channel[..length].iter().copied()).collect()
+        })
+        .collect();
+    engine.reset();
+    Ok(Audio { sample_rate: audio.sample_rate, channels })
+}
+
+/// Processes every channel of `audio` with scheduled changes
+///
+/// Each channel starts from the engine's current settings with the automation
+/// rewound, and changes are centred on their sample as by
+/// [`Engine::process_iter_automated`]. The engine is left with the settings it
+/// started with. Otherwise works like [`process`].
+///
+/// # Errors
+///
+/// See [`process`].
+pub fn process_automated<const N: usize, const HALF_N: usize, F, const CAPACITY: usize>(
+    engine: &mut Engine<N, HALF_N, F>,
+    audio: &Audio,
+    automation: &mut Automation<CAPACITY>,
+) -> Result<Audio, VocalEffectsError>
+where
+    F: DynFft<N, HALF_N>,
+{
+    check_engine(engine, audio)?;
+    let settings = *engine.settings();
+    let length = audio.len();
+    let channels = audio
+        .channels
+        .iter()
+        .map(|channel| {
+            engine.set_settings(settings);
+            engine.reset();
+            automation.rewind();
+            engine
+                .process_iter_automated(channel[..length].iter().copied(), automation)
+                .collect()
+        })
+        .collect();
+    engine.set_settings(settings);
+    engine.reset();
+    Ok(Audio { sample_rate: audio.sample_rate, channels })
+}
+
+/// Pitch correction of one frame of a recording
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CorrectionFrame {
+    /// Centre of the frame in seconds from the start of the recording
+    pub time: f32,
+    /// Detected pitch in Hz, 0.0 if no pitch was found
+    pub detected_frequency: f32,
+    /// Pitch the frame was corrected towards in Hz
+    pub target_frequency: f32,
+    /// Pitch shift ratio applied to the frame
+    pub pitch_shift_ratio: f32,
+}
+
+/// Processes every channel of `audio` and logs the correction of each frame
+///
+/// Returns the output as [`process`] does, and for each channel the
+/// correction of every autotune frame whose centre lies within the recording,
+/// in order. Frames in other modes detect no pitch and are left out.
+///
+/// # Errors
+///
+/// See [`process`].
+pub fn process_logged<const N: usize, const HALF_N: usize, F>(
+    engine: &mut Engine<N, HALF_N, F>,
+    audio: &Audio,
+) -> Result<(Audio, Vec<Vec<CorrectionFrame>>), VocalEffectsError>
+where
+    F: DynFft<N, HALF_N>,
+{
+    check_engine(engine, audio)?;
+    let length = audio.len();
+    let (hop, sample_rate) = (engine.hop_size(), engine.config().sample_rate);
+    let centre = engine.config().frame_size_for(N) / 2;
+    let mut logs = Vec::with_capacity(audio.channels.len());
+    let channels = audio
+        .channels
+        .iter()
+        .map(|channel| {
+            let mut log = Vec::new();
+            let mut observer = |frame: &FrameView<'_>| {
+                // The frame ends with the input of its hop
+                let end = frame.hop_index as usize * hop;
+                let Some(time) = end.checked_sub(centre).filter(|&time| time < length) else {
+                    return;
+                };
+                if frame.mode == ProcessingMode::Autotune {
+                    log.push(CorrectionFrame {
+                        time: time as f32 / sample_rate,
+                        detected_frequency: frame.analysis.detected_frequency,
+                        target_frequency: frame.analysis.target_frequency,
+                        pitch_shift_ratio: frame.analysis.pitch_shift_ratio,
+                    });
+                }
+            };
+            engine.reset();
+            let output =
+                engine.process_iter_observed(channel[..length].iter().copied(), &mut observer);
+            let output = output.collect();
+            logs.push(log);
+            output
+        })
+        .collect();
+    engine.reset();
+    Ok((Audio { sample_rate: audio.sample_rate, channels }, logs))
+}
+
+/// Checks that `engine` can process `audio`
+fn check_engine<const N: usize, const HALF_N: usize, F>(
+    engine: &Engine<N, HALF_N, F>,
+    audio: &Audio,
+) -> Result<(), VocalEffectsError>
+where
+    F: DynFft<N, HALF_N>,
+{
+    let (hop, synthesis_hop) = (engine.hop_size(), engine.synthesis_hop_size());
+    if engine.config().sample_rate != audio.sample_rate as f32
+        || hop == 0
+        || hop > N
+        || synthesis_hop == 0
+        || synthesis_hop > N
+    {
+        return Err(VocalEffectsError::InvalidConfiguration);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Engine1024, MusicalSettings, ProcessingMode, VocalEffectsConfig,
+        engine::automation::{AutomationEvent, Change},
+    };
+
+    #[test]
+    fn test_interleaving_round_trips() {
+        let samples = [0.1, -0.1, 0.2, -0.2, 0.3, -0.3, 0.4];
+        let audio = Audio::from_interleaved(48000, 2, &samples);
+        assert_eq!(audio.channels, [[0.1, 0.2, 0.3], [-0.1, -0.2, -0.3]]);
+        assert_eq!(audio.len(), 3);
+        assert_eq!(audio.interleaved(), samples[..6]);
+        assert!(Audio::default().is_empty());
+    }
+
+    #[test]
+    fn test_automation_replays_for_every_channel() {
+        let config = VocalEffectsConfig::builder().mode_crossfade_hops(0).build().unwrap();
+        let settings = MusicalSettings { mode: ProcessingMode::Dry, ..Default::default() };
+        let mut engine = Engine1024::new(config, settings);
+        let mut automation = Automation::<1>::new();
+        let mute = AutomationEvent::new(4000, Change::Mode(ProcessingMode::Vocode));
+        automation.schedule(mute).unwrap();
+
+        let sine: Vec<f32> = (0..8000).map(|n| 0.5 * libm::sinf(n as f32 * 0.1)).collect();
+        let audio = Audio { sample_rate: 48000, channels: std::vec![sine.clone(), sine] };
+        let output = process_automated(&mut engine, &audio, &mut automation).unwrap();
+        for channel in &output.channels {
+            assert!(channel[3000..3400].iter().any(|sample| sample.abs() > 0.3));
+            assert!(channel[4600..].iter().all(|sample| sample.abs() < 1e-3));
+        }
+        assert_eq!(*engine.settings(), settings);
+    }
+
+    #[test]
+    fn test_log_follows_the_correction() {
+        let config = VocalEffectsConfig::builder().mode_crossfade_hops(0).build().unwrap();
+        let mut engine = Engine1024::new(config, MusicalSettings::default());
+        let hop = engine.hop_size();
+        // A slightly flat A, corrected up to 440 Hz
+        let tone = |hz: f32| -> Vec<f32> {
+            (0..9600)
+                .map(|n| 0.5 * libm::sinf(2.0 * core::f32::consts::PI * hz * n as f32 / 48000.0))
+                .collect()
+        };
+        let audio = Audio { sample_rate: 48000, channels: std::vec![tone(436.0), tone(220.0)] };
+
+        let (output, logs) = process_logged(&mut engine, &audio).unwrap();
+        assert_eq!(output, process(&mut engine, &audio).unwrap());
+        assert_eq!(logs.len(), 2);
+        for (log, target) in logs.iter().zip([440.0, 220.0]) {
+            // One frame per hop, from the first centred inside the recording
+            assert_eq!(log.len(), 9600usize.div_ceil(hop));
+            let step = hop as f32 / 48000.0;
+            assert!(log.windows(2).all(|pair| (pair[1].time - pair[0].time - step).abs() < 1e-6));
+            let settled = log.last().unwrap();
+            assert_eq!(settled.target_frequency, target);
+            assert!((settled.detected_frequency * settled.pitch_shift_ratio - target).abs() < 2.0);
+        }
+
+        // Other modes detect no pitch
+        engine.set_mode(ProcessingMode::Dry);
+        let (_, logs) = process_logged(&mut engine, &audio).unwrap();
+        assert!(logs.iter().all(Vec::is_empty));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_log_serializes() {
+        fn serializable<T: serde::Serialize + serde::de::DeserializeOwned>() {}
+        serializable::<Vec<CorrectionFrame>>();
+    }
+
+    #[test]
+    fn test_detects_file_format() {
+        assert_eq!(FileFormat::detect(b"RIFF\x24\0\0\0WAVE"), Some(FileFormat::Wav));
+        assert_eq!(FileFormat::detect(b"fLaC"), Some(FileFormat::Flac));
+        assert_eq!(FileFormat::detect(b"OggS\0\x02"), Some(FileFormat::Ogg));
+        assert_eq!(FileFormat::detect(b"ID3\x04"), None);
+        assert_eq!(FileFormat::detect(b"fLa"), None);
+    }
+
+    #[test]
+    fn test_process_keeps_channels_aligned() {
+        let config = VocalEffectsConfig::builder().wet_dry(0.0).build().unwrap();
+        let mut engine = Engine1024::new(config, MusicalSettings::default());
+        let impulse = |at: usize| (0..3000).map(|n| if n == at { 1.0 } else { 0.0 }).collect();
+        let audio = Audio { sample_rate: 48000, channels: std::vec![impulse(500), impulse(2500)] };
+
+        let output = process(&mut engine, &audio).unwrap();
+        assert_eq!(output.sample_rate, 48000);
+        assert_eq!(output.channels[0].len(), 3000);
+        assert_eq!((output.channels[0][500], output.channels[1][2500]), (1.0, 1.0));
+
+        let wrong_rate = Audio { sample_rate: 44100, ..audio };
+        assert!(matches!(
+            process(&mut engine, &wrong_rate),
+            Err(VocalEffectsError::InvalidConfiguration)
+        ));
+    }
+}
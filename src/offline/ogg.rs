@@ -0,0 +1,43 @@
+//! Ogg Vorbis decoding with lewton.
+//!
+//! [`read`] decodes the first Vorbis stream of an Ogg file. Vorbis decodes to
+//! floating point, so the processed version is written as 32-bit float WAV and
+//! keeps whatever the lossy encoding left of the quiet passages.
+
+use std::{
+    io::{Read, Seek},
+    vec::Vec,
+};
+
+use lewton::{inside_ogg::OggStreamReader, samples::InterleavedSamples};
+
+use super::{Audio, wav::WavFormat};
+
+/// Reads an Ogg Vorbis file, returning its audio and the format to write it back in
+pub fn read<R: Read + Seek>(reader: R) -> Result<(Audio, WavFormat), lewton::VorbisError> {
+    let mut reader = OggStreamReader::new(reader)?;
+    let channels = usize::from(reader.ident_hdr.audio_channels);
+    let mut samples = Vec::new();
+    while let Some(packet) = reader.read_dec_packet_generic::<InterleavedSamples<f32>>()? {
+        samples.extend_from_slice(&packet.samples);
+    }
+    let audio = Audio::from_interleaved(reader.ident_hdr.audio_sample_rate, channels, &samples);
+    Ok((audio, WavFormat::Float32))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::offline::{self, OfflineError};
+
+    #[test]
+    fn test_rejects_ogg_without_vorbis() {
+        // An Ogg page whose only packet is not a Vorbis identification header
+        let mut page = b"OggS\0\x02".to_vec();
+        page.extend_from_slice(&[0; 8]);
+        page.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 4]);
+        page.extend_from_slice(b"Opus");
+        assert!(matches!(offline::read(Cursor::new(page)), Err(OfflineError::Ogg(_))));
+    }
+}
@@ -0,0 +1,270 @@
+//! WAV reading and writing with hound.
+//!
+//! [`read`] accepts integer files of 8 to 32 bits and 32-bit float files with
+//! any number of channels, and reports the [`WavFormat`] to write a processed
+//! version back in: the source format, widened to the nearest one [`write()`]
+//! supports. [`process`] reads, processes and writes in one call.
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Seek, Write},
+    path::Path,
+    vec::Vec,
+};
+
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+
+use super::{Audio, OfflineError};
+use crate::{Engine, convert, dsp::DynFft};
+
+/// Sample format of a written WAV file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WavFormat {
+    /// 16-bit integer
+    Int16,
+    /// 24-bit integer
+    Int24,
+    /// 32-bit integer
+    Int32,
+    /// 32-bit float
+    Float32,
+}
+
+impl WavFormat {
+    /// Format that holds samples of `spec` without losing resolution
+    ///
+    /// Integer files narrower than 16 bits widen to 16, and ones between the
+    /// supported widths to the next wider one.
+    pub fn negotiate(spec: &WavSpec) -> Self {
+        match spec.sample_format {
+            SampleFormat::Float => Self::Float32,
+            SampleFormat::Int => Self::for_int_bits(u32::from(spec.bits_per_sample)),
+        }
+    }
+
+    /// Integer format that holds `bits` bits per sample, widened as in
+    /// [`WavFormat::negotiate`]
+    pub fn for_int_bits(bits: u32) -> Self {
+        match bits {
+            0..=16 => Self::Int16,
+            17..=24 => Self::Int24,
+            _ => Self::Int32,
+        }
+    }
+
+    /// Bits per sample
+    pub fn bits_per_sample(self) -> u16 {
+        match self {
+            Self::Int16 => 16,
+            Self::Int24 => 24,
+            Self::Int32 | Self::Float32 => 32,
+        }
+    }
+
+    /// hound spec of a file in this format
+    pub fn spec(self, channels: u16, sample_rate: u32) -> WavSpec {
+        let sample_format = if self == Self::Float32 {
+            SampleFormat::Float
+        } else {
+            SampleFormat::Int
+        };
+        WavSpec { channels, sample_rate, bits_per_sample: self.bits_per_sample(), sample_format }
+    }
+}
+
+/// Reads a WAV file, returning its audio and the format to write it back in
+pub fn read<R: Read>(reader: R) -> Result<(Audio, WavFormat), hound::Error> {
+    let mut reader = WavReader::new(reader)?;
+    let spec = reader.spec();
+    let samples: Vec<f32> = match spec.sample_format {
+        SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+        SampleFormat::Int => {
+            let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 * scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
+    let audio = Audio::from_interleaved(spec.sample_rate, usize::from(spec.channels), &samples);
+    Ok((audio, WavFormat::negotiate(&spec)))
+}
+
+/// Writes `audio` as a WAV file in `format`
+///
+/// Integer formats saturate samples beyond full scale. Channels are cut to the
+/// shortest one.
+pub fn write<W: Write + Seek>(
+    writer: W,
+    audio: &Audio,
+    format: WavFormat,
+) -> Result<(), hound::Error> {
+    let channels = u16::try_from(audio.channels.len())
+        .ok()
+        .filter(|&channels| channels > 0)
+        .ok_or(hound::Error::Unsupported)?;
+    let mut writer = WavWriter::new(writer, format.spec(channels, audio.sample_rate))?;
+    let samples = audio.interleaved();
+    // The lengths always match, so the conversions cannot fail
+    match format {
+        WavFormat::Int16 => {
+            let mut converted = std::vec![0i16; samples.len()];
+            let _ = convert::f32_to_i16(&samples, &mut converted);
+            converted.into_iter().try_for_each(|sample| writer.write_sample(sample))?;
+        }
+        WavFormat::Int24 | WavFormat::Int32 => {
+            let mut converted = std::vec![0i32; samples.len()];
+            let _ = if format == WavFormat::Int24 {
+                convert::f32_to_i24(&samples, &mut converted)
+            } else {
+                convert::f32_to_i32(&samples, &mut converted)
+            };
+            converted.into_iter().try_for_each(|sample| writer.write_sample(sample))?;
+        }
+        WavFormat::Float32 => {
+            samples.into_iter().try_for_each(|sample| writer.write_sample(sample))?;
+        }
+    }
+    writer.finalize()
+}
+
+/// Processes a WAV file with `engine` into a WAV file of the same format
+///
+/// The output has the sample rate and channels of the input, and is aligned
+/// with it as by [`offline::process`](super::process). Returns the format the
+/// output was written in.
+///
+/// # Errors
+///
+/// Returns [`OfflineError::Wav`] if the input cannot be read or the output
+/// written, and [`OfflineError::Processing`] if the engine is configured for
+/// another sample rate or has no valid hop size.
+pub fn process<const N: usize, const HALF_N: usize, F, R, W>(
+    engine: &mut Engine<N, HALF_N, F>,
+    input: R,
+    output: W,
+) -> Result<WavFormat, OfflineError>
+where
+    F: DynFft<N, HALF_N>,
+    R: Read,
+    W: Write + Seek,
+{
+    let (audio, format) = read(input)?;
+    let processed = super::process(engine, &audio)?;
+    write(output, &processed, format)?;
+    Ok(format)
+}
+
+/// Processes the audio file at `input` into a new WAV file at `output`
+///
+/// The input can be in any format [`offline::read`](super::read) accepts, and the
+/// output is written in the format it reports. Otherwise works like [`process`].
+pub fn process_file<const N: usize, const HALF_N: usize, F>(
+    engine: &mut Engine<N, HALF_N, F>,
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+) -> Result<WavFormat, OfflineError>
+where
+    F: DynFft<N, HALF_N>,
+{
+    let (audio, format) = super::read(BufReader::new(File::open(input)?))?;
+    let processed = super::process(engine, &audio)?;
+    write(BufWriter::new(File::create(output)?), &processed, format)?;
+    Ok(format)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{Engine1024, MusicalSettings, ProcessingMode, VocalEffectsConfig};
+
+    fn wav_bytes(spec: WavSpec, samples: &[i32]) -> Vec<u8> {
+        let mut bytes = Cursor::new(Vec::new());
+        let mut writer = WavWriter::new(&mut bytes, spec).unwrap();
+        for &sample in samples {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn test_negotiates_nearest_supported_format() {
+        let spec = |bits_per_sample, sample_format| WavSpec {
+            channels: 1,
+            sample_rate: 48000,
+            bits_per_sample,
+            sample_format,
+        };
+        assert_eq!(WavFormat::negotiate(&spec(8, SampleFormat::Int)), WavFormat::Int16);
+        assert_eq!(WavFormat::negotiate(&spec(16, SampleFormat::Int)), WavFormat::Int16);
+        assert_eq!(WavFormat::negotiate(&spec(20, SampleFormat::Int)), WavFormat::Int24);
+        assert_eq!(WavFormat::negotiate(&spec(32, SampleFormat::Int)), WavFormat::Int32);
+        assert_eq!(WavFormat::negotiate(&spec(32, SampleFormat::Float)), WavFormat::Float32);
+
+        // 8-bit full scale reads as it would at 16 bits
+        let (audio, _) =
+            read(Cursor::new(wav_bytes(spec(8, SampleFormat::Int), &[-128, 64]))).unwrap();
+        assert_eq!(audio.channels, [[-1.0, 0.5]]);
+    }
+
+    #[test]
+    fn test_write_read_round_trip() {
+        let samples: Vec<f32> = (0..1000).map(|n| 0.8 * libm::sinf(n as f32 * 0.01)).collect();
+        let audio = Audio::from_interleaved(44100, 2, &samples);
+        for (format, tolerance) in [
+            (WavFormat::Int16, 1.0 / 32768.0),
+            (WavFormat::Int24, 1.0 / 8_388_608.0),
+            (WavFormat::Int32, 1e-7),
+            (WavFormat::Float32, 0.0),
+        ] {
+            let mut bytes = Cursor::new(Vec::new());
+            write(&mut bytes, &audio, format).unwrap();
+            bytes.set_position(0);
+            let (read_back, negotiated) = read(bytes).unwrap();
+            assert_eq!(negotiated, format);
+            assert_eq!(
+                (read_back.sample_rate, read_back.channels.len(), read_back.len()),
+                (44100, 2, 500)
+            );
+            for (read_back, original) in read_back.interleaved().iter().zip(&samples) {
+                assert!((read_back - original).abs() <= tolerance, "{format:?}");
+            }
+        }
+        assert!(write(Cursor::new(Vec::new()), &Audio::default(), WavFormat::Int16).is_err());
+    }
+
+    #[test]
+    fn test_processed_file_is_aligned_with_input() {
+        // A dry render passes each click through at the sample it was recorded at
+        let spec = WavFormat::Int24.spec(2, 48000);
+        let clicks: Vec<i32> =
+            (0..9000).map(|n| if n == 1001 || n == 6000 { 4_000_000 } else { 0 }).collect();
+        let config = VocalEffectsConfig::builder().wet_dry(0.0).build().unwrap();
+        let settings = MusicalSettings { mode: ProcessingMode::Dry, ..Default::default() };
+        let mut engine = Engine1024::new(config, settings);
+
+        let mut output = Cursor::new(Vec::new());
+        let format =
+            process(&mut engine, Cursor::new(wav_bytes(spec, &clicks)), &mut output).unwrap();
+        assert_eq!(format, WavFormat::Int24);
+
+        output.set_position(0);
+        let mut reader = WavReader::new(output).unwrap();
+        assert_eq!(reader.spec(), spec);
+        let processed: Vec<i32> = reader.samples::<i32>().map(Result::unwrap).collect();
+        assert_eq!(processed, clicks);
+
+        let wrong_rate = wav_bytes(WavFormat::Int16.spec(1, 44100), &[0; 100]);
+        assert!(matches!(
+            process(&mut engine, Cursor::new(wrong_rate), Cursor::new(Vec::new())),
+            Err(OfflineError::Processing(_))
+        ));
+        assert!(matches!(
+            process(&mut engine, Cursor::new(&b"RIFF"[..]), Cursor::new(Vec::new())),
+            Err(OfflineError::Wav(_))
+        ));
+    }
+}
@@ -0,0 +1,235 @@
+//! Per-stage cycle counts for verifying real-time headroom.
+//!
+//! With the `profiling` feature every processing stage is timed and the results
+//! are collected in global per-stage statistics. On Cortex-M (with the
+//! `cortex-m` feature) the DWT cycle counter is used, which the firmware must
+//! enable once at startup:
+//!
+//! ```rust,ignore
+//! let mut cp = cortex_m::Peripherals::take().unwrap();
+//! cp.DCB.enable_trace();
+//! cp.DWT.enable_cycle_counter();
+//! ```
+//!
+//! On `std` hosts the counts are nanoseconds instead of cycles. Statistics are
+//! updated with plain atomic loads and stores, so frames must be processed from
+//! one context at a time.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// A timed processing stage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Stage {
+    /// Applying the analysis window
+    Window,
+    /// Forward FFT
+    Fft,
+    /// Magnitude, phase and instantaneous frequency analysis
+    Analysis,
+    /// Cepstral envelope extraction
+    Envelope,
+    /// Pitch detection and correction target
+    PitchDetection,
+    /// Spectral shifting and phase reconstruction
+    Synthesis,
+    /// Inverse FFT
+    Ifft,
+}
+
+impl Stage {
+    /// Number of stages
+    pub const COUNT: usize = 7;
+
+    /// All stages in processing order
+    pub const ALL: [Stage; Self::COUNT] = [
+        Stage::Window,
+        Stage::Fft,
+        Stage::Analysis,
+        Stage::Envelope,
+        Stage::PitchDetection,
+        Stage::Synthesis,
+        Stage::Ifft,
+    ];
+}
+
+/// Cycle statistics of one stage
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct StageStats {
+    /// Number of measurements
+    pub count: u32,
+    /// Most recent measurement
+    pub last: u32,
+    /// Shortest measurement
+    pub min: u32,
+    /// Longest measurement
+    pub max: u32,
+    /// Sum of all measurements
+    pub total: u64,
+}
+
+impl StageStats {
+    /// Mean cycles per measurement
+    pub fn mean(&self) -> u32 {
+        if self.count == 0 {
+            0
+        } else {
+            (self.total / self.count as u64) as u32
+        }
+    }
+}
+
+struct StageCounters {
+    count: AtomicU32,
+    last: AtomicU32,
+    min: AtomicU32,
+    max: AtomicU32,
+    total_low: AtomicU32,
+    total_high: AtomicU32,
+}
+
+impl StageCounters {
+    const fn new() -> Self {
+        Self {
+            count: AtomicU32::new(0),
+            last: AtomicU32::new(0),
+            min: AtomicU32::new(u32::MAX),
+            max: AtomicU32::new(0),
+            total_low: AtomicU32::new(0),
+            total_high: AtomicU32::new(0),
+        }
+    }
+}
+
+static COUNTERS: [StageCounters; Stage::COUNT] = [const { StageCounters::new() }; Stage::COUNT];
+
+/// Current value of the cycle counter
+#[inline(always)]
+pub fn cycle_count() -> u32 {
+    #[cfg(all(feature = "cortex-m", target_arch = "arm"))]
+    {
+        cortex_m::peripheral::DWT::cycle_count()
+    }
+    #[cfg(all(feature = "std", not(all(feature = "cortex-m", target_arch = "arm"))))]
+    {
+        static EPOCH: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+        EPOCH.get_or_init(std::time::Instant::now).elapsed().as_nanos() as u32
+    }
+    #[cfg(not(any(feature = "std", all(feature = "cortex-m", target_arch = "arm"))))]
+    {
+        0
+    }
+}
+
+/// Records a measurement of `stage` that started at cycle `start`
+pub fn record(stage: Stage, start: u32) {
+    let cycles = cycle_count().wrapping_sub(start);
+    let counters = &COUNTERS[stage as usize];
+
+    counters
+        .count
+        .store(counters.count.load(Ordering::Relaxed).wrapping_add(1), Ordering::Relaxed);
+    counters.last.store(cycles, Ordering::Relaxed);
+    if cycles < counters.min.load(Ordering::Relaxed) {
+        counters.min.store(cycles, Ordering::Relaxed);
+    }
+    if cycles > counters.max.load(Ordering::Relaxed) {
+        counters.max.store(cycles, Ordering::Relaxed);
+    }
+    let total = total(counters).wrapping_add(cycles as u64);
+    counters.total_low.store(total as u32, Ordering::Relaxed);
+    counters.total_high.store((total >> 32) as u32, Ordering::Relaxed);
+}
+
+fn total(counters: &StageCounters) -> u64 {
+    (counters.total_high.load(Ordering::Relaxed) as u64) << 32
+        | counters.total_low.load(Ordering::Relaxed) as u64
+}
+
+/// Returns the statistics of `stage`
+pub fn stats(stage: Stage) -> StageStats {
+    let counters = &COUNTERS[stage as usize];
+    let count = counters.count.load(Ordering::Relaxed);
+    StageStats {
+        count,
+        last: counters.last.load(Ordering::Relaxed),
+        min: if count == 0 {
+            0
+        } else {
+            counters.min.load(Ordering::Relaxed)
+        },
+        max: counters.max.load(Ordering::Relaxed),
+        total: total(counters),
+    }
+}
+
+/// Returns the statistics of every stage, indexed like [`Stage::ALL`]
+pub fn report() -> [StageStats; Stage::COUNT] {
+    Stage::ALL.map(stats)
+}
+
+/// Clears all statistics
+pub fn reset() {
+    for counters in COUNTERS.iter() {
+        counters.count.store(0, Ordering::Relaxed);
+        counters.last.store(0, Ordering::Relaxed);
+        counters.min.store(u32::MAX, Ordering::Relaxed);
+        counters.max.store(0, Ordering::Relaxed);
+        counters.total_low.store(0, Ordering::Relaxed);
+        counters.total_high.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Records the time from its creation to its drop as one measurement of a stage
+pub struct StageTimer {
+    stage: Stage,
+    start: u32,
+}
+
+impl StageTimer {
+    /// Starts timing `stage`
+    #[inline(always)]
+    pub fn start(stage: Stage) -> Self {
+        Self { stage, start: cycle_count() }
+    }
+}
+
+impl Drop for StageTimer {
+    #[inline(always)]
+    fn drop(&mut self) {
+        record(self.stage, self.start);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Engine1024, Formant, MusicalSettings, VocalEffectsConfig};
+
+    #[test]
+    fn test_stats_mean() {
+        let stats = StageStats { count: 4, last: 10, min: 5, max: 20, total: 50 };
+        assert_eq!(stats.mean(), 12);
+        assert_eq!(StageStats::default().mean(), 0);
+    }
+
+    #[test]
+    fn test_engine_records_every_stage() {
+        let settings = MusicalSettings { formant: Formant::Lower, ..Default::default() };
+        let mut engine = Engine1024::new(VocalEffectsConfig::default(), settings);
+        let before = report();
+
+        let input = [0.1f32; 256];
+        let mut output = [0.0f32; 256];
+        for _ in 0..4 {
+            engine.process_hop(&input, None, &mut output).unwrap();
+        }
+
+        // Other tests may be processing concurrently, so only check for growth
+        for (stage, (after, before)) in Stage::ALL.iter().zip(report().iter().zip(before.iter())) {
+            assert!(after.count > before.count, "{stage:?}");
+            assert!(after.min <= after.max, "{stage:?}");
+        }
+    }
+}
@@ -2,9 +2,13 @@
 //!
 //! This module provides a high-performance, lock-free ring buffer optimized for audio
 //! processing applications where one thread produces data and another consumes it.
+//!
+//! [`DelayBuffer`] is the single-owner counterpart for delay lines: it runs over
+//! any storage, a fixed array or a heap buffer, and reads samples back by age.
 
 use core::{
     cell::UnsafeCell,
+    ops::Range,
     sync::atomic::{AtomicU32, Ordering},
 };
 
@@ -99,6 +103,25 @@ impl<const N: usize> RingBuffer<N> {
         }
     }
 
+    /// Clears the buffer contents and returns both pointers to zero.
+    ///
+    /// Takes the buffer exclusively, so neither the producer nor the consumer can
+    /// be accessing it at the same time.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use synthphone_e_vocal_dsp::ring_buffer::RingBuffer;
+    /// let mut buffer: RingBuffer<1024> = RingBuffer::with_offset(512);
+    /// buffer.reset();
+    /// assert_eq!(buffer.available_samples(), 0);
+    /// ```
+    pub fn reset(&mut self) {
+        self.buf.get_mut().fill(0.0);
+        *self.read.get_mut() = 0;
+        *self.write.get_mut() = 0;
+    }
+
     /// Pushes a single sample into the ring buffer.
     ///
     /// This method should only be called from the producer thread. It writes
@@ -121,6 +144,10 @@ impl<const N: usize> RingBuffer<N> {
     /// ```
     pub fn push(&self, v: f32) {
         let w = self.write.load(Ordering::Relaxed);
+        #[cfg(feature = "defmt")]
+        if w.wrapping_sub(self.read.load(Ordering::Relaxed)) >= N as u32 {
+            defmt::warn!("ring buffer overrun");
+        }
         unsafe { (*self.buf.get())[w as usize & (N - 1)] = v };
         self.write.store(w.wrapping_add(1), Ordering::Release);
     }
@@ -157,6 +184,112 @@ impl<const N: usize> RingBuffer<N> {
         v
     }
 
+    /// Splits `len` samples starting at ring index `start` into the contiguous
+    /// regions of the underlying storage.
+    ///
+    /// The second region is empty unless the span wraps around the end of the
+    /// storage. DMA and slice copies can then work on plain slices instead of
+    /// masking every index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` exceeds the capacity `N`. With the `no-panic` feature it is
+    /// clamped to the capacity instead.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use synthphone_e_vocal_dsp::ring_buffer::RingBuffer;
+    /// let (first, second) = RingBuffer::<8>::regions(6, 4);
+    /// assert_eq!(first, 6..8);
+    /// assert_eq!(second, 0..2);
+    /// ```
+    pub fn regions(start: u32, len: usize) -> (Range<usize>, Range<usize>) {
+        #[cfg(not(feature = "no-panic"))]
+        assert!(len <= N, "region longer than the ring buffer");
+        #[cfg(feature = "no-panic")]
+        let len = len.min(N);
+        let start = start as usize & (N - 1);
+        let first = len.min(N - start);
+        (start..start + first, 0..len - first)
+    }
+
+    /// Pushes a block of samples, copying contiguous regions.
+    ///
+    /// Like [`RingBuffer::push`], unread data is overwritten if the buffer is full.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `samples` is longer than the capacity `N`. With the `no-panic`
+    /// feature only its last `N` samples are kept, as if the rest had been
+    /// overwritten.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use synthphone_e_vocal_dsp::ring_buffer::RingBuffer;
+    /// let buffer: RingBuffer<8> = RingBuffer::new();
+    /// buffer.push_slice(&[0.1, 0.2, 0.3]);
+    /// assert_eq!(buffer.available_samples(), 3);
+    /// ```
+    pub fn push_slice(&self, samples: &[f32]) {
+        let w = self.write.load(Ordering::Relaxed);
+        #[cfg(feature = "defmt")]
+        if w.wrapping_sub(self.read.load(Ordering::Relaxed)) as usize + samples.len() > N {
+            defmt::warn!("ring buffer overrun");
+        }
+        #[cfg(feature = "no-panic")]
+        let (w, samples) = {
+            let skipped = samples.len().saturating_sub(N);
+            (w.wrapping_add(skipped as u32), &samples[skipped..])
+        };
+        let (first, second) = Self::regions(w, samples.len());
+        let split = first.len();
+        unsafe {
+            let buf = &mut *self.buf.get();
+            buf[first].copy_from_slice(&samples[..split]);
+            buf[second].copy_from_slice(&samples[split..]);
+        }
+        self.write.store(w.wrapping_add(samples.len() as u32), Ordering::Release);
+    }
+
+    /// Pops up to `dest.len()` samples, copying contiguous regions.
+    ///
+    /// Only samples that are available are read; the rest of `dest` is filled
+    /// with silence and the read pointer is not advanced past the write pointer.
+    ///
+    /// # Returns
+    ///
+    /// The number of samples read. Less than `dest.len()` means an underrun.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use synthphone_e_vocal_dsp::ring_buffer::RingBuffer;
+    /// let buffer: RingBuffer<8> = RingBuffer::new();
+    /// buffer.push_slice(&[0.5, 0.25]);
+    /// let mut block = [1.0f32; 3];
+    /// assert_eq!(buffer.pop_slice(&mut block), 2);
+    /// assert_eq!(block, [0.5, 0.25, 0.0]);
+    /// ```
+    pub fn pop_slice(&self, dest: &mut [f32]) -> usize {
+        let r = self.read.load(Ordering::Relaxed);
+        let available = self.write.load(Ordering::Acquire).wrapping_sub(r) as usize;
+        let count = dest.len().min(available).min(N);
+        let (first, second) = Self::regions(r, count);
+        let split = first.len();
+        unsafe {
+            let buf = &mut *self.buf.get();
+            dest[..split].copy_from_slice(&buf[first.clone()]);
+            dest[split..count].copy_from_slice(&buf[second.clone()]);
+            buf[first].fill(0.0);
+            buf[second].fill(0.0);
+        }
+        dest[count..].fill(0.0);
+        self.read.store(r.wrapping_add(count as u32), Ordering::Release);
+        count
+    }
+
     /// Returns the current write index.
     ///
     /// This can be used for synchronization or to determine how much data
@@ -385,6 +518,97 @@ impl<const N: usize> RingBuffer<N> {
     }
 }
 
+/// Ring buffer of the most recent samples, read back by age
+///
+/// Storage can be anything that derefs to a slice of samples, such as an array
+/// on `no_std` targets or a `Vec` sized at runtime. Any length works; it does not
+/// have to be a power of two.
+///
+/// # Example
+///
+/// ```rust
+/// use synthphone_e_vocal_dsp::ring_buffer::DelayBuffer;
+/// let mut line = DelayBuffer::new([0.0f32; 4]);
+/// line.push(0.5);
+/// line.push(0.25);
+/// assert_eq!(line.tap(1), 0.25);
+/// assert_eq!(line.tap(2), 0.5);
+/// assert_eq!(line.tap_fractional(1.5), 0.375);
+/// ```
+#[derive(Debug, Clone)]
+pub struct DelayBuffer<S> {
+    storage: S,
+    /// Position the next sample is written to
+    write: usize,
+}
+
+impl<S> DelayBuffer<S>
+where
+    S: AsRef<[f32]> + AsMut<[f32]>,
+{
+    /// Creates a delay buffer over `storage`, keeping its contents as history
+    pub fn new(storage: S) -> Self {
+        Self { storage, write: 0 }
+    }
+
+    /// Number of samples held, the longest delay that can be read
+    pub fn capacity(&self) -> usize {
+        self.storage.as_ref().len()
+    }
+
+    /// Writes a sample, dropping the oldest one
+    pub fn push(&mut self, sample: f32) {
+        let storage = self.storage.as_mut();
+        if storage.is_empty() {
+            return;
+        }
+        storage[self.write] = sample;
+        self.write = if self.write + 1 == storage.len() {
+            0
+        } else {
+            self.write + 1
+        };
+    }
+
+    /// Sample written `delay` samples ago, 1 for the latest
+    ///
+    /// Delays are clamped to between 1 and the capacity; an empty buffer reads 0.0.
+    pub fn tap(&self, delay: usize) -> f32 {
+        let storage = self.storage.as_ref();
+        if storage.is_empty() {
+            return 0.0;
+        }
+        let delay = delay.clamp(1, storage.len());
+        let index = if delay <= self.write {
+            self.write - delay
+        } else {
+            self.write + storage.len() - delay
+        };
+        storage[index]
+    }
+
+    /// Sample `delay` samples ago, interpolated linearly between samples
+    pub fn tap_fractional(&self, delay: f32) -> f32 {
+        if self.capacity() == 0 {
+            return 0.0;
+        }
+        let delay = delay.clamp(1.0, self.capacity() as f32);
+        let whole = delay as usize;
+        let fraction = delay - whole as f32;
+        let newer = self.tap(whole);
+        if fraction == 0.0 {
+            return newer;
+        }
+        newer + (self.tap(whole + 1) - newer) * fraction
+    }
+
+    /// Fills the history with silence
+    pub fn clear(&mut self) {
+        self.storage.as_mut().fill(0.0);
+        self.write = 0;
+    }
+}
+
 #[cfg(feature = "std")]
 #[cfg(test)]
 mod tests {
@@ -419,6 +643,21 @@ mod tests {
         assert_eq!(buffer.available_samples(), 512);
     }
 
+    #[test]
+    fn test_reset() {
+        let mut buffer: RingBuffer<8> = RingBuffer::with_offset(3);
+        buffer.push(1.0);
+        buffer.pop();
+
+        buffer.reset();
+        assert_eq!(buffer.write_index(), 0);
+        assert_eq!(buffer.available_samples(), 0);
+
+        let mut block = [1.0f32; 8];
+        buffer.latest_block(&mut block);
+        assert!(block.iter().all(|&s| s == 0.0));
+    }
+
     #[test]
     fn test_advance_write() {
         let buffer: RingBuffer<1024> = RingBuffer::new();
@@ -483,6 +722,33 @@ mod tests {
         assert!((block[3] - 7.0).abs() < f32::EPSILON);
     }
 
+    #[test]
+    fn test_regions() {
+        assert_eq!(RingBuffer::<8>::regions(2, 4), (2..6, 0..0));
+        assert_eq!(RingBuffer::<8>::regions(14, 5), (6..8, 0..3));
+        assert_eq!(RingBuffer::<8>::regions(0, 8), (0..8, 0..0));
+    }
+
+    #[test]
+    fn test_slice_push_pop_wraps() {
+        let buffer: RingBuffer<8> = RingBuffer::with_offset(6);
+        let mut skip = [0.0f32; 6];
+        assert_eq!(buffer.pop_slice(&mut skip), 6);
+
+        buffer.push_slice(&[1.0, 2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(buffer.available_samples(), 5);
+
+        let mut block = [0.0f32; 3];
+        assert_eq!(buffer.pop_slice(&mut block), 3);
+        assert_eq!(block, [1.0, 2.0, 3.0]);
+
+        // Only two samples remain, the rest is an underrun
+        let mut block = [9.0f32; 4];
+        assert_eq!(buffer.pop_slice(&mut block), 2);
+        assert_eq!(block, [4.0, 5.0, 0.0, 0.0]);
+        assert_eq!(buffer.available_samples(), 0);
+    }
+
     #[test]
     fn test_ring_buffer_wrap_around() {
         let buffer: RingBuffer<4> = RingBuffer::new(); // Small buffer for testing wrap
@@ -548,4 +814,22 @@ mod tests {
             assert!((sample - i as f32).abs() < f32::EPSILON);
         }
     }
+
+    #[test]
+    fn test_delay_buffer_reads_by_age() {
+        let mut line = DelayBuffer::new(vec![0.0f32; 5]);
+        for i in 1..=12 {
+            line.push(i as f32);
+        }
+        assert_eq!(line.capacity(), 5);
+        assert_eq!([line.tap(1), line.tap(3), line.tap(5)], [12.0, 10.0, 8.0]);
+        assert_eq!([line.tap(0), line.tap(9)], [12.0, 8.0]);
+        assert_eq!(line.tap_fractional(2.25), 10.75);
+        assert_eq!(line.tap_fractional(7.0), 8.0);
+
+        line.clear();
+        assert_eq!(line.tap(1), 0.0);
+        let empty = DelayBuffer::new([0.0f32; 0]);
+        assert_eq!((empty.tap(1), empty.tap_fractional(2.5)), (0.0, 0.0));
+    }
 }
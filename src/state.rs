@@ -1,5 +1,6 @@
 /// Processing modes for vocal effects
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum ProcessingMode {
     /// Pitch correction/autotune mode
     Autotune,
@@ -7,31 +8,986 @@ pub enum ProcessingMode {
     Vocode,
     /// Dry mode - pitch shifting with formant preservation but no correction
     Dry,
+    /// Formant mode - shifts formants while leaving the pitch untouched
+    Formant,
+    /// Talk-box mode - filters the carrier through the smoothed vocal formant envelope
+    TalkBox,
+}
+
+impl ProcessingMode {
+    /// The mode as a [`CarrierMode`], `None` for modes that run on the voice alone
+    pub fn carrier_mode(self) -> Option<CarrierMode> {
+        match self {
+            ProcessingMode::Vocode => Some(CarrierMode::Vocode),
+            ProcessingMode::TalkBox => Some(CarrierMode::TalkBox),
+            _ => None,
+        }
+    }
+}
+
+/// Processing modes that cannot run without a carrier
+///
+/// The `process_carrier_effects_*` functions take one of these together with a
+/// carrier buffer, so a carrier mode cannot be asked for without a carrier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CarrierMode {
+    /// Vocoder mode - applies vocal formants to the carrier
+    Vocode,
+    /// Talk-box mode - filters the carrier through the smoothed vocal formant envelope
+    TalkBox,
+}
+
+impl From<CarrierMode> for ProcessingMode {
+    fn from(mode: CarrierMode) -> Self {
+        match mode {
+            CarrierMode::Vocode => ProcessingMode::Vocode,
+            CarrierMode::TalkBox => ProcessingMode::TalkBox,
+        }
+    }
+}
+
+use crate::{
+    VocalEffectsError,
+    audio::keys::{KEYS, KeyScaleFrequencies},
+    dsp::{BinTables, EnvelopeCache},
+};
+
+/// Musical key the autotune snaps to, in the order of [`KEYS`]
+///
+/// The discriminants are the legacy `i32` key indices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(i32)]
+pub enum Key {
+    #[default]
+    CMajor = 0,
+    GMajor,
+    DMajor,
+    AMajor,
+    EMajor,
+    BMajor,
+    FSharpMajor,
+    CSharpMajor,
+    FMajor,
+    BFlatMajor,
+    EFlatMajor,
+    AFlatMajor,
+    AMinor,
+    EMinor,
+    BMinor,
+    FSharpMinor,
+    CSharpMinor,
+    GSharpMinor,
+    DMinor,
+    GMinor,
+    CMinor,
+    FMinor,
+    BFlatMinor,
+    EFlatMinor,
+}
+
+impl Key {
+    /// All keys in index order
+    pub const ALL: [Key; 24] = [
+        Key::CMajor,
+        Key::GMajor,
+        Key::DMajor,
+        Key::AMajor,
+        Key::EMajor,
+        Key::BMajor,
+        Key::FSharpMajor,
+        Key::CSharpMajor,
+        Key::FMajor,
+        Key::BFlatMajor,
+        Key::EFlatMajor,
+        Key::AFlatMajor,
+        Key::AMinor,
+        Key::EMinor,
+        Key::BMinor,
+        Key::FSharpMinor,
+        Key::CSharpMinor,
+        Key::GSharpMinor,
+        Key::DMinor,
+        Key::GMinor,
+        Key::CMinor,
+        Key::FMinor,
+        Key::BFlatMinor,
+        Key::EFlatMinor,
+    ];
+
+    /// Index of the key in [`KEYS`]
+    pub const fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Name of the key's root note, e.g. `"F#"`
+    pub fn name(self) -> &'static str {
+        KEYS[self.index()].1
+    }
+
+    /// Returns `true` for the natural minor keys
+    pub const fn is_minor(self) -> bool {
+        self.index() >= 12
+    }
+
+    /// Pitch class of the key's root, 0 for C up to 11 for B
+    pub const fn tonic(self) -> u8 {
+        match self {
+            Key::CMajor | Key::CMinor => 0,
+            Key::CSharpMajor | Key::CSharpMinor => 1,
+            Key::DMajor | Key::DMinor => 2,
+            Key::EFlatMajor | Key::EFlatMinor => 3,
+            Key::EMajor | Key::EMinor => 4,
+            Key::FMajor | Key::FMinor => 5,
+            Key::FSharpMajor | Key::FSharpMinor => 6,
+            Key::GMajor | Key::GMinor => 7,
+            Key::AFlatMajor | Key::GSharpMinor => 8,
+            Key::AMajor | Key::AMinor => 9,
+            Key::BFlatMajor | Key::BFlatMinor => 10,
+            Key::BMajor | Key::BMinor => 11,
+        }
+    }
+
+    /// Key of the same mode whose root is `tonic`, a pitch class from 0 for C
+    pub fn from_tonic(tonic: u8, minor: bool) -> Key {
+        let tonic = tonic % 12;
+        let offset = if minor { 12 } else { 0 };
+        Key::ALL[offset..offset + 12]
+            .iter()
+            .copied()
+            .find(|key| key.tonic() == tonic)
+            .unwrap_or(Key::CMajor)
+    }
+
+    /// Key of the same mode moved by `semitones`
+    pub fn transposed(self, semitones: i32) -> Key {
+        let tonic = (i32::from(self.tonic()) + semitones).rem_euclid(12) as u8;
+        Key::from_tonic(tonic, self.is_minor())
+    }
+
+    /// Major key for a minor key and the other way round, on the same root
+    pub fn parallel(self) -> Key {
+        Key::from_tonic(self.tonic(), !self.is_minor())
+    }
+
+    /// Semitones above the tonic of the seven scale degrees
+    pub const fn scale_semitones(self) -> [u8; 7] {
+        if self.is_minor() {
+            [0, 2, 3, 5, 7, 8, 10]
+        } else {
+            [0, 2, 4, 5, 7, 9, 11]
+        }
+    }
+
+    /// Frequencies of every note of the key's scale
+    pub fn scale_frequencies(self) -> &'static KeyScaleFrequencies {
+        &KEYS[self.index()].0.1
+    }
+}
+
+impl TryFrom<i32> for Key {
+    type Error = VocalEffectsError;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        usize::try_from(value)
+            .ok()
+            .and_then(|index| Key::ALL.get(index).copied())
+            .ok_or(VocalEffectsError::InvalidConfiguration)
+    }
+}
+
+impl From<Key> for i32 {
+    fn from(key: Key) -> Self {
+        key as i32
+    }
+}
+
+/// Note the autotune holds, as a degree of the key's scale
+///
+/// The discriminants are the legacy `i32` note values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(i32)]
+pub enum Note {
+    /// Snap to the nearest note of the key
+    #[default]
+    Auto = 0,
+    Degree1,
+    Degree2,
+    Degree3,
+    Degree4,
+    Degree5,
+    Degree6,
+    Degree7,
+    /// The root one octave up
+    Degree8,
+    /// The second one octave up
+    Degree9,
+}
+
+impl Note {
+    /// All notes, `Auto` first
+    pub const ALL: [Note; 10] = [
+        Note::Auto,
+        Note::Degree1,
+        Note::Degree2,
+        Note::Degree3,
+        Note::Degree4,
+        Note::Degree5,
+        Note::Degree6,
+        Note::Degree7,
+        Note::Degree8,
+        Note::Degree9,
+    ];
+
+    /// Returns `true` when the autotune snaps to the nearest note
+    pub fn is_auto(self) -> bool {
+        self == Note::Auto
+    }
+}
+
+impl TryFrom<i32> for Note {
+    type Error = VocalEffectsError;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        usize::try_from(value)
+            .ok()
+            .and_then(|index| Note::ALL.get(index).copied())
+            .ok_or(VocalEffectsError::InvalidConfiguration)
+    }
+}
+
+impl From<Note> for i32 {
+    fn from(note: Note) -> Self {
+        note as i32
+    }
+}
+
+/// Octave of held notes
+///
+/// The discriminants are the legacy `i32` octave flags. The octave shift of the
+/// output is set separately with [`OctaveShift`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(i32)]
+pub enum Octave {
+    /// One octave down
+    Low = 1,
+    #[default]
+    Middle = 2,
+    /// One octave up
+    High = 4,
+}
+
+impl Octave {
+    /// All octaves, lowest first
+    pub const ALL: [Octave; 3] = [Octave::Low, Octave::Middle, Octave::High];
+
+    /// Frequency ratio of the octave relative to [`Octave::Middle`]
+    pub fn ratio(self) -> f32 {
+        match self {
+            Octave::Low => 0.5,
+            Octave::Middle => 1.0,
+            Octave::High => 2.0,
+        }
+    }
+}
+
+impl TryFrom<i32> for Octave {
+    type Error = VocalEffectsError;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        Octave::ALL
+            .into_iter()
+            .find(|octave| *octave as i32 == value)
+            .ok_or(VocalEffectsError::InvalidConfiguration)
+    }
+}
+
+impl From<Octave> for i32 {
+    fn from(octave: Octave) -> Self {
+        octave as i32
+    }
+}
+
+/// Octave transposition of the output
+///
+/// Applied the same way in every mode that shifts pitch: on top of the corrected
+/// ratio in autotune mode and on top of
+/// [`pitch_shift_semitones`](MusicalSettings::pitch_shift_semitones) in dry mode.
+/// The discriminants are the shift in octaves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(i32)]
+pub enum OctaveShift {
+    /// Two octaves down
+    Down2 = -2,
+    /// One octave down
+    Down1 = -1,
+    /// No octave shift
+    #[default]
+    None = 0,
+    /// One octave up
+    Up1 = 1,
+    /// Two octaves up
+    Up2 = 2,
+}
+
+impl OctaveShift {
+    /// All shifts, lowest first
+    pub const ALL: [OctaveShift; 5] = [
+        OctaveShift::Down2,
+        OctaveShift::Down1,
+        OctaveShift::None,
+        OctaveShift::Up1,
+        OctaveShift::Up2,
+    ];
+
+    /// Shift in octaves
+    pub const fn octaves(self) -> i32 {
+        self as i32
+    }
+
+    /// Frequency ratio of the shift
+    pub fn ratio(self) -> f32 {
+        match self {
+            OctaveShift::Down2 => 0.25,
+            OctaveShift::Down1 => 0.5,
+            OctaveShift::None => 1.0,
+            OctaveShift::Up1 => 2.0,
+            OctaveShift::Up2 => 4.0,
+        }
+    }
+}
+
+impl TryFrom<i32> for OctaveShift {
+    type Error = VocalEffectsError;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        OctaveShift::ALL
+            .into_iter()
+            .find(|shift| *shift as i32 == value)
+            .ok_or(VocalEffectsError::InvalidConfiguration)
+    }
+}
+
+impl From<OctaveShift> for i32 {
+    fn from(shift: OctaveShift) -> Self {
+        shift as i32
+    }
+}
+
+/// Shift that an [`Octave`] used to apply in dry mode, for settings stored with
+/// the legacy `i32` octave flags (1 one down, 2 none, 4 one up)
+impl From<Octave> for OctaveShift {
+    fn from(octave: Octave) -> Self {
+        match octave {
+            Octave::Low => OctaveShift::Down1,
+            Octave::Middle => OctaveShift::None,
+            Octave::High => OctaveShift::Up1,
+        }
+    }
+}
+
+/// Formant shift direction
+///
+/// The discriminants are the legacy `i32` formant values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(i32)]
+pub enum Formant {
+    /// No formant shift
+    #[default]
+    None = 0,
+    /// Lower formants
+    Lower,
+    /// Raise formants
+    Higher,
+}
+
+impl Formant {
+    /// All formant settings
+    pub const ALL: [Formant; 3] = [Formant::None, Formant::Lower, Formant::Higher];
+
+    /// Returns `true` if the formants are shifted
+    pub fn is_shifted(self) -> bool {
+        self != Formant::None
+    }
+
+    /// Envelope scaling factor in autotune mode, an octave either way
+    pub fn ratio(self) -> f32 {
+        match self {
+            Formant::None => 1.0,
+            Formant::Lower => 0.5,
+            Formant::Higher => 2.0,
+        }
+    }
+
+    /// Gentler envelope scaling factor of dry and formant mode
+    ///
+    /// Without a correction moving the partials, an octave shift of the envelope
+    /// swamps the partials it is meant to colour and the loudest one changes, so
+    /// these modes shift it by about a fourth instead.
+    pub fn uncorrected_ratio(self) -> f32 {
+        match self {
+            Formant::None => 1.0,
+            Formant::Lower => 0.8,
+            Formant::Higher => 1.3,
+        }
+    }
+}
+
+impl TryFrom<i32> for Formant {
+    type Error = VocalEffectsError;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        usize::try_from(value)
+            .ok()
+            .and_then(|index| Formant::ALL.get(index).copied())
+            .ok_or(VocalEffectsError::InvalidConfiguration)
+    }
+}
+
+impl From<Formant> for i32 {
+    fn from(formant: Formant) -> Self {
+        formant as i32
+    }
+}
+
+/// Chord quality of a [`ChordSpec`] built from a root
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ChordQuality {
+    Major,
+    Minor,
+    Diminished,
+    Augmented,
+    Sus2,
+    Sus4,
+    Dominant7,
+    Major7,
+    Minor7,
+    HalfDiminished7,
+    Diminished7,
+}
+
+impl ChordQuality {
+    /// Semitones of the chord tones above the root, the root included
+    pub const fn intervals(self) -> &'static [u8] {
+        match self {
+            ChordQuality::Major => &[0, 4, 7],
+            ChordQuality::Minor => &[0, 3, 7],
+            ChordQuality::Diminished => &[0, 3, 6],
+            ChordQuality::Augmented => &[0, 4, 8],
+            ChordQuality::Sus2 => &[0, 2, 7],
+            ChordQuality::Sus4 => &[0, 5, 7],
+            ChordQuality::Dominant7 => &[0, 4, 7, 10],
+            ChordQuality::Major7 => &[0, 4, 7, 11],
+            ChordQuality::Minor7 => &[0, 3, 7, 10],
+            ChordQuality::HalfDiminished7 => &[0, 3, 6, 10],
+            ChordQuality::Diminished7 => &[0, 3, 6, 9],
+        }
+    }
+}
+
+/// Set of pitch classes the correction snaps to while a chord plays
+///
+/// Stored as a 12-bit mask with C in bit 0, so any note set can be expressed,
+/// including chords outside the key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ChordSpec {
+    pitch_classes: u16,
+}
+
+impl ChordSpec {
+    /// Chord of `quality` on `root`, a pitch class with 0 for C (taken modulo 12)
+    pub const fn new(root: u8, quality: ChordQuality) -> Self {
+        let intervals = quality.intervals();
+        let mut pitch_classes = 0;
+        let mut i = 0;
+        while i < intervals.len() {
+            pitch_classes |= 1 << ((root as usize + intervals[i] as usize) % 12);
+            i += 1;
+        }
+        Self { pitch_classes }
+    }
+
+    /// Chord of explicit pitch classes (taken modulo 12)
+    pub const fn from_pitch_classes(pitch_classes: &[u8]) -> Self {
+        let mut mask = 0;
+        let mut i = 0;
+        while i < pitch_classes.len() {
+            mask |= 1 << (pitch_classes[i] % 12);
+            i += 1;
+        }
+        Self { pitch_classes: mask }
+    }
+
+    /// Chord from a mask with bit `n` set for pitch class `n`; bits above 11 are ignored
+    pub const fn from_mask(mask: u16) -> Self {
+        Self { pitch_classes: mask & 0x0FFF }
+    }
+
+    /// Mask with bit `n` set for pitch class `n`
+    pub const fn mask(self) -> u16 {
+        self.pitch_classes
+    }
+
+    /// Returns `true` if `pitch_class` (0 for C) is a chord tone
+    pub const fn contains(self, pitch_class: u8) -> bool {
+        pitch_class < 12 && self.pitch_classes & (1 << pitch_class) != 0
+    }
+
+    /// Returns `true` if the chord has no tones
+    pub const fn is_empty(self) -> bool {
+        self.pitch_classes == 0
+    }
+}
+
+/// How strongly each note is corrected, by its distance from the key's tonic
+///
+/// Amounts run from 0.0 (leave the sung pitch alone) to 1.0 (snap fully onto the
+/// target) and are looked up for the target note, so passing tones can be corrected
+/// gently while chord tones are hard-tuned. They are kept relative to the tonic and
+/// follow key changes. Amounts are stored with 8-bit resolution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CorrectionStrength {
+    amounts: [u8; 12],
+}
+
+impl CorrectionStrength {
+    /// Full correction of every note
+    pub const FULL: Self = Self { amounts: [u8::MAX; 12] };
+
+    /// The same amount for every note
+    pub fn uniform(amount: f32) -> Self {
+        Self { amounts: [quantize(amount); 12] }
+    }
+
+    /// Amount for the note `semitones` above the tonic (taken modulo 12)
+    pub fn semitone(&self, semitones: u8) -> f32 {
+        self.amounts[semitones as usize % 12] as f32 / u8::MAX as f32
+    }
+
+    /// Sets the amount for the note `semitones` above the tonic (taken modulo 12)
+    pub fn set_semitone(&mut self, semitones: u8, amount: f32) {
+        self.amounts[semitones as usize % 12] = quantize(amount);
+    }
+
+    /// Sets the amount for a degree of `key`'s scale, [`Note::Auto`] is ignored
+    ///
+    /// Degrees 8 and 9 are the first and second degree an octave up.
+    pub fn set_degree(&mut self, key: Key, degree: Note, amount: f32) {
+        if let Some(index) = (degree as usize).checked_sub(1) {
+            self.set_semitone(key.scale_semitones()[index % 7], amount);
+        }
+    }
+
+    /// Amount for pitch class `pitch_class` (0 for C) in `key`
+    pub fn for_pitch_class(&self, key: Key, pitch_class: u8) -> f32 {
+        self.semitone((pitch_class % 12 + 12 - key.tonic()) % 12)
+    }
+
+    /// Raw 8-bit amounts from the tonic upwards
+    pub const fn to_bytes(self) -> [u8; 12] {
+        self.amounts
+    }
+
+    /// Creates the strengths from raw 8-bit amounts, 255 being full correction
+    pub const fn from_bytes(amounts: [u8; 12]) -> Self {
+        Self { amounts }
+    }
+}
+
+impl Default for CorrectionStrength {
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
+fn quantize(amount: f32) -> u8 {
+    libm::roundf(amount.clamp(0.0, 1.0) * u8::MAX as f32) as u8
+}
+
+/// Bands of a [`VocoderEq`]
+pub const VOCODER_BANDS: usize = 16;
+
+/// Centre of the lowest [`VocoderEq`] band in Hz
+const LOWEST_BAND_HZ: f32 = 100.0;
+
+/// Ratio between the highest and lowest [`VocoderEq`] band centres, 100 Hz to 8 kHz
+const BAND_SPAN: f32 = 80.0;
+
+/// [`VocoderEq`] gain range in dB
+const MUTE_DB: i8 = -60;
+const MAX_DB: i8 = 12;
+
+/// Per-band gains on the voice spectrum in vocode mode, like the band sliders of a
+/// hardware vocoder
+///
+/// The [`VOCODER_BANDS`] bands are spaced evenly in pitch from 100 Hz to 8 kHz and
+/// blend into each other between their centres. Gains are stored in whole dB from
+/// -60 dB, which mutes the band, to +12 dB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct VocoderEq {
+    gains_db: [i8; VOCODER_BANDS],
+}
+
+impl VocoderEq {
+    /// Every band at 0 dB
+    pub const FLAT: Self = Self { gains_db: [0; VOCODER_BANDS] };
+
+    /// Lowest gain in dB, which mutes a band
+    pub const MUTE_DB: f32 = MUTE_DB as f32;
+
+    /// Highest gain in dB
+    pub const MAX_DB: f32 = MAX_DB as f32;
+
+    /// Centre frequency of `band` in Hz
+    pub fn band_centre_hz(band: usize) -> f32 {
+        LOWEST_BAND_HZ * libm::powf(BAND_SPAN, band as f32 / (VOCODER_BANDS - 1) as f32)
+    }
+
+    /// Gain of `band` in dB
+    pub fn band_db(&self, band: usize) -> f32 {
+        self.gains_db[band % VOCODER_BANDS] as f32
+    }
+
+    /// Sets the gain of `band` in dB, rounded to whole dB and clamped to the range
+    pub fn set_band_db(&mut self, band: usize, gain_db: f32) {
+        let gain_db = if gain_db.is_nan() { 0.0 } else { gain_db };
+        self.gains_db[band % VOCODER_BANDS] =
+            libm::roundf(gain_db.clamp(Self::MUTE_DB, Self::MAX_DB)) as i8;
+    }
+
+    /// Linear gain of `band`
+    pub fn band_gain(&self, band: usize) -> f32 {
+        let gain_db = self.band_db(band);
+        if gain_db <= Self::MUTE_DB {
+            0.0
+        } else {
+            libm::powf(10.0, gain_db / 20.0)
+        }
+    }
+
+    /// Linear gains of all bands
+    pub fn gains(&self) -> [f32; VOCODER_BANDS] {
+        core::array::from_fn(|band| self.band_gain(band))
+    }
+
+    /// Gain at `frequency_hz` from per-band linear `gains`, interpolated in pitch
+    /// between the band centres
+    pub(crate) fn gain_at(gains: &[f32; VOCODER_BANDS], frequency_hz: f32) -> f32 {
+        let position = libm::log2f(frequency_hz.max(LOWEST_BAND_HZ) / LOWEST_BAND_HZ)
+            / libm::log2f(BAND_SPAN)
+            * (VOCODER_BANDS - 1) as f32;
+        let position = position.min((VOCODER_BANDS - 1) as f32);
+        let band = position as usize;
+        let frac = position - band as f32;
+        if band < VOCODER_BANDS - 1 {
+            gains[band] * (1.0 - frac) + gains[band + 1] * frac
+        } else {
+            gains[band]
+        }
+    }
+
+    /// Raw gains in dB from the lowest band up
+    pub const fn to_bytes(self) -> [u8; VOCODER_BANDS] {
+        let mut bytes = [0; VOCODER_BANDS];
+        let mut i = 0;
+        while i < VOCODER_BANDS {
+            bytes[i] = self.gains_db[i] as u8;
+            i += 1;
+        }
+        bytes
+    }
+
+    /// Creates the gains from raw bytes, each a gain in dB as `i8`, clamped to the range
+    pub const fn from_bytes(bytes: [u8; VOCODER_BANDS]) -> Self {
+        let mut gains_db = [0; VOCODER_BANDS];
+        let mut i = 0;
+        while i < VOCODER_BANDS {
+            let gain = bytes[i] as i8;
+            gains_db[i] = if gain < MUTE_DB {
+                MUTE_DB
+            } else if gain > MAX_DB {
+                MAX_DB
+            } else {
+                gain
+            };
+            i += 1;
+        }
+        Self { gains_db }
+    }
+}
+
+impl Default for VocoderEq {
+    fn default() -> Self {
+        Self::FLAT
+    }
+}
+
+/// Notes the automatic correction snaps to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TargetSource {
+    /// Notes of [`MusicalSettings::key`]
+    #[default]
+    Key,
+    /// Tones of the chord currently playing; an empty chord falls back to the key
+    Chord(ChordSpec),
 }
 
 /// Musical settings for vocal effects processing
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct MusicalSettings {
-    /// Musical key (0-23, see keys module for mapping)
-    pub key: i32,
-    /// Specific note (0 = auto mode, 1-9 = specific note in scale)
-    pub note: i32,
-    /// Octave setting
-    pub octave: i32,
-    /// Formant shift mode (0 = none, 1 = lower, 2 = higher)
-    pub formant: i32,
+    /// Musical key
+    pub key: Key,
+    /// Specific note to hold, or automatic
+    pub note: Note,
+    /// Octave of held notes
+    pub octave: Octave,
+    /// Detune of held notes in cents, within ±[`MAX_DETUNE_CENTS`](crate::audio::keys::MAX_DETUNE_CENTS)
+    ///
+    /// Sharpens or flattens the target slightly, e.g. for a harmony part tuned
+    /// against the lead. Out-of-range values leave the correction unchanged.
+    pub held_note_cents: f32,
+    /// Octave transposition of the output in autotune and dry mode
+    pub octave_shift: OctaveShift,
+    /// Formant shift mode
+    pub formant: Formant,
+    /// Formant shift of the voice in vocode mode, in semitones
+    ///
+    /// Warps the voice spectrum across frequency before it is imposed on the
+    /// carrier: negative values give a giant robot, positive ones a chipmunk.
+    pub vocoder_formant_semitones: f32,
+    /// Pitch shift applied in dry mode, in semitones (fractional part gives cents resolution)
+    pub pitch_shift_semitones: f32,
+    /// Share of the synth input in dry mode while a note is held, from 0.0 (voice
+    /// only) to 1.0 (synth only)
+    ///
+    /// Voice and synth are crossfaded with an equal-power law, so the blend keeps
+    /// its loudness across the range.
+    pub synth_mix: f32,
     /// Processing mode for vocal effects
     pub mode: ProcessingMode,
+    /// Notes [`Note::Auto`] snaps to, the key or the current chord
+    pub target: TargetSource,
+    /// Correction amount of each target note
+    pub correction_strength: CorrectionStrength,
+    /// Gains on the voice spectrum in vocode mode, by band
+    pub vocoder_eq: VocoderEq,
 }
 
 impl Default for MusicalSettings {
     fn default() -> Self {
         Self {
-            key: 0,  // C Major
-            note: 0, // Auto mode
-            octave: 2,
-            formant: 0, // No formant shift
+            key: Key::CMajor,
+            note: Note::Auto,
+            octave: Octave::Middle,
+            held_note_cents: 0.0,
+            octave_shift: OctaveShift::None,
+            formant: Formant::None,
+            vocoder_formant_semitones: 0.0,
+            pitch_shift_semitones: 0.0,
+            synth_mix: 0.04,
             mode: ProcessingMode::Autotune,
+            target: TargetSource::Key,
+            correction_strength: CorrectionStrength::FULL,
+            vocoder_eq: VocoderEq::FLAT,
+        }
+    }
+}
+
+/// Pitch analysis of a processed frame
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FrameAnalysis {
+    /// Detected fundamental frequency in Hz
+    pub detected_frequency: f32,
+    /// Frequency the correction is pulling towards in Hz (0.0 when the detected
+    /// frequency was out of range and the previous ratio was held)
+    ///
+    /// Lags behind [`note_frequency`](Self::note_frequency) while a
+    /// [`Glide`](crate::Glide) is in progress.
+    pub target_frequency: f32,
+    /// Note the target is gliding towards in Hz, equal to the target without a glide
+    pub note_frequency: f32,
+    /// Largest change of the target per hop in octaves for the current glide
+    pub glide_step: f32,
+    /// Scoop or fall offset included in the ratio, in cents from the target (see
+    /// [`Ornaments`](crate::Ornaments)); not included in the target frequency
+    pub ornament_cents: f32,
+    /// Pitch shift ratio applied to the frame
+    pub pitch_shift_ratio: f32,
+    /// Confidence of the pitch detector in the detected frequency (0.0 to 1.0)
+    pub confidence: f32,
+}
+
+impl Default for FrameAnalysis {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameAnalysis {
+    /// Analysis of a frame with nothing detected and a unity ratio
+    pub const fn new() -> Self {
+        Self {
+            detected_frequency: 0.0,
+            target_frequency: 0.0,
+            note_frequency: 0.0,
+            glide_step: 0.0,
+            ornament_cents: 0.0,
+            pitch_shift_ratio: 1.0,
+            confidence: 0.0,
+        }
+    }
+
+    /// Analysis that carries `pitch_shift_ratio` over from a previous frame
+    pub const fn with_ratio(pitch_shift_ratio: f32) -> Self {
+        Self { pitch_shift_ratio, ..Self::new() }
+    }
+}
+
+/// Per-stream phase vocoder state carried between frames
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProcessingState<const N: usize> {
+    /// Analysis phases of the previous frame
+    pub last_input_phases: [f32; N],
+    /// Synthesis phases of the previous frame
+    pub last_output_phases: [f32; N],
+    /// Spectrum magnitudes of the two previous frames, newest first, for
+    /// harmonic/percussive separation
+    pub magnitude_history: [f32; N],
+    /// Per-bin gains of the spectral gate
+    pub gate_gains: [f32; N],
+    /// Smoothed voice envelope of vocode mode, see
+    /// [`VocoderEnvelope`](crate::VocoderEnvelope)
+    pub vocoder_envelope: [f32; N],
+    /// Formant envelope kept between extractions, see
+    /// [`envelope_update_interval`](crate::VocalEffectsConfig::envelope_update_interval)
+    pub formant_envelope: EnvelopeCache<N>,
+    /// Smoothed linear gains of the [`VocoderEq`] bands
+    pub vocoder_eq_gains: [f32; VOCODER_BANDS],
+    /// Pitch analysis of the previous frame, including the applied pitch shift ratio
+    pub analysis: FrameAnalysis,
+    /// Frames processed since the synthesis phases were last re-anchored
+    pub frames_since_anchor: u32,
+    /// Per-bin phase advances for the hop size in use
+    ///
+    /// Rebuilt by [`process_frame`](crate::vocal_effects::process_frame) when the
+    /// hop size changes, so a new or reset state builds them on its first frame.
+    pub bin_tables: BinTables<N>,
+}
+
+impl<const N: usize> Default for ProcessingState<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> ProcessingState<N> {
+    /// Create a state with zeroed phases and a unity pitch shift ratio
+    pub const fn new() -> Self {
+        Self {
+            last_input_phases: [0.0; N],
+            last_output_phases: [0.0; N],
+            magnitude_history: [0.0; N],
+            gate_gains: [0.0; N],
+            vocoder_envelope: [0.0; N],
+            formant_envelope: EnvelopeCache::new(),
+            vocoder_eq_gains: [1.0; VOCODER_BANDS],
+            analysis: FrameAnalysis::new(),
+            frames_since_anchor: 0,
+            bin_tables: BinTables::empty(),
+        }
+    }
+
+    /// Pitch shift ratio applied to the previous frame
+    pub fn pitch_shift_ratio(&self) -> f32 {
+        self.analysis.pitch_shift_ratio
+    }
+
+    /// Clear all phases and return the pitch shift ratio to unity
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Re-anchor the synthesis phases to the analysis phases.
+    ///
+    /// This removes the drift accumulated by the phase vocoder while keeping the
+    /// pitch shift ratio, so processing continues from the current frame.
+    pub fn reanchor_phases(&mut self) {
+        self.last_output_phases = self.last_input_phases;
+        self.frames_since_anchor = 0;
+    }
+
+    /// Borrows the state the frame processors read and update
+    pub fn frame_state(&mut self) -> FrameState<'_, N> {
+        FrameState {
+            last_input_phases: &mut self.last_input_phases,
+            last_output_phases: &mut self.last_output_phases,
+            bins: &self.bin_tables,
+            magnitude_history: Some(&mut self.magnitude_history),
+            gate_gains: Some(&mut self.gate_gains),
+            envelope_cache: Some(&mut self.formant_envelope),
+            vocoder_envelope: Some(&mut self.vocoder_envelope),
+            vocoder_eq_gains: Some(&mut self.vocoder_eq_gains),
+            analysis: &mut self.analysis,
+        }
+    }
+}
+
+/// State of a stream borrowed for processing one frame
+///
+/// The phases, bin tables and pitch analysis are always needed. Each optional
+/// part carries smoothing or caching from frame to frame; without it the frame
+/// is processed on its own, as by the `process_vocal_effects_*` functions.
+pub struct FrameState<'a, const N: usize> {
+    /// Analysis phases of the previous frame
+    pub last_input_phases: &'a mut [f32; N],
+    /// Synthesis phases of the previous frame
+    pub last_output_phases: &'a mut [f32; N],
+    /// Phase vocoder tables for the hop and frame size in use
+    pub bins: &'a BinTables<N>,
+    /// See [`ProcessingState::magnitude_history`]
+    pub magnitude_history: Option<&'a mut [f32; N]>,
+    /// See [`ProcessingState::gate_gains`]
+    pub gate_gains: Option<&'a mut [f32; N]>,
+    /// See [`ProcessingState::formant_envelope`]
+    pub envelope_cache: Option<&'a mut EnvelopeCache<N>>,
+    /// See [`ProcessingState::vocoder_envelope`]
+    pub vocoder_envelope: Option<&'a mut [f32; N]>,
+    /// See [`ProcessingState::vocoder_eq_gains`]
+    pub vocoder_eq_gains: Option<&'a mut [f32; VOCODER_BANDS]>,
+    /// Pitch analysis of the previous frame on entry, of this frame on return
+    pub analysis: &'a mut FrameAnalysis,
+}
+
+impl<'a, const N: usize> FrameState<'a, N> {
+    /// State without any of the optional parts
+    pub fn stateless(
+        last_input_phases: &'a mut [f32; N],
+        last_output_phases: &'a mut [f32; N],
+        bins: &'a BinTables<N>,
+        analysis: &'a mut FrameAnalysis,
+    ) -> Self {
+        Self {
+            last_input_phases,
+            last_output_phases,
+            bins,
+            magnitude_history: None,
+            gate_gains: None,
+            envelope_cache: None,
+            vocoder_envelope: None,
+            vocoder_eq_gains: None,
+            analysis,
         }
     }
 }
@@ -42,9 +998,92 @@ mod tests {
     #[test]
     fn test_musical_settings_default() {
         let settings = MusicalSettings::default();
-        assert_eq!(settings.key, 0);
-        assert_eq!(settings.note, 0);
-        assert_eq!(settings.octave, 2);
-        assert_eq!(settings.formant, 0);
+        assert_eq!(settings.key, Key::CMajor);
+        assert_eq!(settings.note, Note::Auto);
+        assert_eq!(settings.octave, Octave::Middle);
+        assert_eq!(settings.formant, Formant::None);
+        assert_eq!(settings.pitch_shift_semitones, 0.0);
+    }
+
+    #[test]
+    fn test_settings_from_legacy_values() {
+        for (index, key) in Key::ALL.iter().enumerate() {
+            assert_eq!(Key::try_from(index as i32), Ok(*key));
+            assert_eq!(i32::from(*key), index as i32);
+        }
+        assert_eq!(Key::FSharpMajor.name(), "F#");
+        assert!(Key::EFlatMinor.is_minor() && !Key::AFlatMajor.is_minor());
+        assert_eq!(Key::try_from(24), Err(VocalEffectsError::InvalidConfiguration));
+        assert_eq!(Key::try_from(-1), Err(VocalEffectsError::InvalidConfiguration));
+
+        assert_eq!(Note::try_from(0), Ok(Note::Auto));
+        assert_eq!(Note::try_from(9), Ok(Note::Degree9));
+        assert!(Note::try_from(10).is_err());
+
+        assert_eq!(Octave::try_from(4), Ok(Octave::High));
+        assert!(Octave::try_from(3).is_err());
+        assert!(Octave::try_from(0).is_err());
+        assert_eq!(i32::from(Octave::Low), 1);
+
+        assert_eq!(OctaveShift::try_from(-2), Ok(OctaveShift::Down2));
+        assert!(OctaveShift::try_from(3).is_err());
+        assert_eq!(i32::from(OctaveShift::Up1), 1);
+        let legacy = [1, 2, 4].map(|flag| OctaveShift::from(Octave::try_from(flag).unwrap()));
+        assert_eq!(legacy.map(OctaveShift::ratio), [0.5, 1.0, 2.0]);
+        assert_eq!(OctaveShift::ALL.map(|shift| shift.ratio().log2() as i32), [-2, -1, 0, 1, 2]);
+
+        assert_eq!(Formant::try_from(2), Ok(Formant::Higher));
+        assert!(Formant::try_from(3).is_err());
+    }
+
+    #[test]
+    fn test_key_transposition() {
+        assert_eq!(Key::CMajor.transposed(1), Key::CSharpMajor);
+        assert_eq!(Key::CMajor.transposed(-1), Key::BMajor);
+        assert_eq!(Key::AMinor.transposed(14), Key::BMinor);
+        assert_eq!(Key::EFlatMinor.parallel(), Key::EFlatMajor);
+        assert_eq!(Key::GMajor.parallel(), Key::GMinor);
+        for key in Key::ALL {
+            assert_eq!(key.transposed(12), key);
+            assert_eq!(key.parallel().parallel(), key);
+        }
+    }
+
+    #[test]
+    fn test_processing_state_reset() {
+        let mut state = ProcessingState::<8>::new();
+        state.last_input_phases[3] = 1.0;
+        state.last_output_phases[3] = 2.5;
+        state.analysis.pitch_shift_ratio = 1.5;
+
+        state.reanchor_phases();
+        assert_eq!(state.last_output_phases[3], 1.0);
+        assert_eq!(state.pitch_shift_ratio(), 1.5);
+
+        state.reset();
+        assert_eq!(state, ProcessingState::new());
+    }
+
+    #[test]
+    fn test_vocoder_eq_storage_and_bands() {
+        let mut eq = VocoderEq::FLAT;
+        eq.set_band_db(3, -6.4);
+        eq.set_band_db(4, 30.0);
+        eq.set_band_db(5, -200.0);
+        eq.set_band_db(6, f32::NAN);
+        assert_eq!(eq.band_db(3), -6.0);
+        assert_eq!(eq.band_db(4), VocoderEq::MAX_DB);
+        assert_eq!(eq.band_gain(5), 0.0);
+        assert_eq!(eq.band_gain(6), 1.0);
+        assert_eq!(VocoderEq::from_bytes(eq.to_bytes()), eq);
+        assert_eq!(VocoderEq::from_bytes([0x80; VOCODER_BANDS]).band_db(0), VocoderEq::MUTE_DB);
+
+        assert!((VocoderEq::band_centre_hz(0) - 100.0).abs() < 1e-3);
+        assert!((VocoderEq::band_centre_hz(VOCODER_BANDS - 1) - 8000.0).abs() < 0.1);
+        let gains: [f32; VOCODER_BANDS] = core::array::from_fn(|band| band as f32);
+        assert_eq!(VocoderEq::gain_at(&gains, 20.0), 0.0);
+        assert_eq!(VocoderEq::gain_at(&gains, 20000.0), 15.0);
+        let between = libm::sqrtf(VocoderEq::band_centre_hz(2) * VocoderEq::band_centre_hz(3));
+        assert!((VocoderEq::gain_at(&gains, between) - 2.5).abs() < 1e-3);
     }
 }
@@ -0,0 +1,165 @@
+//! Stereo placement of a mono voice.
+//!
+//! The input stays mono; [`Placement`] puts the processed voice somewhere in a
+//! stereo output. Panning follows the constant-power law, so a voice keeps its
+//! loudness as it moves across the image. Width spreads the stereo effects
+//! (chorus, ping-pong delay, reverb) around it, from mono at 0.0 to their
+//! natural spread at 1.0 and wider beyond.
+//!
+//! Wide images can fall apart when summed to mono on a phone speaker or a
+//! club system. [`mono_check`] measures how correlated the two channels are and
+//! how much level the mono sum loses, so a UI can warn before it happens.
+
+use core::f32::consts::FRAC_PI_4;
+
+use libm::{cosf, log10f, sinf, sqrtf};
+
+use crate::meter::SILENCE_DB;
+
+/// Widest spread a [`Placement`] takes
+pub const MAX_WIDTH: f32 = 2.0;
+
+/// Position and spread of a voice in the stereo image
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Placement {
+    /// Position from -1.0 (left) through 0.0 (centre) to 1.0 (right)
+    pub pan: f32,
+    /// Spread of the stereo effects, 0.0 (mono) to [`MAX_WIDTH`]
+    pub width: f32,
+}
+
+impl Placement {
+    /// Centred, with the effects at their natural spread
+    pub const CENTRE: Self = Self { pan: 0.0, width: 1.0 };
+
+    /// The placement with pan and width in range, NaN counting as centre and mono
+    pub fn clamped(&self) -> Self {
+        let clamp = |value: f32, low, high| {
+            if value.is_nan() {
+                0.0
+            } else {
+                value.clamp(low, high)
+            }
+        };
+        Self { pan: clamp(self.pan, -1.0, 1.0), width: clamp(self.width, 0.0, MAX_WIDTH) }
+    }
+}
+
+impl Default for Placement {
+    fn default() -> Self {
+        Self::CENTRE
+    }
+}
+
+/// Left and right gains of the constant-power pan law, -3 dB each at the centre
+///
+/// The squares of the gains always add up to one. `pan` is clamped to -1.0 to 1.0.
+pub fn pan_gains(pan: f32) -> (f32, f32) {
+    let angle = (pan.clamp(-1.0, 1.0) + 1.0) * FRAC_PI_4;
+    (cosf(angle), sinf(angle))
+}
+
+/// Scales the side of a stereo pair by `width`, keeping its mid
+#[inline]
+pub fn apply_width(left: f32, right: f32, width: f32) -> (f32, f32) {
+    let mid = 0.5 * (left + right);
+    let side = 0.5 * (left - right) * width;
+    (mid + side, mid - side)
+}
+
+/// How a stereo block holds up summed to mono
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MonoReading {
+    /// Correlation of the channels, 1.0 for mono, 0.0 for unrelated channels and
+    /// negative where they cancel
+    pub correlation: f32,
+    /// Level of the mono sum `(L + R) / 2` relative to the average channel level,
+    /// in dB: 0 for mono, about -3 for unrelated channels, [`SILENCE_DB`] at worst
+    pub mono_loss_db: f32,
+}
+
+impl MonoReading {
+    /// Reading of silence or of identical channels
+    pub const MONO: Self = Self { correlation: 1.0, mono_loss_db: 0.0 };
+
+    /// Returns `true` unless the channels lean towards cancelling in mono
+    pub fn is_mono_compatible(&self) -> bool {
+        self.correlation >= 0.0
+    }
+}
+
+impl Default for MonoReading {
+    fn default() -> Self {
+        Self::MONO
+    }
+}
+
+/// Measures the mono compatibility of one stereo block
+///
+/// Extra samples of the longer channel are ignored; silence reads as mono.
+pub fn mono_check(left: &[f32], right: &[f32]) -> MonoReading {
+    let (mut both, mut left_power, mut right_power) = (0.0f32, 0.0f32, 0.0f32);
+    for (l, r) in left.iter().zip(right) {
+        both += l * r;
+        left_power += l * l;
+        right_power += r * r;
+    }
+    let stereo_power = 0.5 * (left_power + right_power);
+    if stereo_power <= 0.0 {
+        return MonoReading::MONO;
+    }
+    let mono_power = 0.25 * (left_power + right_power + 2.0 * both);
+    let correlation = both / sqrtf(left_power * right_power).max(f32::MIN_POSITIVE);
+    let mono_loss_db = if mono_power > 0.0 {
+        (10.0 * log10f(mono_power / stereo_power)).max(SILENCE_DB)
+    } else {
+        SILENCE_DB
+    };
+    MonoReading { correlation: correlation.clamp(-1.0, 1.0), mono_loss_db }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pan_law_keeps_power() {
+        for pan in [-1.0, -0.6, 0.0, 0.25, 1.0] {
+            let (left, right) = pan_gains(pan);
+            assert!((left * left + right * right - 1.0).abs() < 1e-6, "{pan}");
+        }
+        let (left, right) = pan_gains(0.0);
+        assert!((left - core::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6 && left == right);
+        let (left, right) = pan_gains(-3.0);
+        assert!(left == 1.0 && right.abs() < 1e-7);
+
+        assert_eq!(apply_width(1.0, 0.0, 0.0), (0.5, 0.5));
+        assert_eq!(apply_width(1.0, 0.0, 2.0), (1.5, -0.5));
+        let wild = Placement { pan: 4.0, width: f32::NAN }.clamped();
+        assert_eq!(wild, Placement { pan: 1.0, width: 0.0 });
+    }
+
+    #[test]
+    fn test_mono_check() {
+        let signal: [f32; 480] = core::array::from_fn(|n| libm::sinf(n as f32 * 0.1));
+        let inverted = signal.map(|sample| -sample);
+        let quadrature: [f32; 480] = core::array::from_fn(|n| libm::cosf(n as f32 * 0.1));
+
+        let same = mono_check(&signal, &signal);
+        assert!((same.correlation - 1.0).abs() < 1e-5 && same.mono_loss_db.abs() < 1e-4);
+        let cancelled = mono_check(&signal, &inverted);
+        assert!((cancelled.correlation + 1.0).abs() < 1e-5);
+        assert_eq!(cancelled.mono_loss_db, SILENCE_DB);
+        assert!(!cancelled.is_mono_compatible());
+        let unrelated = mono_check(&signal, &quadrature);
+        assert!(unrelated.correlation.abs() < 0.05, "{unrelated:?}");
+        assert!((unrelated.mono_loss_db + 3.0).abs() < 0.3, "{unrelated:?}");
+
+        // One silent side is uncorrelated, not broken
+        let one_sided = mono_check(&signal, &[0.0; 480]);
+        assert_eq!(one_sided.correlation, 0.0);
+        assert_eq!(mono_check(&[], &[]), MonoReading::MONO);
+    }
+}
@@ -0,0 +1,370 @@
+//! Tempo, tap tempo and note values.
+//!
+//! [`TapTempo`] turns button presses into a tempo: the interval between taps is
+//! averaged over the last few taps, so a slightly uneven foot still gives a
+//! steady tempo. A pause longer than the slowest tempo starts a new count.
+//!
+//! [`TempoClock`] keeps the beat position modulation and delay effects lock to.
+//! It follows the host's [`Transport`] when there is one and otherwise runs on
+//! its own at a set or tapped tempo. Rates and times are given as a
+//! [`NoteValue`], such as a dotted eighth, and converted at the current tempo.
+
+use libm::floor;
+
+/// Slowest tempo a pair of taps can give, in beats per minute
+pub const MIN_TAP_BPM: f32 = 30.0;
+
+/// Fastest tempo a pair of taps can give, in beats per minute
+pub const MAX_TAP_BPM: f32 = 300.0;
+
+/// Tempo of a [`TempoClock`] until it is set, tapped or synced
+pub const DEFAULT_BPM: f32 = 120.0;
+
+/// Tap intervals averaged
+const AVERAGED_TAPS: usize = 4;
+
+/// Rhythmic length of a note, relative to a whole note
+///
+/// Beats are quarter notes, as in MIDI clock and most hosts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NoteValue {
+    /// Whole notes in the numerator, 1 for plain divisions
+    pub numerator: u16,
+    /// Division of the whole note, such as 4 for a quarter note
+    pub denominator: u16,
+    pub feel: Feel,
+}
+
+/// Modifier of a [`NoteValue`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Feel {
+    #[default]
+    Straight,
+    /// One and a half times as long
+    Dotted,
+    /// Two thirds as long, three in the time of two
+    Triplet,
+}
+
+impl NoteValue {
+    pub const WHOLE: Self = Self::new(1, 1);
+    pub const HALF: Self = Self::new(1, 2);
+    pub const QUARTER: Self = Self::new(1, 4);
+    pub const EIGHTH: Self = Self::new(1, 8);
+    pub const SIXTEENTH: Self = Self::new(1, 16);
+    pub const THIRTY_SECOND: Self = Self::new(1, 32);
+
+    /// Straight note of `numerator / denominator` whole notes
+    pub const fn new(numerator: u16, denominator: u16) -> Self {
+        Self { numerator, denominator, feel: Feel::Straight }
+    }
+
+    /// The same note dotted
+    pub const fn dotted(self) -> Self {
+        Self { feel: Feel::Dotted, ..self }
+    }
+
+    /// The same note as a triplet
+    pub const fn triplet(self) -> Self {
+        Self { feel: Feel::Triplet, ..self }
+    }
+
+    /// Length in beats, zero for a zero denominator
+    pub fn beats(&self) -> f64 {
+        if self.denominator == 0 {
+            return 0.0;
+        }
+        let straight = 4.0 * f64::from(self.numerator) / f64::from(self.denominator);
+        match self.feel {
+            Feel::Straight => straight,
+            Feel::Dotted => straight * 1.5,
+            Feel::Triplet => straight * 2.0 / 3.0,
+        }
+    }
+
+    /// Length in seconds at `bpm`
+    pub fn seconds(&self, bpm: f32) -> f32 {
+        if bpm <= 0.0 {
+            return 0.0;
+        }
+        (self.beats() * 60.0 / f64::from(bpm)) as f32
+    }
+
+    /// Length in samples at `bpm`, rounded to the nearest sample
+    pub fn samples(&self, bpm: f32, sample_rate: f32) -> usize {
+        libm::roundf(self.seconds(bpm) * sample_rate) as usize
+    }
+
+    /// Rate in Hz of a cycle lasting this note at `bpm`, zero for a zero length
+    pub fn hz(&self, bpm: f32) -> f32 {
+        let seconds = self.seconds(bpm);
+        if seconds > 0.0 { 1.0 / seconds } else { 0.0 }
+    }
+}
+
+/// Tempo and position reported by a host or sequencer
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Transport {
+    pub bpm: f32,
+    /// Position in beats at the first sample of the next hop
+    pub beat: f64,
+    /// Whether the position moves; a stopped host holds it
+    pub playing: bool,
+}
+
+/// Where a [`TempoClock`] takes its tempo from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TempoSource {
+    /// Runs on its own at a set or tapped tempo
+    #[default]
+    Internal,
+    /// Follows the last [`Transport`]
+    Host,
+}
+
+/// Beat clock for tempo-synced effects
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TempoClock {
+    bpm: f32,
+    beat: f64,
+    playing: bool,
+    source: TempoSource,
+}
+
+impl Default for TempoClock {
+    fn default() -> Self {
+        Self::new(DEFAULT_BPM)
+    }
+}
+
+impl TempoClock {
+    /// Creates a free-running clock at `bpm`, at beat 0
+    pub const fn new(bpm: f32) -> Self {
+        Self { bpm, beat: 0.0, playing: true, source: TempoSource::Internal }
+    }
+
+    /// Tempo in beats per minute
+    pub fn bpm(&self) -> f32 {
+        self.bpm
+    }
+
+    /// Position in beats
+    pub fn beat(&self) -> f64 {
+        self.beat
+    }
+
+    pub fn source(&self) -> TempoSource {
+        self.source
+    }
+
+    /// Locks the clock to the host's tempo and position
+    ///
+    /// The clock stays with the host until [`TempoClock::release`]; between
+    /// updates it runs on at the host's last tempo.
+    pub fn sync(&mut self, transport: Transport) {
+        if transport.bpm > 0.0 && transport.bpm.is_finite() {
+            self.bpm = transport.bpm;
+        }
+        if transport.beat.is_finite() {
+            self.beat = transport.beat;
+        }
+        self.playing = transport.playing;
+        self.source = TempoSource::Host;
+    }
+
+    /// Lets the clock run on its own from where the host left it
+    pub fn release(&mut self) {
+        self.source = TempoSource::Internal;
+        self.playing = true;
+    }
+
+    /// Sets the tempo of the internal clock, ignored while following the host
+    pub fn set_bpm(&mut self, bpm: f32) {
+        if self.source == TempoSource::Internal && bpm > 0.0 && bpm.is_finite() {
+            self.bpm = bpm;
+        }
+    }
+
+    /// Takes a tapped tempo, starting a beat on the tap
+    ///
+    /// Ignored while following the host.
+    pub fn tapped(&mut self, bpm: f32) {
+        if self.source == TempoSource::Internal && bpm > 0.0 && bpm.is_finite() {
+            self.bpm = bpm;
+            self.beat = libm::round(self.beat);
+        }
+    }
+
+    /// Moves the clock on by `seconds`
+    pub fn advance(&mut self, seconds: f64) {
+        if self.playing {
+            self.beat += seconds * f64::from(self.bpm) / 60.0;
+        }
+    }
+
+    /// Returns to beat 0, keeping the tempo and source
+    pub fn rewind(&mut self) {
+        self.beat = 0.0;
+    }
+
+    /// Position within a cycle of `note`, from 0 up to 1
+    ///
+    /// Cycles start on beat 0, so an LFO driven by it stays in phase with the bar.
+    pub fn phase(&self, note: NoteValue) -> f32 {
+        let length = note.beats();
+        if length <= 0.0 {
+            return 0.0;
+        }
+        let cycles = self.beat / length;
+        (cycles - floor(cycles)) as f32
+    }
+
+    /// Length of `note` in seconds at the clock's tempo
+    pub fn seconds(&self, note: NoteValue) -> f32 {
+        note.seconds(self.bpm)
+    }
+
+    /// Rate in Hz of a cycle lasting `note` at the clock's tempo
+    pub fn hz(&self, note: NoteValue) -> f32 {
+        note.hz(self.bpm)
+    }
+}
+
+/// Tempo from tapped beats
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TapTempo {
+    /// Time of the last tap in seconds
+    last_tap: Option<f64>,
+    /// Most recent intervals in seconds, oldest first
+    intervals: [f32; AVERAGED_TAPS],
+    count: usize,
+    bpm: Option<f32>,
+}
+
+impl Default for TapTempo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TapTempo {
+    /// Creates a tap tempo with no tempo yet
+    pub const fn new() -> Self {
+        Self { last_tap: None, intervals: [0.0; AVERAGED_TAPS], count: 0, bpm: None }
+    }
+
+    /// Registers a tap `seconds` into the stream and returns the tempo, if any
+    ///
+    /// Taps closer together than [`MAX_TAP_BPM`] allows are ignored as bounces.
+    pub fn tap(&mut self, seconds: f64) -> Option<f32> {
+        let Some(last_tap) = self.last_tap else {
+            self.last_tap = Some(seconds);
+            return self.bpm;
+        };
+        let interval = (seconds - last_tap) as f32;
+        if interval < 60.0 / MAX_TAP_BPM {
+            return self.bpm;
+        }
+        self.last_tap = Some(seconds);
+        if interval > 60.0 / MIN_TAP_BPM {
+            // Too slow to be the next beat, counting starts again
+            self.count = 0;
+            return self.bpm;
+        }
+
+        if self.count == AVERAGED_TAPS {
+            self.intervals.copy_within(1.., 0);
+            self.count -= 1;
+        }
+        self.intervals[self.count] = interval;
+        self.count += 1;
+        let average = self.intervals[..self.count].iter().sum::<f32>() / self.count as f32;
+        self.bpm = Some(60.0 / average);
+        self.bpm
+    }
+
+    /// Tempo in beats per minute, once two taps have been counted
+    pub fn bpm(&self) -> Option<f32> {
+        self.bpm
+    }
+
+    /// Forgets the taps in progress, keeping the tempo
+    ///
+    /// Use this when the clock the taps are timed on starts again.
+    pub fn restart(&mut self) {
+        self.last_tap = None;
+        self.count = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_taps_average_to_tempo() {
+        let mut tempo = TapTempo::new();
+        assert_eq!(tempo.tap(1.0), None);
+        let bpm = tempo.tap(1.5).unwrap();
+        assert!((bpm - 120.0).abs() < 1e-3);
+
+        // Uneven taps around 0.5 s still give 120 BPM
+        for seconds in [2.02, 2.48, 3.01, 3.5] {
+            tempo.tap(seconds);
+        }
+        assert!((tempo.bpm().unwrap() - 120.0).abs() < 1.0, "{:?}", tempo.bpm());
+
+        // A bounce is ignored, a long pause starts counting again
+        tempo.tap(3.51);
+        assert!((tempo.bpm().unwrap() - 120.0).abs() < 1.0);
+        tempo.tap(10.0);
+        tempo.tap(11.0);
+        assert!((tempo.bpm().unwrap() - 60.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_note_values_at_tempo() {
+        assert_eq!(NoteValue::QUARTER.beats(), 1.0);
+        assert_eq!(NoteValue::EIGHTH.dotted().beats(), 0.75);
+        assert_eq!(NoteValue::QUARTER.triplet().beats(), 2.0 / 3.0);
+        assert_eq!(NoteValue::new(3, 4).beats(), 3.0);
+        assert_eq!(NoteValue::new(1, 0).seconds(120.0), 0.0);
+
+        assert_eq!(NoteValue::QUARTER.seconds(120.0), 0.5);
+        assert_eq!(NoteValue::EIGHTH.dotted().samples(120.0, 48000.0), 18000);
+        assert_eq!(NoteValue::SIXTEENTH.hz(120.0), 8.0);
+        assert_eq!(NoteValue::QUARTER.hz(0.0), 0.0);
+    }
+
+    #[test]
+    fn test_clock_follows_host_then_runs_free() {
+        let mut clock = TempoClock::default();
+        clock.advance(1.0);
+        assert_eq!(clock.beat(), 2.0);
+
+        clock.sync(Transport { bpm: 90.0, beat: 16.5, playing: true });
+        assert_eq!((clock.source(), clock.bpm()), (TempoSource::Host, 90.0));
+        assert_eq!(clock.phase(NoteValue::HALF), 0.25);
+        clock.set_bpm(140.0);
+        clock.tapped(140.0);
+        assert_eq!(clock.bpm(), 90.0);
+
+        // A stopped host holds the position
+        clock.sync(Transport { bpm: 90.0, beat: 20.0, playing: false });
+        clock.advance(1.0);
+        assert_eq!(clock.beat(), 20.0);
+
+        clock.release();
+        clock.advance(2.0);
+        assert_eq!(clock.beat(), 23.0);
+        clock.advance(0.2);
+        clock.tapped(100.0);
+        assert_eq!((clock.bpm(), clock.beat()), (100.0, 23.0));
+    }
+}
@@ -0,0 +1,366 @@
+//! Test-signal generators.
+//!
+//! Deterministic signals for tests, benchmarks and self-test routines on the
+//! device: pure tones, exponential sweeps, vowel-like harmonic stacks with
+//! vibrato, pink noise and click trains. All generators run without `std` and
+//! produce one sample at a time through [`TestSignal`].
+//!
+//! ```rust
+//! use synthphone_e_vocal_dsp::testsig::{TestSignal, Vowel};
+//!
+//! let mut voice = Vowel::new(220.0, 0.5, 48000.0).with_vibrato(5.5, 30.0);
+//! let mut block = [0.0f32; 256];
+//! voice.fill(&mut block);
+//! ```
+
+use core::f32::consts::PI;
+
+use libm::{exp2f, sinf};
+
+/// Source of test samples
+pub trait TestSignal {
+    /// Produces the next sample
+    fn next_sample(&mut self) -> f32;
+
+    /// Fills `output` with the next samples
+    fn fill(&mut self, output: &mut [f32]) {
+        for sample in output.iter_mut() {
+            *sample = self.next_sample();
+        }
+    }
+}
+
+/// Advances a phase in cycles by `increment`, wrapped to `0.0..1.0`
+#[inline]
+fn advance(phase: &mut f32, increment: f32) {
+    *phase += increment;
+    *phase -= libm::floorf(*phase);
+}
+
+/// Sine tone
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sine {
+    increment: f32,
+    amplitude: f32,
+    phase: f32,
+}
+
+impl Sine {
+    /// Creates a tone at `frequency` Hz with peak `amplitude`, starting at zero
+    pub fn new(frequency: f32, amplitude: f32, sample_rate: f32) -> Self {
+        Self { increment: frequency / sample_rate, amplitude, phase: 0.0 }
+    }
+}
+
+impl TestSignal for Sine {
+    fn next_sample(&mut self) -> f32 {
+        let sample = self.amplitude * sinf(2.0 * PI * self.phase);
+        advance(&mut self.phase, self.increment);
+        sample
+    }
+}
+
+/// Exponential sine sweep that holds its end frequency once finished
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sweep {
+    start_increment: f32,
+    octaves: f32,
+    samples: f32,
+    amplitude: f32,
+    position: f32,
+    phase: f32,
+}
+
+impl Sweep {
+    /// Sweeps from `start_hz` to `end_hz` over `seconds` with peak `amplitude`
+    pub fn new(start_hz: f32, end_hz: f32, seconds: f32, amplitude: f32, sample_rate: f32) -> Self {
+        Self {
+            start_increment: start_hz / sample_rate,
+            octaves: libm::log2f(end_hz / start_hz),
+            samples: (seconds * sample_rate).max(1.0),
+            amplitude,
+            position: 0.0,
+            phase: 0.0,
+        }
+    }
+
+    /// Frequency of the next sample as a fraction of the sample rate
+    fn increment(&self) -> f32 {
+        let progress = (self.position / self.samples).min(1.0);
+        self.start_increment * exp2f(self.octaves * progress)
+    }
+}
+
+impl TestSignal for Sweep {
+    fn next_sample(&mut self) -> f32 {
+        let sample = self.amplitude * sinf(2.0 * PI * self.phase);
+        let increment = self.increment();
+        advance(&mut self.phase, increment);
+        self.position += 1.0;
+        sample
+    }
+}
+
+/// Most harmonics in a [`Vowel`]
+pub const VOWEL_HARMONICS: usize = 32;
+
+/// Formant frequencies and bandwidths in Hz of an open "ah" vowel
+const AH_FORMANTS: [(f32, f32); 3] = [(700.0, 110.0), (1220.0, 120.0), (2600.0, 160.0)];
+
+/// Vowel-like harmonic stack with optional vibrato
+///
+/// The harmonics fall off at 6 dB per octave and are shaped by the three
+/// formants of an open "ah", so the spectrum resembles a sung vowel. Harmonics
+/// at or above Nyquist are left out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vowel {
+    f0: f32,
+    sample_rate: f32,
+    weights: [f32; VOWEL_HARMONICS],
+    vibrato_increment: f32,
+    vibrato_cents: f32,
+    vibrato_phase: f32,
+    phase: f32,
+}
+
+impl Vowel {
+    /// Creates a vowel at fundamental `f0` Hz, scaled to a peak near `amplitude`
+    pub fn new(f0: f32, amplitude: f32, sample_rate: f32) -> Self {
+        let mut weights = [0.0f32; VOWEL_HARMONICS];
+        for (i, weight) in weights.iter_mut().enumerate() {
+            let frequency = f0 * (i + 1) as f32;
+            let resonance: f32 = AH_FORMANTS
+                .iter()
+                .map(|&(centre, bandwidth)| {
+                    let detune = (frequency - centre) / bandwidth;
+                    1.0 / (1.0 + detune * detune)
+                })
+                .sum();
+            *weight = (0.2 + resonance) / (i + 1) as f32;
+        }
+        let total: f32 = weights.iter().sum();
+        weights.iter_mut().for_each(|weight| *weight *= amplitude / total);
+
+        Self {
+            f0,
+            sample_rate,
+            weights,
+            vibrato_increment: 0.0,
+            vibrato_cents: 0.0,
+            vibrato_phase: 0.0,
+            phase: 0.0,
+        }
+    }
+
+    /// Adds a sinusoidal vibrato at `rate_hz` with a depth of ± `depth_cents`
+    pub fn with_vibrato(mut self, rate_hz: f32, depth_cents: f32) -> Self {
+        self.vibrato_increment = rate_hz / self.sample_rate;
+        self.vibrato_cents = depth_cents;
+        self
+    }
+
+    /// Changes the fundamental, keeping the spectral shape of the original pitch
+    pub fn set_f0(&mut self, f0: f32) {
+        self.f0 = f0;
+    }
+
+    /// Fundamental of the next sample in Hz, including the vibrato
+    pub fn current_f0(&self) -> f32 {
+        let cents = self.vibrato_cents * sinf(2.0 * PI * self.vibrato_phase);
+        self.f0 * exp2f(cents / 1200.0)
+    }
+}
+
+impl TestSignal for Vowel {
+    fn next_sample(&mut self) -> f32 {
+        let f0 = self.current_f0();
+        let nyquist = self.sample_rate / 2.0;
+        let mut sample = 0.0;
+        for (i, weight) in self.weights.iter().enumerate() {
+            let harmonic = (i + 1) as f32;
+            if f0 * harmonic >= nyquist {
+                break;
+            }
+            sample += weight * sinf(2.0 * PI * harmonic * self.phase);
+        }
+        advance(&mut self.phase, f0 / self.sample_rate);
+        advance(&mut self.vibrato_phase, self.vibrato_increment);
+        sample
+    }
+}
+
+/// Pink noise from a seeded generator, falling off at 3 dB per octave
+///
+/// White noise from xorshift is shaped by Paul Kellet's economy filter, accurate
+/// to about 0.5 dB above 10 Hz at 44.1 or 48 kHz.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PinkNoise {
+    amplitude: f32,
+    rng: u32,
+    state: [f32; 3],
+}
+
+impl PinkNoise {
+    /// Creates noise with an RMS level near `amplitude / 4`; equal seeds give equal noise
+    pub const fn new(amplitude: f32, seed: u32) -> Self {
+        // xorshift has a fixed point at zero
+        let rng = if seed == 0 { 1 } else { seed };
+        Self { amplitude, rng, state: [0.0; 3] }
+    }
+
+    fn white(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        self.rng as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+}
+
+impl TestSignal for PinkNoise {
+    fn next_sample(&mut self) -> f32 {
+        let white = self.white();
+        self.state[0] = 0.99765 * self.state[0] + white * 0.0990460;
+        self.state[1] = 0.96300 * self.state[1] + white * 0.2965164;
+        self.state[2] = 0.57000 * self.state[2] + white * 1.0526913;
+        let pink = self.state[0] + self.state[1] + self.state[2] + white * 0.1848;
+        // The filter has a gain of about 4 at low frequencies
+        self.amplitude * pink * 0.25
+    }
+}
+
+/// Train of single-sample clicks at a fixed interval, starting with a click
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Clicks {
+    interval: u32,
+    amplitude: f32,
+    position: u32,
+}
+
+impl Clicks {
+    /// Clicks of `amplitude` every `interval_seconds`
+    pub fn new(interval_seconds: f32, amplitude: f32, sample_rate: f32) -> Self {
+        Self {
+            interval: ((interval_seconds * sample_rate) as u32).max(1),
+            amplitude,
+            position: 0,
+        }
+    }
+}
+
+impl TestSignal for Clicks {
+    fn next_sample(&mut self) -> f32 {
+        let sample = if self.position == 0 {
+            self.amplitude
+        } else {
+            0.0
+        };
+        self.position = (self.position + 1) % self.interval;
+        sample
+    }
+}
+
+/// Amplitude of the `frequency` component of `samples`
+///
+/// Correlates the block with a cosine and sine at `frequency`, which measures how
+/// much of a tone survives a filter or effect. Exact for a tone that completes
+/// whole cycles in the block.
+pub fn tone_level(samples: &[f32], frequency: f32, sample_rate: f32) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let (mut re, mut im) = (0.0f32, 0.0f32);
+    for (n, sample) in samples.iter().enumerate() {
+        let phase = 2.0 * PI * frequency * n as f32 / sample_rate;
+        re += sample * libm::cosf(phase);
+        im += sample * sinf(phase);
+    }
+    2.0 * libm::sqrtf(re * re + im * im) / samples.len() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    fn rms(samples: &[f32]) -> f32 {
+        libm::sqrtf(samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32)
+    }
+
+    #[test]
+    fn test_sine_and_clicks() {
+        let mut block = [0.0f32; 480];
+        Sine::new(100.0, 0.5, SAMPLE_RATE).fill(&mut block);
+        assert_eq!(block[0], 0.0);
+        assert!((block[120] - 0.5).abs() < 1e-4);
+        assert!((rms(&block) - 0.5 * core::f32::consts::FRAC_1_SQRT_2).abs() < 1e-3);
+
+        Clicks::new(0.001, 1.0, SAMPLE_RATE).fill(&mut block);
+        let clicks: usize = block.iter().filter(|&&s| s == 1.0).count();
+        assert_eq!(clicks, 10);
+        assert_eq!(block[48], 1.0);
+    }
+
+    #[test]
+    fn test_tone_level_picks_out_one_tone() {
+        let mut low = [0.0f32; 4800];
+        let mut high = [0.0f32; 4800];
+        Sine::new(100.0, 0.5, SAMPLE_RATE).fill(&mut low);
+        Sine::new(1000.0, 0.2, SAMPLE_RATE).fill(&mut high);
+        let mixed: [f32; 4800] = core::array::from_fn(|n| low[n] + high[n]);
+
+        assert!((tone_level(&mixed, 100.0, SAMPLE_RATE) - 0.5).abs() < 1e-3);
+        assert!((tone_level(&mixed, 1000.0, SAMPLE_RATE) - 0.2).abs() < 1e-3);
+        assert!(tone_level(&mixed, 500.0, SAMPLE_RATE) < 1e-3);
+        assert_eq!(tone_level(&[], 100.0, SAMPLE_RATE), 0.0);
+    }
+
+    #[test]
+    fn test_sweep_ends_at_target() {
+        let mut sweep = Sweep::new(100.0, 1600.0, 0.1, 1.0, SAMPLE_RATE);
+        let mut block = [0.0f32; 4800];
+        sweep.fill(&mut block);
+        assert!((sweep.increment() * SAMPLE_RATE - 1600.0).abs() < 0.1);
+
+        // Zero crossings double per octave: about 100 Hz * 15 / ln 16 cycles in total
+        let crossings = block.windows(2).filter(|w| w[0] < 0.0 && w[1] >= 0.0).count();
+        assert!((crossings as i32 - 54).abs() <= 2, "{crossings}");
+    }
+
+    #[test]
+    fn test_vowel_fundamental_and_vibrato() {
+        let mut vowel = Vowel::new(220.0, 0.5, SAMPLE_RATE);
+        let mut frame = [0.0f32; 1024];
+        vowel.fill(&mut frame);
+        assert!(frame.iter().all(|s| s.abs() <= 0.5));
+
+        // Strongest periodicity between 100 and 400 Hz
+        let correlation =
+            |lag: usize| -> f32 { frame.iter().zip(&frame[lag..]).map(|(a, b)| a * b).sum() };
+        let period = (120..480).max_by(|&a, &b| correlation(a).total_cmp(&correlation(b))).unwrap();
+        assert!((period as i32 - 218).abs() <= 1, "{period}");
+
+        let mut vibrato = Vowel::new(220.0, 0.5, SAMPLE_RATE).with_vibrato(5.0, 50.0);
+        let mut highest = 0.0f32;
+        for _ in 0..9600 {
+            highest = highest.max(vibrato.current_f0());
+            vibrato.next_sample();
+        }
+        assert!((highest - 220.0 * exp2f(50.0 / 1200.0)).abs() < 0.1, "{highest}");
+    }
+
+    #[test]
+    fn test_pink_noise_is_seeded_and_tilted() {
+        let mut first = [0.0f32; 4096];
+        let mut second = [0.0f32; 4096];
+        PinkNoise::new(1.0, 7).fill(&mut first);
+        PinkNoise::new(1.0, 7).fill(&mut second);
+        assert_eq!(first, second);
+        assert!(rms(&first) > 0.05 && rms(&first) < 0.5, "{}", rms(&first));
+
+        // More energy in the low half of a difference than a white signal would have
+        let differences: f32 = first.windows(2).map(|w| (w[1] - w[0]).powi(2)).sum();
+        let energy: f32 = first.iter().map(|s| s * s).sum();
+        assert!(differences < 0.5 * energy, "{differences} {energy}");
+    }
+}
@@ -4,53 +4,268 @@
 //! to eliminate code duplication across different FFT size configurations.
 
 use crate::{
-    MusicalSettings, ProcessingMode, VocalEffectsConfig,
-    dsp::{Fft512, Fft1024, Fft2048, Fft4096, FftOps},
-    effects::{process_dry_generic, process_pitch_correction_generic, process_vocode_generic},
+    CarrierMode, FrameAnalysis, MusicalSettings, ProcessingMode, VocalEffectsConfig,
+    VocalEffectsError,
+    dsp::{BinTables, DynFft, Fft128, Fft256, Fft512, Fft1024, Fft2048, Fft4096, guards},
+    effects::{
+        process_dry_in_place, process_formant_in_place, process_pitch_correction_in_place,
+        process_talkbox_in_place, process_vocode_in_place,
+    },
+    state::{FrameState, ProcessingState},
+    workspace::Workspace,
 };
 
 /// Generic vocal effects processing function that works with different FFT sizes and processing modes
+///
+/// The output frame is written over `unwrapped_buffer`. Vocode and talk-box mode
+/// fail with [`VocalEffectsError::MissingCarrier`] when `carrier_buffer` is `None`.
 fn process_vocal_effects<const N: usize, const HALF_N: usize, F>(
+    fft: &mut F,
+    workspace: &mut Workspace<N, HALF_N>,
     unwrapped_buffer: &mut [f32; N],
     carrier_buffer: Option<&mut [f32; N]>,
-    last_input_phases: &mut [f32; N],
-    last_output_phases: &mut [f32; N],
-    previous_pitch_shift_ratio: f32,
+    mut state: FrameState<'_, N>,
     config: &VocalEffectsConfig,
     settings: &MusicalSettings,
-) -> [f32; N]
+) -> Result<(), VocalEffectsError>
 where
-    F: FftOps<N, HALF_N>,
+    F: DynFft<N, HALF_N> + ?Sized,
 {
+    // Keep a corrupt input sample from reaching the phase state
+    guards::debug_assert_finite(unwrapped_buffer, "input frame");
+    guards::sanitize(unwrapped_buffer);
+    let mut carrier_buffer = carrier_buffer;
+    if let Some(carrier) = carrier_buffer.as_deref_mut() {
+        guards::debug_assert_finite(carrier, "carrier frame");
+        guards::sanitize(carrier);
+    }
+
+    let state = &mut state;
     match settings.mode {
-        ProcessingMode::Autotune => process_pitch_correction_generic::<N, HALF_N, F>(
+        ProcessingMode::Autotune => process_pitch_correction_in_place(
+            fft,
+            workspace,
             unwrapped_buffer,
-            last_input_phases,
-            last_output_phases,
-            previous_pitch_shift_ratio,
+            state,
             config,
             settings,
         ),
-        ProcessingMode::Vocode => process_vocode_generic::<N, HALF_N, F>(
+        ProcessingMode::Vocode => {
+            let carrier = carrier_buffer.ok_or(VocalEffectsError::MissingCarrier)?;
+            process_vocode_in_place(
+                fft,
+                workspace,
+                unwrapped_buffer,
+                carrier,
+                state,
+                config,
+                settings,
+            )
+        }
+        ProcessingMode::TalkBox => {
+            let carrier = carrier_buffer.ok_or(VocalEffectsError::MissingCarrier)?;
+            process_talkbox_in_place(fft, workspace, unwrapped_buffer, carrier, state, config)
+        }
+        ProcessingMode::Dry => process_dry_in_place(
+            fft,
+            workspace,
             unwrapped_buffer,
-            carrier_buffer.expect("Carrier buffer required for vocode mode"),
-            last_input_phases,
-            last_output_phases,
+            carrier_buffer,
+            state,
             config,
             settings,
         ),
-        ProcessingMode::Dry => process_dry_generic::<N, HALF_N, F>(
-            unwrapped_buffer,
-            carrier_buffer,
+        ProcessingMode::Formant => {
+            process_formant_in_place(fft, workspace, unwrapped_buffer, state, config, settings)
+        }
+    }
+
+    // As many more frames overlap at the output as the synthesis hop is shorter
+    let bins = state.bins;
+    if bins.stretches() && bins.hop_size() > 0 {
+        let gain = bins.synthesis_hop_size() as f32 / bins.hop_size() as f32;
+        unwrapped_buffer.iter_mut().for_each(|sample| *sample *= gain);
+    }
+
+    guards::sanitize_state(
+        state.last_input_phases,
+        state.last_output_phases,
+        &mut state.analysis.pitch_shift_ratio,
+    );
+    guards::debug_assert_finite(unwrapped_buffer, "output frame");
+    guards::sanitize(unwrapped_buffer);
+    dsp_trace!("frame processed: {}", settings.mode);
+    Ok(())
+}
+
+/// Process one frame against a [`ProcessingState`], updating its phases and pitch analysis
+///
+/// `workspace` only provides scratch space and can be shared between states.
+///
+/// # Errors
+///
+/// Returns [`VocalEffectsError::MissingCarrier`] if `settings` select vocode or
+/// talk-box mode and `carrier_buffer` is `None`.
+pub fn process_frame<const N: usize, const HALF_N: usize, F>(
+    fft: &mut F,
+    workspace: &mut Workspace<N, HALF_N>,
+    unwrapped_buffer: &mut [f32; N],
+    carrier_buffer: Option<&mut [f32; N]>,
+    state: &mut ProcessingState<N>,
+    config: &VocalEffectsConfig,
+    settings: &MusicalSettings,
+) -> Result<[f32; N], VocalEffectsError>
+where
+    F: DynFft<N, HALF_N> + ?Sized,
+{
+    process_frame_in_place(
+        fft,
+        workspace,
+        unwrapped_buffer,
+        carrier_buffer,
+        state,
+        config,
+        settings,
+    )?;
+    Ok(*unwrapped_buffer)
+}
+
+/// [`process_frame`] writing the output frame over `unwrapped_buffer`
+///
+/// Saves the frame-sized return value, which matters for large frames on small
+/// stacks. The buffers cannot alias, so the rules are only about their contents:
+///
+/// * `unwrapped_buffer` holds the input frame on entry and the output frame on
+///   return, ready to overlap-add.
+/// * `carrier_buffer` is read in vocode and talk-box mode and holds scratch data
+///   on return, so pass a copy if the carrier history is still needed.
+/// * `state` and `workspace` are the same as for [`process_frame`], and the
+///   output is bit-identical to it.
+///
+/// # Errors
+///
+/// See [`process_frame`].
+pub fn process_frame_in_place<const N: usize, const HALF_N: usize, F>(
+    fft: &mut F,
+    workspace: &mut Workspace<N, HALF_N>,
+    unwrapped_buffer: &mut [f32; N],
+    carrier_buffer: Option<&mut [f32; N]>,
+    state: &mut ProcessingState<N>,
+    config: &VocalEffectsConfig,
+    settings: &MusicalSettings,
+) -> Result<(), VocalEffectsError>
+where
+    F: DynFft<N, HALF_N> + ?Sized,
+{
+    // Measure before processing windows the frame in place
+    let quiet = config.phase_reanchor.is_some_and(|reanchor| {
+        let energy: f32 = unwrapped_buffer.iter().map(|sample| sample * sample).sum();
+        energy <= N as f32 * libm::powf(10.0, reanchor.silence_db / 10.0)
+    });
+    if state.bin_tables.hop_size() != config.hop_size_for(N)
+        || state.bin_tables.synthesis_hop_size() != config.synthesis_hop_size_for(N)
+        || state.bin_tables.frame_size() != config.frame_size_for(N)
+    {
+        state.bin_tables = bin_tables(config);
+    }
+
+    process_vocal_effects(
+        fft,
+        workspace,
+        unwrapped_buffer,
+        carrier_buffer,
+        state.frame_state(),
+        config,
+        settings,
+    )?;
+
+    // The next frame continues from the analysis phases of this quiet one
+    state.frames_since_anchor = state.frames_since_anchor.saturating_add(1);
+    if let Some(reanchor) = &config.phase_reanchor {
+        let hop_seconds = config.hop_size_for(N) as f32 / config.sample_rate;
+        if quiet && state.frames_since_anchor >= reanchor.interval_hops(hop_seconds) {
+            state.reanchor_phases();
+        }
+    }
+    Ok(())
+}
+
+/// Phase vocoder tables of an `N`-point frame under `config`
+fn bin_tables<const N: usize>(config: &VocalEffectsConfig) -> BinTables<N> {
+    BinTables::with_frame_size(config.hop_size_for(N), config.frame_size_for(N))
+        .with_synthesis_hop(config.synthesis_hop_size_for(N))
+}
+
+/// Specialized vocal effects function for 128-point FFT
+///
+/// # Errors
+///
+/// Returns [`VocalEffectsError::MissingCarrier`] if `settings` select vocode or
+/// talk-box mode and `carrier_buffer` is `None`.
+pub fn process_vocal_effects_128(
+    unwrapped_buffer: &mut [f32; 128],
+    carrier_buffer: Option<&mut [f32; 128]>,
+    last_input_phases: &mut [f32; 128],
+    last_output_phases: &mut [f32; 128],
+    previous_pitch_shift_ratio: f32,
+    config: &VocalEffectsConfig,
+    settings: &MusicalSettings,
+) -> Result<[f32; 128], VocalEffectsError> {
+    process_vocal_effects(
+        &mut Fft128,
+        &mut Workspace::new(),
+        unwrapped_buffer,
+        carrier_buffer,
+        FrameState::stateless(
             last_input_phases,
             last_output_phases,
-            config,
-            settings,
+            &bin_tables(config),
+            &mut FrameAnalysis::with_ratio(previous_pitch_shift_ratio),
         ),
-    }
+        config,
+        settings,
+    )?;
+    Ok(*unwrapped_buffer)
+}
+
+/// Specialized vocal effects function for 256-point FFT
+///
+/// # Errors
+///
+/// Returns [`VocalEffectsError::MissingCarrier`] if `settings` select vocode or
+/// talk-box mode and `carrier_buffer` is `None`.
+pub fn process_vocal_effects_256(
+    unwrapped_buffer: &mut [f32; 256],
+    carrier_buffer: Option<&mut [f32; 256]>,
+    last_input_phases: &mut [f32; 256],
+    last_output_phases: &mut [f32; 256],
+    previous_pitch_shift_ratio: f32,
+    config: &VocalEffectsConfig,
+    settings: &MusicalSettings,
+) -> Result<[f32; 256], VocalEffectsError> {
+    process_vocal_effects(
+        &mut Fft256,
+        &mut Workspace::new(),
+        unwrapped_buffer,
+        carrier_buffer,
+        FrameState::stateless(
+            last_input_phases,
+            last_output_phases,
+            &bin_tables(config),
+            &mut FrameAnalysis::with_ratio(previous_pitch_shift_ratio),
+        ),
+        config,
+        settings,
+    )?;
+    Ok(*unwrapped_buffer)
 }
 
 /// Specialized vocal effects function for 512-point FFT
+///
+/// # Errors
+///
+/// Returns [`VocalEffectsError::MissingCarrier`] if `settings` select vocode or
+/// talk-box mode and `carrier_buffer` is `None`.
 pub fn process_vocal_effects_512(
     unwrapped_buffer: &mut [f32; 512],
     carrier_buffer: Option<&mut [f32; 512]>,
@@ -59,19 +274,30 @@ pub fn process_vocal_effects_512(
     previous_pitch_shift_ratio: f32,
     config: &VocalEffectsConfig,
     settings: &MusicalSettings,
-) -> [f32; 512] {
-    process_vocal_effects::<512, 256, Fft512>(
+) -> Result<[f32; 512], VocalEffectsError> {
+    process_vocal_effects(
+        &mut Fft512,
+        &mut Workspace::new(),
         unwrapped_buffer,
         carrier_buffer,
-        last_input_phases,
-        last_output_phases,
-        previous_pitch_shift_ratio,
+        FrameState::stateless(
+            last_input_phases,
+            last_output_phases,
+            &bin_tables(config),
+            &mut FrameAnalysis::with_ratio(previous_pitch_shift_ratio),
+        ),
         config,
         settings,
-    )
+    )?;
+    Ok(*unwrapped_buffer)
 }
 
 /// Specialized vocal effects function for 1024-point FFT
+///
+/// # Errors
+///
+/// Returns [`VocalEffectsError::MissingCarrier`] if `settings` select vocode or
+/// talk-box mode and `carrier_buffer` is `None`.
 pub fn process_vocal_effects_1024(
     unwrapped_buffer: &mut [f32; 1024],
     carrier_buffer: Option<&mut [f32; 1024]>,
@@ -80,19 +306,30 @@ pub fn process_vocal_effects_1024(
     previous_pitch_shift_ratio: f32,
     config: &VocalEffectsConfig,
     settings: &MusicalSettings,
-) -> [f32; 1024] {
-    process_vocal_effects::<1024, 512, Fft1024>(
+) -> Result<[f32; 1024], VocalEffectsError> {
+    process_vocal_effects(
+        &mut Fft1024,
+        &mut Workspace::new(),
         unwrapped_buffer,
         carrier_buffer,
-        last_input_phases,
-        last_output_phases,
-        previous_pitch_shift_ratio,
+        FrameState::stateless(
+            last_input_phases,
+            last_output_phases,
+            &bin_tables(config),
+            &mut FrameAnalysis::with_ratio(previous_pitch_shift_ratio),
+        ),
         config,
         settings,
-    )
+    )?;
+    Ok(*unwrapped_buffer)
 }
 
 /// Specialized vocal effects function for 2048-point FFT
+///
+/// # Errors
+///
+/// Returns [`VocalEffectsError::MissingCarrier`] if `settings` select vocode or
+/// talk-box mode and `carrier_buffer` is `None`.
 pub fn process_vocal_effects_2048(
     unwrapped_buffer: &mut [f32; 2048],
     carrier_buffer: Option<&mut [f32; 2048]>,
@@ -101,19 +338,30 @@ pub fn process_vocal_effects_2048(
     previous_pitch_shift_ratio: f32,
     config: &VocalEffectsConfig,
     settings: &MusicalSettings,
-) -> [f32; 2048] {
-    process_vocal_effects::<2048, 1024, Fft2048>(
+) -> Result<[f32; 2048], VocalEffectsError> {
+    process_vocal_effects(
+        &mut Fft2048,
+        &mut Workspace::new(),
         unwrapped_buffer,
         carrier_buffer,
-        last_input_phases,
-        last_output_phases,
-        previous_pitch_shift_ratio,
+        FrameState::stateless(
+            last_input_phases,
+            last_output_phases,
+            &bin_tables(config),
+            &mut FrameAnalysis::with_ratio(previous_pitch_shift_ratio),
+        ),
         config,
         settings,
-    )
+    )?;
+    Ok(*unwrapped_buffer)
 }
 
 /// Specialized vocal effects function for 4096-point FFT
+///
+/// # Errors
+///
+/// Returns [`VocalEffectsError::MissingCarrier`] if `settings` select vocode or
+/// talk-box mode and `carrier_buffer` is `None`.
 pub fn process_vocal_effects_4096(
     unwrapped_buffer: &mut [f32; 4096],
     carrier_buffer: Option<&mut [f32; 4096]>,
@@ -122,14 +370,216 @@ pub fn process_vocal_effects_4096(
     previous_pitch_shift_ratio: f32,
     config: &VocalEffectsConfig,
     settings: &MusicalSettings,
-) -> [f32; 4096] {
-    process_vocal_effects::<4096, 2048, Fft4096>(
+) -> Result<[f32; 4096], VocalEffectsError> {
+    process_vocal_effects(
+        &mut Fft4096,
+        &mut Workspace::new(),
         unwrapped_buffer,
         carrier_buffer,
-        last_input_phases,
-        last_output_phases,
-        previous_pitch_shift_ratio,
+        FrameState::stateless(
+            last_input_phases,
+            last_output_phases,
+            &bin_tables(config),
+            &mut FrameAnalysis::with_ratio(previous_pitch_shift_ratio),
+        ),
         config,
         settings,
-    )
+    )?;
+    Ok(*unwrapped_buffer)
+}
+
+/// Specialized vocal effects function for 8192-point FFT
+///
+/// # Errors
+///
+/// Returns [`VocalEffectsError::MissingCarrier`] if `settings` select vocode or
+/// talk-box mode and `carrier_buffer` is `None`.
+#[cfg(feature = "fft-8192")]
+pub fn process_vocal_effects_8192(
+    unwrapped_buffer: &mut [f32; 8192],
+    carrier_buffer: Option<&mut [f32; 8192]>,
+    last_input_phases: &mut [f32; 8192],
+    last_output_phases: &mut [f32; 8192],
+    previous_pitch_shift_ratio: f32,
+    config: &VocalEffectsConfig,
+    settings: &MusicalSettings,
+) -> Result<[f32; 8192], VocalEffectsError> {
+    process_vocal_effects(
+        &mut crate::dsp::Fft8192,
+        &mut Workspace::new(),
+        unwrapped_buffer,
+        carrier_buffer,
+        FrameState::stateless(
+            last_input_phases,
+            last_output_phases,
+            &bin_tables(config),
+            &mut FrameAnalysis::with_ratio(previous_pitch_shift_ratio),
+        ),
+        config,
+        settings,
+    )?;
+    Ok(*unwrapped_buffer)
+}
+
+/// Defines the carrier-mode counterpart of a `process_vocal_effects_*` function
+macro_rules! carrier_effects {
+    ($(#[$attr:meta])* $name:ident, $n:literal, $fft:path) => {
+        #[doc = concat!("Specialized carrier-mode function for ", stringify!($n), "-point FFT")]
+        ///
+        /// Runs `mode` in place of the mode of `settings`. Unlike the
+        /// `process_vocal_effects_*` functions, the carrier is not optional, so
+        /// this cannot fail with [`VocalEffectsError::MissingCarrier`].
+        $(#[$attr])*
+        pub fn $name(
+            unwrapped_buffer: &mut [f32; $n],
+            carrier_buffer: &mut [f32; $n],
+            last_input_phases: &mut [f32; $n],
+            last_output_phases: &mut [f32; $n],
+            mode: CarrierMode,
+            config: &VocalEffectsConfig,
+            settings: &MusicalSettings,
+        ) -> [f32; $n] {
+            // The carrier is always given, so processing cannot fail
+            let _ = process_vocal_effects(
+                &mut $fft,
+                &mut Workspace::new(),
+                unwrapped_buffer,
+                Some(carrier_buffer),
+                FrameState::stateless(
+                    last_input_phases,
+                    last_output_phases,
+                    &bin_tables(config),
+                    &mut FrameAnalysis::new(),
+                ),
+                config,
+                &MusicalSettings { mode: mode.into(), ..*settings },
+            );
+            *unwrapped_buffer
+        }
+    };
+}
+
+carrier_effects!(process_carrier_effects_128, 128, Fft128);
+carrier_effects!(process_carrier_effects_256, 256, Fft256);
+carrier_effects!(process_carrier_effects_512, 512, Fft512);
+carrier_effects!(process_carrier_effects_1024, 1024, Fft1024);
+carrier_effects!(process_carrier_effects_2048, 2048, Fft2048);
+carrier_effects!(process_carrier_effects_4096, 4096, Fft4096);
+carrier_effects!(
+    #[cfg(feature = "fft-8192")]
+    process_carrier_effects_8192,
+    8192,
+    crate::dsp::Fft8192
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(frequency: f32) -> [f32; 1024] {
+        core::array::from_fn(|i| {
+            0.3 * libm::sinf(2.0 * core::f32::consts::PI * frequency * i as f32 / 48000.0)
+        })
+    }
+
+    #[test]
+    fn test_carrier_functions_match_carrier_modes() {
+        let config = VocalEffectsConfig::default();
+        for mode in [CarrierMode::Vocode, CarrierMode::TalkBox] {
+            let settings = MusicalSettings { mode: mode.into(), ..Default::default() };
+            let reference = process_vocal_effects_1024(
+                &mut tone(220.0),
+                Some(&mut tone(110.0)),
+                &mut [0.0; 1024],
+                &mut [0.0; 1024],
+                1.0,
+                &config,
+                &settings,
+            )
+            .unwrap();
+            let output = process_carrier_effects_1024(
+                &mut tone(220.0),
+                &mut tone(110.0),
+                &mut [0.0; 1024],
+                &mut [0.0; 1024],
+                mode,
+                &config,
+                &MusicalSettings::default(),
+            );
+            assert_eq!(output, reference, "{mode:?}");
+            assert!(output.iter().any(|&sample| sample != 0.0), "{mode:?}");
+
+            // Through the optional carrier, a missing one is an error
+            let missing = process_vocal_effects_1024(
+                &mut tone(220.0),
+                None,
+                &mut [0.0; 1024],
+                &mut [0.0; 1024],
+                1.0,
+                &config,
+                &settings,
+            );
+            assert_eq!(missing, Err(VocalEffectsError::MissingCarrier), "{mode:?}");
+        }
+        assert_eq!(ProcessingMode::TalkBox.carrier_mode(), Some(CarrierMode::TalkBox));
+        assert_eq!(ProcessingMode::Dry.carrier_mode(), None);
+    }
+}
+
+#[cfg(all(test, feature = "defmt"))]
+mod defmt_tests {
+    use super::*;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Bytes the trace points of every test in the binary have written
+    static WRITTEN: AtomicUsize = AtomicUsize::new(0);
+
+    #[defmt::global_logger]
+    struct CountingLogger;
+
+    unsafe impl defmt::Logger for CountingLogger {
+        fn acquire() {}
+
+        unsafe fn flush() {}
+
+        unsafe fn release() {}
+
+        unsafe fn write(bytes: &[u8]) {
+            WRITTEN.fetch_add(bytes.len(), Ordering::Relaxed);
+        }
+    }
+
+    defmt::timestamp!("{=u32}", 0);
+
+    fn assert_format<T: defmt::Format>() {}
+
+    #[test]
+    fn test_public_types_implement_format() {
+        assert_format::<crate::VocalEffectsError>();
+        assert_format::<VocalEffectsConfig>();
+        assert_format::<MusicalSettings>();
+        assert_format::<FrameAnalysis>();
+    }
+
+    #[test]
+    fn test_trace_points_reach_the_logger() {
+        // Trace points are only compiled in with `DEFMT_LOG=trace`
+        let tracing = option_env!("DEFMT_LOG").is_some_and(|filter| filter.contains("trace"));
+        let before = WRITTEN.load(Ordering::Relaxed);
+
+        let frame: [f32; 1024] = core::array::from_fn(|i| {
+            0.3 * libm::sinf(2.0 * core::f32::consts::PI * 220.0 * i as f32 / 48000.0)
+        });
+        process_vocal_effects_1024(
+            &mut frame.clone(),
+            None,
+            &mut [0.0; 1024],
+            &mut [0.0; 1024],
+            1.0,
+            &VocalEffectsConfig::default(),
+            &MusicalSettings::default(),
+        )
+        .unwrap();
+        assert_eq!(WRITTEN.load(Ordering::Relaxed) > before, tracing);
+    }
 }
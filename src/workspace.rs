@@ -0,0 +1,236 @@
+//! Scratch buffers for frame processing.
+//!
+//! Every processor needs several spectrum-sized buffers per frame, about 80 KB at
+//! 4096 points. [`Workspace`] holds them in one place so the caller decides where
+//! they live: the `process_vocal_effects_*` functions keep them on the stack, which
+//! suits embedded targets where stack is reused between tasks, while the
+//! [`Engine`](crate::Engine) keeps a `HeapWorkspace` when the `alloc` feature is
+//! enabled, so worker threads with small stacks can run large frames.
+
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+
+use microfft::Complex32;
+
+const ZERO: Complex32 = Complex32 { re: 0.0, im: 0.0 };
+
+/// Scratch buffers for processing one frame
+///
+/// The contents are overwritten on every frame; nothing is carried over between
+/// calls, so one workspace can be shared by any number of processing states.
+#[derive(Clone)]
+pub struct Workspace<const N: usize, const HALF_N: usize> {
+    /// Resynthesis spectrum, packed like the forward FFT output
+    pub(crate) spectrum: [Complex32; HALF_N],
+    pub(crate) analysis_magnitudes: [f32; HALF_N],
+    /// Analysis frequencies, or phases in formant mode
+    pub(crate) analysis_frequencies: [f32; HALF_N],
+    /// Synthesis magnitudes, or the cepstrum while the envelope is taken
+    pub(crate) synthesis_magnitudes: [f32; N],
+    pub(crate) synthesis_frequencies: [f32; N],
+    pub(crate) envelope: [f32; HALF_N],
+    /// Harmonic fraction of each bin, 1.0 unless separation is enabled
+    pub(crate) harmonic_mask: [f32; HALF_N],
+}
+
+impl<const N: usize, const HALF_N: usize> Workspace<N, HALF_N> {
+    /// Creates zeroed buffers
+    pub const fn new() -> Self {
+        Self {
+            spectrum: [ZERO; HALF_N],
+            analysis_magnitudes: [0.0; HALF_N],
+            analysis_frequencies: [0.0; HALF_N],
+            synthesis_magnitudes: [0.0; N],
+            synthesis_frequencies: [0.0; N],
+            envelope: [0.0; HALF_N],
+            harmonic_mask: [0.0; HALF_N],
+        }
+    }
+
+    /// Puts the buffers in the state the processors expect at the start of a frame
+    pub(crate) fn prepare(&mut self) {
+        self.spectrum.fill(ZERO);
+        self.analysis_magnitudes.fill(0.0);
+        self.analysis_frequencies.fill(0.0);
+        self.synthesis_magnitudes.fill(0.0);
+        self.synthesis_frequencies.fill(0.0);
+        self.envelope.fill(1.0);
+        self.harmonic_mask.fill(1.0);
+    }
+}
+
+impl<const N: usize, const HALF_N: usize> Default for Workspace<N, HALF_N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// [`Workspace`] allocated on the heap and reused across frames
+///
+/// The buffers are allocated zeroed in place, so creating one never puts the
+/// workspace on the stack, even at 8192 points.
+#[cfg(feature = "alloc")]
+pub struct HeapWorkspace<const N: usize, const HALF_N: usize>(Box<Workspace<N, HALF_N>>);
+
+#[cfg(feature = "alloc")]
+impl<const N: usize, const HALF_N: usize> HeapWorkspace<N, HALF_N> {
+    /// Allocates zeroed buffers
+    pub fn new() -> Self {
+        let layout = core::alloc::Layout::new::<Workspace<N, HALF_N>>();
+        if layout.size() == 0 {
+            return Self(Box::default());
+        }
+        // SAFETY: the layout has a non-zero size, and `Workspace` only holds `f32`
+        // and `Complex32` arrays, for which all-zero bytes are valid `0.0` values.
+        // The pointer comes from the global allocator with the layout of the type
+        // that `Box` will free it with.
+        unsafe {
+            let pointer = alloc::alloc::alloc_zeroed(layout) as *mut Workspace<N, HALF_N>;
+            if pointer.is_null() {
+                alloc::alloc::handle_alloc_error(layout);
+            }
+            Self(Box::from_raw(pointer))
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const N: usize, const HALF_N: usize> Default for HeapWorkspace<N, HALF_N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const N: usize, const HALF_N: usize> Clone for HeapWorkspace<N, HALF_N> {
+    fn clone(&self) -> Self {
+        // Contents are scratch, only the allocation needs to be distinct
+        Self::new()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const N: usize, const HALF_N: usize> core::ops::Deref for HeapWorkspace<N, HALF_N> {
+    type Target = Workspace<N, HALF_N>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const N: usize, const HALF_N: usize> core::ops::DerefMut for HeapWorkspace<N, HALF_N> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Formant, MusicalSettings, ProcessingMode, ProcessingState, VocalEffectsConfig,
+        dsp::Fft512,
+        vocal_effects::{process_frame, process_frame_in_place},
+    };
+
+    #[test]
+    fn test_reused_workspace_matches_fresh_workspace() {
+        let config = VocalEffectsConfig::default();
+        let mut reused = Workspace::<512, 256>::new();
+        let mut reused_state = ProcessingState::new();
+        let mut fresh_state = ProcessingState::new();
+
+        let modes = [
+            ProcessingMode::Autotune,
+            ProcessingMode::Vocode,
+            ProcessingMode::TalkBox,
+            ProcessingMode::Dry,
+            ProcessingMode::Formant,
+        ];
+        for (frame, mode) in modes.iter().cycle().take(12).enumerate() {
+            let settings = MusicalSettings {
+                mode: *mode,
+                formant: Formant::Lower,
+                pitch_shift_semitones: 3.0,
+                ..Default::default()
+            };
+            let input: [f32; 512] =
+                core::array::from_fn(|i| libm::sinf((frame * 128 + i) as f32 * 0.031) * 0.5);
+            let carrier: [f32; 512] = core::array::from_fn(|i| ((i % 37) as f32 / 18.5) - 1.0);
+
+            let expected = process_frame(
+                &mut Fft512,
+                &mut Workspace::new(),
+                &mut input.clone(),
+                Some(&mut carrier.clone()),
+                &mut fresh_state,
+                &config,
+                &settings,
+            );
+            let output = process_frame(
+                &mut Fft512,
+                &mut reused,
+                &mut input.clone(),
+                Some(&mut carrier.clone()),
+                &mut reused_state,
+                &config,
+                &settings,
+            );
+            assert_eq!(output, expected, "frame {frame} ({mode:?})");
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_heap_workspace_is_zeroed() {
+        let workspace = HeapWorkspace::<8, 4>::new();
+        assert!(workspace.spectrum.iter().all(|c| c.re == 0.0 && c.im == 0.0));
+        assert!(workspace.synthesis_magnitudes.iter().all(|&m| m == 0.0));
+    }
+
+    #[test]
+    fn test_in_place_matches_returned_frame() {
+        let config = VocalEffectsConfig::default();
+        let mut workspace = Workspace::<512, 256>::new();
+        let mut returned_state = ProcessingState::new();
+        let mut in_place_state = ProcessingState::new();
+        let carrier: [f32; 512] = core::array::from_fn(|i| ((i % 37) as f32 / 18.5) - 1.0);
+
+        let modes = [
+            ProcessingMode::Autotune,
+            ProcessingMode::Vocode,
+            ProcessingMode::TalkBox,
+            ProcessingMode::Dry,
+            ProcessingMode::Formant,
+        ];
+        for (frame, mode) in modes.iter().enumerate() {
+            let settings = MusicalSettings { mode: *mode, ..Default::default() };
+            let input: [f32; 512] =
+                core::array::from_fn(|i| libm::sinf((frame * 128 + i) as f32 * 0.031) * 0.5);
+
+            let expected = process_frame(
+                &mut Fft512,
+                &mut workspace,
+                &mut input.clone(),
+                Some(&mut carrier.clone()),
+                &mut returned_state,
+                &config,
+                &settings,
+            )
+            .unwrap();
+            let mut buffer = input;
+            process_frame_in_place(
+                &mut Fft512,
+                &mut workspace,
+                &mut buffer,
+                Some(&mut carrier.clone()),
+                &mut in_place_state,
+                &config,
+                &settings,
+            )
+            .unwrap();
+            assert_eq!(buffer, expected, "{mode:?}");
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! Golden-audio regression tests.
+//!
+//! Deterministic synthetic inputs are processed through every mode and frame
+//! size and compared with reference renders stored in `tests/golden/`. After an
+//! intentional change to the processing, regenerate the references with
+//!
+//! ```text
+//! UPDATE_GOLDEN=1 cargo test --features std --test golden
+//! ```
+//!
+//! and listen to the changed files before committing them.
+
+#![cfg(feature = "std")]
+
+use std::{f32::consts::PI, path::PathBuf};
+
+use synthphone_e_vocal_dsp::{
+    Engine, Formant, MusicalSettings, ProcessingMode, VocalEffectsConfig,
+    dsp::{DynFft, Fft512, Fft1024, Fft2048, Fft4096},
+};
+
+const SAMPLE_RATE: u32 = 48000;
+const LENGTH: usize = 8192;
+/// Allows for 16-bit storage and FFT backend rounding differences
+const TOLERANCE: f32 = 1e-3;
+
+/// Exponential sine sweep from 150 Hz to 600 Hz
+fn sweep(n: usize) -> f32 {
+    let duration = LENGTH as f32 / SAMPLE_RATE as f32;
+    let t = n as f32 / SAMPLE_RATE as f32;
+    let k = libm::logf(600.0 / 150.0);
+    let phase = 2.0 * PI * 150.0 * duration / k * (libm::expf(t / duration * k) - 1.0);
+    0.3 * libm::sinf(phase)
+}
+
+/// Four detuned notes, each 30 cents away from A3, C4, E4 and G4
+///
+/// The phase runs on continuously across note changes: a phase jump is a
+/// broadband click whose weak bins have arbitrary phase, which would make the
+/// output depend on rounding in the FFT backend.
+fn melody(n: usize) -> f32 {
+    const FREQUENCIES: [f64; 4] =
+        [220.0 * 1.0175, 261.63 * 0.9828, 329.63 * 1.0175, 392.0 * 0.9828];
+    const NOTE_LENGTH: usize = LENGTH / FREQUENCIES.len();
+    let note = n / NOTE_LENGTH;
+    let cycles: f64 = FREQUENCIES[..note].iter().map(|f| f * NOTE_LENGTH as f64).sum::<f64>()
+        + FREQUENCIES[note] * (n - note * NOTE_LENGTH) as f64;
+    let phase = (cycles / SAMPLE_RATE as f64).fract();
+    0.5 * libm::sinf((2.0 * core::f64::consts::PI * phase) as f32)
+}
+
+/// Vowel-like harmonic stack at 196 Hz with an "ah" formant shape
+///
+/// A little breath noise fills the valleys between harmonics; without it the
+/// cepstral envelope is shaped by FFT rounding noise.
+fn vowel(n: usize) -> f32 {
+    const FORMANTS: [(f32, f32); 3] = [(700.0, 130.0), (1220.0, 70.0), (2600.0, 160.0)];
+    let t = n as f32 / SAMPLE_RATE as f32;
+    let mut sample = 0.0;
+    for harmonic in 1..=24 {
+        let frequency = 196.0 * harmonic as f32;
+        let gain: f32 = FORMANTS
+            .iter()
+            .map(|&(centre, width)| 1.0 / (1.0 + ((frequency - centre) / width).powi(2)))
+            .sum();
+        sample += gain * libm::sinf(2.0 * PI * frequency * t) / harmonic as f32;
+    }
+    // Deterministic white noise around -60 dBFS
+    let hash = (n as u32).wrapping_mul(0x9E37_79B9).rotate_left(13).wrapping_mul(0x85EB_CA6B);
+    let noise = hash as f32 / u32::MAX as f32 - 0.5;
+    0.3 * sample + 2e-3 * noise
+}
+
+/// Band-limited-ish sawtooth carrier at 110 Hz
+fn carrier(n: usize) -> f32 {
+    let t = n as f32 / SAMPLE_RATE as f32;
+    (1..=40)
+        .map(|h| libm::sinf(2.0 * PI * 110.0 * h as f32 * t) / h as f32)
+        .sum::<f32>()
+        * 0.2
+}
+
+fn render<const N: usize, const HALF_N: usize, F: DynFft<N, HALF_N> + Default>(
+    settings: MusicalSettings,
+    input: fn(usize) -> f32,
+) -> Vec<f32> {
+    let mut engine = Engine::<N, HALF_N, F>::new(VocalEffectsConfig::default(), settings);
+    let hop = engine.hop_size();
+    let input: Vec<f32> = (0..LENGTH).map(input).collect();
+    let carrier: Vec<f32> = (0..LENGTH).map(carrier).collect();
+    let mut output = vec![0.0f32; LENGTH];
+
+    for ((input, carrier), output) in input
+        .chunks_exact(hop)
+        .zip(carrier.chunks_exact(hop))
+        .zip(output.chunks_exact_mut(hop))
+    {
+        engine.process_hop(input, Some(carrier), output).unwrap();
+    }
+    output
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{name}.wav"))
+}
+
+fn check_golden(name: &str, output: &[f32]) {
+    let path = golden_path(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for &sample in output {
+            let sample = (sample * 32768.0).round().clamp(-32768.0, 32767.0) as i16;
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+        return;
+    }
+
+    let mut reader = hound::WavReader::open(&path).unwrap_or_else(|error| {
+        panic!("{}: {error}; run with UPDATE_GOLDEN=1 to create it", path.display())
+    });
+    let reference: Vec<f32> =
+        reader.samples::<i16>().map(|s| s.unwrap() as f32 / 32768.0).collect();
+    assert_eq!(reference.len(), output.len(), "{name}: length changed");
+
+    let (index, error) = output
+        .iter()
+        .zip(&reference)
+        .map(|(a, b)| (a - b).abs())
+        .enumerate()
+        .fold((0, 0.0f32), |worst, (i, e)| if e > worst.1 { (i, e) } else { worst });
+    assert!(
+        error <= TOLERANCE,
+        "{name}: sample {index} differs from the reference by {error} ({} vs {})",
+        output[index],
+        reference[index]
+    );
+}
+
+fn check_all_modes<const N: usize, const HALF_N: usize, F: DynFft<N, HALF_N> + Default>() {
+    let cases = [
+        ("autotune_melody", MusicalSettings::default(), melody as fn(usize) -> f32),
+        ("autotune_vowel", MusicalSettings::default(), vowel),
+        (
+            "dry_sweep",
+            MusicalSettings {
+                mode: ProcessingMode::Dry,
+                pitch_shift_semitones: 3.0,
+                ..Default::default()
+            },
+            sweep,
+        ),
+        (
+            "formant_vowel",
+            MusicalSettings {
+                mode: ProcessingMode::Formant,
+                formant: Formant::Higher,
+                ..Default::default()
+            },
+            vowel,
+        ),
+        (
+            "vocode_vowel",
+            MusicalSettings { mode: ProcessingMode::Vocode, ..Default::default() },
+            vowel,
+        ),
+    ];
+
+    for (name, settings, input) in cases {
+        let output = render::<N, HALF_N, F>(settings, input);
+        assert!(output.iter().all(|s| s.is_finite()), "{name}_{N}: non-finite output");
+        check_golden(&format!("{name}_{N}"), &output);
+    }
+}
+
+#[test]
+fn golden_512() {
+    check_all_modes::<512, 256, Fft512>();
+}
+
+#[test]
+fn golden_1024() {
+    check_all_modes::<1024, 512, Fft1024>();
+}
+
+#[test]
+fn golden_2048() {
+    check_all_modes::<2048, 1024, Fft2048>();
+}
+
+#[test]
+fn golden_4096() {
+    check_all_modes::<4096, 2048, Fft4096>();
+}
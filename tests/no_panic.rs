@@ -0,0 +1,91 @@
+//! Panic canary for the `no-panic` feature.
+//!
+//! The test installs a panic hook that aborts the process, so any panic on the
+//! paths below fails the run the way it would in firmware built with
+//! `panic = "abort"`, even if something up the stack would have caught it.
+//! Run it with
+//!
+//! ```text
+//! cargo test --features "std no-panic" --test no_panic
+//! ```
+
+#![cfg(all(feature = "std", feature = "no-panic"))]
+
+use synthphone_e_vocal_dsp::{
+    Engine1024, MusicalSettings, ProcessingMode, VocalEffectsConfig, VocalEffectsError,
+    dsp::sample_rate_reduce, process_vocal_effects_1024, ring_buffer::RingBuffer,
+};
+
+const MODES: [ProcessingMode; 5] = [
+    ProcessingMode::Autotune,
+    ProcessingMode::Dry,
+    ProcessingMode::Formant,
+    ProcessingMode::Vocode,
+    ProcessingMode::TalkBox,
+];
+
+fn install_canary() {
+    std::panic::set_hook(Box::new(|info| {
+        eprintln!("panic with no-panic enabled: {info}");
+        std::process::abort();
+    }));
+}
+
+#[test]
+fn test_hostile_inputs_do_not_panic() {
+    install_canary();
+
+    // Carrier modes without a carrier are errors
+    let config = VocalEffectsConfig::default();
+    for mode in [ProcessingMode::Vocode, ProcessingMode::TalkBox] {
+        let settings = MusicalSettings { mode, ..Default::default() };
+        let mut frame = [0.25f32; 1024];
+        let output = process_vocal_effects_1024(
+            &mut frame,
+            None,
+            &mut [0.0; 1024],
+            &mut [0.0; 1024],
+            1.0,
+            &config,
+            &settings,
+        );
+        assert_eq!(output, Err(VocalEffectsError::MissingCarrier), "{mode:?}");
+        let mut engine = Engine1024::new(config, settings);
+        let result = engine.process_hop(&[0.25; 256], None, &mut [0.0; 256]);
+        assert_eq!(result, Err(VocalEffectsError::MissingCarrier), "{mode:?}");
+    }
+
+    // Non-finite input in every mode
+    for mode in MODES {
+        let settings = MusicalSettings { mode, ..Default::default() };
+        let mut engine = Engine1024::new(config, settings);
+        let mut output = [0.0f32; 256];
+        for input in [[f32::NAN; 256], [f32::INFINITY; 256], [f32::MAX; 256], [1e-40; 256]] {
+            engine.process_hop(&input, Some(&input), &mut output).unwrap();
+            assert!(output.iter().all(|sample| sample.is_finite()), "{mode:?}");
+        }
+    }
+
+    // Hop ratios the builder would reject are errors, not panics
+    for hop_ratio in [0.0, f32::NAN, -0.5, 2.0] {
+        let config = VocalEffectsConfig { hop_ratio, ..Default::default() };
+        let mut engine = Engine1024::new(config, MusicalSettings::default());
+        assert!(engine.process_hop(&[0.0; 256], None, &mut [0.0; 256]).is_err());
+    }
+
+    // A block longer than the ring buffer keeps its newest samples
+    let buffer: RingBuffer<8> = RingBuffer::new();
+    let block: [f32; 12] = core::array::from_fn(|i| i as f32);
+    buffer.push_slice(&block);
+    assert_eq!(buffer.write_index(), 12);
+    let mut popped = [0.0f32; 8];
+    assert_eq!(buffer.pop_slice(&mut popped), 8);
+    popped.sort_by(f32::total_cmp);
+    assert_eq!(popped, [4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 11.0]);
+
+    // Degenerate decimation factors hold or pass through
+    let (mut counter, mut held) = (0, 0.0);
+    for factor in [0, -1, i32::MIN] {
+        assert!(sample_rate_reduce(0.5, factor, &mut counter, &mut held).is_finite());
+    }
+}
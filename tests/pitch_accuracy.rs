@@ -0,0 +1,231 @@
+//! Pitch-accuracy harness.
+//!
+//! Synthetic voices with known F0 trajectories (steady notes, glides and
+//! vibrato) are run through the engine, and the pitch detected and corrected for
+//! every frame is compared with the ground truth in cents. Run with
+//! `--nocapture` to print per-case error statistics when tuning detectors or
+//! smoothing.
+
+#![cfg(feature = "std")]
+
+use std::f64::consts::PI;
+
+use synthphone_e_vocal_dsp::{
+    Engine2048, MusicalSettings, PitchDetector, VocalEffectsConfig, dsp::correct_frequency,
+};
+
+const SAMPLE_RATE: f64 = 48000.0;
+const FRAME_SIZE: usize = 2048;
+
+/// Known F0 trajectory in Hz as a function of time in seconds
+type Trajectory = fn(f64) -> f64;
+
+fn steady(_t: f64) -> f64 {
+    233.0
+}
+
+/// One octave upwards over two seconds, 110 Hz to 220 Hz
+fn glide(t: f64) -> f64 {
+    110.0 * libm::exp2(t / 2.0)
+}
+
+/// 5.5 Hz vibrato, ±40 cents around 300 Hz
+fn vibrato(t: f64) -> f64 {
+    300.0 * libm::exp2(40.0 / 1200.0 * libm::sin(2.0 * PI * 5.5 * t))
+}
+
+/// Cents from `reference` to `frequency`
+fn cents(frequency: f64, reference: f64) -> f64 {
+    1200.0 * libm::log2(frequency / reference)
+}
+
+/// Render a voice following `trajectory`, with harmonics falling at 6 dB per octave
+fn synthesize(trajectory: Trajectory, length: usize) -> Vec<f32> {
+    let mut phase = 0.0f64;
+    (0..length)
+        .map(|n| {
+            let f0 = trajectory(n as f64 / SAMPLE_RATE);
+            let sample: f64 =
+                (1..=10).map(|h| libm::sin(2.0 * PI * phase * h as f64) / h as f64).sum();
+            phase = (phase + f0 / SAMPLE_RATE).fract();
+            (0.3 * sample) as f32
+        })
+        .collect()
+}
+
+/// Per-frame measurement against the ground truth
+struct FrameError {
+    true_frequency: f64,
+    detection_cents: f64,
+    /// Cents between the corrected pitch and the target note
+    correction_cents: f64,
+    /// Smallest detection error that leads to the chosen target note, 0.0 when the
+    /// truth itself does
+    target_offset_cents: f64,
+}
+
+fn measure(trajectory: Trajectory, seconds: f64, detector: PitchDetector) -> Vec<FrameError> {
+    let config = VocalEffectsConfig::builder().pitch_detector(detector).build().unwrap();
+    let settings = MusicalSettings::default();
+    let mut engine = Engine2048::new(config, settings);
+    let hop = engine.hop_size();
+    let input = synthesize(trajectory, (seconds * SAMPLE_RATE) as usize);
+    let mut output = vec![0.0f32; hop];
+
+    let mut errors = Vec::new();
+    for (block, input) in input.chunks_exact(hop).enumerate() {
+        engine.process_hop(input, None, &mut output).unwrap();
+        // Let the analysis frame fill and the phase history settle
+        if (block + 1) * hop < FRAME_SIZE + hop {
+            continue;
+        }
+
+        // The phase vocoder measures the mean frequency between the centres of
+        // the previous and current frames, autocorrelation the current frame only
+        let frame_end = (block + 1) * hop;
+        let measurement_delay = match detector {
+            PitchDetector::Autocorrelation => 0.0,
+            _ => hop as f64 / 2.0,
+        };
+        let time = ((frame_end - FRAME_SIZE / 2) as f64 - measurement_delay) / SAMPLE_RATE;
+        let true_frequency = trajectory(time);
+        let analysis = engine.state().analysis;
+        let target_for = |cents: f64| {
+            let frequency = true_frequency * libm::exp2(cents / 1200.0);
+            correct_frequency(frequency as f32, 1.0, engine.config(), &settings).target_frequency
+        };
+        let target_offset_cents = (0..=1000)
+            .map(|step| step as f64 * 0.1)
+            .find(|&cents| {
+                target_for(cents) == analysis.target_frequency
+                    || target_for(-cents) == analysis.target_frequency
+            })
+            .unwrap_or(f64::INFINITY);
+
+        errors.push(FrameError {
+            true_frequency,
+            detection_cents: cents(analysis.detected_frequency as f64, true_frequency),
+            correction_cents: cents(
+                (analysis.detected_frequency * analysis.pitch_shift_ratio) as f64,
+                analysis.target_frequency as f64,
+            ),
+            target_offset_cents,
+        });
+    }
+    errors
+}
+
+/// Frames detected more than this far from the truth count as gross errors
+const GROSS_ERROR_CENTS: f64 = 50.0;
+
+/// Error bounds for one case
+struct Bounds {
+    /// Largest detection error of the frames without a gross error
+    detection_cents: f64,
+    /// Largest distance between the corrected pitch and the target note
+    correction_cents: f64,
+    /// Largest fraction of frames with a gross error
+    gross_error_rate: f64,
+}
+
+/// Asserts the per-frame bounds and prints the statistics
+///
+/// Gross errors must be whole octaves, so the detector still finds the right
+/// pitch class. On the remaining frames the target note must be one the truth
+/// would snap to when moved by no more than the detection bound, which allows for
+/// frames at the boundary between two notes of the key.
+fn check(name: &str, errors: &[FrameError], bounds: Bounds) {
+    let (gross, fine): (Vec<&FrameError>, Vec<&FrameError>) =
+        errors.iter().partition(|e| e.detection_cents.abs() > GROSS_ERROR_CENTS);
+    let worst = fine.iter().map(|e| e.detection_cents.abs()).fold(0.0, f64::max);
+    let mean = fine.iter().map(|e| e.detection_cents.abs()).sum::<f64>() / fine.len() as f64;
+    let gross_error_rate = gross.len() as f64 / errors.len() as f64;
+    println!(
+        "{name}: {} frames, detection error mean {mean:.2} max {worst:.2} cents, {:.1}% gross errors",
+        errors.len(),
+        gross_error_rate * 100.0
+    );
+
+    assert!(
+        gross_error_rate <= bounds.gross_error_rate,
+        "{name}: {:.1}% of frames have gross errors",
+        gross_error_rate * 100.0
+    );
+    for (frame, error) in errors.iter().enumerate() {
+        let octaves = error.detection_cents / 1200.0;
+        let detection_cents = (octaves - libm::round(octaves)) * 1200.0;
+        assert!(
+            detection_cents.abs() <= bounds.detection_cents,
+            "{name} frame {frame}: detected {:.2} cents off {:.2} Hz",
+            error.detection_cents,
+            error.true_frequency
+        );
+        assert!(
+            error.correction_cents.abs() <= bounds.correction_cents,
+            "{name} frame {frame}: corrected pitch {:.2} cents off target",
+            error.correction_cents
+        );
+
+        if error.detection_cents.abs() > GROSS_ERROR_CENTS {
+            continue;
+        }
+        assert!(
+            error.target_offset_cents <= bounds.detection_cents,
+            "{name} frame {frame}: wrong target for {:.2} Hz",
+            error.true_frequency
+        );
+    }
+}
+
+#[test]
+fn steady_note() {
+    for detector in [
+        PitchDetector::PeakBin,
+        PitchDetector::HarmonicProduct,
+        PitchDetector::HarmonicSum,
+        PitchDetector::Autocorrelation,
+    ] {
+        let errors = measure(steady, 1.0, detector);
+        let bounds = Bounds { detection_cents: 2.0, correction_cents: 2.0, gross_error_rate: 0.0 };
+        check(&format!("steady {detector:?}"), &errors, bounds);
+    }
+}
+
+#[test]
+fn octave_glide() {
+    let errors = measure(glide, 2.0, PitchDetector::PeakBin);
+    let bounds = Bounds { detection_cents: 5.0, correction_cents: 2.0, gross_error_rate: 0.0 };
+    check("glide PeakBin", &errors, bounds);
+
+    let errors = measure(glide, 2.0, PitchDetector::Autocorrelation);
+    let bounds = Bounds { detection_cents: 5.0, correction_cents: 2.0, gross_error_rate: 0.0 };
+    check("glide Autocorrelation", &errors, bounds);
+
+    // The harmonic product spectrum jumps an octave up on part of the glide
+    let errors = measure(glide, 2.0, PitchDetector::HarmonicProduct);
+    let bounds = Bounds { detection_cents: 5.0, correction_cents: 2.0, gross_error_rate: 0.25 };
+    check("glide HarmonicProduct", &errors, bounds);
+
+    let errors = measure(glide, 2.0, PitchDetector::HarmonicSum);
+    let bounds = Bounds { detection_cents: 5.0, correction_cents: 2.0, gross_error_rate: 0.0 };
+    check("glide HarmonicSum", &errors, bounds);
+}
+
+#[test]
+fn vibrato_note() {
+    let errors = measure(vibrato, 2.0, PitchDetector::PeakBin);
+    let bounds = Bounds { detection_cents: 5.0, correction_cents: 2.0, gross_error_rate: 0.0 };
+    check("vibrato PeakBin", &errors, bounds);
+
+    let errors = measure(vibrato, 2.0, PitchDetector::HarmonicProduct);
+    let bounds = Bounds { detection_cents: 5.0, correction_cents: 2.0, gross_error_rate: 0.35 };
+    check("vibrato HarmonicProduct", &errors, bounds);
+
+    let errors = measure(vibrato, 2.0, PitchDetector::HarmonicSum);
+    let bounds = Bounds { detection_cents: 5.0, correction_cents: 2.0, gross_error_rate: 0.0 };
+    check("vibrato HarmonicSum", &errors, bounds);
+
+    let errors = measure(vibrato, 2.0, PitchDetector::Autocorrelation);
+    let bounds = Bounds { detection_cents: 5.0, correction_cents: 2.0, gross_error_rate: 0.0 };
+    check("vibrato Autocorrelation", &errors, bounds);
+}